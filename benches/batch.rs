@@ -2,7 +2,6 @@ use bytes::Bytes;
 use criterion::{criterion_group, Benchmark, Criterion, Throughput};
 use futures01::{future, Sink, Stream};
 use std::convert::Infallible;
-use std::time::Duration;
 use vector::buffers::Acker;
 use vector::sinks::util::{Batch, BatchSettings, BatchSink, Buffer, Partition, PartitionBatchSink};
 use vector::test_util::random_lines;
@@ -29,10 +28,7 @@ fn batching(
                 let batch_sink = BatchSink::new(
                     tower::service_fn(|_| future::ok::<_, Infallible>(())),
                     Buffer::new(gzip),
-                    BatchSettings {
-                        size: max_size,
-                        timeout: Duration::from_secs(1),
-                    },
+                    BatchSettings::default().bytes(max_size as u64).timeout(1),
                     acker,
                 )
                 .sink_map_err(|e| panic!(e));
@@ -73,10 +69,7 @@ fn partitioned_batching(
                 let batch_sink = PartitionBatchSink::new(
                     tower::service_fn(|_| future::ok::<_, Infallible>(())),
                     PartitionedBuffer::new(gzip),
-                    BatchSettings {
-                        size: max_size,
-                        timeout: Duration::from_secs(1),
-                    },
+                    BatchSettings::default().bytes(max_size as u64).timeout(1),
                     acker,
                 )
                 .sink_map_err(|e| panic!(e));
@@ -158,8 +151,8 @@ impl Batch for PartitionedBuffer {
     type Input = InnerBuffer;
     type Output = InnerBuffer;
 
-    fn len(&self) -> usize {
-        self.inner.len()
+    fn num_bytes(&self) -> usize {
+        self.inner.num_bytes()
     }
 
     fn push(&mut self, item: Self::Input) {
@@ -78,6 +78,8 @@ fn benchmark_buffers(c: &mut Criterion) {
                     config.sinks["out"].buffer = BufferConfig::Disk {
                         max_size: 1_000_000,
                         when_full: Default::default(),
+                        sync_writes: false,
+                        encryption: None,
                     }
                     .into();
                     config.global.data_dir = Some(data_dir.clone());
@@ -120,6 +122,8 @@ fn benchmark_buffers(c: &mut Criterion) {
                     config.sinks["out"].buffer = BufferConfig::Disk {
                         max_size: 10_000,
                         when_full: Default::default(),
+                        sync_writes: false,
+                        encryption: None,
                     };
                     config.global.data_dir = Some(data_dir2.clone());
 
@@ -51,6 +51,7 @@ fn benchmark_files_without_partitions(c: &mut Criterion) {
                         path: output.into(),
                         idle_timeout_secs: None,
                         encoding: sinks::file::Encoding::Text.into(),
+                        avro: Default::default(),
                     },
                 );
 
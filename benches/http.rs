@@ -31,15 +31,19 @@ fn benchmark_http_no_compression(c: &mut Criterion) {
                     &["in"],
                     sinks::http::HttpSinkConfig {
                         uri: out_addr.to_string().parse::<http::Uri>().unwrap().into(),
-                        compression: Some(sinks::util::Compression::None),
+                        compression: Some(sinks::http::Compression::None),
                         method: Default::default(),
                         healthcheck_uri: Default::default(),
                         auth: Default::default(),
                         headers: Default::default(),
                         batch: Default::default(),
                         encoding: sinks::http::Encoding::Text.into(),
+                        avro: Default::default(),
+                        csv: Default::default(),
+                        protobuf: Default::default(),
                         request: Default::default(),
                         tls: Default::default(),
+                        proxy: Default::default(),
                     },
                 );
 
@@ -89,15 +93,19 @@ fn benchmark_http_gzip(c: &mut Criterion) {
                     &["in"],
                     sinks::http::HttpSinkConfig {
                         uri: out_addr.to_string().parse::<http::Uri>().unwrap().into(),
-                        compression: Default::default(),
+                        compression: Some(sinks::http::Compression::Gzip),
                         method: Default::default(),
                         healthcheck_uri: Default::default(),
                         auth: Default::default(),
                         headers: Default::default(),
                         batch: Default::default(),
                         encoding: sinks::http::Encoding::Text.into(),
+                        avro: Default::default(),
+                        csv: Default::default(),
+                        protobuf: Default::default(),
                         request: Default::default(),
                         tls: Default::default(),
+                        proxy: Default::default(),
                     },
                 );
 
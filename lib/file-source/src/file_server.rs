@@ -27,6 +27,8 @@ pub struct FileServer {
     pub exclude: Vec<PathBuf>,
     pub max_read_bytes: usize,
     pub start_at_beginning: bool,
+    pub read_from: ReadFrom,
+    pub ignore_checkpoints: bool,
     pub ignore_before: Option<time::SystemTime>,
     pub max_line_bytes: usize,
     pub data_dir: PathBuf,
@@ -35,6 +37,15 @@ pub struct FileServer {
     pub oldest_first: bool,
 }
 
+/// Where reading files found at startup starts when there's no checkpoint
+/// for them. Files found later are always read from the beginning, as they
+/// are new.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReadFrom {
+    Beginning,
+    End,
+}
+
 /// `FileServer` as Source
 ///
 /// The 'run' of `FileServer` performs the cooperative scheduling of reads over
@@ -62,8 +73,12 @@ impl FileServer {
         let mut backoff_cap: usize = 1;
         let mut lines = Vec::new();
 
+        // When replaying files, checkpoints are neither read nor written, so
+        // those of earlier runs are kept for when replaying stops.
         let mut checkpointer = Checkpointer::new(&self.data_dir);
-        checkpointer.read_checkpoints(self.ignore_before);
+        if !self.ignore_checkpoints {
+            checkpointer.read_checkpoints(self.ignore_before);
+        }
 
         let exclude_patterns = self
             .exclude
@@ -103,13 +118,12 @@ impl FileServer {
         });
 
         for (path, file_id) in existing_files {
-            self.watch_new_file(
-                path,
-                file_id,
-                &mut fp_map,
-                &checkpointer,
-                self.start_at_beginning,
-            );
+            let start = if self.start_at_beginning {
+                Start::Beginning
+            } else {
+                Start::Checkpoint(self.read_from)
+            };
+            self.watch_new_file(path, file_id, &mut fp_map, &checkpointer, start);
         }
 
         // Alright friends, how does this work?
@@ -130,10 +144,12 @@ impl FileServer {
                 next_glob_time = now_time.checked_add(self.glob_minimum_cooldown).unwrap();
 
                 // Write any stored checkpoints (uses glob to find old checkpoints).
-                checkpointer
-                    .write_checkpoints()
-                    .map_err(|e| warn!("Problem writing checkpoints: {:?}", e))
-                    .ok();
+                if !self.ignore_checkpoints {
+                    checkpointer
+                        .write_checkpoints()
+                        .map_err(|e| warn!("Problem writing checkpoints: {:?}", e))
+                        .ok();
+                }
 
                 // Search (glob) for files to detect major file changes.
                 for (_file_id, watcher) in &mut fp_map {
@@ -203,7 +219,7 @@ impl FileServer {
                                     file_id,
                                     &mut fp_map,
                                     &checkpointer,
-                                    false,
+                                    Start::Checkpoint(ReadFrom::Beginning),
                                 );
                             }
                         }
@@ -296,12 +312,17 @@ impl FileServer {
         file_id: FileFingerprint,
         fp_map: &mut IndexMap<FileFingerprint, FileWatcher>,
         checkpointer: &Checkpointer,
-        read_from_beginning: bool,
+        start: Start,
     ) {
-        let file_position = if read_from_beginning {
-            0
-        } else {
-            checkpointer.get_checkpoint(file_id).unwrap_or(0)
+        let file_position = match start {
+            Start::Beginning => 0,
+            Start::Checkpoint(read_from) => match checkpointer.get_checkpoint(file_id) {
+                Some(position) => position,
+                None => match read_from {
+                    ReadFrom::Beginning => 0,
+                    ReadFrom::End => fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                },
+            },
         };
         match FileWatcher::new(path.clone(), file_position, self.ignore_before) {
             Ok(mut watcher) => {
@@ -318,6 +339,14 @@ impl FileServer {
     }
 }
 
+/// Where `watch_new_file` starts reading a file.
+#[derive(Clone, Copy, Debug)]
+enum Start {
+    Beginning,
+    /// At the file's checkpoint, if it has one.
+    Checkpoint(ReadFrom),
+}
+
 pub struct Checkpointer {
     directory: PathBuf,
     glob_string: String,
@@ -7,7 +7,7 @@ mod file_server;
 mod file_watcher;
 mod metadata_ext;
 
-pub use self::file_server::{FileServer, Fingerprinter};
+pub use self::file_server::{FileServer, Fingerprinter, ReadFrom};
 
 type FileFingerprint = u64;
 type FilePosition = u64;
@@ -1,3 +1,14 @@
+//! A `tracing` layer that collapses repeated events from the same callsite.
+//!
+//! Events are keyed by their callsite, that is the message template rather
+//! than the formatted arguments, so `warn!("Request failed: {}", error)` is
+//! limited no matter which error it logs. The first event of a window is let
+//! through, the rest are counted, and the first event after the window ends
+//! is preceded by a single line with that count.
+//!
+//! Events opt in with a `rate_limit_secs` field setting their window.
+//! `Limit::with_default_limit` also limits every warning and error.
+
 use std::fmt;
 use std::{
     collections::HashMap,
@@ -11,60 +22,77 @@ use tracing_core::{
     callsite::Identifier,
     field::{display, Field, Value, Visit},
     subscriber::Interest,
-    Event, Metadata, Subscriber,
+    Event, Level, Metadata, Subscriber,
 };
 use tracing_subscriber::layer::{Context, Layer};
 
 const RATE_LIMIT_FIELD: &str = "rate_limit_secs";
 const MESSAGE_FIELD: &str = "message";
-const DEFAULT_LIMIT: u64 = 5;
 
 #[derive(Debug, Default)]
 pub struct Limit {
+    /// The window of warnings and errors without a `rate_limit_secs` field.
+    default_limit: Option<u64>,
     events: RwLock<HashMap<Identifier, State>>,
     callsite_store: RwLock<HashMap<Identifier, &'static Metadata<'static>>>,
 }
 
 #[derive(Debug)]
 struct State {
-    start: Option<Instant>,
+    start: Instant,
+    /// Events suppressed since `start`.
     count: AtomicUsize,
     limit: u64,
     message: String,
 }
 
 impl Limit {
+    /// Limits every warning and error to one per `limit_secs` seconds and
+    /// callsite, unless it sets its own `rate_limit_secs`.
+    pub fn with_default_limit(limit_secs: u64) -> Self {
+        Self {
+            default_limit: Some(limit_secs),
+            ..Self::default()
+        }
+    }
+
+    fn is_limited(&self, metadata: &Metadata<'_>) -> bool {
+        if !metadata.is_event() {
+            return false;
+        }
+        let has_limit_field = metadata
+            .fields()
+            .iter()
+            .any(|f| f.name() == RATE_LIMIT_FIELD);
+        let level = *metadata.level();
+
+        has_limit_field
+            || (self.default_limit.is_some() && (level == Level::WARN || level == Level::ERROR))
+    }
+
+    /// Emits an event carrying only `message` from the callsite `id`.
     fn create_event<S: Subscriber>(&self, id: &Identifier, ctx: &Context<S>, message: String) {
-        let store = self.callsite_store.read().unwrap();
-        let metadata = store.get(id).unwrap();
+        let store = self.callsite_store.read().expect("lock poisoned!");
+        let metadata = match store.get(id) {
+            Some(metadata) => *metadata,
+            None => return,
+        };
+        drop(store);
 
         let fields = metadata.fields();
-
         let message = display(message);
 
-        if let Some(message_field) = fields.field("message") {
-            let values = [
-                (&message_field, Some(&message as &dyn Value)),
-                (
-                    &fields.field(RATE_LIMIT_FIELD).unwrap(),
-                    Some(&5 as &dyn Value),
-                ),
-            ];
-
-            let valueset = fields.value_set(&values);
-            let event = Event::new(metadata, &valueset);
-            ctx.event(&event);
-            drop(store);
-        } else {
-            let values = [(
-                &fields.field(RATE_LIMIT_FIELD).unwrap(),
-                Some(&5 as &dyn Value),
-            )];
-
-            let valueset = fields.value_set(&values);
-            let event = Event::new(metadata, &valueset);
-            ctx.event(&event);
-            drop(store);
+        match fields.field(MESSAGE_FIELD) {
+            Some(message_field) => {
+                let values = [(&message_field, Some(&message as &dyn Value))];
+                let valueset = fields.value_set(&values);
+                ctx.event(&Event::new(metadata, &valueset));
+            }
+            None => {
+                let values: [(&Field, Option<&dyn Value>); 0] = [];
+                let valueset = fields.value_set(&values);
+                ctx.event(&Event::new(metadata, &valueset));
+            }
         }
     }
 }
@@ -75,27 +103,9 @@ where
     Self: 'static,
 {
     fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
-        if metadata
-            .fields()
-            .iter()
-            .any(|f| f.name() == RATE_LIMIT_FIELD)
-        {
-            let id = metadata.callsite();
-            let mut events = self.events.write().expect("lock poisoned!");
-
-            let state = State {
-                start: None,
-                count: AtomicUsize::new(0),
-                limit: DEFAULT_LIMIT,
-                message: String::new(),
-            };
-
-            events.insert(id.clone(), state);
-            drop(events);
-
-            let mut callsite_store = self.callsite_store.write().unwrap();
-            callsite_store.insert(id, metadata);
-            drop(callsite_store);
+        if self.is_limited(metadata) {
+            let mut callsite_store = self.callsite_store.write().expect("lock poisoned!");
+            callsite_store.insert(metadata.callsite(), metadata);
 
             Interest::sometimes()
         } else {
@@ -104,45 +114,35 @@ where
     }
 
     fn enabled(&self, metadata: &Metadata, ctx: Context<S>) -> bool {
-        if ctx.enabled(metadata) {
-            let events = self.events.read().expect("lock poisoned!");
-            let id = metadata.callsite();
-
-            if events.contains_key(&id) {
-                // check if the event exists within the map, if it does
-                // that means we are currently rate limiting it.
-                if let Some(state) = events.get(&id) {
-                    let start = state.start.unwrap_or_else(Instant::now);
-
-                    if start.elapsed().as_secs() < state.limit {
-                        if state.count.load(Ordering::Acquire) == 1 {
-                            let message = format!("{:?} is being rate limited.", state.message);
-
-                            self.create_event(&id, &ctx, message);
-                        }
-
-                        let prev = state.count.fetch_add(1, Ordering::Relaxed);
-
-                        if prev == 0 {
-                            return true;
-                        }
-                    } else {
-                        drop(events);
-
-                        let mut events = self.events.write().expect("lock poisoned!");
-
-                        if let Some(state) = events.remove(&id) {
-                            let message = format!(
-                                "{:?} {:?} events were rate limited.",
-                                state.count, state.message
-                            );
-
-                            self.create_event(&id, &ctx, message);
-                        }
-                    }
+        if !ctx.enabled(metadata) || !self.is_limited(metadata) {
+            return true;
+        }
 
-                    return false;
-                }
+        let id = metadata.callsite();
+        let events = self.events.read().expect("lock poisoned!");
+        let state = match events.get(&id) {
+            Some(state) => state,
+            // The window starts with this event, in `on_event`.
+            None => return true,
+        };
+
+        if state.start.elapsed().as_secs() < state.limit {
+            state.count.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        drop(events);
+
+        let state = self.events.write().expect("lock poisoned!").remove(&id);
+        if let Some(state) = state {
+            let count = state.count.into_inner();
+            if count > 0 {
+                let message = format!(
+                    "{} ({} similar {} suppressed)",
+                    state.message,
+                    count,
+                    if count == 1 { "message" } else { "messages" }
+                );
+                self.create_event(&id, &ctx, message);
             }
         }
 
@@ -150,55 +150,45 @@ where
     }
 
     fn on_event(&self, event: &Event, _ctx: Context<S>) {
-        if is_limited(event.metadata()) {
-            let mut limit_visitor = LimitVisitor::default();
-            event.record(&mut limit_visitor);
-
-            if let Some(limit) = limit_visitor.limit {
-                let start = Instant::now();
-
-                let id = event.metadata().callsite();
+        if !self.is_limited(event.metadata()) {
+            return;
+        }
 
-                let mut events = self.events.write().expect("lock poisoned!");
+        let mut limit_visitor = LimitVisitor::default();
+        event.record(&mut limit_visitor);
 
-                let state = State {
-                    start: Some(start),
-                    count: AtomicUsize::new(1),
-                    limit: limit as u64,
-                    message: limit_visitor
-                        .message
-                        .unwrap_or_else(|| event.metadata().name().into()),
-                };
+        if let Some(limit) = limit_visitor.limit.or(self.default_limit) {
+            let state = State {
+                start: Instant::now(),
+                count: AtomicUsize::new(0),
+                limit,
+                message: limit_visitor
+                    .message
+                    .unwrap_or_else(|| event.metadata().name().into()),
+            };
 
-                events.insert(id, state);
-            }
+            let mut events = self.events.write().expect("lock poisoned!");
+            events.insert(event.metadata().callsite(), state);
         }
     }
 }
 
-fn is_limited(metadata: &Metadata<'_>) -> bool {
-    metadata
-        .fields()
-        .iter()
-        .any(|f| f.name() == RATE_LIMIT_FIELD)
-}
-
 #[derive(Default)]
 struct LimitVisitor {
-    pub limit: Option<usize>,
+    pub limit: Option<u64>,
     pub message: Option<String>,
 }
 
 impl Visit for LimitVisitor {
     fn record_u64(&mut self, field: &Field, value: u64) {
         if field.name() == RATE_LIMIT_FIELD {
-            self.limit = Some(value as usize);
+            self.limit = Some(value);
         }
     }
 
     fn record_i64(&mut self, field: &Field, value: i64) {
         if field.name() == RATE_LIMIT_FIELD {
-            self.limit = Some(value as usize);
+            self.limit = Some(value.max(0) as u64);
         }
     }
 
@@ -208,5 +198,123 @@ impl Visit for LimitVisitor {
         }
     }
 
-    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        // Formatted messages, like `warn!("failed: {}", error)`, are recorded
+        // as `fmt::Arguments`.
+        if field.name() == MESSAGE_FIELD {
+            self.message = Some(format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+    use tracing::{
+        span::{Attributes, Id, Record},
+        warn,
+    };
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Keeps the message of each event it sees.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut visitor = LimitVisitor::default();
+            event.record(&mut visitor);
+            self.0
+                .lock()
+                .unwrap()
+                .push(visitor.message.unwrap_or_default());
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    fn record(limit: Limit, emit: impl FnOnce()) -> Vec<String> {
+        let recorder = Recorder::default();
+        let messages = recorder.0.clone();
+        tracing::subscriber::with_default(recorder.with(limit), emit);
+
+        let messages = messages.lock().unwrap();
+        messages.clone()
+    }
+
+    #[test]
+    fn repeated_events_collapse_into_one_with_count() {
+        let failed = |attempt| warn!("Request failed on attempt {}.", attempt);
+
+        let messages = record(Limit::with_default_limit(1), || {
+            for attempt in 0..10 {
+                failed(attempt);
+            }
+            thread::sleep(Duration::from_millis(1100));
+            failed(10);
+        });
+
+        assert_eq!(
+            messages,
+            vec![
+                "Request failed on attempt 0.",
+                "Request failed on attempt 0. (9 similar messages suppressed)",
+                "Request failed on attempt 10.",
+            ]
+        );
+    }
+
+    #[test]
+    fn callsites_are_limited_separately() {
+        let messages = record(Limit::with_default_limit(60), || {
+            for _ in 0..3 {
+                warn!("first");
+                warn!("second");
+                tracing::info!("not limited");
+                tracing::info!(message = "opted in", rate_limit_secs = 60);
+            }
+        });
+
+        assert_eq!(
+            messages,
+            vec![
+                "first",
+                "second",
+                "not limited",
+                "opted in",
+                "not limited",
+                "not limited",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_limit_is_opt_in() {
+        let messages = record(Limit::default(), || {
+            for _ in 0..2 {
+                warn!("repeated");
+            }
+        });
+
+        assert_eq!(messages, vec!["repeated", "repeated"]);
+    }
 }
@@ -0,0 +1,396 @@
+//! An HTTP API for operating a running Vector.
+//!
+//! `GET /tap?component=<id>&kind=<input|output>&limit=<events per second>`
+//! streams a sample of the events flowing through a component as
+//! newline-delimited JSON, like `{"log":{...},"skipped":3}`, where `skipped`
+//! counts the events left out since the previous one.
+//!
+//! `GET /topology` describes the running components as JSON: their ids,
+//! kinds and types, how they're wired together, whether sinks passed their
+//! healthcheck, and event and error counts from the internal metrics. Error
+//! counts of components that don't label their errors by id are shared by all
+//! components of the same kind and type.
+
+use crate::{
+    event::{log_schema, Event, LogEvent},
+    topology::{
+        graph::{Component, ComponentKind, Graph},
+        tap::{TapError, TapEvent, TapKind, Taps},
+    },
+};
+use futures01::{Future, Stream};
+use hyper::{service::service_fn_ok, Body, Method, Request, Response, Server, StatusCode};
+use metrics_core::Key;
+use metrics_runtime::Measurement;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{io, net::SocketAddr};
+use string_cache::DefaultAtom as Atom;
+
+/// Replaces the values of the fields the log schema marks as redacted.
+const REDACTED: &str = "[REDACTED]";
+
+const DEFAULT_TAP_LIMIT: usize = 10;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Options {
+    pub enabled: bool,
+    pub address: SocketAddr,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:8686".parse().unwrap(),
+        }
+    }
+}
+
+/// Binds the API server, which runs until the returned future is dropped.
+pub fn serve(
+    options: &Options,
+    taps: Taps,
+    graph: Graph,
+) -> crate::Result<impl Future<Item = (), Error = ()>> {
+    let new_service = move || {
+        let (taps, graph) = (taps.clone(), graph.clone());
+        service_fn_ok(move |req| handle(req, &taps, &graph))
+    };
+
+    let server = Server::try_bind(&options.address)?
+        .serve(new_service)
+        .map_err(|error| error!(message = "API server error.", %error));
+
+    info!(message = "API server listening.", address = %options.address);
+    Ok(server)
+}
+
+fn handle(req: Request<Body>, taps: &Taps, graph: &Graph) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/tap") => handle_tap(&req, taps),
+        (&Method::GET, "/topology") => handle_topology(graph),
+        (_, "/tap") | (_, "/topology") => {
+            error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+fn handle_topology(graph: &Graph) -> Response<Body> {
+    let body = describe_topology(graph);
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn handle_tap(req: &Request<Body>, taps: &Taps) -> Response<Body> {
+    let (component, kind, limit) = match parse_tap_query(req.uri().query().unwrap_or("")) {
+        Ok(query) => query,
+        Err(message) => return error_response(StatusCode::BAD_REQUEST, &message),
+    };
+
+    match taps.tap(&component, kind, limit) {
+        Ok(rx) => {
+            let lines = rx
+                .map(|tapped| encode(tapped, log_schema().redacted_keys()))
+                .map_err(|()| io::Error::new(io::ErrorKind::Other, "Tap failed"));
+            Response::builder()
+                .header("Content-Type", "application/x-ndjson")
+                .body(Body::wrap_stream(lines))
+                .unwrap()
+        }
+        Err(error @ TapError::ComponentNotFound { .. }) => {
+            error_response(StatusCode::NOT_FOUND, &error.to_string())
+        }
+        Err(error) => error_response(StatusCode::BAD_REQUEST, &error.to_string()),
+    }
+}
+
+fn parse_tap_query(query: &str) -> Result<(String, TapKind, usize), String> {
+    let mut component = None;
+    let mut kind = TapKind::Output;
+    let mut limit = DEFAULT_TAP_LIMIT;
+
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "component" => component = Some(value.into_owned()),
+            "kind" => {
+                kind = match value.as_ref() {
+                    "input" => TapKind::Input,
+                    "output" => TapKind::Output,
+                    _ => return Err(format!("Invalid kind {:?}", value)),
+                }
+            }
+            "limit" => {
+                limit = match value.parse() {
+                    Ok(limit) if limit > 0 => limit,
+                    _ => return Err(format!("Invalid limit {:?}", value)),
+                }
+            }
+            _ => return Err(format!("Unknown parameter {:?}", key)),
+        }
+    }
+
+    let component = component.ok_or_else(|| "Missing component".to_string())?;
+    Ok((component, kind, limit))
+}
+
+fn encode(tapped: TapEvent, redacted_keys: &[Atom]) -> Vec<u8> {
+    let mut json = match tapped.event {
+        Event::Log(log) => json!({ "log": redact(log, redacted_keys) }),
+        Event::Metric(metric) => json!({ "metric": metric }),
+        Event::Trace(trace) => json!({ "trace": redact(trace.into_log(), redacted_keys) }),
+    };
+    if tapped.skipped > 0 {
+        json["skipped"] = tapped.skipped.into();
+    }
+
+    let mut line = serde_json::to_vec(&json).unwrap();
+    line.push(b'\n');
+    line
+}
+
+fn redact(mut log: LogEvent, redacted_keys: &[Atom]) -> LogEvent {
+    for key in redacted_keys {
+        if log.contains(key) {
+            log.insert(key, REDACTED);
+        }
+    }
+    log
+}
+
+/// Describes the components along with their health and metrics, which are
+/// null if the metrics system isn't running.
+/// Describes the components as `GET /topology` does, without their health
+/// and metrics unless the metrics system is installed.
+pub fn describe_topology(graph: &Graph) -> serde_json::Value {
+    let measurements = crate::metrics::CONTROLLER
+        .get()
+        .map(|controller| controller.snapshot().into_measurements());
+    describe(graph.components(), measurements.as_deref())
+}
+
+fn describe(
+    components: Vec<Component>,
+    measurements: Option<&[(Key, Measurement)]>,
+) -> serde_json::Value {
+    let components = components
+        .into_iter()
+        .map(|component| {
+            let mut json = json!(component);
+            json["healthy"] = json!(measurements.and_then(|m| healthy(&component, m)));
+            json["metrics"] = match measurements {
+                Some(measurements) => component_metrics(&component, measurements),
+                None => serde_json::Value::Null,
+            };
+            json
+        })
+        .collect::<Vec<_>>();
+    json!({ "components": components })
+}
+
+fn healthy(component: &Component, measurements: &[(Key, Measurement)]) -> Option<bool> {
+    measurements
+        .iter()
+        .filter(|(key, _)| key.name() == "component_healthy" && labelled_with_id(key, component))
+        .find_map(|(_, measurement)| match measurement {
+            Measurement::Gauge(healthy) => Some(*healthy > 0),
+            _ => None,
+        })
+}
+
+fn component_metrics(
+    component: &Component,
+    measurements: &[(Key, Measurement)],
+) -> serde_json::Value {
+    let sum = |matches: &dyn Fn(&Key) -> bool| {
+        measurements
+            .iter()
+            .filter(|(key, _)| matches(key))
+            .map(|(_, measurement)| match measurement {
+                Measurement::Counter(count) => *count,
+                _ => 0,
+            })
+            .sum::<u64>()
+    };
+    let received_events = sum(&|key| {
+        key.name() == "component_received_events_total" && labelled_with_id(key, component)
+    });
+    let sent_events =
+        sum(&|key| key.name() == "component_sent_events_total" && labelled_with_id(key, component));
+    let errors = sum(&|key| key.name().ends_with("_errors") && labelled_for(key, component));
+
+    let mut json = json!({ "errors": errors });
+    // Sources don't receive events from other components, and sinks don't
+    // send any on.
+    if component.kind != ComponentKind::Source {
+        json["received_events"] = received_events.into();
+    }
+    if component.kind != ComponentKind::Sink {
+        json["sent_events"] = sent_events.into();
+    }
+    json
+}
+
+fn label<'a>(key: &'a Key, name: &str) -> Option<&'a str> {
+    key.labels()
+        .find(|label| label.key() == name)
+        .map(|label| label.value())
+}
+
+fn labelled_with_id(key: &Key, component: &Component) -> bool {
+    label(key, "component_id") == Some(component.id.as_str())
+}
+
+/// Whether a metric is about the component, either by id or, failing that,
+/// by kind and type.
+fn labelled_for(key: &Key, component: &Component) -> bool {
+    match label(key, "component_id") {
+        Some(id) => id == component.id,
+        None => {
+            label(key, "component_kind") == Some(component.kind.as_str())
+                && label(key, "component_type") == Some(component.component_type.as_str())
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(json!({ "error": message }).to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics_core::Label;
+
+    #[test]
+    fn api_parses_tap_query() {
+        assert_eq!(
+            parse_tap_query("component=in"),
+            Ok(("in".into(), TapKind::Output, DEFAULT_TAP_LIMIT))
+        );
+        assert_eq!(
+            parse_tap_query("component=my%20sink&kind=input&limit=3"),
+            Ok(("my sink".into(), TapKind::Input, 3))
+        );
+        assert!(parse_tap_query("").is_err());
+        assert!(parse_tap_query("component=in&kind=sideways").is_err());
+        assert!(parse_tap_query("component=in&limit=0").is_err());
+        assert!(parse_tap_query("component=in&rate=1").is_err());
+    }
+
+    #[test]
+    fn api_encodes_tapped_events() {
+        let mut event = Event::from("hello");
+        event.as_mut_log().insert("password", "hunter2");
+
+        let line = encode(TapEvent { event, skipped: 2 }, &["password".into()]);
+
+        assert_eq!(line.last(), Some(&b'\n'));
+        let json: serde_json::Value = serde_json::from_slice(&line).unwrap();
+        assert_eq!(json["log"]["message"], "hello");
+        assert_eq!(json["log"]["password"], REDACTED);
+        assert_eq!(json["skipped"], 2);
+
+        let line = encode(
+            TapEvent {
+                event: Event::from("hello"),
+                skipped: 0,
+            },
+            &[],
+        );
+        let json: serde_json::Value = serde_json::from_slice(&line).unwrap();
+        assert_eq!(json["log"]["message"], "hello");
+        assert!(json.get("skipped").is_none());
+    }
+
+    #[test]
+    fn api_describes_components_with_metrics() {
+        let component = |id: &str, kind, inputs: &[&str]| Component {
+            id: id.into(),
+            kind,
+            component_type: "http".into(),
+            inputs: inputs.iter().map(|&input| input.into()).collect(),
+            outputs: Vec::new(),
+        };
+        let key = |name: &str, labels: &[(&'static str, &'static str)]| {
+            let labels = labels
+                .iter()
+                .map(|&(key, value)| Label::new(key, value))
+                .collect::<Vec<_>>();
+            Key::from_name_and_labels(name.to_owned(), labels)
+        };
+        let components = vec![
+            component("in", ComponentKind::Source, &[]),
+            component("out", ComponentKind::Sink, &["in"]),
+        ];
+        let measurements = vec![
+            (
+                key(
+                    "component_sent_events_total",
+                    &[("component_kind", "source"), ("component_id", "in")],
+                ),
+                Measurement::Counter(5),
+            ),
+            (
+                key(
+                    "component_received_events_total",
+                    &[("component_kind", "sink"), ("component_id", "out")],
+                ),
+                Measurement::Counter(4),
+            ),
+            (
+                key(
+                    "component_healthy",
+                    &[("component_kind", "sink"), ("component_id", "out")],
+                ),
+                Measurement::Gauge(0),
+            ),
+            (
+                key(
+                    "request_errors",
+                    &[("component_kind", "sink"), ("component_type", "http")],
+                ),
+                Measurement::Counter(2),
+            ),
+            (
+                key(
+                    "parse_errors",
+                    &[("component_kind", "source"), ("component_type", "http")],
+                ),
+                Measurement::Counter(1),
+            ),
+            (
+                key(
+                    "request_errors",
+                    &[("component_kind", "sink"), ("component_id", "other")],
+                ),
+                Measurement::Counter(7),
+            ),
+        ];
+
+        let json = describe(components.clone(), Some(&measurements));
+        assert_eq!(json["components"][0]["id"], "in");
+        assert_eq!(json["components"][0]["healthy"], serde_json::Value::Null);
+        assert_eq!(
+            json["components"][0]["metrics"],
+            json!({ "sent_events": 5, "errors": 1 })
+        );
+        assert_eq!(json["components"][1]["inputs"], json!(["in"]));
+        assert_eq!(json["components"][1]["healthy"], false);
+        assert_eq!(
+            json["components"][1]["metrics"],
+            json!({ "received_events": 4, "errors": 2 })
+        );
+
+        let json = describe(components, None);
+        assert_eq!(json["components"][1]["metrics"], serde_json::Value::Null);
+    }
+}
@@ -1,5 +1,6 @@
 #![cfg(feature = "leveldb")]
 
+use super::encryption::{self, DecryptError, Encryption};
 use crate::event::{proto, Event};
 use futures01::{
     task::{self, AtomicTask, Task},
@@ -10,6 +11,7 @@ use leveldb::database::{
     compaction::Compaction,
     iterator::{Iterable, LevelDBIterator},
     kv::KV,
+    management,
     options::{Options, ReadOptions, WriteOptions},
     Database,
 };
@@ -17,6 +19,7 @@ use prost::Message;
 use snafu::{ResultExt, Snafu};
 use std::collections::VecDeque;
 use std::convert::TryInto;
+use std::fs;
 use std::io;
 use std::mem::size_of;
 use std::path::{Path, PathBuf};
@@ -24,6 +27,7 @@ use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc, Mutex,
 };
+use tempfile::TempDir;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -41,6 +45,94 @@ pub enum Error {
         data_dir: PathBuf,
         source: leveldb::database::error::Error,
     },
+    #[snafu(display(
+        "The buffer holds encrypted records, but no encryption is configured for it"
+    ))]
+    EncryptionNotConfigured,
+    #[snafu(display(
+        "The buffer holds records encrypted with key {:?}, which isn't configured",
+        key_id
+    ))]
+    UnknownEncryptionKey { key_id: String },
+}
+
+#[derive(Debug, Snafu)]
+enum RecordError {
+    #[snafu(display("Record is {} bytes, too short for its header", len))]
+    Truncated { len: usize },
+    #[snafu(display(
+        "Record checksum mismatch, expected {:#010x}, found {:#010x}",
+        expected,
+        found
+    ))]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[snafu(display("Record is encrypted, but no encryption is configured"))]
+    NotDecryptable,
+    #[snafu(display("{}", source))]
+    Decrypt { source: DecryptError },
+    #[snafu(display("Unable to decode event: {}", source))]
+    Decode { source: prost::DecodeError },
+}
+
+/// Marks a record as `[CHECKSUMMED][crc32 of the event, big endian][event]`.
+/// An encoded event never starts with a zero byte, as that isn't a valid
+/// protobuf tag, so records written before checksums were added can still be
+/// told apart and read.
+const CHECKSUMMED: u8 = 0;
+const HEADER_LEN: usize = 1 + size_of::<u32>();
+/// Marks a record as `[ENCRYPTED][encrypted event]`, see `Encryption::encrypt`.
+/// Its authentication tag takes the place of the checksum.
+const ENCRYPTED: u8 = 1;
+
+fn encode_record(event: Event, encryption: Option<&Encryption>) -> Vec<u8> {
+    let event = proto::EventWrapper::from(event);
+    let mut record = Vec::with_capacity(HEADER_LEN + event.encoded_len());
+
+    match encryption {
+        Some(encryption) => {
+            let mut encoded = Vec::with_capacity(event.encoded_len());
+            event.encode(&mut encoded).unwrap(); // This will not error when writing to a Vec
+            record.push(ENCRYPTED);
+            encryption.encrypt(&encoded, &mut record);
+        }
+        None => {
+            record.push(CHECKSUMMED);
+            record.extend_from_slice(&[0; size_of::<u32>()]);
+            event.encode(&mut record).unwrap(); // This will not error when writing to a Vec
+
+            let checksum = crc32fast::hash(&record[HEADER_LEN..]);
+            record[1..HEADER_LEN].copy_from_slice(&checksum.to_be_bytes());
+        }
+    }
+
+    record
+}
+
+fn decode_record(record: &[u8], encryption: Option<&Encryption>) -> Result<Event, RecordError> {
+    let decrypted;
+    let event = match record.first() {
+        Some(&CHECKSUMMED) => {
+            if record.len() < HEADER_LEN {
+                return Err(RecordError::Truncated { len: record.len() });
+            }
+            let expected = u32::from_be_bytes(record[1..HEADER_LEN].try_into().unwrap());
+            let found = crc32fast::hash(&record[HEADER_LEN..]);
+            if expected != found {
+                return Err(RecordError::ChecksumMismatch { expected, found });
+            }
+            &record[HEADER_LEN..]
+        }
+        Some(&ENCRYPTED) => {
+            let encryption = encryption.ok_or(RecordError::NotDecryptable)?;
+            decrypted = encryption.decrypt(&record[1..]).context(Decrypt)?;
+            &decrypted[..]
+        }
+        _ => record,
+    };
+
+    proto::EventWrapper::decode(event)
+        .map(Event::from)
+        .context(Decode)
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -68,6 +160,8 @@ pub struct Writer {
     batch_size: usize,
     max_size: usize,
     current_size: Arc<AtomicUsize>,
+    sync_writes: bool,
+    encryption: Option<Arc<Encryption>>,
 }
 
 // Writebatch isn't Send, but the leveldb docs explicitly say that it's okay to share across threads
@@ -84,6 +178,8 @@ impl Clone for Writer {
             batch_size: 0,
             max_size: self.max_size,
             current_size: Arc::clone(&self.current_size),
+            sync_writes: self.sync_writes,
+            encryption: self.encryption.clone(),
         }
     }
 }
@@ -96,8 +192,7 @@ impl Sink for Writer {
         &mut self,
         event: Self::SinkItem,
     ) -> Result<AsyncSink<Self::SinkItem>, Self::SinkError> {
-        let mut value = vec![];
-        proto::EventWrapper::from(event).encode(&mut value).unwrap(); // This will not error when writing to a Vec
+        let value = encode_record(event, self.encryption.as_deref());
         let event_size = value.len();
 
         if self.current_size.fetch_add(event_size, Ordering::Relaxed) + (event_size / 2)
@@ -112,7 +207,7 @@ impl Sink for Writer {
 
             self.poll_complete()?;
 
-            let event = proto::EventWrapper::decode(value).unwrap().into();
+            let event = decode_record(&value, self.encryption.as_deref()).unwrap();
             return Ok(AsyncSink::NotReady(event));
         }
 
@@ -129,9 +224,9 @@ impl Sink for Writer {
     }
 
     fn poll_complete(&mut self) -> Result<Async<()>, Self::SinkError> {
-        // This doesn't write all the way through to disk and doesn't need to be wrapped
-        // with `blocking`. (It does get written to a memory mapped table that will be
-        // flushed even in the case of a process crash.)
+        // Unless `sync_writes` is set, this doesn't write all the way through to disk and
+        // doesn't need to be wrapped with `blocking`. (It does get written to leveldb's log,
+        // which survives a process crash, but not necessarily an OS crash or power loss.)
         if self.batch_size > 0 {
             self.write_batch();
         }
@@ -142,9 +237,9 @@ impl Sink for Writer {
 
 impl Writer {
     fn write_batch(&mut self) {
-        self.db
-            .write(WriteOptions::new(), &self.writebatch)
-            .unwrap();
+        let mut options = WriteOptions::new();
+        options.sync = self.sync_writes;
+        self.db.write(options, &self.writebatch).unwrap();
         self.writebatch = Writebatch::new();
         self.batch_size = 0;
         self.write_notifier.notify();
@@ -165,12 +260,25 @@ impl Drop for Writer {
 pub struct Reader {
     db: Arc<Database<Key>>,
     read_offset: usize,
+    write_offset: Arc<AtomicUsize>,
+    /// Where writes were at when the buffer was opened. Records missing below it
+    /// were lost before that, to a crash or a repair, and are never coming.
+    recovered_offset: usize,
     delete_offset: usize,
     write_notifier: Arc<AtomicTask>,
     blocked_write_tasks: Arc<Mutex<Vec<Task>>>,
     current_size: Arc<AtomicUsize>,
     ack_counter: Arc<AtomicUsize>,
-    unacked_sizes: VecDeque<usize>,
+    unacked: VecDeque<Unacked>,
+    encryption: Option<Arc<Encryption>>,
+}
+
+/// A record that was read, but not deleted yet.
+struct Unacked {
+    size: usize,
+    /// Records that couldn't be read are never handed out, so never acked
+    /// either, and are deleted along with the records around them.
+    skipped: bool,
 }
 
 // Writebatch isn't Send, but the leveldb docs explicitly say that it's okay to share across threads
@@ -187,30 +295,48 @@ impl Stream for Reader {
         // using write_notifier to wake this task up after the next write.
         self.write_notifier.register();
 
-        // This will usually complete instantly, but in the case of a large queue (or a fresh launch of
-        // the app), this will have to go to disk.
-        let next = tokio::task::block_in_place(|| {
-            self.db
-                .get(ReadOptions::new(), Key(self.read_offset))
-                .unwrap()
-        });
-
-        if let Some(value) = next {
-            self.unacked_sizes.push_back(value.len());
-            self.read_offset += 1;
+        loop {
+            // This will usually complete instantly, but in the case of a large queue (or a fresh
+            // launch of the app), this will have to go to disk.
+            let next = tokio::task::block_in_place(|| {
+                self.db.get(ReadOptions::new(), Key(self.read_offset))
+            });
+
+            let value = match next {
+                Ok(Some(value)) => value,
+                Ok(None) if self.read_offset < self.recovered_offset => {
+                    warn!(message = "Record missing from disk buffer; skipping it.");
+                    self.skip(0);
+                    continue;
+                }
+                Ok(None) => break,
+                // Whatever was written under this key can't be read back, so there's
+                // nothing to do but skip it.
+                Err(error) if self.read_offset < self.write_offset.load(Ordering::Relaxed) => {
+                    error!(message = "Unable to read record from disk buffer; skipping it.", %error);
+                    self.skip(0);
+                    continue;
+                }
+                Err(_) => break,
+            };
 
-            match proto::EventWrapper::decode(value) {
+            match decode_record(&value, self.encryption.as_deref()) {
                 Ok(event) => {
-                    let event = Event::from(event);
-                    Ok(Async::Ready(Some(event)))
+                    self.unacked.push_back(Unacked {
+                        size: value.len(),
+                        skipped: false,
+                    });
+                    self.read_offset += 1;
+                    return Ok(Async::Ready(Some(event)));
                 }
-                Err(err) => {
-                    error!("Error deserializing proto: {:?}", err);
-                    debug_assert!(false);
-                    self.poll()
+                Err(error) => {
+                    error!(message = "Corrupt record in disk buffer; skipping it.", %error);
+                    self.skip(value.len());
                 }
             }
-        } else if Arc::strong_count(&self.db) == 1 {
+        }
+
+        if Arc::strong_count(&self.db) == 1 {
             // There are no writers left
             Ok(Async::Ready(None))
         } else {
@@ -226,15 +352,35 @@ impl Drop for Reader {
 }
 
 impl Reader {
+    fn skip(&mut self, size: usize) {
+        self.unacked.push_back(Unacked {
+            size,
+            skipped: true,
+        });
+        self.read_offset += 1;
+    }
+
     fn delete_acked(&mut self) {
-        let num_to_delete = self.ack_counter.swap(0, Ordering::Relaxed);
+        let mut num_acked = self.ack_counter.swap(0, Ordering::Relaxed);
+
+        // Acks count the events that were handed out, so skipped records are
+        // deleted as soon as everything before them is.
+        let mut num_to_delete = 0;
+        let mut size_deleted = 0;
+        for record in &self.unacked {
+            if !record.skipped {
+                if num_acked == 0 {
+                    break;
+                }
+                num_acked -= 1;
+            }
+            num_to_delete += 1;
+            size_deleted += record.size;
+        }
+        assert!(num_acked == 0, "Tried to ack beyond read offset");
 
         if num_to_delete > 0 {
             let new_offset = self.delete_offset + num_to_delete;
-            assert!(
-                new_offset <= self.read_offset,
-                "Tried to ack beyond read offset"
-            );
 
             let mut delete_batch = Writebatch::new();
 
@@ -248,7 +394,7 @@ impl Reader {
 
             self.db.compact(&Key(0), &Key(self.delete_offset));
 
-            let size_deleted = self.unacked_sizes.drain(..num_to_delete).sum();
+            self.unacked.drain(..num_to_delete);
             self.current_size.fetch_sub(size_deleted, Ordering::Relaxed);
         }
 
@@ -258,10 +404,18 @@ impl Reader {
     }
 }
 
+fn options() -> Options {
+    let mut options = Options::new();
+    options.create_if_missing = true;
+    options
+}
+
 pub fn open(
     data_dir: &Path,
     buffer_dir: &Path,
     max_size: usize,
+    sync_writes: bool,
+    encryption: Option<Encryption>,
 ) -> Result<(Writer, Reader, super::Acker), Error> {
     let path = data_dir.join(buffer_dir);
 
@@ -289,12 +443,20 @@ pub fn open(
             }
         })?;
 
-    let mut options = Options::new();
-    options.create_if_missing = true;
-
-    let db: Database<Key> = Database::open(&path, options).with_context(|| DataDirOpenError {
-        data_dir: data_dir.to_path_buf(),
-    })?;
+    // Leveldb recovers from a torn write at the end of its log by itself, but damage
+    // elsewhere, like a table file cut short by a crash during compaction, keeps it
+    // from opening until it's repaired. Repairing keeps whatever can still be read.
+    let db: Database<Key> = match Database::open(&path, options()) {
+        Ok(db) => db,
+        Err(error) => {
+            warn!(message = "Unable to open disk buffer; repairing it.", ?path, %error);
+            management::repair(&path, options())
+                .and_then(|()| Database::open(&path, options()))
+                .with_context(|| DataDirOpenError {
+                    data_dir: data_dir.to_path_buf(),
+                })?
+        }
+    };
     let db = Arc::new(db);
 
     let head;
@@ -306,7 +468,25 @@ pub fn open(
         tail = if iter.valid() { iter.key().0 + 1 } else { 0 };
     }
 
-    let initial_size = db.value_iter(ReadOptions::new()).map(|v| v.len()).sum();
+    let mut initial_size = 0;
+    for value in db.value_iter(ReadOptions::new()) {
+        initial_size += value.len();
+        // Better to refuse to start than to find out while reading, and have
+        // to drop every record that can't be decrypted.
+        if value.first() == Some(&ENCRYPTED) {
+            if let Ok(key_id) = encryption::key_id(&value[1..]) {
+                match &encryption {
+                    None => return Err(Error::EncryptionNotConfigured),
+                    Some(encryption) if !encryption.has_key(key_id) => {
+                        return Err(Error::UnknownEncryptionKey {
+                            key_id: key_id.to_owned(),
+                        })
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+    }
     let current_size = Arc::new(AtomicUsize::new(initial_size));
 
     let write_notifier = Arc::new(AtomicTask::new());
@@ -316,26 +496,317 @@ pub fn open(
     let ack_counter = Arc::new(AtomicUsize::new(0));
     let acker = super::Acker::Disk(Arc::clone(&ack_counter), Arc::clone(&write_notifier));
 
+    let write_offset = Arc::new(AtomicUsize::new(tail));
+    let encryption = encryption.map(Arc::new);
+
     let writer = Writer {
         db: Arc::clone(&db),
         write_notifier: Arc::clone(&write_notifier),
         blocked_write_tasks: Arc::clone(&blocked_write_tasks),
-        offset: Arc::new(AtomicUsize::new(tail)),
+        offset: Arc::clone(&write_offset),
         writebatch: Writebatch::new(),
         batch_size: 0,
         max_size,
         current_size: Arc::clone(&current_size),
+        sync_writes,
+        encryption: encryption.clone(),
     };
     let reader = Reader {
         db: Arc::clone(&db),
         write_notifier: Arc::clone(&write_notifier),
         blocked_write_tasks,
         read_offset: head,
+        write_offset,
+        recovered_offset: tail,
         delete_offset: head,
         current_size,
         ack_counter,
-        unacked_sizes: VecDeque::new(),
+        unacked: VecDeque::new(),
+        encryption,
     };
 
     Ok((writer, reader, acker))
 }
+
+/// How many times to copy a buffer that keeps changing while it's copied.
+const SNAPSHOT_ATTEMPTS: usize = 3;
+
+/// A copy of a disk buffer, to read what's in it without disturbing the
+/// buffer itself, which a running Vector keeps locked.
+pub struct Snapshot {
+    db: Database<Key>,
+    encryption: Option<Encryption>,
+    // Declared after `db` so the copy is closed before it's removed.
+    _dir: TempDir,
+}
+
+/// A record in a snapshot, holding an event unless it's unreadable.
+#[derive(Debug)]
+pub struct SnapshotRecord {
+    pub key: usize,
+    pub size: usize,
+    pub event: Result<Event, String>,
+}
+
+impl Snapshot {
+    pub fn open(path: &Path, encryption: Option<Encryption>) -> crate::Result<Self> {
+        let dir = tempfile::tempdir()?;
+
+        // Leveldb moves records to new files and deletes old ones as it goes,
+        // so a file can vanish before it's copied, in which case copying again
+        // gets the new ones.
+        let mut copied = copy_buffer(path, dir.path());
+        for _ in 1..SNAPSHOT_ATTEMPTS {
+            if copied.is_ok() {
+                break;
+            }
+            copied = copy_buffer(path, dir.path());
+        }
+        copied.map_err(|error| format!("Unable to copy disk buffer {:?}: {}", path, error))?;
+
+        // A copy taken in the middle of a compaction may not open as is, but
+        // repairing it keeps whatever can be read, and only touches the copy.
+        let db = match Database::open(dir.path(), options()) {
+            Ok(db) => db,
+            Err(_) => management::repair(dir.path(), options())
+                .and_then(|()| Database::open(dir.path(), options()))?,
+        };
+
+        Ok(Self {
+            db,
+            encryption,
+            _dir: dir,
+        })
+    }
+
+    /// The records in the order they'd be read from the buffer.
+    pub fn records(&self) -> impl Iterator<Item = SnapshotRecord> + '_ {
+        self.db
+            .iter(ReadOptions::new())
+            .map(move |(key, value)| SnapshotRecord {
+                key: key.0,
+                size: value.len(),
+                event: decode_record(&value, self.encryption.as_ref())
+                    .map_err(|error| error.to_string()),
+            })
+    }
+}
+
+/// Copies the files of the buffer at `from`, all but the lock a running
+/// Vector holds on it.
+fn copy_buffer(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() != "LOCK" && entry.file_type()?.is_file() {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::runtime;
+    use futures01::{future, sync::oneshot, try_ready, Future};
+    use std::fs::{self, OpenOptions};
+    use tempfile::tempdir;
+
+    fn events(count: usize) -> Vec<Event> {
+        (0..count)
+            .map(|i| Event::from(format!("event {}", i)))
+            .collect()
+    }
+
+    /// Reads up to `count` events, handing the reader back to be acked.
+    fn read(reader: Reader, count: usize) -> (Vec<Event>, Reader) {
+        let rt = runtime();
+        let mut reader = Some(reader);
+        let mut events = vec![];
+        let read = future::poll_fn(move || {
+            while events.len() < count {
+                match try_ready!(reader.as_mut().unwrap().poll()) {
+                    Some(event) => events.push(event),
+                    None => break,
+                }
+            }
+            Ok(Async::Ready((events.split_off(0), reader.take().unwrap())))
+        });
+        oneshot::spawn(read, &rt.executor()).wait().unwrap()
+    }
+
+    #[test]
+    fn disk_buffer_recovers_from_truncated_log() {
+        let data_dir = tempdir().unwrap();
+        let events = events(10);
+
+        let (mut writer, reader, _acker) =
+            open(data_dir.path(), "buffer".as_ref(), 1_000_000, false, None).unwrap();
+        // Sending flushes, so each event is a record of its own in leveldb's log.
+        for event in &events {
+            writer = writer.send(event.clone()).wait().unwrap();
+        }
+        drop((writer, reader));
+
+        // Cut the last record short, as a crash in the middle of writing it would.
+        let log = fs::read_dir(data_dir.path().join("buffer"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "log")
+            })
+            .unwrap();
+        let file = OpenOptions::new().write(true).open(&log).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 3).unwrap();
+        drop(file);
+
+        let (writer, reader, _acker) =
+            open(data_dir.path(), "buffer".as_ref(), 1_000_000, false, None).unwrap();
+        let writer = writer.send(Event::from("after recovery")).wait().unwrap();
+        drop(writer);
+
+        let (read, _reader) = read(reader, 20);
+        assert_eq!(&read[..9], &events[..9]);
+        assert_eq!(read[9], Event::from("after recovery"));
+        assert_eq!(read.len(), 10);
+    }
+
+    #[test]
+    fn disk_buffer_skips_corrupt_records() {
+        let data_dir = tempdir().unwrap();
+        let events = events(3);
+
+        let mut corrupt = encode_record(events[1].clone(), None);
+        *corrupt.last_mut().unwrap() ^= 0xff;
+        let mut legacy = vec![];
+        proto::EventWrapper::from(events[2].clone())
+            .encode(&mut legacy)
+            .unwrap();
+
+        let (writer, reader, _acker) =
+            open(data_dir.path(), "buffer".as_ref(), 1_000_000, false, None).unwrap();
+        let records = &[encode_record(events[0].clone(), None), corrupt, legacy];
+        for (key, record) in records.iter().enumerate() {
+            writer
+                .db
+                .put(WriteOptions::new(), Key(key), record)
+                .unwrap();
+        }
+        drop(writer);
+
+        let (writer, reader, acker) =
+            open(data_dir.path(), "buffer".as_ref(), 1_000_000, false, None).unwrap();
+        drop(writer);
+
+        let (read, mut reader) = read(reader, 3);
+        assert_eq!(read, vec![events[0].clone(), events[2].clone()]);
+
+        // Acking the events it did hand out deletes the corrupt record too.
+        acker.ack(2);
+        reader.delete_acked();
+        assert!(reader.unacked.is_empty());
+        assert_eq!(reader.current_size.load(Ordering::Relaxed), 0);
+        assert!(reader.db.keys_iter(ReadOptions::new()).next().is_none());
+    }
+
+    #[test]
+    fn disk_buffer_snapshot_reads_records_in_place() {
+        let data_dir = tempdir().unwrap();
+        let events = events(3);
+        let mut corrupt = encode_record(events[1].clone(), None);
+        *corrupt.last_mut().unwrap() ^= 0xff;
+        let records = vec![
+            encode_record(events[0].clone(), None),
+            corrupt,
+            encode_record(events[2].clone(), None),
+        ];
+
+        let (writer, reader, _acker) =
+            open(data_dir.path(), "buffer".as_ref(), 1_000_000, false, None).unwrap();
+        for (key, record) in records.iter().enumerate() {
+            writer
+                .db
+                .put(WriteOptions::new(), Key(key), record)
+                .unwrap();
+        }
+
+        // The buffer is still open, and locked, as it would be in a running Vector.
+        let snapshot = Snapshot::open(&data_dir.path().join("buffer"), None).unwrap();
+        let snapshot_records = snapshot.records().collect::<Vec<_>>();
+        assert_eq!(snapshot_records.len(), 3);
+        for (key, (record, size)) in snapshot_records
+            .iter()
+            .zip(records.iter().map(Vec::len))
+            .enumerate()
+        {
+            assert_eq!((record.key, record.size), (key, size));
+        }
+        assert_eq!(snapshot_records[0].event.as_ref().unwrap(), &events[0]);
+        assert!(snapshot_records[1]
+            .event
+            .as_ref()
+            .unwrap_err()
+            .contains("checksum mismatch"));
+        assert_eq!(snapshot_records[2].event.as_ref().unwrap(), &events[2]);
+        drop(snapshot);
+
+        // And the buffer itself is left as it was.
+        drop(writer);
+        let (read, _reader) = read(reader, 3);
+        assert_eq!(read, vec![events[0].clone(), events[2].clone()]);
+    }
+
+    fn encryption(key_id: &str, keys: &[&str]) -> Encryption {
+        let keys = keys
+            .iter()
+            .map(|id| (id.to_string(), base64::encode(&[id.as_bytes()[0]; 32])))
+            .collect();
+        let config = crate::buffers::EncryptionConfig {
+            key_id: key_id.into(),
+            keys,
+        };
+        config.build().unwrap()
+    }
+
+    #[test]
+    fn disk_buffer_encrypts_records() {
+        let data_dir = tempdir().unwrap();
+        let reopen = |encryption| {
+            open(
+                data_dir.path(),
+                "buffer".as_ref(),
+                1_000_000,
+                false,
+                encryption,
+            )
+        };
+        let event = Event::from("a very sensitive message");
+
+        let (writer, reader, _acker) = reopen(Some(encryption("old", &["old"]))).unwrap();
+        let writer = writer.send(event.clone()).wait().unwrap();
+        drop((writer, reader));
+
+        for entry in fs::read_dir(data_dir.path().join("buffer")).unwrap() {
+            let contents = fs::read(entry.unwrap().path()).unwrap();
+            assert!(!contents
+                .windows(b"sensitive".len())
+                .any(|window| window == b"sensitive"));
+        }
+
+        assert!(matches!(reopen(None), Err(Error::EncryptionNotConfigured)));
+        assert!(matches!(
+            reopen(Some(encryption("new", &["new"]))),
+            Err(Error::UnknownEncryptionKey { .. })
+        ));
+
+        // After rotating, old records are read with the old key, and new ones are
+        // written with the new key.
+        let (writer, reader, _acker) = reopen(Some(encryption("new", &["old", "new"]))).unwrap();
+        let writer = writer.send(Event::from("rotated")).wait().unwrap();
+        drop(writer);
+        let (read, _reader) = read(reader, 2);
+        assert_eq!(read, vec![event, Event::from("rotated")]);
+    }
+}
@@ -0,0 +1,222 @@
+use openssl::{
+    error::ErrorStack,
+    rand::rand_bytes,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Encrypts disk buffer records with AES-256-GCM.
+///
+/// New records are encrypted with the key `key_id` names, and tagged with
+/// that id, so keys can be rotated by adding a new key and pointing `key_id`
+/// at it. Old keys have to stay in `keys` until the records encrypted with
+/// them were read.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionConfig {
+    pub key_id: String,
+    /// Base64 encoded 256 bit keys, by id.
+    pub keys: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum BuildError {
+    #[snafu(display("Encryption key {:?} is not one of the configured keys", key_id))]
+    MissingKey { key_id: String },
+    #[snafu(display("Encryption key {:?} is not valid base64: {}", key_id, source))]
+    InvalidKey {
+        key_id: String,
+        source: base64::DecodeError,
+    },
+    #[snafu(display(
+        "Encryption key {:?} is {} bytes long, it must be {} bytes",
+        key_id,
+        len,
+        KEY_LEN
+    ))]
+    InvalidKeyLength { key_id: String, len: usize },
+    #[snafu(display("Encryption key id {:?} is longer than 255 bytes", key_id))]
+    KeyIdTooLong { key_id: String },
+}
+
+#[derive(Debug, Snafu)]
+pub enum DecryptError {
+    #[snafu(display("Encrypted record is truncated"))]
+    Truncated,
+    #[snafu(display("Record was encrypted with unknown key {:?}", key_id))]
+    UnknownKey { key_id: String },
+    #[snafu(display("Unable to decrypt record: {}", source))]
+    Decrypt { source: ErrorStack },
+}
+
+impl EncryptionConfig {
+    pub fn build(&self) -> Result<Encryption, BuildError> {
+        if !self.keys.contains_key(&self.key_id) {
+            return Err(BuildError::MissingKey {
+                key_id: self.key_id.clone(),
+            });
+        }
+
+        let mut keys = HashMap::new();
+        for (key_id, key) in &self.keys {
+            if key_id.len() > u8::max_value() as usize {
+                return Err(BuildError::KeyIdTooLong {
+                    key_id: key_id.clone(),
+                });
+            }
+            let key = base64::decode(key).with_context(|| InvalidKey {
+                key_id: key_id.clone(),
+            })?;
+            if key.len() != KEY_LEN {
+                return Err(BuildError::InvalidKeyLength {
+                    key_id: key_id.clone(),
+                    len: key.len(),
+                });
+            }
+            keys.insert(key_id.clone(), key);
+        }
+
+        Ok(Encryption {
+            key_id: self.key_id.clone(),
+            keys,
+        })
+    }
+}
+
+pub struct Encryption {
+    key_id: String,
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryption")
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl Encryption {
+    /// Appends `[key id length][key id][nonce][tag][ciphertext]` to `out`.
+    /// Every record gets a random nonce, and the key id is authenticated
+    /// along with the ciphertext.
+    pub fn encrypt(&self, plaintext: &[u8], out: &mut Vec<u8>) {
+        let key = &self.keys[&self.key_id];
+        let mut nonce = [0; NONCE_LEN];
+        let mut tag = [0; TAG_LEN];
+        rand_bytes(&mut nonce).expect("Unable to generate nonce");
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(&nonce),
+            self.key_id.as_bytes(),
+            plaintext,
+            &mut tag,
+        )
+        .expect("AES-GCM encryption with a valid key can't fail");
+
+        out.push(self.key_id.len() as u8);
+        out.extend_from_slice(self.key_id.as_bytes());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&tag);
+        out.extend_from_slice(&ciphertext);
+    }
+
+    pub fn decrypt(&self, record: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        let key_id = key_id(record)?;
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| DecryptError::UnknownKey {
+                key_id: key_id.to_owned(),
+            })?;
+
+        let rest = &record[1 + key_id.len()..];
+        if rest.len() < NONCE_LEN + TAG_LEN {
+            return Err(DecryptError::Truncated);
+        }
+        let (nonce, rest) = rest.split_at(NONCE_LEN);
+        let (tag, ciphertext) = rest.split_at(TAG_LEN);
+        decrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            key_id.as_bytes(),
+            ciphertext,
+            tag,
+        )
+        .context(Decrypt)
+    }
+
+    /// Whether records encrypted with `key_id` can be decrypted.
+    pub fn has_key(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+}
+
+/// The id of the key an encrypted record was encrypted with.
+pub fn key_id(record: &[u8]) -> Result<&str, DecryptError> {
+    let len = *record.first().ok_or(DecryptError::Truncated)? as usize;
+    let key_id = record.get(1..1 + len).ok_or(DecryptError::Truncated)?;
+    // Key ids are written from strings, so anything else is corruption.
+    std::str::from_utf8(key_id).map_err(|_| DecryptError::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(key_id: &str, keys: &[(&str, &[u8])]) -> EncryptionConfig {
+        EncryptionConfig {
+            key_id: key_id.into(),
+            keys: keys
+                .iter()
+                .map(|(id, key)| (id.to_string(), base64::encode(key)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn encryption_round_trips_across_key_rotation() {
+        let old = config("old", &[("old", &[1; 32])]).build().unwrap();
+        let mut record = vec![];
+        old.encrypt(b"sensitive", &mut record);
+        assert!(!record.windows(9).any(|window| window == b"sensitive"));
+        assert_eq!(key_id(&record).unwrap(), "old");
+
+        let rotated = config("new", &[("old", &[1; 32]), ("new", &[2; 32])])
+            .build()
+            .unwrap();
+        assert_eq!(rotated.decrypt(&record).unwrap(), b"sensitive");
+
+        let removed = config("new", &[("new", &[2; 32])]).build().unwrap();
+        assert!(matches!(
+            removed.decrypt(&record),
+            Err(DecryptError::UnknownKey { .. })
+        ));
+
+        let last = record.len() - 1;
+        record[last] ^= 0xff;
+        assert!(matches!(
+            rotated.decrypt(&record),
+            Err(DecryptError::Decrypt { .. })
+        ));
+    }
+
+    #[test]
+    fn encryption_rejects_invalid_keys() {
+        assert!(config("missing", &[("key", &[1; 32])]).build().is_err());
+        assert!(config("key", &[("key", &[1; 16])]).build().is_err());
+
+        let mut config = config("key", &[("key", &[1; 32])]);
+        config.keys.insert("key".into(), "not base64!".into());
+        assert!(config.build().is_err());
+    }
+}
@@ -9,26 +9,48 @@ use std::sync::{
 
 #[cfg(feature = "leveldb")]
 mod disk;
+#[cfg(feature = "leveldb")]
+mod encryption;
+
+#[cfg(feature = "leveldb")]
+pub use disk::{Snapshot, SnapshotRecord};
+#[cfg(feature = "leveldb")]
+pub use encryption::EncryptionConfig;
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum BufferConfig {
+    /// A channel holding up to `max_events` events. Once full, the sink's
+    /// inputs block or drop events according to `when_full`.
     Memory {
+        #[serde(default = "default_max_events")]
         max_events: usize,
+        #[serde(default)]
         when_full: WhenFull,
     },
+    /// A leveldb database holding up to `max_size` bytes of events, which
+    /// survives restarts and crashes. With `sync_writes`, every write is
+    /// synced to disk, so it survives an OS crash or power loss too. With
+    /// `encryption`, events are encrypted before they're written.
     #[cfg(feature = "leveldb")]
     Disk {
         max_size: usize,
         when_full: WhenFull,
+        #[serde(default)]
+        sync_writes: bool,
+        encryption: Option<EncryptionConfig>,
     },
 }
 
+fn default_max_events() -> usize {
+    500
+}
+
 impl Default for BufferConfig {
     fn default() -> Self {
         BufferConfig::Memory {
-            max_events: 500,
+            max_events: default_max_events(),
             when_full: Default::default(),
         }
     }
@@ -98,6 +120,10 @@ impl BufferConfig {
                 max_events,
                 when_full,
             } => {
+                if *max_events == 0 {
+                    return Err("Memory buffer max_events must be greater than 0.".into());
+                }
+
                 let (tx, rx) = mpsc::channel(*max_events);
                 let tx = BufferInputCloner::Memory(tx, *when_full);
                 let rx = Box::new(rx);
@@ -108,20 +134,73 @@ impl BufferConfig {
             BufferConfig::Disk {
                 max_size,
                 when_full,
+                sync_writes,
+                encryption,
             } => {
+                if *max_size == 0 {
+                    return Err("Disk buffer max_size must be greater than 0.".into());
+                }
+
                 let data_dir = data_dir
                     .as_ref()
                     .ok_or_else(|| "Must set data_dir to use on-disk buffering.".to_string())?;
-                let buffer_dir = format!("{}_buffer", sink_name);
+                let buffer_dir = disk_buffer_dir(sink_name);
 
-                let (tx, rx, acker) = disk::open(&data_dir, buffer_dir.as_ref(), *max_size)
+                let encryption = encryption
+                    .as_ref()
+                    .map(EncryptionConfig::build)
+                    .transpose()
                     .map_err(|err| err.to_string())?;
+
+                let (tx, rx, acker) = disk::open(
+                    &data_dir,
+                    buffer_dir.as_ref(),
+                    *max_size,
+                    *sync_writes,
+                    encryption,
+                )
+                .map_err(|err| err.to_string())?;
                 let tx = BufferInputCloner::Disk(tx, *when_full);
                 let rx = Box::new(rx);
                 Ok((tx, rx, acker))
             }
         }
     }
+
+    /// Copies the sink's disk buffer to read what's in it, leaving the buffer
+    /// itself as it is, even if a running Vector is using it.
+    #[cfg(feature = "leveldb")]
+    pub fn snapshot(
+        &self,
+        data_dir: &Option<PathBuf>,
+        sink_name: &str,
+    ) -> Result<Snapshot, String> {
+        match &self {
+            BufferConfig::Memory { .. } => Err("Sink doesn't have a disk buffer.".into()),
+            BufferConfig::Disk { encryption, .. } => {
+                let data_dir = data_dir
+                    .as_ref()
+                    .ok_or_else(|| "Must set data_dir to use on-disk buffering.".to_string())?;
+                let path = data_dir.join(disk_buffer_dir(sink_name));
+                if !path.is_dir() {
+                    return Err(format!("No disk buffer found at {:?}.", path));
+                }
+
+                let encryption = encryption
+                    .as_ref()
+                    .map(EncryptionConfig::build)
+                    .transpose()
+                    .map_err(|err| err.to_string())?;
+
+                Snapshot::open(&path, encryption).map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "leveldb")]
+fn disk_buffer_dir(sink_name: &str) -> String {
+    format!("{}_buffer", sink_name)
 }
 
 #[derive(Debug, Clone)]
@@ -187,8 +266,8 @@ impl<S: Sink> Sink for DropWhenFull<S> {
 
 #[cfg(test)]
 mod test {
-    use super::{Acker, DropWhenFull};
-    use crate::test_util::block_on;
+    use super::{Acker, BufferConfig, DropWhenFull, WhenFull};
+    use crate::{test_util::block_on, Event};
     use futures01::{future, sync::mpsc, task::AtomicTask, Async, AsyncSink, Sink, Stream};
     use std::sync::{atomic::AtomicUsize, Arc};
     use tokio01_test::task::MockTask;
@@ -215,6 +294,70 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn memory_buffer_config_defaults() {
+        let config: BufferConfig = toml::from_str(r#"type = "memory""#).unwrap();
+        match config {
+            BufferConfig::Memory {
+                max_events: 500,
+                when_full: WhenFull::Block,
+            } => (),
+            config => panic!("Unexpected config {:?}", config),
+        }
+
+        let config: BufferConfig = toml::from_str(
+            r#"
+            type = "memory"
+            max_events = 10
+            "#,
+        )
+        .unwrap();
+        match config {
+            BufferConfig::Memory { max_events: 10, .. } => (),
+            config => panic!("Unexpected config {:?}", config),
+        }
+    }
+
+    #[test]
+    fn memory_buffer_rejects_zero_capacity() {
+        let config = BufferConfig::Memory {
+            max_events: 0,
+            when_full: WhenFull::Block,
+        };
+        assert!(config.build(&None, "out").is_err());
+    }
+
+    #[test]
+    fn memory_buffer_blocks_at_capacity() {
+        let config = BufferConfig::Memory {
+            max_events: 2,
+            when_full: WhenFull::Block,
+        };
+        let (tx, mut rx, _acker) = config.build(&None, "out").unwrap();
+        let mut tx = tx.get();
+
+        let mut mock = MockTask::new();
+        mock.enter(|| {
+            // The channel holds one more event for each sender.
+            for _ in 0..3 {
+                assert!(tx.start_send(Event::from("hello")).unwrap().is_ready());
+            }
+            assert!(tx.start_send(Event::from("hello")).unwrap().is_not_ready());
+        });
+        assert!(!mock.is_notified());
+
+        block_on::<_, _, ()>(future::lazy(|| {
+            assert!(rx.poll().unwrap().is_ready());
+            future::ok(())
+        }))
+        .unwrap();
+        assert!(mock.is_notified());
+
+        mock.enter(|| {
+            assert!(tx.start_send(Event::from("hello")).unwrap().is_ready());
+        });
+    }
+
     #[test]
     fn ack_with_none() {
         let counter = Arc::new(AtomicUsize::new(0));
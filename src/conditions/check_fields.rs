@@ -1,6 +1,6 @@
 use crate::{
     conditions::{Condition, ConditionConfig, ConditionDescription},
-    event::Value,
+    event::{TraceEvent, Value},
     Event,
 };
 use indexmap::IndexMap;
@@ -49,23 +49,25 @@ impl EqualsPredicate {
 impl CheckFieldsPredicate for EqualsPredicate {
     fn check(&self, event: &Event) -> bool {
         match event {
-            Event::Log(l) => l.get(&self.target).map_or(false, |v| match &self.arg {
-                CheckFieldsPredicateArg::String(s) => s.as_bytes() == v.as_bytes(),
-                CheckFieldsPredicateArg::Integer(i) => match v {
-                    Value::Integer(vi) => *i == *vi,
-                    Value::Float(vf) => *i == *vf as i64,
-                    _ => false,
-                },
-                CheckFieldsPredicateArg::Float(f) => match v {
-                    Value::Float(vf) => *f == *vf,
-                    Value::Integer(vi) => *f == *vi as f64,
-                    _ => false,
-                },
-                CheckFieldsPredicateArg::Boolean(b) => match v {
-                    Value::Boolean(vb) => *b == *vb,
-                    _ => false,
-                },
-            }),
+            Event::Log(l) | Event::Trace(TraceEvent(l)) => {
+                l.get(&self.target).map_or(false, |v| match &self.arg {
+                    CheckFieldsPredicateArg::String(s) => s.as_bytes() == v.as_bytes(),
+                    CheckFieldsPredicateArg::Integer(i) => match v {
+                        Value::Integer(vi) => *i == *vi,
+                        Value::Float(vf) => *i == *vf as i64,
+                        _ => false,
+                    },
+                    CheckFieldsPredicateArg::Float(f) => match v {
+                        Value::Float(vf) => *f == *vf,
+                        Value::Integer(vi) => *f == *vi as f64,
+                        _ => false,
+                    },
+                    CheckFieldsPredicateArg::Boolean(b) => match v {
+                        Value::Boolean(vb) => *b == *vb,
+                        _ => false,
+                    },
+                })
+            }
             Event::Metric(m) => m
                 .tags
                 .as_ref()
@@ -104,7 +106,7 @@ impl ContainsPredicate {
 impl CheckFieldsPredicate for ContainsPredicate {
     fn check(&self, event: &Event) -> bool {
         match event {
-            Event::Log(l) => l
+            Event::Log(l) | Event::Trace(TraceEvent(l)) => l
                 .get(&self.target)
                 .map_or(false, |v| v.to_string_lossy().contains(&self.arg)),
             _ => false,
@@ -138,7 +140,7 @@ impl StartsWithPredicate {
 impl CheckFieldsPredicate for StartsWithPredicate {
     fn check(&self, event: &Event) -> bool {
         match event {
-            Event::Log(l) => l
+            Event::Log(l) | Event::Trace(TraceEvent(l)) => l
                 .get(&self.target)
                 .map_or(false, |v| v.to_string_lossy().starts_with(&self.arg)),
             _ => false,
@@ -172,7 +174,7 @@ impl EndsWithPredicate {
 impl CheckFieldsPredicate for EndsWithPredicate {
     fn check(&self, event: &Event) -> bool {
         match event {
-            Event::Log(l) => l
+            Event::Log(l) | Event::Trace(TraceEvent(l)) => l
                 .get(&self.target)
                 .map_or(false, |v| v.to_string_lossy().ends_with(&self.arg)),
             _ => false,
@@ -208,7 +210,7 @@ impl NotEqualsPredicate {
 impl CheckFieldsPredicate for NotEqualsPredicate {
     fn check(&self, event: &Event) -> bool {
         match event {
-            Event::Log(l) => l
+            Event::Log(l) | Event::Trace(TraceEvent(l)) => l
                 .get(&self.target)
                 .map(|f| f.as_bytes())
                 .map_or(false, |b| b != self.arg.as_bytes()),
@@ -248,7 +250,7 @@ impl RegexPredicate {
 impl CheckFieldsPredicate for RegexPredicate {
     fn check(&self, event: &Event) -> bool {
         match event {
-            Event::Log(log) => log
+            Event::Log(log) | Event::Trace(TraceEvent(log)) => log
                 .get(&self.target)
                 .map(|field| field.to_string_lossy())
                 .map_or(false, |field| self.regex.is_match(&field)),
@@ -287,7 +289,7 @@ impl ExistsPredicate {
 impl CheckFieldsPredicate for ExistsPredicate {
     fn check(&self, event: &Event) -> bool {
         (match event {
-            Event::Log(l) => l.get(&self.target).is_some(),
+            Event::Log(l) | Event::Trace(TraceEvent(l)) => l.get(&self.target).is_some(),
             Event::Metric(m) => m
                 .tags
                 .as_ref()
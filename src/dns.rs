@@ -1,15 +1,19 @@
 use crate::runtime::TaskExecutor;
-use futures01::{future, Future};
+use futures01::{future, Async, Future, Poll};
 use hyper::client::connect::dns::{Name, Resolve};
+use serde::{Deserialize, Serialize};
 use snafu::{futures01::FutureExt, ResultExt};
 use std::{
-    fmt,
+    collections::{HashMap, VecDeque},
+    fmt, io,
     net::{IpAddr, SocketAddr},
     str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+use tokio01::timer::Delay;
 use trust_dns_resolver::{
     config::{LookupIpStrategy, NameServerConfig, Protocol, ResolverConfig, ResolverOpts},
-    lookup_ip::LookupIpIntoIter,
     system_conf, AsyncResolver,
 };
 
@@ -18,19 +22,89 @@ const DNS_PORT: u16 = 53;
 
 pub type ResolverFuture = Box<dyn Future<Item = LookupIp, Error = DnsError> + Send + 'static>;
 
+pub type BackendFuture = Box<dyn Future<Item = Resolved, Error = DnsError> + Send + 'static>;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Options {
+    /// Caps how long answers are cached for, however long their records'
+    /// TTLs are. `None` caches them for as long as their TTLs.
+    pub cache_max_ttl_secs: Option<u64>,
+    /// How long failed lookups are cached for. Kept short, so a name that
+    /// starts resolving again is picked up quickly.
+    pub negative_ttl_secs: u64,
+    /// How long a connection attempt to one address gets before one to the
+    /// next address is started alongside it, per RFC 8305.
+    pub happy_eyeballs_delay_ms: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            cache_max_ttl_secs: None,
+            negative_ttl_secs: 1,
+            happy_eyeballs_delay_ms: 250,
+        }
+    }
+}
+
+/// What a lookup resolved to, and until when it may be used.
 #[derive(Debug, Clone)]
+pub struct Resolved {
+    pub addrs: Vec<IpAddr>,
+    pub valid_until: Instant,
+}
+
+/// Looks names up, for the `Resolver` to cache.
+pub trait Backend: Send + Sync {
+    fn lookup(&self, name: &str) -> BackendFuture;
+}
+
+impl Backend for AsyncResolver {
+    fn lookup(&self, name: &str) -> BackendFuture {
+        Box::new(
+            self.lookup_ip(name)
+                .context(UnableLookup)
+                .map(|lookup| Resolved {
+                    addrs: lookup.iter().collect(),
+                    valid_until: lookup.valid_until(),
+                }),
+        )
+    }
+}
+
+/// Resolves names through a `Backend`, caching answers for as long as their
+/// TTLs allow, so endpoints moving to other addresses are picked up without
+/// a restart, and failures for a short while.
+#[derive(Clone)]
 pub struct Resolver {
-    inner: AsyncResolver,
+    backend: Arc<dyn Backend>,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    options: Options,
+}
+
+#[derive(Clone)]
+struct CacheEntry {
+    result: Result<Vec<IpAddr>, String>,
+    expires: Instant,
 }
 
 pub enum LookupIp {
     Single(Option<IpAddr>),
-    Query(LookupIpIntoIter),
+    Addrs(std::vec::IntoIter<IpAddr>),
 }
 
 impl Resolver {
     pub fn new(dns_servers: Vec<String>, exec: TaskExecutor) -> Result<Self, DnsError> {
-        let (config, opt) = if !dns_servers.is_empty() {
+        Self::with_options(dns_servers, Options::default(), exec)
+    }
+
+    pub fn with_options(
+        dns_servers: Vec<String>,
+        options: Options,
+        exec: TaskExecutor,
+    ) -> Result<Self, DnsError> {
+        let (config, mut opt) = if !dns_servers.is_empty() {
             let mut config = ResolverConfig::new();
 
             let mut errors = Vec::new();
@@ -73,24 +147,82 @@ impl Resolver {
             res
         };
 
+        // Answers are cached here, so trust-dns' own cache mustn't hold on
+        // to them any longer.
+        opt.positive_max_ttl = options.cache_max_ttl_secs.map(Duration::from_secs);
+        opt.negative_max_ttl = Some(Duration::from_secs(options.negative_ttl_secs));
+
         let (inner, bg_task) = AsyncResolver::new(config, opt);
 
         exec.spawn(bg_task);
 
-        Ok(Self { inner })
+        Ok(Self::with_backend(inner, options))
+    }
+
+    pub fn with_backend(backend: impl Backend + 'static, options: Options) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            options,
+        }
+    }
+
+    /// How long to give a connection attempt before racing the next address
+    /// against it, see `HappyEyeballs`.
+    pub fn happy_eyeballs_delay(&self) -> Duration {
+        Duration::from_millis(self.options.happy_eyeballs_delay_ms)
     }
 
     pub fn lookup_ip(&self, name: impl AsRef<str>) -> ResolverFuture {
-        if let Ok(ip) = IpAddr::from_str(name.as_ref()) {
+        let name = name.as_ref();
+        if let Ok(ip) = IpAddr::from_str(name) {
             return Box::new(future::ok(LookupIp::Single(Some(ip))));
         }
 
-        Box::new(
-            self.inner
-                .lookup_ip(name.as_ref())
-                .context(UnableLookup)
-                .map(|lu| LookupIp::Query(lu.into_iter())),
-        )
+        let cached = self.cache.lock().unwrap().get(name).cloned();
+        match cached {
+            Some(entry) if entry.expires > Instant::now() => {
+                return Box::new(future::result(
+                    entry
+                        .result
+                        .map(|addrs| LookupIp::Addrs(addrs.into_iter()))
+                        .map_err(|message| DnsError::CachedFailure { message }),
+                ))
+            }
+            _ => (),
+        }
+
+        let cache = Arc::clone(&self.cache);
+        let name = name.to_owned();
+        let max_ttl = self.options.cache_max_ttl_secs.map(Duration::from_secs);
+        let negative_ttl = Duration::from_secs(self.options.negative_ttl_secs);
+        Box::new(self.backend.lookup(&name).then(move |result| {
+            let now = Instant::now();
+            let entry = match &result {
+                Ok(resolved) => CacheEntry {
+                    result: Ok(resolved.addrs.clone()),
+                    expires: match max_ttl {
+                        Some(max_ttl) => resolved.valid_until.min(now + max_ttl),
+                        None => resolved.valid_until,
+                    },
+                },
+                Err(error) => CacheEntry {
+                    result: Err(error.to_string()),
+                    expires: now + negative_ttl,
+                },
+            };
+            cache.lock().unwrap().insert(name, entry);
+
+            result.map(|resolved| LookupIp::Addrs(resolved.addrs.into_iter()))
+        }))
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver")
+            .field("options", &self.options)
+            .finish()
     }
 }
 
@@ -100,7 +232,7 @@ impl Iterator for LookupIp {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             LookupIp::Single(ip) => ip.take(),
-            LookupIp::Query(iter) => iter.next(),
+            LookupIp::Addrs(iter) => iter.next(),
         }
     }
 }
@@ -110,14 +242,126 @@ impl Resolve for Resolver {
     type Future = Box<dyn Future<Item = Self::Addrs, Error = std::io::Error> + Send + 'static>;
 
     fn resolve(&self, name: Name) -> Self::Future {
-        let fut = self.lookup_ip(name.as_str()).map_err(|e| {
-            use std::io;
-            io::Error::new(io::ErrorKind::Other, e)
-        });
+        let fut = self
+            .lookup_ip(name.as_str())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
         Box::new(fut)
     }
 }
 
+/// Orders addresses so that address families alternate, starting with the
+/// family of the first address, as RFC 8305 recommends.
+pub fn interleave(addrs: impl IntoIterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+    let addrs = addrs.into_iter().collect::<Vec<_>>();
+    let first_v6 = addrs.first().map_or(false, SocketAddr::is_ipv6);
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_v6);
+
+    let mut interleaved = Vec::with_capacity(first.len() + second.len());
+    loop {
+        match (first.pop_front(), second.pop_front()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Connects to the first of several addresses to accept, per RFC 8305.
+///
+/// Addresses are tried in the given order, with an attempt started every
+/// `delay` or as soon as the previous one fails, whichever is first. Earlier
+/// attempts keep going, and whichever succeeds first wins, so a dual-stack
+/// host with a broken address family is connected to after `delay` instead
+/// of after a connect timeout. The attempts that lost are dropped.
+pub struct HappyEyeballs<F, C> {
+    connect: F,
+    pending: VecDeque<SocketAddr>,
+    attempts: Vec<C>,
+    delay: Duration,
+    timer: Option<Delay>,
+    last_error: Option<io::Error>,
+}
+
+impl<F, C> HappyEyeballs<F, C>
+where
+    F: FnMut(&SocketAddr) -> C,
+    C: Future<Error = io::Error>,
+{
+    pub fn new(addrs: impl IntoIterator<Item = SocketAddr>, delay: Duration, connect: F) -> Self {
+        Self {
+            connect,
+            pending: addrs.into_iter().collect(),
+            attempts: Vec::new(),
+            delay,
+            timer: None,
+            last_error: None,
+        }
+    }
+
+    fn start_next(&mut self) {
+        if let Some(addr) = self.pending.pop_front() {
+            debug!(message = "connecting.", %addr);
+            self.attempts.push((self.connect)(&addr));
+            self.timer = Some(Delay::new(Instant::now() + self.delay));
+        }
+    }
+}
+
+impl<F, C> Future for HappyEyeballs<F, C>
+where
+    F: FnMut(&SocketAddr) -> C,
+    C: Future<Error = io::Error>,
+{
+    type Item = C::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.attempts.is_empty() && self.last_error.is_none() {
+            self.start_next();
+        }
+
+        loop {
+            let mut failed = false;
+            let mut index = 0;
+            while index < self.attempts.len() {
+                match self.attempts[index].poll() {
+                    Ok(Async::Ready(connection)) => return Ok(Async::Ready(connection)),
+                    Ok(Async::NotReady) => index += 1,
+                    Err(error) => {
+                        debug!(message = "connection attempt failed.", %error);
+                        self.attempts.swap_remove(index);
+                        self.last_error = Some(error);
+                        failed = true;
+                    }
+                }
+            }
+
+            // The timer can only fail if the runtime is shutting down, in
+            // which case moving on to the next address is harmless.
+            let elapsed = match &mut self.timer {
+                Some(timer) => !matches!(timer.poll(), Ok(Async::NotReady)),
+                None => false,
+            };
+
+            if self.pending.is_empty() {
+                if self.attempts.is_empty() {
+                    return Err(self.last_error.take().unwrap_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")
+                    }));
+                }
+                return Ok(Async::NotReady);
+            }
+
+            if failed || elapsed {
+                self.start_next();
+            } else {
+                return Ok(Async::NotReady);
+            }
+        }
+    }
+}
+
 #[derive(Debug, snafu::Snafu)]
 pub enum DnsError {
     #[snafu(display("Unable to parse dns servers: {}", errors.join(", ")))]
@@ -136,6 +380,8 @@ pub enum DnsError {
         #[snafu(source(from(trust_dns_resolver::error::ResolveError, ResolveError::from)))]
         source: ResolveError,
     },
+    #[snafu(display("Unable to resolve name, recently: {}", message))]
+    CachedFailure { message: String },
     #[snafu(display("Invalid dns name: {}", source))]
     InvalidName {
         #[snafu(source(from(trust_dns_proto::error::ProtoError, ProtoError::from)))]
@@ -181,14 +427,14 @@ impl From<trust_dns_proto::error::ProtoError> for ProtoError {
 
 #[cfg(test)]
 mod tests {
-    use super::Resolver;
+    use super::*;
     use crate::runtime::Runtime;
     use crate::test_util::{next_addr, runtime};
     use crate::topology::config::GlobalOptions;
     use std::collections::BTreeMap;
-    use std::net::{IpAddr, SocketAddr, UdpSocket};
-    use std::str::FromStr;
-    use tokio01::prelude::{future::poll_fn, Async};
+    use std::net::UdpSocket;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio01::prelude::future::poll_fn;
     use trust_dns::rr::{record_data::RData, LowerName, Name, RecordSet, RecordType, RrKey};
     use trust_dns_proto::rr::rdata::soa::SOA;
     use trust_dns_server::{
@@ -400,4 +646,203 @@ mod tests {
             Some(IpAddr::from_str("2001:0db8:85a3:0000:0000:8a2e:0370:7334").unwrap())
         );
     }
+
+    /// Answers lookups with `answer`, called with the number of earlier
+    /// lookups, and counts them.
+    #[derive(Clone)]
+    struct MockBackend {
+        lookups: Arc<AtomicUsize>,
+        answer: fn(usize) -> Result<(IpAddr, Duration), ()>,
+    }
+
+    impl MockBackend {
+        fn new(answer: fn(usize) -> Result<(IpAddr, Duration), ()>) -> Self {
+            Self {
+                lookups: Arc::new(AtomicUsize::new(0)),
+                answer,
+            }
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Backend for MockBackend {
+        fn lookup(&self, _name: &str) -> BackendFuture {
+            let count = self.lookups.fetch_add(1, Ordering::SeqCst);
+            Box::new(future::result(
+                (self.answer)(count)
+                    .map(|(ip, ttl)| Resolved {
+                        addrs: vec![ip],
+                        valid_until: Instant::now() + ttl,
+                    })
+                    .map_err(|()| DnsError::CachedFailure {
+                        message: "mock failure".into(),
+                    }),
+            ))
+        }
+    }
+
+    fn lookup(rt: &mut Runtime, resolver: &Resolver) -> Result<IpAddr, DnsError> {
+        rt.block_on(resolver.lookup_ip("endpoint.test"))
+            .map(|mut addrs| addrs.next().unwrap())
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([10, 0, 0, last])
+    }
+
+    #[test]
+    fn resolve_cache_expires_with_ttl() {
+        let mut rt = runtime();
+        // Each answer is a new address, valid for 200ms.
+        let backend = MockBackend::new(|count| Ok((ip(count as u8), Duration::from_millis(200))));
+        let resolver = Resolver::with_backend(backend.clone(), Options::default());
+
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(0));
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(0));
+        assert_eq!(backend.lookups(), 1);
+
+        std::thread::sleep(Duration::from_millis(300));
+
+        // The endpoint moved, and is found at its new address.
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(1));
+        assert_eq!(backend.lookups(), 2);
+    }
+
+    #[test]
+    fn resolve_cache_max_ttl() {
+        let mut rt = runtime();
+        let backend = MockBackend::new(|count| Ok((ip(count as u8), Duration::from_secs(3600))));
+        let options = Options {
+            cache_max_ttl_secs: Some(0),
+            ..Options::default()
+        };
+        let resolver = Resolver::with_backend(backend.clone(), options);
+
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(0));
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(1));
+        assert_eq!(backend.lookups(), 2);
+    }
+
+    #[test]
+    fn resolve_cache_failures_briefly() {
+        let mut rt = runtime();
+        // Fails once, then resolves.
+        let backend = MockBackend::new(|count| match count {
+            0 => Err(()),
+            _ => Ok((ip(1), Duration::from_secs(3600))),
+        });
+        let resolver = Resolver::with_backend(backend.clone(), Options::default());
+
+        assert!(lookup(&mut rt, &resolver).is_err());
+        assert!(lookup(&mut rt, &resolver).is_err());
+        assert_eq!(backend.lookups(), 1);
+
+        std::thread::sleep(Duration::from_millis(1100));
+
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(1));
+        assert_eq!(lookup(&mut rt, &resolver).unwrap(), ip(1));
+        assert_eq!(backend.lookups(), 2);
+    }
+
+    #[test]
+    fn resolve_literals_skip_backend() {
+        let mut rt = runtime();
+        let backend = MockBackend::new(|_| Err(()));
+        let resolver = Resolver::with_backend(backend.clone(), Options::default());
+
+        let mut res = rt.block_on(resolver.lookup_ip("10.0.0.1")).unwrap();
+        assert_eq!(res.next(), Some(ip(1)));
+        assert_eq!(backend.lookups(), 0);
+    }
+
+    fn v4(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::from([127, 0, 0, 1]), port)
+    }
+
+    fn v6(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]), port)
+    }
+
+    type Attempt = Box<dyn Future<Item = SocketAddr, Error = io::Error> + Send>;
+
+    #[test]
+    fn happy_eyeballs_interleaves_families() {
+        assert_eq!(
+            interleave(vec![v6(1), v6(2), v6(3), v4(4), v4(5)]),
+            vec![v6(1), v4(4), v6(2), v4(5), v6(3)]
+        );
+        assert_eq!(
+            interleave(vec![v4(1), v6(2), v4(3)]),
+            vec![v4(1), v6(2), v4(3)]
+        );
+        assert_eq!(interleave(vec![]), vec![]);
+    }
+
+    #[test]
+    fn happy_eyeballs_races_after_delay() {
+        let mut rt = runtime();
+        let delay = Duration::from_millis(100);
+        // IPv6 hangs, like a black holed route would, while IPv4 connects.
+        let connect = |addr: &SocketAddr| -> Attempt {
+            if addr.is_ipv6() {
+                Box::new(future::empty())
+            } else {
+                Box::new(future::ok(*addr))
+            }
+        };
+
+        let start = Instant::now();
+        let connected = rt
+            .block_on(HappyEyeballs::new(vec![v6(1), v4(2)], delay, connect))
+            .unwrap();
+
+        assert_eq!(connected, v4(2));
+        assert!(start.elapsed() >= delay);
+    }
+
+    #[test]
+    fn happy_eyeballs_prefers_first_address() {
+        let mut rt = runtime();
+        let connect = |addr: &SocketAddr| -> Attempt { Box::new(future::ok(*addr)) };
+
+        let connected = rt
+            .block_on(HappyEyeballs::new(
+                vec![v6(1), v4(2)],
+                Duration::from_secs(10),
+                connect,
+            ))
+            .unwrap();
+
+        assert_eq!(connected, v6(1));
+    }
+
+    #[test]
+    fn happy_eyeballs_moves_on_after_failure() {
+        let mut rt = runtime();
+        let delay = Duration::from_secs(10);
+        let connect = |addr: &SocketAddr| -> Attempt {
+            if addr.is_ipv6() {
+                Box::new(future::err(io::ErrorKind::ConnectionRefused.into()))
+            } else {
+                Box::new(future::ok(*addr))
+            }
+        };
+
+        let start = Instant::now();
+        let connected = rt
+            .block_on(HappyEyeballs::new(vec![v6(1), v4(2)], delay, connect))
+            .unwrap();
+
+        // Didn't wait for the delay once the first attempt failed.
+        assert_eq!(connected, v4(2));
+        assert!(start.elapsed() < delay);
+
+        let error = rt
+            .block_on(HappyEyeballs::new(vec![v6(1), v6(2)], delay, connect))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
 }
@@ -0,0 +1,195 @@
+//! Framing of the events exchanged by the `vector` sink and source.
+//!
+//! Frames are length delimited by the transport. Since protocol version 2
+//! the payload starts with a two byte header, the protocol version followed
+//! by flags, and the protobuf encoded `EventWrapper`, gzip compressed when
+//! the flag is set. Version 1 frames have no header, they are recognized by
+//! their first byte: a protobuf tag never encodes field number zero, so it
+//! can't be lower than 8.
+
+use super::{proto, Event};
+use bytes::{BufMut, Bytes, BytesMut};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::io::{self, Read, Write};
+
+/// The latest protocol version, the one sent by default.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+const FLAG_GZIP: u8 = 0b0000_0001;
+const KNOWN_FLAGS: u8 = FLAG_GZIP;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Derivative)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[derivative(Default)]
+    None,
+    Gzip,
+}
+
+#[derive(Debug, Snafu)]
+pub enum FrameError {
+    #[snafu(display(
+        "Unsupported protocol version {}, the highest supported version is {}. Is the sending Vector newer than this one?",
+        version,
+        PROTOCOL_VERSION
+    ))]
+    UnsupportedVersion { version: u8 },
+    #[snafu(display("Unknown frame flags {:#010b}", flags))]
+    UnknownFlags { flags: u8 },
+    #[snafu(display("Frame is too short to contain a header"))]
+    MissingHeader,
+    #[snafu(display("Failed to decompress frame: {}", source))]
+    Decompress { source: io::Error },
+    #[snafu(display("Failed to decode protobuf message: {}", source))]
+    Decode { source: prost::DecodeError },
+}
+
+/// Encodes the payload of a frame, without its length prefix.
+pub fn encode(event: Event, version: u8, compression: Compression) -> io::Result<Bytes> {
+    let event = proto::EventWrapper::from(event);
+
+    if version < 2 {
+        let mut out = BytesMut::with_capacity(event.encoded_len());
+        event.encode(&mut out).expect("buffer has enough capacity");
+        return Ok(out.freeze());
+    }
+
+    let mut encoded = Vec::with_capacity(event.encoded_len());
+    event.encode(&mut encoded).expect("vec grows as needed");
+
+    let (flags, body) = match compression {
+        Compression::None => (0, encoded),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&encoded)?;
+            (FLAG_GZIP, encoder.finish()?)
+        }
+    };
+
+    let mut out = BytesMut::with_capacity(body.len() + 2);
+    out.put_u8(version);
+    out.put_u8(flags);
+    out.put_slice(&body);
+    Ok(out.freeze())
+}
+
+/// Decodes the payload of a frame sent with any supported protocol version.
+pub fn decode(frame: &[u8]) -> Result<Event, FrameError> {
+    let event = match frame.first() {
+        Some(&version) if version < 8 => {
+            if version > PROTOCOL_VERSION || version < 2 {
+                return Err(FrameError::UnsupportedVersion { version });
+            }
+            let flags = *frame.get(1).ok_or(FrameError::MissingHeader)?;
+            if flags & !KNOWN_FLAGS != 0 {
+                return Err(FrameError::UnknownFlags { flags });
+            }
+
+            let body = &frame[2..];
+            if flags & FLAG_GZIP != 0 {
+                let mut decompressed = Vec::new();
+                GzDecoder::new(body)
+                    .read_to_end(&mut decompressed)
+                    .context(Decompress)?;
+                proto::EventWrapper::decode(&decompressed[..]).context(Decode)?
+            } else {
+                proto::EventWrapper::decode(body).context(Decode)?
+            }
+        }
+        // Version 1, the frame is the protobuf message.
+        _ => proto::EventWrapper::decode(frame).context(Decode)?,
+    };
+
+    Ok(Event::from(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{
+        metric::{Metric, MetricKind, MetricValue},
+        Value,
+    };
+    use chrono::{TimeZone, Utc};
+
+    fn nested_log() -> Event {
+        let mut event = Event::from("hello world");
+        let log = event.as_mut_log();
+        log.insert("bytes", "value");
+        log.insert("integer", 42);
+        log.insert("float", 1.5);
+        log.insert("boolean", true);
+        log.insert("null", Value::Null);
+        log.insert(
+            "timestamp",
+            Utc.ymd(2020, 10, 15).and_hms_nano(12, 0, 0, 11),
+        );
+        log.insert("map.nested.deeply", "value");
+        log.insert("array[0]", 1);
+        log.insert("array[1].key", "value");
+        event
+    }
+
+    fn metric() -> Event {
+        Event::Metric(Metric {
+            name: "requests".into(),
+            timestamp: Some(Utc.ymd(2020, 10, 15).and_hms(12, 0, 0)),
+            tags: Some(
+                vec![("host".to_owned(), "a".to_owned())]
+                    .into_iter()
+                    .collect(),
+            ),
+            kind: MetricKind::Incremental,
+            value: MetricValue::AggregatedHistogram {
+                buckets: vec![1.0, 2.0],
+                counts: vec![3, 4],
+                count: 7,
+                sum: 10.5,
+            },
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        for compression in &[Compression::None, Compression::Gzip] {
+            for event in vec![nested_log(), metric()] {
+                let frame = encode(event.clone(), PROTOCOL_VERSION, *compression).unwrap();
+                assert_eq!(frame[0], PROTOCOL_VERSION);
+                assert_eq!(decode(&frame).unwrap(), event);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_version_1() {
+        let event = nested_log();
+        let frame = encode(event.clone(), 1, Compression::None).unwrap();
+        assert!(frame[0] >= 8);
+        assert_eq!(decode(&frame).unwrap(), event);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let error = decode(&[PROTOCOL_VERSION + 1, 0, 1, 2]).unwrap_err();
+        match error {
+            FrameError::UnsupportedVersion { version } => assert_eq!(version, PROTOCOL_VERSION + 1),
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        let error = decode(&[PROTOCOL_VERSION, 0b1000_0000]).unwrap_err();
+        assert!(matches!(error, FrameError::UnknownFlags { .. }));
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        let error = decode(&[PROTOCOL_VERSION]).unwrap_err();
+        assert!(matches!(error, FrameError::MissingHeader));
+    }
+}
@@ -0,0 +1,90 @@
+use super::Value;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Key under which the name of the source that produced an event is encoded
+/// along with the other metadata.
+pub const SOURCE_ID: &str = "source_id";
+
+/// Key under which sources that decode events from what they receive, like
+/// syslog lines or JSON, record the size of what an event was decoded from,
+/// in bytes. The topology takes it out as the event leaves the source.
+pub const RECEIVED_BYTES: &str = "received_bytes";
+
+/// Key under which the topology records when an event left its source, as a
+/// timestamp.
+pub const INGESTED_AT: &str = "ingested_at";
+
+/// Pipeline context carried alongside an event's fields.
+///
+/// Metadata is kept apart from the event data so it never collides with user
+/// fields and is not encoded by sinks, but it survives transforms and
+/// buffering just like the fields do.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct EventMetadata {
+    fields: BTreeMap<String, Value>,
+    /// The name of the source that produced the event, as recorded by the
+    /// topology. It is shared by all events of a source, so recording it
+    /// doesn't allocate.
+    source_id: Option<Arc<str>>,
+}
+
+impl EventMetadata {
+    pub fn source_id(&self) -> Option<&str> {
+        self.source_id.as_deref()
+    }
+
+    pub fn set_source_id(&mut self, source_id: Arc<str>) {
+        self.source_id = Some(source_id);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<Value>
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.fields.insert(key.into(), value.into())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.fields.remove(key)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.fields.contains_key(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.source_id.is_none()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.fields.iter()
+    }
+}
+
+impl From<BTreeMap<String, Value>> for EventMetadata {
+    fn from(mut fields: BTreeMap<String, Value>) -> Self {
+        let source_id = fields
+            .remove(SOURCE_ID)
+            .map(|source_id| Arc::from(source_id.to_string_lossy()));
+        Self { fields, source_id }
+    }
+}
+
+/// Yields the source id under `SOURCE_ID` along with the other fields.
+impl IntoIterator for EventMetadata {
+    type Item = (String, Value);
+    type IntoIter = std::collections::btree_map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut fields = self.fields;
+        if let Some(source_id) = self.source_id {
+            fields.insert(SOURCE_ID.to_owned(), Value::from(&*source_id));
+        }
+        fields.into_iter()
+    }
+}
@@ -11,12 +11,18 @@ use std::{collections::BTreeMap, iter::FromIterator};
 use string_cache::DefaultAtom as Atom;
 
 pub mod discriminant;
+pub mod frame;
 pub mod merge;
 pub mod merge_state;
+pub mod metadata;
 pub mod metric;
+pub mod sample_rate;
+pub mod trace;
 mod util;
 
+pub use metadata::EventMetadata;
 pub use metric::Metric;
+pub use trace::TraceEvent;
 pub(crate) use util::log::PathComponent;
 #[cfg(feature = "transforms-grok_parser")]
 pub(crate) use util::log::PathIter;
@@ -36,6 +42,7 @@ lazy_static! {
         kubernetes_key: Atom::from("kubernetes"),
         source_key: Atom::from("source"),
         source_type_key: Atom::from("source_type"),
+        redacted_keys: Vec::new(),
     };
 }
 
@@ -43,11 +50,13 @@ lazy_static! {
 pub enum Event {
     Log(LogEvent),
     Metric(Metric),
+    Trace(TraceEvent),
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct LogEvent {
     fields: BTreeMap<String, Value>,
+    metadata: EventMetadata,
 }
 
 impl Event {
@@ -96,15 +105,45 @@ impl Event {
             _ => panic!("failed type coercion, {:?} is not a metric", self),
         }
     }
+
+    pub fn as_trace(&self) -> &TraceEvent {
+        match self {
+            Event::Trace(trace) => trace,
+            _ => panic!("failed type coercion, {:?} is not a trace event", self),
+        }
+    }
+
+    pub fn as_mut_trace(&mut self) -> &mut TraceEvent {
+        match self {
+            Event::Trace(trace) => trace,
+            _ => panic!("failed type coercion, {:?} is not a trace event", self),
+        }
+    }
+
+    pub fn into_trace(self) -> TraceEvent {
+        match self {
+            Event::Trace(trace) => trace,
+            _ => panic!("failed type coercion, {:?} is not a trace event", self),
+        }
+    }
 }
 
 impl LogEvent {
     pub fn new() -> Self {
         Self {
             fields: BTreeMap::new(),
+            metadata: EventMetadata::default(),
         }
     }
 
+    pub fn metadata(&self) -> &EventMetadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut EventMetadata {
+        &mut self.metadata
+    }
+
     pub fn get(&self, key: &Atom) -> Option<&Value> {
         util::log::get(&self.fields, key)
     }
@@ -156,11 +195,26 @@ impl LogEvent {
         util::log::all_fields(&self.fields)
     }
 
+    /// The values of the top level fields, for changing them in place.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.fields.values_mut()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.fields.is_empty()
     }
 }
 
+/// Log events are compared by their fields only, the metadata describes how
+/// the event travelled through the pipeline rather than the event itself.
+/// Tests that care about the metadata too use
+/// `test_util::assert_event_eq_with_metadata`.
+impl PartialEq for LogEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields
+    }
+}
+
 impl std::ops::Index<&Atom> for LogEvent {
     type Output = Value;
 
@@ -186,7 +240,8 @@ impl<K: Into<Atom>, V: Into<Value>> FromIterator<(K, V)> for LogEvent {
     }
 }
 
-/// Converts event into an iterator over top-level key/value pairs.
+/// Converts event into an iterator over top-level key/value pairs. Metadata
+/// is not included.
 impl IntoIterator for LogEvent {
     type Item = (String, Value);
     type IntoIter = std::collections::btree_map::IntoIter<String, Value>;
@@ -229,6 +284,11 @@ pub struct LogSchema {
     #[serde(default = "LogSchema::default_source_type_key")]
     #[getset(get = "pub", set = "pub(crate)")]
     source_type_key: Atom,
+    /// Fields whose values are hidden wherever events are shown for
+    /// debugging, like by the tap API.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[getset(get = "pub", set = "pub(crate)")]
+    redacted_keys: Vec<Atom>,
 }
 
 impl Default for LogSchema {
@@ -240,11 +300,35 @@ impl Default for LogSchema {
             kubernetes_key: Atom::from("kubernetes"),
             source_key: Atom::from("source"),
             source_type_key: Atom::from("source_type"),
+            redacted_keys: Vec::new(),
         }
     }
 }
 
 impl LogSchema {
+    /// Ensures none of the canonical keys were configured as empty strings,
+    /// which would make sources write to and sinks read from the event root.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        for (name, key) in &[
+            ("message_key", &self.message_key),
+            ("timestamp_key", &self.timestamp_key),
+            ("host_key", &self.host_key),
+            ("source_key", &self.source_key),
+            ("source_type_key", &self.source_type_key),
+        ] {
+            if key.trim().is_empty() {
+                errors.push(format!("log_schema.{} must not be empty", name));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     fn default_message_key() -> Atom {
         Atom::from("message")
     }
@@ -502,15 +586,8 @@ impl From<proto::EventWrapper> for Event {
         let event = proto.event.unwrap();
 
         match event {
-            EventProto::Log(proto) => {
-                let fields = proto
-                    .fields
-                    .into_iter()
-                    .filter_map(|(k, v)| decode_value(v).map(|value| (k, value)))
-                    .collect::<BTreeMap<_, _>>();
-
-                Event::Log(LogEvent { fields })
-            }
+            EventProto::Log(proto) => Event::Log(decode_log(proto)),
+            EventProto::Trace(proto) => Event::Trace(TraceEvent(decode_log(proto))),
             EventProto::Metric(proto) => {
                 let kind = match proto.kind() {
                     proto::metric::Kind::Incremental => MetricKind::Incremental,
@@ -567,6 +644,35 @@ impl From<proto::EventWrapper> for Event {
     }
 }
 
+fn decode_log(proto: Log) -> LogEvent {
+    let fields = proto
+        .fields
+        .into_iter()
+        .filter_map(|(k, v)| decode_value(v).map(|value| (k, value)))
+        .collect::<BTreeMap<_, _>>();
+    let metadata = proto
+        .metadata
+        .into_iter()
+        .filter_map(|(k, v)| decode_value(v).map(|value| (k, value)))
+        .collect::<BTreeMap<_, _>>()
+        .into();
+
+    LogEvent { fields, metadata }
+}
+
+fn encode_log(LogEvent { fields, metadata }: LogEvent) -> Log {
+    let fields = fields
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), encode_value(v)))
+        .collect::<BTreeMap<_, _>>();
+    let metadata = metadata
+        .into_iter()
+        .map(|(k, v)| (k, encode_value(v)))
+        .collect::<BTreeMap<_, _>>();
+
+    Log { fields, metadata }
+}
+
 fn encode_value(value: Value) -> proto::Value {
     proto::Value {
         kind: match value {
@@ -603,14 +709,12 @@ fn encode_array(items: Vec<Value>) -> proto::ValueArray {
 impl From<Event> for proto::EventWrapper {
     fn from(event: Event) -> Self {
         match event {
-            Event::Log(LogEvent { fields }) => {
-                let fields = fields
-                    .into_iter()
-                    .map(|(k, v)| (k.to_string(), encode_value(v)))
-                    .collect::<BTreeMap<_, _>>();
-
-                let event = EventProto::Log(Log { fields });
-
+            Event::Log(log) => {
+                let event = EventProto::Log(encode_log(log));
+                proto::EventWrapper { event: Some(event) }
+            }
+            Event::Trace(TraceEvent(log)) => {
+                let event = EventProto::Trace(encode_log(log));
                 proto::EventWrapper { event: Some(event) }
             }
             Event::Metric(Metric {
@@ -700,9 +804,7 @@ impl From<Event> for Vec<u8> {
 
 impl From<Bytes> for Event {
     fn from(message: Bytes) -> Self {
-        let mut event = Event::Log(LogEvent {
-            fields: BTreeMap::new(),
-        });
+        let mut event = Event::new_empty_log();
 
         event
             .as_mut_log()
@@ -739,9 +841,15 @@ impl From<Metric> for Event {
     }
 }
 
+impl From<TraceEvent> for Event {
+    fn from(trace: TraceEvent) -> Self {
+        Event::Trace(trace)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Atom, Event, LogSchema, Value};
+    use super::{proto, Atom, Event, LogSchema, Value};
     use regex::Regex;
     use std::collections::HashSet;
 
@@ -765,6 +873,49 @@ mod test {
         assert!(rfc3339_re.is_match(actual_all.pointer("/timestamp").unwrap().as_str().unwrap()));
     }
 
+    #[test]
+    fn metadata_is_not_serialized() {
+        let mut event = Event::from("raw log line");
+        event.as_mut_log().metadata_mut().set_source_id("in".into());
+
+        let actual = serde_json::to_value(event.as_log()).unwrap();
+        assert!(actual.get("source_id").is_none());
+        assert_eq!(event.as_log().keys().count(), 2);
+        assert!(event.as_log().get(&Atom::from("source_id")).is_none());
+    }
+
+    #[test]
+    fn metadata_survives_proto_roundtrip() {
+        let mut event = Event::from("raw log line");
+        event.as_mut_log().metadata_mut().set_source_id("in".into());
+        event.as_mut_log().metadata_mut().insert("sample_rate", 10);
+
+        let decoded = Event::from(proto::EventWrapper::from(event.clone()));
+        crate::test_util::assert_event_eq_with_metadata(&decoded, &event);
+        assert_eq!(decoded.as_log().metadata().source_id(), Some("in"));
+    }
+
+    #[test]
+    fn log_schema_validation() {
+        assert!(LogSchema::default().validate().is_ok());
+
+        let schema: LogSchema = toml::from_str(
+            r#"
+            message_key = ""
+            timestamp_key = "ts"
+            host_key = " "
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            schema.validate(),
+            Err(vec![
+                "log_schema.message_key must not be empty".to_owned(),
+                "log_schema.host_key must not be empty".to_owned(),
+            ])
+        );
+    }
+
     #[test]
     fn type_serialization() {
         use serde_json::json;
@@ -0,0 +1,107 @@
+//! Sample rates recorded on log events by sampling transforms.
+//!
+//! An event with a sample rate of `n` stands in for `n` events seen upstream.
+//! Each sampling stage multiplies the rate by its own, so sinks that report
+//! rates downstream keep aggregate counts accurate however many stages there
+//! are. Events without a rate count as sampled at 1.
+
+use super::{LogEvent, Value};
+use string_cache::DefaultAtom as Atom;
+
+/// Field the rate is kept in. Sources may also set it in the event metadata.
+pub const SAMPLE_RATE_KEY: &str = "sample_rate";
+
+/// The rate `log` has been sampled at so far. The field wins over metadata,
+/// fractional rates are rounded, and anything unusable counts as 1.
+pub fn get(log: &LogEvent) -> u64 {
+    log.get(&Atom::from(SAMPLE_RATE_KEY))
+        .or_else(|| log.metadata().get(SAMPLE_RATE_KEY))
+        .and_then(parse)
+        .unwrap_or(1)
+}
+
+/// Records that `log` survived sampling at `rate` on top of any earlier
+/// sampling, returning the rate it now stands for.
+pub fn compose(log: &mut LogEvent, rate: u64) -> u64 {
+    let rate = get(log).saturating_mul(rate.max(1));
+    log.insert(SAMPLE_RATE_KEY, rate.to_string());
+    rate
+}
+
+fn parse(value: &Value) -> Option<u64> {
+    let rate = match value {
+        Value::Integer(rate) => *rate as f64,
+        Value::Float(rate) => *rate,
+        Value::Bytes(bytes) => std::str::from_utf8(bytes).ok()?.trim().parse().ok()?,
+        _ => return None,
+    };
+
+    if !rate.is_finite() || rate < 1.0 {
+        None
+    } else if rate >= u64::max_value() as f64 {
+        // Float to integer casts only saturate from Rust 1.45 on.
+        Some(u64::max_value())
+    } else {
+        Some(rate.round() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    #[test]
+    fn sample_rate_defaults_to_one() {
+        let mut event = Event::from("hello");
+        assert_eq!(get(event.as_log()), 1);
+
+        for invalid in &["0", "-4", "nope", ""] {
+            event.as_mut_log().insert(SAMPLE_RATE_KEY, *invalid);
+            assert_eq!(get(event.as_log()), 1, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn sample_rate_reads_numbers_and_strings() {
+        let mut event = Event::from("hello");
+
+        event.as_mut_log().insert(SAMPLE_RATE_KEY, 4);
+        assert_eq!(get(event.as_log()), 4);
+
+        event.as_mut_log().insert(SAMPLE_RATE_KEY, " 10 ");
+        assert_eq!(get(event.as_log()), 10);
+
+        event.as_mut_log().insert(SAMPLE_RATE_KEY, 2.5);
+        assert_eq!(get(event.as_log()), 3);
+
+        event.as_mut_log().insert(SAMPLE_RATE_KEY, "1.4");
+        assert_eq!(get(event.as_log()), 1);
+    }
+
+    #[test]
+    fn sample_rate_falls_back_to_metadata() {
+        let mut event = Event::from("hello");
+        event.as_mut_log().metadata_mut().insert(SAMPLE_RATE_KEY, 5);
+        assert_eq!(get(event.as_log()), 5);
+
+        event.as_mut_log().insert(SAMPLE_RATE_KEY, "2");
+        assert_eq!(get(event.as_log()), 2);
+    }
+
+    #[test]
+    fn sample_rate_composes() {
+        let mut event = Event::from("hello");
+
+        assert_eq!(compose(event.as_mut_log(), 1), 1);
+        assert_eq!(compose(event.as_mut_log(), 4), 4);
+        assert_eq!(compose(event.as_mut_log(), 0), 4);
+        assert_eq!(compose(event.as_mut_log(), 3), 12);
+        assert_eq!(event.as_log()[&Atom::from(SAMPLE_RATE_KEY)], "12".into());
+
+        assert_eq!(
+            compose(event.as_mut_log(), u64::max_value()),
+            u64::max_value()
+        );
+    }
+}
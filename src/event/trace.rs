@@ -0,0 +1,124 @@
+use super::{LogEvent, Value};
+use serde::{Serialize, Serializer};
+
+/// Identifies the trace a span belongs to.
+pub const TRACE_ID: &str = "trace_id";
+/// Identifies the span within its trace.
+pub const SPAN_ID: &str = "span_id";
+/// The span this one is a child of, absent for the root span of a trace.
+pub const PARENT_ID: &str = "parent_id";
+/// Whether the trace should be kept, as decided where it started. Agents
+/// pass it on, so it must survive the pipeline.
+pub const SAMPLING_PRIORITY: &str = "sampling_priority";
+
+/// A span of a distributed trace.
+///
+/// Spans keep their data as fields, like log events, so templates, field
+/// conditions and transforms that work on fields work on them too, while the
+/// ids relating spans to each other live at well-known keys. Ids are kept as
+/// strings, as they're 64 bits wide in some tracing systems and 128 in others.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TraceEvent(pub(crate) LogEvent);
+
+impl TraceEvent {
+    pub fn new(trace_id: impl Into<String>, span_id: impl Into<String>) -> Self {
+        let mut log = LogEvent::new();
+        log.insert(TRACE_ID, trace_id.into());
+        log.insert(SPAN_ID, span_id.into());
+        Self(log)
+    }
+
+    pub fn trace_id(&self) -> Option<String> {
+        self.get_string(TRACE_ID)
+    }
+
+    pub fn span_id(&self) -> Option<String> {
+        self.get_string(SPAN_ID)
+    }
+
+    pub fn parent_id(&self) -> Option<String> {
+        self.get_string(PARENT_ID)
+    }
+
+    pub fn set_parent_id(&mut self, parent_id: impl Into<String>) {
+        self.0.insert(PARENT_ID, parent_id.into());
+    }
+
+    pub fn sampling_priority(&self) -> Option<i64> {
+        match self.0.get(&SAMPLING_PRIORITY.into()) {
+            Some(Value::Integer(priority)) => Some(*priority),
+            _ => None,
+        }
+    }
+
+    pub fn set_sampling_priority(&mut self, priority: i64) {
+        self.0.insert(SAMPLING_PRIORITY, priority);
+    }
+
+    pub fn as_log(&self) -> &LogEvent {
+        &self.0
+    }
+
+    pub fn as_mut_log(&mut self) -> &mut LogEvent {
+        &mut self.0
+    }
+
+    pub fn into_log(self) -> LogEvent {
+        self.0
+    }
+
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.0.get(&key.into()).map(Value::to_string_lossy)
+    }
+}
+
+impl From<LogEvent> for TraceEvent {
+    fn from(log: LogEvent) -> Self {
+        Self(log)
+    }
+}
+
+impl Serialize for TraceEvent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{proto, Event};
+
+    #[test]
+    fn trace_event_relationships() {
+        let mut span = TraceEvent::new("abc", "2");
+        assert_eq!(span.trace_id(), Some("abc".into()));
+        assert_eq!(span.span_id(), Some("2".into()));
+        assert_eq!(span.parent_id(), None);
+        assert_eq!(span.sampling_priority(), None);
+
+        span.set_parent_id("1");
+        span.set_sampling_priority(-1);
+        assert_eq!(span.parent_id(), Some("1".into()));
+        assert_eq!(span.sampling_priority(), Some(-1));
+    }
+
+    #[test]
+    fn trace_event_survives_encoding() {
+        let mut span = TraceEvent::new("abc", "2");
+        span.set_parent_id("1");
+        span.set_sampling_priority(1);
+        span.as_mut_log().insert("duration", 1500);
+        let event = Event::from(span);
+
+        let encoded = proto::EventWrapper::from(event.clone());
+        let decoded = Event::from(encoded);
+
+        assert_eq!(decoded, event);
+        assert_eq!(decoded.as_trace().parent_id(), Some("1".into()));
+        assert_eq!(decoded.as_trace().sampling_priority(), Some(1));
+    }
+}
@@ -33,11 +33,11 @@ where
                 fields.insert(current, Value::Map(map))
             }
         }
-        (Some(PathComponent::Key(current)), Some(&PathComponent::Index(next))) => {
+        (Some(PathComponent::Key(current)), Some(PathComponent::Index(_))) => {
             if let Some(Value::Array(array)) = fields.get_mut(&current) {
                 array_insert(array, path_iter, value)
             } else {
-                let mut array = Vec::with_capacity(next + 1);
+                let mut array = Vec::new();
                 array_insert(&mut array, path_iter, value);
                 fields.insert(current, Value::Array(array))
             }
@@ -73,11 +73,11 @@ where
                 Some(std::mem::replace(&mut values[current], Value::Map(map)))
             }
         }
-        (Some(PathComponent::Index(current)), Some(PathComponent::Index(next))) => {
+        (Some(PathComponent::Index(current)), Some(PathComponent::Index(_))) => {
             if let Some(Value::Array(array)) = values.get_mut(current) {
                 array_insert(array, path_iter, value)
             } else {
-                let mut array = Vec::with_capacity(next + 1);
+                let mut array = Vec::new();
                 array_insert(&mut array, path_iter, value);
                 while values.len() <= current {
                     values.push(Value::Null);
@@ -115,7 +115,11 @@ impl<'a> Iterator for PathIter<'a> {
                     _ => Invalid,
                 },
                 Index(i) => match c {
-                    Some(c) if c >= '0' && c <= '9' => Index(10 * i + (c as usize - '0' as usize)),
+                    // Indexes too large for `usize` can't point into any array.
+                    Some(c) if c >= '0' && c <= '9' => i
+                        .checked_mul(10)
+                        .and_then(|i| i.checked_add(c as usize - '0' as usize))
+                        .map_or(Invalid, Index),
                     Some(']') => {
                         res = Some(Some(PathComponent::Index(i)));
                         ClosingBracket
@@ -208,6 +212,14 @@ mod test {
         }
     }
 
+    #[test]
+    fn path_iter_index_overflow() {
+        use PathComponent::*;
+
+        let actual: Vec<_> = PathIter::new("flying[99999999999999999999999].squirrel").collect();
+        assert_eq!(actual, vec![Key("flying".into()), Invalid]);
+    }
+
     #[test]
     fn path_iter_invalid() {
         let inputs = vec![
@@ -4,7 +4,9 @@ use std::{cmp::Ordering, collections::BTreeMap, iter::Peekable, mem};
 /// Removes field value specified by the given path and return its value.
 ///
 /// A special case worth mentioning: if there is a nested array and an item is removed
-/// from the middle of this array, then it is just replaced by `Value::Null`.
+/// from the middle of this array, then it is just replaced by `Value::Null`, so the
+/// indexes of the items after it don't change. Removing the last item shrinks the
+/// array, which is pruned like a map once it's empty.
 pub fn remove(fields: &mut BTreeMap<String, Value>, path: &str, prune: bool) -> Option<Value> {
     remove_map(fields, PathIter::new(path).peekable(), prune).map(|(value, _)| value)
 }
@@ -27,7 +29,7 @@ fn remove_array(
 ) -> Option<(Value, bool)> {
     match path.next()? {
         PathComponent::Index(index) => match path.peek() {
-            None => return array_remove(array, index).map(|v| (v, false)),
+            None => return array_remove(array, index).map(|v| (v, array.is_empty())),
             Some(_) => array
                 .get_mut(index)
                 .and_then(|value| Some((remove_rec(value, path, prune)?.0, false))),
@@ -179,4 +181,29 @@ mod test {
         assert_eq!(remove(&mut fields, "a.b.c", true), Some(Value::Integer(5)));
         assert_eq!(fields, fields_from_json(json!({})));
     }
+
+    #[test]
+    fn remove_prune_array() {
+        let mut fields = fields_from_json(json!({
+            "a": {
+                "tags": ["x", "y"],
+                "d": 4,
+            }
+        }));
+
+        assert_eq!(remove(&mut fields, "a.tags[1]", true), Some("y".into()));
+        assert_eq!(remove(&mut fields, "a.tags[5]", true), None);
+        assert_eq!(
+            fields,
+            fields_from_json(json!({
+                "a": {
+                    "tags": ["x"],
+                    "d": 4,
+                }
+            }))
+        );
+
+        assert_eq!(remove(&mut fields, "a.tags[0]", true), Some("x".into()));
+        assert_eq!(fields, fields_from_json(json!({ "a": { "d": 4 } })));
+    }
 }
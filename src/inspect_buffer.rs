@@ -0,0 +1,247 @@
+use crate::{buffers::SnapshotRecord, config_paths, event::Event, topology::config::Config};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+use string_cache::DefaultAtom as Atom;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Opts {
+    /// The sink whose disk buffer to read. The buffer is read from a copy, so
+    /// it's left as it is, even if Vector is running.
+    sink: String,
+
+    /// Read configuration from one or more files. Wildcard paths are supported.
+    /// If zero files are specified the default config path
+    /// `/etc/vector/vector.toml` will be targeted.
+    #[structopt(name = "config", short, long)]
+    config_paths: Vec<PathBuf>,
+
+    /// Only show log events with a field of the given value, as `field=value`.
+    #[structopt(long)]
+    filter: Option<Filter>,
+
+    /// Only show the totals, not the events themselves.
+    #[structopt(short, long)]
+    summary: bool,
+}
+
+#[derive(Debug)]
+struct Filter {
+    field: Atom,
+    value: String,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(field), Some(value)) if !field.is_empty() => Ok(Filter {
+                field: field.into(),
+                value: value.into(),
+            }),
+            _ => Err(format!(
+                "{} is not a valid filter, expected `field=value`",
+                s
+            )),
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, event: &Event) -> bool {
+        match event {
+            Event::Log(log) => log
+                .get(&self.field)
+                .map_or(false, |value| value.to_string_lossy() == self.value),
+            Event::Metric(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Totals {
+    events: usize,
+    bytes: usize,
+    unreadable: usize,
+}
+
+fn load_config(opts: &Opts) -> Result<Config, Vec<String>> {
+    let paths = config_paths::expand(opts.config_paths.clone())
+        .ok_or_else(|| vec!["No config files found.".to_owned()])?;
+
+    let mut config = Config::empty();
+    for path in paths {
+        let file = File::open(&path)
+            .map_err(|error| vec![format!("Could not open {:?}: {}", path, error)])?;
+        config.append(Config::load(file)?)?;
+    }
+    Ok(config)
+}
+
+pub fn cmd(opts: &Opts) -> exitcode::ExitCode {
+    let config = match load_config(opts) {
+        Ok(config) => config,
+        Err(errors) => {
+            for error in errors {
+                error!("Configuration error: {}", error);
+            }
+            return exitcode::CONFIG;
+        }
+    };
+
+    let sink = match config.sinks.get(&opts.sink) {
+        Some(sink) => sink,
+        None => {
+            error!(message = "Sink not found in the config.", sink = %opts.sink);
+            return exitcode::CONFIG;
+        }
+    };
+
+    let snapshot = match sink.buffer.snapshot(&config.global.data_dir, &opts.sink) {
+        Ok(snapshot) => snapshot,
+        Err(error) => {
+            error!(message = "Unable to read disk buffer.", %error);
+            return exitcode::IOERR;
+        }
+    };
+
+    let stdout = io::stdout();
+    match inspect(
+        snapshot.records(),
+        opts.filter.as_ref(),
+        opts.summary,
+        &mut stdout.lock(),
+    ) {
+        Ok(_) => exitcode::OK,
+        Err(error) => {
+            error!(message = "Unable to write output.", %error);
+            exitcode::IOERR
+        }
+    }
+}
+
+/// Writes out the records, unless `summary` is set, followed by their totals.
+/// Unreadable records are always written out, as there's no telling whether
+/// they'd match the filter.
+fn inspect(
+    records: impl Iterator<Item = SnapshotRecord>,
+    filter: Option<&Filter>,
+    summary: bool,
+    out: &mut impl Write,
+) -> io::Result<Totals> {
+    let mut totals = Totals::default();
+
+    for record in records {
+        match record.event {
+            Ok(event) => {
+                if !filter.map_or(true, |filter| filter.matches(&event)) {
+                    continue;
+                }
+                totals.events += 1;
+                totals.bytes += record.size;
+
+                if !summary {
+                    let fields = match &event {
+                        Event::Log(log) => serde_json::to_string_pretty(log),
+                        Event::Metric(metric) => serde_json::to_string_pretty(metric),
+                    }?;
+                    writeln!(out, "#{} ({} bytes)", record.key, record.size)?;
+                    writeln!(out, "{}", fields)?;
+                }
+            }
+            Err(error) => {
+                totals.unreadable += 1;
+                totals.bytes += record.size;
+
+                if !summary {
+                    writeln!(
+                        out,
+                        "#{} ({} bytes) unreadable: {}",
+                        record.key, record.size, error
+                    )?;
+                }
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "{} events, {} unreadable records, {} bytes",
+        totals.events, totals.unreadable, totals.bytes
+    )?;
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(key: usize, event: Result<Event, &str>) -> SnapshotRecord {
+        SnapshotRecord {
+            key,
+            size: 10 + key,
+            event: event.map_err(Into::into),
+        }
+    }
+
+    fn records() -> Vec<SnapshotRecord> {
+        let mut error = Event::from("failed");
+        error.as_mut_log().insert("level", "error");
+        let mut info = Event::from("ok");
+        info.as_mut_log().insert("level", "info");
+
+        vec![
+            record(3, Ok(error)),
+            record(4, Err("Record checksum mismatch")),
+            record(5, Ok(info)),
+        ]
+    }
+
+    fn run(filter: Option<&str>, summary: bool) -> (Totals, String) {
+        let filter = filter.map(|filter| filter.parse::<Filter>().unwrap());
+        let mut out = Vec::new();
+        let totals = inspect(records().into_iter(), filter.as_ref(), summary, &mut out).unwrap();
+        (totals, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn inspect_buffer_shows_records() {
+        let (totals, out) = run(None, false);
+        assert_eq!(
+            totals,
+            Totals {
+                events: 2,
+                bytes: 42,
+                unreadable: 1
+            }
+        );
+        assert!(out.starts_with("#3 (13 bytes)\n{\n"));
+        assert!(out.contains("\"message\": \"failed\""));
+        assert!(out.contains("#4 (14 bytes) unreadable: Record checksum mismatch\n"));
+        assert!(out.contains("#5 (15 bytes)\n"));
+        assert!(out.ends_with("2 events, 1 unreadable records, 42 bytes\n"));
+    }
+
+    #[test]
+    fn inspect_buffer_filters_records() {
+        let (totals, out) = run(Some("level=info"), false);
+        assert_eq!(totals.events, 1);
+        assert!(!out.contains("#3 "));
+        assert!(out.contains("#4 "));
+        assert!(out.contains("\"message\": \"ok\""));
+
+        let (totals, out) = run(Some("level=info"), true);
+        assert_eq!(totals.events, 1);
+        assert_eq!(out, "1 events, 1 unreadable records, 29 bytes\n");
+
+        assert!("level".parse::<Filter>().is_err());
+        assert!("=info".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use super::InternalEvent;
+use metrics::counter;
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Debug)]
+pub struct AmqpEventReceived {
+    pub byte_size: usize,
+}
+
+impl InternalEvent for AmqpEventReceived {
+    fn emit_logs(&self) {
+        trace!(message = "received one event.", rate_limit_secs = 10);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "source",
+            "component_type" => "amqp",
+        );
+        counter!("bytes_processed", self.byte_size as u64,
+            "component_kind" => "source",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpConnectionFailed {
+    pub error: lapin::Error,
+}
+
+impl InternalEvent for AmqpConnectionFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "unable to connect to amqp broker.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("connection_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpConsumeFailed {
+    pub error: lapin::Error,
+}
+
+impl InternalEvent for AmqpConsumeFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to consume from amqp queue, reconnecting.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("consume_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpConsumerCancelled<'a> {
+    pub queue: &'a str,
+}
+
+impl InternalEvent for AmqpConsumerCancelled<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "amqp consumer was cancelled by the broker, reconnecting.",
+            queue = %self.queue,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("consume_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpAckFailed {
+    pub error: lapin::Error,
+}
+
+impl InternalEvent for AmqpAckFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to ack amqp delivery, it will be redelivered.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("ack_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpEventSent {
+    pub byte_size: usize,
+}
+
+impl InternalEvent for AmqpEventSent {
+    fn emit_logs(&self) {
+        trace!(message = "published one event.", rate_limit_secs = 10);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "sink",
+            "component_type" => "amqp",
+        );
+        counter!("bytes_processed", self.byte_size as u64,
+            "component_kind" => "sink",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpPublishFailed {
+    pub error: lapin::Error,
+}
+
+impl InternalEvent for AmqpPublishFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to publish to amqp broker, reconnecting.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("publish_errors", 1,
+            "component_kind" => "sink",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpPublishNacked;
+
+impl InternalEvent for AmqpPublishNacked {
+    fn emit_logs(&self) {
+        warn!(
+            message = "amqp broker rejected published message, retrying.",
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("publish_errors", 1,
+            "component_kind" => "sink",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpPublishReturned<'a> {
+    pub exchange: &'a str,
+    pub routing_key: &'a str,
+}
+
+impl InternalEvent for AmqpPublishReturned<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "mandatory message could not be routed to any queue. Dropping event.",
+            exchange = %self.exchange,
+            routing_key = %self.routing_key,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("messages_returned", 1,
+            "component_kind" => "sink",
+            "component_type" => "amqp",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AmqpRoutingKeyMissing {
+    pub missing_keys: Vec<Atom>,
+}
+
+impl InternalEvent for AmqpRoutingKeyMissing {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Keys do not exist on the event. Dropping event.",
+            missing_keys = ?self.missing_keys,
+            rate_limit_secs = 30
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("processing_errors", 1,
+            "component_kind" => "sink",
+            "component_type" => "amqp",
+            "error_type" => "render_error",
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use super::InternalEvent;
+use metrics::counter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CloudwatchLogsCall {
+    DescribeGroups,
+    DescribeStreams,
+    CreateGroup,
+    CreateStream,
+    PutRetentionPolicy,
+    PutEvents,
+}
+
+impl CloudwatchLogsCall {
+    fn counter_name(self) -> &'static str {
+        match self {
+            CloudwatchLogsCall::DescribeGroups => "cloudwatch_describe_groups_total",
+            CloudwatchLogsCall::DescribeStreams => "cloudwatch_describe_streams_total",
+            CloudwatchLogsCall::CreateGroup => "cloudwatch_create_group_total",
+            CloudwatchLogsCall::CreateStream => "cloudwatch_create_stream_total",
+            CloudwatchLogsCall::PutRetentionPolicy => "cloudwatch_put_retention_policy_total",
+            CloudwatchLogsCall::PutEvents => "cloudwatch_put_events_total",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CloudwatchLogsApiCalled<'a> {
+    pub call: CloudwatchLogsCall,
+    pub group: &'a str,
+    pub stream: &'a str,
+}
+
+impl InternalEvent for CloudwatchLogsApiCalled<'_> {
+    fn emit_logs(&self) {
+        trace!(message = "calling CloudWatch Logs.", call = ?self.call);
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            self.call.counter_name(), 1,
+            "component_kind" => "sink",
+            "component_type" => "aws_cloudwatch_logs",
+            "log_group" => self.group.to_owned(),
+            "log_stream" => self.stream.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct CloudwatchLogsRequestFailed<'a> {
+    pub error_type: &'static str,
+    pub group: &'a str,
+    pub stream: &'a str,
+}
+
+impl InternalEvent for CloudwatchLogsRequestFailed<'_> {
+    fn emit_logs(&self) {
+        debug!(message = "CloudWatch Logs request failed.", error_type = %self.error_type);
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            "cloudwatch_request_errors", 1,
+            "component_kind" => "sink",
+            "component_type" => "aws_cloudwatch_logs",
+            "error_type" => self.error_type,
+            "log_group" => self.group.to_owned(),
+            "log_stream" => self.stream.to_owned(),
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use super::InternalEvent;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct AwsCloudwatchLogsSubscriptionEventsReceived {
+    pub count: usize,
+}
+
+impl InternalEvent for AwsCloudwatchLogsSubscriptionEventsReceived {
+    fn emit_logs(&self) {
+        trace!(message = "received events.", count = %self.count);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "aws_cloudwatch_logs_subscription",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AwsCloudwatchLogsSubscriptionRequestError<'a, E> {
+    pub error: &'a E,
+}
+
+impl<E: std::fmt::Display + std::fmt::Debug> InternalEvent
+    for AwsCloudwatchLogsSubscriptionRequestError<'_, E>
+{
+    fn emit_logs(&self) {
+        error!(
+            message = "error handling request.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("request_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "aws_cloudwatch_logs_subscription",
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use super::InternalEvent;
+use metrics::counter;
+use rusoto_core::RusotoError;
+use rusoto_sqs::{DeleteMessageError, ReceiveMessageError};
+
+#[derive(Debug)]
+pub struct AwsS3ObjectProcessed<'a> {
+    pub bucket: &'a str,
+    pub key: &'a str,
+    pub count: usize,
+}
+
+impl InternalEvent for AwsS3ObjectProcessed<'_> {
+    fn emit_logs(&self) {
+        debug!(
+            message = "processed s3 object.",
+            bucket = %self.bucket,
+            key = %self.key,
+            count = %self.count
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "aws_s3",
+        );
+        counter!("objects_processed", 1,
+            "component_kind" => "source",
+            "component_type" => "aws_s3",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AwsS3ObjectProcessingFailed<E> {
+    pub error: E,
+}
+
+impl<E: std::fmt::Display + std::fmt::Debug> InternalEvent for AwsS3ObjectProcessingFailed<E> {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to process s3 object, the message will be retried.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("object_processing_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "aws_s3",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AwsSqsReceiveFailed {
+    pub error: RusotoError<ReceiveMessageError>,
+}
+
+impl InternalEvent for AwsSqsReceiveFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to receive sqs messages.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("sqs_receive_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "aws_s3",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AwsSqsMessageInvalid<'a> {
+    pub message_id: Option<&'a str>,
+    pub error: serde_json::Error,
+}
+
+impl InternalEvent for AwsSqsMessageInvalid<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "sqs message is not an s3 event notification.",
+            message_id = ?self.message_id,
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("sqs_message_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "aws_s3",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct AwsSqsMessageDeleteFailed {
+    pub error: RusotoError<DeleteMessageError>,
+}
+
+impl InternalEvent for AwsSqsMessageDeleteFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to delete sqs message.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("sqs_delete_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "aws_s3",
+        );
+    }
+}
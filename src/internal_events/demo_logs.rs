@@ -0,0 +1,20 @@
+use super::InternalEvent;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct DemoLogsEventsGenerated {
+    pub count: usize,
+}
+
+impl InternalEvent for DemoLogsEventsGenerated {
+    fn emit_logs(&self) {
+        trace!(message = "generated events.", count = %self.count, rate_limit_secs = 10);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "demo_logs",
+        );
+    }
+}
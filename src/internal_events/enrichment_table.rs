@@ -0,0 +1,80 @@
+use super::InternalEvent;
+use metrics::counter;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct EnrichmentTableLoaded<'a> {
+    pub path: &'a Path,
+    pub rows: usize,
+    /// Rows dropped for a key that an earlier row already has.
+    pub duplicates: usize,
+    /// Rows dropped for missing a key column.
+    pub skipped: usize,
+}
+
+impl InternalEvent for EnrichmentTableLoaded<'_> {
+    fn emit_logs(&self) {
+        info!(
+            message = "loaded enrichment table.",
+            path = ?self.path,
+            rows = %self.rows,
+        );
+        if self.duplicates > 0 || self.skipped > 0 {
+            warn!(
+                message = "ignored enrichment table rows with duplicate or missing keys.",
+                path = ?self.path,
+                duplicates = %self.duplicates,
+                skipped = %self.skipped,
+            );
+        }
+    }
+
+    fn emit_metrics(&self) {
+        counter!("enrichment_table_loads", 1,
+            "component_kind" => "transform",
+            "component_type" => "enrichment_table",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct EnrichmentTableLoadFailed<'a> {
+    pub path: &'a Path,
+    pub error: crate::Error,
+}
+
+impl InternalEvent for EnrichmentTableLoadFailed<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to reload enrichment table; keeping the previous one.",
+            path = ?self.path,
+            error = %self.error,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("enrichment_table_load_errors", 1,
+            "component_kind" => "transform",
+            "component_type" => "enrichment_table",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct EnrichmentTableMissed;
+
+impl InternalEvent for EnrichmentTableMissed {
+    fn emit_logs(&self) {
+        debug!(
+            message = "no enrichment table row found for event.",
+            rate_limit_secs = 30
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("enrichment_table_misses", 1,
+            "component_kind" => "transform",
+            "component_type" => "enrichment_table",
+        );
+    }
+}
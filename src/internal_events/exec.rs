@@ -0,0 +1,103 @@
+use super::InternalEvent;
+use metrics::counter;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct ExecEventReceived<'a> {
+    pub command: &'a str,
+    pub byte_size: usize,
+}
+
+impl InternalEvent for ExecEventReceived<'_> {
+    fn emit_logs(&self) {
+        trace!(
+            message = "received one event.",
+            command = %self.command,
+            rate_limit_secs = 10,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "source",
+            "component_type" => "exec",
+        );
+        counter!("bytes_processed", self.byte_size as u64,
+            "component_kind" => "source",
+            "component_type" => "exec",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ExecFailed<'a> {
+    pub command: &'a str,
+    pub error: std::io::Error,
+}
+
+impl InternalEvent for ExecFailed<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "unable to execute command.",
+            command = %self.command,
+            error = %self.error,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("processing_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "exec",
+            "error_type" => "command_failed",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ExecTimedOut<'a> {
+    pub command: &'a str,
+    pub elapsed: Duration,
+}
+
+impl InternalEvent for ExecTimedOut<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "command timed out, killing it.",
+            command = %self.command,
+            elapsed_ms = self.elapsed.as_millis() as u64,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("processing_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "exec",
+            "error_type" => "timed_out",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ExecCommandExited<'a> {
+    pub command: &'a str,
+    pub exit_code: Option<i32>,
+    pub elapsed: Duration,
+}
+
+impl InternalEvent for ExecCommandExited<'_> {
+    fn emit_logs(&self) {
+        debug!(
+            message = "command exited.",
+            command = %self.command,
+            exit_code = ?self.exit_code,
+            elapsed_ms = self.elapsed.as_millis() as u64,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("commands_executed", 1,
+            "component_kind" => "source",
+            "component_type" => "exec",
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use super::InternalEvent;
+use crate::sources::host_metrics::Collector;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct HostMetricsEventsCollected {
+    pub count: usize,
+}
+
+impl InternalEvent for HostMetricsEventsCollected {
+    fn emit_logs(&self) {
+        trace!(message = "collected host metrics.", count = %self.count);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "host_metrics",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct HostMetricsCollectorFailed {
+    pub collector: Collector,
+    pub error: crate::Error,
+}
+
+impl InternalEvent for HostMetricsCollectorFailed {
+    fn emit_logs(&self) {
+        warn!(
+            message = "host metrics collector failed.",
+            collector = ?self.collector,
+            error = %self.error,
+            rate_limit_secs = 60
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("collector_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "host_metrics",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct HostMetricsCollectorUnsupported {
+    pub collector: Collector,
+}
+
+impl InternalEvent for HostMetricsCollectorUnsupported {
+    fn emit_logs(&self) {
+        warn!(
+            message = "host metrics collector is not available on this platform; skipping it.",
+            collector = ?self.collector,
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use super::InternalEvent;
+use http::Uri;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct HttpScrapeRequestCompleted {
+    pub component_type: &'static str,
+}
+
+impl InternalEvent for HttpScrapeRequestCompleted {
+    fn emit_metrics(&self) {
+        counter!("requests_completed", 1,
+            "component_kind" => "source",
+            "component_type" => self.component_type,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpScrapeError<'a> {
+    pub component_type: &'static str,
+    pub url: &'a Uri,
+    pub error: String,
+}
+
+impl InternalEvent for HttpScrapeError<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "scrape failed.",
+            url = %self.url,
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("http_request_errors", 1,
+            "component_kind" => "source",
+            "component_type" => self.component_type,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpScrapeParseError<'a, E> {
+    pub component_type: &'static str,
+    pub url: &'a Uri,
+    pub error: E,
+}
+
+impl<E: std::fmt::Display + std::fmt::Debug> InternalEvent for HttpScrapeParseError<'_, E> {
+    fn emit_logs(&self) {
+        error!(
+            message = "parsing error.",
+            url = %self.url,
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("parse_errors", 1,
+            "component_kind" => "source",
+            "component_type" => self.component_type,
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use super::InternalEvent;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct InvalidUtf8Message {
+    pub component_type: &'static str,
+    pub action: &'static str,
+    pub byte_size: usize,
+}
+
+impl InternalEvent for InvalidUtf8Message {
+    fn emit_logs(&self) {
+        debug!(
+            message = "received message that isn't valid UTF-8.",
+            action = self.action,
+            byte_size = self.byte_size as u64,
+            rate_limit_secs = 30,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("invalid_utf8_messages_total", 1,
+            "component_kind" => "source",
+            "component_type" => self.component_type,
+            "action" => self.action,
+        );
+    }
+}
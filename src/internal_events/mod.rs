@@ -1,30 +1,120 @@
+#[cfg(any(feature = "sources-amqp", feature = "sinks-amqp"))]
+mod amqp;
+#[cfg(feature = "sinks-aws_cloudwatch_logs")]
+mod aws_cloudwatch_logs;
+#[cfg(feature = "sources-aws_cloudwatch_logs_subscription")]
+mod aws_cloudwatch_logs_subscription;
+#[cfg(feature = "sources-aws_s3")]
+mod aws_s3;
 mod blackhole;
+#[cfg(feature = "sources-demo_logs")]
+mod demo_logs;
 mod elasticsearch;
+#[cfg(feature = "transforms-enrichment_table")]
+mod enrichment_table;
+#[cfg(feature = "sources-exec")]
+mod exec;
 mod file;
+#[cfg(feature = "sources-host_metrics")]
+mod host_metrics;
+#[cfg(any(feature = "sources-apache_metrics", feature = "sources-nginx_metrics"))]
+mod http_scrape;
+#[cfg(any(
+    feature = "sources-file",
+    feature = "sources-kafka",
+    feature = "sources-socket"
+))]
+mod invalid_utf8;
 #[cfg(feature = "transforms-lua")]
 mod lua;
+#[cfg(feature = "sources-mongodb_metrics")]
+mod mongodb_metrics;
+#[cfg(any(feature = "sources-mqtt", feature = "sinks-mqtt"))]
+mod mqtt;
+#[cfg(feature = "sources-opentelemetry")]
+mod opentelemetry;
+mod partition_limit;
 #[cfg(feature = "sources-prometheus")]
 mod prometheus;
 mod regex;
+#[cfg(feature = "transforms-remap")]
+mod remap;
+#[cfg(feature = "transforms-reorder")]
+mod reorder;
+mod sink_batch;
+mod sink_request;
+#[cfg(any(
+    feature = "sources-postgresql_metrics",
+    feature = "sources-mysql_metrics"
+))]
+mod sql_metrics;
 mod syslog;
 mod tcp;
+mod topology;
 mod udp;
 mod unix;
 mod vector;
+#[cfg(feature = "sinks-websocket")]
+mod websocket;
 
+#[cfg(any(feature = "sources-amqp", feature = "sinks-amqp"))]
+pub use self::amqp::*;
+#[cfg(feature = "sinks-aws_cloudwatch_logs")]
+pub use self::aws_cloudwatch_logs::*;
+#[cfg(feature = "sources-aws_cloudwatch_logs_subscription")]
+pub use self::aws_cloudwatch_logs_subscription::*;
+#[cfg(feature = "sources-aws_s3")]
+pub use self::aws_s3::*;
 pub use self::blackhole::*;
+#[cfg(feature = "sources-demo_logs")]
+pub use self::demo_logs::*;
 pub use self::elasticsearch::*;
+#[cfg(feature = "transforms-enrichment_table")]
+pub use self::enrichment_table::*;
+#[cfg(feature = "sources-exec")]
+pub use self::exec::*;
 pub use self::file::*;
+#[cfg(feature = "sources-host_metrics")]
+pub use self::host_metrics::*;
+#[cfg(any(feature = "sources-apache_metrics", feature = "sources-nginx_metrics"))]
+pub use self::http_scrape::*;
+#[cfg(any(
+    feature = "sources-file",
+    feature = "sources-kafka",
+    feature = "sources-socket"
+))]
+pub use self::invalid_utf8::*;
 #[cfg(feature = "transforms-lua")]
 pub use self::lua::*;
+#[cfg(feature = "sources-mongodb_metrics")]
+pub use self::mongodb_metrics::*;
+#[cfg(any(feature = "sources-mqtt", feature = "sinks-mqtt"))]
+pub use self::mqtt::*;
+#[cfg(feature = "sources-opentelemetry")]
+pub use self::opentelemetry::*;
+pub use self::partition_limit::*;
 #[cfg(feature = "sources-prometheus")]
 pub use self::prometheus::*;
 pub use self::regex::*;
+#[cfg(feature = "transforms-remap")]
+pub use self::remap::*;
+#[cfg(feature = "transforms-reorder")]
+pub use self::reorder::*;
+pub use self::sink_batch::*;
+pub use self::sink_request::*;
+#[cfg(any(
+    feature = "sources-postgresql_metrics",
+    feature = "sources-mysql_metrics"
+))]
+pub use self::sql_metrics::*;
 pub use self::syslog::*;
 pub use self::tcp::*;
+pub use self::topology::*;
 pub use self::udp::*;
 pub use self::unix::*;
 pub use self::vector::*;
+#[cfg(feature = "sinks-websocket")]
+pub use self::websocket::*;
 
 pub trait InternalEvent: std::fmt::Debug {
     fn emit_logs(&self) {}
@@ -0,0 +1,39 @@
+use super::InternalEvent;
+use metrics::counter;
+use mongodb::error::Error as MongoError;
+
+#[derive(Debug)]
+pub struct MongoDBMetricsCollectCompleted;
+
+impl InternalEvent for MongoDBMetricsCollectCompleted {
+    fn emit_metrics(&self) {
+        counter!("collect_completed", 1,
+            "component_kind" => "source",
+            "component_type" => "mongodb_metrics",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct MongoDBMetricsCollectError<'a> {
+    pub endpoint: &'a str,
+    pub error: MongoError,
+}
+
+impl InternalEvent for MongoDBMetricsCollectError<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "metrics collection failed.",
+            endpoint = %self.endpoint,
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("collect_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "mongodb_metrics",
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use super::InternalEvent;
+use metrics::counter;
+use rumqttc::{ClientError, ConnectionError};
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Debug)]
+pub struct MqttEventReceived {
+    pub byte_size: usize,
+}
+
+impl InternalEvent for MqttEventReceived {
+    fn emit_logs(&self) {
+        trace!(message = "received one event.", rate_limit_secs = 10);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "source",
+            "component_type" => "mqtt",
+        );
+        counter!("bytes_processed", self.byte_size as u64,
+            "component_kind" => "source",
+            "component_type" => "mqtt",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct MqttSubscribeFailed<'a> {
+    pub topic: &'a str,
+    pub error: ClientError,
+}
+
+impl InternalEvent for MqttSubscribeFailed<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "failed to subscribe to mqtt topic.",
+            topic = %self.topic,
+            error = %self.error,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("subscribe_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "mqtt",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct MqttConnectionFailed {
+    pub error: ConnectionError,
+}
+
+impl InternalEvent for MqttConnectionFailed {
+    fn emit_logs(&self) {
+        error!(
+            message = "mqtt connection failed, reconnecting.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("connection_errors", 1,
+            "component_type" => "mqtt",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct MqttEventSent {
+    pub byte_size: usize,
+}
+
+impl InternalEvent for MqttEventSent {
+    fn emit_logs(&self) {
+        trace!(message = "published one event.", rate_limit_secs = 10);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "sink",
+            "component_type" => "mqtt",
+        );
+        counter!("bytes_processed", self.byte_size as u64,
+            "component_kind" => "sink",
+            "component_type" => "mqtt",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct MqttTopicMissing {
+    pub missing_keys: Vec<Atom>,
+}
+
+impl InternalEvent for MqttTopicMissing {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Keys do not exist on the event. Dropping event.",
+            missing_keys = ?self.missing_keys,
+            rate_limit_secs = 30
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("processing_errors", 1,
+            "component_kind" => "sink",
+            "component_type" => "mqtt",
+            "error_type" => "render_error",
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use super::InternalEvent;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct OpenTelemetryEventsReceived {
+    pub count: usize,
+}
+
+impl InternalEvent for OpenTelemetryEventsReceived {
+    fn emit_logs(&self) {
+        trace!(message = "received events.", count = %self.count);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "opentelemetry",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct OpenTelemetryDataPointsRejected<'a> {
+    pub count: usize,
+    pub reason: &'a str,
+}
+
+impl InternalEvent for OpenTelemetryDataPointsRejected<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "rejected data points.",
+            count = %self.count,
+            reason = %self.reason,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("data_points_rejected", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "opentelemetry",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct OpenTelemetryRequestError<'a, E> {
+    pub error: &'a E,
+}
+
+impl<E: std::fmt::Display + std::fmt::Debug> InternalEvent for OpenTelemetryRequestError<'_, E> {
+    fn emit_logs(&self) {
+        error!(
+            message = "error handling request.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("request_errors", 1,
+            "component_kind" => "source",
+            "component_type" => "opentelemetry",
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use super::InternalEvent;
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct PartitionCardinalityExceeded<'a> {
+    pub component: &'a str,
+    pub max_partitions: usize,
+}
+
+impl InternalEvent for PartitionCardinalityExceeded<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "too many partitions, sending event to the default partition.",
+            max_partitions = %self.max_partitions,
+            rate_limit_secs = 30,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            "partition_cardinality_exceeded_total", 1,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
@@ -50,3 +50,49 @@ impl InternalEvent for PrometheusHttpError {
         );
     }
 }
+
+#[derive(Debug)]
+pub struct PrometheusScrapeTimeout {
+    pub timeout: u64,
+}
+
+impl InternalEvent for PrometheusScrapeTimeout {
+    fn emit_logs(&self) {
+        warn!(
+            message = "scrape timed out.",
+            timeout_secs = %self.timeout,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("scrape_timeouts", 1,
+            "component_kind" => "source",
+            "component_type" => "prometheus_scrape",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct PrometheusStaleSeries<'a> {
+    pub url: &'a str,
+    pub count: usize,
+}
+
+impl InternalEvent for PrometheusStaleSeries<'_> {
+    fn emit_logs(&self) {
+        debug!(
+            message = "marking series stale that disappeared since the previous scrape.",
+            url = %self.url,
+            count = %self.count,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("stale_series", self.count as u64,
+            "component_kind" => "source",
+            "component_type" => "prometheus_scrape",
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use super::InternalEvent;
+use crate::transforms::remap::{Error, ErrorPolicy};
+use metrics::counter;
+
+#[derive(Debug)]
+pub struct RemapEventProcessed;
+
+impl InternalEvent for RemapEventProcessed {
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "transform",
+            "component_type" => "remap",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct RemapFailed {
+    pub error: Error,
+    pub error_policy: ErrorPolicy,
+}
+
+impl InternalEvent for RemapFailed {
+    fn emit_logs(&self) {
+        let message = match self.error_policy {
+            ErrorPolicy::Abort => "remap program failed; passing event on unchanged.",
+            ErrorPolicy::Drop => "remap program failed; dropping event.",
+            ErrorPolicy::Keep => "remap statement failed; skipping it.",
+        };
+        warn!(message = %message, error = %self.error, rate_limit_secs = 30);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("processing_errors", 1,
+            "component_kind" => "transform",
+            "component_type" => "remap",
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use super::InternalEvent;
+use metrics::counter;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct ReorderEventDropped {
+    pub lateness: Duration,
+}
+
+impl InternalEvent for ReorderEventDropped {
+    fn emit_logs(&self) {
+        warn!(
+            message = "event arrived behind events already passed on; dropping it.",
+            lateness_secs = %self.lateness.as_secs(),
+            rate_limit_secs = 30,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("late_events_dropped", 1,
+            "component_kind" => "transform",
+            "component_type" => "reorder",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ReorderEventReleasedEarly {
+    pub max_events: usize,
+}
+
+impl InternalEvent for ReorderEventReleasedEarly {
+    fn emit_logs(&self) {
+        debug!(
+            message = "too many events held; passing the earliest on early.",
+            max_events = %self.max_events,
+            rate_limit_secs = 30,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_released_early", 1,
+            "component_kind" => "transform",
+            "component_type" => "reorder",
+        );
+    }
+}
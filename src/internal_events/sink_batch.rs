@@ -0,0 +1,35 @@
+use super::InternalEvent;
+use metrics::value;
+
+#[derive(Debug)]
+pub struct SinkBatchSent<'a> {
+    pub component: &'a str,
+    pub events: usize,
+    /// The finished batch's size, for batches that know it.
+    pub byte_size: Option<usize>,
+}
+
+impl InternalEvent for SinkBatchSent<'_> {
+    fn emit_logs(&self) {
+        trace!(
+            message = "batch sent.",
+            events = %self.events,
+            byte_size = ?self.byte_size,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        value!(
+            "component_sent_batch_events", self.events as u64,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+        if let Some(byte_size) = self.byte_size {
+            value!(
+                "component_sent_batch_bytes", byte_size as u64,
+                "component_kind" => "sink",
+                "component_id" => self.component.to_owned(),
+            );
+        }
+    }
+}
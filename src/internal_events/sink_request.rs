@@ -0,0 +1,55 @@
+use super::InternalEvent;
+use metrics::{gauge, timing};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct SinkRequestStarted<'a> {
+    pub component: &'a str,
+    pub in_flight: usize,
+}
+
+impl InternalEvent for SinkRequestStarted<'_> {
+    fn emit_logs(&self) {
+        trace!(message = "request started.", in_flight = %self.in_flight);
+    }
+
+    fn emit_metrics(&self) {
+        gauge!(
+            "component_in_flight_requests", self.in_flight as i64,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct SinkRequestFinished<'a> {
+    pub component: &'a str,
+    pub in_flight: usize,
+    pub duration: Duration,
+}
+
+impl InternalEvent for SinkRequestFinished<'_> {
+    fn emit_logs(&self) {
+        trace!(
+            message = "request finished.",
+            in_flight = %self.in_flight,
+            duration = ?self.duration,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        gauge!(
+            "component_in_flight_requests", self.in_flight as i64,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+        // Timings are recorded in nanoseconds, the internal_metrics source
+        // reports histograms named in seconds as such.
+        timing!(
+            "component_request_duration_seconds", self.duration.as_nanos() as u64,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use super::InternalEvent;
+use metrics::counter;
+
+macro_rules! sql_metrics_events {
+    ($completed:ident, $error:ident, $unavailable:ident, $error_type:ty, $component:expr) => {
+        #[derive(Debug)]
+        pub struct $completed;
+
+        impl InternalEvent for $completed {
+            fn emit_metrics(&self) {
+                counter!("collect_completed", 1,
+                    "component_kind" => "source",
+                    "component_type" => $component,
+                );
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct $error<'a> {
+            pub endpoint: &'a str,
+            pub error: $error_type,
+        }
+
+        impl InternalEvent for $error<'_> {
+            fn emit_logs(&self) {
+                error!(
+                    message = "metrics collection failed.",
+                    endpoint = %self.endpoint,
+                    error = %self.error,
+                    rate_limit_secs = 10
+                );
+            }
+
+            fn emit_metrics(&self) {
+                counter!("collect_errors", 1,
+                    "component_kind" => "source",
+                    "component_type" => $component,
+                );
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct $unavailable<E> {
+            pub view: &'static str,
+            pub error: E,
+        }
+
+        impl<E: std::fmt::Debug + std::fmt::Display> InternalEvent for $unavailable<E> {
+            fn emit_logs(&self) {
+                warn!(
+                    message = "insufficient privileges to read statistics, skipping.",
+                    view = %self.view,
+                    error = %self.error,
+                    rate_limit_secs = 60
+                );
+            }
+
+            fn emit_metrics(&self) {
+                counter!("permission_errors", 1,
+                    "component_kind" => "source",
+                    "component_type" => $component,
+                );
+            }
+        }
+    };
+}
+
+#[cfg(feature = "sources-postgresql_metrics")]
+sql_metrics_events!(
+    PostgresqlMetricsCollectCompleted,
+    PostgresqlMetricsCollectError,
+    PostgresqlMetricsViewUnavailable,
+    tokio_postgres::Error,
+    "postgresql_metrics"
+);
+
+#[cfg(feature = "sources-mysql_metrics")]
+sql_metrics_events!(
+    MysqlMetricsCollectCompleted,
+    MysqlMetricsCollectError,
+    MysqlMetricsViewUnavailable,
+    mysql_async::Error,
+    "mysql_metrics"
+);
@@ -56,6 +56,52 @@ impl InternalEvent for TcpConnectionDisconnected {
     }
 }
 
+#[derive(Debug)]
+pub struct TcpEndpointEjected<'a> {
+    pub host: &'a str,
+    pub port: u16,
+    pub cooldown: std::time::Duration,
+}
+
+impl InternalEvent for TcpEndpointEjected<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "endpoint keeps failing, ejecting it.",
+            host = %self.host,
+            port = %self.port,
+            cooldown_secs = %self.cooldown.as_secs(),
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("tcp_endpoints_ejected", 1,
+            "component_kind" => "sink",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct TcpEndpointReadmitted<'a> {
+    pub host: &'a str,
+    pub port: u16,
+}
+
+impl InternalEvent for TcpEndpointReadmitted<'_> {
+    fn emit_logs(&self) {
+        info!(
+            message = "re-admitting endpoint.",
+            host = %self.host,
+            port = %self.port,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("tcp_endpoints_readmitted", 1,
+            "component_kind" => "sink",
+        );
+    }
+}
+
 #[derive(Debug)]
 pub struct TcpConnectionError {
     pub error: std::io::Error,
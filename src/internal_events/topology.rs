@@ -0,0 +1,170 @@
+use super::InternalEvent;
+use metrics::{counter, gauge};
+
+#[derive(Debug)]
+pub struct ComponentEventReceived<'a> {
+    pub kind: &'static str,
+    pub component: &'a str,
+}
+
+impl InternalEvent for ComponentEventReceived<'_> {
+    fn emit_metrics(&self) {
+        counter!(
+            "component_received_events_total", 1,
+            "component_kind" => self.kind,
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceEventReceived<'a> {
+    pub component: &'a str,
+    pub byte_size: usize,
+}
+
+impl InternalEvent for SourceEventReceived<'_> {
+    fn emit_metrics(&self) {
+        counter!(
+            "component_received_events_total", 1,
+            "component_kind" => "source",
+            "component_id" => self.component.to_owned(),
+        );
+        counter!(
+            "component_received_bytes_total", self.byte_size as u64,
+            "component_kind" => "source",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ComponentEventSent<'a> {
+    pub kind: &'static str,
+    pub component: &'a str,
+}
+
+impl InternalEvent for ComponentEventSent<'_> {
+    fn emit_metrics(&self) {
+        counter!(
+            "component_sent_events_total", 1,
+            "component_kind" => self.kind,
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct SinkHealthcheckFinished<'a> {
+    pub component: &'a str,
+    pub healthy: bool,
+}
+
+impl InternalEvent for SinkHealthcheckFinished<'_> {
+    fn emit_metrics(&self) {
+        gauge!(
+            "component_healthy", self.healthy as i64,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct OversizedEventHandled<'a> {
+    pub component: &'a str,
+    pub action: &'static str,
+    pub size: usize,
+    pub max_bytes: usize,
+}
+
+impl InternalEvent for OversizedEventHandled<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "event exceeds the maximum size.",
+            action = %self.action,
+            size = %self.size,
+            max_bytes = %self.max_bytes,
+            rate_limit_secs = 10,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            "oversized_events_total", 1,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+            "action" => self.action,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct ExpiredEventDropped<'a> {
+    pub component: &'a str,
+    pub age_secs: u64,
+    pub max_age_secs: u64,
+}
+
+impl InternalEvent for ExpiredEventDropped<'_> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "event exceeds the maximum age; dropping it.",
+            age_secs = %self.age_secs,
+            max_age_secs = %self.max_age_secs,
+            rate_limit_secs = 10,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            "events_dropped_expired_total", 1,
+            "component_kind" => "sink",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct TransformPanicked<'a> {
+    pub component: &'a str,
+    pub message: &'a str,
+    /// Where the panic happened, followed by the backtrace.
+    pub backtrace: Option<&'a str>,
+}
+
+impl InternalEvent for TransformPanicked<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "Transform panicked; dropping the event.",
+            component = %self.component,
+            panic = %self.message,
+            backtrace = %self.backtrace.unwrap_or("unavailable"),
+            rate_limit_secs = 30,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            "transform_panics", 1,
+            "component_kind" => "transform",
+            "component_id" => self.component.to_owned(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct TransformPanicLoop<'a> {
+    pub component: &'a str,
+    pub panics: usize,
+}
+
+impl InternalEvent for TransformPanicLoop<'_> {
+    fn emit_logs(&self) {
+        error!(
+            message = "Transform keeps panicking without taking in new events; stopping it.",
+            component = %self.component,
+            panics = %self.panics,
+        );
+    }
+}
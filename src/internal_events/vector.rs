@@ -1,6 +1,6 @@
 use super::InternalEvent;
+use crate::event::frame::FrameError;
 use metrics::counter;
-use prost::DecodeError;
 
 #[derive(Debug)]
 pub struct VectorEventSent {
@@ -47,19 +47,45 @@ impl InternalEvent for VectorEventReceived {
 }
 
 #[derive(Debug)]
-pub struct VectorProtoDecodeError {
-    pub error: DecodeError,
+pub struct VectorFrameDecodeError {
+    pub error: FrameError,
 }
 
-impl InternalEvent for VectorProtoDecodeError {
+impl InternalEvent for VectorFrameDecodeError {
     fn emit_logs(&self) {
-        error!(message = "failed to decode protobuf message", error = %self.error);
+        error!(message = "failed to decode event frame", error = %self.error);
+    }
+
+    fn emit_metrics(&self) {
+        match self.error {
+            FrameError::Decode { .. } => counter!(
+                "protobuf_decode_errors", 1,
+                "component_kind" => "source",
+                "component_type" => "vector",
+            ),
+            _ => counter!(
+                "protocol_errors", 1,
+                "component_kind" => "source",
+                "component_type" => "vector",
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VectorEventEncodeFailed {
+    pub error: std::io::Error,
+}
+
+impl InternalEvent for VectorEventEncodeFailed {
+    fn emit_logs(&self) {
+        error!(message = "failed to encode event frame, dropping event", error = %self.error);
     }
 
     fn emit_metrics(&self) {
         counter!(
-            "protobuf_decode_errors", 1,
-            "component_kind" => "source",
+            "encode_errors", 1,
+            "component_kind" => "sink",
             "component_type" => "vector",
         );
     }
@@ -0,0 +1,63 @@
+use super::InternalEvent;
+use metrics::counter;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct WebSocketConnectionEstablished;
+
+impl InternalEvent for WebSocketConnectionEstablished {
+    fn emit_logs(&self) {
+        debug!(message = "connected.");
+    }
+
+    fn emit_metrics(&self) {
+        counter!("connection_established", 1,
+            "component_kind" => "sink",
+            "component_type" => "websocket",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct WebSocketConnectionFailed<E> {
+    pub error: E,
+}
+
+impl<E: Display + std::fmt::Debug> InternalEvent for WebSocketConnectionFailed<E> {
+    fn emit_logs(&self) {
+        error!(
+            message = "websocket connection failed, reconnecting.",
+            error = %self.error,
+            rate_limit_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("connection_errors", 1,
+            "component_kind" => "sink",
+            "component_type" => "websocket",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct WebSocketEventSent {
+    pub byte_size: usize,
+}
+
+impl InternalEvent for WebSocketEventSent {
+    fn emit_logs(&self) {
+        trace!(message = "sent one event.", rate_limit_secs = 10);
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_processed", 1,
+            "component_kind" => "sink",
+            "component_type" => "websocket",
+        );
+        counter!("bytes_processed", self.byte_size as u64,
+            "component_kind" => "sink",
+            "component_type" => "websocket",
+        );
+    }
+}
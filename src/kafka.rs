@@ -2,12 +2,30 @@ use crate::tls::TlsOptions;
 use rdkafka::ClientConfig;
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 #[derive(Debug, Snafu)]
 enum KafkaError {
     #[snafu(display("invalid path: {:?}", path))]
     InvalidPath { path: PathBuf },
+    #[snafu(display("SASL is enabled but `sasl.mechanism` is not set"))]
+    MissingSaslMechanism,
+    #[snafu(display(
+        "SASL mechanism {} needs both `sasl.username` and `sasl.password`",
+        mechanism
+    ))]
+    MissingSaslCredentials { mechanism: &'static str },
+    #[snafu(display(
+        "librdkafka option {:?} = {:?} contradicts the `sasl` and `tls` options, which call for {:?}",
+        option,
+        value,
+        expected
+    ))]
+    ConflictingOption {
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
 }
 
 #[derive(Clone, Copy, Debug, Derivative, Deserialize, Serialize)]
@@ -22,6 +40,59 @@ pub(crate) enum KafkaCompression {
     Zstd,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub(crate) enum KafkaSaslMechanism {
+    #[serde(rename = "PLAIN")]
+    Plain,
+    #[serde(rename = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[serde(rename = "SCRAM-SHA-512")]
+    ScramSha512,
+}
+
+impl KafkaSaslMechanism {
+    fn as_str(self) -> &'static str {
+        match self {
+            KafkaSaslMechanism::Plain => "PLAIN",
+            KafkaSaslMechanism::ScramSha256 => "SCRAM-SHA-256",
+            KafkaSaslMechanism::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct KafkaSaslConfig {
+    pub enabled: Option<bool>,
+    pub mechanism: Option<KafkaSaslMechanism>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl KafkaSaslConfig {
+    fn apply(&self, options: &mut Options) -> crate::Result<KafkaSaslMechanism> {
+        let mechanism = self.mechanism.ok_or(KafkaError::MissingSaslMechanism)?;
+        // Every mechanism supported so far authenticates with a username and
+        // a password.
+        match (&self.username, &self.password) {
+            (Some(username), Some(password)) => {
+                set(options, "sasl.mechanisms", mechanism.as_str());
+                set(options, "sasl.username", username);
+                set(options, "sasl.password", password);
+                Ok(mechanism)
+            }
+            _ => Err(KafkaError::MissingSaslCredentials {
+                mechanism: mechanism.as_str(),
+            }
+            .into()),
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub(crate) struct KafkaTlsConfig {
     pub enabled: Option<bool>,
@@ -30,22 +101,36 @@ pub(crate) struct KafkaTlsConfig {
 }
 
 impl KafkaTlsConfig {
-    pub(crate) fn apply(&self, client: &mut ClientConfig) -> crate::Result<()> {
-        client.set(
-            "security.protocol",
-            if self.enabled() { "ssl" } else { "plaintext" },
-        );
+    fn apply(&self, options: &mut Options) -> crate::Result<()> {
         if let Some(ref path) = self.options.ca_path {
-            client.set("ssl.ca.location", pathbuf_to_string(&path)?);
-        }
-        if let Some(ref path) = self.options.crt_path {
-            client.set("ssl.certificate.location", pathbuf_to_string(&path)?);
-        }
-        if let Some(ref path) = self.options.key_path {
-            client.set("ssl.keystore.location", pathbuf_to_string(&path)?);
+            set(options, "ssl.ca.location", pathbuf_to_string(&path)?);
         }
-        if let Some(ref pass) = self.options.key_pass {
-            client.set("ssl.keystore.password", pass);
+        match (&self.options.crt_path, &self.options.key_path) {
+            // A certificate without a key is a PKCS#12 archive holding both.
+            (Some(crt_path), None) => {
+                set(
+                    options,
+                    "ssl.keystore.location",
+                    pathbuf_to_string(&crt_path)?,
+                );
+                if let Some(ref pass) = self.options.key_pass {
+                    set(options, "ssl.keystore.password", pass);
+                }
+            }
+            (crt_path, Some(key_path)) => {
+                if let Some(crt_path) = crt_path {
+                    set(
+                        options,
+                        "ssl.certificate.location",
+                        pathbuf_to_string(&crt_path)?,
+                    );
+                }
+                set(options, "ssl.key.location", pathbuf_to_string(&key_path)?);
+                if let Some(ref pass) = self.options.key_pass {
+                    set(options, "ssl.key.password", pass);
+                }
+            }
+            (None, None) => (),
         }
         Ok(())
     }
@@ -55,7 +140,218 @@ impl KafkaTlsConfig {
     }
 }
 
+type Options = HashMap<String, String>;
+
+fn set(options: &mut Options, option: &str, value: &str) {
+    options.insert(option.into(), value.into());
+}
+
+/// Sets up `client` to connect over SASL, SSL, both or neither, as `sasl`
+/// and `tls` say, then sets `librdkafka_options` on top of that.
+///
+/// Without `sasl` or `tls` enabled the librdkafka options are free to set
+/// up security themselves, otherwise they can't contradict them.
+pub(crate) fn apply_client_options(
+    client: &mut ClientConfig,
+    sasl: Option<&KafkaSaslConfig>,
+    tls: Option<&KafkaTlsConfig>,
+    librdkafka_options: Option<&HashMap<String, String>>,
+) -> crate::Result<()> {
+    for (option, value) in client_options(sasl, tls, librdkafka_options)? {
+        client.set(&option, &value);
+    }
+    Ok(())
+}
+
+fn client_options(
+    sasl: Option<&KafkaSaslConfig>,
+    tls: Option<&KafkaTlsConfig>,
+    librdkafka_options: Option<&HashMap<String, String>>,
+) -> crate::Result<Options> {
+    let sasl = sasl.filter(|sasl| sasl.enabled());
+    let tls = tls.filter(|tls| tls.enabled());
+    let mut options = Options::new();
+
+    let protocol = match (sasl.is_some(), tls.is_some()) {
+        (false, false) => "plaintext",
+        (false, true) => "ssl",
+        (true, false) => "sasl_plaintext",
+        (true, true) => "sasl_ssl",
+    };
+    set(&mut options, "security.protocol", protocol);
+
+    let mechanism = match sasl {
+        Some(sasl) => Some(sasl.apply(&mut options)?),
+        None => None,
+    };
+    if let Some(tls) = tls {
+        tls.apply(&mut options)?;
+    }
+
+    for (option, value) in librdkafka_options.into_iter().flatten() {
+        let expected = match option.as_str() {
+            "security.protocol" if sasl.is_some() || tls.is_some() => Some(protocol),
+            "sasl.mechanism" | "sasl.mechanisms" => mechanism.map(KafkaSaslMechanism::as_str),
+            _ => None,
+        };
+        match expected {
+            Some(expected) if !value.eq_ignore_ascii_case(expected) => {
+                return Err(KafkaError::ConflictingOption {
+                    option: option.clone(),
+                    value: value.clone(),
+                    expected,
+                }
+                .into());
+            }
+            _ => set(&mut options, option, value),
+        }
+    }
+
+    Ok(options)
+}
+
 fn pathbuf_to_string(path: &PathBuf) -> crate::Result<&str> {
     path.to_str()
         .ok_or_else(|| KafkaError::InvalidPath { path: path.into() }.into())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build(
+        sasl: Option<&str>,
+        tls: Option<&str>,
+        librdkafka_options: &[(&str, &str)],
+    ) -> crate::Result<Options> {
+        let sasl: Option<KafkaSaslConfig> = sasl.map(|sasl| toml::from_str(sasl).unwrap());
+        let tls: Option<KafkaTlsConfig> = tls.map(|tls| toml::from_str(tls).unwrap());
+        let librdkafka_options: Options = librdkafka_options
+            .iter()
+            .map(|(option, value)| (option.to_string(), value.to_string()))
+            .collect();
+
+        client_options(sasl.as_ref(), tls.as_ref(), Some(&librdkafka_options))
+    }
+
+    fn assert_options(options: &Options, expected: &[(&str, &str)]) {
+        let mut expected = expected
+            .iter()
+            .map(|(option, value)| (option.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        expected.sort();
+        let mut options = options.clone().into_iter().collect::<Vec<_>>();
+        options.sort();
+        assert_eq!(options, expected);
+    }
+
+    const SASL: &str = r#"
+        enabled = true
+        mechanism = "SCRAM-SHA-512"
+        username = "user"
+        password = "secret"
+    "#;
+
+    const TLS: &str = r#"
+        enabled = true
+        ca_path = "ca.crt"
+        crt_path = "client.crt"
+        key_path = "client.key"
+        key_pass = "keypass"
+    "#;
+
+    #[test]
+    fn kafka_plaintext() {
+        let options = build(None, None, &[("client.id", "test")]).unwrap();
+        assert_options(
+            &options,
+            &[("security.protocol", "plaintext"), ("client.id", "test")],
+        );
+
+        // Disabled options are ignored.
+        let options = build(Some("enabled = false"), Some("enabled = false"), &[]);
+        assert_options(&options.unwrap(), &[("security.protocol", "plaintext")]);
+    }
+
+    #[test]
+    fn kafka_sasl_plaintext() {
+        let options = build(Some(SASL), None, &[]).unwrap();
+        assert_options(
+            &options,
+            &[
+                ("security.protocol", "sasl_plaintext"),
+                ("sasl.mechanisms", "SCRAM-SHA-512"),
+                ("sasl.username", "user"),
+                ("sasl.password", "secret"),
+            ],
+        );
+    }
+
+    #[test]
+    fn kafka_ssl() {
+        let options = build(None, Some(TLS), &[]).unwrap();
+        assert_options(
+            &options,
+            &[
+                ("security.protocol", "ssl"),
+                ("ssl.ca.location", "ca.crt"),
+                ("ssl.certificate.location", "client.crt"),
+                ("ssl.key.location", "client.key"),
+                ("ssl.key.password", "keypass"),
+            ],
+        );
+
+        let pkcs12 = "enabled = true\ncrt_path = \"client.p12\"\nkey_pass = \"keypass\"";
+        let options = build(None, Some(pkcs12), &[]).unwrap();
+        assert_options(
+            &options,
+            &[
+                ("security.protocol", "ssl"),
+                ("ssl.keystore.location", "client.p12"),
+                ("ssl.keystore.password", "keypass"),
+            ],
+        );
+    }
+
+    #[test]
+    fn kafka_sasl_ssl() {
+        let sasl =
+            "enabled = true\nmechanism = \"PLAIN\"\nusername = \"user\"\npassword = \"secret\"";
+        let tls = "enabled = true\nca_path = \"ca.crt\"";
+        let options = build(Some(sasl), Some(tls), &[("client.id", "test")]);
+        assert_options(
+            &options.unwrap(),
+            &[
+                ("security.protocol", "sasl_ssl"),
+                ("sasl.mechanisms", "PLAIN"),
+                ("sasl.username", "user"),
+                ("sasl.password", "secret"),
+                ("ssl.ca.location", "ca.crt"),
+                ("client.id", "test"),
+            ],
+        );
+    }
+
+    #[test]
+    fn kafka_librdkafka_options_set_up_security_alone() {
+        let librdkafka_options = [
+            ("security.protocol", "sasl_ssl"),
+            ("sasl.mechanisms", "PLAIN"),
+        ];
+        let options = build(None, None, &librdkafka_options).unwrap();
+        assert_options(&options, &librdkafka_options);
+    }
+
+    #[test]
+    fn kafka_invalid_security_options() {
+        assert!(toml::from_str::<KafkaSaslConfig>("mechanism = \"GSSAPI\"").is_err());
+        assert!(build(Some("enabled = true"), None, &[]).is_err());
+        let no_password = "enabled = true\nmechanism = \"PLAIN\"\nusername = \"user\"";
+        assert!(build(Some(no_password), None, &[]).is_err());
+
+        assert!(build(Some(SASL), None, &[("security.protocol", "ssl")]).is_err());
+        assert!(build(None, Some(TLS), &[("security.protocol", "plaintext")]).is_err());
+        assert!(build(Some(SASL), None, &[("sasl.mechanism", "PLAIN")]).is_err());
+        assert!(build(Some(SASL), None, &[("security.protocol", "SASL_PLAINTEXT")]).is_ok());
+    }
+}
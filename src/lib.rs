@@ -9,6 +9,9 @@ extern crate derivative;
 #[global_allocator]
 static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
+#[cfg(any(feature = "sources-amqp", feature = "sinks-amqp"))]
+pub mod amqp;
+pub mod api;
 pub mod buffers;
 pub mod conditions;
 pub mod config_paths;
@@ -18,10 +21,15 @@ pub mod expiring_hash_map;
 pub mod generate;
 #[macro_use]
 pub mod internal_events;
+#[cfg(feature = "leveldb")]
+pub mod inspect_buffer;
 #[cfg(feature = "rdkafka")]
 pub mod kafka;
 pub mod list;
+pub mod logfmt;
 pub mod metrics;
+#[cfg(any(feature = "sources-mqtt", feature = "sinks-mqtt"))]
+pub mod mqtt;
 pub mod region;
 pub mod runtime;
 pub mod serde;
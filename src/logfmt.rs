@@ -0,0 +1,232 @@
+//! Reading and writing [logfmt](https://brandur.org/logfmt) lines, the
+//! `key=value` pairs used by the `logfmt_parser` transform and the `logfmt`
+//! sink encoding.
+//!
+//! Nested fields are flattened into their field path, like `request.path` or
+//! `tags[0]`, which inserting the parsed pairs into an event reconstructs.
+
+use crate::event::{LogEvent, Value};
+use std::fmt::Write;
+
+/// Encodes every field of `log` as a `key=value` pair, without a trailing
+/// newline.
+pub fn encode(log: &LogEvent) -> String {
+    let mut line = String::new();
+    for (key, value) in log.all_fields() {
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        write_key(&mut line, &key);
+        line.push('=');
+        if let Value::Null = value {
+            continue;
+        }
+        write_value(&mut line, &value.to_string_lossy());
+    }
+    line
+}
+
+/// Keys can't be quoted, so characters that would end them are replaced.
+fn write_key(line: &mut String, key: &str) {
+    line.extend(key.chars().map(|c| {
+        if c <= ' ' || c == '=' || c == '"' {
+            '_'
+        } else {
+            c
+        }
+    }));
+}
+
+fn write_value(line: &mut String, value: &str) {
+    if !value.chars().any(|c| c <= ' ' || c == '=' || c == '"') {
+        line.push_str(value);
+        return;
+    }
+
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => {
+                write!(line, "\\u{:04x}", c as u32).expect("Writing to String can't fail")
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Parses the `key=value` pairs of `line` in order, duplicates included.
+/// Words without a value, like the free text around pairs, are skipped.
+pub fn parse(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '=' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        if chars.peek() != Some(&'=') {
+            continue;
+        }
+        chars.next();
+        if key.is_empty() {
+            continue;
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') | None => break,
+                    Some('\\') => unescape(&mut chars, &mut value),
+                    Some(c) => value.push(c),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+
+        pairs.push((key, value));
+    }
+
+    pairs
+}
+
+/// Reads the escape sequence following a backslash in a quoted value.
+/// Unknown sequences are kept as they are.
+fn unescape(chars: &mut impl Iterator<Item = char>, value: &mut String) {
+    match chars.next() {
+        Some('n') => value.push('\n'),
+        Some('r') => value.push('\r'),
+        Some('t') => value.push('\t'),
+        Some('u') => {
+            let code = chars.take(4).collect::<String>();
+            match u32::from_str_radix(&code, 16)
+                .ok()
+                .and_then(std::char::from_u32)
+            {
+                Some(c) => value.push(c),
+                None => {
+                    value.push_str("\\u");
+                    value.push_str(&code);
+                }
+            }
+        }
+        Some(c @ '"') | Some(c @ '\\') => value.push(c),
+        Some(c) => {
+            value.push('\\');
+            value.push(c);
+        }
+        None => value.push('\\'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.into(), value.into()))
+            .collect()
+    }
+
+    #[test]
+    fn logfmt_parses_bare_and_quoted_values() {
+        assert_eq!(
+            parse(r#"at=info path="/a b" msg="say \"hi\"\n" empty= fwd="1.2.3.4""#),
+            pairs(&[
+                ("at", "info"),
+                ("path", "/a b"),
+                ("msg", "say \"hi\"\n"),
+                ("empty", ""),
+                ("fwd", "1.2.3.4"),
+            ])
+        );
+    }
+
+    #[test]
+    fn logfmt_skips_words_without_values() {
+        assert_eq!(
+            parse("info | Sent 200 in 54.2ms duration=54.2ms =oops status=200 status=304"),
+            pairs(&[("duration", "54.2ms"), ("status", "200"), ("status", "304"),])
+        );
+    }
+
+    #[test]
+    fn logfmt_quotes_values_when_needed() {
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+        log.insert("bare", "a\\b");
+        log.insert("empty", "");
+        log.insert("equals", "a=b");
+        log.insert("quotes", r#"say "hi""#);
+        log.insert("lines", "one\ntwo\u{7}");
+        log.insert("null", Value::Null);
+        log.insert("spaces", "a b");
+
+        assert_eq!(
+            encode(event.as_log()),
+            r#"bare=a\b empty= equals="a=b" lines="one\ntwo\u0007" null= quotes="say \"hi\"" spaces="a b""#
+        );
+    }
+
+    #[test]
+    fn logfmt_flattens_nested_fields() {
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+        log.insert("request.path", "/");
+        log.insert("request.status", 200);
+        log.insert("tags", vec![Value::from("a"), Value::from(true)]);
+
+        assert_eq!(
+            encode(event.as_log()),
+            "request.path=/ request.status=200 tags[0]=a tags[1]=true"
+        );
+    }
+
+    #[test]
+    fn logfmt_round_trips() {
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+        log.insert("message", "a \"quoted\" \\ value\twith\r\nbreaks");
+        log.insert("request.path", "/a=b");
+        log.insert("request.query", "");
+        log.insert("key with spaces", "x");
+
+        let mut parsed = Event::new_empty_log();
+        for (key, value) in parse(&encode(event.as_log())) {
+            parsed.as_mut_log().insert(key, value);
+        }
+
+        event.as_mut_log().remove(&"key with spaces".into());
+        event.as_mut_log().insert("key_with_spaces", "x");
+        assert_eq!(parsed.as_log(), event.as_log());
+    }
+}
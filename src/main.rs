@@ -11,7 +11,11 @@ use structopt::{clap::AppSettings, StructOpt};
 #[cfg(unix)]
 use tokio_signal::unix::{Signal, SIGHUP, SIGINT, SIGQUIT, SIGTERM};
 use topology::Config;
-use vector::{config_paths, event, generate, list, metrics, runtime, topology, trace, unit_test};
+#[cfg(feature = "leveldb")]
+use vector::inspect_buffer;
+use vector::{
+    api, config_paths, event, generate, list, metrics, runtime, sinks, topology, trace, unit_test,
+};
 
 #[derive(StructOpt, Debug)]
 #[structopt(rename_all = "kebab-case")]
@@ -52,7 +56,8 @@ struct RootOpts {
     #[structopt(short, long, parse(from_occurrences))]
     quiet: u8,
 
-    /// Set the logging format. Options are "text" or "json". Defaults to "text".
+    /// Set the logging format. Options are "text" or "json". Defaults to the `LOG_FORMAT`
+    /// environment variable, or "text".
     #[structopt(long)]
     log_format: Option<LogFormat>,
 
@@ -71,6 +76,12 @@ struct RootOpts {
     /// Watch for changes in configuration file, and reload accordingly.
     #[structopt(short, long)]
     watch_config: bool,
+
+    /// Also collapse internal warnings and errors repeated from the same place within this many
+    /// seconds into a single line with the number of repeats. By default only messages that set
+    /// their own rate limit are collapsed.
+    #[structopt(long, default_value = "0")]
+    internal_log_rate_limit: u64,
 }
 
 #[derive(StructOpt, Debug)]
@@ -88,6 +99,10 @@ enum SubCommand {
     /// Run Vector config unit tests, then exit. This command is experimental and therefore subject to change.
     /// For guidance on how to write unit tests check out: https://vector.dev/docs/setup/guides/unit-testing/
     Test(unit_test::Opts),
+
+    /// Print the events held in a sink's disk buffer, then exit. The buffer is left as it is.
+    #[cfg(feature = "leveldb")]
+    InspectBuffer(inspect_buffer::Opts),
 }
 
 #[derive(StructOpt, Debug)]
@@ -198,12 +213,23 @@ fn main() {
         Color::Never => false,
     };
 
-    let json = match &opts.log_format.unwrap_or(LogFormat::Text) {
+    let log_format = match opts.log_format.clone() {
+        Some(format) => format,
+        None => match std::env::var("LOG_FORMAT") {
+            Ok(format) => format.parse().unwrap_or_else(|error| {
+                eprintln!("Invalid LOG_FORMAT: {}", error);
+                std::process::exit(exitcode::CONFIG);
+            }),
+            Err(_) => LogFormat::Text,
+        },
+    };
+
+    let json = match log_format {
         LogFormat::Text => false,
         LogFormat::Json => true,
     };
 
-    trace::init(color, json, levels.as_str());
+    trace::init(color, json, levels.as_str(), opts.internal_log_rate_limit);
 
     metrics::init().expect("metrics initialization failed");
 
@@ -213,6 +239,8 @@ fn main() {
             SubCommand::List(l) => list::cmd(&l),
             SubCommand::Test(t) => unit_test::cmd(&t),
             SubCommand::Generate(g) => generate::cmd(&g),
+            #[cfg(feature = "leveldb")]
+            SubCommand::InspectBuffer(i) => inspect_buffer::cmd(&i),
         })
     });
 
@@ -259,6 +287,11 @@ fn main() {
     event::LOG_SCHEMA
         .set(config.global.log_schema.clone())
         .expect("Couldn't set schema");
+    if let Some(budget) = config.global.retry_budget {
+        sinks::util::retries::GLOBAL_RETRY_BUDGET
+            .set(sinks::util::retries::RetryBudget::new(budget))
+            .expect("Couldn't set retry budget");
+    }
 
     let mut rt = {
         let threads = opts.threads.unwrap_or(max(1, num_cpus::get()));
@@ -286,6 +319,7 @@ fn main() {
         std::process::exit(exitcode::OK);
     }
 
+    let api_options = config.global.api.clone();
     let result = topology::start_validated(config, pieces, &mut rt, opts.require_healthy);
     let (topology, mut graceful_crash) = result.unwrap_or_else(|| {
         std::process::exit(exitcode::CONFIG);
@@ -296,6 +330,18 @@ fn main() {
         std::process::exit(exitcode::OK);
     }
 
+    if api_options.enabled {
+        match api::serve(&api_options, topology.taps(), topology.graph()) {
+            Ok(server) => {
+                rt.spawn(server);
+            }
+            Err(error) => {
+                error!(message = "Unable to start the API server.", %error);
+                std::process::exit(exitcode::CONFIG);
+            }
+        }
+    }
+
     #[cfg(unix)]
     {
         let mut topology = topology;
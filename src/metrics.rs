@@ -16,3 +16,11 @@ pub fn init() -> crate::Result<()> {
 
     Ok(())
 }
+
+/// Installs the metrics system for tests, which may all ask for it but only
+/// get it set up once per process.
+#[cfg(test)]
+pub fn init_test() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| init().expect("metrics initialization failed"));
+}
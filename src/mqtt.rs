@@ -0,0 +1,86 @@
+use rumqttc::{MqttOptions, QoS};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Derivative, Deserialize, Serialize)]
+#[derivative(Default)]
+pub(crate) struct MqttConfig {
+    #[derivative(Default(value = "default_host()"))]
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[derivative(Default(value = "default_port()"))]
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    #[derivative(Default(value = "default_client_id()"))]
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    #[derivative(Default(value = "default_keep_alive_secs()"))]
+    #[serde(default = "default_keep_alive_secs")]
+    pub keep_alive_secs: u16,
+    /// Without a clean session the broker keeps subscriptions and queued
+    /// QoS 1/2 messages for `client_id` while we are disconnected.
+    #[derivative(Default(value = "true"))]
+    #[serde(default = "default_clean_session")]
+    pub clean_session: bool,
+    #[derivative(Default(value = "default_reconnect_secs()"))]
+    #[serde(default = "default_reconnect_secs")]
+    pub reconnect_secs: u64,
+}
+
+fn default_host() -> String {
+    "127.0.0.1".into()
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    "vector".into()
+}
+
+fn default_keep_alive_secs() -> u16 {
+    60
+}
+
+fn default_clean_session() -> bool {
+    true
+}
+
+fn default_reconnect_secs() -> u64 {
+    5
+}
+
+#[derive(Clone, Copy, Debug, Derivative, Deserialize, Serialize, Eq, PartialEq)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MqttQos {
+    AtMostOnce,
+    #[derivative(Default)]
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl From<MqttQos> for QoS {
+    fn from(qos: MqttQos) -> Self {
+        match qos {
+            MqttQos::AtMostOnce => QoS::AtMostOnce,
+            MqttQos::AtLeastOnce => QoS::AtLeastOnce,
+            MqttQos::ExactlyOnce => QoS::ExactlyOnce,
+        }
+    }
+}
+
+impl MqttConfig {
+    pub(crate) fn options(&self) -> MqttOptions {
+        let mut options = MqttOptions::new(self.client_id.clone(), self.host.clone(), self.port);
+        options
+            .set_keep_alive(self.keep_alive_secs)
+            .set_clean_session(self.clean_session);
+        if let Some(user) = &self.user {
+            options.set_credentials(user, self.password.as_deref().unwrap_or(""));
+        }
+        options
+    }
+}
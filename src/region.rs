@@ -4,7 +4,18 @@ use http::{uri::InvalidUri, Uri};
 use rusoto_core::{region::ParseRegionError, Region};
 use serde::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    env,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+const IMDS_ENDPOINT: &str = "http://169.254.169.254";
+// Metadata services answer within milliseconds, this only bounds how long
+// we wait when not running on AWS at all.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 #[serde(default)]
@@ -37,15 +48,17 @@ pub enum ParseError {
     RegionParseError { source: ParseRegionError },
     #[snafu(display("Only one of 'region' or 'endpoint' can be specified"))]
     BothRegionAndEndpoint,
-    #[snafu(display("Must set either 'region' or 'endpoint'"))]
+    #[snafu(display(
+        "Must set either 'region' or 'endpoint', no region could be detected from the environment"
+    ))]
     MissingRegionAndEndpoint,
+    #[snafu(display("'region' is {} but the resource is in {}", configured, region))]
+    ConflictingRegion { configured: String, region: String },
 }
 
-impl TryFrom<&RegionOrEndpoint> for Region {
-    type Error = ParseError;
-
-    fn try_from(r: &RegionOrEndpoint) -> Result<Self, Self::Error> {
-        match (&r.region, &r.endpoint) {
+impl RegionOrEndpoint {
+    fn resolve(&self, detector: &RegionDetector) -> Result<Region, ParseError> {
+        match (&self.region, &self.endpoint) {
             (Some(region), None) => region.parse().context(RegionParseError),
             (None, Some(endpoint)) => endpoint
                 .parse::<Uri>()
@@ -55,9 +68,44 @@ impl TryFrom<&RegionOrEndpoint> for Region {
                 })
                 .context(EndpointParseError),
             (Some(_), Some(_)) => Err(ParseError::BothRegionAndEndpoint),
-            (None, None) => Err(ParseError::MissingRegionAndEndpoint),
+            (None, None) => detector
+                .detect()
+                .ok_or(ParseError::MissingRegionAndEndpoint)?
+                .parse()
+                .context(RegionParseError),
         }
     }
+
+    /// Resolves to `region` when it is already fixed by the resource being
+    /// accessed, such as by an ARN. An `endpoint` still decides where requests
+    /// go, but a configured `region` has to agree.
+    pub fn resolve_for(&self, region: &str) -> Result<Region, ParseError> {
+        match (&self.region, &self.endpoint) {
+            (Some(_), Some(_)) => Err(ParseError::BothRegionAndEndpoint),
+            (Some(configured), None) if configured != region => {
+                Err(ParseError::ConflictingRegion {
+                    configured: configured.clone(),
+                    region: region.into(),
+                })
+            }
+            (_, Some(endpoint)) => endpoint
+                .parse::<Uri>()
+                .map(|_| Region::Custom {
+                    name: region.into(),
+                    endpoint: endpoint.into(),
+                })
+                .context(EndpointParseError),
+            (_, None) => region.parse().context(RegionParseError),
+        }
+    }
+}
+
+impl TryFrom<&RegionOrEndpoint> for Region {
+    type Error = ParseError;
+
+    fn try_from(r: &RegionOrEndpoint) -> Result<Self, Self::Error> {
+        r.resolve(&RegionDetector::from_env())
+    }
 }
 
 impl TryFrom<RegionOrEndpoint> for Region {
@@ -67,6 +115,150 @@ impl TryFrom<RegionOrEndpoint> for Region {
     }
 }
 
+/// Finds the region when none is configured, trying in order the
+/// `AWS_REGION` and `AWS_DEFAULT_REGION` environment variables, the ECS task
+/// metadata endpoint and the EC2 instance metadata service (IMDS).
+#[derive(Debug, Default)]
+struct RegionDetector {
+    env_region: Option<String>,
+    ecs_metadata_uri: Option<String>,
+    imds_endpoint: Option<String>,
+}
+
+impl RegionDetector {
+    fn from_env() -> Self {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+        let imds_disabled = var("AWS_EC2_METADATA_DISABLED")
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Self {
+            env_region: var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION")),
+            ecs_metadata_uri: var("ECS_CONTAINER_METADATA_URI_V4")
+                .or_else(|| var("ECS_CONTAINER_METADATA_URI")),
+            imds_endpoint: if imds_disabled {
+                None
+            } else {
+                Some(
+                    var("AWS_EC2_METADATA_SERVICE_ENDPOINT")
+                        .unwrap_or_else(|| IMDS_ENDPOINT.into()),
+                )
+            },
+        }
+    }
+
+    fn detect(&self) -> Option<String> {
+        if let Some(region) = &self.env_region {
+            return Some(region.clone());
+        }
+
+        let region = self
+            .ecs_metadata_uri
+            .as_ref()
+            .and_then(|uri| ecs_region(uri))
+            .or_else(|| self.imds_endpoint.as_ref().and_then(|uri| imds_region(uri)));
+
+        if let Some(region) = &region {
+            info!(message = "detected AWS region from instance metadata.", %region);
+        }
+        region
+    }
+}
+
+/// The region of the task's ARN, `arn:aws:ecs:<region>:<account>:task/...`.
+fn ecs_region(metadata_uri: &str) -> Option<String> {
+    let response = metadata_request(&format!("{}/task", metadata_uri), "GET", &[])
+        .map_err(|error| debug!(message = "ECS task metadata unavailable.", %error))
+        .ok()?;
+    let task: serde_json::Value = serde_json::from_str(&response).ok()?;
+
+    task.get("TaskARN")?
+        .as_str()?
+        .split(':')
+        .nth(3)
+        .filter(|region| !region.is_empty())
+        .map(Into::into)
+}
+
+fn imds_region(endpoint: &str) -> Option<String> {
+    // IMDSv2 requires a session token, fall back to IMDSv1 when the
+    // service doesn't hand one out.
+    let token = metadata_request(
+        &format!("{}/latest/api/token", endpoint),
+        "PUT",
+        &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+    );
+
+    let region_uri = format!("{}/latest/meta-data/placement/region", endpoint);
+    let response = match token {
+        Ok(token) => metadata_request(
+            &region_uri,
+            "GET",
+            &[("X-aws-ec2-metadata-token", token.trim())],
+        ),
+        Err(error) if error.kind() == io::ErrorKind::Other => {
+            metadata_request(&region_uri, "GET", &[])
+        }
+        // Not reachable at all, most likely not running on EC2.
+        Err(error) => Err(error),
+    };
+
+    response
+        .map_err(|error| debug!(message = "EC2 instance metadata unavailable.", %error))
+        .ok()
+        .map(|region| region.trim().to_owned())
+        .filter(|region| !region.is_empty())
+}
+
+/// A bare bones blocking HTTP/1.0 request for talking to the metadata
+/// services while sinks are being built. Non-200 responses are reported as
+/// `io::ErrorKind::Other`.
+fn metadata_request(uri: &str, method: &str, headers: &[(&str, &str)]) -> io::Result<String> {
+    let uri = uri
+        .parse::<Uri>()
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    let host = uri
+        .host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or(80);
+    let path = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::AddrNotAvailable, "no address"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, METADATA_TIMEOUT)?;
+    stream.set_read_timeout(Some(METADATA_TIMEOUT))?;
+    stream.set_write_timeout(Some(METADATA_TIMEOUT))?;
+
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: {}\r\n", method, path, host);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("Content-Length: 0\r\n\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let mut parts = response.splitn(2, "\r\n\r\n");
+    let head = parts.next().unwrap_or("");
+    let body = parts.next().unwrap_or("");
+    let status = head.split(' ').nth(1).unwrap_or("");
+
+    if status == "200" {
+        Ok(body.to_owned())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} {} returned status {:?}", method, uri, status),
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,10 +320,146 @@ mod tests {
         )
         .unwrap();
 
-        let region: Result<Region, ParseError> = config.inner.region.try_into();
+        let region = config.inner.region.resolve(&RegionDetector::default());
         match region {
             Err(ParseError::MissingRegionAndEndpoint) => {}
             other => panic!("assertion failed, wrong result {:?}", other),
         }
     }
+
+    #[test]
+    fn region_fixed_by_resource() {
+        let region = RegionOrEndpoint::default().resolve_for("eu-west-1");
+        assert_eq!(region.unwrap(), Region::EuWest1);
+
+        let region = RegionOrEndpoint::with_region("eu-west-1".into()).resolve_for("eu-west-1");
+        assert_eq!(region.unwrap(), Region::EuWest1);
+
+        let region = RegionOrEndpoint::with_endpoint("http://localhost:9000".into())
+            .resolve_for("eu-west-1");
+        assert_eq!(
+            region.unwrap(),
+            Region::Custom {
+                name: "eu-west-1".into(),
+                endpoint: "http://localhost:9000".into(),
+            }
+        );
+
+        let region = RegionOrEndpoint::with_region("us-east-1".into()).resolve_for("eu-west-1");
+        match region {
+            Err(ParseError::ConflictingRegion { .. }) => {}
+            other => panic!("assertion failed, wrong result {:?}", other),
+        }
+    }
+
+    /// Serves one canned response per expected request, in order, and
+    /// hands back the requests it received.
+    fn metadata_server(
+        responses: Vec<&'static str>,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut request = vec![0; 1024];
+                    let n = stream.read(&mut request).unwrap();
+                    stream.write_all(response.as_bytes()).unwrap();
+                    String::from_utf8_lossy(&request[..n]).into_owned()
+                })
+                .collect()
+        });
+
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn region_detected_from_env_first() {
+        let detector = RegionDetector {
+            env_region: Some("eu-west-1".into()),
+            ecs_metadata_uri: Some("http://127.0.0.1:1".into()),
+            imds_endpoint: Some("http://127.0.0.1:1".into()),
+        };
+
+        let region = RegionOrEndpoint::default().resolve(&detector).unwrap();
+        assert_eq!(region, Region::EuWest1);
+    }
+
+    #[test]
+    fn region_detected_from_imds_v2() {
+        let (endpoint, server) = metadata_server(vec![
+            "HTTP/1.0 200 OK\r\n\r\nsecret-token",
+            "HTTP/1.0 200 OK\r\n\r\nus-west-2",
+        ]);
+        let detector = RegionDetector {
+            imds_endpoint: Some(endpoint),
+            ..Default::default()
+        };
+
+        let region = RegionOrEndpoint::default().resolve(&detector).unwrap();
+        assert_eq!(region, Region::UsWest2);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("PUT /latest/api/token "));
+        assert!(requests[0].contains("X-aws-ec2-metadata-token-ttl-seconds: "));
+        assert!(requests[1].starts_with("GET /latest/meta-data/placement/region "));
+        assert!(requests[1].contains("X-aws-ec2-metadata-token: secret-token\r\n"));
+    }
+
+    #[test]
+    fn region_detected_from_imds_v1() {
+        let (endpoint, server) = metadata_server(vec![
+            "HTTP/1.0 405 Method Not Allowed\r\n\r\n",
+            "HTTP/1.0 200 OK\r\n\r\nus-west-2",
+        ]);
+        let detector = RegionDetector {
+            imds_endpoint: Some(endpoint),
+            ..Default::default()
+        };
+
+        let region = RegionOrEndpoint::default().resolve(&detector).unwrap();
+        assert_eq!(region, Region::UsWest2);
+
+        let requests = server.join().unwrap();
+        assert!(!requests[1].contains("X-aws-ec2-metadata-token"));
+    }
+
+    #[test]
+    fn region_detected_from_ecs_task_arn() {
+        let (endpoint, server) = metadata_server(vec![
+            "HTTP/1.0 200 OK\r\n\r\n\
+             {\"TaskARN\": \"arn:aws:ecs:ap-southeast-2:012345678910:task/9781c248\"}",
+        ]);
+        let detector = RegionDetector {
+            ecs_metadata_uri: Some(format!("{}/v4/abcd", endpoint)),
+            imds_endpoint: Some("http://127.0.0.1:1".into()),
+            ..Default::default()
+        };
+
+        let region = RegionOrEndpoint::default().resolve(&detector).unwrap();
+        assert_eq!(region, Region::ApSoutheast2);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /v4/abcd/task "));
+    }
+
+    #[test]
+    fn region_detection_gives_up_on_unresponsive_imds() {
+        // Accepts the connection but never answers.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let detector = RegionDetector {
+            imds_endpoint: Some(format!("http://{}", listener.local_addr().unwrap())),
+            ..Default::default()
+        };
+
+        let start = std::time::Instant::now();
+        match RegionOrEndpoint::default().resolve(&detector) {
+            Err(ParseError::MissingRegionAndEndpoint) => {}
+            other => panic!("assertion failed, wrong result {:?}", other),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
 }
@@ -3,6 +3,8 @@ use std::io;
 use std::pin::Pin;
 use tokio::task::JoinHandle;
 use tokio_compat::runtime::{Builder, Runtime as TokioRuntime, TaskExecutor as TokioTaskExecutor};
+use tracing::Span;
+use tracing_futures::Instrument;
 
 pub struct Runtime {
     rt: TokioRuntime,
@@ -83,8 +85,10 @@ pub struct TaskExecutor {
 }
 
 impl TaskExecutor {
+    /// Spawns `f` in the span it's spawned from, so whatever it logs keeps
+    /// the context of the component that spawned it.
     pub fn spawn(&self, f: impl Future<Item = (), Error = ()> + Send + 'static) {
-        self.execute(f).unwrap()
+        self.execute(f.instrument(Span::current())).unwrap()
     }
 
     pub fn spawn_std(&self, f: impl std::future::Future<Output = ()> + Send + 'static) {
@@ -108,7 +112,7 @@ impl tokio01::executor::Executor for TaskExecutor {
         &mut self,
         fut: Box<dyn Future<Item = (), Error = ()> + Send + 'static>,
     ) -> Result<(), tokio01::executor::SpawnError> {
-        Ok(self.inner.spawn(fut))
+        Ok(self.inner.spawn(fut.instrument(Span::current())))
     }
 }
 
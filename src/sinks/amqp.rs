@@ -0,0 +1,401 @@
+use crate::{
+    amqp::AmqpConfig,
+    event::{self, Event},
+    internal_events::{
+        AmqpEventSent, AmqpPublishFailed, AmqpPublishNacked, AmqpPublishReturned,
+        AmqpRoutingKeyMissing,
+    },
+    sinks::util::{
+        encoding::{EncodingConfig, EncodingConfiguration},
+        StreamSink,
+    },
+    template::Template,
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use async_trait::async_trait;
+use futures::{pin_mut, stream::Stream, FutureExt, StreamExt, TryFutureExt};
+use lapin::{
+    options::{BasicPublishOptions, ConfirmSelectOptions, ExchangeDeclareOptions},
+    publisher_confirm::Confirmation,
+    types::FieldTable,
+    BasicProperties, Channel, Connection, ExchangeKind,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::streaming_sink::{self, StreamingSink};
+
+/// AMQP delivery mode marking a message as persistent.
+const PERSISTENT: u8 = 2;
+
+#[derive(Clone, Debug, Derivative, Deserialize, Serialize)]
+#[derivative(Default)]
+pub struct AmqpSinkConfig {
+    #[serde(flatten)]
+    connection: AmqpConfig,
+    exchange: String,
+    #[serde(default)]
+    routing_key: Option<String>,
+    encoding: EncodingConfig<Encoding>,
+    #[derivative(Default(value = "true"))]
+    #[serde(default = "default_true")]
+    durable: bool,
+    #[serde(default)]
+    mandatory: bool,
+    #[derivative(Default(value = "true"))]
+    #[serde(default = "default_true")]
+    confirm: bool,
+    #[derivative(Default(value = "default_reconnect_secs()"))]
+    #[serde(default = "default_reconnect_secs")]
+    reconnect_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_reconnect_secs() -> u64 {
+    5
+}
+
+#[derive(Clone, Copy, Debug, Derivative, Deserialize, Serialize, Eq, PartialEq)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[derivative(Default)]
+    Text,
+    Json,
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<AmqpSinkConfig>("amqp")
+}
+
+#[typetag::serde(name = "amqp")]
+impl SinkConfig for AmqpSinkConfig {
+    fn build(&self, mut cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let sink = AmqpSink {
+            config: self.clone(),
+            routing_key: Template::from(self.routing_key.as_deref().unwrap_or("")),
+            connection: None,
+        };
+        let sink = streaming_sink::compat::adapt_to_topology(&mut cx, sink);
+        let sink = StreamSink::new(sink, cx.acker());
+
+        let healthcheck = healthcheck(self.clone()).boxed().compat();
+
+        Ok((Box::new(sink), Box::new(healthcheck)))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "amqp"
+    }
+}
+
+async fn healthcheck(config: AmqpSinkConfig) -> crate::Result<()> {
+    let (connection, channel) = config.connection.connect().await?;
+    // The default exchange always exists and can't be declared.
+    if !config.exchange.is_empty() {
+        channel
+            .exchange_declare(
+                &config.exchange,
+                ExchangeKind::Direct,
+                ExchangeDeclareOptions {
+                    passive: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+    }
+    let _ = connection.close(200, "OK").await;
+    Ok(())
+}
+
+enum PublishError {
+    /// The channel is unusable and has to be reopened before retrying.
+    Channel(lapin::Error),
+    /// The broker refused the message, it may be retried as is.
+    Nacked,
+    /// The message could not be routed to any queue.
+    Returned,
+}
+
+struct AmqpSink {
+    config: AmqpSinkConfig,
+    routing_key: Template,
+    connection: Option<(Connection, Channel)>,
+}
+
+impl AmqpSink {
+    async fn channel(&mut self) -> Result<Channel, lapin::Error> {
+        if let Some((_, channel)) = &self.connection {
+            if channel.status().connected() {
+                return Ok(channel.clone());
+            }
+        }
+        self.connection = None;
+
+        let (connection, channel) = self.config.connection.connect().await?;
+        if self.config.confirm {
+            channel
+                .confirm_select(ConfirmSelectOptions::default())
+                .await?;
+        }
+        self.connection = Some((connection, channel.clone()));
+        Ok(channel)
+    }
+
+    async fn publish(&mut self, routing_key: &str, payload: Vec<u8>) -> Result<(), PublishError> {
+        let channel = self.channel().await.map_err(PublishError::Channel)?;
+
+        let mut properties = BasicProperties::default();
+        if self.config.durable {
+            properties = properties.with_delivery_mode(PERSISTENT);
+        }
+        let options = BasicPublishOptions {
+            mandatory: self.config.mandatory,
+            ..Default::default()
+        };
+
+        let confirm = channel
+            .basic_publish(
+                &self.config.exchange,
+                routing_key,
+                options,
+                payload,
+                properties,
+            )
+            .await
+            .map_err(PublishError::Channel)?;
+
+        match confirm.await.map_err(PublishError::Channel)? {
+            // Unroutable mandatory messages are returned ahead of the ack.
+            Confirmation::Ack(Some(_)) => Err(PublishError::Returned),
+            Confirmation::Ack(None) | Confirmation::NotRequested => Ok(()),
+            Confirmation::Nack(_) => Err(PublishError::Nacked),
+        }
+    }
+
+    /// Publishes a message, reopening the channel and retrying until it
+    /// has been accepted by the broker.
+    async fn send(&mut self, routing_key: &str, payload: Vec<u8>) {
+        let byte_size = payload.len();
+        let reconnect = Duration::from_secs(self.config.reconnect_secs);
+
+        loop {
+            match self.publish(routing_key, payload.clone()).await {
+                Ok(()) => {
+                    emit!(AmqpEventSent { byte_size });
+                    return;
+                }
+                Err(PublishError::Returned) => {
+                    emit!(AmqpPublishReturned {
+                        exchange: &self.config.exchange,
+                        routing_key,
+                    });
+                    return;
+                }
+                Err(PublishError::Nacked) => emit!(AmqpPublishNacked),
+                Err(PublishError::Channel(error)) => {
+                    emit!(AmqpPublishFailed { error });
+                    self.connection = None;
+                }
+            }
+            tokio::time::delay_for(reconnect).await;
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingSink for AmqpSink {
+    async fn run(
+        &mut self,
+        input: impl Stream<Item = Event> + Send + Sync + 'static,
+    ) -> crate::Result<()> {
+        pin_mut!(input);
+        while let Some(event) = input.next().await {
+            let routing_key = match self.routing_key.render_string(&event) {
+                Ok(routing_key) => routing_key,
+                Err(missing_keys) => {
+                    emit!(AmqpRoutingKeyMissing { missing_keys });
+                    continue;
+                }
+            };
+            let payload = encode_event(event, &self.config.encoding);
+            self.send(&routing_key, payload).await;
+        }
+
+        if let Some((connection, _)) = self.connection.take() {
+            let _ = connection.close(200, "OK").await;
+        }
+        Ok(())
+    }
+}
+
+fn encode_event(mut event: Event, encoding: &EncodingConfig<Encoding>) -> Vec<u8> {
+    encoding.apply_rules(&mut event);
+    let log = event.into_log();
+    match encoding.codec {
+        Encoding::Json => serde_json::to_vec(&log).expect("Unable to encode event as JSON."),
+        Encoding::Text => log
+            .get(&event::log_schema().message_key())
+            .map(|v| v.as_bytes().to_vec())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn amqp_encode_event_text() {
+        let message = "hello world".to_string();
+        let bytes = encode_event(
+            message.clone().into(),
+            &EncodingConfig::from(Encoding::Text),
+        );
+
+        assert_eq!(&bytes[..], message.as_bytes());
+    }
+
+    #[test]
+    fn amqp_encode_event_json() {
+        let message = "hello world".to_string();
+        let mut event = Event::from(message.clone());
+        event.as_mut_log().insert("key", "value");
+        let bytes = encode_event(event, &EncodingConfig::from(Encoding::Json));
+
+        let map: BTreeMap<String, String> = serde_json::from_slice(&bytes[..]).unwrap();
+
+        assert_eq!(map[&event::log_schema().message_key().to_string()], message);
+        assert_eq!(map["key"], "value".to_string());
+    }
+
+    #[test]
+    fn amqp_routing_key_template() {
+        let config: AmqpSinkConfig = toml::from_str(
+            r#"
+            exchange = "logs"
+            routing_key = "{{ service }}.{{ level }}"
+            encoding = "text"
+            "#,
+        )
+        .unwrap();
+        assert!(config.durable);
+        assert!(config.confirm);
+        assert!(!config.mandatory);
+
+        let template = Template::from(config.routing_key.unwrap().as_str());
+        let mut event = Event::from("hello");
+        event.as_mut_log().insert("service", "api");
+        event.as_mut_log().insert("level", "error");
+        assert_eq!(template.render_string(&event).unwrap(), "api.error");
+
+        let event = Event::from("hello");
+        assert!(template.render_string(&event).is_err());
+    }
+}
+
+#[cfg(feature = "amqp-integration-tests")]
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_util::{random_lines, random_string, runtime};
+    use lapin::options::{BasicGetOptions, QueueBindOptions, QueueDeclareOptions};
+
+    #[test]
+    fn amqp_confirmed_publish() {
+        let mut rt = runtime();
+        let exchange = format!("test-{}", random_string(10));
+        let queue = format!("test-{}", random_string(10));
+
+        let config = AmqpSinkConfig {
+            exchange: exchange.clone(),
+            routing_key: Some("{{ service }}".into()),
+            encoding: EncodingConfig::from(Encoding::Text),
+            mandatory: true,
+            ..Default::default()
+        };
+
+        let (_connection, channel) = rt.block_on_std({
+            let config = config.clone();
+            let exchange = exchange.clone();
+            let queue = queue.clone();
+            async move {
+                let (connection, channel) = config.connection.connect().await.unwrap();
+                channel
+                    .exchange_declare(
+                        &exchange,
+                        ExchangeKind::Direct,
+                        ExchangeDeclareOptions::default(),
+                        FieldTable::default(),
+                    )
+                    .await
+                    .unwrap();
+                channel
+                    .queue_declare(
+                        &queue,
+                        QueueDeclareOptions::default(),
+                        FieldTable::default(),
+                    )
+                    .await
+                    .unwrap();
+                channel
+                    .queue_bind(
+                        &queue,
+                        &exchange,
+                        "api",
+                        QueueBindOptions::default(),
+                        FieldTable::default(),
+                    )
+                    .await
+                    .unwrap();
+                (connection, channel)
+            }
+        });
+
+        rt.block_on_std(healthcheck(config.clone())).unwrap();
+
+        let lines: Vec<String> = random_lines(100).take(10).collect();
+        let mut events: Vec<Event> = lines
+            .iter()
+            .map(|line| Event::from(line.as_str()))
+            .collect();
+        for event in events.iter_mut() {
+            event.as_mut_log().insert("service", "api");
+        }
+        // Not bound to the queue, so the broker returns it to us.
+        let mut unroutable = Event::from("unroutable");
+        unroutable.as_mut_log().insert("service", "web");
+        events.push(unroutable);
+
+        let mut sink = AmqpSink {
+            routing_key: Template::from(config.routing_key.as_deref().unwrap()),
+            config,
+            connection: None,
+        };
+        rt.block_on_std(async move {
+            sink.run(futures::stream::iter(events)).await.unwrap();
+        });
+
+        let received = rt.block_on_std(async move {
+            let mut received = Vec::new();
+            while let Some(message) = channel
+                .basic_get(&queue, BasicGetOptions { no_ack: true })
+                .await
+                .unwrap()
+            {
+                received.push(String::from_utf8(message.delivery.data).unwrap());
+            }
+            received
+        });
+        assert_eq!(received, lines);
+    }
+}
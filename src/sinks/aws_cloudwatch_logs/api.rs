@@ -0,0 +1,216 @@
+//! The CloudWatch Logs API calls the sink makes, as requests to a tower
+//! service. A stream's calls all go through one stack of the sink's request
+//! layers, so describing, creating and putting are retried, rate limited and
+//! timed out alike.
+
+use super::CloudwatchError;
+use crate::{
+    internal_events::{CloudwatchLogsApiCalled, CloudwatchLogsCall},
+    sinks::util::rusoto::AwsCredentialsProvider,
+};
+use futures01::{Future, Poll};
+use rusoto_core::RusotoFuture;
+use rusoto_logs::{
+    CloudWatchLogs, CloudWatchLogsClient, CreateLogGroupRequest, CreateLogStreamRequest,
+    DescribeLogGroupsRequest, DescribeLogGroupsResponse, DescribeLogStreamsRequest,
+    DescribeLogStreamsResponse, InputLogEvent, PutLogEventsRequest, PutLogEventsResponse,
+    PutRetentionPolicyRequest,
+};
+use std::time::Duration;
+use tower::Service;
+
+#[derive(Clone, Debug)]
+pub enum ApiRequest {
+    /// Lists the groups sharing the group name as prefix, the group first.
+    DescribeGroups,
+    /// Lists a page of the group's streams, only those sharing the stream
+    /// name as prefix if `prefixed`.
+    DescribeStreams {
+        prefixed: bool,
+        next_token: Option<String>,
+    },
+    CreateGroup,
+    CreateStream,
+    PutRetentionPolicy {
+        days: i64,
+    },
+    PutEvents {
+        sequence_token: Option<String>,
+        events: Vec<InputLogEvent>,
+    },
+}
+
+#[derive(Debug)]
+pub enum ApiResponse {
+    DescribeGroups(DescribeLogGroupsResponse),
+    DescribeStreams(DescribeLogStreamsResponse),
+    /// The call answers with nothing, like creating a group does.
+    Done,
+    PutEvents(PutLogEventsResponse),
+}
+
+impl ApiRequest {
+    fn call(&self) -> CloudwatchLogsCall {
+        match self {
+            ApiRequest::DescribeGroups => CloudwatchLogsCall::DescribeGroups,
+            ApiRequest::DescribeStreams { .. } => CloudwatchLogsCall::DescribeStreams,
+            ApiRequest::CreateGroup => CloudwatchLogsCall::CreateGroup,
+            ApiRequest::CreateStream => CloudwatchLogsCall::CreateStream,
+            ApiRequest::PutRetentionPolicy { .. } => CloudwatchLogsCall::PutRetentionPolicy,
+            ApiRequest::PutEvents { .. } => CloudwatchLogsCall::PutEvents,
+        }
+    }
+}
+
+/// Makes the calls for one stream of one group.
+#[derive(Clone)]
+pub struct ApiService {
+    client: CloudWatchLogsClient,
+    credentials: AwsCredentialsProvider,
+    stream_name: String,
+    group_name: String,
+    timeout: Option<Duration>,
+}
+
+impl ApiService {
+    pub fn new(
+        client: CloudWatchLogsClient,
+        credentials: AwsCredentialsProvider,
+        stream_name: String,
+        group_name: String,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            client,
+            credentials,
+            stream_name,
+            group_name,
+            timeout,
+        }
+    }
+
+    pub fn group_name(&self) -> &str {
+        &self.group_name
+    }
+
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+
+    fn with_timeout<T, E>(&self, future: RusotoFuture<T, E>) -> RusotoFuture<T, E> {
+        match self.timeout {
+            Some(timeout) => future.with_timeout(timeout),
+            None => future,
+        }
+    }
+}
+
+impl Service<ApiRequest> for ApiService {
+    type Response = ApiResponse;
+    type Error = CloudwatchError;
+    type Future = Box<dyn Future<Item = ApiResponse, Error = CloudwatchError> + Send>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(().into())
+    }
+
+    fn call(&mut self, request: ApiRequest) -> Self::Future {
+        emit!(CloudwatchLogsApiCalled {
+            call: request.call(),
+            group: &self.group_name,
+            stream: &self.stream_name,
+        });
+
+        let group_name = self.group_name.clone();
+        let stream_name = self.stream_name.clone();
+        let response: Self::Future = match request {
+            ApiRequest::DescribeGroups => {
+                let request = DescribeLogGroupsRequest {
+                    log_group_name_prefix: Some(group_name),
+                    limit: Some(1),
+                    ..Default::default()
+                };
+                Box::new(
+                    self.with_timeout(self.client.describe_log_groups(request))
+                        .map(ApiResponse::DescribeGroups)
+                        .map_err(CloudwatchError::DescribeGroups),
+                )
+            }
+            ApiRequest::DescribeStreams {
+                prefixed,
+                next_token,
+            } => {
+                let request = DescribeLogStreamsRequest {
+                    log_group_name: group_name,
+                    log_stream_name_prefix: if prefixed { Some(stream_name) } else { None },
+                    next_token,
+                    ..Default::default()
+                };
+                Box::new(
+                    self.with_timeout(self.client.describe_log_streams(request))
+                        .map(ApiResponse::DescribeStreams)
+                        .map_err(CloudwatchError::Describe),
+                )
+            }
+            ApiRequest::CreateGroup => {
+                let request = CreateLogGroupRequest {
+                    log_group_name: group_name,
+                    ..Default::default()
+                };
+                Box::new(
+                    self.with_timeout(self.client.create_log_group(request))
+                        .map(|()| ApiResponse::Done)
+                        .map_err(CloudwatchError::CreateGroup),
+                )
+            }
+            ApiRequest::CreateStream => {
+                let request = CreateLogStreamRequest {
+                    log_group_name: group_name,
+                    log_stream_name: stream_name,
+                };
+                Box::new(
+                    self.with_timeout(self.client.create_log_stream(request))
+                        .map(|()| ApiResponse::Done)
+                        .map_err(CloudwatchError::CreateStream),
+                )
+            }
+            ApiRequest::PutRetentionPolicy { days } => {
+                let request = PutRetentionPolicyRequest {
+                    log_group_name: group_name,
+                    retention_in_days: days,
+                };
+                Box::new(
+                    self.with_timeout(self.client.put_retention_policy(request))
+                        .map(|()| ApiResponse::Done)
+                        .map_err(CloudwatchError::PutRetentionPolicy),
+                )
+            }
+            ApiRequest::PutEvents {
+                sequence_token,
+                events,
+            } => {
+                let request = PutLogEventsRequest {
+                    log_events: events,
+                    sequence_token,
+                    log_group_name: group_name,
+                    log_stream_name: stream_name,
+                };
+                Box::new(
+                    self.with_timeout(self.client.put_log_events(request))
+                        .map(ApiResponse::PutEvents)
+                        .map_err(CloudwatchError::Put),
+                )
+            }
+        };
+
+        // Temporary credentials can expire while a call is in flight, the
+        // retry then goes out with fresh ones.
+        let credentials = self.credentials.clone();
+        Box::new(response.map_err(move |error| {
+            if error.is_expired_credentials() {
+                credentials.refresh();
+            }
+            error
+        }))
+    }
+}
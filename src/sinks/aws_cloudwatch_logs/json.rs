@@ -0,0 +1,216 @@
+//! Options for the JSON events are encoded to, to keep messages small, as
+//! CloudWatch Logs charges by the byte, and to keep them simple to query with
+//! CloudWatch Logs Insights.
+
+use crate::event::{LogEvent, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct JsonEncodingOptions {
+    /// Leave out fields set to null. Absent fields are never encoded.
+    pub omit_null_fields: bool,
+    /// Leave out empty strings, arrays and objects, objects emptied by
+    /// leaving out their fields included.
+    pub omit_empty_fields: bool,
+    /// Flatten nested objects to dotted keys, `{"a.b": 1}` for
+    /// `{"a": {"b": 1}}`. Objects in arrays are kept as they are.
+    pub flatten: bool,
+}
+
+impl JsonEncodingOptions {
+    pub fn encode(&self, log: LogEvent) -> String {
+        if *self == Self::default() {
+            return serde_json::to_string(&log).unwrap();
+        }
+
+        let mut fields = BTreeMap::new();
+        for (key, value) in log {
+            match self.prune(value) {
+                Some(value) if self.flatten => flatten_into(&mut fields, key, value),
+                Some(value) => {
+                    fields.insert(key, value);
+                }
+                None => (),
+            }
+        }
+        serde_json::to_string(&fields).unwrap()
+    }
+
+    /// `value` with its fields left out as configured, or `None` if it's
+    /// left out itself.
+    fn prune(&self, value: Value) -> Option<Value> {
+        let value = self.prune_fields(value);
+        let omit = match &value {
+            Value::Null => self.omit_null_fields,
+            Value::Bytes(bytes) => self.omit_empty_fields && bytes.is_empty(),
+            Value::Array(values) => self.omit_empty_fields && values.is_empty(),
+            Value::Map(map) => self.omit_empty_fields && map.is_empty(),
+            _ => false,
+        };
+
+        if omit {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Leaves out the fields of objects within `value`. Array elements are
+    /// kept, as their positions matter.
+    fn prune_fields(&self, value: Value) -> Value {
+        match value {
+            Value::Map(map) => Value::Map(
+                map.into_iter()
+                    .filter_map(|(key, value)| Some((key, self.prune(value)?)))
+                    .collect(),
+            ),
+            Value::Array(values) => Value::Array(
+                values
+                    .into_iter()
+                    .map(|value| self.prune_fields(value))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+}
+
+fn flatten_into(fields: &mut BTreeMap<String, Value>, key: String, value: Value) {
+    match value {
+        Value::Map(map) if !map.is_empty() => {
+            for (child, value) in map {
+                flatten_into(fields, format!("{}.{}", key, child), value);
+            }
+        }
+        value => {
+            fields.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+    use serde_json::json;
+
+    fn event() -> LogEvent {
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+        log.insert("message", "hello");
+        log.insert("empty", "");
+        log.insert("null", Value::Null);
+        log.insert("count", 3);
+        log.insert("ratio", 0.5);
+        log.insert("zero", 0);
+        log.insert("flag", false);
+        log.insert("http.status", 200);
+        log.insert("http.path", "");
+        log.insert("http.headers.host", Value::Null);
+        log.insert("tags", Value::Array(vec![Value::Null, "".into(), 1.into()]));
+        log.insert("none", Value::Array(Vec::new()));
+        event.into_log()
+    }
+
+    fn encode(options: JsonEncodingOptions) -> serde_json::Value {
+        serde_json::from_str(&options.encode(event())).unwrap()
+    }
+
+    #[test]
+    fn cloudwatch_json_defaults_encode_everything() {
+        let options = JsonEncodingOptions::default();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&options.encode(event())).unwrap(),
+            serde_json::to_value(event()).unwrap()
+        );
+    }
+
+    #[test]
+    fn cloudwatch_json_omits_null_fields() {
+        let encoded = encode(JsonEncodingOptions {
+            omit_null_fields: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            encoded,
+            json!({
+                "message": "hello",
+                "empty": "",
+                "count": 3,
+                "ratio": 0.5,
+                "zero": 0,
+                "flag": false,
+                "http": {"status": 200, "path": "", "headers": {}},
+                "tags": [null, "", 1],
+                "none": [],
+            })
+        );
+    }
+
+    #[test]
+    fn cloudwatch_json_omits_empty_fields() {
+        let encoded = encode(JsonEncodingOptions {
+            omit_null_fields: true,
+            omit_empty_fields: true,
+            ..Default::default()
+        });
+
+        // Zeroes and false aren't empty, and numbers stay numbers.
+        assert_eq!(
+            encoded,
+            json!({
+                "message": "hello",
+                "count": 3,
+                "ratio": 0.5,
+                "zero": 0,
+                "flag": false,
+                "http": {"status": 200},
+                "tags": [null, "", 1],
+            })
+        );
+
+        // Only empty values are left out, nulls are kept.
+        let encoded = encode(JsonEncodingOptions {
+            omit_empty_fields: true,
+            ..Default::default()
+        });
+        assert_eq!(encoded["null"], json!(null));
+        assert_eq!(
+            encoded["http"],
+            json!({"status": 200, "headers": {"host": null}})
+        );
+    }
+
+    #[test]
+    fn cloudwatch_json_flattens_objects() {
+        let encoded = encode(JsonEncodingOptions {
+            omit_null_fields: true,
+            omit_empty_fields: true,
+            flatten: true,
+        });
+
+        assert_eq!(
+            encoded,
+            json!({
+                "message": "hello",
+                "count": 3,
+                "ratio": 0.5,
+                "zero": 0,
+                "flag": false,
+                "http.status": 200,
+                "tags": [null, "", 1],
+            })
+        );
+
+        let encoded = encode(JsonEncodingOptions {
+            flatten: true,
+            ..Default::default()
+        });
+        assert_eq!(encoded["http.headers.host"], json!(null));
+        assert_eq!(encoded["http.path"], json!(""));
+        assert!(encoded.get("http").is_none());
+    }
+}
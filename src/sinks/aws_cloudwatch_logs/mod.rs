@@ -1,15 +1,21 @@
+mod api;
+mod json;
 mod request;
 
+pub use self::json::JsonEncodingOptions;
+
 use crate::{
     dns::Resolver,
-    event::{self, Event, LogEvent, Value},
+    event::{self, Event, Value},
     region::RegionOrEndpoint,
     sinks::util::{
         encoding::{EncodingConfig, EncodingConfiguration},
-        retries::{FixedRetryPolicy, RetryLogic},
+        partition_limit::PartitionLimitConfig,
+        retries::RetryLogic,
         rusoto::{self, AwsCredentialsProvider},
-        BatchEventsConfig, PartitionBatchSink, PartitionBuffer, PartitionInnerBuffer,
-        TowerRequestConfig, TowerRequestSettings,
+        service::{RequestMetrics, RequestMetricsLayer},
+        BatchConfig, BatchSettings, DeadLetter, PartitionBatchSink, PartitionBuffer,
+        PartitionInnerBuffer, ProxyConfig, TowerRequestConfig, TowerRequestSettings, VecBuffer,
     },
     template::Template,
     topology::config::{DataType, SinkConfig, SinkContext},
@@ -19,21 +25,15 @@ use futures01::{future, stream::iter_ok, sync::oneshot, Async, Future, Poll, Sin
 use lazy_static::lazy_static;
 use rusoto_core::{request::BufferedHttpResponse, Region, RusotoError};
 use rusoto_logs::{
-    CloudWatchLogs, CloudWatchLogsClient, CreateLogGroupError, CreateLogStreamError,
-    DescribeLogGroupsRequest, DescribeLogStreamsError, InputLogEvent, PutLogEventsError,
+    CloudWatchLogsClient, CreateLogGroupError, CreateLogStreamError, DescribeLogGroupsError,
+    DescribeLogStreamsError, InputLogEvent, PutLogEventsError, PutRetentionPolicyError,
 };
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use std::{collections::HashMap, convert::TryInto, fmt};
+use std::{collections::HashMap, convert::TryInto, fmt, str::FromStr, time::Duration};
 use tower::{
-    buffer::Buffer,
-    limit::{
-        concurrency::ConcurrencyLimit,
-        rate::{Rate, RateLimit},
-    },
-    retry::Retry,
-    timeout::Timeout,
-    Service, ServiceBuilder, ServiceExt,
+    buffer::Buffer, layer::Layer, limit::concurrency::ConcurrencyLimit, Service, ServiceBuilder,
+    ServiceExt,
 };
 
 #[derive(Debug, Snafu)]
@@ -46,40 +46,116 @@ enum BuildError {
     InvalidCloudwatchCredentials {
         source: rusoto_core::CredentialsError,
     },
+    #[snafu(display("Must set either 'group_name' or 'destination_arn'"))]
+    MissingGroupName,
+    #[snafu(display("Only one of 'group_name' or 'destination_arn' can be specified"))]
+    BothGroupNameAndDestinationArn,
+    #[snafu(display("Invalid log group ARN: {:?}", arn))]
+    InvalidDestinationArn { arn: String },
+    #[snafu(display("'group_name' can't be empty"))]
+    EmptyGroupName,
+    #[snafu(display("'stream_name' can't be empty"))]
+    EmptyStreamName,
+    #[snafu(display("'initial_sequence_token' needs a static 'group_name' and 'stream_name'"))]
+    SequenceTokenWithDynamicStream,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct CloudwatchLogsSinkConfig {
-    pub group_name: Template,
+    #[serde(default)]
+    pub group_name: Option<Template>,
+    /// ARN of a log group, possibly in another account, to write to instead
+    /// of `group_name`. It also fixes the region.
+    pub destination_arn: Option<String>,
     pub stream_name: Template,
     #[serde(flatten)]
     pub region: RegionOrEndpoint,
     pub encoding: EncodingConfig<Encoding>,
+    /// How events are encoded with the `json` codec.
+    #[serde(default)]
+    pub json_encoding: JsonEncodingOptions,
     pub create_missing_group: Option<bool>,
     pub create_missing_stream: Option<bool>,
     #[serde(default)]
-    pub batch: BatchEventsConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub assume_role: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// Timeout of each CloudWatch Logs API call, enforced by the HTTP
+    /// client. `request.timeout_secs` bounds each attempt at a call as well.
+    pub request_timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    /// Retention applied to log groups created by the sink, or to every
+    /// group it writes to with `force_retention`.
+    #[serde(default, deserialize_with = "deserialize_retention_days")]
+    pub retention_days: Option<u32>,
+    #[serde(default)]
+    pub force_retention: bool,
+    /// Sequence token of the stream as of the last put, when tracked outside
+    /// of Vector, so the first put after startup needs no describe call.
+    pub initial_sequence_token: Option<String>,
+    /// Check whether a log group exists once for all of its streams, rather
+    /// than in each stream's first request.
+    #[serde(default)]
+    pub share_group_checks: bool,
+    /// Caps the number of streams batched at once, per the rendered
+    /// `stream_name`.
+    pub partition_limit: Option<PartitionLimitConfig>,
 }
 
 #[cfg(test)]
 fn default_config(e: Encoding) -> CloudwatchLogsSinkConfig {
     CloudwatchLogsSinkConfig {
         group_name: Default::default(),
+        destination_arn: Default::default(),
         stream_name: Default::default(),
         region: Default::default(),
         encoding: e.into(),
+        json_encoding: Default::default(),
         create_missing_group: Default::default(),
         create_missing_stream: Default::default(),
         batch: Default::default(),
         request: Default::default(),
         assume_role: Default::default(),
+        proxy: Default::default(),
+        request_timeout_secs: Default::default(),
+        connect_timeout_secs: Default::default(),
+        retention_days: Default::default(),
+        force_retention: Default::default(),
+        initial_sequence_token: Default::default(),
+        share_group_checks: Default::default(),
+        partition_limit: Default::default(),
+    }
+}
+
+// The only values PutRetentionPolicy accepts.
+const RETENTION_DAYS: &[u32] = &[
+    1, 3, 5, 7, 14, 30, 60, 90, 120, 150, 180, 365, 400, 545, 731, 1827, 3653,
+];
+
+fn deserialize_retention_days<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let days = Option::<u32>::deserialize(deserializer)?;
+    match days {
+        Some(days) if !RETENTION_DAYS.contains(&days) => Err(serde::de::Error::custom(format!(
+            "invalid retention_days {}, expected one of {:?}",
+            days, RETENTION_DAYS
+        ))),
+        days => Ok(days),
     }
 }
 
+// Limits of a single PutLogEvents call. Each event counts its UTF-8 message
+// length plus a fixed overhead against the byte limit.
+const MAX_EVENTS_PER_BATCH: usize = 10_000;
+const MAX_BYTES_PER_BATCH: usize = 1_048_576;
+const EVENT_SIZE_OVERHEAD: usize = 26;
+
 lazy_static! {
     static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
         ..Default::default()
@@ -87,33 +163,33 @@ lazy_static! {
 }
 
 pub struct CloudwatchLogsSvc {
-    client: CloudWatchLogsClient,
-    encoding: EncodingConfig<Encoding>,
-    stream_name: String,
-    group_name: String,
+    client: request::Client,
     create_missing_group: bool,
     create_missing_stream: bool,
+    retention: Option<request::Retention>,
     token: Option<String>,
     token_rx: Option<oneshot::Receiver<Option<String>>>,
+    group_checks: Option<request::GroupChecks>,
 }
 
-type Svc = Buffer<
-    ConcurrencyLimit<
-        RateLimit<
-            Retry<
-                FixedRetryPolicy<CloudwatchRetryLogic>,
-                Buffer<Timeout<CloudwatchLogsSvc>, Vec<Event>>,
-            >,
-        >,
-    >,
-    Vec<Event>,
->;
+type Svc = Buffer<ConcurrencyLimit<RequestMetrics<CloudwatchLogsSvc>>, Vec<InputLogEvent>>;
+
+/// An event as sent to CloudWatch, along with the event it was encoded from
+/// when there is a dead-letter sink to hand it to.
+#[derive(Debug, Clone)]
+pub struct EncodedEvent {
+    event: InputLogEvent,
+    original: Option<Event>,
+}
 
 pub struct CloudwatchLogsPartitionSvc {
     config: CloudwatchLogsSinkConfig,
     clients: HashMap<CloudwatchKey, Svc>,
+    group_checks: Option<request::GroupChecks>,
     request_settings: TowerRequestSettings,
     resolver: Resolver,
+    dead_letter: DeadLetter,
+    request_metrics: RequestMetricsLayer,
 }
 
 #[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Clone)]
@@ -127,9 +203,13 @@ pub enum Encoding {
 pub enum CloudwatchError {
     Put(RusotoError<PutLogEventsError>),
     Describe(RusotoError<DescribeLogStreamsError>),
+    DescribeGroups(RusotoError<DescribeLogGroupsError>),
     CreateStream(RusotoError<CreateLogStreamError>),
     CreateGroup(RusotoError<CreateLogGroupError>),
+    PutRetentionPolicy(RusotoError<PutRetentionPolicyError>),
+    InvalidConfig(&'static str),
     NoStreamsFound,
+    TooManyStreams,
     ServiceDropped,
     MakeService,
 }
@@ -137,24 +217,63 @@ pub enum CloudwatchError {
 #[typetag::serde(name = "aws_cloudwatch_logs")]
 impl SinkConfig for CloudwatchLogsSinkConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
-        let batch = self.batch.unwrap_or(1000, 1);
+        let batch = self.batch_settings();
         let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
 
-        let log_group = self.group_name.clone();
-        let log_stream = self.stream_name.clone();
+        let log_group = self.log_group()?;
+        let log_stream = self.log_stream()?;
+        let encoding = self.encoding.clone();
+        let json_encoding = self.json_encoding.clone();
+        let mut dead_letter = cx.dead_letter();
+        let mut partition_limit = match &self.partition_limit {
+            Some(limit) => Some((
+                limit.build(cx.name(), batch.timeout)?,
+                Bytes::from(limit.default_partition.as_str()),
+            )),
+            None => None,
+        };
 
         let svc = ServiceBuilder::new()
             .concurrency_limit(request.in_flight_limit)
-            .service(CloudwatchLogsPartitionSvc::new(
-                self.clone(),
-                cx.resolver(),
-            )?);
+            .service(CloudwatchLogsPartitionSvc::new(self.clone(), &cx)?);
 
         let sink = {
-            let buffer = PartitionBuffer::new(Vec::new());
+            let buffer = PartitionBuffer::new(VecBuffer::new(encoded_event_size));
             let svc_sink = PartitionBatchSink::new(svc, buffer, batch, cx.acker())
+                .component(cx.name())
                 .sink_map_err(|e| error!("Fatal cloudwatchlogs sink error: {}", e))
-                .with_flat_map(move |event| iter_ok(partition(event, &log_group, &log_stream)));
+                .with_flat_map(move |event: Event| {
+                    let original = if dead_letter.is_enabled() {
+                        Some(event.clone())
+                    } else {
+                        None
+                    };
+
+                    let encoded = match partition(event, &log_group, &log_stream) {
+                        Some(event) => {
+                            let (event, mut key) = event.into_parts();
+                            if let Some((limiter, default_stream)) = &mut partition_limit {
+                                key = limiter.partition(key, |key| CloudwatchKey {
+                                    group: key.group,
+                                    stream: default_stream.clone(),
+                                });
+                            }
+                            let event = encode_log(event, &encoding, &json_encoding);
+                            Some(PartitionInnerBuffer::new(
+                                EncodedEvent { event, original },
+                                key,
+                            ))
+                        }
+                        None => {
+                            if let Some(event) = original {
+                                dead_letter
+                                    .send(event, "Group or stream name could not be rendered.");
+                            }
+                            None
+                        }
+                    };
+                    iter_ok(encoded)
+                });
             Box::new(svc_sink)
         };
 
@@ -170,22 +289,133 @@ impl SinkConfig for CloudwatchLogsSinkConfig {
     fn sink_type(&self) -> &'static str {
         "aws_cloudwatch_logs"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+impl CloudwatchLogsSinkConfig {
+    fn batch_settings(&self) -> BatchSettings {
+        let mut batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .events(MAX_EVENTS_PER_BATCH)
+                .bytes(MAX_BYTES_PER_BATCH as u64)
+                .timeout(1),
+        );
+        batch.size.events = batch.size.events.min(MAX_EVENTS_PER_BATCH);
+        batch.size.bytes = batch.size.bytes.min(MAX_BYTES_PER_BATCH);
+        batch
+    }
+
+    fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout_secs.map(Duration::from_secs)
+    }
+
+    fn log_group(&self) -> crate::Result<Template> {
+        match (&self.group_name, &self.destination_arn) {
+            (Some(group_name), None) if is_empty(group_name) => {
+                Err(BuildError::EmptyGroupName.into())
+            }
+            (Some(group_name), None) => Ok(group_name.clone()),
+            (None, Some(arn)) => Ok(arn.parse::<LogGroupArn>()?.group_name.into()),
+            (Some(_), Some(_)) => Err(BuildError::BothGroupNameAndDestinationArn.into()),
+            (None, None) => Err(BuildError::MissingGroupName.into()),
+        }
+    }
+
+    fn log_stream(&self) -> crate::Result<Template> {
+        if is_empty(&self.stream_name) {
+            return Err(BuildError::EmptyStreamName.into());
+        }
+        // The token belongs to a single stream, it can't seed every stream
+        // the names render to.
+        let dynamic_group = self.group_name.as_ref().map_or(false, Template::is_dynamic);
+        if self.initial_sequence_token.is_some() && (dynamic_group || self.stream_name.is_dynamic())
+        {
+            return Err(BuildError::SequenceTokenWithDynamicStream.into());
+        }
+        Ok(self.stream_name.clone())
+    }
+
+    fn resolve_region(&self) -> crate::Result<Region> {
+        match &self.destination_arn {
+            Some(arn) => Ok(self
+                .region
+                .resolve_for(&arn.parse::<LogGroupArn>()?.region)?),
+            None => Ok(self.region.clone().try_into()?),
+        }
+    }
+}
+
+fn is_empty(template: &Template) -> bool {
+    !template.is_dynamic() && template.get_ref().is_empty()
+}
+
+/// `arn:<partition>:logs:<region>:<account>:log-group:<name>`, optionally
+/// followed by the `:*` the console shows. Writing to a group in another
+/// account also needs `assume_role` set to a role in that account.
+#[derive(Debug, PartialEq)]
+struct LogGroupArn {
+    region: String,
+    group_name: String,
+}
+
+impl FromStr for LogGroupArn {
+    type Err = BuildError;
+
+    fn from_str(arn: &str) -> Result<Self, Self::Err> {
+        let parts = arn
+            .trim_end_matches(":*")
+            .splitn(7, ':')
+            .collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["arn", _, "logs", region, account, "log-group", group_name]
+                if !region.is_empty() && !account.is_empty() && !group_name.is_empty() =>
+            {
+                Ok(Self {
+                    region: (*region).into(),
+                    group_name: (*group_name).into(),
+                })
+            }
+            _ => Err(BuildError::InvalidDestinationArn { arn: arn.into() }),
+        }
+    }
 }
 
 impl CloudwatchLogsPartitionSvc {
-    pub fn new(config: CloudwatchLogsSinkConfig, resolver: Resolver) -> crate::Result<Self> {
+    pub fn new(config: CloudwatchLogsSinkConfig, cx: &SinkContext) -> crate::Result<Self> {
         let request_settings = config.request.unwrap_with(&REQUEST_DEFAULTS);
 
+        let group_checks = if config.share_group_checks {
+            Some(request::GroupChecks::default())
+        } else {
+            None
+        };
+
         Ok(Self {
             config,
             clients: HashMap::new(),
+            group_checks,
             request_settings,
-            resolver,
+            resolver: cx.resolver(),
+            dead_letter: cx.dead_letter(),
+            request_metrics: RequestMetricsLayer::new(cx.name()),
         })
     }
+
+    /// Puts the batches of one stream one at a time, for its sequence token.
+    fn stream_service(&self, cloudwatch: CloudwatchLogsSvc) -> Svc {
+        let cloudwatch = self.request_metrics.layer(cloudwatch);
+        let concurrency = ConcurrencyLimit::new(cloudwatch, 1);
+
+        Buffer::new(concurrency, 1)
+    }
 }
 
-impl Service<PartitionInnerBuffer<Vec<Event>, CloudwatchKey>> for CloudwatchLogsPartitionSvc {
+impl Service<PartitionInnerBuffer<Vec<EncodedEvent>, CloudwatchKey>>
+    for CloudwatchLogsPartitionSvc
+{
     type Response = ();
     type Error = crate::Error;
     type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error> + Send + 'static>;
@@ -194,41 +424,58 @@ impl Service<PartitionInnerBuffer<Vec<Event>, CloudwatchKey>> for CloudwatchLogs
         Ok(().into())
     }
 
-    fn call(&mut self, req: PartitionInnerBuffer<Vec<Event>, CloudwatchKey>) -> Self::Future {
+    fn call(
+        &mut self,
+        req: PartitionInnerBuffer<Vec<EncodedEvent>, CloudwatchKey>,
+    ) -> Self::Future {
         let (events, key) = req.into_parts();
+        let (events, originals): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .map(|encoded| (encoded.event, encoded.original))
+            .unzip();
 
         let svc = if let Some(svc) = &mut self.clients.get_mut(&key) {
             svc.clone()
         } else {
-            let svc = {
-                let policy = self.request_settings.retry_policy(CloudwatchRetryLogic);
-
-                let cloudwatch =
-                    CloudwatchLogsSvc::new(&self.config, &key, self.resolver.clone()).unwrap();
-                let timeout = Timeout::new(cloudwatch, self.request_settings.timeout);
-
-                let buffer = Buffer::new(timeout, 1);
-                let retry = Retry::new(policy, buffer);
-
-                let rate = Rate::new(
-                    self.request_settings.rate_limit_num,
-                    self.request_settings.rate_limit_duration,
-                );
-                let rate = RateLimit::new(retry, rate);
-                let concurrency = ConcurrencyLimit::new(rate, 1);
-
-                Buffer::new(concurrency, 1)
-            };
+            // Each call is retried on its own.
+            let cloudwatch = CloudwatchLogsSvc::new(
+                &self.config,
+                &key,
+                self.resolver.clone(),
+                self.group_checks.clone(),
+                &self.request_settings,
+            )
+            .unwrap();
+            let svc = self.stream_service(cloudwatch);
 
             self.clients.insert(key, svc.clone());
             svc
         };
 
+        // Once retries are exhausted or the error can't be retried, the batch
+        // goes to the dead-letter sink instead of failing the sink.
+        let mut dead_letter = self.dead_letter.clone();
+        let dead_letters = if dead_letter.is_enabled() {
+            Some(originals.into_iter().flatten().collect::<Vec<_>>())
+        } else {
+            None
+        };
+
         let fut = svc
             .ready()
             .map_err(Into::into)
             .and_then(move |mut svc| svc.call(events))
-            .map_err(Into::into);
+            .map_err(Into::into)
+            .or_else(move |error: crate::Error| match dead_letters {
+                Some(events) => {
+                    let reason = error.to_string();
+                    for event in events {
+                        dead_letter.send(event, &reason);
+                    }
+                    Ok(())
+                }
+                None => Err(error),
+            });
 
         Box::new(fut)
     }
@@ -239,9 +486,18 @@ impl CloudwatchLogsSvc {
         config: &CloudwatchLogsSinkConfig,
         key: &CloudwatchKey,
         resolver: Resolver,
+        group_checks: Option<request::GroupChecks>,
+        request_settings: &TowerRequestSettings,
     ) -> crate::Result<Self> {
-        let region = config.region.clone().try_into()?;
-        let client = create_client(region, config.assume_role.clone(), resolver)?;
+        let region = config.resolve_region()?;
+        let credentials = AwsCredentialsProvider::new(&region, config.assume_role.clone())?;
+        let client = create_client_with_credentials(
+            region,
+            credentials.clone(),
+            resolver,
+            &config.proxy,
+            config.connect_timeout(),
+        )?;
 
         let group_name = String::from_utf8_lossy(&key.group[..]).into_owned();
         let stream_name = String::from_utf8_lossy(&key.stream[..]).into_owned();
@@ -249,45 +505,75 @@ impl CloudwatchLogsSvc {
         let create_missing_group = config.create_missing_group.unwrap_or(true);
         let create_missing_stream = config.create_missing_stream.unwrap_or(true);
 
-        Ok(CloudwatchLogsSvc {
+        let api = api::ApiService::new(
             client,
-            encoding: config.encoding.clone(),
+            credentials,
             stream_name,
             group_name,
+            config.request_timeout_secs.map(Duration::from_secs),
+        );
+
+        Ok(CloudwatchLogsSvc {
+            client: request::Client::new(api, request_settings),
             create_missing_group,
             create_missing_stream,
-            token: None,
+            retention: config.retention_days.map(|days| request::Retention {
+                days: days.into(),
+                force: config.force_retention,
+            }),
+            token: config.initial_sequence_token.clone(),
             token_rx: None,
+            group_checks,
         })
     }
+}
 
-    pub fn encode_log(&self, mut log: LogEvent) -> InputLogEvent {
-        let timestamp =
-            if let Some(Value::Timestamp(ts)) = log.remove(&event::log_schema().timestamp_key()) {
-                ts.timestamp_millis()
-            } else {
-                chrono::Utc::now().timestamp_millis()
-            };
+fn encode_log(
+    mut event: Event,
+    encoding: &EncodingConfig<Encoding>,
+    json_encoding: &JsonEncodingOptions,
+) -> InputLogEvent {
+    // CloudWatch takes the timestamp in milliseconds, whatever the format
+    // of the one kept in the message, if `timestamp_format` is set.
+    let timestamp_key = event::log_schema().timestamp_key();
+    let timestamp = match event.as_log().get(&timestamp_key) {
+        Some(Value::Timestamp(ts)) => ts.timestamp_millis(),
+        _ => chrono::Utc::now().timestamp_millis(),
+    };
+    if encoding.timestamp_format.is_none() {
+        event.as_mut_log().remove(&timestamp_key);
+    }
 
-        match self.encoding.codec {
-            Encoding::Json => {
-                let message = serde_json::to_string(&log).unwrap();
-                InputLogEvent { message, timestamp }
-            }
-            Encoding::Text => {
-                let message = log
-                    .get(&event::log_schema().message_key())
-                    .map(|v| v.to_string_lossy())
-                    .unwrap_or_else(|| "".into());
-                InputLogEvent { message, timestamp }
-            }
+    encoding.apply_rules(&mut event);
+    let log = event.into_log();
+
+    match encoding.codec {
+        Encoding::Json => {
+            let message = json_encoding.encode(log);
+            InputLogEvent { message, timestamp }
+        }
+        Encoding::Text => {
+            let message = log
+                .get(&event::log_schema().message_key())
+                .map(|v| v.to_string_lossy())
+                .unwrap_or_else(|| "".into());
+            InputLogEvent { message, timestamp }
         }
     }
 }
 
-impl Service<Vec<Event>> for CloudwatchLogsSvc {
+fn event_size(event: &InputLogEvent) -> usize {
+    event.message.len() + EVENT_SIZE_OVERHEAD
+}
+
+/// Only what is sent to CloudWatch counts towards its batch limits.
+fn encoded_event_size(encoded: &EncodedEvent) -> usize {
+    event_size(&encoded.event)
+}
+
+impl Service<Vec<InputLogEvent>> for CloudwatchLogsSvc {
     type Response = ();
-    type Error = CloudwatchError;
+    type Error = crate::Error;
     type Future = request::CloudwatchFuture;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
@@ -312,28 +598,18 @@ impl Service<Vec<Event>> for CloudwatchLogsSvc {
         }
     }
 
-    fn call(&mut self, req: Vec<Event>) -> Self::Future {
+    fn call(&mut self, events: Vec<InputLogEvent>) -> Self::Future {
         if self.token_rx.is_none() {
-            let events = req
-                .into_iter()
-                .map(|mut e| {
-                    self.encoding.apply_rules(&mut e);
-                    e
-                })
-                .map(|e| e.into_log())
-                .map(|e| self.encode_log(e))
-                .collect::<Vec<_>>();
-
             let (tx, rx) = oneshot::channel();
             self.token_rx = Some(rx);
 
             info!(message = "Sending events.", events = %events.len());
             request::CloudwatchFuture::new(
                 self.client.clone(),
-                self.stream_name.clone(),
-                self.group_name.clone(),
                 self.create_missing_group,
                 self.create_missing_stream,
+                self.retention,
+                self.group_checks.clone(),
                 events,
                 self.token.take(),
                 tx,
@@ -379,6 +655,15 @@ fn partition(
         }
     };
 
+    // CloudWatch rejects empty names, so these are treated like missing keys.
+    if group.is_empty() || stream.is_empty() {
+        warn!(
+            message = "group or stream name rendered empty; dropping event.",
+            rate_limit_secs = 30
+        );
+        return None;
+    }
+
     let key = CloudwatchKey { stream, group };
 
     Some(PartitionInnerBuffer::new(event, key))
@@ -386,84 +671,111 @@ fn partition(
 
 #[derive(Debug, Snafu)]
 enum HealthcheckError {
+    #[snafu(display(
+        "Not allowed to describe the streams of log group {:?}: {}",
+        group,
+        source
+    ))]
+    AccessDenied {
+        group: String,
+        source: RusotoError<DescribeLogStreamsError>,
+    },
+    #[snafu(display(
+        "Log group {:?} does not exist and `create_missing_group` is false",
+        group
+    ))]
+    NoLogGroup { group: String },
     #[snafu(display("DescribeLogStreams failed: {}", source))]
     DescribeLogStreamsFailed {
-        source: RusotoError<rusoto_logs::DescribeLogGroupsError>,
+        source: RusotoError<DescribeLogStreamsError>,
     },
-    #[snafu(display("No log group found"))]
-    NoLogGroup,
-    #[snafu(display("Unable to extract group name"))]
-    GroupNameError,
-    #[snafu(display("Group name mismatch: expected {}, found {}", expected, name))]
-    GroupNameMismatch { expected: String, name: String },
 }
 
 fn healthcheck(
     config: CloudwatchLogsSinkConfig,
     resolver: Resolver,
 ) -> crate::Result<super::Healthcheck> {
-    if config.group_name.is_dynamic() {
+    let group_name = config.log_group()?;
+    if group_name.is_dynamic() {
         info!("cloudwatch group_name is dynamic; skipping healthcheck.");
         return Ok(Box::new(future::ok(())));
     }
 
-    let group_name = String::from_utf8_lossy(&config.group_name.get_ref()[..]).into_owned();
-
-    let client = create_client(
-        config.region.clone().try_into()?,
-        config.assume_role,
+    let group_name = String::from_utf8_lossy(&group_name.get_ref()[..]).into_owned();
+    // A dynamic stream name can't be rendered without an event, so any of
+    // the group's streams are described instead, which needs the same
+    // permission.
+    let stream_name = config.log_stream()?;
+    let prefixed = !stream_name.is_dynamic();
+    let stream_name = String::from_utf8_lossy(&stream_name.get_ref()[..]).into_owned();
+
+    let region = config.resolve_region()?;
+    let credentials = AwsCredentialsProvider::new(&region, config.assume_role.clone())?;
+    let client = create_client_with_credentials(
+        region,
+        credentials.clone(),
         resolver,
+        &config.proxy,
+        config.connect_timeout(),
     )?;
+    let api = api::ApiService::new(
+        client,
+        credentials,
+        stream_name,
+        group_name,
+        config.request_timeout_secs.map(Duration::from_secs),
+    );
+
+    Ok(Box::new(check_streams_access(
+        api,
+        prefixed,
+        config.create_missing_group.unwrap_or(true),
+    )))
+}
 
-    let request = DescribeLogGroupsRequest {
-        limit: Some(1),
-        log_group_name_prefix: Some(group_name.clone()),
-        ..Default::default()
+/// Checks that the streams of the group can be described, which fails when
+/// the group is missing, unless the sink will create it. The call isn't
+/// retried, a healthcheck reports how things are right now.
+fn check_streams_access(
+    mut api: api::ApiService,
+    prefixed: bool,
+    create_missing_group: bool,
+) -> impl Future<Item = (), Error = crate::Error> {
+    let group = api.group_name().to_owned();
+    let request = api::ApiRequest::DescribeStreams {
+        prefixed,
+        next_token: None,
     };
-
-    let expected_group_name = group_name;
-
-    // This will attempt to find the group name passed in and verify that
-    // it matches the one that AWS sends back.
-    let fut = client
-        .describe_log_groups(request)
-        .map_err(|source| HealthcheckError::DescribeLogStreamsFailed { source }.into())
-        .and_then(|response| {
-            response
-                .log_groups
-                .ok_or_else(|| HealthcheckError::NoLogGroup.into())
-        })
-        .and_then(move |groups| {
-            if let Some(group) = groups.into_iter().next() {
-                if let Some(name) = group.log_group_name {
-                    if name == expected_group_name {
-                        Ok(())
-                    } else {
-                        Err(HealthcheckError::GroupNameMismatch {
-                            expected: expected_group_name,
-                            name,
-                        }
-                        .into())
-                    }
-                } else {
-                    Err(HealthcheckError::GroupNameError.into())
-                }
-            } else {
-                Err(HealthcheckError::NoLogGroup.into())
-            }
-        });
-
-    Ok(Box::new(fut))
+    api.call(request).then(move |result| match result {
+        Ok(_) => Ok(()),
+        Err(CloudwatchError::Describe(RusotoError::Service(
+            DescribeLogStreamsError::ResourceNotFound(_),
+        ))) if create_missing_group => {
+            info!(message = "log group does not exist; it will be created.", %group);
+            Ok(())
+        }
+        Err(CloudwatchError::Describe(RusotoError::Service(
+            DescribeLogStreamsError::ResourceNotFound(_),
+        ))) => Err(HealthcheckError::NoLogGroup { group }.into()),
+        Err(CloudwatchError::Describe(source)) if rusoto::is_access_denied(&source) => {
+            Err(HealthcheckError::AccessDenied { group, source }.into())
+        }
+        Err(CloudwatchError::Describe(source)) => {
+            Err(HealthcheckError::DescribeLogStreamsFailed { source }.into())
+        }
+        Err(error) => Err(error.into()),
+    })
 }
 
-fn create_client(
+fn create_client_with_credentials(
     region: Region,
-    assume_role: Option<String>,
+    credentials: AwsCredentialsProvider,
     resolver: Resolver,
+    proxy: &ProxyConfig,
+    connect_timeout: Option<Duration>,
 ) -> crate::Result<CloudWatchLogsClient> {
-    let http = rusoto::client(resolver)?;
-    let creds = AwsCredentialsProvider::new(&region, assume_role)?;
-    Ok(CloudWatchLogsClient::new_with(http, creds, region))
+    let http = rusoto::client_with_connect_timeout(resolver, proxy, connect_timeout)?;
+    Ok(CloudWatchLogsClient::new_with(http, credentials, region))
 }
 
 #[derive(Debug, Clone)]
@@ -471,87 +783,111 @@ struct CloudwatchRetryLogic;
 
 impl RetryLogic for CloudwatchRetryLogic {
     type Error = CloudwatchError;
-    type Response = ();
+    type Response = api::ApiResponse;
 
     fn is_retriable_error(&self, error: &Self::Error) -> bool {
-        match error {
-            CloudwatchError::Put(err) => match err {
-                RusotoError::Service(PutLogEventsError::ServiceUnavailable(error)) => {
-                    error!(message = "put logs service unavailable.", %error);
-                    true
-                }
-
-                RusotoError::HttpDispatch(error) => {
-                    error!(message = "put logs http dispatch.", %error);
-                    true
-                }
-
-                RusotoError::Unknown(res)
-                    if res.status.is_server_error()
-                        || res.status == http::StatusCode::TOO_MANY_REQUESTS =>
-                {
-                    let BufferedHttpResponse { status, body, .. } = res;
-                    let body = String::from_utf8_lossy(&body[..]);
-                    let body = &body[..body.len().min(50)];
-
-                    error!(message = "put logs http error.", %status, %body);
-                    true
-                }
-
-                _ => false,
-            },
+        // The failed call already refreshed the credentials.
+        if error.is_expired_credentials() {
+            warn!(message = "credentials expired; retrying with refreshed credentials.");
+            return true;
+        }
 
-            CloudwatchError::Describe(err) => match err {
-                RusotoError::Service(DescribeLogStreamsError::ServiceUnavailable(error)) => {
-                    error!(message = "describe streams service unavailable.", %error);
-                    true
-                }
+        let call = error.error_type();
+        match error {
+            CloudwatchError::Put(RusotoError::Service(PutLogEventsError::ServiceUnavailable(
+                message,
+            )))
+            | CloudwatchError::Describe(RusotoError::Service(
+                DescribeLogStreamsError::ServiceUnavailable(message),
+            ))
+            | CloudwatchError::DescribeGroups(RusotoError::Service(
+                DescribeLogGroupsError::ServiceUnavailable(message),
+            ))
+            | CloudwatchError::CreateStream(RusotoError::Service(
+                CreateLogStreamError::ServiceUnavailable(message),
+            ))
+            | CloudwatchError::CreateGroup(RusotoError::Service(
+                CreateLogGroupError::ServiceUnavailable(message),
+            ))
+            | CloudwatchError::PutRetentionPolicy(RusotoError::Service(
+                PutRetentionPolicyError::ServiceUnavailable(message),
+            )) => {
+                error!(message = "service unavailable.", %call, error = %message);
+                true
+            }
 
-                RusotoError::Unknown(res)
-                    if res.status.is_server_error()
-                        || res.status == http::StatusCode::TOO_MANY_REQUESTS =>
-                {
-                    let BufferedHttpResponse { status, body, .. } = res;
-                    let body = String::from_utf8_lossy(&body[..]);
-                    let body = &body[..body.len().min(50)];
+            CloudwatchError::CreateGroup(RusotoError::Service(
+                CreateLogGroupError::OperationAborted(message),
+            ))
+            | CloudwatchError::PutRetentionPolicy(RusotoError::Service(
+                PutRetentionPolicyError::OperationAborted(message),
+            )) => {
+                error!(message = "operation aborted.", %call, error = %message);
+                true
+            }
 
-                    error!(message = "describe streams http error.", %status, %body);
-                    true
-                }
+            CloudwatchError::Put(error) => is_retriable_call(call, error),
+            CloudwatchError::Describe(error) => is_retriable_call(call, error),
+            CloudwatchError::DescribeGroups(error) => is_retriable_call(call, error),
+            CloudwatchError::CreateStream(error) => is_retriable_call(call, error),
+            CloudwatchError::CreateGroup(error) => is_retriable_call(call, error),
+            CloudwatchError::PutRetentionPolicy(error) => is_retriable_call(call, error),
 
-                RusotoError::HttpDispatch(error) => {
-                    error!(message = "describe streams http dispatch.", %error);
-                    true
-                }
+            _ => false,
+        }
+    }
+}
 
-                _ => false,
-            },
+/// Whether a call failed in a way any call can, and that may not happen
+/// again: it didn't reach CloudWatch, or CloudWatch failed or throttled it.
+fn is_retriable_call<E>(call: &str, error: &RusotoError<E>) -> bool {
+    match error {
+        RusotoError::HttpDispatch(error) => {
+            error!(message = "http dispatch.", %call, %error);
+            true
+        }
 
-            CloudwatchError::CreateStream(err) => match err {
-                RusotoError::Service(CreateLogStreamError::ServiceUnavailable(error)) => {
-                    error!(message = "create stream service unavailable.", %error);
-                    true
-                }
+        RusotoError::Unknown(res)
+            if res.status.is_server_error()
+                || res.status == http::StatusCode::TOO_MANY_REQUESTS =>
+        {
+            let BufferedHttpResponse { status, body, .. } = res;
+            let body = String::from_utf8_lossy(&body[..]);
+            let body = &body[..body.len().min(50)];
 
-                RusotoError::Unknown(res)
-                    if res.status.is_server_error()
-                        || res.status == http::StatusCode::TOO_MANY_REQUESTS =>
-                {
-                    let BufferedHttpResponse { status, body, .. } = res;
-                    let body = String::from_utf8_lossy(&body[..]);
-                    let body = &body[..body.len().min(50)];
+            error!(message = "http error.", %call, %status, %body);
+            true
+        }
 
-                    error!(message = "create stream http error.", %status, %body);
-                    true
-                }
+        _ => false,
+    }
+}
 
-                RusotoError::HttpDispatch(error) => {
-                    error!(message = "create stream http dispatch.", %error);
-                    true
-                }
+impl CloudwatchError {
+    fn error_type(&self) -> &'static str {
+        match self {
+            CloudwatchError::Put(_) => "put_events",
+            CloudwatchError::Describe(_) => "describe_streams",
+            CloudwatchError::DescribeGroups(_) => "describe_groups",
+            CloudwatchError::CreateStream(_) => "create_stream",
+            CloudwatchError::CreateGroup(_) => "create_group",
+            CloudwatchError::PutRetentionPolicy(_) => "put_retention_policy",
+            CloudwatchError::InvalidConfig(_) => "invalid_config",
+            CloudwatchError::NoStreamsFound => "no_streams_found",
+            CloudwatchError::TooManyStreams => "too_many_streams",
+            CloudwatchError::ServiceDropped => "service_dropped",
+            CloudwatchError::MakeService => "make_service",
+        }
+    }
 
-                _ => false,
-            },
+    fn is_expired_credentials(&self) -> bool {
+        match self {
+            CloudwatchError::Put(error) => rusoto::is_expired_credentials(error),
+            CloudwatchError::Describe(error) => rusoto::is_expired_credentials(error),
+            CloudwatchError::DescribeGroups(error) => rusoto::is_expired_credentials(error),
+            CloudwatchError::CreateStream(error) => rusoto::is_expired_credentials(error),
+            CloudwatchError::CreateGroup(error) => rusoto::is_expired_credentials(error),
+            CloudwatchError::PutRetentionPolicy(error) => rusoto::is_expired_credentials(error),
             _ => false,
         }
     }
@@ -562,9 +898,23 @@ impl fmt::Display for CloudwatchError {
         match self {
             CloudwatchError::Put(e) => write!(f, "CloudwatchError::Put: {}", e),
             CloudwatchError::Describe(e) => write!(f, "CloudwatchError::Describe: {}", e),
+            CloudwatchError::DescribeGroups(e) => {
+                write!(f, "CloudwatchError::DescribeGroups: {}", e)
+            }
             CloudwatchError::CreateStream(e) => write!(f, "CloudwatchError::CreateStream: {}", e),
             CloudwatchError::CreateGroup(e) => write!(f, "CloudwatchError::CreateGroup: {}", e),
+            CloudwatchError::PutRetentionPolicy(e) => {
+                write!(f, "CloudwatchError::PutRetentionPolicy: {}", e)
+            }
+            CloudwatchError::InvalidConfig(message) => {
+                write!(f, "CloudwatchError::InvalidConfig: {}", message)
+            }
             CloudwatchError::NoStreamsFound => write!(f, "CloudwatchError: No Streams Found"),
+            CloudwatchError::TooManyStreams => write!(
+                f,
+                "CloudwatchError: Stream not found in the first {} pages of streams sharing its name as prefix",
+                request::MAX_DESCRIBE_PAGES
+            ),
             CloudwatchError::ServiceDropped => write!(
                 f,
                 "CloudwatchError: The service was dropped while there was a request in flight."
@@ -594,13 +944,14 @@ impl From<RusotoError<DescribeLogStreamsError>> for CloudwatchError {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{
-        dns::Resolver,
-        event::{self, Event, Value},
-        region::RegionOrEndpoint,
-        test_util::runtime,
+    use crate::event::{self, Event, Value};
+    use crate::sinks::util::encoding::TimestampFormat;
+    use metrics_runtime::Measurement;
+    use rusoto_credential::{AwsCredentials, CredentialsError, ProvideAwsCredentials};
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
     };
-    use std::collections::HashMap;
     use string_cache::DefaultAtom as Atom;
 
     #[test]
@@ -688,27 +1039,30 @@ mod tests {
         assert!(stream_val.is_none());
     }
 
-    fn svc(config: CloudwatchLogsSinkConfig) -> CloudwatchLogsSvc {
-        let config = CloudwatchLogsSinkConfig {
-            region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
-            ..config
-        };
-        let key = CloudwatchKey {
-            stream: "stream".into(),
-            group: "group".into(),
-        };
-        let rt = runtime();
-        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
-        CloudwatchLogsSvc::new(&config, &key, resolver).unwrap()
+    #[test]
+    fn partition_empty_rendered_names() {
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("log_group", "");
+        event.as_mut_log().insert("log_stream", "");
+
+        let group = Template::from("{{log_group}}");
+        let stream = Template::from("{{log_stream}}");
+        assert!(partition(event.clone(), &group, &"stream".into()).is_none());
+        assert!(partition(event, &"group".into(), &stream).is_none());
     }
 
     #[test]
     fn cloudwatch_encoded_event_retains_timestamp() {
-        let mut event = Event::from("hello world").into_log();
-        event.insert("key", "value");
-        let encoded = svc(default_config(Encoding::Json)).encode_log(event.clone());
-
-        let ts = if let Value::Timestamp(ts) = event[&event::log_schema().timestamp_key()] {
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("key", "value");
+        let encoded = encode_log(
+            event.clone(),
+            &default_config(Encoding::Json).encoding,
+            &Default::default(),
+        );
+
+        let ts = if let Value::Timestamp(ts) = event.as_log()[&event::log_schema().timestamp_key()]
+        {
             ts.timestamp_millis()
         } else {
             panic!()
@@ -720,21 +1074,1062 @@ mod tests {
     #[test]
     fn cloudwatch_encode_log_as_json() {
         let config = default_config(Encoding::Json);
-        let mut event = Event::from("hello world").into_log();
-        event.insert("key", "value");
-        let encoded = svc(config).encode_log(event.clone());
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("key", "value");
+        let encoded = encode_log(event, &config.encoding, &config.json_encoding);
         let map: HashMap<Atom, String> = serde_json::from_str(&encoded.message[..]).unwrap();
         assert!(map.get(&event::log_schema().timestamp_key()).is_none());
     }
 
+    #[test]
+    fn cloudwatch_encode_log_with_timestamp_format() {
+        let mut config = default_config(Encoding::Json);
+        config.encoding.timestamp_format = Some(TimestampFormat::Unix);
+        let timestamp = chrono::Utc.ymd(2020, 3, 5).and_hms_milli(8, 9, 10, 11);
+        let mut event = Event::from("hello world");
+        event
+            .as_mut_log()
+            .insert(event::log_schema().timestamp_key(), timestamp);
+
+        let encoded = encode_log(event, &config.encoding, &config.json_encoding);
+        let map: HashMap<Atom, serde_json::Value> =
+            serde_json::from_str(&encoded.message[..]).unwrap();
+        assert_eq!(
+            map[&event::log_schema().timestamp_key()],
+            timestamp.timestamp()
+        );
+        assert_eq!(encoded.timestamp, timestamp.timestamp_millis());
+    }
+
     #[test]
     fn cloudwatch_encode_log_as_text() {
         let config = default_config(Encoding::Text);
-        let mut event = Event::from("hello world").into_log();
-        event.insert("key", "value");
-        let encoded = svc(config).encode_log(event.clone());
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("key", "value");
+        let encoded = encode_log(event, &config.encoding, &config.json_encoding);
         assert_eq!(encoded.message, "hello world");
     }
+
+    #[test]
+    fn cloudwatch_event_size_counts_overhead() {
+        let event = encode_log(
+            Event::from("hello world"),
+            &Encoding::Text.into(),
+            &Default::default(),
+        );
+        assert_eq!(event_size(&event), 11 + 26);
+    }
+
+    #[test]
+    fn cloudwatch_batch_settings_capped_to_put_log_events_limits() {
+        let mut config = default_config(Encoding::Text);
+        let batch = config.batch_settings();
+        assert_eq!(batch.size.events, 10_000);
+        assert_eq!(batch.size.bytes, 1_048_576);
+
+        config.batch.max_events = Some(20_000);
+        config.batch.max_bytes = Some(100);
+        let batch = config.batch_settings();
+        assert_eq!(batch.size.events, 10_000);
+        assert_eq!(batch.size.bytes, 100);
+    }
+
+    #[test]
+    fn cloudwatch_retention_days_validated() {
+        let config = |days: u32| {
+            toml::from_str::<CloudwatchLogsSinkConfig>(&format!(
+                r#"
+                group_name = "group"
+                stream_name = "stream"
+                region = "us-east-1"
+                encoding = "text"
+                retention_days = {}
+                "#,
+                days
+            ))
+        };
+
+        assert_eq!(config(30).unwrap().retention_days, Some(30));
+        assert_eq!(config(3653).unwrap().retention_days, Some(3653));
+        assert!(config(0).is_err());
+        assert!(config(31).is_err());
+    }
+
+    #[test]
+    fn cloudwatch_empty_names_rejected() {
+        let config = |group_name: &str, stream_name: &str| {
+            toml::from_str::<CloudwatchLogsSinkConfig>(&format!(
+                r#"
+                group_name = "{}"
+                stream_name = "{}"
+                region = "us-east-1"
+                encoding = "text"
+                "#,
+                group_name, stream_name
+            ))
+            .unwrap()
+        };
+
+        let error = config("", "stream").log_group().unwrap_err();
+        assert_eq!(error.to_string(), "'group_name' can't be empty");
+        let error = config("group", "").log_stream().unwrap_err();
+        assert_eq!(error.to_string(), "'stream_name' can't be empty");
+
+        let config = config("{{ group }}", "{{ stream }}");
+        assert!(config.log_group().is_ok());
+        assert!(config.log_stream().is_ok());
+    }
+
+    #[test]
+    fn cloudwatch_sequence_token_needs_static_names() {
+        let config = |group_name: &str, stream_name: &str| {
+            toml::from_str::<CloudwatchLogsSinkConfig>(&format!(
+                r#"
+                group_name = "{}"
+                stream_name = "{}"
+                region = "us-east-1"
+                encoding = "text"
+                initial_sequence_token = "token"
+                "#,
+                group_name, stream_name
+            ))
+            .unwrap()
+        };
+
+        assert!(config("group", "stream").log_stream().is_ok());
+        for (group_name, stream_name) in &[("group", "{{ stream }}"), ("{{ group }}", "stream")] {
+            let error = config(group_name, stream_name).log_stream().unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "'initial_sequence_token' needs a static 'group_name' and 'stream_name'"
+            );
+        }
+    }
+
+    #[test]
+    fn cloudwatch_parses_log_group_arn() {
+        let expected = LogGroupArn {
+            region: "us-west-2".into(),
+            group_name: "/aws/lambda/app".into(),
+        };
+        let arn = "arn:aws:logs:us-west-2:123456789012:log-group:/aws/lambda/app";
+        assert_eq!(arn.parse::<LogGroupArn>().unwrap(), expected);
+        assert_eq!(
+            format!("{}:*", arn).parse::<LogGroupArn>().unwrap(),
+            expected
+        );
+
+        for invalid in &[
+            "",
+            "/aws/lambda/app",
+            "arn:aws:s3:us-west-2:123456789012:log-group:app",
+            "arn:aws:logs:us-west-2:123456789012:destination:app",
+            "arn:aws:logs::123456789012:log-group:app",
+            "arn:aws:logs:us-west-2:123456789012:log-group:",
+        ] {
+            assert!(invalid.parse::<LogGroupArn>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn cloudwatch_destination_arn_sets_group_and_region() {
+        let config: CloudwatchLogsSinkConfig = toml::from_str(
+            r#"
+            destination_arn = "arn:aws:logs:eu-west-1:123456789012:log-group:shared:*"
+            stream_name = "stream"
+            encoding = "text"
+            assume_role = "arn:aws:iam::123456789012:role/vector"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.log_group().unwrap().get_ref(), "shared");
+        assert_eq!(config.resolve_region().unwrap(), Region::EuWest1);
+
+        let config = CloudwatchLogsSinkConfig {
+            region: RegionOrEndpoint::with_region("us-east-1".into()),
+            ..config
+        };
+        assert!(config.resolve_region().is_err());
+
+        let config = CloudwatchLogsSinkConfig {
+            group_name: Some("group".into()),
+            ..config
+        };
+        assert!(config.log_group().is_err());
+
+        let config = CloudwatchLogsSinkConfig {
+            group_name: None,
+            destination_arn: None,
+            ..config
+        };
+        assert!(config.log_group().is_err());
+    }
+
+    /// Calls a CloudWatch Logs served at `addr`, for `stream` of `group`.
+    fn api_service(
+        rt: &crate::runtime::Runtime,
+        addr: std::net::SocketAddr,
+        group: &str,
+        stream: &str,
+    ) -> api::ApiService {
+        let credentials = AwsCredentialsProvider::new_minimal("test", "test");
+        api_service_with(rt, addr, credentials, group, stream, None)
+    }
+
+    fn api_service_with(
+        rt: &crate::runtime::Runtime,
+        addr: std::net::SocketAddr,
+        credentials: AwsCredentialsProvider,
+        group: &str,
+        stream: &str,
+        timeout: Option<Duration>,
+    ) -> api::ApiService {
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+        let region = Region::Custom {
+            name: "localstack".into(),
+            endpoint: format!("http://{}", addr),
+        };
+        let client = CloudWatchLogsClient::new_with(
+            rusoto::client(resolver, &Default::default()).unwrap(),
+            credentials.clone(),
+            region,
+        );
+        api::ApiService::new(client, credentials, stream.into(), group.into(), timeout)
+    }
+
+    /// Retries each call up to `retry_attempts` times, without waiting long
+    /// in between.
+    fn request_settings(retry_attempts: usize) -> TowerRequestSettings {
+        TowerRequestSettings {
+            retry_attempts,
+            retry_initial_backoff_secs: Duration::from_millis(10),
+            retry_max_duration_secs: Duration::from_millis(10),
+            ..TowerRequestConfig::default().unwrap_with(&REQUEST_DEFAULTS)
+        }
+    }
+
+    /// Runs the request `put` makes with a client made on the runtime, where
+    /// the client's request layers run.
+    fn run_put<F>(
+        rt: &mut crate::runtime::Runtime,
+        api: api::ApiService,
+        settings: TowerRequestSettings,
+        put: F,
+    ) -> crate::Result<()>
+    where
+        F: FnOnce(request::Client) -> request::CloudwatchFuture + Send + 'static,
+    {
+        rt.block_on(future::lazy(move || {
+            put(request::Client::new(api, &settings))
+        }))
+    }
+
+    fn hello() -> Vec<InputLogEvent> {
+        vec![encode_log(
+            Event::from("hello"),
+            &Encoding::Text.into(),
+            &Default::default(),
+        )]
+    }
+
+    #[test]
+    fn cloudwatch_request_timeout_is_retriable() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        // Accepts connections but never answers them.
+        let addr = crate::test_util::next_addr();
+        let listener = std::net::TcpListener::bind(addr).unwrap();
+        std::thread::spawn(move || {
+            let _connections = listener.incoming().collect::<Vec<_>>();
+        });
+
+        let credentials = AwsCredentialsProvider::new_minimal("test", "test");
+        let api = api_service_with(
+            &rt,
+            addr,
+            credentials,
+            "group",
+            "stream",
+            Some(Duration::from_millis(100)),
+        );
+
+        let (tx, _rx) = oneshot::channel();
+        let error = run_put(&mut rt, api, request_settings(0), move |client| {
+            request::CloudwatchFuture::new(
+                client,
+                false,
+                false,
+                None,
+                None,
+                hello(),
+                Some("token".into()),
+                tx,
+            )
+        })
+        .unwrap_err();
+        match error.downcast_ref::<CloudwatchError>() {
+            Some(error @ CloudwatchError::Put(RusotoError::HttpDispatch(_))) => {
+                assert!(CloudwatchRetryLogic.is_retriable_error(error))
+            }
+            _ => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    /// Hands out `key-1`, `key-2`, ... as access keys, one per fetch.
+    struct KeyPerFetch(AtomicUsize);
+
+    impl ProvideAwsCredentials for KeyPerFetch {
+        type Future = future::FutureResult<AwsCredentials, CredentialsError>;
+
+        fn credentials(&self) -> Self::Future {
+            let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            future::ok(AwsCredentials::new(
+                format!("key-{}", n),
+                "secret",
+                None,
+                None,
+            ))
+        }
+    }
+
+    /// Answers one connection per response, in order, and hands back the
+    /// requests it received. Connections getting an empty response are left
+    /// unanswered.
+    fn mock_server(
+        responses: Vec<String>,
+    ) -> (std::net::SocketAddr, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let mut unanswered = Vec::new();
+            responses
+                .into_iter()
+                .map(move |response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut request = Vec::new();
+                    let mut buf = [0; 4096];
+                    loop {
+                        let n = stream.read(&mut buf).unwrap();
+                        request.extend_from_slice(&buf[..n]);
+
+                        let text = String::from_utf8_lossy(&request).to_lowercase();
+                        if let Some(end) = text.find("\r\n\r\n") {
+                            let length = text[..end]
+                                .lines()
+                                .find(|line| line.starts_with("content-length:"))
+                                .and_then(|line| line[15..].trim().parse::<usize>().ok())
+                                .unwrap_or(0);
+                            if n == 0 || request.len() >= end + 4 + length {
+                                break;
+                            }
+                        }
+                    }
+                    if response.is_empty() {
+                        unanswered.push(stream);
+                    } else {
+                        stream.write_all(response.as_bytes()).unwrap();
+                    }
+                    String::from_utf8_lossy(&request).into_owned()
+                })
+                .collect()
+        });
+
+        (addr, handle)
+    }
+
+    fn json_response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/x-amz-json-1.1\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn cloudwatch_refreshes_expired_credentials() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        let (addr, server) = mock_server(vec![
+            json_response(
+                "400 Bad Request",
+                r#"{"__type":"ExpiredTokenException","message":"The security token included in the request is expired"}"#,
+            ),
+            json_response("200 OK", r#"{"nextSequenceToken":"token2"}"#),
+        ]);
+
+        let credentials = AwsCredentialsProvider::from_provider(KeyPerFetch(AtomicUsize::new(0)));
+        let api = api_service_with(&rt, addr, credentials, "group", "stream", None);
+
+        // The put is retried with the refreshed credentials.
+        let (tx, rx) = oneshot::channel();
+        run_put(&mut rt, api, request_settings(1), move |client| {
+            request::CloudwatchFuture::new(
+                client,
+                false,
+                false,
+                None,
+                None,
+                hello(),
+                Some("token".into()),
+                tx,
+            )
+        })
+        .unwrap();
+        assert_eq!(rx.wait().unwrap(), Some("token2".into()));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("Credential=key-1/"));
+        assert!(requests[1].contains("Credential=key-2/"));
+    }
+
+    #[test]
+    fn cloudwatch_skips_empty_batches() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        // Refuses connections once it's done, so any request would fail.
+        let (addr, server) = mock_server(Vec::new());
+        let requests = server.join().unwrap();
+        assert!(requests.is_empty());
+
+        for token in vec![Some("token".to_string()), None] {
+            let api = api_service(&rt, addr, "group", "stream");
+            let (tx, rx) = oneshot::channel();
+            let put_token = token.clone();
+            run_put(&mut rt, api, request_settings(0), move |client| {
+                request::CloudwatchFuture::new(
+                    client,
+                    true,
+                    true,
+                    Some(request::Retention {
+                        days: 7,
+                        force: true,
+                    }),
+                    None,
+                    Vec::new(),
+                    put_token,
+                    tx,
+                )
+            })
+            .unwrap();
+            assert_eq!(rx.wait().unwrap(), token);
+        }
+    }
+
+    #[test]
+    fn cloudwatch_empty_names_fail_without_requests() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        // Refuses connections once it's done, so any request would fail.
+        let (addr, server) = mock_server(Vec::new());
+        server.join().unwrap();
+
+        for (group, stream) in vec![("", "stream"), ("group", "")] {
+            let api = api_service(&rt, addr, group, stream);
+            let (tx, _rx) = oneshot::channel();
+            let error = run_put(&mut rt, api, request_settings(0), move |client| {
+                request::CloudwatchFuture::new(client, true, true, None, None, hello(), None, tx)
+            })
+            .unwrap_err();
+
+            match error.downcast_ref::<CloudwatchError>() {
+                Some(error @ CloudwatchError::InvalidConfig(_)) => {
+                    assert!(!CloudwatchRetryLogic.is_retriable_error(error))
+                }
+                _ => panic!("Unexpected error: {}", error),
+            }
+        }
+    }
+
+    fn put_without_token(
+        addr: std::net::SocketAddr,
+        create_missing_stream: bool,
+    ) -> crate::Result<()> {
+        put_without_token_with(addr, create_missing_stream, request_settings(0))
+    }
+
+    fn put_without_token_with(
+        addr: std::net::SocketAddr,
+        create_missing_stream: bool,
+        settings: TowerRequestSettings,
+    ) -> crate::Result<()> {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+        let api = api_service(&rt, addr, "group", "stream");
+
+        let (tx, _rx) = oneshot::channel();
+        run_put(&mut rt, api, settings, move |client| {
+            request::CloudwatchFuture::new(
+                client,
+                false,
+                create_missing_stream,
+                None,
+                None,
+                hello(),
+                None,
+                tx,
+            )
+        })
+    }
+
+    fn assert_error(result: crate::Result<()>, expected: fn(&CloudwatchError) -> bool) {
+        let error = result.unwrap_err();
+        match error.downcast_ref::<CloudwatchError>() {
+            Some(error) if expected(error) => (),
+            _ => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    fn check_access_against(
+        response: String,
+        create_missing_group: bool,
+    ) -> (crate::Result<()>, Vec<String>) {
+        check_access_with(response, true, create_missing_group)
+    }
+
+    fn check_access_with(
+        response: String,
+        prefixed: bool,
+        create_missing_group: bool,
+    ) -> (crate::Result<()>, Vec<String>) {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        let (addr, server) = mock_server(vec![response]);
+        let api = api_service(&rt, addr, "group", "stream");
+
+        let result = rt.block_on(check_streams_access(api, prefixed, create_missing_group));
+        (result, server.join().unwrap())
+    }
+
+    #[test]
+    fn cloudwatch_healthcheck_fails_on_access_denied() {
+        let (result, requests) = check_access_against(
+            json_response(
+                "400 Bad Request",
+                r#"{"__type":"AccessDeniedException","message":"User is not authorized to perform: logs:DescribeLogStreams"}"#,
+            ),
+            true,
+        );
+
+        assert!(requests[0].contains("DescribeLogStreams"));
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("Not allowed to describe the streams of log group \"group\""),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn cloudwatch_healthcheck_passes_on_missing_group_to_create() {
+        let not_found = || {
+            json_response(
+                "400 Bad Request",
+                r#"{"__type":"ResourceNotFoundException","message":"The specified log group does not exist."}"#,
+            )
+        };
+
+        let (result, _) = check_access_against(not_found(), true);
+        result.unwrap();
+
+        let (result, _) = check_access_against(not_found(), false);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Log group \"group\" does not exist and `create_missing_group` is false"
+        );
+    }
+
+    #[test]
+    fn cloudwatch_healthcheck_passes_on_existing_group() {
+        let (result, _) =
+            check_access_against(json_response("200 OK", r#"{"logStreams":[]}"#), false);
+        result.unwrap();
+    }
+
+    #[test]
+    fn cloudwatch_healthcheck_describes_any_stream_for_dynamic_name() {
+        let response = || json_response("200 OK", r#"{"logStreams":[]}"#);
+
+        let (result, requests) = check_access_with(response(), true, false);
+        result.unwrap();
+        assert!(requests[0].contains(r#""logStreamNamePrefix":"stream""#));
+
+        let (result, requests) = check_access_with(response(), false, false);
+        result.unwrap();
+        assert!(!requests[0].contains("logStreamNamePrefix"));
+    }
+
+    #[test]
+    fn cloudwatch_describe_finds_exact_stream_across_pages() {
+        let (addr, server) = mock_server(vec![
+            json_response(
+                "200 OK",
+                r#"{"logStreams":[{"logStreamName":"stream-1","uploadSequenceToken":"wrong1"},{"logStreamName":"stream-2","uploadSequenceToken":"wrong2"}],"nextToken":"page2"}"#,
+            ),
+            json_response(
+                "200 OK",
+                r#"{"logStreams":[{"logStreamName":"stream-3","uploadSequenceToken":"wrong3"},{"logStreamName":"stream","uploadSequenceToken":"right"}],"nextToken":"page3"}"#,
+            ),
+            json_response("200 OK", r#"{"nextSequenceToken":"token2"}"#),
+        ]);
+
+        put_without_token(addr, false).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("page2"));
+        assert!(requests[1].contains(r#""nextToken":"page2""#));
+        assert!(requests[2].contains(r#""sequenceToken":"right""#));
+    }
+
+    #[test]
+    fn cloudwatch_describe_without_exact_stream() {
+        let (addr, server) = mock_server(vec![json_response(
+            "200 OK",
+            r#"{"logStreams":[{"logStreamName":"stream-1","uploadSequenceToken":"wrong1"}]}"#,
+        )]);
+
+        assert_error(put_without_token(addr, false), |error| {
+            matches!(error, CloudwatchError::NoStreamsFound)
+        });
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cloudwatch_describe_pages_are_bounded() {
+        let page = json_response(
+            "200 OK",
+            r#"{"logStreams":[{"logStreamName":"stream-1","uploadSequenceToken":"wrong1"}],"nextToken":"more"}"#,
+        );
+        let (addr, server) = mock_server(vec![page; request::MAX_DESCRIBE_PAGES]);
+
+        assert_error(put_without_token(addr, true), |error| {
+            matches!(error, CloudwatchError::TooManyStreams)
+        });
+        assert_eq!(server.join().unwrap().len(), request::MAX_DESCRIBE_PAGES);
+    }
+
+    #[test]
+    fn cloudwatch_retries_each_call() {
+        let unavailable = || {
+            json_response(
+                "503 Service Unavailable",
+                r#"{"__type":"ServiceUnavailableException","message":"The service cannot complete the request."}"#,
+            )
+        };
+        let (addr, server) = mock_server(vec![
+            unavailable(),
+            json_response("200 OK", r#"{"logStreams":[]}"#),
+            unavailable(),
+            json_response("200 OK", ""),
+            json_response(
+                "200 OK",
+                r#"{"logStreams":[{"logStreamName":"stream","uploadSequenceToken":"right"}]}"#,
+            ),
+            unavailable(),
+            json_response("200 OK", r#"{"nextSequenceToken":"token2"}"#),
+        ]);
+
+        put_without_token_with(addr, true, request_settings(1)).unwrap();
+
+        // Only the failed call is made again, not those before it.
+        let requests = server.join().unwrap();
+        let calls = requests
+            .iter()
+            .map(|request| {
+                ["DescribeLogStreams", "CreateLogStream", "PutLogEvents"]
+                    .iter()
+                    .find(|call| request.contains(*call))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                &"DescribeLogStreams",
+                &"DescribeLogStreams",
+                &"CreateLogStream",
+                &"CreateLogStream",
+                &"DescribeLogStreams",
+                &"PutLogEvents",
+                &"PutLogEvents",
+            ]
+        );
+        assert!(requests[5].contains(r#""sequenceToken":"right""#));
+        assert!(requests[6].contains(r#""sequenceToken":"right""#));
+    }
+
+    #[test]
+    fn cloudwatch_rate_limits_each_call() {
+        let (addr, server) = mock_server(vec![
+            json_response("200 OK", r#"{"logStreams":[]}"#),
+            json_response("200 OK", ""),
+            json_response("200 OK", r#"{"logStreams":[{"logStreamName":"stream"}]}"#),
+            json_response("200 OK", r#"{"nextSequenceToken":"token"}"#),
+        ]);
+
+        let settings = TowerRequestSettings {
+            rate_limit_num: 1,
+            rate_limit_duration: Duration::from_millis(200),
+            ..request_settings(0)
+        };
+        let start = std::time::Instant::now();
+        put_without_token_with(addr, true, settings).unwrap();
+
+        // Describing, creating the stream, describing it again and putting
+        // make four calls, one per 200ms.
+        assert!(start.elapsed() >= Duration::from_millis(600));
+        assert_eq!(server.join().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn cloudwatch_counts_api_calls() {
+        crate::metrics::init_test();
+        let controller = crate::metrics::CONTROLLER.get().unwrap();
+        let count = |name: &str, error_type: Option<&str>| {
+            controller
+                .snapshot()
+                .into_measurements()
+                .into_iter()
+                .filter(|(key, _)| {
+                    let has_label = |label_key: &str, label_value: &str| {
+                        key.labels()
+                            .any(|label| label.key() == label_key && label.value() == label_value)
+                    };
+                    key.name() == name
+                        && has_label("log_group", "metrics-group")
+                        && has_label("log_stream", "stream")
+                        && error_type.map_or(true, |error_type| has_label("error_type", error_type))
+                })
+                .map(|(_, measurement)| match measurement {
+                    Measurement::Counter(count) => count,
+                    _ => panic!("{} is not a counter", name),
+                })
+                .sum::<u64>()
+        };
+
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        let (addr, server) = mock_server(vec![
+            json_response(
+                "400 Bad Request",
+                r#"{"__type":"ResourceNotFoundException","message":"The specified log group does not exist."}"#,
+            ),
+            json_response("200 OK", ""),
+            json_response("200 OK", ""),
+            json_response("200 OK", r#"{"logStreams":[{"logStreamName":"stream"}]}"#),
+            json_response("200 OK", r#"{"nextSequenceToken":"token1"}"#),
+            json_response(
+                "400 Bad Request",
+                r#"{"__type":"InvalidParameterException","message":"Invalid parameter."}"#,
+            ),
+        ]);
+        let api = api_service(&rt, addr, "metrics-group", "stream");
+
+        let mut put = |token: Option<String>| {
+            let (tx, rx) = oneshot::channel();
+            run_put(&mut rt, api.clone(), request_settings(0), move |client| {
+                request::CloudwatchFuture::new(client, true, true, None, None, hello(), token, tx)
+            })
+            .map(|()| rx.wait().unwrap())
+        };
+
+        // Describes the stream, then creates the missing group and stream.
+        let token = put(None).unwrap();
+        assert_eq!(token, Some("token1".into()));
+        assert_eq!(count("cloudwatch_describe_streams_total", None), 2);
+        assert_eq!(count("cloudwatch_create_group_total", None), 1);
+        assert_eq!(count("cloudwatch_create_stream_total", None), 1);
+        assert_eq!(count("cloudwatch_put_events_total", None), 1);
+        assert_eq!(count("cloudwatch_request_errors", None), 0);
+
+        // With the token the events are put right away.
+        put(token).unwrap_err();
+        assert_eq!(count("cloudwatch_describe_streams_total", None), 2);
+        assert_eq!(count("cloudwatch_put_events_total", None), 2);
+        assert_eq!(count("cloudwatch_request_errors", Some("put_events")), 1);
+        assert_eq!(count("cloudwatch_request_errors", None), 1);
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn cloudwatch_checks_group_once_for_its_streams() {
+        const STREAMS: usize = 10;
+
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        // The streams' requests go out in any order, so every response
+        // answers any of them.
+        let streams = (0..STREAMS)
+            .map(|i| format!(r#"{{"logStreamName":"stream-{}"}}"#, i))
+            .collect::<Vec<_>>()
+            .join(",");
+        let response = json_response(
+            "200 OK",
+            &format!(
+                r#"{{"logGroups":[{{"logGroupName":"group"}}],"logStreams":[{}],"nextSequenceToken":"token"}}"#,
+                streams
+            ),
+        );
+        let not_found = json_response(
+            "400 Bad Request",
+            r#"{"__type":"ResourceNotFoundException","message":"The specified log group does not exist."}"#,
+        );
+        let mut responses = vec![response.clone(); 1 + 2 * STREAMS];
+        responses.push(not_found);
+        responses.extend(vec![response; 4 + 3]);
+        let (addr, server) = mock_server(responses);
+
+        let apis = (0..STREAMS)
+            .map(|stream| api_service(&rt, addr, "group", &format!("stream-{}", stream)))
+            .collect::<Vec<_>>();
+        let group_checks = request::GroupChecks::default();
+        let put = {
+            let group_checks = group_checks.clone();
+            move |api: api::ApiService| {
+                let (tx, _rx) = oneshot::channel();
+                request::CloudwatchFuture::new(
+                    request::Client::new(api, &request_settings(0)),
+                    true,
+                    true,
+                    None,
+                    Some(group_checks.clone()),
+                    hello(),
+                    None,
+                    tx,
+                )
+            }
+        };
+
+        // The first requests of all the streams share one group check.
+        let (all, first, second) = (apis.clone(), apis[0].clone(), apis[1].clone());
+        let put_all = put.clone();
+        rt.block_on(future::lazy(move || {
+            future::join_all(all.into_iter().map(put_all).collect::<Vec<_>>())
+        }))
+        .unwrap();
+        // The group is found missing after all, so it's created and checked
+        // again for the next stream.
+        let put_first = put.clone();
+        rt.block_on(future::lazy(move || put_first(first))).unwrap();
+        rt.block_on(future::lazy(move || put(second))).unwrap();
+
+        let requests = server.join().unwrap();
+        let count = |call: &str| requests.iter().filter(|r| r.contains(call)).count();
+        assert_eq!(count("DescribeLogGroups"), 2);
+        assert_eq!(count("DescribeLogStreams"), STREAMS + 3);
+        assert_eq!(count("CreateLogGroup"), 1);
+        assert_eq!(count("CreateLogStream"), 1);
+        assert_eq!(count("PutLogEvents"), STREAMS + 2);
+    }
+
+    #[test]
+    fn cloudwatch_put_completes_after_service_is_dropped() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        let (addr, server) = mock_server(vec![json_response(
+            "200 OK",
+            r#"{"nextSequenceToken":"token2"}"#,
+        )]);
+        let api = api_service(&rt, addr, "group", "stream");
+
+        let (tx, rx) = oneshot::channel();
+        drop(rx);
+        run_put(&mut rt, api, request_settings(0), move |client| {
+            request::CloudwatchFuture::new(
+                client,
+                false,
+                false,
+                None,
+                None,
+                hello(),
+                Some("token".into()),
+                tx,
+            )
+        })
+        .unwrap();
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cloudwatch_rejected_token_falls_back_to_describe() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        let invalid_token = json_response(
+            "400 Bad Request",
+            r#"{"__type":"InvalidSequenceTokenException","message":"The given sequenceToken is invalid."}"#,
+        );
+        let (addr, server) = mock_server(vec![
+            invalid_token.clone(),
+            json_response(
+                "200 OK",
+                r#"{"logStreams":[{"logStreamName":"stream","uploadSequenceToken":"right"}]}"#,
+            ),
+            json_response("200 OK", r#"{"nextSequenceToken":"token2"}"#),
+            invalid_token,
+            json_response(
+                "200 OK",
+                r#"{"logStreams":[{"logStreamName":"stream","uploadSequenceToken":"other"}]}"#,
+            ),
+            json_response("200 OK", r#"{"nextSequenceToken":"token3"}"#),
+        ]);
+        let api = api_service(&rt, addr, "group", "stream");
+
+        let mut svc = rt
+            .block_on(future::lazy(move || {
+                Ok::<_, ()>(CloudwatchLogsSvc {
+                    client: request::Client::new(api, &request_settings(0)),
+                    create_missing_group: false,
+                    create_missing_stream: false,
+                    retention: None,
+                    token: Some("stale".into()),
+                    token_rx: None,
+                    group_checks: None,
+                })
+            }))
+            .unwrap();
+        let mut put = |svc: &mut CloudwatchLogsSvc| {
+            rt.block_on(futures01::future::poll_fn(|| svc.poll_ready()))
+                .unwrap();
+            rt.block_on(svc.call(hello()))
+        };
+
+        // The stale seed is rejected, so the token of the stream is used.
+        put(&mut svc).unwrap();
+        // So is a token from a put, once another writer put to the stream.
+        put(&mut svc).unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 6);
+        assert!(requests[0].contains(r#""sequenceToken":"stale""#));
+        assert!(requests[1].contains("DescribeLogStreams"));
+        assert!(requests[2].contains(r#""sequenceToken":"right""#));
+        assert!(requests[3].contains(r#""sequenceToken":"token2""#));
+        assert!(requests[4].contains("DescribeLogStreams"));
+        assert!(requests[5].contains(r#""sequenceToken":"other""#));
+    }
+
+    #[test]
+    fn cloudwatch_retried_put_is_not_duplicated() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        // The first put times out, though CloudWatch accepted it.
+        let (addr, server) = mock_server(vec![
+            String::new(),
+            json_response(
+                "400 Bad Request",
+                r#"{"__type":"DataAlreadyAcceptedException","expectedSequenceToken":"token2","message":"The given batch of log events has already been accepted."}"#,
+            ),
+            json_response(
+                "200 OK",
+                r#"{"logStreams":[{"logStreamName":"stream","uploadSequenceToken":"token2"}]}"#,
+            ),
+        ]);
+        let credentials = AwsCredentialsProvider::new_minimal("test", "test");
+        let api = api_service_with(
+            &rt,
+            addr,
+            credentials,
+            "group",
+            "stream",
+            Some(Duration::from_millis(100)),
+        );
+
+        let (tx, rx) = oneshot::channel();
+        run_put(&mut rt, api, request_settings(1), move |client| {
+            request::CloudwatchFuture::new(
+                client,
+                false,
+                false,
+                None,
+                None,
+                hello(),
+                Some("token".into()),
+                tx,
+            )
+        })
+        .unwrap();
+        assert_eq!(rx.wait().unwrap(), Some("token2".into()));
+
+        // The retry is made with the same token, and the events aren't put
+        // again once they turn out to be accepted.
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].contains(r#""sequenceToken":"token""#));
+        assert!(requests[1].contains(r#""sequenceToken":"token""#));
+        assert!(requests[2].contains("DescribeLogStreams"));
+    }
+
+    #[test]
+    fn cloudwatch_dead_letters_original_events() {
+        use futures01::Stream;
+
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+
+        let (addr, server) = mock_server(vec![json_response(
+            "400 Bad Request",
+            r#"{"__type":"InvalidParameterException","message":"Log event too large."}"#,
+        )]);
+        let api = api_service(&rt, addr, "group", "stream");
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+        let (tx, rx) = futures01::sync::mpsc::channel(10);
+        let key = CloudwatchKey {
+            group: "group".into(),
+            stream: "stream".into(),
+        };
+
+        // The service of the stream is made up front, so it puts to the
+        // mock server.
+        let stream_key = key.clone();
+        let mut svc = rt
+            .block_on(future::lazy(move || {
+                let config = default_config(Encoding::Json);
+                let mut svc = CloudwatchLogsPartitionSvc {
+                    request_settings: config.request.unwrap_with(&REQUEST_DEFAULTS),
+                    config,
+                    clients: HashMap::new(),
+                    group_checks: None,
+                    resolver,
+                    dead_letter: DeadLetter::new("cloudwatch", tx),
+                    request_metrics: RequestMetricsLayer::new("cloudwatch"),
+                };
+                let stream = svc.stream_service(CloudwatchLogsSvc {
+                    client: request::Client::new(api, &request_settings(0)),
+                    create_missing_group: false,
+                    create_missing_stream: false,
+                    retention: None,
+                    token: Some("token".into()),
+                    token_rx: None,
+                    group_checks: None,
+                });
+                svc.clients.insert(stream_key, stream);
+                Ok::<_, ()>(svc)
+            }))
+            .unwrap();
+
+        let mut event = Event::from("hello");
+        event.as_mut_log().insert("key", "value");
+        let encoded = EncodedEvent {
+            event: encode_log(event.clone(), &Encoding::Json.into(), &Default::default()),
+            original: Some(event),
+        };
+        rt.block_on(svc.call(PartitionInnerBuffer::new(vec![encoded], key)))
+            .unwrap();
+        drop(svc);
+
+        // The event itself is dead-lettered, not what was sent to CloudWatch.
+        let events = rx.collect().wait().unwrap();
+        assert_eq!(events.len(), 1);
+        let log = events[0].as_log();
+        assert_eq!(log[&event::log_schema().message_key()], "hello".into());
+        assert_eq!(log[&"key".into()], "value".into());
+        assert!(log.get(&event::log_schema().timestamp_key()).is_some());
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
 }
 
 #[cfg(feature = "cloudwatch-logs-integration-tests")]
@@ -750,10 +2145,23 @@ mod integration_tests {
     use futures01::Sink;
     use pretty_assertions::assert_eq;
     use rusoto_core::Region;
-    use rusoto_logs::{CloudWatchLogs, CreateLogGroupRequest, GetLogEventsRequest};
+    use rusoto_logs::{
+        CloudWatchLogs, CreateLogGroupRequest, DescribeLogGroupsRequest, GetLogEventsRequest,
+    };
 
     const GROUP_NAME: &'static str = "vector-cw";
 
+    fn create_client(
+        region: Region,
+        assume_role: Option<String>,
+        resolver: Resolver,
+        proxy: &ProxyConfig,
+        connect_timeout: Option<Duration>,
+    ) -> crate::Result<CloudWatchLogsClient> {
+        let credentials = AwsCredentialsProvider::new(&region, assume_role)?;
+        create_client_with_credentials(region, credentials, resolver, proxy, connect_timeout)
+    }
+
     #[test]
     fn cloudwatch_insert_log_event() {
         let mut rt = Runtime::single_threaded().unwrap();
@@ -769,14 +2177,24 @@ mod integration_tests {
 
         let config = CloudwatchLogsSinkConfig {
             stream_name: stream_name.clone().into(),
-            group_name: GROUP_NAME.into(),
+            group_name: Some(GROUP_NAME.into()),
+            destination_arn: None,
             region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
             encoding: Encoding::Text.into(),
+            json_encoding: Default::default(),
             create_missing_group: None,
             create_missing_stream: None,
             batch: Default::default(),
             request: Default::default(),
             assume_role: None,
+            proxy: Default::default(),
+            request_timeout_secs: None,
+            connect_timeout_secs: None,
+            retention_days: None,
+            force_retention: false,
+            initial_sequence_token: None,
+            share_group_checks: false,
+            partition_limit: None,
         };
 
         let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
@@ -795,7 +2213,7 @@ mod integration_tests {
         request.log_group_name = GROUP_NAME.into();
         request.start_time = Some(timestamp.timestamp_millis());
 
-        let client = create_client(region, None, resolver).unwrap();
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
 
         let response = rt.block_on(client.get_log_events(request)).unwrap();
 
@@ -824,14 +2242,24 @@ mod integration_tests {
 
         let config = CloudwatchLogsSinkConfig {
             stream_name: stream_name.clone().into(),
-            group_name: group_name.clone().into(),
+            group_name: Some(group_name.clone().into()),
+            destination_arn: None,
             region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
             encoding: Encoding::Text.into(),
+            json_encoding: Default::default(),
             create_missing_group: None,
             create_missing_stream: None,
             batch: Default::default(),
             request: Default::default(),
             assume_role: None,
+            proxy: Default::default(),
+            request_timeout_secs: None,
+            connect_timeout_secs: None,
+            retention_days: None,
+            force_retention: false,
+            initial_sequence_token: None,
+            share_group_checks: false,
+            partition_limit: None,
         };
 
         let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
@@ -850,7 +2278,7 @@ mod integration_tests {
         request.log_group_name = group_name;
         request.start_time = Some(timestamp.timestamp_millis());
 
-        let client = create_client(region, None, resolver).unwrap();
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
 
         let response = rt.block_on(client.get_log_events(request)).unwrap();
 
@@ -880,17 +2308,28 @@ mod integration_tests {
 
         let config = CloudwatchLogsSinkConfig {
             stream_name: stream_name.clone().into(),
-            group_name: group_name.clone().into(),
+            group_name: Some(group_name.clone().into()),
+            destination_arn: None,
             region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
             encoding: Encoding::Text.into(),
+            json_encoding: Default::default(),
             create_missing_group: None,
             create_missing_stream: None,
-            batch: BatchEventsConfig {
+            batch: BatchConfig {
                 timeout_secs: None,
                 max_events: Some(2),
+                ..Default::default()
             },
             request: Default::default(),
             assume_role: None,
+            proxy: Default::default(),
+            request_timeout_secs: None,
+            connect_timeout_secs: None,
+            retention_days: None,
+            force_retention: false,
+            initial_sequence_token: None,
+            share_group_checks: false,
+            partition_limit: None,
         };
 
         let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
@@ -909,7 +2348,7 @@ mod integration_tests {
         request.log_group_name = group_name.into();
         request.start_time = Some(timestamp.timestamp_millis());
 
-        let client = create_client(region, None, resolver).unwrap();
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
 
         let response = rt.block_on(client.get_log_events(request)).unwrap();
 
@@ -936,19 +2375,30 @@ mod integration_tests {
             endpoint: "http://localhost:6000".into(),
         };
 
-        let client = create_client(region.clone(), None, resolver).unwrap();
+        let client =
+            create_client(region.clone(), None, resolver, &Default::default(), None).unwrap();
         ensure_group(region);
 
         let config = CloudwatchLogsSinkConfig {
-            group_name: GROUP_NAME.into(),
+            group_name: Some(GROUP_NAME.into()),
+            destination_arn: None,
             stream_name: format!("{}-{{{{key}}}}", stream_name).into(),
             region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
             encoding: Encoding::Text.into(),
+            json_encoding: Default::default(),
             create_missing_group: None,
             create_missing_stream: None,
             batch: Default::default(),
             request: Default::default(),
             assume_role: None,
+            proxy: Default::default(),
+            request_timeout_secs: None,
+            connect_timeout_secs: None,
+            retention_days: None,
+            force_retention: false,
+            initial_sequence_token: None,
+            share_group_checks: false,
+            partition_limit: None,
         };
 
         let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
@@ -1018,6 +2468,130 @@ mod integration_tests {
         assert_eq!(output_lines, expected_output);
     }
 
+    #[test]
+    fn cloudwatch_retention_on_created_group() {
+        let mut rt = Runtime::single_threaded().unwrap();
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+
+        let group_name = gen_name();
+        let region = Region::Custom {
+            name: "localstack".into(),
+            endpoint: "http://localhost:6000".into(),
+        };
+
+        let config = CloudwatchLogsSinkConfig {
+            group_name: Some(group_name.clone().into()),
+            stream_name: gen_name().into(),
+            region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
+            retention_days: Some(7),
+            ..default_config(Encoding::Text)
+        };
+
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+        let (_, events) = random_lines_with_stream(100, 11);
+        let (sink, _) = rt.block_on(sink.send_all(events)).unwrap();
+        drop(sink);
+
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
+        assert_eq!(retention_in_days(&mut rt, &client, group_name), Some(7));
+    }
+
+    #[test]
+    fn cloudwatch_forced_retention_on_existing_group() {
+        let mut rt = Runtime::single_threaded().unwrap();
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+
+        let group_name = gen_name();
+        let region = Region::Custom {
+            name: "localstack".into(),
+            endpoint: "http://localhost:6000".into(),
+        };
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
+        rt.block_on(client.create_log_group(CreateLogGroupRequest {
+            log_group_name: group_name.clone(),
+            ..Default::default()
+        }))
+        .unwrap();
+
+        let config = CloudwatchLogsSinkConfig {
+            group_name: Some(group_name.clone().into()),
+            stream_name: gen_name().into(),
+            region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
+            retention_days: Some(14),
+            force_retention: true,
+            ..default_config(Encoding::Text)
+        };
+
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+        let (_, events) = random_lines_with_stream(100, 11);
+        let (sink, _) = rt.block_on(sink.send_all(events)).unwrap();
+        drop(sink);
+
+        assert_eq!(retention_in_days(&mut rt, &client, group_name), Some(14));
+    }
+
+    #[test]
+    fn cloudwatch_insert_log_event_cross_account() {
+        let mut rt = Runtime::single_threaded().unwrap();
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+
+        let group_name = gen_name();
+        let stream_name = gen_name();
+
+        let config = CloudwatchLogsSinkConfig {
+            group_name: None,
+            destination_arn: Some(format!(
+                "arn:aws:logs:us-east-1:000000000000:log-group:{}:*",
+                group_name
+            )),
+            stream_name: stream_name.clone().into(),
+            region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
+            assume_role: Some("arn:aws:iam::000000000000:role/vector-cross-account".into()),
+            ..default_config(Encoding::Text)
+        };
+
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+
+        let timestamp = chrono::Utc::now();
+        let (input_lines, events) = random_lines_with_stream(100, 11);
+        let (sink, _) = rt.block_on(sink.send_all(events)).unwrap();
+        drop(sink);
+
+        let region = Region::Custom {
+            name: "us-east-1".into(),
+            endpoint: "http://localhost:6000".into(),
+        };
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
+
+        let mut request = GetLogEventsRequest::default();
+        request.log_stream_name = stream_name;
+        request.log_group_name = group_name;
+        request.start_time = Some(timestamp.timestamp_millis());
+
+        let response = rt.block_on(client.get_log_events(request)).unwrap();
+        let output_lines = response
+            .events
+            .unwrap()
+            .into_iter()
+            .map(|e| e.message.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(output_lines, input_lines);
+    }
+
+    fn retention_in_days(
+        rt: &mut Runtime,
+        client: &CloudWatchLogsClient,
+        group_name: String,
+    ) -> Option<i64> {
+        let request = DescribeLogGroupsRequest {
+            log_group_name_prefix: Some(group_name),
+            ..Default::default()
+        };
+        let response = rt.block_on(client.describe_log_groups(request)).unwrap();
+        response.log_groups.unwrap()[0].retention_in_days
+    }
+
     #[test]
     fn cloudwatch_healthcheck() {
         let region = Region::Custom {
@@ -1028,14 +2602,24 @@ mod integration_tests {
 
         let config = CloudwatchLogsSinkConfig {
             stream_name: "test-stream".into(),
-            group_name: GROUP_NAME.into(),
+            group_name: Some(GROUP_NAME.into()),
+            destination_arn: None,
             region: RegionOrEndpoint::with_endpoint("http://localhost:6000".into()),
             encoding: Encoding::Text.into(),
+            json_encoding: Default::default(),
             create_missing_group: None,
             create_missing_stream: None,
             batch: Default::default(),
             request: Default::default(),
             assume_role: None,
+            proxy: Default::default(),
+            request_timeout_secs: None,
+            connect_timeout_secs: None,
+            retention_days: None,
+            force_retention: false,
+            initial_sequence_token: None,
+            share_group_checks: false,
+            partition_limit: None,
         };
 
         let mut rt = Runtime::single_threaded().unwrap();
@@ -1048,7 +2632,7 @@ mod integration_tests {
         let mut rt = Runtime::single_threaded().unwrap();
         let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
 
-        let client = create_client(region, None, resolver).unwrap();
+        let client = create_client(region, None, resolver, &Default::default(), None).unwrap();
 
         let req = CreateLogGroupRequest {
             log_group_name: GROUP_NAME.into(),
@@ -1,58 +1,163 @@
-use super::CloudwatchError;
-use futures01::{sync::oneshot, try_ready, Async, Future, Poll};
-use rusoto_core::{RusotoError, RusotoFuture};
+use super::{
+    api::{ApiRequest, ApiResponse, ApiService},
+    CloudwatchError, CloudwatchRetryLogic,
+};
+use crate::{
+    internal_events::CloudwatchLogsRequestFailed,
+    sinks::util::{
+        service::{Elapsed, ServiceBuilderExt},
+        TowerRequestSettings,
+    },
+};
+use futures01::{
+    future::{self, Shared},
+    sync::oneshot,
+    try_ready, Async, Future, Poll,
+};
+use rusoto_core::RusotoError;
 use rusoto_logs::{
-    CloudWatchLogs, CloudWatchLogsClient, CreateLogGroupError, CreateLogGroupRequest,
-    CreateLogStreamError, CreateLogStreamRequest, DescribeLogStreamsError,
-    DescribeLogStreamsRequest, DescribeLogStreamsResponse, InputLogEvent, PutLogEventsError,
-    PutLogEventsRequest, PutLogEventsResponse,
+    CreateLogGroupError, DescribeLogGroupsResponse, DescribeLogStreamsError,
+    DescribeLogStreamsResponse, InputLogEvent, PutLogEventsError, PutLogEventsResponse,
+    PutRetentionPolicyError,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
 };
+use tower::{buffer::Buffer, util::BoxService, Service, ServiceBuilder, ServiceExt};
+
+/// Bounds how many pages of streams sharing the stream name as prefix are
+/// looked through for the stream itself.
+pub const MAX_DESCRIBE_PAGES: usize = 10;
+
+/// Bounds how often a put describes the stream for a new token after its
+/// token was rejected, as happens while another writer puts to the stream.
+const MAX_TOKEN_REFRESHES: usize = 3;
 
 pub struct CloudwatchFuture {
     client: Client,
     state: State,
     create_missing_group: bool,
     create_missing_stream: bool,
-    events: Option<Vec<InputLogEvent>>,
+    retention: Option<Retention>,
+    group_checks: Option<GroupChecks>,
+    group_created: bool,
+    describe_pages: usize,
+    /// Kept until they're put, as a put that is retried or made with a
+    /// stale token needs the token of the stream to put them again.
+    events: Vec<InputLogEvent>,
+    /// An earlier attempt at the put went through, so only the token of the
+    /// stream is left to get.
+    accepted: bool,
+    token_refreshes: usize,
     token_tx: Option<oneshot::Sender<Option<String>>>,
 }
 
-struct Client {
-    client: CloudWatchLogsClient,
+/// Retention to set on log groups. Only groups created by the sink get it,
+/// unless `force` is set.
+#[derive(Clone, Copy, Debug)]
+pub struct Retention {
+    pub days: i64,
+    pub force: bool,
+}
+
+/// The checks of whether the log groups of a sink exist, shared by all the
+/// streams of each group, so a group with many streams is described once
+/// rather than by each stream. A check applies a forced retention, and
+/// creates the group if it's missing and `create_missing_group` is set.
+///
+/// A check's outcome is kept until a stream finds its group missing after
+/// all, as happens when the group is deleted.
+#[derive(Clone, Default)]
+pub struct GroupChecks(Arc<Mutex<HashMap<String, GroupCheck>>>);
+
+type GroupCheck = Shared<Box<dyn Future<Item = GroupStatus, Error = ()> + Send>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GroupStatus {
+    Exists,
+    Created,
+    /// The check failed or found no group to create, so each stream finds
+    /// out for itself, as without shared checks.
+    Unknown,
+}
+
+impl GroupChecks {
+    fn check(
+        &self,
+        client: &Client,
+        create_missing_group: bool,
+        retention: Option<Retention>,
+    ) -> GroupCheck {
+        let mut checks = self.0.lock().unwrap();
+        checks
+            .entry(client.group_name.clone())
+            .or_insert_with(|| {
+                let check = client
+                    .check_group(create_missing_group, retention)
+                    .or_else(|error| {
+                        warn!(message = "log group check failed; checking per stream.", %error);
+                        Ok::<_, ()>(GroupStatus::Unknown)
+                    });
+                let check: Box<dyn Future<Item = GroupStatus, Error = ()> + Send> = Box::new(check);
+                check.shared()
+            })
+            .clone()
+    }
+
+    fn invalidate(&self, group_name: &str) {
+        self.0.lock().unwrap().remove(group_name);
+    }
+}
+
+/// The calls of one stream, through the sink's request layers.
+pub type Api = Buffer<BoxService<ApiRequest, ApiResponse, crate::Error>, ApiRequest>;
+
+type ApiFuture<T> = Box<dyn Future<Item = T, Error = crate::Error> + Send>;
+
+#[derive(Clone)]
+pub struct Client {
+    api: Api,
     stream_name: String,
     group_name: String,
 }
 
 enum State {
-    CreateGroup(RusotoFuture<(), CreateLogGroupError>),
-    CreateStream(RusotoFuture<(), CreateLogStreamError>),
-    DescribeStream(RusotoFuture<DescribeLogStreamsResponse, DescribeLogStreamsError>),
-    Put(RusotoFuture<PutLogEventsResponse, PutLogEventsError>),
+    CheckGroup(GroupCheck),
+    CreateGroup(ApiFuture<()>),
+    CreateStream(ApiFuture<()>),
+    PutRetentionPolicy(ApiFuture<()>),
+    DescribeStream(ApiFuture<DescribeLogStreamsResponse>),
+    Put(ApiFuture<PutLogEventsResponse>),
+    /// There are no events to put, so the token is passed on as it is.
+    Skip(Option<String>),
+    /// CloudWatch would reject the request, so it isn't made.
+    Invalid(&'static str),
 }
 
 impl CloudwatchFuture {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        client: CloudWatchLogsClient,
-        stream_name: String,
-        group_name: String,
+        client: Client,
         create_missing_group: bool,
         create_missing_stream: bool,
+        retention: Option<Retention>,
+        group_checks: Option<GroupChecks>,
         events: Vec<InputLogEvent>,
         token: Option<String>,
         token_tx: oneshot::Sender<Option<String>>,
     ) -> Self {
-        let client = Client {
-            client,
-            stream_name,
-            group_name,
-        };
-
-        let (state, events) = if let Some(token) = token {
-            let state = State::Put(client.put_logs(Some(token), events));
-            (state, None)
+        let state = if let Err(message) = client.validate() {
+            State::Invalid(message)
+        } else if events.is_empty() {
+            debug!(message = "no events to put; skipping request.");
+            State::Skip(token)
+        } else if let Some(token) = token {
+            State::Put(client.put_logs(Some(token), events.clone()))
+        } else if let Some(group_checks) = &group_checks {
+            State::CheckGroup(group_checks.check(&client, create_missing_group, retention))
         } else {
-            let state = State::DescribeStream(client.describe_stream());
-            (state, Some(events))
+            client.first_state(retention)
         };
 
         Self {
@@ -62,97 +167,249 @@ impl CloudwatchFuture {
             token_tx: Some(token_tx),
             create_missing_group,
             create_missing_stream,
+            retention,
+            group_checks,
+            group_created: false,
+            describe_pages: 0,
+            accepted: false,
+            token_refreshes: 0,
         }
     }
 }
 
 impl Future for CloudwatchFuture {
     type Item = ();
-    type Error = CloudwatchError;
+    type Error = crate::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.poll_state().map_err(|error| {
+            emit!(CloudwatchLogsRequestFailed {
+                error_type: error_type(&error),
+                group: &self.client.group_name,
+                stream: &self.client.stream_name,
+            });
+            error
+        })
+    }
+}
+
+impl CloudwatchFuture {
+    fn poll_state(&mut self) -> Poll<(), crate::Error> {
         loop {
             match &mut self.state {
+                State::CheckGroup(fut) => {
+                    let status = match fut.poll() {
+                        Ok(Async::Ready(status)) => *status,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(_) => GroupStatus::Unknown,
+                    };
+                    debug!(message = "log group checked.", ?status, name = %self.client.group_name);
+
+                    self.state = match status {
+                        GroupStatus::Exists => {
+                            State::DescribeStream(self.client.describe_stream(None))
+                        }
+                        GroupStatus::Created => {
+                            self.group_created = true;
+                            State::DescribeStream(self.client.describe_stream(None))
+                        }
+                        GroupStatus::Unknown => {
+                            self.invalidate_group_check();
+                            self.client.first_state(self.retention)
+                        }
+                    };
+                }
+
                 State::DescribeStream(fut) => {
                     let response = match fut.poll() {
                         Ok(Async::Ready(res)) => res,
                         Ok(Async::NotReady) => return Ok(Async::NotReady),
-                        Err(e) => {
-                            if let RusotoError::Service(
-                                DescribeLogStreamsError::ResourceNotFound(_),
-                            ) = e
-                            {
-                                if self.create_missing_group {
-                                    info!("log group provided does not exist; creating a new one.");
-
-                                    self.state = State::CreateGroup(self.client.create_log_group());
-                                    continue;
-                                } else {
-                                    return Err(CloudwatchError::Describe(e));
-                                }
+                        Err(error) => {
+                            let group_missing = matches!(
+                                error.downcast_ref::<CloudwatchError>(),
+                                Some(
+                                    CloudwatchError::Describe(
+                                        RusotoError::Service(
+                                            DescribeLogStreamsError::ResourceNotFound(_),
+                                        ),
+                                    ),
+                                )
+                            );
+                            if !group_missing {
+                                return Err(error);
+                            }
+
+                            // The group was deleted since it was checked.
+                            self.invalidate_group_check();
+                            if self.create_missing_group {
+                                info!("log group provided does not exist; creating a new one.");
+
+                                self.state = State::CreateGroup(self.client.create_log_group());
+                                continue;
                             } else {
-                                return Err(CloudwatchError::Describe(e));
+                                return Err(error);
                             }
                         }
                     };
 
-                    if let Some(stream) = response
+                    self.describe_pages += 1;
+                    let stream_name = &self.client.stream_name;
+                    let stream = response
                         .log_streams
                         .ok_or(CloudwatchError::NoStreamsFound)?
                         .into_iter()
-                        .next()
-                    {
-                        debug!(message = "stream found", stream = ?stream.log_stream_name);
+                        .find(|stream| stream.log_stream_name.as_ref() == Some(stream_name));
 
-                        let events = self
-                            .events
-                            .take()
-                            .expect("Token got called twice, this is a bug!");
+                    if let Some(stream) = stream {
+                        debug!(message = "stream found", stream = ?stream.log_stream_name);
 
                         let token = stream.upload_sequence_token;
+                        if self.accepted {
+                            self.send_token(token);
+                            return Ok(().into());
+                        }
 
                         info!(message = "putting logs.", ?token);
-                        self.state = State::Put(self.client.put_logs(token, events));
-                    } else if self.create_missing_stream {
+                        self.state = State::Put(self.client.put_logs(token, self.events.clone()));
+                    } else if let Some(next_token) = response.next_token {
+                        // Other streams share the name as prefix, the stream
+                        // may be on a later page.
+                        if self.describe_pages >= MAX_DESCRIBE_PAGES {
+                            return Err(CloudwatchError::TooManyStreams.into());
+                        }
+                        debug!(message = "stream not found yet; describing next page.");
+                        self.state =
+                            State::DescribeStream(self.client.describe_stream(Some(next_token)));
+                    } else if self.create_missing_stream || self.group_created {
                         info!("provided stream does not exist; creating a new one.");
                         self.state = State::CreateStream(self.client.create_log_stream());
                     } else {
-                        return Err(CloudwatchError::NoStreamsFound);
+                        return Err(CloudwatchError::NoStreamsFound.into());
                     }
                 }
 
                 State::CreateGroup(fut) => {
-                    try_ready!(fut.poll().map_err(CloudwatchError::CreateGroup));
+                    try_ready!(fut.poll());
 
                     info!(message = "group created.", name = %self.client.group_name);
+                    self.group_created = true;
+
+                    self.state = match self.retention {
+                        Some(Retention { days, .. }) => {
+                            State::PutRetentionPolicy(self.client.put_retention_policy(days))
+                        }
+                        // This does not abide by `create_missing_stream` since a group
+                        // never has any streams and thus we need to create one if a group
+                        // is created no matter what.
+                        None => State::CreateStream(self.client.create_log_stream()),
+                    };
+                }
+
+                State::PutRetentionPolicy(fut) => {
+                    match fut.poll() {
+                        Ok(Async::Ready(())) => {
+                            info!(
+                                message = "retention policy set.",
+                                name = %self.client.group_name,
+                                days = ?self.retention.map(|retention| retention.days),
+                            );
+                        }
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        // A forced retention on a group that doesn't exist yet
+                        // is applied once the group gets created.
+                        Err(error)
+                            if !self.group_created
+                                && matches!(
+                                    error.downcast_ref::<CloudwatchError>(),
+                                    Some(
+                                        CloudwatchError::PutRetentionPolicy(
+                                            RusotoError::Service(
+                                                PutRetentionPolicyError::ResourceNotFound(_),
+                                            ),
+                                        ),
+                                    )
+                                ) =>
+                        {
+                            debug!(
+                                message = "log group not found for retention policy.",
+                                name = %self.client.group_name,
+                            );
+                        }
+                        Err(error) => return Err(error),
+                    }
 
-                    // This does not abide by `create_missing_stream` since a group
-                    // never has any streams and thus we need to create one if a group
-                    // is created no matter what.
-                    self.state = State::CreateStream(self.client.create_log_stream());
+                    self.state = if self.group_created {
+                        State::CreateStream(self.client.create_log_stream())
+                    } else {
+                        State::DescribeStream(self.client.describe_stream(None))
+                    };
                 }
 
                 State::CreateStream(fut) => {
-                    try_ready!(fut.poll().map_err(CloudwatchError::CreateStream));
+                    try_ready!(fut.poll());
 
                     info!(message = "stream created.", name = %self.client.stream_name);
 
-                    self.state = State::DescribeStream(self.client.describe_stream());
+                    self.describe_pages = 0;
+                    self.state = State::DescribeStream(self.client.describe_stream(None));
                 }
 
                 State::Put(fut) => {
-                    let res = try_ready!(fut.poll().map_err(CloudwatchError::Put));
+                    let res = match fut.poll() {
+                        Ok(Async::Ready(res)) => res,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(error) => match error.downcast_ref::<CloudwatchError>() {
+                            // The token is stale: it was seeded, another
+                            // writer put to the stream, or a retried attempt
+                            // went through after all.
+                            Some(CloudwatchError::Put(RusotoError::Service(
+                                PutLogEventsError::InvalidSequenceToken(message),
+                            ))) if self.token_refreshes < MAX_TOKEN_REFRESHES => {
+                                warn!(
+                                    message = "sequence token was rejected; describing the stream.",
+                                    %message,
+                                );
+                                self.token_refreshes += 1;
+                                self.describe_pages = 0;
+                                self.state =
+                                    State::DescribeStream(self.client.describe_stream(None));
+                                continue;
+                            }
+                            // An earlier attempt at this put went through,
+                            // e.g. one that timed out, so putting the events
+                            // again would duplicate them.
+                            Some(CloudwatchError::Put(RusotoError::Service(
+                                PutLogEventsError::DataAlreadyAccepted(message),
+                            ))) => {
+                                debug!(
+                                    message = "events were already accepted; describing the stream.",
+                                    %message,
+                                );
+                                self.accepted = true;
+                                self.describe_pages = 0;
+                                self.state =
+                                    State::DescribeStream(self.client.describe_stream(None));
+                                continue;
+                            }
+                            _ => return Err(error),
+                        },
+                    };
 
                     let next_token = res.next_sequence_token;
 
                     info!(message = "putting logs was successful.", ?next_token);
 
-                    self.token_tx
-                        .take()
-                        .expect("Put was polled twice.")
-                        .send(next_token)
-                        .expect("CloudwatchLogsSvc was dropped unexpectedly");
+                    self.send_token(next_token);
+                    return Ok(().into());
+                }
+
+                State::Invalid(message) => {
+                    return Err(CloudwatchError::InvalidConfig(*message).into())
+                }
 
+                State::Skip(token) => {
+                    let token = token.take();
+                    self.send_token(token);
                     return Ok(().into());
                 }
             }
@@ -160,50 +417,195 @@ impl Future for CloudwatchFuture {
     }
 }
 
+impl CloudwatchFuture {
+    fn invalidate_group_check(&self) {
+        if let Some(group_checks) = &self.group_checks {
+            group_checks.invalidate(&self.client.group_name);
+        }
+    }
+
+    fn send_token(&mut self, token: Option<String>) {
+        let token_tx = self.token_tx.take().expect("Token was sent twice.");
+        // The service is gone if the sink is shutting down, and then so is
+        // the need for the token.
+        if token_tx.send(token).is_err() {
+            debug!(message = "sink was dropped before the token could be passed on.");
+        }
+    }
+}
+
+/// What failed, for the errors counted by `CloudwatchLogsRequestFailed`.
+fn error_type(error: &crate::Error) -> &'static str {
+    if let Some(error) = error.downcast_ref::<CloudwatchError>() {
+        error.error_type()
+    } else if error.is::<Elapsed>() {
+        "timed_out"
+    } else {
+        "unknown"
+    }
+}
+
 impl Client {
+    /// Sends the calls of `service`'s stream through the retry, rate limit,
+    /// concurrency and timeout layers of `settings`. These run as a task of
+    /// their own, so the client has to be made on the runtime.
+    ///
+    /// A retried put reuses its sequence token, so it may find its events
+    /// already accepted or the token used up, which `CloudwatchFuture`
+    /// recovers from.
+    pub fn new(service: ApiService, settings: &TowerRequestSettings) -> Self {
+        let stream_name = service.stream_name().to_owned();
+        let group_name = service.group_name().to_owned();
+        let api = ServiceBuilder::new()
+            .settings(settings.clone(), CloudwatchRetryLogic)
+            .service(service);
+
+        Self {
+            api: Buffer::new(api, 1),
+            stream_name,
+            group_name,
+        }
+    }
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.group_name.is_empty() {
+            Err("log group name is empty")
+        } else if self.stream_name.is_empty() {
+            Err("log stream name is empty")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The state a request without a token starts in, when the group isn't
+    /// checked for all of its streams.
+    fn first_state(&self, retention: Option<Retention>) -> State {
+        match retention {
+            // Without a token this is the first request for the stream, so a
+            // forced retention is applied once up front.
+            Some(Retention { days, force: true }) => {
+                State::PutRetentionPolicy(self.put_retention_policy(days))
+            }
+            _ => State::DescribeStream(self.describe_stream(None)),
+        }
+    }
+
+    /// Whether the group exists, once it's been created if it's missing and
+    /// `create_missing_group` is set.
+    fn check_group(
+        &self,
+        create_missing_group: bool,
+        retention: Option<Retention>,
+    ) -> impl Future<Item = GroupStatus, Error = crate::Error> {
+        let client = self.clone();
+        self.describe_group().and_then(move |response| {
+            // Groups are listed by name, so the group comes first among
+            // those sharing its name as prefix.
+            let exists = response
+                .log_groups
+                .unwrap_or_default()
+                .first()
+                .and_then(|group| group.log_group_name.as_ref())
+                == Some(&client.group_name);
+
+            let status: ApiFuture<GroupStatus> = if exists {
+                Box::new(future::ok(GroupStatus::Exists))
+            } else if create_missing_group {
+                info!("log group provided does not exist; creating a new one.");
+                Box::new(
+                    client
+                        .create_log_group()
+                        .map(|()| GroupStatus::Created)
+                        .or_else(|error| match error.downcast_ref::<CloudwatchError>() {
+                            Some(CloudwatchError::CreateGroup(RusotoError::Service(
+                                CreateLogGroupError::ResourceAlreadyExists(_),
+                            ))) => Ok(GroupStatus::Exists),
+                            _ => Err(error),
+                        }),
+                )
+            } else {
+                Box::new(future::ok(GroupStatus::Unknown))
+            };
+
+            status.and_then(move |status| {
+                let days = match (status, retention) {
+                    (GroupStatus::Created, Some(Retention { days, .. })) => days,
+                    (GroupStatus::Exists, Some(Retention { days, force: true })) => days,
+                    _ => return future::Either::A(future::ok(status)),
+                };
+                future::Either::B(client.put_retention_policy(days).map(move |()| status))
+            })
+        })
+    }
+
     pub fn put_logs(
         &self,
         sequence_token: Option<String>,
-        log_events: Vec<InputLogEvent>,
-    ) -> RusotoFuture<PutLogEventsResponse, PutLogEventsError> {
-        let request = PutLogEventsRequest {
-            log_events,
+        events: Vec<InputLogEvent>,
+    ) -> ApiFuture<PutLogEventsResponse> {
+        let request = ApiRequest::PutEvents {
             sequence_token,
-            log_group_name: self.group_name.clone(),
-            log_stream_name: self.stream_name.clone(),
+            events,
         };
-
-        self.client.put_log_events(request)
+        self.call(request, |response| match response {
+            ApiResponse::PutEvents(response) => Some(response),
+            _ => None,
+        })
     }
 
     pub fn describe_stream(
         &self,
-    ) -> RusotoFuture<DescribeLogStreamsResponse, DescribeLogStreamsError> {
-        let request = DescribeLogStreamsRequest {
-            limit: Some(1),
-            log_group_name: self.group_name.clone(),
-            log_stream_name_prefix: Some(self.stream_name.clone()),
-            ..Default::default()
+        next_token: Option<String>,
+    ) -> ApiFuture<DescribeLogStreamsResponse> {
+        let request = ApiRequest::DescribeStreams {
+            prefixed: true,
+            next_token,
         };
+        self.call(request, |response| match response {
+            ApiResponse::DescribeStreams(response) => Some(response),
+            _ => None,
+        })
+    }
 
-        self.client.describe_log_streams(request)
+    pub fn describe_group(&self) -> ApiFuture<DescribeLogGroupsResponse> {
+        self.call(ApiRequest::DescribeGroups, |response| match response {
+            ApiResponse::DescribeGroups(response) => Some(response),
+            _ => None,
+        })
     }
 
-    pub fn create_log_group(&self) -> RusotoFuture<(), CreateLogGroupError> {
-        let request = CreateLogGroupRequest {
-            log_group_name: self.group_name.clone(),
-            ..Default::default()
-        };
+    pub fn create_log_group(&self) -> ApiFuture<()> {
+        self.call(ApiRequest::CreateGroup, done)
+    }
 
-        self.client.create_log_group(request)
+    pub fn create_log_stream(&self) -> ApiFuture<()> {
+        self.call(ApiRequest::CreateStream, done)
     }
 
-    pub fn create_log_stream(&self) -> RusotoFuture<(), CreateLogStreamError> {
-        let request = CreateLogStreamRequest {
-            log_group_name: self.group_name.clone(),
-            log_stream_name: self.stream_name.clone(),
-        };
+    pub fn put_retention_policy(&self, days: i64) -> ApiFuture<()> {
+        self.call(ApiRequest::PutRetentionPolicy { days }, done)
+    }
+
+    fn call<T: Send + 'static>(
+        &self,
+        request: ApiRequest,
+        answer: fn(ApiResponse) -> Option<T>,
+    ) -> ApiFuture<T> {
+        let call = self
+            .api
+            .clone()
+            .ready()
+            .and_then(move |mut api| api.call(request))
+            .map(move |response| {
+                answer(response).expect("Response doesn't answer the request, this is a bug!")
+            });
+        Box::new(call)
+    }
+}
 
-        self.client.create_log_stream(request)
+fn done(response: ApiResponse) -> Option<()> {
+    match response {
+        ApiResponse::Done => Some(()),
+        _ => None,
     }
 }
@@ -5,7 +5,7 @@ use crate::{
     sinks::util::{
         retries::RetryLogic,
         rusoto::{self, AwsCredentialsProvider},
-        BatchEventsConfig, MetricBuffer, TowerRequestConfig,
+        BatchConfig, BatchSettings, MetricBuffer, ProxyConfig, TowerRequestConfig,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -34,10 +34,12 @@ pub struct CloudWatchMetricsSinkConfig {
     #[serde(flatten)]
     pub region: RegionOrEndpoint,
     #[serde(default)]
-    pub batch: BatchEventsConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub assume_role: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 lazy_static! {
@@ -67,6 +69,10 @@ impl SinkConfig for CloudWatchMetricsSinkConfig {
     fn sink_type(&self) -> &'static str {
         "aws_cloudwatch_metrics"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl CloudWatchMetricsSvc {
@@ -78,9 +84,12 @@ impl CloudWatchMetricsSvc {
             config.region.clone().try_into()?,
             config.assume_role.clone(),
             cx.resolver(),
+            &config.proxy,
         )?;
 
-        let batch = config.batch.unwrap_or(20, 1);
+        let batch = config
+            .batch
+            .unwrap_events_only(BatchSettings::default().events(20).timeout(1))?;
         let request = config.request.unwrap_with(&REQUEST_DEFAULTS);
 
         let cloudwatch_metrics = CloudWatchMetricsSvc { client, config };
@@ -91,7 +100,7 @@ impl CloudWatchMetricsSvc {
                 cloudwatch_metrics,
                 MetricBuffer::new(),
                 batch,
-                cx.acker(),
+                &cx,
             )
             .sink_map_err(|e| error!("CloudwatchMetrics sink error: {}", e));
 
@@ -106,6 +115,7 @@ impl CloudWatchMetricsSvc {
             config.region.clone().try_into()?,
             config.assume_role.clone(),
             resolver,
+            &config.proxy,
         )?;
 
         let datum = MetricDatum {
@@ -128,6 +138,7 @@ impl CloudWatchMetricsSvc {
         region: Region,
         assume_role: Option<String>,
         resolver: Resolver,
+        proxy: &ProxyConfig,
     ) -> crate::Result<CloudWatchClient> {
         let region = if cfg!(test) {
             // Moto (used for mocking AWS) doesn't recognize 'custom' as valid region name
@@ -141,7 +152,7 @@ impl CloudWatchMetricsSvc {
         } else {
             region
         };
-        let d = rusoto::client(resolver)?;
+        let d = rusoto::client(resolver, proxy)?;
         let p = AwsCredentialsProvider::new(&region, assume_role)?;
 
         Ok(CloudWatchClient::new_with(d, p, region))
@@ -287,7 +298,8 @@ mod tests {
         let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
         let config = config();
         let region = config.region.clone().try_into().unwrap();
-        let client = CloudWatchMetricsSvc::create_client(region, None, resolver).unwrap();
+        let client =
+            CloudWatchMetricsSvc::create_client(region, None, resolver, &config.proxy).unwrap();
 
         CloudWatchMetricsSvc { client, config }
     }
@@ -6,7 +6,7 @@ use crate::{
         encoding::{EncodingConfig, EncodingConfiguration},
         retries::RetryLogic,
         rusoto::{self, AwsCredentialsProvider},
-        BatchEventsConfig, TowerRequestConfig,
+        BatchConfig, BatchSettings, ProxyConfig, TowerRequestConfig, VecBuffer,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -38,10 +38,12 @@ pub struct KinesisFirehoseSinkConfig {
     pub region: RegionOrEndpoint,
     pub encoding: EncodingConfig<Encoding>,
     #[serde(default)]
-    pub batch: BatchEventsConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub assume_role: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 lazy_static! {
@@ -78,6 +80,10 @@ impl SinkConfig for KinesisFirehoseSinkConfig {
     fn sink_type(&self) -> &'static str {
         "aws_kinesis_firehose"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl KinesisFirehoseService {
@@ -89,9 +95,15 @@ impl KinesisFirehoseService {
             config.region.clone().try_into()?,
             config.assume_role.clone(),
             cx.resolver(),
+            &config.proxy,
         )?;
 
-        let batch = config.batch.unwrap_or(500, 1);
+        let batch = config.batch.unwrap_or(
+            BatchSettings::default()
+                .events(500)
+                .bytes(bytesize::mib(4u64))
+                .timeout(1),
+        );
         let request = config.request.unwrap_with(&REQUEST_DEFAULTS);
         let encoding = config.encoding.clone();
 
@@ -101,9 +113,9 @@ impl KinesisFirehoseService {
             .batch_sink(
                 KinesisFirehoseRetryLogic,
                 kinesis,
-                Vec::new(),
+                VecBuffer::new(|record: &Record| record.data.len()),
                 batch,
-                cx.acker(),
+                &cx,
             )
             .sink_map_err(|e| error!("Fatal kinesis firehose sink error: {}", e))
             .with_flat_map(move |e| iter_ok(encode_event(e, &encoding)));
@@ -177,7 +189,12 @@ fn healthcheck(
     config: KinesisFirehoseSinkConfig,
     resolver: Resolver,
 ) -> crate::Result<super::Healthcheck> {
-    let client = create_client(config.region.try_into()?, config.assume_role, resolver)?;
+    let client = create_client(
+        config.region.try_into()?,
+        config.assume_role,
+        resolver,
+        &config.proxy,
+    )?;
     let stream_name = config.stream_name;
 
     let fut = client
@@ -203,8 +220,9 @@ fn create_client(
     region: Region,
     assume_role: Option<String>,
     resolver: Resolver,
+    proxy: &ProxyConfig,
 ) -> crate::Result<KinesisFirehoseClient> {
-    let client = rusoto::client(resolver)?;
+    let client = rusoto::client(resolver, proxy)?;
     let creds = AwsCredentialsProvider::new(&region, assume_role)?;
 
     Ok(KinesisFirehoseClient::new_with(client, creds, region))
@@ -264,7 +282,7 @@ mod integration_tests {
         runtime,
         sinks::{
             elasticsearch::{ElasticSearchAuth, ElasticSearchCommon, ElasticSearchConfig},
-            util::BatchEventsConfig,
+            util::BatchConfig,
         },
         test_util::{random_events_with_stream, random_string},
         topology::config::SinkContext,
@@ -293,9 +311,10 @@ mod integration_tests {
             stream_name: stream.clone(),
             region: RegionOrEndpoint::with_endpoint("http://localhost:4573".into()),
             encoding: EncodingConfig::from(Encoding::Json), // required for ES destination w/ localstack
-            batch: BatchEventsConfig {
+            batch: BatchConfig {
                 max_events: Some(2),
                 timeout_secs: None,
+                ..Default::default()
             },
             request: TowerRequestConfig {
                 timeout_secs: Some(10),
@@ -303,6 +322,7 @@ mod integration_tests {
                 ..Default::default()
             },
             assume_role: None,
+            proxy: Default::default(),
         };
 
         let mut rt = runtime::Runtime::new().unwrap();
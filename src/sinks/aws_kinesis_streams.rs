@@ -6,7 +6,7 @@ use crate::{
         encoding::{EncodingConfig, EncodingConfiguration},
         retries::RetryLogic,
         rusoto::{self, AwsCredentialsProvider},
-        BatchEventsConfig, TowerRequestConfig,
+        BatchConfig, BatchSettings, ProxyConfig, TowerRequestConfig, VecBuffer,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -41,10 +41,12 @@ pub struct KinesisSinkConfig {
     pub region: RegionOrEndpoint,
     pub encoding: EncodingConfig<Encoding>,
     #[serde(default)]
-    pub batch: BatchEventsConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub assume_role: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 lazy_static! {
@@ -81,6 +83,10 @@ impl SinkConfig for KinesisSinkConfig {
     fn sink_type(&self) -> &'static str {
         "aws_kinesis_streams"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl KinesisService {
@@ -92,9 +98,15 @@ impl KinesisService {
             config.region.clone().try_into()?,
             config.assume_role.clone(),
             cx.resolver(),
+            &config.proxy,
         )?);
 
-        let batch = config.batch.unwrap_or(500, 1);
+        let batch = config.batch.unwrap_or(
+            BatchSettings::default()
+                .events(500)
+                .bytes(bytesize::mib(5u64))
+                .timeout(1),
+        );
         let request = config.request.unwrap_with(&REQUEST_DEFAULTS);
         let encoding = config.encoding.clone();
         let partition_key_field = config.partition_key_field.clone();
@@ -102,7 +114,15 @@ impl KinesisService {
         let kinesis = KinesisService { client, config };
 
         let sink = request
-            .batch_sink(KinesisRetryLogic, kinesis, Vec::new(), batch, cx.acker())
+            .batch_sink(
+                KinesisRetryLogic,
+                kinesis,
+                VecBuffer::new(|record: &PutRecordsRequestEntry| {
+                    record.data.len() + record.partition_key.len()
+                }),
+                batch,
+                &cx,
+            )
             .sink_map_err(|e| error!("Fatal kinesis streams sink error: {}", e))
             .with_flat_map(move |e| iter_ok(encode_event(e, &partition_key_field, &encoding)));
 
@@ -181,6 +201,7 @@ fn healthcheck(config: KinesisSinkConfig, resolver: Resolver) -> crate::Result<s
         config.region.try_into()?,
         config.assume_role.clone(),
         resolver,
+        &config.proxy,
     )?;
     let stream_name = config.stream_name;
 
@@ -207,8 +228,9 @@ fn create_client(
     region: Region,
     assume_role: Option<String>,
     resolver: Resolver,
+    proxy: &ProxyConfig,
 ) -> crate::Result<KinesisClient> {
-    let client = rusoto::client(resolver)?;
+    let client = rusoto::client(resolver, proxy)?;
     let creds = AwsCredentialsProvider::new(&region, assume_role)?;
     Ok(KinesisClient::new_with(client, creds, region))
 }
@@ -349,12 +371,14 @@ mod integration_tests {
             partition_key_field: None,
             region: RegionOrEndpoint::with_endpoint("http://localhost:4568".into()),
             encoding: Encoding::Text.into(),
-            batch: BatchEventsConfig {
+            batch: BatchConfig {
                 max_events: Some(2),
                 timeout_secs: None,
+                ..Default::default()
             },
             request: Default::default(),
             assume_role: None,
+            proxy: Default::default(),
         };
 
         let mut rt = runtime::Runtime::new().unwrap();
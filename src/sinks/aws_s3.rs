@@ -4,16 +4,18 @@ use crate::{
     region::RegionOrEndpoint,
     serde::to_string,
     sinks::util::{
+        self,
+        compression::Compression,
         encoding::{EncodingConfigWithDefault, EncodingConfiguration},
+        partition_limit::PartitionLimitConfig,
         retries::RetryLogic,
-        rusoto, BatchBytesConfig, Buffer, PartitionBatchSink, PartitionBuffer,
-        PartitionInnerBuffer, ServiceBuilderExt, TowerRequestConfig,
+        rusoto, BatchConfig, BatchSettings, Buffer, PartitionBatchSink, PartitionBuffer,
+        PartitionInnerBuffer, ProxyConfig, ServiceBuilderExt, TowerRequestConfig,
     },
     template::Template,
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
 use bytes::Bytes;
-use chrono::Utc;
 use futures01::{stream::iter_ok, Future, Poll, Sink};
 use lazy_static::lazy_static;
 use rusoto_core::{Region, RusotoError, RusotoFuture};
@@ -27,7 +29,6 @@ use std::convert::TryInto;
 use tower::{Service, ServiceBuilder};
 use tracing::field;
 use tracing_futures::{Instrument, Instrumented};
-use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct S3Sink {
@@ -53,14 +54,21 @@ pub struct S3SinkConfig {
     pub encoding: EncodingConfigWithDefault<Encoding>,
     pub compression: Compression,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
+    /// Send the batches of each key prefix in order, one at a time.
+    #[serde(default)]
+    pub preserve_order: bool,
+    /// Caps the number of key prefixes batched at once.
+    pub partition_limit: Option<PartitionLimitConfig>,
     pub assume_role: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
-struct S3Options {
+pub(crate) struct S3Options {
     acl: Option<S3CannedAcl>,
     grant_full_control: Option<String>,
     grant_read: Option<String>,
@@ -124,15 +132,6 @@ pub enum Encoding {
     Ndjson,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Derivative)]
-#[serde(rename_all = "snake_case")]
-#[derivative(Default)]
-pub enum Compression {
-    #[derivative(Default)]
-    Gzip,
-    None,
-}
-
 inventory::submit! {
     SinkDescription::new::<S3SinkConfig>("aws_s3")
 }
@@ -153,6 +152,16 @@ impl SinkConfig for S3SinkConfig {
     fn sink_type(&self) -> &'static str {
         "aws_s3"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("zstd compression isn't supported by the aws_s3 sink"))]
+    ZstdUnsupported,
 }
 
 #[derive(Debug, Snafu)]
@@ -170,13 +179,18 @@ impl S3Sink {
         let request = config.request.unwrap_with(&REQUEST_DEFAULTS);
         let encoding = config.encoding.clone();
 
-        let compression = match config.compression {
-            Compression::Gzip => true,
-            Compression::None => false,
+        let gzip_level = match config.compression {
+            Compression::Zstd(_) => return Err(BuildError::ZstdUnsupported.into()),
+            compression => compression.gzip_level(),
         };
+        let compression = gzip_level.is_some();
         let filename_time_format = config.filename_time_format.clone().unwrap_or("%s".into());
         let filename_append_uuid = config.filename_append_uuid.unwrap_or(true);
-        let batch = config.batch.unwrap_or(bytesize::mib(10u64), 300);
+        let batch = config.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(300),
+        );
 
         let key_prefix = if let Some(kp) = &config.key_prefix {
             Template::from(kp.as_str())
@@ -184,10 +198,23 @@ impl S3Sink {
             Template::from("date=%F/")
         };
 
+        let mut partition_limit = match &config.partition_limit {
+            Some(limit) => Some((
+                limit.build(cx.name(), batch.timeout)?,
+                Bytes::from(limit.default_partition.as_str()),
+            )),
+            None => None,
+        };
+
         let region = config.region.clone().try_into()?;
 
         let s3 = S3Sink {
-            client: Self::create_client(region, config.assume_role.clone(), cx.resolver())?,
+            client: Self::create_client(
+                region,
+                config.assume_role.clone(),
+                cx.resolver(),
+                &config.proxy,
+            )?,
         };
 
         let filename_extension = config.filename_extension.clone();
@@ -207,12 +234,25 @@ impl S3Sink {
                 )
             })
             .settings(request, S3RetryLogic)
+            .request_metrics(&cx)
             .service(s3);
 
-        let buffer = PartitionBuffer::new(Buffer::new(compression));
+        let buffer = PartitionBuffer::new(Buffer::with_gzip_level(gzip_level));
 
         let sink = PartitionBatchSink::new(svc, buffer, batch, cx.acker())
-            .with_flat_map(move |e| iter_ok(encode_event(e, &key_prefix, &encoding)))
+            .component(cx.name())
+            .preserve_order(config.preserve_order)
+            .with_flat_map(move |e| {
+                let item = encode_event(e, &key_prefix, &encoding);
+                iter_ok(match (item, &mut partition_limit) {
+                    (Some(item), Some((limiter, default_partition))) => {
+                        let (inner, key) = item.into_parts();
+                        let key = limiter.partition(key, |_| default_partition.clone());
+                        Some(PartitionInnerBuffer::new(inner, key))
+                    }
+                    (item, _) => item,
+                })
+            })
             .sink_map_err(|error| error!("Sink failed to flush: {}", error));
 
         Ok(Box::new(sink))
@@ -226,15 +266,20 @@ impl S3Sink {
             config.region.clone().try_into()?,
             config.assume_role.clone(),
             resolver,
+            &config.proxy,
         )?;
 
+        Ok(Self::bucket_healthcheck(client, config.bucket.clone()))
+    }
+
+    /// Checks that `bucket` exists and the client's credentials may use it.
+    pub(crate) fn bucket_healthcheck(client: S3Client, bucket: String) -> super::Healthcheck {
         let request = HeadBucketRequest {
-            bucket: config.bucket.clone(),
+            bucket: bucket.clone(),
         };
 
         let response = client.head_bucket(request);
 
-        let bucket = config.bucket.clone();
         let healthcheck = response.map_err(|err| match err {
             RusotoError::Unknown(response) => match response.status {
                 http::status::StatusCode::FORBIDDEN => HealthcheckError::InvalidCredentials.into(),
@@ -246,15 +291,22 @@ impl S3Sink {
             err => err.into(),
         });
 
-        Ok(Box::new(healthcheck))
+        Box::new(healthcheck)
+    }
+
+    /// Uploads whole objects built elsewhere, for sinks with their own
+    /// object formats.
+    pub(crate) fn with_client(client: S3Client) -> Self {
+        Self { client }
     }
 
     pub fn create_client(
         region: Region,
         _assume_role: Option<String>,
         resolver: Resolver,
+        proxy: &ProxyConfig,
     ) -> crate::Result<S3Client> {
-        let client = rusoto::client(resolver)?;
+        let client = rusoto::client(resolver, proxy)?;
 
         #[cfg(not(test))]
         let creds = rusoto::AwsCredentialsProvider::new(&region, _assume_role)?;
@@ -320,23 +372,8 @@ fn build_request(
 ) -> Request {
     let (inner, key) = req.into_parts();
 
-    // TODO: pull the seconds from the last event
-    let filename = {
-        let seconds = Utc::now().format(&time_format);
-
-        if uuid {
-            let uuid = Uuid::new_v4();
-            format!("{}-{}", seconds, uuid.to_hyphenated())
-        } else {
-            seconds.to_string()
-        }
-    };
-
     let extension = extension.unwrap_or_else(|| if gzip { "log.gz".into() } else { "log".into() });
-
-    let key = String::from_utf8_lossy(&key[..]).into_owned();
-
-    let key = format!("{}{}.{}", key, filename, extension);
+    let key = util::object_name(&key[..], &time_format, uuid, &extension);
 
     debug!(
         message = "sending events.",
@@ -355,16 +392,16 @@ fn build_request(
 }
 
 #[derive(Debug, Clone)]
-struct Request {
-    body: Vec<u8>,
-    bucket: String,
-    key: String,
-    content_encoding: Option<String>,
-    options: S3Options,
+pub(crate) struct Request {
+    pub(crate) body: Vec<u8>,
+    pub(crate) bucket: String,
+    pub(crate) key: String,
+    pub(crate) content_encoding: Option<String>,
+    pub(crate) options: S3Options,
 }
 
 #[derive(Debug, Clone)]
-struct S3RetryLogic;
+pub(crate) struct S3RetryLogic;
 
 impl RetryLogic for S3RetryLogic {
     type Error = RusotoError<PutObjectError>;
@@ -504,6 +541,30 @@ mod tests {
         );
         assert_ne!(req.key, "key/date.log.gz".to_string());
     }
+
+    #[test]
+    fn s3_compression_levels() {
+        let config = |compression| {
+            toml::from_str::<S3SinkConfig>(&format!(
+                r#"
+                bucket = "bucket"
+                region = "us-east-1"
+                compression = "{}"
+                "#,
+                compression
+            ))
+        };
+
+        assert_eq!(
+            config("gzip(9)").unwrap().compression,
+            Compression::Gzip(Some(9))
+        );
+        assert!(config("gzip(10)").is_err());
+
+        let rt = crate::test_util::runtime();
+        let cx = SinkContext::new_test(rt.executor());
+        assert!(S3Sink::new(&config("zstd(3)").unwrap(), cx).is_err());
+    }
 }
 
 #[cfg(feature = "s3-integration-tests")]
@@ -675,7 +736,7 @@ mod integration_tests {
         ensure_bucket(&client());
 
         let config = S3SinkConfig {
-            compression: Compression::Gzip,
+            compression: Compression::gzip_default(),
             filename_time_format: Some("%S%f".into()),
             ..config(1000)
         };
@@ -755,9 +816,10 @@ mod integration_tests {
             key_prefix: Some(random_string(10) + "/date=%F/"),
             bucket: BUCKET.to_string(),
             compression: Compression::None,
-            batch: BatchBytesConfig {
-                max_size: Some(batch_size),
+            batch: BatchConfig {
+                max_bytes: Some(batch_size),
                 timeout_secs: Some(5),
+                ..Default::default()
             },
             region: RegionOrEndpoint::with_endpoint("http://localhost:9000".to_owned()),
             ..Default::default()
@@ -0,0 +1,485 @@
+//! Authorizes Blob service requests, with the account's key, a shared access
+//! signature (SAS) token, or a token of the managed identity of the VM Vector
+//! runs on.
+
+use crate::sinks::util::http::HttpClient;
+use chrono::Utc;
+use futures::compat::Future01CompatExt;
+use futures01::Stream;
+use http::{HeaderValue, Request, StatusCode, Uri};
+use hyper::Body;
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/// The Blob service version requests are made with.
+pub const API_VERSION: &str = "2019-12-12";
+
+/// Azurite's well-known account, which `UseDevelopmentStorage=true` stands for.
+const DEVELOPMENT_ACCOUNT: &str = "devstoreaccount1";
+const DEVELOPMENT_KEY: &str =
+    "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+const DEVELOPMENT_ENDPOINT: &str = "http://127.0.0.1:10000/devstoreaccount1";
+
+const IMDS_TOKEN_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+const STORAGE_RESOURCE: &str = "https://storage.azure.com/";
+/// Tokens are renewed this long before they expire, so none expires in flight.
+const TOKEN_RENEWAL_MARGIN: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Snafu)]
+pub enum AuthError {
+    #[snafu(display(
+        "Exactly one of `connection_string`, `sas_token` or `managed_identity` must be set"
+    ))]
+    NoCredentials,
+    #[snafu(display("`{}` can't be set along with `connection_string`", option))]
+    ConflictsWithConnectionString { option: &'static str },
+    #[snafu(display("`storage_account` must be set along with `{}`", option))]
+    MissingStorageAccount { option: &'static str },
+    #[snafu(display("The connection string has no {:?}", key))]
+    MissingConnectionStringKey { key: &'static str },
+    #[snafu(display("The connection string part {:?} isn't a `key=value` pair", part))]
+    InvalidConnectionStringPart { part: String },
+    #[snafu(display("The account key isn't valid base64: {}", source))]
+    InvalidAccountKey { source: base64::DecodeError },
+    #[snafu(display("The managed identity token request returned {}: {}", status, body))]
+    TokenRequestFailed { status: StatusCode, body: String },
+    #[snafu(display("The managed identity token response is invalid: {}", source))]
+    InvalidTokenResponse { source: serde_json::Error },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AzureAuthConfig {
+    /// Holds the account, its endpoint and its key or a SAS token.
+    pub connection_string: Option<String>,
+    pub storage_account: Option<String>,
+    /// The Blob service endpoint, when it isn't the account's default one.
+    pub endpoint: Option<String>,
+    pub sas_token: Option<String>,
+    #[serde(default)]
+    pub managed_identity: bool,
+    /// The client ID of a user-assigned managed identity.
+    pub managed_identity_client_id: Option<String>,
+}
+
+impl AzureAuthConfig {
+    pub fn build(&self) -> Result<Account, AuthError> {
+        if let Some(connection_string) = &self.connection_string {
+            let conflicting = [
+                ("storage_account", self.storage_account.is_some()),
+                ("endpoint", self.endpoint.is_some()),
+                ("sas_token", self.sas_token.is_some()),
+                ("managed_identity", self.managed_identity),
+            ];
+            if let Some((option, _)) = conflicting.iter().find(|(_, set)| *set) {
+                return Err(AuthError::ConflictsWithConnectionString { option: *option });
+            }
+            return Account::from_connection_string(connection_string);
+        }
+
+        let credentials = match (&self.sas_token, self.managed_identity) {
+            (Some(token), false) => Credentials::Sas(token.trim_start_matches('?').to_owned()),
+            (None, true) => Credentials::ManagedIdentity(ManagedIdentity::new(
+                self.managed_identity_client_id.clone(),
+            )),
+            _ => return Err(AuthError::NoCredentials),
+        };
+        let account = self
+            .storage_account
+            .clone()
+            .ok_or(AuthError::MissingStorageAccount {
+                option: match credentials {
+                    Credentials::Sas(_) => "sas_token",
+                    _ => "managed_identity",
+                },
+            })?;
+        let endpoint = self
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://{}.blob.core.windows.net", account));
+
+        Ok(Account::new(account, endpoint, credentials))
+    }
+}
+
+/// A storage account, and how to authorize requests to it.
+#[derive(Clone)]
+pub struct Account {
+    name: String,
+    endpoint: String,
+    credentials: Credentials,
+}
+
+#[derive(Clone)]
+enum Credentials {
+    SharedKey(Vec<u8>),
+    Sas(String),
+    ManagedIdentity(ManagedIdentity),
+}
+
+impl Account {
+    fn new(name: String, endpoint: String, credentials: Credentials) -> Self {
+        Self {
+            name,
+            endpoint: endpoint.trim_end_matches('/').to_owned(),
+            credentials,
+        }
+    }
+
+    fn from_connection_string(connection_string: &str) -> Result<Self, AuthError> {
+        let mut parts = BTreeMap::new();
+        for part in connection_string.split(';').map(str::trim) {
+            if part.is_empty() {
+                continue;
+            }
+            let mut pair = part.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(key), Some(value)) => parts.insert(key, value),
+                _ => return Err(AuthError::InvalidConnectionStringPart { part: part.into() }),
+            };
+        }
+
+        if parts.get("UseDevelopmentStorage") == Some(&"true") {
+            return Ok(Self::new(
+                DEVELOPMENT_ACCOUNT.into(),
+                DEVELOPMENT_ENDPOINT.into(),
+                Credentials::SharedKey(base64::decode(DEVELOPMENT_KEY).unwrap()),
+            ));
+        }
+
+        let name = *parts
+            .get("AccountName")
+            .ok_or(AuthError::MissingConnectionStringKey { key: "AccountName" })?;
+        let endpoint = match parts.get("BlobEndpoint") {
+            Some(endpoint) => endpoint.to_string(),
+            None => format!(
+                "{}://{}.blob.{}",
+                parts.get("DefaultEndpointsProtocol").unwrap_or(&"https"),
+                name,
+                parts.get("EndpointSuffix").unwrap_or(&"core.windows.net")
+            ),
+        };
+        let credentials = match (parts.get("AccountKey"), parts.get("SharedAccessSignature")) {
+            (Some(key), _) => {
+                Credentials::SharedKey(base64::decode(key).context(InvalidAccountKey)?)
+            }
+            (None, Some(token)) => Credentials::Sas(token.trim_start_matches('?').to_owned()),
+            (None, None) => {
+                return Err(AuthError::MissingConnectionStringKey { key: "AccountKey" })
+            }
+        };
+
+        Ok(Self::new(name.into(), endpoint, credentials))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The URL of `path` in the Blob service, like `container/blob`, with
+    /// `query` and, for SAS credentials, the token.
+    pub fn url(&self, path: &str, query: &str) -> String {
+        let mut url = format!("{}/{}", self.endpoint, path);
+        let token = match &self.credentials {
+            Credentials::Sas(token) => token.as_str(),
+            _ => "",
+        };
+        for (i, part) in [query, token]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .enumerate()
+        {
+            url.push(if i == 0 { '?' } else { '&' });
+            url.push_str(part);
+        }
+        url
+    }
+
+    /// Sets the date, the version and, unless a SAS token in the URL does, the
+    /// authorization of `request`. It must have all its other headers by then,
+    /// as they are signed with a shared key.
+    pub async fn authorize(
+        &self,
+        client: &mut HttpClient,
+        request: &mut Request<Body>,
+    ) -> crate::Result<()> {
+        let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let headers = request.headers_mut();
+        headers.insert("x-ms-date", HeaderValue::from_str(&date)?);
+        headers.insert("x-ms-version", HeaderValue::from_static(API_VERSION));
+
+        let authorization = match &self.credentials {
+            Credentials::SharedKey(key) => {
+                let signature = sign(key, &string_to_sign(&self.name, request))?;
+                format!("SharedKey {}:{}", self.name, signature)
+            }
+            Credentials::Sas(_) => return Ok(()),
+            Credentials::ManagedIdentity(identity) => {
+                format!("Bearer {}", identity.token(client).await?)
+            }
+        };
+        request
+            .headers_mut()
+            .insert("authorization", HeaderValue::from_str(&authorization)?);
+        Ok(())
+    }
+}
+
+/// What's signed with a shared key: the method, the standard headers, the
+/// `x-ms-` headers and the resource, each in the order the service expects.
+fn string_to_sign(account: &str, request: &Request<Body>) -> String {
+    let headers = request.headers();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+    };
+
+    let mut string = format!("{}\n", request.method());
+    for &name in &[
+        "content-encoding",
+        "content-language",
+        "content-length",
+        "content-md5",
+        "content-type",
+        "date",
+        "if-modified-since",
+        "if-match",
+        "if-none-match",
+        "if-unmodified-since",
+        "range",
+    ] {
+        // An empty body's length is left out since version 2015-02-21.
+        match (name, header(name)) {
+            ("content-length", "0") => {}
+            (_, value) => string.push_str(value),
+        }
+        string.push('\n');
+    }
+
+    let ms_headers = headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with("x-ms-"))
+        .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("").trim()))
+        .collect::<BTreeMap<_, _>>();
+    for (name, value) in ms_headers {
+        string.push_str(&format!("{}:{}\n", name, value));
+    }
+
+    string.push_str(&format!("/{}{}", account, request.uri().path()));
+    let query = url::form_urlencoded::parse(request.uri().query().unwrap_or("").as_bytes())
+        .map(|(name, value)| (name.to_lowercase(), value.into_owned()))
+        .collect::<BTreeMap<_, _>>();
+    for (name, value) in query {
+        string.push_str(&format!("\n{}:{}", name, value));
+    }
+
+    string
+}
+
+fn sign(key: &[u8], string: &str) -> crate::Result<String> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(string.as_bytes())?;
+    Ok(base64::encode(&signer.sign_to_vec()?))
+}
+
+/// Gets tokens from the instance metadata service, and keeps them until
+/// they're about to expire.
+#[derive(Clone)]
+struct ManagedIdentity {
+    client_id: Option<String>,
+    token: Arc<RwLock<Option<Token>>>,
+}
+
+struct Token {
+    access_token: String,
+    renew_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// The seconds the token is valid for, as a string.
+    expires_in: String,
+}
+
+impl ManagedIdentity {
+    fn new(client_id: Option<String>) -> Self {
+        Self {
+            client_id,
+            token: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    async fn token(&self, client: &mut HttpClient) -> crate::Result<String> {
+        let cached = self
+            .token
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|token| token.renew_at > Instant::now())
+            .map(|token| token.access_token.clone());
+        if let Some(token) = cached {
+            return Ok(token);
+        }
+
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        query
+            .append_pair("api-version", "2018-02-01")
+            .append_pair("resource", STORAGE_RESOURCE);
+        if let Some(client_id) = &self.client_id {
+            query.append_pair("client_id", client_id);
+        }
+        let uri = format!("{}?{}", IMDS_TOKEN_ENDPOINT, query.finish()).parse::<Uri>()?;
+        let request = Request::get(uri)
+            .header("Metadata", "true")
+            .body(Body::empty())?;
+
+        let response = client.send(request).await?;
+        let status = response.status();
+        let body = response.into_body().concat2().compat().await?;
+        if !status.is_success() {
+            return Err(AuthError::TokenRequestFailed {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            }
+            .into());
+        }
+
+        let response: TokenResponse =
+            serde_json::from_slice(&body).context(InvalidTokenResponse)?;
+        let expires_in = Duration::from_secs(response.expires_in.parse().unwrap_or(0));
+        *self.token.write().unwrap() = Some(Token {
+            access_token: response.access_token.clone(),
+            renew_at: Instant::now()
+                + expires_in
+                    .checked_sub(TOKEN_RENEWAL_MARGIN)
+                    .unwrap_or_default(),
+        });
+        debug!(message = "Renewed the managed identity token.", ?expires_in);
+
+        Ok(response.access_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth(config: &str) -> Result<Account, AuthError> {
+        toml::from_str::<AzureAuthConfig>(config).unwrap().build()
+    }
+
+    #[test]
+    fn azure_blob_parses_connection_strings() {
+        let account = auth(
+            r#"connection_string = "DefaultEndpointsProtocol=https;AccountName=logs;AccountKey=a2V5;EndpointSuffix=core.chinacloudapi.cn""#,
+        )
+        .unwrap();
+        assert_eq!(account.name(), "logs");
+        assert_eq!(
+            account.url("container/blob", ""),
+            "https://logs.blob.core.chinacloudapi.cn/container/blob"
+        );
+        assert!(matches!(account.credentials, Credentials::SharedKey(ref key) if key == b"key"));
+
+        let account = auth(
+            r#"connection_string = "BlobEndpoint=http://127.0.0.1:10000/logs/;AccountName=logs;SharedAccessSignature=sv=2019-12-12&sig=abc""#,
+        )
+        .unwrap();
+        assert_eq!(
+            account.url("container/blob", "comp=block"),
+            "http://127.0.0.1:10000/logs/container/blob?comp=block&sv=2019-12-12&sig=abc"
+        );
+
+        let account = auth(
+            r#"
+            storage_account = "logs"
+            sas_token = "?sig=abc"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            account.url("container/blob", ""),
+            "https://logs.blob.core.windows.net/container/blob?sig=abc"
+        );
+
+        let account = auth(r#"connection_string = "UseDevelopmentStorage=true""#).unwrap();
+        assert_eq!(
+            account.url("c", ""),
+            "http://127.0.0.1:10000/devstoreaccount1/c"
+        );
+    }
+
+    #[test]
+    fn azure_blob_rejects_invalid_auth() {
+        assert!(matches!(
+            auth(r#"connection_string = "AccountName=logs""#),
+            Err(AuthError::MissingConnectionStringKey { key: "AccountKey" })
+        ));
+        assert!(matches!(
+            auth(r#"connection_string = "AccountName""#),
+            Err(AuthError::InvalidConnectionStringPart { .. })
+        ));
+        assert!(matches!(
+            auth(
+                r#"
+                connection_string = "UseDevelopmentStorage=true"
+                sas_token = "sig=abc"
+                "#
+            ),
+            Err(AuthError::ConflictsWithConnectionString {
+                option: "sas_token"
+            })
+        ));
+        assert!(matches!(
+            auth(
+                r#"
+                storage_account = "logs"
+                sas_token = "sig=abc"
+                managed_identity = true
+                "#
+            ),
+            Err(AuthError::NoCredentials)
+        ));
+        assert!(matches!(
+            auth(r#"managed_identity = true"#),
+            Err(AuthError::MissingStorageAccount {
+                option: "managed_identity"
+            })
+        ));
+    }
+
+    #[test]
+    fn azure_blob_signs_with_shared_key() {
+        let request =
+            Request::put("http://127.0.0.1:10000/logs/container/blob?comp=block&blockid=YQ%3D%3D")
+                .header("content-length", "11")
+                .header("x-ms-version", API_VERSION)
+                .header("x-ms-date", "Fri, 16 Oct 2026 12:00:00 GMT")
+                .body(Body::empty())
+                .unwrap();
+
+        assert_eq!(
+            string_to_sign("logs", &request),
+            "PUT\n\n\n11\n\n\n\n\n\n\n\n\n\
+             x-ms-date:Fri, 16 Oct 2026 12:00:00 GMT\n\
+             x-ms-version:2019-12-12\n\
+             /logs/logs/container/blob\n\
+             blockid:YQ==\n\
+             comp:block"
+        );
+
+        assert_eq!(
+            sign(b"key", "The quick brown fox jumps over the lazy dog").unwrap(),
+            "97yD9DBThCSxMpjmqm+xQ+9NWaFJRhdZl0edvC0aPNg="
+        );
+    }
+}
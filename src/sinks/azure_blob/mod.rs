@@ -0,0 +1,905 @@
+mod auth;
+
+use self::auth::{Account, AzureAuthConfig};
+use crate::{
+    event::{self, Event},
+    sinks::{
+        util::{
+            self,
+            compression::Compression,
+            encoding::{EncodingConfigWithDefault, EncodingConfiguration},
+            http::HttpClient,
+            retries::RetryLogic,
+            BatchConfig, BatchSettings, Buffer, PartitionBatchSink, PartitionBuffer,
+            PartitionInnerBuffer, ServiceBuilderExt, TowerRequestConfig,
+        },
+        Healthcheck, RouterSink,
+    },
+    template::Template,
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use futures::{compat::Future01CompatExt, FutureExt, TryFutureExt};
+use futures01::{stream::iter_ok, Future, Poll, Sink, Stream};
+use http::{HeaderValue, Request, StatusCode, Uri};
+use hyper::Body;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::io::Write;
+use tower::{Service, ServiceBuilder};
+use url::percent_encoding::{utf8_percent_encode, DEFAULT_ENCODE_SET};
+
+const NAME: &str = "azure_blob";
+
+/// Batches up to this size are uploaded whole, larger ones in blocks of it.
+const DEFAULT_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+const MAX_BLOCK_SIZE: usize = 4000 * 1024 * 1024;
+const MAX_BLOCKS: usize = 50_000;
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AzureBlobSinkConfig {
+    pub container_name: String,
+    pub blob_prefix: Option<String>,
+    pub blob_time_format: Option<String>,
+    pub blob_append_uuid: Option<bool>,
+    pub blob_extension: Option<String>,
+    pub block_size: Option<usize>,
+    #[serde(flatten)]
+    pub auth: AzureAuthConfig,
+    #[serde(
+        skip_serializing_if = "crate::serde::skip_serializing_if_default",
+        default
+    )]
+    pub encoding: EncodingConfigWithDefault<Encoding>,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    /// Upload the batches of each blob prefix in order, one at a time.
+    #[serde(default)]
+    pub preserve_order: bool,
+    pub tls: Option<TlsOptions>,
+}
+
+lazy_static! {
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        in_flight_limit: Some(50),
+        rate_limit_num: Some(250),
+        ..Default::default()
+    };
+}
+
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Clone, Copy, Derivative)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Encoding {
+    #[derivative(Default)]
+    Text,
+    Ndjson,
+}
+
+impl Encoding {
+    fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Text => "text/plain",
+            Encoding::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<AzureBlobSinkConfig>(NAME)
+}
+
+#[typetag::serde(name = "azure_blob")]
+impl SinkConfig for AzureBlobSinkConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(RouterSink, Healthcheck)> {
+        validate_container_name(&self.container_name)?;
+        let account = self.auth.build()?;
+        account
+            .url(&self.container_name, "")
+            .parse::<Uri>()
+            .context(InvalidEndpoint)?;
+
+        let tls = TlsSettings::from_options(&self.tls)?;
+        let client = HttpClient::new(cx.resolver(), tls)?;
+
+        let healthcheck = healthcheck(client.clone(), account.clone(), self.container_name.clone());
+        let sink = self.service(client, account, &cx)?;
+
+        Ok((sink, Box::new(Box::pin(healthcheck).compat())))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        NAME
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display(
+        "Invalid container name {:?}: it must be 3 to 63 lowercase letters, digits and \
+         single hyphens, starting and ending with a letter or digit",
+        name
+    ))]
+    InvalidContainerName { name: String },
+    #[snafu(display("Invalid endpoint: {}", source))]
+    InvalidEndpoint { source: http::uri::InvalidUri },
+    #[snafu(display(
+        "`block_size` must be from 1 to {} bytes, got {}",
+        MAX_BLOCK_SIZE,
+        block_size
+    ))]
+    InvalidBlockSize { block_size: usize },
+}
+
+#[derive(Debug, Snafu)]
+enum HealthcheckError {
+    #[snafu(display("Invalid credentials"))]
+    InvalidCredentials,
+    #[snafu(display("Unknown container: {:?}", container))]
+    UnknownContainer { container: String },
+    #[snafu(display("Unknown status code: {}", status))]
+    UnknownStatus { status: StatusCode },
+}
+
+#[derive(Debug, Snafu)]
+pub enum AzureBlobError {
+    #[snafu(display("Failed to authorize the request: {}", source))]
+    Authorize { source: crate::Error },
+    #[snafu(display("Failed to send the request: {}", source))]
+    Http { source: hyper::Error },
+    #[snafu(display("{} returned {}: {}", call, status, body))]
+    Status {
+        call: &'static str,
+        status: StatusCode,
+        body: String,
+    },
+    #[snafu(display("Blob name {:?} doesn't make a valid URL", name))]
+    InvalidBlobName { name: String },
+    #[snafu(display(
+        "The batch takes {} blocks, more than the {} a blob can have",
+        blocks,
+        MAX_BLOCKS
+    ))]
+    TooManyBlocks { blocks: usize },
+}
+
+/// The service's own rules, the `$root` container aside.
+fn validate_container_name(name: &str) -> Result<(), BuildError> {
+    let valid = name == "$root"
+        || (name.len() >= 3
+            && name.len() <= 63
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !name.starts_with('-')
+            && !name.ends_with('-')
+            && !name.contains("--"));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(BuildError::InvalidContainerName { name: name.into() })
+    }
+}
+
+impl AzureBlobSinkConfig {
+    fn service(
+        &self,
+        client: HttpClient,
+        account: Account,
+        cx: &SinkContext,
+    ) -> crate::Result<RouterSink> {
+        let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+        let encoding = self.encoding.clone();
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(300),
+        );
+
+        let block_size = self.block_size.unwrap_or(DEFAULT_BLOCK_SIZE);
+        if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+            return Err(BuildError::InvalidBlockSize { block_size }.into());
+        }
+
+        let blob_prefix = Template::from(self.blob_prefix.as_deref().unwrap_or("date=%F/"));
+        let settings = RequestSettings::new(self);
+
+        let service = AzureBlobService {
+            client,
+            account,
+            container_name: self.container_name.clone(),
+            block_size,
+        };
+        let svc = ServiceBuilder::new()
+            .map(move |req| build_request(req, &settings))
+            .settings(request, AzureBlobRetryLogic)
+            .request_metrics(cx)
+            .service(service);
+
+        let buffer = PartitionBuffer::new(Buffer::new(false));
+
+        let sink = PartitionBatchSink::new(svc, buffer, batch, cx.acker())
+            .component(cx.name())
+            .preserve_order(self.preserve_order)
+            .sink_map_err(|error| error!("Fatal azure_blob sink error: {}", error))
+            .with_flat_map(move |e| iter_ok(encode_event(e, &blob_prefix, &encoding)));
+
+        Ok(Box::new(sink))
+    }
+}
+
+/// Checks that the container exists and the credentials may use it.
+async fn healthcheck(
+    mut client: HttpClient,
+    account: Account,
+    container: String,
+) -> crate::Result<()> {
+    let uri = account
+        .url(&container, "restype=container")
+        .parse::<Uri>()?;
+    let mut request = Request::head(uri).body(Body::empty())?;
+    account.authorize(&mut client, &mut request).await?;
+
+    let response = client.send(request).await?;
+    match response.status() {
+        StatusCode::OK => Ok(()),
+        StatusCode::FORBIDDEN => Err(HealthcheckError::InvalidCredentials.into()),
+        StatusCode::NOT_FOUND => Err(HealthcheckError::UnknownContainer { container }.into()),
+        status => Err(HealthcheckError::UnknownStatus { status }.into()),
+    }
+}
+
+// Settings required to name and describe the blobs that do not change per
+// request.
+#[derive(Clone, Debug)]
+struct RequestSettings {
+    time_format: String,
+    append_uuid: bool,
+    extension: String,
+    compression: Compression,
+    content_type: &'static str,
+}
+
+impl RequestSettings {
+    fn new(config: &AzureBlobSinkConfig) -> Self {
+        let extension = config.blob_extension.clone().unwrap_or_else(|| {
+            match config.compression {
+                Compression::None => "log",
+                Compression::Gzip(_) => "log.gz",
+                Compression::Zstd(_) => "log.zst",
+            }
+            .into()
+        });
+
+        Self {
+            time_format: config.blob_time_format.clone().unwrap_or("%s".into()),
+            append_uuid: config.blob_append_uuid.unwrap_or(true),
+            extension,
+            compression: config.compression,
+            content_type: config.encoding.codec().content_type(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct UploadRequest {
+    blob_name: String,
+    body: Bytes,
+    content_type: &'static str,
+    content_encoding: Option<&'static str>,
+}
+
+fn build_request(
+    req: PartitionInnerBuffer<Vec<u8>, Bytes>,
+    settings: &RequestSettings,
+) -> UploadRequest {
+    let (body, prefix) = req.into_parts();
+
+    let blob_name = util::object_name(
+        &prefix[..],
+        &settings.time_format,
+        settings.append_uuid,
+        &settings.extension,
+    );
+
+    UploadRequest {
+        blob_name,
+        body: compress(settings.compression, body).into(),
+        content_type: settings.content_type,
+        content_encoding: settings.compression.content_encoding(),
+    }
+}
+
+/// Compresses the whole batch, so the blob is one gzip or zstd stream.
+fn compress(compression: Compression, body: Vec<u8>) -> Vec<u8> {
+    if let Some(level) = compression.gzip_level() {
+        let mut encoder = GzEncoder::new(Vec::new(), level);
+        encoder
+            .write_all(&body)
+            .and_then(|_| encoder.finish())
+            .expect("Writing to Vec can't fail")
+    } else if let Some(level) = compression.zstd_level() {
+        zstd::stream::encode_all(&body[..], level).expect("Writing to Vec can't fail")
+    } else {
+        body
+    }
+}
+
+#[derive(Clone)]
+struct AzureBlobService {
+    client: HttpClient,
+    account: Account,
+    container_name: String,
+    block_size: usize,
+}
+
+impl Service<UploadRequest> for AzureBlobService {
+    type Response = ();
+    type Error = AzureBlobError;
+    type Future = Box<dyn Future<Item = (), Error = AzureBlobError> + Send>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(().into())
+    }
+
+    fn call(&mut self, request: UploadRequest) -> Self::Future {
+        let service = self.clone();
+        Box::new(service.upload(request).boxed().compat())
+    }
+}
+
+impl AzureBlobService {
+    /// Puts batches up to the block size as one blob. Larger ones are staged
+    /// block by block, and only become the blob once the list of blocks is
+    /// committed, so a failed upload never leaves a partial blob behind.
+    async fn upload(mut self, request: UploadRequest) -> Result<(), AzureBlobError> {
+        debug!(
+            message = "uploading blob.",
+            blob = %request.blob_name,
+            bytes = request.body.len(),
+        );
+
+        if request.body.len() <= self.block_size {
+            let mut put = self.request(&request.blob_name, "", request.body.clone())?;
+            put.headers_mut()
+                .insert("x-ms-blob-type", "BlockBlob".parse().unwrap());
+            content_headers(&mut put, &request);
+            return self.send("Put Blob", put).await;
+        }
+
+        let blocks = (request.body.len() + self.block_size - 1) / self.block_size;
+        if blocks > MAX_BLOCKS {
+            return Err(AzureBlobError::TooManyBlocks { blocks });
+        }
+
+        let mut block_list = String::from(r#"<?xml version="1.0" encoding="utf-8"?><BlockList>"#);
+        for index in 0..blocks {
+            let start = index * self.block_size;
+            let end = request.body.len().min(start + self.block_size);
+            let id = block_id(index);
+
+            let query = format!(
+                "comp=block&blockid={}",
+                url::form_urlencoded::byte_serialize(id.as_bytes()).collect::<String>()
+            );
+            let put = self.request(&request.blob_name, &query, request.body.slice(start, end))?;
+            self.send("Put Block", put).await?;
+
+            block_list.push_str(&format!("<Latest>{}</Latest>", id));
+        }
+        block_list.push_str("</BlockList>");
+
+        let mut commit = self.request(&request.blob_name, "comp=blocklist", block_list.into())?;
+        content_headers(&mut commit, &request);
+        self.send("Put Block List", commit).await
+    }
+
+    fn request(
+        &self,
+        blob_name: &str,
+        query: &str,
+        body: Bytes,
+    ) -> Result<Request<Body>, AzureBlobError> {
+        let path = format!(
+            "{}/{}",
+            self.container_name,
+            utf8_percent_encode(blob_name, DEFAULT_ENCODE_SET)
+        );
+        let uri = self.account.url(&path, query).parse::<Uri>().map_err(|_| {
+            AzureBlobError::InvalidBlobName {
+                name: blob_name.into(),
+            }
+        })?;
+
+        let content_length = HeaderValue::from(body.len());
+        let mut request = Request::put(uri).body(Body::from(body)).unwrap();
+        request
+            .headers_mut()
+            .insert("content-length", content_length);
+        Ok(request)
+    }
+
+    async fn send(
+        &mut self,
+        call: &'static str,
+        mut request: Request<Body>,
+    ) -> Result<(), AzureBlobError> {
+        self.account
+            .authorize(&mut self.client, &mut request)
+            .await
+            .context(Authorize)?;
+
+        let response = self.client.call(request).compat().await.context(Http)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = response
+            .into_body()
+            .concat2()
+            .compat()
+            .await
+            .context(Http)?;
+        Err(AzureBlobError::Status {
+            call,
+            status,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+}
+
+/// The properties of the blob, set when it's put or its blocks committed.
+fn content_headers(put: &mut Request<Body>, request: &UploadRequest) {
+    let headers = put.headers_mut();
+    headers.insert(
+        "x-ms-blob-content-type",
+        request.content_type.parse().unwrap(),
+    );
+    if let Some(content_encoding) = request.content_encoding {
+        headers.insert(
+            "x-ms-blob-content-encoding",
+            content_encoding.parse().unwrap(),
+        );
+    }
+}
+
+/// Block IDs are base64, and must all have the same length within a blob.
+fn block_id(index: usize) -> String {
+    base64::encode(&format!("block-{:06}", index))
+}
+
+#[derive(Clone)]
+struct AzureBlobRetryLogic;
+
+impl RetryLogic for AzureBlobRetryLogic {
+    type Error = AzureBlobError;
+    type Response = ();
+
+    fn is_retriable_error(&self, error: &Self::Error) -> bool {
+        match error {
+            // Managed identity tokens are fetched over the network too.
+            AzureBlobError::Authorize { .. } => true,
+            AzureBlobError::Http { source } => source.is_connect() || source.is_closed(),
+            AzureBlobError::Status { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            AzureBlobError::InvalidBlobName { .. } | AzureBlobError::TooManyBlocks { .. } => false,
+        }
+    }
+}
+
+fn encode_event(
+    mut event: Event,
+    blob_prefix: &Template,
+    encoding: &EncodingConfigWithDefault<Encoding>,
+) -> Option<PartitionInnerBuffer<Vec<u8>, Bytes>> {
+    encoding.apply_rules(&mut event);
+    let prefix = blob_prefix
+        .render_string(&event)
+        .map_err(|missing_keys| {
+            warn!(
+                message = "Keys do not exist on the event. Dropping event.",
+                ?missing_keys,
+                rate_limit_secs = 30,
+            );
+        })
+        .ok()?;
+
+    let log = event.into_log();
+    let bytes = match encoding.codec() {
+        Encoding::Ndjson => serde_json::to_vec(&log)
+            .map(|mut b| {
+                b.push(b'\n');
+                b
+            })
+            .expect("Failed to encode event as json, this is a bug!"),
+        Encoding::Text => {
+            let mut bytes = log
+                .get(&event::log_schema().message_key())
+                .map(|v| v.as_bytes().to_vec())
+                .unwrap_or_default();
+            bytes.push(b'\n');
+            bytes
+        }
+    };
+
+    Some(PartitionInnerBuffer::new(bytes, prefix.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+    use chrono::{TimeZone, Utc};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn settings(config: &str) -> RequestSettings {
+        let config = toml::from_str::<AzureBlobSinkConfig>(&format!(
+            r#"
+            container_name = "logs"
+            connection_string = "UseDevelopmentStorage=true"
+            {}
+            "#,
+            config
+        ))
+        .unwrap();
+        RequestSettings::new(&config)
+    }
+
+    #[test]
+    fn azure_blob_renders_blob_prefix_templates() {
+        let blob_prefix = Template::from("app={{ app }}/date=%F/");
+
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("app", "api");
+        event.as_mut_log().insert(
+            event::log_schema().timestamp_key().clone(),
+            Utc.ymd(2020, 10, 28).and_hms(10, 20, 30),
+        );
+
+        let (bytes, prefix) = encode_event(event, &blob_prefix, &Encoding::Text.into())
+            .unwrap()
+            .into_parts();
+        assert_eq!(&bytes[..], b"hello world\n");
+        assert_eq!(&prefix[..], b"app=api/date=2020-10-28/");
+
+        assert!(
+            encode_event(Event::from("no app"), &blob_prefix, &Encoding::Text.into()).is_none()
+        );
+    }
+
+    #[test]
+    fn azure_blob_names_blobs() {
+        let buf = PartitionInnerBuffer::new(b"hello\n".to_vec(), Bytes::from("date=2020-10-28/"));
+
+        let req = build_request(
+            buf.clone(),
+            &settings(
+                r#"
+                blob_time_format = "batch"
+                blob_append_uuid = false
+                blob_extension = "ext"
+                "#,
+            ),
+        );
+        assert_eq!(req.blob_name, "date=2020-10-28/batch.ext");
+        assert_eq!(req.content_type, "text/plain");
+        assert_eq!(req.content_encoding, None);
+
+        let req = build_request(buf.clone(), &settings(r#"blob_time_format = "batch""#));
+        assert!(req.blob_name.starts_with("date=2020-10-28/batch-"));
+        assert!(req.blob_name.ends_with(".log"));
+        assert_ne!(
+            req.blob_name,
+            build_request(buf.clone(), &settings(r#"blob_time_format = "batch""#)).blob_name
+        );
+    }
+
+    #[test]
+    fn azure_blob_compresses_batches() {
+        let buf = PartitionInnerBuffer::new(b"hello\n".to_vec(), Bytes::from("logs/"));
+
+        let req = build_request(
+            buf.clone(),
+            &settings(
+                r#"
+                compression = "gzip"
+                encoding = "ndjson"
+                blob_append_uuid = false
+                "#,
+            ),
+        );
+        assert!(req.blob_name.ends_with(".log.gz"));
+        assert_eq!(req.content_type, "application/x-ndjson");
+        assert_eq!(req.content_encoding, Some("gzip"));
+        let mut body = String::new();
+        GzDecoder::new(&req.body[..])
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, "hello\n");
+
+        let req = build_request(buf, &settings(r#"compression = "zstd(19)""#));
+        assert!(req.blob_name.ends_with(".log.zst"));
+        assert_eq!(req.content_encoding, Some("zstd"));
+        assert_eq!(zstd::stream::decode_all(&req.body[..]).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn azure_blob_validates_container_names() {
+        for name in &["logs", "app-logs-2020", "abc", "$root"] {
+            assert!(validate_container_name(name).is_ok(), "{}", name);
+        }
+        for name in &[
+            "ab",
+            "Logs",
+            "app--logs",
+            "-logs",
+            "logs-",
+            "app_logs",
+            "$logs",
+        ] {
+            assert!(validate_container_name(name).is_err(), "{}", name);
+        }
+        assert!(validate_container_name(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn azure_blob_block_ids_have_one_length() {
+        assert_eq!(block_id(0).len(), block_id(MAX_BLOCKS - 1).len());
+        assert_ne!(block_id(0), block_id(1));
+    }
+}
+
+#[cfg(feature = "azure-blob-integration-tests")]
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::{
+        assert_downcast_matches,
+        test_util::{random_lines_with_stream, random_string, runtime},
+    };
+    use flate2::read::GzDecoder;
+    use http::HeaderMap;
+    use std::io::{BufRead, BufReader};
+
+    const CONTAINER: &str = "logs";
+    const CONNECTION_STRING: &str = "UseDevelopmentStorage=true";
+
+    fn config(batch_size: usize) -> AzureBlobSinkConfig {
+        AzureBlobSinkConfig {
+            container_name: CONTAINER.into(),
+            blob_prefix: Some(random_string(10) + "/date=%F/"),
+            auth: AzureAuthConfig {
+                connection_string: Some(CONNECTION_STRING.into()),
+                ..Default::default()
+            },
+            batch: BatchConfig {
+                max_bytes: Some(batch_size),
+                timeout_secs: Some(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Sends `lines` through a sink built from `config`, and returns the
+    /// blobs it wrote, in name order, with their headers and bodies.
+    fn upload(config: &AzureBlobSinkConfig, events: usize) -> (Vec<String>, Vec<Blob>) {
+        let mut rt = runtime();
+        let cx = SinkContext::new_test(rt.executor());
+        create_container(&mut rt);
+
+        let (sink, _) = config.build(cx).unwrap();
+        let (lines, events) = random_lines_with_stream(100, events);
+        rt.block_on(sink.send_all(events)).unwrap();
+
+        let prefix = config
+            .blob_prefix
+            .as_ref()
+            .unwrap()
+            .split('/')
+            .next()
+            .unwrap();
+        let blobs = list_blobs(&mut rt, prefix)
+            .into_iter()
+            .map(|name| get_blob(&mut rt, &name, ""))
+            .collect();
+        (lines, blobs)
+    }
+
+    #[test]
+    fn azure_blob_insert_lines_into_blob() {
+        let (lines, blobs) = upload(&config(1_000_000), 10);
+
+        assert_eq!(blobs.len(), 1);
+        assert!(blobs[0].name.ends_with(".log"));
+        assert_eq!(blobs[0].headers["content-type"], "text/plain");
+        assert!(blobs[0].headers.get("content-encoding").is_none());
+        assert_eq!(blobs[0].lines(), lines);
+    }
+
+    #[test]
+    fn azure_blob_gzip() {
+        let config = AzureBlobSinkConfig {
+            compression: Compression::gzip_default(),
+            encoding: Encoding::Ndjson.into(),
+            ..config(1_000_000)
+        };
+        let (lines, blobs) = upload(&config, 10);
+
+        assert_eq!(blobs.len(), 1);
+        assert!(blobs[0].name.ends_with(".log.gz"));
+        assert_eq!(blobs[0].headers["content-type"], "application/x-ndjson");
+        assert_eq!(blobs[0].headers["content-encoding"], "gzip");
+
+        let messages = BufReader::new(GzDecoder::new(&blobs[0].body[..]))
+            .lines()
+            .map(|line| {
+                let event: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                event[&*event::log_schema().message_key()]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(messages, lines);
+    }
+
+    #[test]
+    fn azure_blob_commits_large_batches_in_blocks() {
+        let config = AzureBlobSinkConfig {
+            block_size: Some(1000),
+            ..config(1_000_000)
+        };
+        let (lines, blobs) = upload(&config, 100);
+
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].lines(), lines);
+        assert_eq!(blobs[0].headers["content-type"], "text/plain");
+
+        let mut rt = runtime();
+        let block_list = get_blob(&mut rt, &blobs[0].name, "comp=blocklist&blocklisttype=all");
+        let block_list = String::from_utf8(block_list.body).unwrap();
+        let committed = block_list.matches("<Block>").count();
+        // 100 lines of 100 characters, with their newlines.
+        assert_eq!(committed, (100 * 101 + 999) / 1000);
+        assert!(!block_list.contains("<UncommittedBlocks><Block>"));
+    }
+
+    #[test]
+    fn azure_blob_healthchecks() {
+        let mut rt = runtime();
+        create_container(&mut rt);
+
+        let (_, healthcheck) = config(1)
+            .build(SinkContext::new_test(rt.executor()))
+            .unwrap();
+        rt.block_on(healthcheck).unwrap();
+    }
+
+    #[test]
+    fn azure_blob_healthchecks_unknown_container() {
+        let mut rt = runtime();
+        let config = AzureBlobSinkConfig {
+            container_name: "unknown-container".into(),
+            ..config(1)
+        };
+
+        let (_, healthcheck) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+        assert_downcast_matches!(
+            rt.block_on(healthcheck).unwrap_err(),
+            HealthcheckError,
+            HealthcheckError::UnknownContainer { .. }
+        );
+    }
+
+    struct Blob {
+        name: String,
+        headers: HeaderMap,
+        body: Vec<u8>,
+    }
+
+    impl Blob {
+        fn lines(&self) -> Vec<String> {
+            BufReader::new(&self.body[..])
+                .lines()
+                .map(Result::unwrap)
+                .collect()
+        }
+    }
+
+    fn account() -> Account {
+        AzureAuthConfig {
+            connection_string: Some(CONNECTION_STRING.into()),
+            ..Default::default()
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn send(
+        rt: &mut crate::runtime::Runtime,
+        method: &str,
+        path: &str,
+        query: &str,
+    ) -> http::Response<Vec<u8>> {
+        let resolver = crate::dns::Resolver::new(Vec::new(), rt.executor()).unwrap();
+        let mut client = HttpClient::new(resolver, None).unwrap();
+        let account = account();
+        let uri = account.url(path, query).parse::<Uri>().unwrap();
+
+        rt.block_on_std(async move {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-length", "0")
+                .body(Body::empty())
+                .unwrap();
+            account.authorize(&mut client, &mut request).await.unwrap();
+
+            let response = client.send(request).await.unwrap();
+            let (parts, body) = response.into_parts();
+            let body = body.concat2().compat().await.unwrap();
+            http::Response::from_parts(parts, body.to_vec())
+        })
+    }
+
+    fn create_container(rt: &mut crate::runtime::Runtime) {
+        let response = send(rt, "PUT", CONTAINER, "restype=container");
+        let status = response.status();
+        assert!(
+            status == StatusCode::CREATED || status == StatusCode::CONFLICT,
+            "Couldn't create container: {}",
+            status
+        );
+    }
+
+    fn list_blobs(rt: &mut crate::runtime::Runtime, prefix: &str) -> Vec<String> {
+        let query = format!("restype=container&comp=list&prefix={}", prefix);
+        let response = send(rt, "GET", CONTAINER, &query);
+        assert!(response.status().is_success());
+
+        let listing = String::from_utf8(response.into_body()).unwrap();
+        listing
+            .split("<Name>")
+            .skip(1)
+            .map(|name| name.split("</Name>").next().unwrap().to_owned())
+            .collect()
+    }
+
+    fn get_blob(rt: &mut crate::runtime::Runtime, name: &str, query: &str) -> Blob {
+        let path = format!(
+            "{}/{}",
+            CONTAINER,
+            utf8_percent_encode(name, DEFAULT_ENCODE_SET)
+        );
+        let response = send(rt, "GET", &path, query);
+        assert!(response.status().is_success(), "{}", response.status());
+
+        let (parts, body) = response.into_parts();
+        Blob {
+            name: name.into(),
+            headers: parts.headers,
+            body,
+        }
+    }
+}
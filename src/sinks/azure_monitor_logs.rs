@@ -0,0 +1,412 @@
+//! Posts logs to the Log Analytics workspace of [Azure Monitor], through its
+//! [HTTP Data Collector API].
+//!
+//! [Azure Monitor]: https://docs.microsoft.com/en-us/azure/azure-monitor/
+//! [HTTP Data Collector API]: https://docs.microsoft.com/en-us/azure/azure-monitor/platform/data-collector-api
+
+use crate::{
+    dns::Resolver,
+    event::{self, Event},
+    sinks::util::{
+        encoding::{EncodingConfigWithDefault, EncodingConfiguration},
+        http::{BatchedHttpSink, HttpClient, HttpSink},
+        BatchConfig, BatchSettings, BoxedRawValue, TowerRequestConfig, VecBuffer,
+    },
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use chrono::Utc;
+use futures::{compat::Future01CompatExt, TryFutureExt};
+use futures01::{Sink, Stream};
+use http::{header::HeaderValue, Request, StatusCode, Uri};
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use snafu::{ResultExt, Snafu};
+
+const NAME: &str = "azure_monitor_logs";
+const API_VERSION: &str = "2016-04-01";
+const RESOURCE: &str = "/api/logs";
+const CONTENT_TYPE: &str = "application/json";
+
+/// The API rejects posts larger than this.
+const MAX_BATCH_BYTES: usize = 30_000_000;
+/// Nor does it accept longer record types.
+const MAX_LOG_TYPE_LEN: usize = 100;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AzureMonitorLogsConfig {
+    /// The ID of the Log Analytics workspace.
+    pub customer_id: String,
+    /// The primary or secondary key of the workspace, base64 encoded.
+    pub shared_key: String,
+    /// The record type the logs are stored as, `_CL` suffix excluded.
+    pub log_type: String,
+    /// The field holding the time of each record. Defaults to the
+    /// timestamp key of the log schema.
+    pub time_generated_key: Option<String>,
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(
+        skip_serializing_if = "crate::serde::skip_serializing_if_default",
+        default
+    )]
+    pub encoding: EncodingConfigWithDefault<Encoding>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    pub tls: Option<TlsOptions>,
+}
+
+fn default_host() -> String {
+    "ods.opinsights.azure.com".into()
+}
+
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Clone, Derivative)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Encoding {
+    #[derivative(Default)]
+    Default,
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<AzureMonitorLogsConfig>(NAME)
+}
+
+#[typetag::serde(name = "azure_monitor_logs")]
+impl SinkConfig for AzureMonitorLogsConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let request = self.request.unwrap_with(&TowerRequestConfig::default());
+        let batch = self.batch_settings();
+        let tls_settings = TlsSettings::from_options(&self.tls)?;
+        let sink = AzureMonitorLogsSink::new(self)?;
+
+        let healthcheck = healthcheck(sink.clone(), tls_settings.clone(), cx.resolver());
+        let healthcheck = Box::new(Box::pin(healthcheck).compat());
+
+        let sink = BatchedHttpSink::new(
+            sink,
+            // Each record takes a comma or the closing bracket of the array.
+            VecBuffer::new(|record: &BoxedRawValue| record.get().len() + 1),
+            request,
+            batch,
+            tls_settings,
+            &cx,
+        )
+        .sink_map_err(|e| error!("Fatal azure_monitor_logs sink error: {}", e));
+
+        Ok((Box::new(sink), healthcheck))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        NAME
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+impl AzureMonitorLogsConfig {
+    fn batch_settings(&self) -> BatchSettings {
+        let mut batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(1),
+        );
+        // Leaves room for the opening bracket of the array.
+        batch.size.bytes = batch.size.bytes.min(MAX_BATCH_BYTES - 1);
+        batch
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display(
+        "Invalid log_type {:?}: it must be 1 to {} letters, digits or underscores",
+        log_type,
+        MAX_LOG_TYPE_LEN
+    ))]
+    InvalidLogType { log_type: String },
+    #[snafu(display("Invalid time_generated_key {:?}: {}", key, source))]
+    InvalidTimeGeneratedKey {
+        key: String,
+        source: http::header::InvalidHeaderValue,
+    },
+    #[snafu(display("shared_key is not valid base64: {}", source))]
+    InvalidSharedKey { source: base64::DecodeError },
+    #[snafu(display("Invalid host {:?}: {}", host, source))]
+    InvalidHost {
+        host: String,
+        source: http::uri::InvalidUri,
+    },
+}
+
+#[derive(Debug, Snafu)]
+enum HealthcheckError {
+    #[snafu(display("Invalid customer_id or shared_key"))]
+    InvalidCredentials,
+    #[snafu(display("Server returned unexpected status {}: {}", status, body))]
+    UnexpectedStatus { status: StatusCode, body: String },
+}
+
+#[derive(Clone)]
+struct AzureMonitorLogsSink {
+    uri: Uri,
+    customer_id: String,
+    shared_key: PKey<Private>,
+    log_type: HeaderValue,
+    time_generated_key: HeaderValue,
+    encoding: EncodingConfigWithDefault<Encoding>,
+}
+
+impl AzureMonitorLogsSink {
+    fn new(config: &AzureMonitorLogsConfig) -> crate::Result<Self> {
+        let log_type = &config.log_type;
+        let valid_log_type = !log_type.is_empty()
+            && log_type.len() <= MAX_LOG_TYPE_LEN
+            && log_type
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_log_type {
+            return Err(BuildError::InvalidLogType {
+                log_type: log_type.clone(),
+            }
+            .into());
+        }
+
+        let key = config
+            .time_generated_key
+            .clone()
+            .unwrap_or_else(|| event::log_schema().timestamp_key().to_string());
+        let time_generated_key =
+            HeaderValue::from_str(&key).context(InvalidTimeGeneratedKey { key: key.clone() })?;
+
+        let shared_key = base64::decode(&config.shared_key).context(InvalidSharedKey)?;
+
+        let host = &config.host;
+        let uri = format!(
+            "https://{}.{}{}?api-version={}",
+            config.customer_id, host, RESOURCE, API_VERSION
+        )
+        .parse::<Uri>()
+        .context(InvalidHost { host: host.clone() })?;
+
+        Ok(Self {
+            uri,
+            customer_id: config.customer_id.clone(),
+            shared_key: PKey::hmac(&shared_key)?,
+            log_type: HeaderValue::from_str(log_type)?,
+            time_generated_key,
+            encoding: config.encoding.clone(),
+        })
+    }
+
+    /// The `Authorization` header of a post of `content_length` bytes, sent
+    /// with `date` as its `x-ms-date` header.
+    fn authorization(&self, date: &str, content_length: usize) -> String {
+        let string_to_sign = format!(
+            "POST\n{}\n{}\nx-ms-date:{}\n{}",
+            content_length, CONTENT_TYPE, date, RESOURCE
+        );
+
+        let signature = Signer::new(MessageDigest::sha256(), &self.shared_key)
+            .and_then(|mut signer| {
+                signer.update(string_to_sign.as_bytes())?;
+                signer.sign_to_vec()
+            })
+            .expect("HMAC signing can't fail");
+
+        format!(
+            "SharedKey {}:{}",
+            self.customer_id,
+            base64::encode(&signature)
+        )
+    }
+}
+
+impl HttpSink for AzureMonitorLogsSink {
+    type Input = BoxedRawValue;
+    type Output = Vec<BoxedRawValue>;
+
+    fn encode_event(&self, mut event: Event) -> Option<Self::Input> {
+        self.encoding.apply_rules(&mut event);
+
+        let record = serde_json::to_string(event.as_log())
+            .expect("Failed to encode event as json, this is a bug!");
+        Some(RawValue::from_string(record).expect("Encoded event should be valid json"))
+    }
+
+    fn build_request(&self, records: Self::Output) -> http::Request<Vec<u8>> {
+        let body = serde_json::to_vec(&records).expect("Records should be valid json");
+        // The API only takes RFC 1123 dates, which are always in GMT.
+        let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+        let mut request = Request::post(self.uri.clone());
+        request
+            .header("Content-Type", CONTENT_TYPE)
+            .header("Authorization", self.authorization(&date, body.len()))
+            .header("Log-Type", self.log_type.clone())
+            .header("x-ms-date", date)
+            .header("time-generated-field", self.time_generated_key.clone());
+
+        request.body(body).unwrap()
+    }
+}
+
+/// Posts an empty batch, which the API authenticates but stores nothing of.
+async fn healthcheck(
+    sink: AzureMonitorLogsSink,
+    tls: TlsSettings,
+    resolver: Resolver,
+) -> crate::Result<()> {
+    let mut client = HttpClient::new(resolver, tls)?;
+    let request = sink.build_request(Vec::new()).map(hyper::Body::from);
+
+    let response = client.send(request).await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    if status == StatusCode::FORBIDDEN {
+        return Err(HealthcheckError::InvalidCredentials.into());
+    }
+
+    let body = response.into_body().concat2().compat().await?;
+    Err(HealthcheckError::UnexpectedStatus {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOMER_ID: &str = "97ce69d9-b4be-4241-8dbd-d265edcf06c4";
+    // The 64 bytes from 0 to 63.
+    const SHARED_KEY: &str =
+        "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+Pw==";
+
+    fn config(extra: &str) -> AzureMonitorLogsConfig {
+        toml::from_str(&format!(
+            r#"
+            customer_id = "{}"
+            shared_key = "{}"
+            log_type = "Vector"
+            {}
+            "#,
+            CUSTOMER_ID, SHARED_KEY, extra
+        ))
+        .unwrap()
+    }
+
+    fn sink(extra: &str) -> AzureMonitorLogsSink {
+        AzureMonitorLogsSink::new(&config(extra)).unwrap()
+    }
+
+    #[test]
+    fn azure_monitor_logs_signs_with_shared_key() {
+        let payload = r#"[{"message":"hello world"}]"#;
+
+        assert_eq!(
+            sink("").authorization("Mon, 04 Apr 2016 08:00:00 GMT", payload.len()),
+            format!(
+                "SharedKey {}:H2t1x5WEaEsnMMolDcN90rSQ0oAbR8yuXEkiG6RaZwI=",
+                CUSTOMER_ID
+            )
+        );
+    }
+
+    #[test]
+    fn azure_monitor_logs_builds_requests() {
+        let sink = sink(r#"time_generated_key = "time""#);
+        let records = vec![
+            sink.encode_event(Event::from("hello")).unwrap(),
+            sink.encode_event(Event::from("world")).unwrap(),
+        ];
+
+        let request = sink.build_request(records);
+        let (parts, body) = request.into_parts();
+        let headers = &parts.headers;
+
+        assert_eq!(parts.method, http::Method::POST);
+        assert_eq!(
+            parts.uri,
+            format!(
+                "https://{}.ods.opinsights.azure.com/api/logs?api-version=2016-04-01",
+                CUSTOMER_ID
+            )
+            .as_str()
+        );
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["log-type"], "Vector");
+        assert_eq!(headers["time-generated-field"], "time");
+
+        let date = headers["x-ms-date"].to_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc2822(date).is_ok());
+        assert!(date.ends_with(" GMT"));
+        assert_eq!(
+            headers["authorization"],
+            sink.authorization(date, body.len()).as_str()
+        );
+
+        let records: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["message"], "hello");
+        assert_eq!(records[1]["message"], "world");
+    }
+
+    #[test]
+    fn azure_monitor_logs_applies_encoding_rules() {
+        let sink = sink(r#"encoding.except_fields = ["secret"]"#);
+        let mut event = Event::from("hello");
+        event.as_mut_log().insert("secret", "hunter2");
+
+        let record: serde_json::Value =
+            serde_json::from_str(sink.encode_event(event).unwrap().get()).unwrap();
+        assert_eq!(record["message"], "hello");
+        assert!(record.get("secret").is_none());
+        assert!(record.get("timestamp").is_some());
+    }
+
+    #[test]
+    fn azure_monitor_logs_defaults_time_generated_key() {
+        assert_eq!(sink("").time_generated_key, "timestamp");
+    }
+
+    #[test]
+    fn azure_monitor_logs_caps_batch_size() {
+        let settings = config("batch.max_bytes = 50_000_000").batch_settings();
+        assert_eq!(settings.size.bytes, MAX_BATCH_BYTES - 1);
+
+        let settings = config("batch.max_bytes = 1_000").batch_settings();
+        assert_eq!(settings.size.bytes, 1_000);
+    }
+
+    #[test]
+    fn azure_monitor_logs_rejects_invalid_config() {
+        let long = "a".repeat(MAX_LOG_TYPE_LEN + 1);
+        for log_type in &["", "Vector-Logs", "Vector Logs", long.as_str()] {
+            let mut config = config("");
+            config.log_type = log_type.to_string();
+            assert!(AzureMonitorLogsSink::new(&config).is_err(), "{}", log_type);
+        }
+
+        let mut config = config("");
+        config.shared_key = "not base64!".into();
+        assert!(AzureMonitorLogsSink::new(&config).is_err());
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
         encoding::{EncodingConfigWithDefault, EncodingConfiguration},
         http::{Auth, BatchedHttpSink, HttpClient, HttpRetryLogic, HttpSink, Response},
         retries::{RetryAction, RetryLogic},
-        BatchBytesConfig, Buffer, Compression, TowerRequestConfig,
+        BatchConfig, BatchSettings, Buffer, Compression, TowerRequestConfig,
     },
     tls::{TlsOptions, TlsSettings},
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
@@ -32,7 +32,7 @@ pub struct ClickhouseConfig {
     )]
     pub encoding: EncodingConfigWithDefault<Encoding>,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
     pub auth: Option<Auth>,
     #[serde(default)]
     pub request: TowerRequestConfig,
@@ -67,7 +67,11 @@ impl SinkConfig for ClickhouseConfig {
             Compression::Gzip => true,
         };
 
-        let batch = self.batch.unwrap_or(bytesize::mib(10u64), 1);
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(1),
+        );
         let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
         let tls_settings = TlsSettings::from_options(&self.tls)?;
 
@@ -91,6 +95,10 @@ impl SinkConfig for ClickhouseConfig {
     fn sink_type(&self) -> &'static str {
         "clickhouse"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for ClickhouseConfig {
@@ -264,9 +272,10 @@ mod integration_tests {
             host: host.clone(),
             table: table.clone(),
             compression: Some(Compression::None),
-            batch: BatchBytesConfig {
-                max_size: Some(1),
+            batch: BatchConfig {
+                max_bytes: Some(1),
                 timeout_secs: None,
+                ..Default::default()
             },
             request: TowerRequestConfig {
                 retry_attempts: Some(1),
@@ -311,9 +320,10 @@ mod integration_tests {
                 except_fields: None,
                 only_fields: None,
             },
-            batch: BatchBytesConfig {
-                max_size: Some(1),
+            batch: BatchConfig {
+                max_bytes: Some(1),
                 timeout_secs: None,
+                ..Default::default()
             },
             request: TowerRequestConfig {
                 retry_attempts: Some(1),
@@ -427,9 +437,10 @@ compression = "none"
             host: host.clone(),
             table: table.clone(),
             compression: Some(Compression::None),
-            batch: BatchBytesConfig {
-                max_size: Some(1),
+            batch: BatchConfig {
+                max_bytes: Some(1),
                 timeout_secs: None,
+                ..Default::default()
             },
             ..Default::default()
         };
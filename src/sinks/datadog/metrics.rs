@@ -6,7 +6,7 @@ use crate::{
     },
     sinks::util::{
         http::{BatchedHttpSink, HttpClient, HttpSink},
-        BatchEventsConfig, MetricBuffer, TowerRequestConfig,
+        BatchConfig, BatchSettings, MetricBuffer, TowerRequestConfig,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -44,7 +44,7 @@ pub struct DatadogConfig {
     pub host: String,
     pub api_key: String,
     #[serde(default)]
-    pub batch: BatchEventsConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
 }
@@ -112,7 +112,9 @@ impl SinkConfig for DatadogConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
         let healthcheck = healthcheck(self.clone(), cx.resolver())?;
 
-        let batch = self.batch.unwrap_or(20, 1);
+        let batch = self
+            .batch
+            .unwrap_events_only(BatchSettings::default().events(20).timeout(1))?;
         let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
 
         let uri = format!("{}/api/v1/series?api_key={}", self.host, self.api_key)
@@ -139,6 +141,10 @@ impl SinkConfig for DatadogConfig {
     fn sink_type(&self) -> &'static str {
         "datadog_metrics"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for DatadogSink {
@@ -1,4 +1,5 @@
 pub mod logs;
 pub mod metrics;
+pub mod traces;
 
 pub(self) use super::{Healthcheck, HealthcheckError, RouterSink, UriParseError};
@@ -0,0 +1,747 @@
+//! Datadog traces sink
+//!
+//! Posts spans to the Datadog trace intake, as the Datadog agent would, as
+//! msgpack. The intake also expects the APM stats the agent computes from
+//! the spans it sees, so these are computed here and posted alongside.
+//!
+//! Spans and stats are posted to paths of their own, so every span is
+//! batched twice, partitioned by payload.
+
+use crate::{
+    dns::Resolver,
+    event::{
+        trace::{PARENT_ID, SAMPLING_PRIORITY, SPAN_ID, TRACE_ID},
+        LogEvent, TraceEvent, Value,
+    },
+    runtime::FutureExt,
+    sinks::util::{
+        http::{HttpBatchService, HttpClient, HttpRetryLogic},
+        BatchConfig, BatchSettings, PartitionBatchSink, PartitionBuffer, PartitionInnerBuffer,
+        ServiceBuilderExt, TowerRequestConfig, UriSerde, VecBuffer,
+    },
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+    Event,
+};
+use futures01::{stream::iter_ok, Sink};
+use http::Uri;
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tower::ServiceBuilder;
+
+const TRACES_PATH: &str = "/api/v0.2/traces";
+const STATS_PATH: &str = "/api/v0.2/stats";
+
+/// The metric Datadog reads the sampling priority of a trace from.
+const SAMPLING_PRIORITY_METRIC: &str = "_sampling_priority_v1";
+/// Marks spans that aren't top-level, but should be counted in stats anyway.
+const MEASURED_METRIC: &str = "_dd.measured";
+/// Stats are aggregated in buckets of 10 seconds, as the agent does.
+const BUCKET_DURATION_NANOS: u64 = 10_000_000_000;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DatadogTracesConfig {
+    pub api_key: String,
+    #[serde(default)]
+    pub region: Region,
+    /// Overrides the trace intake of the region, which `/api/v0.2/traces`
+    /// and `/api/v0.2/stats` are appended to.
+    pub endpoint: Option<UriSerde>,
+    /// The environment spans are tagged with, unless they already have one.
+    pub env: Option<String>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    pub tls: Option<TlsOptions>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    Us,
+    Eu,
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Region::Us
+    }
+}
+
+impl Region {
+    fn trace_intake(self) -> &'static str {
+        match self {
+            Region::Us => "https://trace.agent.datadoghq.com",
+            Region::Eu => "https://trace.agent.datadoghq.eu",
+        }
+    }
+
+    fn api(self) -> &'static str {
+        match self {
+            Region::Us => "https://api.datadoghq.com",
+            Region::Eu => "https://api.datadoghq.eu",
+        }
+    }
+}
+
+lazy_static! {
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        retry_attempts: Some(5),
+        ..Default::default()
+    };
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<DatadogTracesConfig>("datadog_traces")
+}
+
+#[typetag::serde(name = "datadog_traces")]
+impl SinkConfig for DatadogTracesConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .events(1_000)
+                .bytes(bytesize::mib(3u64))
+                .timeout(1),
+        );
+        let tls = TlsSettings::from_options(&self.tls)?;
+
+        let sink = Arc::new(DatadogTracesSink::new(
+            self.clone(),
+            hostname::get_hostname().unwrap_or_default(),
+        ));
+        let sink1 = Arc::clone(&sink);
+        let svc = ServiceBuilder::new()
+            .settings(request, HttpRetryLogic)
+            .request_metrics(&cx)
+            .service(HttpBatchService::new(cx.resolver(), tls, move |batch| {
+                sink1.build_request(batch)
+            }));
+
+        let buffer = PartitionBuffer::new(VecBuffer::new(Span::estimated_size));
+        let sink = PartitionBatchSink::new(svc, buffer, batch, cx.acker())
+            .component(cx.name())
+            .with_flat_map(move |event| iter_ok(sink.encode_event(event)))
+            .sink_map_err(|error| error!("Fatal datadog_traces sink error: {}", error));
+
+        let healthcheck = healthcheck(self.clone(), cx.resolver()).boxed_compat();
+
+        Ok((Box::new(sink), Box::new(healthcheck)))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Trace
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "datadog_traces"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Payload {
+    Traces,
+    Stats,
+}
+
+/// A span as the trace intake expects it, in the agent's v0.4 format.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Span {
+    service: String,
+    name: String,
+    resource: String,
+    trace_id: u64,
+    span_id: u64,
+    parent_id: u64,
+    start: i64,
+    duration: i64,
+    error: i32,
+    meta: BTreeMap<String, String>,
+    metrics: BTreeMap<String, f64>,
+    #[serde(rename = "type")]
+    span_type: String,
+}
+
+impl Span {
+    fn estimated_size(&self) -> usize {
+        let strings = self.service.len() + self.name.len() + self.resource.len();
+        let meta: usize = self.meta.iter().map(|(k, v)| k.len() + v.len()).sum();
+        let metrics: usize = self.metrics.keys().map(|k| k.len() + 9).sum();
+        strings + self.span_type.len() + meta + metrics + 64
+    }
+}
+
+type Spans = PartitionInnerBuffer<Vec<Span>, Payload>;
+
+// The agent's `StatsPayload`, which the intake decodes with the field names
+// of its protobuf definition.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct StatsPayload {
+    agent_hostname: String,
+    agent_env: String,
+    stats: Vec<ClientStatsPayload>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ClientStatsPayload {
+    hostname: String,
+    env: String,
+    version: String,
+    stats: Vec<ClientStatsBucket>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ClientStatsBucket {
+    start: u64,
+    duration: u64,
+    stats: Vec<ClientGroupedStats>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ClientGroupedStats {
+    service: String,
+    name: String,
+    resource: String,
+    #[serde(rename = "HTTPStatusCode")]
+    http_status_code: u32,
+    #[serde(rename = "Type")]
+    span_type: String,
+    hits: u64,
+    errors: u64,
+    duration: u64,
+    top_level_hits: u64,
+}
+
+/// What stats are grouped by within a bucket.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Aggregation {
+    service: String,
+    name: String,
+    resource: String,
+    span_type: String,
+    http_status_code: u32,
+}
+
+#[derive(Default)]
+struct Counts {
+    hits: u64,
+    errors: u64,
+    duration: u64,
+    top_level_hits: u64,
+}
+
+struct DatadogTracesSink {
+    config: DatadogTracesConfig,
+    hostname: String,
+}
+
+impl DatadogTracesSink {
+    fn new(config: DatadogTracesConfig, hostname: String) -> Self {
+        Self { config, hostname }
+    }
+
+    fn encode_event(&self, event: Event) -> Vec<PartitionInnerBuffer<Span, Payload>> {
+        match encode_span(event.into_trace(), self.config.env.as_deref()) {
+            Some(span) => vec![
+                PartitionInnerBuffer::new(span.clone(), Payload::Traces),
+                PartitionInnerBuffer::new(span, Payload::Stats),
+            ],
+            None => vec![],
+        }
+    }
+
+    fn build_request(&self, batch: Spans) -> http::Request<Vec<u8>> {
+        let (spans, payload) = batch.into_parts();
+
+        let body = match payload {
+            Payload::Traces => rmp_serde::to_vec_named(&group_traces(spans)),
+            Payload::Stats => rmp_serde::to_vec_named(&compute_stats(
+                &spans,
+                &self.hostname,
+                self.config.env.as_deref().unwrap_or_default(),
+            )),
+        }
+        .expect("spans and stats encode as msgpack");
+
+        http::Request::post(self.uri(payload))
+            .header("Content-Type", "application/msgpack")
+            .header("DD-API-KEY", self.config.api_key.as_str())
+            .body(body)
+            .unwrap()
+    }
+
+    fn uri(&self, payload: Payload) -> Uri {
+        let path = match payload {
+            Payload::Traces => TRACES_PATH,
+            Payload::Stats => STATS_PATH,
+        };
+        let endpoint = match &self.config.endpoint {
+            Some(endpoint) => endpoint.to_string(),
+            None => self.config.region.trace_intake().to_owned(),
+        };
+        format!("{}{}", endpoint.trim_end_matches('/'), path)
+            .parse()
+            .expect("appending a path keeps the uri valid")
+    }
+}
+
+/// The span's `service`, `name`, `resource`, `type`, `start`, `duration` and
+/// `error` fields are taken as they are, with `start` either a timestamp or
+/// nanoseconds since the epoch. Fields under `meta` and numeric fields under
+/// `metrics` become the tags and metrics of the span, by their paths, and all
+/// other fields are added to its tags. Spans without valid ids or a start are
+/// dropped.
+fn encode_span(trace: TraceEvent, env: Option<&str>) -> Option<Span> {
+    let ids = (
+        trace.trace_id().as_deref().and_then(parse_id),
+        trace.span_id().as_deref().and_then(parse_id),
+        trace.parent_id().as_deref().map(parse_id),
+    );
+    let (trace_id, span_id, parent_id) = match ids {
+        (Some(trace_id), Some(span_id), None) => (trace_id, span_id, 0),
+        (Some(trace_id), Some(span_id), Some(Some(parent_id))) => (trace_id, span_id, parent_id),
+        _ => {
+            warn!(
+                message = "Span has a missing or invalid id; dropping it.",
+                rate_limit_secs = 30
+            );
+            return None;
+        }
+    };
+    let sampling_priority = trace.sampling_priority();
+
+    let mut log = trace.into_log();
+    for key in &[TRACE_ID, SPAN_ID, PARENT_ID, SAMPLING_PRIORITY] {
+        log.remove(&(*key).into());
+    }
+
+    let start = match log.remove(&"start".into()) {
+        Some(Value::Timestamp(timestamp)) => timestamp.timestamp_nanos(),
+        Some(Value::Integer(nanos)) => nanos,
+        _ => {
+            warn!(
+                message = "Span has no start time; dropping it.",
+                rate_limit_secs = 30
+            );
+            return None;
+        }
+    };
+    let duration = match log.remove(&"duration".into()) {
+        Some(Value::Integer(nanos)) => nanos.max(0),
+        _ => 0,
+    };
+    let error = match log.remove(&"error".into()) {
+        Some(Value::Integer(error)) => error as i32,
+        Some(Value::Boolean(error)) => error as i32,
+        _ => 0,
+    };
+
+    let name = take_string(&mut log, "name");
+    let resource = match take_string(&mut log, "resource") {
+        resource if resource.is_empty() => name.clone(),
+        resource => resource,
+    };
+    let mut span = Span {
+        service: take_string(&mut log, "service"),
+        name,
+        resource,
+        trace_id,
+        span_id,
+        parent_id,
+        start,
+        duration,
+        error,
+        meta: BTreeMap::new(),
+        metrics: BTreeMap::new(),
+        span_type: take_string(&mut log, "type"),
+    };
+
+    for (key, value) in log.all_fields() {
+        if key.starts_with("metrics.") {
+            let key = key["metrics.".len()..].to_owned();
+            match value {
+                Value::Integer(value) => span.metrics.insert(key, *value as f64),
+                Value::Float(value) => span.metrics.insert(key, *value),
+                _ => None,
+            };
+        } else if key.starts_with("meta.") {
+            let key = key["meta.".len()..].to_owned();
+            span.meta.insert(key, value.to_string_lossy());
+        } else {
+            span.meta
+                .entry(key)
+                .or_insert_with(|| value.to_string_lossy());
+        }
+    }
+    if let Some(priority) = sampling_priority {
+        span.metrics
+            .insert(SAMPLING_PRIORITY_METRIC.into(), priority as f64);
+    }
+    if let Some(env) = env {
+        span.meta
+            .entry("env".into())
+            .or_insert_with(|| env.to_owned());
+    }
+    Some(span)
+}
+
+fn take_string(log: &mut LogEvent, key: &str) -> String {
+    log.remove(&key.into())
+        .map(|value| value.to_string_lossy())
+        .unwrap_or_default()
+}
+
+/// Datadog ids are 64 bits wide. Decimal ids are taken as they are, while
+/// hex ids, like OpenTelemetry's 128-bit trace ids, are cut to their lower
+/// 64 bits, as Datadog does when it receives them. Ids that are valid as
+/// both are read as decimal.
+fn parse_id(id: &str) -> Option<u64> {
+    if let Ok(id) = id.parse() {
+        return Some(id);
+    }
+    if id.is_empty() || id.len() > 32 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(&id[id.len().saturating_sub(16)..], 16).ok()
+}
+
+/// Groups spans into traces, in the order their traces were first seen.
+fn group_traces(spans: Vec<Span>) -> Vec<Vec<Span>> {
+    let mut traces = IndexMap::<u64, Vec<Span>>::new();
+    for span in spans {
+        traces.entry(span.trace_id).or_default().push(span);
+    }
+    traces.into_iter().map(|(_, spans)| spans).collect()
+}
+
+/// Counts hits, errors and durations of top-level spans, the entry points of
+/// each service, and of spans marked as measured, in buckets by their end
+/// time. A span is top-level when its parent isn't in the batch or belongs
+/// to another service. Latency distributions aren't computed, only totals.
+fn compute_stats(spans: &[Span], hostname: &str, env: &str) -> StatsPayload {
+    let services: HashMap<(u64, u64), &str> = spans
+        .iter()
+        .map(|span| ((span.trace_id, span.span_id), span.service.as_str()))
+        .collect();
+
+    let mut buckets = BTreeMap::<u64, BTreeMap<Aggregation, Counts>>::new();
+    for span in spans {
+        let top_level = match services.get(&(span.trace_id, span.parent_id)) {
+            Some(service) => *service != span.service,
+            None => true,
+        };
+        let measured = span.metrics.get(MEASURED_METRIC) == Some(&1.0);
+        if !top_level && !measured {
+            continue;
+        }
+
+        let end = (span.start + span.duration).max(0) as u64;
+        let aggregation = Aggregation {
+            service: span.service.clone(),
+            name: span.name.clone(),
+            resource: span.resource.clone(),
+            span_type: span.span_type.clone(),
+            http_status_code: span
+                .meta
+                .get("http.status_code")
+                .and_then(|code| code.parse().ok())
+                .unwrap_or(0),
+        };
+        let counts = buckets
+            .entry(end - end % BUCKET_DURATION_NANOS)
+            .or_default()
+            .entry(aggregation)
+            .or_default();
+        counts.hits += 1;
+        counts.errors += (span.error != 0) as u64;
+        counts.duration += span.duration as u64;
+        counts.top_level_hits += top_level as u64;
+    }
+
+    let stats = buckets
+        .into_iter()
+        .map(|(start, groups)| ClientStatsBucket {
+            start,
+            duration: BUCKET_DURATION_NANOS,
+            stats: groups
+                .into_iter()
+                .map(|(aggregation, counts)| ClientGroupedStats {
+                    service: aggregation.service,
+                    name: aggregation.name,
+                    resource: aggregation.resource,
+                    http_status_code: aggregation.http_status_code,
+                    span_type: aggregation.span_type,
+                    hits: counts.hits,
+                    errors: counts.errors,
+                    duration: counts.duration,
+                    top_level_hits: counts.top_level_hits,
+                })
+                .collect(),
+        })
+        .collect();
+
+    StatsPayload {
+        agent_hostname: hostname.to_owned(),
+        agent_env: env.to_owned(),
+        stats: vec![ClientStatsPayload {
+            hostname: hostname.to_owned(),
+            env: env.to_owned(),
+            version: crate::get_version(),
+            stats,
+        }],
+    }
+}
+
+async fn healthcheck(config: DatadogTracesConfig, resolver: Resolver) -> crate::Result<()> {
+    let mut client = HttpClient::new(resolver, None)?;
+
+    let request = http::Request::get(format!("{}/api/v1/validate", config.region.api()))
+        .header("DD-API-KEY", config.api_key.as_str())
+        .body(hyper::Body::empty())
+        .unwrap();
+    let response = client.send(request).await?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(super::HealthcheckError::UnexpectedStatus { status }.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sink() -> DatadogTracesSink {
+        DatadogTracesSink::new(
+            toml::from_str(
+                r#"
+                api_key = "abc123"
+                region = "eu"
+                env = "prod"
+                "#,
+            )
+            .unwrap(),
+            "web-1".into(),
+        )
+    }
+
+    fn span(trace_id: &str, span_id: &str, parent_id: Option<&str>, service: &str) -> Event {
+        let mut span = TraceEvent::new(trace_id, span_id);
+        if let Some(parent_id) = parent_id {
+            span.set_parent_id(parent_id);
+        }
+        let log = span.as_mut_log();
+        log.insert("service", service);
+        log.insert("name", "http.request");
+        log.insert("start", 1_500_000_000_000_000_000i64);
+        log.insert("duration", 2_000_000i64);
+        Event::from(span)
+    }
+
+    fn send(
+        sink: &DatadogTracesSink,
+        events: Vec<Event>,
+        payload: Payload,
+    ) -> http::Request<Vec<u8>> {
+        let spans = events
+            .into_iter()
+            .flat_map(|event| sink.encode_event(event))
+            .map(|span| span.into_parts())
+            .filter(|(_, other)| *other == payload)
+            .map(|(span, _)| span)
+            .collect();
+
+        let request = sink.build_request(PartitionInnerBuffer::new(spans, payload));
+        assert_eq!(request.headers()["Content-Type"], "application/msgpack");
+        assert_eq!(request.headers()["DD-API-KEY"], "abc123");
+        request
+    }
+
+    fn decode(request: &http::Request<Vec<u8>>) -> serde_json::Value {
+        rmp_serde::from_slice(request.body()).unwrap()
+    }
+
+    #[test]
+    fn datadog_traces_parses_ids() {
+        assert_eq!(parse_id("42"), Some(42));
+        assert_eq!(parse_id("18446744073709551615"), Some(u64::max_value()));
+        assert_eq!(parse_id("eee19b7ec3c1b174"), Some(0xeee1_9b7e_c3c1_b174));
+        assert_eq!(
+            parse_id("5b8efff798038103d269b633813fc60c"),
+            Some(0xd269_b633_813f_c60c)
+        );
+        assert_eq!(parse_id(""), None);
+        assert_eq!(parse_id("not-an-id"), None);
+        assert_eq!(parse_id("5b8efff798038103d269b633813fc60c00"), None);
+    }
+
+    #[test]
+    fn datadog_traces_encodes_traces_as_msgpack() {
+        let mut root = span("5b8efff798038103d269b633813fc60c", "1", None, "web");
+        let log = root.as_mut_trace().as_mut_log();
+        log.insert("resource", "GET /checkout");
+        log.insert("type", "web");
+        log.insert("error", true);
+        log.insert("meta.http.method", "GET");
+        log.insert("metrics.retries", 2);
+        log.insert("region", "eu-west-1");
+        root.as_mut_trace().set_sampling_priority(2);
+        let child = span("5b8efff798038103d269b633813fc60c", "2", Some("1"), "db");
+        let other = span("7", "3", None, "web");
+
+        let request = send(&sink(), vec![root, other, child], Payload::Traces);
+        assert_eq!(
+            request.uri(),
+            "https://trace.agent.datadoghq.eu/api/v0.2/traces"
+        );
+
+        let expected = |span_id: u64, parent_id: u64, service: &str, trace_id: u64| {
+            json!({
+                "service": service,
+                "name": "http.request",
+                "resource": "http.request",
+                "trace_id": trace_id,
+                "span_id": span_id,
+                "parent_id": parent_id,
+                "start": 1_500_000_000_000_000_000i64,
+                "duration": 2_000_000,
+                "error": 0,
+                "meta": {"env": "prod"},
+                "metrics": {},
+                "type": "",
+            })
+        };
+        let trace_id = 0xd269_b633_813f_c60c_u64;
+        assert_eq!(
+            decode(&request),
+            json!([
+                [
+                    {
+                        "service": "web",
+                        "name": "http.request",
+                        "resource": "GET /checkout",
+                        "trace_id": trace_id,
+                        "span_id": 1,
+                        "parent_id": 0,
+                        "start": 1_500_000_000_000_000_000i64,
+                        "duration": 2_000_000,
+                        "error": 1,
+                        "meta": {"env": "prod", "http.method": "GET", "region": "eu-west-1"},
+                        "metrics": {"retries": 2.0, "_sampling_priority_v1": 2.0},
+                        "type": "web",
+                    },
+                    expected(2, 1, "db", trace_id),
+                ],
+                [expected(3, 0, "web", 7)],
+            ])
+        );
+    }
+
+    #[test]
+    fn datadog_traces_drops_invalid_spans() {
+        let mut unstarted = span("1", "2", None, "web");
+        unstarted
+            .as_mut_trace()
+            .as_mut_log()
+            .remove(&"start".into());
+
+        assert!(sink()
+            .encode_event(span("1", "x-2", None, "web"))
+            .is_empty());
+        assert!(sink()
+            .encode_event(span("1", "2", Some("?"), "web"))
+            .is_empty());
+        assert!(sink().encode_event(unstarted).is_empty());
+        assert_eq!(sink().encode_event(span("1", "2", None, "web")).len(), 2);
+    }
+
+    #[test]
+    fn datadog_traces_computes_stats() {
+        let mut failed = span("1", "4", Some("1"), "db");
+        let log = failed.as_mut_trace().as_mut_log();
+        log.insert("error", 1);
+        log.insert("duration", 3_000_000i64);
+        let mut measured = span("1", "5", Some("1"), "web");
+        measured
+            .as_mut_trace()
+            .as_mut_log()
+            .insert("metrics._dd.measured", 1);
+        let mut late = span("2", "6", None, "web");
+        late.as_mut_trace()
+            .as_mut_log()
+            .insert("start", 1_500_000_010_000_000_000i64);
+
+        let events = vec![
+            span("1", "1", None, "web"),
+            // Not top-level, as its parent belongs to the same service.
+            span("1", "2", Some("1"), "web"),
+            span("1", "3", Some("1"), "db"),
+            failed,
+            measured,
+            late,
+        ];
+        let request = send(&sink(), events, Payload::Stats);
+        assert_eq!(
+            request.uri(),
+            "https://trace.agent.datadoghq.eu/api/v0.2/stats"
+        );
+
+        let group = |service: &str, hits, errors, duration, top_level_hits| {
+            json!({
+                "Service": service,
+                "Name": "http.request",
+                "Resource": "http.request",
+                "HTTPStatusCode": 0,
+                "Type": "",
+                "Hits": hits,
+                "Errors": errors,
+                "Duration": duration,
+                "TopLevelHits": top_level_hits,
+            })
+        };
+        assert_eq!(
+            decode(&request),
+            json!({
+                "AgentHostname": "web-1",
+                "AgentEnv": "prod",
+                "Stats": [{
+                    "Hostname": "web-1",
+                    "Env": "prod",
+                    "Version": crate::get_version(),
+                    "Stats": [
+                        {
+                            "Start": 1_500_000_000_000_000_000u64,
+                            "Duration": BUCKET_DURATION_NANOS,
+                            "Stats": [
+                                group("db", 2, 1, 5_000_000, 2),
+                                group("web", 2, 0, 4_000_000, 1),
+                            ],
+                        },
+                        {
+                            "Start": 1_500_000_010_000_000_000u64,
+                            "Duration": BUCKET_DURATION_NANOS,
+                            "Stats": [group("web", 1, 0, 2_000_000, 1)],
+                        },
+                    ],
+                }],
+            })
+        );
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
         encoding::{EncodingConfigWithDefault, EncodingConfiguration},
         http::{BatchedHttpSink, HttpClient, HttpSink},
         retries::{RetryAction, RetryLogic},
-        BatchBytesConfig, Buffer, Compression, TowerRequestConfig,
+        BatchConfig, BatchSettings, Buffer, Compression, ProxyConfig, TowerRequestConfig,
     },
     template::Template,
     tls::{TlsOptions, TlsSettings},
@@ -44,7 +44,7 @@ pub struct ElasticSearchConfig {
     )]
     pub encoding: EncodingConfigWithDefault<Encoding>,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub auth: Option<ElasticSearchAuth>,
@@ -53,6 +53,8 @@ pub struct ElasticSearchConfig {
     pub query: Option<HashMap<String, String>>,
 
     pub tls: Option<TlsOptions>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 lazy_static! {
@@ -96,9 +98,14 @@ impl SinkConfig for ElasticSearchConfig {
         let common = ElasticSearchCommon::parse_config(&self)?;
         let healthcheck = healthcheck(cx.resolver(), &common)?;
 
-        let batch = self.batch.unwrap_or(bytesize::mib(10u64), 1);
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(1),
+        );
         let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
         let tls_settings = common.tls_settings.clone();
+        let proxy = self.proxy.clone();
 
         let gzip = common.compression == Compression::Gzip;
 
@@ -109,6 +116,7 @@ impl SinkConfig for ElasticSearchConfig {
             request,
             batch,
             tls_settings,
+            &proxy,
             &cx,
         )
         .sink_map_err(|e| error!("Fatal elasticsearch sink error: {}", e));
@@ -123,6 +131,10 @@ impl SinkConfig for ElasticSearchConfig {
     fn sink_type(&self) -> &'static str {
         "elasticsearch"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -384,7 +396,7 @@ fn healthcheck(
     let request = builder.body(Body::empty())?;
 
     Ok(Box::new(
-        HttpClient::new(resolver, common.tls_settings.clone())?
+        HttpClient::with_proxy(resolver, common.tls_settings.clone(), &common.config.proxy)?
             .call(request)
             .map_err(|err| err.into())
             .and_then(|response| match response.status() {
@@ -717,9 +729,10 @@ mod integration_tests {
 
     fn config() -> ElasticSearchConfig {
         ElasticSearchConfig {
-            batch: BatchBytesConfig {
-                max_size: Some(1),
+            batch: BatchConfig {
+                max_bytes: Some(1),
                 timeout_secs: None,
+                ..Default::default()
             },
             ..Default::default()
         }
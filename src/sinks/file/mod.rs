@@ -1,7 +1,10 @@
 use crate::expiring_hash_map::ExpiringHashMap;
 use crate::{
-    event::{self, Event},
+    event::{self, Event, TraceEvent},
+    logfmt,
     sinks::util::{
+        avro::{self, AvroConfig},
+        csv::{self, CsvConfig},
         encoding::{EncodingConfigWithDefault, EncodingConfiguration},
         StreamSink,
     },
@@ -34,6 +37,8 @@ pub struct FileSinkConfig {
         skip_serializing_if = "crate::serde::skip_serializing_if_default"
     )]
     pub encoding: EncodingConfigWithDefault<Encoding>,
+    pub avro: Option<AvroConfig>,
+    pub csv: Option<CsvConfig>,
 }
 
 inventory::submit! {
@@ -45,6 +50,9 @@ inventory::submit! {
 pub enum Encoding {
     Text,
     Ndjson,
+    Avro,
+    Csv,
+    Logfmt,
 }
 
 impl Default for Encoding {
@@ -56,14 +64,24 @@ impl Default for Encoding {
 #[typetag::serde(name = "file")]
 impl SinkConfig for FileSinkConfig {
     fn build(&self, mut cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        avro::validate(self.encoding.codec == Encoding::Avro, &self.avro)?;
+        csv::validate(self.encoding.codec == Encoding::Csv, &self.csv)?;
         let sink = FileSink::new(&self);
         let sink = streaming_sink::compat::adapt_to_topology(&mut cx, sink);
         let sink = StreamSink::new(sink, cx.acker());
-        Ok((Box::new(sink), Box::new(futures01::future::ok(()))))
+        let sink = match &self.avro {
+            Some(avro) => avro.await_schema_id(Box::new(sink), &cx)?,
+            None => Box::new(sink),
+        };
+        Ok((sink, Box::new(futures01::future::ok(()))))
     }
 
     fn input_type(&self) -> DataType {
-        DataType::Log
+        // Metrics can only be written as JSON.
+        match self.encoding.codec {
+            Encoding::Ndjson => DataType::Any,
+            _ => DataType::Log,
+        }
     }
 
     fn sink_type(&self) -> &'static str {
@@ -75,6 +93,8 @@ impl SinkConfig for FileSinkConfig {
 pub struct FileSink {
     path: Template,
     encoding: EncodingConfigWithDefault<Encoding>,
+    avro: Option<AvroConfig>,
+    csv: Option<CsvConfig>,
     idle_timeout: Duration,
     files: ExpiringHashMap<Bytes, File>,
 }
@@ -84,6 +104,8 @@ impl FileSink {
         Self {
             path: config.path.clone(),
             encoding: config.encoding.clone(),
+            avro: config.avro.clone(),
+            csv: config.csv.clone(),
             idle_timeout: Duration::from_secs(config.idle_timeout_secs.unwrap_or(30)),
             files: ExpiringHashMap::new(),
         }
@@ -170,7 +192,7 @@ impl FileSink {
             file
         } else {
             trace!(message = "Opening new file.", ?path);
-            let file = match open_file(BytesPath::new(path.clone())).await {
+            let file = match self.open_file(BytesPath::new(path.clone())).await {
                 Ok(file) => file,
                 Err(error) => {
                     // We coundn't open the file for this event.
@@ -185,10 +207,24 @@ impl FileSink {
         };
 
         trace!(message = "Writing an event to file.", ?path);
-        if let Err(error) = write_event_to_file(file, event, &self.encoding).await {
+        if let Err(error) =
+            write_event_to_file(file, event, &self.encoding, &self.avro, &self.csv).await
+        {
             error!(message = "Failed to write file.", ?path, %error);
         }
     }
+
+    /// Opens the file at `path`, starting it with the CSV header when that's
+    /// configured and the file is empty.
+    async fn open_file(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<File> {
+        let mut file = open_file(path).await?;
+        if let Some(csv) = self.csv.as_ref().filter(|csv| csv.header) {
+            if file.metadata().await?.len() == 0 {
+                file.write_all(&csv.header()).await?;
+            }
+        }
+        Ok(file)
+    }
 }
 
 async fn open_file(path: impl AsRef<std::path::Path>) -> std::io::Result<File> {
@@ -206,26 +242,75 @@ async fn open_file(path: impl AsRef<std::path::Path>) -> std::io::Result<File> {
         .await
 }
 
-pub fn encode_event(encoding: &EncodingConfigWithDefault<Encoding>, mut event: Event) -> Vec<u8> {
+/// Encodes `event` as one line of text, JSON, CSV or logfmt, or as an Avro datum,
+/// which is written without a trailing newline. Metrics are written as JSON,
+/// in the same shape as in the `http` sink.
+pub fn encode_event(
+    encoding: &EncodingConfigWithDefault<Encoding>,
+    avro: &Option<AvroConfig>,
+    csv: &Option<CsvConfig>,
+    mut event: Event,
+) -> Option<Vec<u8>> {
     encoding.apply_rules(&mut event);
-    let log = event.into_log();
-    match encoding.codec {
+    let log = match event {
+        Event::Log(log) | Event::Trace(TraceEvent(log)) => log,
+        Event::Metric(metric) => {
+            if encoding.codec != Encoding::Ndjson {
+                warn!(
+                    message = "Metrics can only be encoded as JSON; dropping event.",
+                    rate_limit_secs = 30,
+                );
+                return None;
+            }
+            let mut buf = serde_json::to_vec(&metric).expect("Unable to encode metric as JSON.");
+            buf.push(b'\n');
+            return Some(buf);
+        }
+    };
+    let mut buf = match encoding.codec {
         Encoding::Ndjson => serde_json::to_vec(&log).expect("Unable to encode event as JSON."),
         Encoding::Text => log
             .get(&event::log_schema().message_key())
             .map(|v| v.to_string_lossy().into_bytes())
             .unwrap_or_default(),
-    }
+        Encoding::Logfmt => logfmt::encode(&log).into_bytes(),
+        Encoding::Avro => {
+            let avro = avro
+                .as_ref()
+                .expect("avro codec is validated to have a schema");
+            return avro
+                .encode(&log)
+                .map_err(|error| {
+                    warn!(
+                        message = "Unable to encode event as Avro; dropping event.",
+                        %error,
+                        rate_limit_secs = 30,
+                    )
+                })
+                .ok();
+        }
+        Encoding::Csv => {
+            let csv = csv
+                .as_ref()
+                .expect("csv codec is validated to have columns");
+            return Some(csv.encode(&log));
+        }
+    };
+    buf.push(b'\n');
+    Some(buf)
 }
 
 async fn write_event_to_file(
     file: &mut File,
     event: Event,
     encoding: &EncodingConfigWithDefault<Encoding>,
+    avro: &Option<AvroConfig>,
+    csv: &Option<CsvConfig>,
 ) -> Result<(), std::io::Error> {
-    let mut buf = encode_event(encoding, event);
-    buf.push(b'\n');
-    file.write_all(&buf[..]).await
+    match encode_event(encoding, avro, csv, event) {
+        Some(buf) => file.write_all(&buf[..]).await,
+        None => Ok(()),
+    }
 }
 
 #[async_trait]
@@ -260,6 +345,8 @@ mod tests {
             path: template.clone().into(),
             idle_timeout_secs: None,
             encoding: Encoding::Text.into(),
+            avro: None,
+            csv: None,
         };
 
         let mut sink = FileSink::new(&config);
@@ -278,6 +365,142 @@ mod tests {
         }
     }
 
+    #[test]
+    fn avro_datums() {
+        test_util::trace_init();
+
+        let template = temp_file();
+
+        let config: FileSinkConfig = toml::from_str(&format!(
+            r#"
+            path = "{}"
+            encoding = "avro"
+            [avro]
+            schema = '{{"type": "record", "name": "Log", "fields": [{{"name": "message", "type": "string"}}]}}'
+            "#,
+            template.display()
+        ))
+        .unwrap();
+
+        let mut sink = FileSink::new(&config);
+        let (input, _) = random_lines_with_stream(100, 16);
+
+        let events = stream::iter(input.clone().into_iter().map(Event::from));
+
+        let mut rt = crate::test_util::runtime();
+        let _ = rt
+            .block_on_std(async move { sink.run(events).await })
+            .unwrap();
+
+        let schema = avro_rs::Schema::parse_str(
+            r#"{"type": "record", "name": "Log", "fields": [{"name": "message", "type": "string"}]}"#,
+        )
+        .unwrap();
+        let contents = std::fs::read(template).unwrap();
+        let mut reader = &contents[..];
+        for line in input {
+            let datum = avro_rs::from_avro_datum(&schema, &mut reader, None).unwrap();
+            assert_eq!(
+                datum,
+                avro_rs::types::Value::Record(vec![(
+                    "message".into(),
+                    avro_rs::types::Value::String(line)
+                )])
+            );
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn csv_header_once_per_file() {
+        test_util::trace_init();
+
+        let directory = temp_dir();
+        let config: FileSinkConfig = toml::from_str(&format!(
+            r#"
+            path = "{}/{{{{ level }}}}.csv"
+            encoding = "csv"
+            [csv]
+            fields = ["level", "message"]
+            header = true
+            "#,
+            directory.display()
+        ))
+        .unwrap();
+
+        let input = vec![("info", "a"), ("warn", "b, c"), ("info", "d")];
+        let events = input.into_iter().map(|(level, message)| {
+            let mut event = Event::from(message);
+            event.as_mut_log().insert("level", level);
+            event
+        });
+
+        let mut sink = FileSink::new(&config);
+        let mut rt = crate::test_util::runtime();
+        let _ = rt
+            .block_on_std(async move { sink.run(stream::iter(events)).await })
+            .unwrap();
+
+        assert_eq!(
+            lines_from_file(&directory.join("info.csv")),
+            vec!["level,message", "info,a", "info,d"]
+        );
+        assert_eq!(
+            lines_from_file(&directory.join("warn.csv")),
+            vec!["level,message", "warn,\"b, c\""]
+        );
+    }
+
+    #[test]
+    fn logfmt_lines() {
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("request.status", 200);
+        event
+            .as_mut_log()
+            .remove(&event::log_schema().timestamp_key());
+
+        let encoding = Encoding::Logfmt.into();
+        let line = encode_event(&encoding, &None, &None, event).unwrap();
+
+        assert_eq!(
+            line,
+            b"message=\"hello world\" request.status=200\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn metric_lines() {
+        use crate::event::metric::{Metric, MetricKind, MetricValue};
+
+        let event = Event::Metric(Metric {
+            name: "latency".into(),
+            timestamp: None,
+            tags: None,
+            kind: MetricKind::Incremental,
+            value: MetricValue::Distribution {
+                values: vec![1.5, 2.0],
+                sample_rates: vec![1, 10],
+            },
+        });
+
+        let encoding = Encoding::Ndjson.into();
+        let line = encode_event(&encoding, &None, &None, event.clone()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&line).unwrap(),
+            serde_json::json!({
+                "name": "latency",
+                "timestamp": null,
+                "tags": null,
+                "kind": "incremental",
+                "distribution": {"values": [1.5, 2.0], "sample_rates": [1, 10]}
+            })
+        );
+        assert_eq!(line.last(), Some(&b'\n'));
+
+        let encoding = Encoding::Text.into();
+        assert_eq!(encode_event(&encoding, &None, &None, event), None);
+    }
+
     #[test]
     fn many_partitions() {
         test_util::trace_init();
@@ -293,6 +516,8 @@ mod tests {
             path: template.clone().into(),
             idle_timeout_secs: None,
             encoding: Encoding::Text.into(),
+            avro: None,
+            csv: None,
         };
 
         let mut sink = FileSink::new(&config);
@@ -4,11 +4,12 @@ use crate::{
     serde::to_string,
     sinks::{
         util::{
+            self,
             encoding::{EncodingConfig, EncodingConfiguration},
             http::{HttpClient, HttpClientFuture},
             retries::{RetryAction, RetryLogic},
-            BatchBytesConfig, Buffer, PartitionBuffer, PartitionInnerBuffer, ServiceBuilderExt,
-            TowerRequestConfig,
+            BatchConfig, BatchSettings, Buffer, PartitionBuffer, PartitionInnerBuffer,
+            ServiceBuilderExt, TowerRequestConfig,
         },
         Healthcheck, RouterSink,
     },
@@ -17,7 +18,6 @@ use crate::{
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
 use bytes::Bytes;
-use chrono::Utc;
 use futures01::{stream::iter_ok, Future, Poll, Sink};
 use http::{Method, StatusCode, Uri};
 use hyper::{
@@ -30,7 +30,6 @@ use snafu::Snafu;
 use std::collections::HashMap;
 use tower::{Service, ServiceBuilder};
 use tracing::field;
-use uuid::Uuid;
 
 const NAME: &str = "gcp_cloud_storage";
 const BASE_URL: &str = "https://storage.googleapis.com/";
@@ -64,9 +63,12 @@ pub struct GcsSinkConfig {
     encoding: EncodingConfig<Encoding>,
     compression: Compression,
     #[serde(default)]
-    batch: BatchBytesConfig,
+    batch: BatchConfig,
     #[serde(default)]
     request: TowerRequestConfig,
+    /// Send the batches of each key prefix in order, one at a time.
+    #[serde(default)]
+    preserve_order: bool,
     #[serde(flatten)]
     auth: GcpAuthConfig,
     tls: Option<TlsOptions>,
@@ -87,6 +89,7 @@ fn default_config(e: Encoding) -> GcsSinkConfig {
         compression: Default::default(),
         batch: Default::default(),
         request: Default::default(),
+        preserve_order: Default::default(),
         auth: Default::default(),
         tls: Default::default(),
     }
@@ -185,6 +188,10 @@ impl SinkConfig for GcsSinkConfig {
     fn sink_type(&self) -> &'static str {
         NAME
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, Snafu)]
@@ -222,7 +229,11 @@ impl GcsSink {
             Compression::Gzip => true,
             Compression::None => false,
         };
-        let batch = config.batch.unwrap_or(bytesize::mib(10u64), 300);
+        let batch = config.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(300),
+        );
 
         let key_prefix = if let Some(kp) = &config.key_prefix {
             Template::from(kp.as_str())
@@ -235,11 +246,14 @@ impl GcsSink {
         let svc = ServiceBuilder::new()
             .map(move |req| RequestWrapper::new(req, settings.clone()))
             .settings(request, GcsRetryLogic)
+            .request_metrics(cx)
             .service(self);
 
         let buffer = PartitionBuffer::new(Buffer::new(compression));
 
         let sink = crate::sinks::util::PartitionBatchSink::new(svc, buffer, batch, cx.acker())
+            .component(cx.name())
+            .preserve_order(config.preserve_order)
             .sink_map_err(|e| error!("Fatal gcs sink error: {}", e))
             .with_flat_map(move |e| iter_ok(encode_event(e, &key_prefix, &encoding)));
 
@@ -325,23 +339,11 @@ impl RequestWrapper {
     fn new(req: PartitionInnerBuffer<Vec<u8>, Bytes>, settings: RequestSettings) -> Self {
         let (body, key) = req.into_parts();
 
-        // TODO: pull the seconds from the last event
-        let filename = {
-            let seconds = Utc::now().format(&settings.time_format);
-
-            if settings.append_uuid {
-                let uuid = Uuid::new_v4();
-                format!("{}-{}", seconds, uuid.to_hyphenated())
-            } else {
-                seconds.to_string()
-            }
-        };
-
-        let key = format!(
-            "{}{}.{}",
-            String::from_utf8_lossy(&key[..]),
-            filename,
-            settings.extension
+        let key = util::object_name(
+            &key[..],
+            &settings.time_format,
+            settings.append_uuid,
+            &settings.extension,
         );
 
         debug!(
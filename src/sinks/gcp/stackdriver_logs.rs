@@ -5,7 +5,7 @@ use crate::{
         util::{
             encoding::{EncodingConfigWithDefault, EncodingConfiguration},
             http::{BatchedHttpSink, HttpClient, HttpSink},
-            BatchBytesConfig, BoxedRawValue, JsonArrayBuffer, TowerRequestConfig,
+            BatchConfig, BatchSettings, BoxedRawValue, JsonArrayBuffer, TowerRequestConfig,
         },
         Healthcheck, RouterSink,
     },
@@ -45,7 +45,7 @@ pub struct StackdriverConfig {
     pub encoding: EncodingConfigWithDefault<Encoding>,
 
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
 
@@ -108,7 +108,11 @@ impl SinkConfig for StackdriverConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(RouterSink, Healthcheck)> {
         let creds = self.auth.make_credentials(Scope::LoggingWrite)?;
 
-        let batch = self.batch.unwrap_or(bytesize::kib(5000u64), 1);
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::kib(5000u64))
+                .timeout(1),
+        );
         let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
         let tls_settings = TlsSettings::from_options(&self.tls)?;
 
@@ -139,6 +143,10 @@ impl SinkConfig for StackdriverConfig {
     fn sink_type(&self) -> &'static str {
         "gcp_stackdriver_logs"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for StackdriverSink {
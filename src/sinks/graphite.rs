@@ -0,0 +1,353 @@
+use crate::{
+    event::metric::{Metric, MetricValue},
+    event::Event,
+    sinks::util::{
+        tcp::{tcp_healthcheck, TcpSink},
+        StreamSink,
+    },
+    template::Template,
+    tls::{MaybeTlsSettings, TlsConfig},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use bytes::Bytes;
+use chrono::Utc;
+use futures01::{stream::iter_ok, Sink};
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("Missing host in address field"))]
+    MissingHost,
+    #[snafu(display("Missing port in address field"))]
+    MissingPort,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GraphiteSinkConfig {
+    pub address: String,
+    /// Prepended to every metric path.
+    pub namespace: Option<String>,
+    /// The metric path, rendered from the metric's `name` and its tags.
+    #[serde(default = "default_path")]
+    pub path: Template,
+    /// Appends every tag as `;tag=value`, for Graphite's tag support.
+    #[serde(default)]
+    pub tagged: bool,
+    pub tls: Option<TlsConfig>,
+}
+
+fn default_path() -> Template {
+    Template::from("{{ name }}")
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<GraphiteSinkConfig>("graphite")
+}
+
+#[typetag::serde(name = "graphite")]
+impl SinkConfig for GraphiteSinkConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let uri = self.address.parse::<http::Uri>()?;
+        let host = uri.host().ok_or(BuildError::MissingHost)?.to_string();
+        let port = uri.port_u16().ok_or(BuildError::MissingPort)?;
+        let tls = MaybeTlsSettings::from_config(&self.tls, false)?;
+
+        let tcp = TcpSink::new(host.clone(), port, cx.resolver(), tls);
+        let healthcheck = tcp_healthcheck(host, port, cx.resolver());
+
+        let encoder = Encoder {
+            namespace: self.namespace.clone(),
+            path: self.path.clone(),
+            tagged: self.tagged,
+        };
+        let sink = StreamSink::new(tcp, cx.acker())
+            .with_flat_map(move |event| iter_ok(encoder.encode(event)));
+
+        Ok((Box::new(sink), healthcheck))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "graphite"
+    }
+}
+
+struct Encoder {
+    namespace: Option<String>,
+    path: Template,
+    tagged: bool,
+}
+
+impl Encoder {
+    /// Renders a metric as Carbon plaintext, `path[;tag=value...] value timestamp`
+    /// lines. Values that aren't a single number, like distributions, are sent as
+    /// several lines, each with a suffix added to the path.
+    fn encode(&self, event: Event) -> Option<Bytes> {
+        let metric = sanitize(event.into_metric());
+
+        let path = match self.path.render_string(&Event::Metric(metric.clone())) {
+            Ok(path) => path,
+            Err(missing_keys) => {
+                warn!(
+                    message = "Keys in path template do not exist on the metric; dropping metric.",
+                    ?missing_keys,
+                    rate_limit_secs = 30,
+                );
+                return None;
+            }
+        };
+        let path = match &self.namespace {
+            Some(namespace) => format!("{}.{}", namespace, path),
+            None => path,
+        };
+        let tags = match &metric.tags {
+            Some(tags) if self.tagged => encode_tags(tags),
+            _ => String::new(),
+        };
+        let timestamp = metric.timestamp.unwrap_or_else(Utc::now).timestamp();
+
+        let mut lines = String::new();
+        for (suffix, value) in values(&metric.value) {
+            // Carbon has no way to represent these, and rejects the whole line.
+            if !value.is_finite() {
+                debug!(message = "Dropping non-finite value.", %path, %value);
+                continue;
+            }
+            let _ = writeln!(lines, "{}{}{} {} {}", path, suffix, tags, value, timestamp);
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.into())
+        }
+    }
+}
+
+/// The values a metric is sent as, each with the suffix added to its path.
+/// `f64`'s `Display` is used to format them, as it never uses an exponent,
+/// and round trips.
+fn values(value: &MetricValue) -> Vec<(String, f64)> {
+    match value {
+        MetricValue::Counter { value } | MetricValue::Gauge { value } => {
+            vec![(String::new(), *value)]
+        }
+        MetricValue::Set { values } => vec![(String::new(), values.len() as f64)],
+        MetricValue::Distribution {
+            values,
+            sample_rates,
+        } => {
+            if values.is_empty() {
+                return vec![];
+            }
+            let count = sample_rates.iter().map(|&rate| f64::from(rate)).sum();
+            let sum = values
+                .iter()
+                .zip(sample_rates)
+                .map(|(value, &rate)| value * f64::from(rate))
+                .sum();
+            let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            vec![
+                (".count".into(), count),
+                (".sum".into(), sum),
+                (".min".into(), min),
+                (".max".into(), max),
+            ]
+        }
+        MetricValue::AggregatedHistogram {
+            buckets,
+            counts,
+            count,
+            sum,
+        } => buckets
+            .iter()
+            .zip(counts)
+            .map(|(bucket, &count)| {
+                let suffix = format!(".bucket.{}", number_segment(*bucket));
+                (suffix, f64::from(count))
+            })
+            .chain(vec![
+                (".count".into(), f64::from(*count)),
+                (".sum".into(), *sum),
+            ])
+            .collect(),
+        MetricValue::AggregatedSummary {
+            quantiles,
+            values,
+            count,
+            sum,
+        } => quantiles
+            .iter()
+            .zip(values)
+            .map(|(quantile, &value)| {
+                let suffix = format!(".quantile.{}", number_segment(*quantile));
+                (suffix, value)
+            })
+            .chain(vec![
+                (".count".into(), f64::from(*count)),
+                (".sum".into(), *sum),
+            ])
+            .collect(),
+    }
+}
+
+/// Formats a bucket bound or quantile as a single path segment, so `0.5` becomes `0_5`.
+fn number_segment(number: f64) -> String {
+    if number == f64::INFINITY {
+        "inf".into()
+    } else {
+        number.to_string().replace('.', "_").replace('-', "minus_")
+    }
+}
+
+fn encode_tags(tags: &BTreeMap<String, String>) -> String {
+    let mut encoded = String::new();
+    for (name, value) in tags {
+        let _ = write!(encoded, ";{}={}", name, value);
+    }
+    encoded
+}
+
+/// Replaces everything that would break the path or the line apart.
+///
+/// Dots separate the nodes of a path, so they're kept in metric names, but
+/// replaced in tags, whose values usually end up as a single node. That also
+/// keeps tags clear of the characters Graphite doesn't allow in its tags.
+fn sanitize(mut metric: Metric) -> Metric {
+    metric.name = replace_chars(&metric.name, |c| !is_path_char(c) && c != '.');
+    metric.tags = metric.tags.map(|tags| {
+        tags.into_iter()
+            .map(|(name, value)| {
+                (
+                    replace_chars(&name, |c| !is_path_char(c)),
+                    replace_chars(&value, |c| !is_path_char(c)),
+                )
+            })
+            .collect()
+    });
+    metric
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':'
+}
+
+fn replace_chars(s: &str, replace: impl Fn(char) -> bool) -> String {
+    s.chars()
+        .map(|c| if replace(c) { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::metric::MetricKind;
+    use chrono::{TimeZone, Utc};
+
+    fn encoder(config: &str) -> Encoder {
+        let config: GraphiteSinkConfig =
+            toml::from_str(&format!("address = \"127.0.0.1:2003\"\n{}", config)).unwrap();
+        Encoder {
+            namespace: config.namespace,
+            path: config.path,
+            tagged: config.tagged,
+        }
+    }
+
+    fn metric(name: &str, tags: &[(&str, &str)], value: MetricValue) -> Event {
+        Event::Metric(Metric {
+            name: name.into(),
+            timestamp: Some(Utc.ymd(2020, 6, 1).and_hms(12, 0, 0)),
+            tags: Some(
+                tags.iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            kind: MetricKind::Absolute,
+            value,
+        })
+    }
+
+    fn encode(encoder: &Encoder, event: Event) -> String {
+        String::from_utf8(encoder.encode(event).unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn graphite_encodes_plaintext_lines() {
+        let encoder = encoder(
+            r#"
+            namespace = "vector"
+            path = "{{ tags.host }}.{{ name }}"
+            "#,
+        );
+        let counter = metric(
+            "http.requests total",
+            &[("host", "web-1.example.com")],
+            MetricValue::Counter { value: 1.5 },
+        );
+
+        assert_eq!(
+            encode(&encoder, counter),
+            "vector.web-1_example_com.http.requests_total 1.5 1591012800\n"
+        );
+
+        let distribution = metric(
+            "latency",
+            &[("host", "db")],
+            MetricValue::Distribution {
+                values: vec![0.25, 2.0],
+                sample_rates: vec![2, 1],
+            },
+        );
+        assert_eq!(
+            encode(&encoder, distribution),
+            "vector.db.latency.count 3 1591012800\n\
+             vector.db.latency.sum 2.5 1591012800\n\
+             vector.db.latency.min 0.25 1591012800\n\
+             vector.db.latency.max 2 1591012800\n"
+        );
+
+        let missing_tag = metric("latency", &[], MetricValue::Gauge { value: 1.0 });
+        assert!(encoder.encode(missing_tag).is_none());
+    }
+
+    #[test]
+    fn graphite_encodes_tags() {
+        let encoder = encoder("tagged = true");
+        let gauge = metric(
+            "memory.used",
+            &[("host", "web 1"), ("region", "~eu;west=1")],
+            MetricValue::Gauge { value: 0.000_000_1 },
+        );
+
+        assert_eq!(
+            encode(&encoder, gauge),
+            "memory.used;host=web_1;region=_eu_west_1 0.0000001 1591012800\n"
+        );
+
+        let summary = metric(
+            "latency",
+            &[("host", "web")],
+            MetricValue::AggregatedSummary {
+                quantiles: vec![0.5, 0.99],
+                values: vec![1.0, f64::NAN],
+                count: 3,
+                sum: 6.0,
+            },
+        );
+        assert_eq!(
+            encode(&encoder, summary),
+            "latency.quantile.0_5;host=web 1 1591012800\n\
+             latency.count;host=web 3 1591012800\n\
+             latency.sum;host=web 6 1591012800\n"
+        );
+    }
+}
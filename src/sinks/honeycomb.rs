@@ -1,8 +1,10 @@
 use crate::{
     dns::Resolver,
-    event::{log_schema, Event, Value},
+    event::{log_schema, sample_rate, Event, Value},
     sinks::util::http::{BatchedHttpSink, HttpClient, HttpSink},
-    sinks::util::{BatchBytesConfig, BoxedRawValue, JsonArrayBuffer, TowerRequestConfig, UriSerde},
+    sinks::util::{
+        BatchConfig, BatchSettings, BoxedRawValue, JsonArrayBuffer, TowerRequestConfig, UriSerde,
+    },
     tls::TlsSettings,
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -11,6 +13,7 @@ use futures01::{Sink, Stream};
 use http::{Request, StatusCode, Uri};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use string_cache::DefaultAtom as Atom;
 
 lazy_static::lazy_static! {
     static ref HOST: UriSerde = Uri::from_static("https://api.honeycomb.io/1/batch").into();
@@ -25,7 +28,7 @@ pub struct HoneycombConfig {
     dataset: String,
 
     #[serde(default)]
-    batch: BatchBytesConfig,
+    batch: BatchConfig,
 
     #[serde(default)]
     request: TowerRequestConfig,
@@ -39,7 +42,11 @@ inventory::submit! {
 impl SinkConfig for HoneycombConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
         let request_settings = self.request.unwrap_with(&TowerRequestConfig::default());
-        let batch_settings = self.batch.unwrap_or(bytesize::mib(5u64), 1);
+        let batch_settings = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(5u64))
+                .timeout(1),
+        );
 
         let sink = BatchedHttpSink::new(
             self.clone(),
@@ -63,6 +70,10 @@ impl SinkConfig for HoneycombConfig {
     fn sink_type(&self) -> &'static str {
         "honeycomb"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for HoneycombConfig {
@@ -79,10 +90,20 @@ impl HttpSink for HoneycombConfig {
             chrono::Utc::now()
         };
 
-        Some(json!({
+        // Honeycomb scales counts by the rate itself, so it is sent alongside
+        // the data rather than in it.
+        let rate = sample_rate::get(&log);
+        log.remove(&Atom::from(sample_rate::SAMPLE_RATE_KEY));
+
+        let mut event = json!({
             "timestamp": timestamp.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
-            "data": log.all_fields(),
-        }))
+            "data": log,
+        });
+        if rate > 1 {
+            event["samplerate"] = rate.into();
+        }
+
+        Some(event)
     }
 
     fn build_request(&self, events: Self::Output) -> http::Request<Vec<u8>> {
@@ -142,3 +163,31 @@ async fn healthcheck(config: HoneycombConfig, resolver: Resolver) -> Result<(),
         .into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HoneycombConfig {
+        toml::from_str(
+            r#"
+            api_key = "secret"
+            dataset = "logs"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn honeycomb_encodes_sample_rate() {
+        let mut event = Event::from("hello");
+        event.as_mut_log().insert("sample_rate", "10");
+
+        let encoded = config().encode_event(event).unwrap();
+        assert_eq!(encoded["samplerate"], 10);
+        assert!(encoded["data"].get("sample_rate").is_none());
+
+        let encoded = config().encode_event(Event::from("hello")).unwrap();
+        assert!(encoded.get("samplerate").is_none());
+    }
+}
@@ -1,14 +1,20 @@
 use crate::{
     dns::Resolver,
-    event::{self, Event},
+    event::{self, Event, Metric, TraceEvent},
+    logfmt,
     sinks::util::{
+        avro::{self, AvroConfig},
+        compression::Compression,
+        csv::{self, CsvConfig},
         encoding::{EncodingConfig, EncodingConfiguration},
-        http::{Auth, BatchedHttpSink, HttpClient, HttpSink},
-        BatchBytesConfig, Buffer, Compression, TowerRequestConfig, UriSerde,
+        http::{Auth, BatchedHttpSink, HttpClient, HttpRetryLogic, HttpSink},
+        protobuf::{self, ProtobufConfig},
+        BatchConfig, BatchSettings, Buffer, ProxyConfig, TowerRequestConfig, UriSerde,
     },
     tls::{TlsOptions, TlsSettings},
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
+use flate2::write::GzEncoder;
 use futures01::{future, Future, Sink};
 use http::{
     header::{self, HeaderName, HeaderValue},
@@ -19,6 +25,7 @@ use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
+use std::io::Write;
 use tower::Service;
 
 #[derive(Debug, Snafu)]
@@ -45,11 +52,16 @@ pub struct HttpSinkConfig {
     pub headers: Option<IndexMap<String, String>>,
     pub compression: Option<Compression>,
     pub encoding: EncodingConfig<Encoding>,
+    pub avro: Option<AvroConfig>,
+    pub csv: Option<CsvConfig>,
+    pub protobuf: Option<ProtobufConfig>,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub tls: Option<TlsOptions>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
 }
 
 #[cfg(test)]
@@ -63,8 +75,12 @@ fn default_config(e: Encoding) -> HttpSinkConfig {
         compression: Default::default(),
         batch: Default::default(),
         encoding: e.into(),
+        avro: Default::default(),
+        csv: Default::default(),
+        protobuf: Default::default(),
         request: Default::default(),
         tls: Default::default(),
+        proxy: Default::default(),
     }
 }
 
@@ -86,12 +102,32 @@ pub enum HttpMethod {
     Put,
 }
 
+/// Compresses the whole request body once it has been encoded and framed,
+/// which is announced to the receiver through `Content-Encoding`.
+fn compress(compression: Compression, body: Vec<u8>) -> Vec<u8> {
+    if let Some(level) = compression.gzip_level() {
+        let mut encoder = GzEncoder::new(Vec::new(), level);
+        encoder
+            .write_all(&body)
+            .and_then(|_| encoder.finish())
+            .expect("Writing to Vec can't fail")
+    } else if let Some(level) = compression.zstd_level() {
+        zstd::stream::encode_all(&body[..], level).expect("Writing to Vec can't fail")
+    } else {
+        body
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum Encoding {
     Text,
     Ndjson,
     Json,
+    Logfmt,
+    Avro,
+    Csv,
+    Protobuf,
 }
 
 inventory::submit! {
@@ -102,34 +138,53 @@ inventory::submit! {
 impl SinkConfig for HttpSinkConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
         validate_headers(&self.headers)?;
+        avro::validate(self.encoding.codec == Encoding::Avro, &self.avro)?;
+        csv::validate(self.encoding.codec == Encoding::Csv, &self.csv)?;
+        protobuf::validate(self.encoding.codec == Encoding::Protobuf, &self.protobuf)?;
         let tls = TlsSettings::from_options(&self.tls)?;
 
         let mut config = self.clone();
+        if let Some(protobuf) = &mut config.protobuf {
+            protobuf.load()?;
+        }
 
         config.uri = build_uri(config.uri.clone()).into();
-        let gzip = match config.compression.unwrap_or(Compression::None) {
-            Compression::None => false,
-            Compression::Gzip => true,
-        };
-        let batch = config.batch.unwrap_or(bytesize::mib(10u64), 1);
+        let batch = config.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(1),
+        );
         let request = config.request.unwrap_with(&REQUEST_DEFAULTS);
+        let proxy = config.proxy.clone();
 
-        let sink = BatchedHttpSink::new(
+        let sink = BatchedHttpSink::with_retry_logic(
             config,
-            Buffer::new(gzip),
+            // The body is compressed as a whole in `build_request`, after
+            // the JSON framing has been added.
+            Buffer::new(false),
+            HttpRetryLogic,
             request,
             batch,
             Some(tls.clone()),
+            &proxy,
             &cx,
         )
         .sink_map_err(|e| error!("Fatal http sink error: {}", e));
 
-        let sink = Box::new(sink);
+        let sink = match &self.avro {
+            Some(avro) => avro.await_schema_id(Box::new(sink), &cx)?,
+            None => Box::new(sink),
+        };
 
         match self.healthcheck_uri.clone() {
             Some(healthcheck_uri) => {
-                let healthcheck =
-                    healthcheck(healthcheck_uri, self.auth.clone(), cx.resolver(), tls)?;
+                let healthcheck = healthcheck(
+                    healthcheck_uri,
+                    self.auth.clone(),
+                    cx.resolver(),
+                    tls,
+                    &self.proxy,
+                )?;
                 Ok((sink, healthcheck))
             }
             None => Ok((sink, Box::new(future::ok(())))),
@@ -137,12 +192,20 @@ impl SinkConfig for HttpSinkConfig {
     }
 
     fn input_type(&self) -> DataType {
-        DataType::Log
+        // Metrics can only be sent as JSON.
+        match self.encoding.codec {
+            Encoding::Json | Encoding::Ndjson => DataType::Any,
+            _ => DataType::Log,
+        }
     }
 
     fn sink_type(&self) -> &'static str {
         "http"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for HttpSinkConfig {
@@ -151,7 +214,10 @@ impl HttpSink for HttpSinkConfig {
 
     fn encode_event(&self, mut event: Event) -> Option<Self::Input> {
         self.encoding.apply_rules(&mut event);
-        let event = event.into_log();
+        let event = match event {
+            Event::Log(log) | Event::Trace(TraceEvent(log)) => log,
+            Event::Metric(metric) => return self.encode_metric(&metric),
+        };
 
         let body = match &self.encoding.codec {
             Encoding::Text => {
@@ -183,6 +249,61 @@ impl HttpSink for HttpSinkConfig {
                 b.push(b',');
                 b
             }
+
+            Encoding::Logfmt => {
+                let mut b = logfmt::encode(&event).into_bytes();
+                b.push(b'\n');
+                b
+            }
+
+            Encoding::Avro => {
+                let avro = self
+                    .avro
+                    .as_ref()
+                    .expect("avro codec is validated to have a schema");
+                match avro.encode(&event) {
+                    Ok(b) => b,
+                    Err(error) => {
+                        warn!(
+                            message = "Unable to encode event as Avro; dropping event.",
+                            %error,
+                            rate_limit_secs = 30,
+                        );
+                        return None;
+                    }
+                }
+            }
+
+            Encoding::Csv => self
+                .csv
+                .as_ref()
+                .expect("csv codec is validated to have columns")
+                .encode(&event),
+
+            Encoding::Protobuf => {
+                let protobuf = self
+                    .protobuf
+                    .as_ref()
+                    .expect("protobuf codec is validated to have a descriptor set");
+                match protobuf.encode(event) {
+                    // Messages aren't self-delimiting, so each one is
+                    // prefixed with its length.
+                    Ok(message) => {
+                        let mut b = Vec::with_capacity(message.len() + 5);
+                        prost::encoding::encode_varint(message.len() as u64, &mut b);
+                        b.extend_from_slice(&message);
+                        b
+                    }
+                    Err(error) => {
+                        warn!(
+                            message = "Unable to encode event as protobuf; dropping event.",
+                            %error,
+                            rate_limit_secs = 30,
+                        );
+                        return None;
+                    }
+                }
+            }
         };
 
         Some(body)
@@ -210,10 +331,20 @@ impl HttpSink for HttpSinkConfig {
                 body.push(b']');
                 builder.header("Content-Type", "application/json")
             }
+            Encoding::Logfmt => builder.header("Content-Type", "text/plain"),
+            Encoding::Avro => builder.header("Content-Type", "avro/binary"),
+            Encoding::Csv => {
+                if let Some(csv) = self.csv.as_ref().filter(|csv| csv.header) {
+                    body = [csv.header(), body].concat();
+                }
+                builder.header("Content-Type", "text/csv")
+            }
+            Encoding::Protobuf => builder.header("Content-Type", "application/x-protobuf"),
         };
 
-        if let Some(Compression::Gzip) = &self.compression {
-            builder.header("Content-Encoding", "gzip");
+        let compression = self.compression.unwrap_or_default();
+        if let Some(encoding) = compression.content_encoding() {
+            builder.header("Content-Encoding", encoding);
         }
 
         if let Some(headers) = &self.headers {
@@ -222,7 +353,7 @@ impl HttpSink for HttpSinkConfig {
             }
         }
 
-        let mut request = builder.body(body).unwrap();
+        let mut request = builder.body(compress(compression, body)).unwrap();
 
         if let Some(auth) = &self.auth {
             auth.apply(&mut request);
@@ -237,6 +368,7 @@ fn healthcheck(
     auth: Option<Auth>,
     resolver: Resolver,
     tls_settings: TlsSettings,
+    proxy: &ProxyConfig,
 ) -> crate::Result<super::Healthcheck> {
     let uri = build_uri(uri);
     let mut request = Request::head(&uri).body(Body::empty()).unwrap();
@@ -245,7 +377,7 @@ fn healthcheck(
         auth.apply(&mut request);
     }
 
-    let mut client = HttpClient::new(resolver, tls_settings)?;
+    let mut client = HttpClient::with_proxy(resolver, tls_settings, proxy)?;
 
     let healthcheck = client
         .call(request)
@@ -262,6 +394,31 @@ fn healthcheck(
     Ok(Box::new(healthcheck))
 }
 
+impl HttpSinkConfig {
+    /// Metrics are sent as JSON objects holding their `name`, `kind`,
+    /// `timestamp` and `tags`, along with their value under its type, like
+    /// `"counter": {"value": 1.0}`, or
+    /// `"aggregated_histogram": {"buckets": [...], "counts": [...], "count": 3, "sum": 4.5}`.
+    fn encode_metric(&self, metric: &Metric) -> Option<Vec<u8>> {
+        let separator = match self.encoding.codec {
+            Encoding::Json => b',',
+            Encoding::Ndjson => b'\n',
+            _ => {
+                warn!(
+                    message = "Metrics can only be encoded as JSON; dropping event.",
+                    rate_limit_secs = 30,
+                );
+                return None;
+            }
+        };
+        let mut b = serde_json::to_vec(metric)
+            .map_err(|e| panic!("Unable to encode into JSON: {}", e))
+            .ok()?;
+        b.push(separator);
+        Some(b)
+    }
+}
+
 fn validate_headers(headers: &Option<IndexMap<String, String>>) -> crate::Result<()> {
     if let Some(map) = headers {
         for (name, value) in map {
@@ -294,7 +451,10 @@ mod tests {
         assert_downcast_matches,
         runtime::Runtime,
         sinks::http::HttpSinkConfig,
-        sinks::util::http::HttpSink,
+        sinks::util::{
+            dead_letter::{self, DeadLetter},
+            http::HttpSink,
+        },
         test_util::{next_addr, random_lines_with_stream, shutdown_on_idle},
         topology::config::SinkContext,
     };
@@ -304,7 +464,7 @@ mod tests {
     use hyper::service::service_fn_ok;
     use hyper::{Body, Request, Response, Server};
     use serde::Deserialize;
-    use std::io::{BufRead, BufReader};
+    use std::io::{BufRead, BufReader, Read};
 
     #[test]
     fn http_encode_event_text() {
@@ -339,6 +499,191 @@ mod tests {
         assert_eq!(output.message, "hello world".to_string());
     }
 
+    #[test]
+    fn http_encode_metrics_json() {
+        use crate::event::metric::{MetricKind, MetricValue};
+        use chrono::{offset::TimeZone, Utc};
+
+        let timestamp = Utc.ymd(2020, 5, 1).and_hms(12, 0, 0);
+        let metric = |name: &str, kind, value| {
+            Event::Metric(Metric {
+                name: name.into(),
+                timestamp: Some(timestamp),
+                tags: Some(
+                    vec![("host".to_owned(), "a".to_owned())]
+                        .into_iter()
+                        .collect(),
+                ),
+                kind,
+                value,
+            })
+        };
+        let events = vec![
+            metric(
+                "requests",
+                MetricKind::Incremental,
+                MetricValue::Counter { value: 1.0 },
+            ),
+            metric(
+                "memory",
+                MetricKind::Absolute,
+                MetricValue::Gauge { value: 512.5 },
+            ),
+            metric(
+                "latency",
+                MetricKind::Absolute,
+                MetricValue::AggregatedHistogram {
+                    buckets: vec![0.1, 1.0],
+                    counts: vec![2, 1],
+                    count: 3,
+                    sum: 1.2,
+                },
+            ),
+        ];
+
+        let config = default_config(Encoding::Json);
+        assert_eq!(config.input_type(), DataType::Any);
+        assert_eq!(default_config(Encoding::Text).input_type(), DataType::Log);
+
+        let body = events
+            .into_iter()
+            .flat_map(|event| config.encode_event(event).unwrap())
+            .collect::<Vec<u8>>();
+        let request = config.build_request(body);
+        let output = serde_json::from_slice::<serde_json::Value>(request.body()).unwrap();
+
+        assert_eq!(
+            output,
+            serde_json::json!([
+                {
+                    "name": "requests",
+                    "timestamp": "2020-05-01T12:00:00Z",
+                    "tags": {"host": "a"},
+                    "kind": "incremental",
+                    "counter": {"value": 1.0}
+                },
+                {
+                    "name": "memory",
+                    "timestamp": "2020-05-01T12:00:00Z",
+                    "tags": {"host": "a"},
+                    "kind": "absolute",
+                    "gauge": {"value": 512.5}
+                },
+                {
+                    "name": "latency",
+                    "timestamp": "2020-05-01T12:00:00Z",
+                    "tags": {"host": "a"},
+                    "kind": "absolute",
+                    "aggregated_histogram": {
+                        "buckets": [0.1, 1.0],
+                        "counts": [2, 1],
+                        "count": 3,
+                        "sum": 1.2
+                    }
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn http_encode_event_logfmt() {
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("level", "info");
+        event
+            .as_mut_log()
+            .remove(&event::log_schema().timestamp_key());
+
+        let config = default_config(Encoding::Logfmt);
+        let bytes = config.encode_event(event).unwrap();
+
+        assert_eq!(bytes, b"level=info message=\"hello world\"\n".to_vec());
+    }
+
+    #[test]
+    fn http_encode_event_avro() {
+        let config = r#"
+        uri = "http://$IN_ADDR/frames"
+        encoding = "avro"
+        [avro]
+        schema = '{"type": "record", "name": "Log", "fields": [{"name": "message", "type": "string"}]}'
+        "#;
+        let config: HttpSinkConfig = toml::from_str(&config).unwrap();
+
+        let mut body = config.encode_event(Event::from("hi")).unwrap();
+        body.extend(config.encode_event(Event::from("there")).unwrap());
+        assert_eq!(body, b"\x04hi\x0athere".to_vec());
+
+        let request = config.build_request(body);
+        assert_eq!(request.headers()["Content-Type"], "avro/binary");
+    }
+
+    #[test]
+    fn http_encode_event_csv() {
+        let config = r#"
+        uri = "http://$IN_ADDR/frames"
+        encoding = "csv"
+        [csv]
+        fields = ["message", "status"]
+        header = true
+        "#;
+        let config: HttpSinkConfig = toml::from_str(&config).unwrap();
+
+        let mut body = config.encode_event(Event::from("hi")).unwrap();
+        body.extend(config.encode_event(Event::from("a \"b\"")).unwrap());
+        assert_eq!(body, b"hi,\n\"a \"\"b\"\"\",\n".to_vec());
+
+        // The header starts each batch.
+        let request = config.build_request(body);
+        assert_eq!(request.headers()["Content-Type"], "text/csv");
+        assert_eq!(
+            request.body(),
+            &b"message,status\nhi,\n\"a \"\"b\"\"\",\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn http_encode_event_protobuf() {
+        let config = r#"
+        uri = "http://$IN_ADDR/frames"
+        encoding = "protobuf"
+        [protobuf]
+        descriptor_set_file = "tests/data/protobuf/log.desc"
+        message_type = "test.Log"
+        "#;
+        let mut config: HttpSinkConfig = toml::from_str(&config).unwrap();
+        config.protobuf.as_mut().unwrap().load().unwrap();
+
+        let mut body = config.encode_event(Event::from("hi")).unwrap();
+        body.extend(config.encode_event(Event::from("there")).unwrap());
+        let mut messages = Vec::new();
+        let mut rest = &body[..];
+        while !rest.is_empty() {
+            // Both messages are short enough for single byte lengths.
+            let length = rest[0] as usize;
+            messages.push(&rest[1..=length]);
+            rest = &rest[length + 1..];
+        }
+        // Each event has its message and timestamp fields encoded.
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with(b"\x0a\x02hi\x12"));
+        assert!(messages[1].starts_with(b"\x0a\x05there\x12"));
+
+        let request = config.build_request(body);
+        assert_eq!(request.headers()["Content-Type"], "application/x-protobuf");
+    }
+
+    #[test]
+    fn http_requires_avro_schema() {
+        let config = r#"
+        uri = "http://$IN_ADDR/frames"
+        encoding = "avro"
+        "#;
+        let config: HttpSinkConfig = toml::from_str(&config).unwrap();
+        let rt = Runtime::new().unwrap();
+
+        assert!(config.build(SinkContext::new_test(rt.executor())).is_err());
+    }
+
     #[test]
     fn http_validates_normal_headers() {
         let config = r#"
@@ -559,6 +904,139 @@ mod tests {
         assert_eq!(input_lines, output_lines);
     }
 
+    #[test]
+    fn http_compression_levels() {
+        let body = (0..10_000)
+            .map(|i| {
+                format!(
+                    "{{\"message\":\"line {} of the body\",\"i\":{}}}\n",
+                    i,
+                    i * 7
+                )
+            })
+            .collect::<String>()
+            .into_bytes();
+        let size = |compression: &str| compress(compression.parse().unwrap(), body.clone()).len();
+
+        assert!(size("gzip(0)") > size("gzip(1)"));
+        assert!(size("gzip(1)") > size("gzip(9)"));
+        assert_eq!(size("gzip"), size("gzip(6)"));
+        assert!(size("zstd(1)") > size("zstd(19)"));
+        assert_eq!(size("zstd"), size("zstd(3)"));
+
+        for compression in &["gzip(10)", "zstd(22)"] {
+            let config = format!(
+                r#"
+                uri = "http://localhost:9000/frames"
+                compression = "{}"
+                encoding = "json"
+                "#,
+                compression
+            );
+            assert!(toml::from_str::<HttpSinkConfig>(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn http_compresses_after_framing() {
+        for compression in &["none", "gzip", "gzip(9)", "zstd", "zstd(19)"] {
+            let num_lines = 100;
+            let in_addr = next_addr();
+
+            let config = r#"
+            uri = "http://$IN_ADDR/frames"
+            compression = "$COMPRESSION"
+            encoding = "json"
+        "#
+            .replace("$IN_ADDR", &format!("{}", in_addr))
+            .replace("$COMPRESSION", compression);
+            let config: HttpSinkConfig = toml::from_str(&config).unwrap();
+
+            let mut rt = Runtime::new().unwrap();
+            let cx = SinkContext::new_test(rt.executor());
+
+            let (sink, _) = config.build(cx).unwrap();
+            let (rx, trigger, server) = build_test_server(&in_addr);
+
+            let (input_lines, events) = random_lines_with_stream(100, num_lines);
+            let pump = sink.send_all(events);
+
+            rt.spawn(server);
+
+            let _ = rt.block_on(pump).unwrap();
+            drop(trigger);
+
+            let output_lines = rx
+                .wait()
+                .map(Result::unwrap)
+                .flat_map(|(parts, body)| {
+                    let content_length = parts.headers.get("Content-Length").unwrap();
+                    assert_eq!(content_length.to_str().unwrap(), body.len().to_string());
+
+                    let content_encoding = parts
+                        .headers
+                        .get("Content-Encoding")
+                        .map(|v| v.to_str().unwrap().to_owned());
+                    let body = match content_encoding.as_ref().map(String::as_str) {
+                        None => body.to_vec(),
+                        Some("gzip") => {
+                            let mut decoded = Vec::new();
+                            flate2::read::GzDecoder::new(body.reader())
+                                .read_to_end(&mut decoded)
+                                .unwrap();
+                            decoded
+                        }
+                        Some("zstd") => zstd::stream::decode_all(body.reader()).unwrap(),
+                        Some(other) => panic!("unexpected Content-Encoding {:?}", other),
+                    };
+                    assert!(compression.starts_with(
+                        content_encoding
+                            .as_ref()
+                            .map(String::as_str)
+                            .unwrap_or("none")
+                    ));
+
+                    serde_json::from_slice::<Vec<serde_json::Value>>(&body).unwrap()
+                })
+                .map(|val| val.get("message").unwrap().as_str().unwrap().to_owned())
+                .collect::<Vec<_>>();
+
+            shutdown_on_idle(rt);
+
+            assert_eq!(input_lines, output_lines);
+        }
+    }
+
+    #[test]
+    fn http_dead_letters_unencodable_events() {
+        let config = r#"
+        uri = "http://127.0.0.1:9999/frames"
+        encoding = "text"
+    "#;
+        let config: HttpSinkConfig = toml::from_str(&config).unwrap();
+
+        let mut rt = Runtime::new().unwrap();
+        let (tx, rx) = mpsc::channel(10);
+        let cx = SinkContext::new_test(rt.executor()).with_dead_letter(DeadLetter::new("out", tx));
+        let (sink, _) = config.build(cx).unwrap();
+
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("not_message", "hello");
+        let sink = rt.block_on(sink.send(event)).unwrap();
+        drop(sink);
+
+        let dead_letters = rx.collect().wait().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        let log = dead_letters[0].as_log();
+        assert_eq!(log[&"not_message".into()], "hello".into());
+        assert_eq!(
+            log[&dead_letter::REASON_FIELD.into()],
+            "Event could not be encoded.".into()
+        );
+
+        shutdown_on_idle(rt);
+    }
+
     fn build_test_server(
         addr: &std::net::SocketAddr,
     ) -> (
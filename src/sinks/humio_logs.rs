@@ -1,6 +1,6 @@
 use crate::{
     sinks::splunk_hec::{self, HecSinkConfig},
-    sinks::util::{encoding::EncodingConfigWithDefault, BatchBytesConfig, TowerRequestConfig},
+    sinks::util::{encoding::EncodingConfigWithDefault, BatchConfig, TowerRequestConfig},
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
 use serde::{Deserialize, Serialize};
@@ -21,7 +21,7 @@ pub struct HumioLogsConfig {
     request: TowerRequestConfig,
 
     #[serde(default)]
-    batch: BatchBytesConfig,
+    batch: BatchConfig,
 }
 
 inventory::submit! {
@@ -73,6 +73,10 @@ impl SinkConfig for HumioLogsConfig {
     fn sink_type(&self) -> &'static str {
         "humio_logs"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
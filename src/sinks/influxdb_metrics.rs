@@ -6,7 +6,7 @@ use crate::{
             Error as HttpError, HttpBatchService, HttpClient, HttpRetryLogic,
             Response as HttpResponse,
         },
-        BatchEventsConfig, MetricBuffer, TowerRequestConfig,
+        BatchConfig, BatchSettings, MetricBuffer, TowerRequestConfig,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -61,7 +61,7 @@ pub struct InfluxDBConfig {
     #[serde(flatten)]
     pub influxdb2_settings: Option<InfluxDB2Settings>,
     #[serde(default)]
-    pub batch: BatchEventsConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
 }
@@ -167,6 +167,10 @@ impl SinkConfig for InfluxDBConfig {
     fn sink_type(&self) -> &'static str {
         "influxdb_metrics"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl InfluxDBSvc {
@@ -176,7 +180,9 @@ impl InfluxDBSvc {
         let endpoint = config.endpoint.clone();
         let token = settings.token();
 
-        let batch = config.batch.unwrap_or(20, 1);
+        let batch = config
+            .batch
+            .unwrap_events_only(BatchSettings::default().events(20).timeout(1))?;
         let request = config.request.unwrap_with(&REQUEST_DEFAULTS);
 
         let uri = settings.write_uri(endpoint)?;
@@ -204,7 +210,7 @@ impl InfluxDBSvc {
                 influxdb_http_service,
                 MetricBuffer::new(),
                 batch,
-                cx.acker(),
+                &cx,
             )
             .sink_map_err(|e| error!("Fatal influxdb sink error: {}", e));
 
@@ -1,9 +1,13 @@
 use crate::{
     buffers::Acker,
     event::{self, Event},
-    kafka::{KafkaCompression, KafkaTlsConfig},
+    kafka::{self, KafkaCompression, KafkaSaslConfig, KafkaTlsConfig},
     serde::to_string,
-    sinks::util::encoding::{EncodingConfig, EncodingConfigWithDefault, EncodingConfiguration},
+    sinks::util::{
+        avro::{self, AvroConfig},
+        encoding::{EncodingConfig, EncodingConfigWithDefault, EncodingConfiguration},
+        protobuf::{self, ProtobufConfig},
+    },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
 use futures::compat::Compat;
@@ -34,7 +38,10 @@ pub struct KafkaSinkConfig {
     topic: String,
     key_field: Option<Atom>,
     encoding: EncodingConfigWithDefault<Encoding>,
+    avro: Option<AvroConfig>,
+    protobuf: Option<ProtobufConfig>,
     compression: Option<KafkaCompression>,
+    sasl: Option<KafkaSaslConfig>,
     tls: Option<KafkaTlsConfig>,
     #[serde(default = "default_socket_timeout_ms")]
     socket_timeout_ms: u64,
@@ -58,6 +65,8 @@ pub enum Encoding {
     #[derivative(Default)]
     Text,
     Json,
+    Avro,
+    Protobuf,
 }
 
 pub struct KafkaSink {
@@ -65,6 +74,8 @@ pub struct KafkaSink {
     topic: String,
     key_field: Option<Atom>,
     encoding: EncodingConfig<Encoding>,
+    avro: Option<AvroConfig>,
+    protobuf: Option<ProtobufConfig>,
     in_flight: FuturesUnordered<MetadataFuture<Compat<DeliveryFuture>, usize>>,
 
     acker: Acker,
@@ -80,9 +91,19 @@ inventory::submit! {
 #[typetag::serde(name = "kafka")]
 impl SinkConfig for KafkaSinkConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
-        let sink = KafkaSink::new(self.clone(), cx.acker())?;
+        avro::validate(self.encoding.codec == Encoding::Avro, &self.avro)?;
+        protobuf::validate(self.encoding.codec == Encoding::Protobuf, &self.protobuf)?;
+        let mut config = self.clone();
+        if let Some(protobuf) = &mut config.protobuf {
+            protobuf.load()?;
+        }
+        let sink = KafkaSink::new(config, cx.acker())?;
+        let sink = match &self.avro {
+            Some(avro) => avro.await_schema_id(Box::new(sink), &cx)?,
+            None => Box::new(sink),
+        };
         let hc = healthcheck(self.clone());
-        Ok((Box::new(sink), hc))
+        Ok((sink, hc))
     }
 
     fn input_type(&self) -> DataType {
@@ -92,26 +113,28 @@ impl SinkConfig for KafkaSinkConfig {
     fn sink_type(&self) -> &'static str {
         "kafka"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl KafkaSinkConfig {
     fn to_rdkafka(&self) -> crate::Result<rdkafka::ClientConfig> {
         let mut client_config = rdkafka::ClientConfig::new();
         client_config.set("bootstrap.servers", &self.bootstrap_servers);
-        if let Some(tls) = &self.tls {
-            tls.apply(&mut client_config)?;
-        }
         client_config.set(
             "compression.codec",
             &to_string(self.compression.unwrap_or_default()),
         );
         client_config.set("socket.timeout.ms", &self.socket_timeout_ms.to_string());
         client_config.set("message.timeout.ms", &self.message_timeout_ms.to_string());
-        if let Some(ref librdkafka_options) = self.librdkafka_options {
-            for (key, value) in librdkafka_options.iter() {
-                client_config.set(key.as_str(), value.as_str());
-            }
-        }
+        kafka::apply_client_options(
+            &mut client_config,
+            self.sasl.as_ref(),
+            self.tls.as_ref(),
+            self.librdkafka_options.as_ref(),
+        )?;
         Ok(client_config)
     }
 }
@@ -124,6 +147,8 @@ impl KafkaSink {
             topic: config.topic,
             key_field: config.key_field,
             encoding: config.encoding.into(),
+            avro: config.avro,
+            protobuf: config.protobuf,
             in_flight: FuturesUnordered::new(),
             acker,
             seq_head: 0,
@@ -140,7 +165,23 @@ impl Sink for KafkaSink {
     fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
         let topic = self.topic.clone();
 
-        let (key, body) = encode_event(item.clone(), &self.key_field, &self.encoding);
+        let (key, body) = match encode_event(
+            item.clone(),
+            &self.key_field,
+            &self.encoding,
+            &self.avro,
+            &self.protobuf,
+        ) {
+            Some(encoded) => encoded,
+            None => {
+                // Nothing is sent for this event, so it's acked in turn
+                // with the deliveries around it.
+                self.pending_acks.insert(self.seq_head);
+                self.seq_head += 1;
+                self.ack_delivered();
+                return Ok(AsyncSink::Ready);
+            }
+        };
 
         let record = FutureRecord::to(&topic).key(&key).payload(&body[..]);
 
@@ -192,13 +233,7 @@ impl Sink for KafkaSink {
                     };
 
                     self.pending_acks.insert(seqno);
-
-                    let mut num_to_ack = 0;
-                    while self.pending_acks.remove(&self.seq_tail) {
-                        num_to_ack += 1;
-                        self.seq_tail += 1
-                    }
-                    self.acker.ack(num_to_ack);
+                    self.ack_delivered();
                 }
 
                 // request got canceled (according to docs)
@@ -208,6 +243,19 @@ impl Sink for KafkaSink {
     }
 }
 
+impl KafkaSink {
+    /// Acks the events whose sequence numbers up to the first still in
+    /// flight have completed.
+    fn ack_delivered(&mut self) {
+        let mut num_to_ack = 0;
+        while self.pending_acks.remove(&self.seq_tail) {
+            num_to_ack += 1;
+            self.seq_tail += 1
+        }
+        self.acker.ack(num_to_ack);
+    }
+}
+
 fn healthcheck(config: KafkaSinkConfig) -> super::Healthcheck {
     let consumer: BaseConsumer = config.to_rdkafka().unwrap().create().unwrap();
 
@@ -227,7 +275,9 @@ fn encode_event(
     mut event: Event,
     key_field: &Option<Atom>,
     encoding: &EncodingConfig<Encoding>,
-) -> (Vec<u8>, Vec<u8>) {
+    avro: &Option<AvroConfig>,
+    protobuf: &Option<ProtobufConfig>,
+) -> Option<(Vec<u8>, Vec<u8>)> {
     encoding.apply_rules(&mut event);
     let key = key_field
         .as_ref()
@@ -242,9 +292,41 @@ fn encode_event(
             .get(&event::log_schema().message_key())
             .map(|v| v.as_bytes().to_vec())
             .unwrap_or_default(),
+        Encoding::Avro => {
+            let avro = avro
+                .as_ref()
+                .expect("avro codec is validated to have a schema");
+            match avro.encode(event.as_log()) {
+                Ok(body) => body,
+                Err(error) => {
+                    warn!(
+                        message = "Unable to encode event as Avro; dropping event.",
+                        %error,
+                        rate_limit_secs = 30,
+                    );
+                    return None;
+                }
+            }
+        }
+        Encoding::Protobuf => {
+            let protobuf = protobuf
+                .as_ref()
+                .expect("protobuf codec is validated to have a descriptor set");
+            match protobuf.encode(event.into_log()) {
+                Ok(body) => body,
+                Err(error) => {
+                    warn!(
+                        message = "Unable to encode event as protobuf; dropping event.",
+                        %error,
+                        rate_limit_secs = 30,
+                    );
+                    return None;
+                }
+            }
+        }
     };
 
-    (key, body)
+    Some((key, body))
 }
 
 #[cfg(test)]
@@ -261,7 +343,10 @@ mod tests {
             message.clone().into(),
             &None,
             &EncodingConfig::from(Encoding::Text),
-        );
+            &None,
+            &None,
+        )
+        .unwrap();
 
         assert_eq!(&key_bytes[..], key.as_bytes());
         assert_eq!(&bytes[..], message.as_bytes());
@@ -278,7 +363,10 @@ mod tests {
             event,
             &Some("key".into()),
             &EncodingConfig::from(Encoding::Json),
-        );
+            &None,
+            &None,
+        )
+        .unwrap();
 
         let map: BTreeMap<String, String> = serde_json::from_slice(&bytes[..]).unwrap();
 
@@ -287,6 +375,59 @@ mod tests {
         assert_eq!(map["key"], "value".to_string());
         assert_eq!(map["foo"], "bar".to_string());
     }
+
+    #[test]
+    fn kafka_encode_event_avro() {
+        let avro: AvroConfig = toml::from_str(
+            r#"
+            schema = '{"type": "record", "name": "Log", "fields": [{"name": "message", "type": "string"}]}'
+            schema_id = 3
+            "#,
+        )
+        .unwrap();
+        let mut event = Event::from("hello world");
+        event.as_mut_log().insert("key", "value");
+
+        let (key, bytes) = encode_event(
+            event,
+            &Some("key".into()),
+            &EncodingConfig::from(Encoding::Avro),
+            &Some(avro),
+            &None,
+        )
+        .unwrap();
+
+        assert_eq!(&key[..], "value".as_bytes());
+        // Magic byte, schema id 3, then the string's zigzag length and bytes.
+        assert_eq!(&bytes[..6], &[0, 0, 0, 0, 3, 22]);
+        assert_eq!(&bytes[6..], "hello world".as_bytes());
+    }
+
+    #[test]
+    fn kafka_encode_event_protobuf() {
+        let mut protobuf: ProtobufConfig = toml::from_str(
+            r#"
+            descriptor_set_file = "tests/data/protobuf/log.desc"
+            message_type = "test.Log"
+            "#,
+        )
+        .unwrap();
+        protobuf.load().unwrap();
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("message", "hi");
+        event.as_mut_log().insert("status", 200);
+
+        let (_, bytes) = encode_event(
+            event,
+            &None,
+            &EncodingConfig::from(Encoding::Protobuf),
+            &None,
+            &Some(protobuf),
+        )
+        .unwrap();
+
+        assert_eq!(bytes, b"\x0a\x02hi\x18\xc8\x01".to_vec());
+    }
 }
 
 #[cfg(feature = "kafka-integration-tests")]
@@ -3,8 +3,8 @@ use crate::{
     event::{self, Event},
     sinks::util::http::{Auth, BatchedHttpSink, HttpClient, HttpSink},
     sinks::util::{
-        encoding::EncodingConfigWithDefault, BatchBytesConfig, BoxedRawValue, JsonArrayBuffer,
-        TowerRequestConfig, UriSerde,
+        encoding::EncodingConfigWithDefault, BatchConfig, BatchSettings, BoxedRawValue,
+        JsonArrayBuffer, TowerRequestConfig, UriSerde,
     },
     tls::TlsSettings,
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
@@ -41,7 +41,7 @@ pub struct LogdnaConfig {
     default_app: Option<String>,
 
     #[serde(default)]
-    batch: BatchBytesConfig,
+    batch: BatchConfig,
 
     #[serde(default)]
     request: TowerRequestConfig,
@@ -63,7 +63,11 @@ pub enum Encoding {
 impl SinkConfig for LogdnaConfig {
     fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
         let request_settings = self.request.unwrap_with(&TowerRequestConfig::default());
-        let batch_settings = self.batch.unwrap_or(bytesize::mib(10u64), 1);
+        let batch_settings = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(1),
+        );
 
         let sink = BatchedHttpSink::new(
             self.clone(),
@@ -87,6 +91,10 @@ impl SinkConfig for LogdnaConfig {
     fn sink_type(&self) -> &'static str {
         "logdna"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for LogdnaConfig {
@@ -19,7 +19,7 @@ use crate::{
     sinks::util::http::{Auth, BatchedHttpSink, HttpClient, HttpSink},
     sinks::util::{
         encoding::{EncodingConfig, EncodingConfiguration},
-        BatchBytesConfig, TowerRequestConfig, UriSerde,
+        BatchConfig, BatchSettings, TowerRequestConfig, UriSerde,
     },
     template::Template,
     tls::{TlsOptions, TlsSettings},
@@ -53,7 +53,7 @@ pub struct LokiConfig {
     request: TowerRequestConfig,
 
     #[serde(default)]
-    batch: BatchBytesConfig,
+    batch: BatchConfig,
 
     tls: Option<TlsOptions>,
 }
@@ -79,7 +79,11 @@ impl SinkConfig for LokiConfig {
         }
 
         let request_settings = self.request.unwrap_with(&TowerRequestConfig::default());
-        let batch_settings = self.batch.unwrap_or(bytesize::mib(10u64), 1);
+        let batch_settings = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(10u64))
+                .timeout(1),
+        );
         let tls = TlsSettings::from_options(&self.tls)?;
 
         let sink = BatchedHttpSink::new(
@@ -104,6 +108,10 @@ impl SinkConfig for LokiConfig {
     fn sink_type(&self) -> &'static str {
         "loki"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for LokiConfig {
@@ -143,8 +151,9 @@ impl HttpSink for LokiConfig {
         }
 
         let event = match &self.encoding.codec {
-            Encoding::Json => serde_json::to_string(&event.as_log().all_fields())
-                .expect("json encoding should never fail"),
+            Encoding::Json => {
+                serde_json::to_string(event.as_log()).expect("json encoding should never fail")
+            }
 
             Encoding::Text => event
                 .as_log()
@@ -364,7 +373,7 @@ mod integration_tests {
         let outputs = fetch_stream(stream.to_string());
 
         for (i, output) in outputs.iter().enumerate() {
-            let expected_json = serde_json::to_string(&events[i].as_log().all_fields()).unwrap();
+            let expected_json = serde_json::to_string(events[i].as_log()).unwrap();
             assert_eq!(output, &expected_json);
         }
     }
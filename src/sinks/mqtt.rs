@@ -0,0 +1,292 @@
+use crate::{
+    event::{self, Event},
+    internal_events::{MqttConnectionFailed, MqttEventSent, MqttTopicMissing},
+    mqtt::{MqttConfig, MqttQos},
+    sinks::util::{
+        encoding::{EncodingConfig, EncodingConfiguration},
+        StreamSink,
+    },
+    template::Template,
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use async_trait::async_trait;
+use futures::{pin_mut, stream::Stream, StreamExt};
+use futures01::future;
+use rumqttc::{AsyncClient, ConnectionError, Event as MqttEvent, EventLoop, Incoming, QoS};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::streaming_sink::{self, StreamingSink};
+
+/// Maximum number of publishes waiting in the client's request channel.
+const REQUEST_CAPACITY: usize = 100;
+
+#[derive(Clone, Debug, Derivative, Deserialize, Serialize)]
+#[derivative(Default)]
+pub struct MqttSinkConfig {
+    #[serde(flatten)]
+    connection: MqttConfig,
+    topic: String,
+    encoding: EncodingConfig<Encoding>,
+    #[serde(default)]
+    qos: MqttQos,
+    #[serde(default)]
+    retain: bool,
+    #[derivative(Default(value = "default_flush_timeout_secs()"))]
+    #[serde(default = "default_flush_timeout_secs")]
+    flush_timeout_secs: u64,
+}
+
+fn default_flush_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Clone, Copy, Debug, Derivative, Deserialize, Serialize, Eq, PartialEq)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[derivative(Default)]
+    Text,
+    Json,
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<MqttSinkConfig>("mqtt")
+}
+
+#[typetag::serde(name = "mqtt")]
+impl SinkConfig for MqttSinkConfig {
+    fn build(&self, mut cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let sink = MqttSink {
+            config: self.clone(),
+            topic: Template::from(self.topic.as_str()),
+        };
+        let sink = streaming_sink::compat::adapt_to_topology(&mut cx, sink);
+        let sink = StreamSink::new(sink, cx.acker());
+
+        Ok((Box::new(sink), Box::new(future::ok(()))))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "mqtt"
+    }
+}
+
+struct MqttSink {
+    config: MqttSinkConfig,
+    topic: Template,
+}
+
+#[async_trait]
+impl StreamingSink for MqttSink {
+    async fn run(
+        &mut self,
+        input: impl Stream<Item = Event> + Send + Sync + 'static,
+    ) -> crate::Result<()> {
+        let (client, eventloop) =
+            AsyncClient::new(self.config.connection.options(), REQUEST_CAPACITY);
+        let qos: QoS = self.config.qos.into();
+        let unacked = Arc::new(AtomicUsize::new(0));
+        let reconnect = Duration::from_secs(self.config.connection.reconnect_secs);
+        let driver = tokio::spawn(drive(eventloop, Arc::clone(&unacked), reconnect));
+
+        pin_mut!(input);
+        while let Some(event) = input.next().await {
+            let topic = match self.topic.render_string(&event) {
+                Ok(topic) => topic,
+                Err(missing_keys) => {
+                    emit!(MqttTopicMissing { missing_keys });
+                    continue;
+                }
+            };
+            let payload = encode_event(event, &self.config.encoding);
+            let byte_size = payload.len();
+
+            if qos != QoS::AtMostOnce {
+                unacked.fetch_add(1, Ordering::SeqCst);
+            }
+            client
+                .publish(topic, qos, self.config.retain, payload)
+                .await
+                .map_err(|error| format!("MQTT event loop stopped: {}", error))?;
+            emit!(MqttEventSent { byte_size });
+        }
+
+        // Unacknowledged QoS 1/2 publishes are retransmitted by the event
+        // loop after reconnecting, so give them a chance to be acked before
+        // shutting down.
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_secs(self.config.flush_timeout_secs);
+        while unacked.load(Ordering::SeqCst) > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+        }
+        let remaining = unacked.load(Ordering::SeqCst);
+        if remaining > 0 {
+            warn!(
+                message = "Shutting down with unacknowledged messages.",
+                count = remaining
+            );
+        }
+
+        let _ = client.disconnect().await;
+        let _ = driver.await;
+        Ok(())
+    }
+}
+
+/// Polls the event loop, which sends the publishes queued by the client,
+/// and counts down acknowledged QoS 1 (`PUBACK`) and QoS 2 (`PUBCOMP`)
+/// publishes. Polling again after a connection error reconnects.
+async fn drive(mut eventloop: EventLoop, unacked: Arc<AtomicUsize>, reconnect: Duration) {
+    loop {
+        match eventloop.poll().await {
+            Ok(MqttEvent::Incoming(Incoming::PubAck(_)))
+            | Ok(MqttEvent::Incoming(Incoming::PubComp(_))) => {
+                unacked.fetch_sub(1, Ordering::SeqCst);
+            }
+            Ok(MqttEvent::Incoming(Incoming::Disconnect)) => break,
+            Ok(_) => {}
+            Err(ConnectionError::RequestsDone) => break,
+            Err(error) => {
+                emit!(MqttConnectionFailed { error });
+                tokio::time::delay_for(reconnect).await;
+            }
+        }
+    }
+}
+
+fn encode_event(mut event: Event, encoding: &EncodingConfig<Encoding>) -> Vec<u8> {
+    encoding.apply_rules(&mut event);
+    let log = event.into_log();
+    match encoding.codec {
+        Encoding::Json => serde_json::to_vec(&log).expect("Unable to encode event as JSON."),
+        Encoding::Text => log
+            .get(&event::log_schema().message_key())
+            .map(|v| v.as_bytes().to_vec())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn mqtt_encode_event_text() {
+        let message = "hello world".to_string();
+        let bytes = encode_event(
+            message.clone().into(),
+            &EncodingConfig::from(Encoding::Text),
+        );
+
+        assert_eq!(&bytes[..], message.as_bytes());
+    }
+
+    #[test]
+    fn mqtt_encode_event_json() {
+        let message = "hello world".to_string();
+        let mut event = Event::from(message.clone());
+        event.as_mut_log().insert("key", "value");
+        let bytes = encode_event(event, &EncodingConfig::from(Encoding::Json));
+
+        let map: BTreeMap<String, String> = serde_json::from_slice(&bytes[..]).unwrap();
+
+        assert_eq!(map[&event::log_schema().message_key().to_string()], message);
+        assert_eq!(map["key"], "value".to_string());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: MqttSinkConfig = toml::from_str(
+            r#"
+            topic = "logs/{{ host }}"
+            encoding = "json"
+            qos = "at_most_once"
+            retain = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.connection.host, "127.0.0.1");
+        assert_eq!(config.qos, MqttQos::AtMostOnce);
+        assert!(config.retain);
+        assert!(config.connection.clean_session);
+    }
+}
+
+#[cfg(feature = "mqtt-integration-tests")]
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_util::{random_lines, random_string, runtime};
+
+    #[test]
+    fn mqtt_publish_acknowledged() {
+        let mut rt = runtime();
+        let prefix = format!("test-{}", random_string(10));
+
+        let config = MqttSinkConfig {
+            connection: MqttConfig {
+                client_id: format!("{}-sink", prefix),
+                ..Default::default()
+            },
+            topic: format!("{}/{{{{ service }}}}", prefix),
+            encoding: EncodingConfig::from(Encoding::Text),
+            qos: MqttQos::ExactlyOnce,
+            ..Default::default()
+        };
+
+        let subscriber = MqttConfig {
+            client_id: format!("{}-subscriber", prefix),
+            ..Default::default()
+        };
+        let (client, mut eventloop) = AsyncClient::new(subscriber.options(), 10);
+        let topic = format!("{}/api", prefix);
+        let lines: Vec<String> = random_lines(100).take(10).collect();
+        let expected = lines.len();
+
+        let received = rt.spawn_handle(async move {
+            client.subscribe(topic, QoS::AtLeastOnce).await.unwrap();
+            let mut received = Vec::new();
+            while received.len() < expected {
+                if let MqttEvent::Incoming(Incoming::Publish(publish)) =
+                    eventloop.poll().await.unwrap()
+                {
+                    received.push(String::from_utf8(publish.payload.to_vec()).unwrap());
+                }
+            }
+            received
+        });
+
+        let events: Vec<Event> = lines
+            .iter()
+            .map(|line| {
+                let mut event = Event::from(line.as_str());
+                event.as_mut_log().insert("service", "api");
+                event
+            })
+            .collect();
+        let mut sink = MqttSink {
+            topic: Template::from(config.topic.as_str()),
+            config,
+        };
+        rt.block_on_std(async move {
+            // Give the subscriber time to subscribe.
+            tokio::time::delay_for(Duration::from_secs(1)).await;
+            sink.run(futures::stream::iter(events)).await.unwrap();
+        });
+
+        assert_eq!(rt.block_on_std(received).unwrap(), lines);
+    }
+}
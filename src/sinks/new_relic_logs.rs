@@ -1,8 +1,8 @@
 use crate::{
-    sinks::http::{HttpMethod, HttpSinkConfig},
+    sinks::http::{Compression, HttpMethod, HttpSinkConfig},
     sinks::util::{
         encoding::{EncodingConfig, EncodingConfigWithDefault},
-        BatchBytesConfig, Compression, TowerRequestConfig,
+        BatchConfig, TowerRequestConfig,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
@@ -37,7 +37,7 @@ pub struct NewRelicLogsConfig {
     #[serde(skip_serializing_if = "skip_serializing_if_default", default)]
     pub encoding: EncodingConfigWithDefault<Encoding>,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
 
     #[serde(default)]
     pub request: TowerRequestConfig,
@@ -82,6 +82,10 @@ impl SinkConfig for NewRelicLogsConfig {
     fn sink_type(&self) -> &'static str {
         "new_relic_logs"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl NewRelicLogsConfig {
@@ -101,10 +105,10 @@ impl NewRelicLogsConfig {
             NewRelicLogsRegion::Eu => Uri::from_static("https://log-api.eu.newrelic.com/log/v1"),
         };
 
-        let batch = BatchBytesConfig {
+        let batch = BatchConfig {
             // The max request size is 10MiB, so in order to be comfortably
             // within this we batch up to 5MiB.
-            max_size: Some(self.batch.max_size.unwrap_or(bytesize::mib(5u64) as usize)),
+            max_bytes: Some(self.batch.max_bytes.unwrap_or(bytesize::mib(5u64) as usize)),
             ..self.batch
         };
 
@@ -129,11 +133,15 @@ impl NewRelicLogsConfig {
                 except_fields: self.encoding.except_fields.clone(),
                 timestamp_format: self.encoding.timestamp_format.clone(),
             },
+            avro: None,
+            csv: None,
+            protobuf: None,
 
             batch,
             request,
 
             tls: None,
+            proxy: Default::default(),
         })
     }
 }
@@ -179,7 +187,7 @@ mod tests {
         assert_eq!(http_config.method, Some(HttpMethod::Post));
         assert_eq!(http_config.encoding.codec, Encoding::Json.into());
         assert_eq!(
-            http_config.batch.max_size,
+            http_config.batch.max_bytes,
             Some(bytesize::mib(5u64) as usize)
         );
         assert_eq!(http_config.request.in_flight_limit, Some(100));
@@ -197,7 +205,7 @@ mod tests {
         let mut nr_config = NewRelicLogsConfig::default();
         nr_config.insert_key = Some("foo".to_owned());
         nr_config.region = Some(NewRelicLogsRegion::Eu);
-        nr_config.batch.max_size = Some(bytesize::mib(8u64) as usize);
+        nr_config.batch.max_bytes = Some(bytesize::mib(8u64) as usize);
         nr_config.request.in_flight_limit = Some(12);
         nr_config.request.rate_limit_num = Some(24);
 
@@ -210,7 +218,7 @@ mod tests {
         assert_eq!(http_config.method, Some(HttpMethod::Post));
         assert_eq!(http_config.encoding.codec, Encoding::Json.into());
         assert_eq!(
-            http_config.batch.max_size,
+            http_config.batch.max_bytes,
             Some(bytesize::mib(8u64) as usize)
         );
         assert_eq!(http_config.request.in_flight_limit, Some(12));
@@ -247,7 +255,7 @@ mod tests {
         assert_eq!(http_config.method, Some(HttpMethod::Post));
         assert_eq!(http_config.encoding.codec, Encoding::Json.into());
         assert_eq!(
-            http_config.batch.max_size,
+            http_config.batch.max_bytes,
             Some(bytesize::mib(8u64) as usize)
         );
         assert_eq!(http_config.request.in_flight_limit, Some(12));
@@ -0,0 +1,718 @@
+//! OpenTelemetry sink
+//!
+//! Exports logs and metrics to an OpenTelemetry collector, or any other
+//! receiver of the OpenTelemetry protocol (OTLP), as protobuf over HTTP.
+//!
+//! https://opentelemetry.io/docs/specs/otlp/#otlphttp
+//!
+//! Logs and metrics are posted to paths of their own, so they're batched
+//! separately, with events partitioned by their type. Every request carries
+//! the configured resource attributes. OTLP over gRPC isn't supported.
+
+use crate::{
+    dns::Resolver,
+    event::{
+        self,
+        metric::{Metric, MetricKind, MetricValue},
+        LogEvent, Value,
+    },
+    runtime::FutureExt,
+    sinks::util::{
+        http::{HttpBatchService, HttpClient, HttpRetryLogic},
+        BatchConfig, BatchSettings, PartitionBatchSink, PartitionBuffer, PartitionInnerBuffer,
+        ServiceBuilderExt, TowerRequestConfig, UriSerde, VecBuffer,
+    },
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+    Event,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures01::{stream::iter_ok, Sink};
+use http::{
+    header::{self, HeaderName, HeaderValue},
+    Uri,
+};
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{collections::BTreeMap, sync::Arc};
+use string_cache::DefaultAtom as Atom;
+use tower::ServiceBuilder;
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/opentelemetry.rs"));
+}
+
+const LOGS_PATH: &str = "/v1/logs";
+const METRICS_PATH: &str = "/v1/metrics";
+
+// `AggregationTemporality` values.
+const DELTA: i32 = 1;
+const CUMULATIVE: i32 = 2;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("{}: {}", source, name))]
+    InvalidHeaderName {
+        name: String,
+        source: header::InvalidHeaderName,
+    },
+    #[snafu(display("{}: {}", source, value))]
+    InvalidHeaderValue {
+        value: String,
+        source: header::InvalidHeaderValue,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OpenTelemetryConfig {
+    /// The base URL of the receiver, which `/v1/logs` and `/v1/metrics` are
+    /// appended to.
+    pub endpoint: UriSerde,
+    pub headers: Option<IndexMap<String, String>>,
+    /// Attributes of the resource everything is exported as, like `service.name`.
+    #[serde(default)]
+    pub resource: BTreeMap<String, String>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    pub tls: Option<TlsOptions>,
+}
+
+lazy_static! {
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        retry_attempts: Some(5),
+        ..Default::default()
+    };
+    static ref SEVERITY_KEYS: [Atom; 2] = [Atom::from("severity"), Atom::from("level")];
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<OpenTelemetryConfig>("opentelemetry")
+}
+
+#[typetag::serde(name = "opentelemetry")]
+impl SinkConfig for OpenTelemetryConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        validate_headers(&self.headers)?;
+
+        let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .events(1_000)
+                .bytes(bytesize::mib(1u64))
+                .timeout(1),
+        );
+        let tls = TlsSettings::from_options(&self.tls)?;
+
+        let sink = Arc::new(OpenTelemetrySink::new(self.clone()));
+        let sink1 = Arc::clone(&sink);
+        let svc = ServiceBuilder::new()
+            .settings(request, HttpRetryLogic)
+            .request_metrics(&cx)
+            .service(HttpBatchService::new(cx.resolver(), tls, move |batch| {
+                sink1.build_request(batch)
+            }));
+
+        let buffer = PartitionBuffer::new(VecBuffer::new(Record::encoded_len));
+        let sink = PartitionBatchSink::new(svc, buffer, batch, cx.acker())
+            .component(cx.name())
+            .with_flat_map(move |event| iter_ok(sink.encode_event(event, Utc::now())))
+            .sink_map_err(|error| error!("Fatal opentelemetry sink error: {}", error));
+
+        let healthcheck = healthcheck(self.clone(), cx.resolver()).boxed_compat();
+
+        Ok((Box::new(sink), Box::new(healthcheck)))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "opentelemetry"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+fn validate_headers(headers: &Option<IndexMap<String, String>>) -> crate::Result<()> {
+    for (name, value) in headers.iter().flatten() {
+        HeaderName::from_bytes(name.as_bytes()).with_context(|| InvalidHeaderName { name })?;
+        HeaderValue::from_bytes(value.as_bytes()).with_context(|| InvalidHeaderValue { value })?;
+    }
+    Ok(())
+}
+
+/// The two kinds of data OTLP is exported as, each with its own request.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Signal {
+    Logs,
+    Metrics,
+}
+
+#[derive(Clone, Debug)]
+enum Record {
+    Log(proto::LogRecord),
+    Metric(proto::Metric),
+}
+
+impl Record {
+    fn encoded_len(&self) -> usize {
+        match self {
+            Record::Log(log) => log.encoded_len(),
+            Record::Metric(metric) => metric.encoded_len(),
+        }
+    }
+}
+
+type Records = PartitionInnerBuffer<Vec<Record>, Signal>;
+
+struct OpenTelemetrySink {
+    config: OpenTelemetryConfig,
+    resource: proto::Resource,
+}
+
+impl OpenTelemetrySink {
+    fn new(config: OpenTelemetryConfig) -> Self {
+        let resource = proto::Resource {
+            attributes: config
+                .resource
+                .iter()
+                .map(|(key, value)| string_attribute(key, value))
+                .collect(),
+        };
+        Self { config, resource }
+    }
+
+    /// Log records are observed at `now`, which is also the time of metrics
+    /// that don't have a timestamp.
+    fn encode_event(
+        &self,
+        event: Event,
+        now: DateTime<Utc>,
+    ) -> Option<PartitionInnerBuffer<Record, Signal>> {
+        match event {
+            Event::Log(log) => Some(PartitionInnerBuffer::new(
+                Record::Log(encode_log(log, now)),
+                Signal::Logs,
+            )),
+            Event::Metric(metric) => encode_metric(metric, now)
+                .map(|metric| PartitionInnerBuffer::new(Record::Metric(metric), Signal::Metrics)),
+            Event::Trace(_) => {
+                warn!(
+                    message = "Exporting spans isn't supported; dropping trace event.",
+                    rate_limit_secs = 30
+                );
+                None
+            }
+        }
+    }
+
+    fn build_request(&self, batch: Records) -> http::Request<Vec<u8>> {
+        let (records, signal) = batch.into_parts();
+        let resource = Some(self.resource.clone());
+
+        let body = match signal {
+            Signal::Logs => proto::ExportLogsServiceRequest {
+                resource_logs: vec![proto::ResourceLogs {
+                    resource,
+                    scope_logs: vec![proto::ScopeLogs {
+                        scope: Some(scope()),
+                        log_records: records
+                            .into_iter()
+                            .filter_map(|record| match record {
+                                Record::Log(log) => Some(log),
+                                Record::Metric(_) => None,
+                            })
+                            .collect(),
+                    }],
+                }],
+            }
+            .encode_to_vec(),
+            Signal::Metrics => proto::ExportMetricsServiceRequest {
+                resource_metrics: vec![proto::ResourceMetrics {
+                    resource,
+                    scope_metrics: vec![proto::ScopeMetrics {
+                        scope: Some(scope()),
+                        metrics: records
+                            .into_iter()
+                            .filter_map(|record| match record {
+                                Record::Metric(metric) => Some(metric),
+                                Record::Log(_) => None,
+                            })
+                            .collect(),
+                    }],
+                }],
+            }
+            .encode_to_vec(),
+        };
+
+        build_request(&self.config, signal, body)
+    }
+}
+
+trait EncodeToVec {
+    fn encode_to_vec(&self) -> Vec<u8>;
+}
+
+impl<M: Message> EncodeToVec for M {
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut body).expect("vec grows as needed");
+        body
+    }
+}
+
+fn build_request(
+    config: &OpenTelemetryConfig,
+    signal: Signal,
+    body: Vec<u8>,
+) -> http::Request<Vec<u8>> {
+    let mut builder = http::Request::post(uri(&config.endpoint, signal));
+    builder.header("Content-Type", "application/x-protobuf");
+    for (name, value) in config.headers.iter().flatten() {
+        builder.header(name.as_str(), value.as_str());
+    }
+    builder.body(body).unwrap()
+}
+
+fn uri(endpoint: &UriSerde, signal: Signal) -> Uri {
+    let path = match signal {
+        Signal::Logs => LOGS_PATH,
+        Signal::Metrics => METRICS_PATH,
+    };
+    let endpoint = endpoint.to_string();
+    format!("{}{}", endpoint.trim_end_matches('/'), path)
+        .parse()
+        .expect("appending a path keeps the uri valid")
+}
+
+fn scope() -> proto::InstrumentationScope {
+    proto::InstrumentationScope {
+        name: "vector".into(),
+        version: crate::get_version(),
+    }
+}
+
+/// The message becomes the body of the record, and a string `severity`, or
+/// else `level`, field its severity. All other fields are kept as attributes.
+fn encode_log(mut log: LogEvent, now: DateTime<Utc>) -> proto::LogRecord {
+    let time_unix_nano = match log.remove(event::log_schema().timestamp_key()) {
+        Some(Value::Timestamp(timestamp)) => unix_nanos(timestamp),
+        // Zero marks the time as unknown.
+        _ => 0,
+    };
+    let body = log
+        .remove(event::log_schema().message_key())
+        .map(encode_value);
+
+    let severity = SEVERITY_KEYS
+        .iter()
+        .find(|key| matches!(log.get(key), Some(Value::Bytes(_))))
+        .and_then(|key| log.remove(key));
+    let severity_text = severity
+        .map(|severity| String::from_utf8_lossy(&severity.as_bytes()).into_owned())
+        .unwrap_or_default();
+
+    proto::LogRecord {
+        time_unix_nano,
+        observed_time_unix_nano: unix_nanos(now),
+        severity_number: severity_number(&severity_text),
+        severity_text,
+        body,
+        attributes: log
+            .into_iter()
+            .map(|(key, value)| proto::KeyValue {
+                key,
+                value: Some(encode_value(value)),
+            })
+            .collect(),
+    }
+}
+
+/// Maps common level names, including syslog's, to `SeverityNumber`s.
+fn severity_number(severity: &str) -> i32 {
+    match severity.to_lowercase().as_str() {
+        "trace" => 1,
+        "debug" => 5,
+        "info" | "informational" => 9,
+        "notice" => 10,
+        "warn" | "warning" => 13,
+        "err" | "error" => 17,
+        "crit" | "critical" => 18,
+        "alert" => 19,
+        "fatal" | "emerg" | "emergency" | "panic" => 21,
+        _ => 0,
+    }
+}
+
+fn encode_value(value: Value) -> proto::AnyValue {
+    use proto::any_value::Value as AnyValue;
+
+    let value = match value {
+        Value::Bytes(bytes) => match String::from_utf8(bytes.to_vec()) {
+            Ok(string) => AnyValue::StringValue(string),
+            Err(error) => AnyValue::BytesValue(error.into_bytes()),
+        },
+        Value::Integer(value) => AnyValue::IntValue(value),
+        Value::Float(value) => AnyValue::DoubleValue(value),
+        Value::Boolean(value) => AnyValue::BoolValue(value),
+        Value::Timestamp(timestamp) => {
+            AnyValue::StringValue(timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+        Value::Map(map) => AnyValue::KvlistValue(proto::KeyValueList {
+            values: map
+                .into_iter()
+                .map(|(key, value)| proto::KeyValue {
+                    key,
+                    value: Some(encode_value(value)),
+                })
+                .collect(),
+        }),
+        Value::Array(values) => AnyValue::ArrayValue(proto::ArrayValue {
+            values: values.into_iter().map(encode_value).collect(),
+        }),
+        Value::Null => return proto::AnyValue { value: None },
+    };
+    proto::AnyValue { value: Some(value) }
+}
+
+/// Counters become monotonic sums, and absolute gauges gauges. Incremental
+/// gauges are the change of a value, so they're sent as a delta sum that
+/// isn't monotonic. Distributions become histograms with a single bucket,
+/// as OTLP has no way of sending individual samples.
+fn encode_metric(metric: Metric, now: DateTime<Utc>) -> Option<proto::Metric> {
+    use proto::metric::Data;
+
+    let attributes: Vec<_> = metric
+        .tags
+        .iter()
+        .flatten()
+        .map(|(key, value)| string_attribute(key, value))
+        .collect();
+    let time_unix_nano = unix_nanos(metric.timestamp.unwrap_or(now));
+    let temporality = match metric.kind {
+        MetricKind::Incremental => DELTA,
+        MetricKind::Absolute => CUMULATIVE,
+    };
+    let number = |value| proto::NumberDataPoint {
+        attributes: attributes.clone(),
+        start_time_unix_nano: 0,
+        time_unix_nano,
+        value: Some(value),
+    };
+    let histogram = |count, sum, bucket_counts, explicit_bounds| {
+        Data::Histogram(proto::Histogram {
+            data_points: vec![proto::HistogramDataPoint {
+                attributes: attributes.clone(),
+                start_time_unix_nano: 0,
+                time_unix_nano,
+                count,
+                sum,
+                bucket_counts,
+                explicit_bounds,
+            }],
+            aggregation_temporality: temporality,
+        })
+    };
+
+    let data = match metric.value {
+        MetricValue::Counter { value } => Data::Sum(proto::Sum {
+            data_points: vec![number(proto::number_data_point::Value::AsDouble(value))],
+            aggregation_temporality: temporality,
+            is_monotonic: true,
+        }),
+        MetricValue::Gauge { value } => {
+            let point = number(proto::number_data_point::Value::AsDouble(value));
+            match metric.kind {
+                MetricKind::Absolute => Data::Gauge(proto::Gauge {
+                    data_points: vec![point],
+                }),
+                MetricKind::Incremental => Data::Sum(proto::Sum {
+                    data_points: vec![point],
+                    aggregation_temporality: DELTA,
+                    is_monotonic: false,
+                }),
+            }
+        }
+        MetricValue::Set { values } => Data::Gauge(proto::Gauge {
+            data_points: vec![number(proto::number_data_point::Value::AsInt(
+                values.len() as i64
+            ))],
+        }),
+        MetricValue::Distribution {
+            values,
+            sample_rates,
+        } => {
+            if values.is_empty() {
+                return None;
+            }
+            let count = sample_rates.iter().map(|&rate| u64::from(rate)).sum();
+            let sum = values
+                .iter()
+                .zip(&sample_rates)
+                .map(|(value, &rate)| value * f64::from(rate))
+                .sum();
+            histogram(count, sum, vec![count], vec![])
+        }
+        MetricValue::AggregatedHistogram {
+            buckets,
+            counts,
+            count,
+            sum,
+        } => {
+            // Vector's bucket counts are cumulative, like Prometheus', while
+            // OTLP counts each bucket on its own, and the values above the
+            // highest bound in a bucket of their own.
+            let mut bucket_counts = Vec::with_capacity(counts.len() + 1);
+            let mut below = 0;
+            for count in counts.into_iter().map(u64::from) {
+                bucket_counts.push(count.saturating_sub(below));
+                below = below.max(count);
+            }
+            bucket_counts.push(u64::from(count).saturating_sub(below));
+            histogram(u64::from(count), sum, bucket_counts, buckets)
+        }
+        MetricValue::AggregatedSummary {
+            quantiles,
+            values,
+            count,
+            sum,
+        } => Data::Summary(proto::Summary {
+            data_points: vec![proto::SummaryDataPoint {
+                attributes: attributes.clone(),
+                start_time_unix_nano: 0,
+                time_unix_nano,
+                count: u64::from(count),
+                sum,
+                quantile_values: quantiles
+                    .into_iter()
+                    .zip(values)
+                    .map(
+                        |(quantile, value)| proto::summary_data_point::ValueAtQuantile {
+                            quantile,
+                            value,
+                        },
+                    )
+                    .collect(),
+            }],
+        }),
+    };
+
+    Some(proto::Metric {
+        name: metric.name,
+        description: String::new(),
+        unit: String::new(),
+        data: Some(data),
+    })
+}
+
+fn string_attribute(key: &str, value: &str) -> proto::KeyValue {
+    proto::KeyValue {
+        key: key.to_owned(),
+        value: Some(proto::AnyValue {
+            value: Some(proto::any_value::Value::StringValue(value.to_owned())),
+        }),
+    }
+}
+
+fn unix_nanos(timestamp: DateTime<Utc>) -> u64 {
+    timestamp.timestamp_nanos() as u64
+}
+
+/// There is no standard health endpoint, but receivers accept an empty
+/// export of either signal.
+async fn healthcheck(config: OpenTelemetryConfig, resolver: Resolver) -> crate::Result<()> {
+    let tls = TlsSettings::from_options(&config.tls)?;
+    let mut client = HttpClient::new(resolver, tls)?;
+
+    for &signal in &[Signal::Logs, Signal::Metrics] {
+        let request = build_request(&config, signal, vec![]).map(hyper::Body::from);
+        let response = client.send(request).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(super::HealthcheckError::UnexpectedStatus { status }.into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::offset::TimeZone;
+
+    fn sink() -> OpenTelemetrySink {
+        OpenTelemetrySink::new(
+            toml::from_str(
+                r#"
+                endpoint = "http://localhost:4318/"
+                headers.X-Tenant = "ops"
+                resource."service.name" = "checkout"
+                "#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn ts(secs: u32) -> DateTime<Utc> {
+        Utc.ymd(2020, 1, 1).and_hms(0, 0, secs)
+    }
+
+    fn send(sink: &OpenTelemetrySink, events: Vec<Event>) -> http::Request<Vec<u8>> {
+        let mut records = events
+            .into_iter()
+            .filter_map(|event| sink.encode_event(event, ts(59)))
+            .map(|record| record.into_parts());
+        let (first, signal) = records.next().unwrap();
+        let mut batch = vec![first];
+        batch.extend(records.map(|(record, other)| {
+            assert_eq!(other, signal);
+            record
+        }));
+
+        let request = sink.build_request(PartitionInnerBuffer::new(batch, signal));
+        assert_eq!(request.headers()["Content-Type"], "application/x-protobuf");
+        assert_eq!(request.headers()["X-Tenant"], "ops");
+        request
+    }
+
+    fn string(value: &str) -> Option<proto::AnyValue> {
+        string_attribute("", value).value
+    }
+
+    fn resource() -> Option<proto::Resource> {
+        Some(proto::Resource {
+            attributes: vec![string_attribute("service.name", "checkout")],
+        })
+    }
+
+    #[test]
+    fn opentelemetry_encodes_logs() {
+        let mut declined = Event::from("card declined");
+        let log = declined.as_mut_log();
+        log.insert(event::log_schema().timestamp_key(), ts(1));
+        log.insert("level", "WARN");
+        log.insert("http.status", 402);
+
+        let mut untimed = Event::from("no timestamp");
+        untimed
+            .as_mut_log()
+            .remove(event::log_schema().timestamp_key());
+
+        let request = send(&sink(), vec![declined, untimed]);
+        assert_eq!(request.uri(), "http://localhost:4318/v1/logs");
+
+        let request = proto::ExportLogsServiceRequest::decode(&request.body()[..]).unwrap();
+        assert_eq!(request.resource_logs.len(), 1);
+        let resource_logs = &request.resource_logs[0];
+        assert_eq!(resource_logs.resource, resource());
+        assert_eq!(resource_logs.scope_logs[0].scope, Some(scope()));
+
+        let mut status = BTreeMap::new();
+        status.insert("status".to_owned(), Value::Integer(402));
+        assert_eq!(
+            resource_logs.scope_logs[0].log_records,
+            vec![
+                proto::LogRecord {
+                    time_unix_nano: unix_nanos(ts(1)),
+                    observed_time_unix_nano: unix_nanos(ts(59)),
+                    severity_number: 13,
+                    severity_text: "WARN".into(),
+                    body: string("card declined"),
+                    attributes: vec![proto::KeyValue {
+                        key: "http".into(),
+                        value: Some(encode_value(Value::Map(status))),
+                    }],
+                },
+                proto::LogRecord {
+                    time_unix_nano: 0,
+                    observed_time_unix_nano: unix_nanos(ts(59)),
+                    severity_number: 0,
+                    severity_text: "".into(),
+                    body: string("no timestamp"),
+                    attributes: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn opentelemetry_encodes_metrics() {
+        let mut tags = BTreeMap::new();
+        tags.insert("host".to_owned(), "web-1".to_owned());
+        let metric = |kind, value| {
+            Event::Metric(Metric {
+                name: "requests".into(),
+                timestamp: Some(ts(1)),
+                tags: Some(tags.clone()),
+                kind,
+                value,
+            })
+        };
+
+        let request = send(
+            &sink(),
+            vec![
+                metric(MetricKind::Incremental, MetricValue::Counter { value: 2.0 }),
+                metric(
+                    MetricKind::Absolute,
+                    MetricValue::AggregatedHistogram {
+                        buckets: vec![0.5, 1.0],
+                        counts: vec![4, 6],
+                        count: 7,
+                        sum: 5.5,
+                    },
+                ),
+            ],
+        );
+        assert_eq!(request.uri(), "http://localhost:4318/v1/metrics");
+
+        let request = proto::ExportMetricsServiceRequest::decode(&request.body()[..]).unwrap();
+        let resource_metrics = &request.resource_metrics[0];
+        assert_eq!(resource_metrics.resource, resource());
+
+        let attributes = vec![string_attribute("host", "web-1")];
+        let metrics: Vec<_> = resource_metrics.scope_metrics[0]
+            .metrics
+            .iter()
+            .map(|metric| metric.data.clone().unwrap())
+            .collect();
+        assert_eq!(
+            metrics,
+            vec![
+                proto::metric::Data::Sum(proto::Sum {
+                    data_points: vec![proto::NumberDataPoint {
+                        attributes: attributes.clone(),
+                        start_time_unix_nano: 0,
+                        time_unix_nano: unix_nanos(ts(1)),
+                        value: Some(proto::number_data_point::Value::AsDouble(2.0)),
+                    }],
+                    aggregation_temporality: DELTA,
+                    is_monotonic: true,
+                }),
+                proto::metric::Data::Histogram(proto::Histogram {
+                    data_points: vec![proto::HistogramDataPoint {
+                        attributes,
+                        start_time_unix_nano: 0,
+                        time_unix_nano: unix_nanos(ts(1)),
+                        count: 7,
+                        sum: 5.5,
+                        bucket_counts: vec![4, 2, 1],
+                        explicit_bounds: vec![0.5, 1.0],
+                    }],
+                    aggregation_temporality: CUMULATIVE,
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,635 @@
+//! Sends alerts to PagerDuty with its [Events API v2], which triggers,
+//! acknowledges and resolves alerts one event per request.
+//!
+//! [Events API v2]: https://developer.pagerduty.com/docs/events-api-v2/overview/
+
+use crate::{
+    event::{log_schema, Event, Value},
+    sinks::util::{
+        http::{BatchedHttpSink, HttpRetryLogic, HttpSink, Response},
+        retries::{RetryAction, RetryLogic},
+        BatchSettings, TowerRequestConfig, UriSerde,
+    },
+    template::Template,
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use chrono::{SecondsFormat, Utc};
+use futures01::{future, Sink};
+use http::{Request, StatusCode, Uri};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use string_cache::DefaultAtom as Atom;
+
+/// PagerDuty limits dedup keys to 255 characters, and truncates summaries
+/// over 1024.
+const MAX_DEDUP_KEY_LENGTH: usize = 255;
+const MAX_SUMMARY_LENGTH: usize = 1024;
+
+lazy_static! {
+    static ref DEFAULT_ENDPOINT: UriSerde = Uri::from_static("https://events.pagerduty.com").into();
+    // One request at a time keeps a resolve from overtaking the trigger it
+    // resolves, and two per second stays within PagerDuty's rate limit of
+    // 120 events a minute per routing key.
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        in_flight_limit: Some(1),
+        rate_limit_duration_secs: Some(1),
+        rate_limit_num: Some(2),
+        ..Default::default()
+    };
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PagerDutyConfig {
+    pub routing_key: String,
+    #[serde(default = "default_endpoint")]
+    pub endpoint: UriSerde,
+    /// Groups the events of an alert, so they trigger, acknowledge and
+    /// resolve the same one.
+    pub dedup_key: Option<Template>,
+    #[serde(default = "default_summary")]
+    pub summary: Template,
+    /// Defaults to the host of the event, or else the host Vector runs on.
+    pub source: Option<Template>,
+    /// Field holding the action of the event, which is a trigger when unset.
+    pub action_field: Option<Atom>,
+    #[serde(default = "default_severity_field")]
+    pub severity_field: Atom,
+    #[serde(default)]
+    pub default_severity: Severity,
+    pub component: Option<Template>,
+    pub group: Option<Template>,
+    pub class: Option<Template>,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    pub tls: Option<TlsOptions>,
+}
+
+fn default_endpoint() -> UriSerde {
+    DEFAULT_ENDPOINT.clone()
+}
+
+fn default_summary() -> Template {
+    Template::from("{{ message }}")
+}
+
+fn default_severity_field() -> Atom {
+    Atom::from("severity")
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Trigger,
+    Acknowledge,
+    Resolve,
+}
+
+impl Action {
+    fn parse(action: &str) -> Option<Self> {
+        match action.to_lowercase().as_str() {
+            "trigger" | "triggered" => Some(Action::Trigger),
+            "acknowledge" | "acknowledged" | "ack" => Some(Action::Acknowledge),
+            "resolve" | "resolved" => Some(Action::Resolve),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Severity {
+    Critical,
+    #[derivative(Default)]
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    /// Maps common level names, including syslog's, to PagerDuty severities.
+    fn parse(severity: &str) -> Option<Self> {
+        match severity.to_lowercase().as_str() {
+            "critical" | "crit" | "alert" | "emerg" | "emergency" | "fatal" | "panic" => {
+                Some(Severity::Critical)
+            }
+            "error" | "err" => Some(Severity::Error),
+            "warning" | "warn" => Some(Severity::Warning),
+            "info" | "informational" | "notice" | "debug" | "trace" => Some(Severity::Info),
+            _ => None,
+        }
+    }
+}
+
+/// An event of the Events API.
+#[derive(Serialize, Debug, Clone)]
+pub struct PagerDutyEvent {
+    routing_key: String,
+    event_action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dedup_key: Option<String>,
+    /// Only triggers describe the alert, the other actions just refer to it
+    /// by its dedup key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<Payload>,
+    client: &'static str,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct Payload {
+    summary: String,
+    source: String,
+    severity: Severity,
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class: Option<String>,
+    custom_details: serde_json::Value,
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<PagerDutyConfig>("pagerduty")
+}
+
+#[typetag::serde(name = "pagerduty")]
+impl SinkConfig for PagerDutyConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+        // The Events API takes a single event per request.
+        let batch = BatchSettings::default().events(1);
+        let tls_settings = TlsSettings::from_options(&self.tls)?;
+
+        let sink = BatchedHttpSink::with_retry_logic(
+            self.clone(),
+            Vec::new(),
+            PagerDutyRetryLogic {
+                inner: HttpRetryLogic,
+            },
+            request,
+            batch,
+            tls_settings,
+            &Default::default(),
+            &cx,
+        )
+        .sink_map_err(|e| error!("Fatal pagerduty sink error: {}", e));
+
+        // Checking the routing key would mean sending an alert.
+        let healthcheck = Box::new(future::ok(()));
+
+        Ok((Box::new(sink), healthcheck))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "pagerduty"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+impl HttpSink for PagerDutyConfig {
+    type Input = PagerDutyEvent;
+    type Output = Vec<PagerDutyEvent>;
+
+    fn encode_event(&self, event: Event) -> Option<Self::Input> {
+        let action = match &self.action_field {
+            Some(field) => {
+                let action = event.as_log().get(field).map(Value::to_string_lossy);
+                match action.as_ref().map(|action| Action::parse(action)) {
+                    Some(Some(action)) => action,
+                    Some(None) => {
+                        warn!(
+                            message = "Unknown action; dropping event.",
+                            field = field.as_ref(),
+                            action = action.as_deref().unwrap_or_default(),
+                            rate_limit_secs = 30,
+                        );
+                        return None;
+                    }
+                    None => Action::Trigger,
+                }
+            }
+            None => Action::Trigger,
+        };
+
+        let dedup_key = match &self.dedup_key {
+            Some(template) => match template.render_string(&event) {
+                Ok(key) if key.chars().count() > MAX_DEDUP_KEY_LENGTH => {
+                    warn!(
+                        message = "Dedup key is too long; dropping event.",
+                        max_length = MAX_DEDUP_KEY_LENGTH as u64,
+                        rate_limit_secs = 30,
+                    );
+                    return None;
+                }
+                Ok(key) => Some(key),
+                // Without its dedup key an event would open an alert that
+                // can't be resolved, or resolve nothing.
+                Err(missing_keys) => {
+                    warn!(
+                        message = "Keys for dedup key do not exist on the event; dropping event.",
+                        ?missing_keys,
+                        rate_limit_secs = 30,
+                    );
+                    return None;
+                }
+            },
+            None => None,
+        };
+
+        let payload = match action {
+            Action::Trigger => Some(self.payload(&event)?),
+            Action::Acknowledge | Action::Resolve if dedup_key.is_none() => {
+                warn!(
+                    message =
+                        "Acknowledging or resolving an alert requires a dedup key; dropping event.",
+                    rate_limit_secs = 30,
+                );
+                return None;
+            }
+            Action::Acknowledge | Action::Resolve => None,
+        };
+
+        Some(PagerDutyEvent {
+            routing_key: self.routing_key.clone(),
+            event_action: action,
+            dedup_key,
+            payload,
+            client: "Vector",
+        })
+    }
+
+    fn build_request(&self, mut events: Self::Output) -> http::Request<Vec<u8>> {
+        let event = events.pop().expect("batches hold a single event");
+        let body = serde_json::to_vec(&event).expect("events should serialize to JSON");
+
+        let uri = format!(
+            "{}/v2/enqueue",
+            self.endpoint.to_string().trim_end_matches('/')
+        );
+        let uri = uri.parse::<Uri>().expect("endpoint should be a valid URI");
+
+        Request::post(uri)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .unwrap()
+    }
+}
+
+impl PagerDutyConfig {
+    /// The payload a trigger requires, which can't be sent without a summary
+    /// and a source.
+    fn payload(&self, event: &Event) -> Option<Payload> {
+        let log = event.as_log();
+
+        let summary = match render(&self.summary, event, "summary")? {
+            summary if summary.trim().is_empty() => {
+                warn!(
+                    message = "Summary is empty; dropping event.",
+                    rate_limit_secs = 30,
+                );
+                return None;
+            }
+            summary if summary.chars().count() > MAX_SUMMARY_LENGTH => {
+                summary.chars().take(MAX_SUMMARY_LENGTH).collect()
+            }
+            summary => summary,
+        };
+
+        let source = match &self.source {
+            Some(source) => render(source, event, "source")?,
+            None => log
+                .get(log_schema().host_key())
+                .map(Value::to_string_lossy)
+                .or_else(hostname::get_hostname)
+                .unwrap_or_else(|| "vector".into()),
+        };
+
+        let severity = log
+            .get(&self.severity_field)
+            .and_then(|severity| Severity::parse(&severity.to_string_lossy()))
+            .unwrap_or(self.default_severity);
+
+        let timestamp = match log.get(log_schema().timestamp_key()) {
+            Some(Value::Timestamp(timestamp)) => *timestamp,
+            _ => Utc::now(),
+        };
+
+        let optional = |template: &Option<Template>, name: &str| match template {
+            Some(template) => render(template, event, name),
+            None => None,
+        };
+
+        Some(Payload {
+            summary,
+            source,
+            severity,
+            timestamp: timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            component: optional(&self.component, "component"),
+            group: optional(&self.group, "group"),
+            class: optional(&self.class, "class"),
+            custom_details: serde_json::to_value(log).expect("fields should serialize to JSON"),
+        })
+    }
+}
+
+fn render(template: &Template, event: &Event, name: &str) -> Option<String> {
+    template
+        .render_string(event)
+        .map_err(|missing_keys| {
+            warn!(
+                message = "Keys for payload field do not exist on the event.",
+                field = name,
+                ?missing_keys,
+                rate_limit_secs = 30,
+            );
+        })
+        .ok()
+}
+
+#[derive(Clone)]
+struct PagerDutyRetryLogic {
+    inner: HttpRetryLogic,
+}
+
+impl RetryLogic for PagerDutyRetryLogic {
+    type Response = Response;
+    type Error = hyper::Error;
+
+    fn is_retriable_error(&self, error: &Self::Error) -> bool {
+        self.inner.is_retriable_error(error)
+    }
+
+    fn should_retry_response(&self, response: &Self::Response) -> RetryAction {
+        match response.status() {
+            // Rate limited, which happens when too many events are sent for
+            // a routing key, so backing off lets them through again.
+            StatusCode::TOO_MANY_REQUESTS => RetryAction::Retry("Rate limited by PagerDuty".into()),
+            // An invalid event, which will never be accepted. The response
+            // lists what's wrong with it.
+            StatusCode::BAD_REQUEST => RetryAction::DontRetry(
+                format!(
+                    "Invalid event: {}",
+                    String::from_utf8_lossy(response.body())
+                )
+                .into(),
+            ),
+            _ => self.inner.should_retry_response(response),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Runtime,
+        sinks::util::test::build_test_server,
+        test_util::{next_addr, shutdown_on_idle},
+    };
+    use bytes::Bytes;
+    use chrono::TimeZone;
+    use futures01::{stream, Stream};
+    use serde_json::json;
+
+    fn config(extra: &str) -> PagerDutyConfig {
+        toml::from_str(&format!(r#"routing_key = "abc123"{}"#, extra)).unwrap()
+    }
+
+    fn encode(config: &PagerDutyConfig, event: Event) -> Option<serde_json::Value> {
+        config
+            .encode_event(event)
+            .map(|event| serde_json::to_value(event).unwrap())
+    }
+
+    fn alert(fields: &[(&str, &str)]) -> Event {
+        let mut event = Event::from("Disk is full");
+        let log = event.as_mut_log();
+        log.insert(
+            log_schema().timestamp_key().clone(),
+            Utc.ymd(2020, 9, 28).and_hms(10, 30, 0),
+        );
+        log.insert(log_schema().host_key().clone(), "db-1");
+        for (name, value) in fields {
+            log.insert(*name, *value);
+        }
+        event
+    }
+
+    #[test]
+    fn pagerduty_encodes_triggers() {
+        let config = config(
+            r#"
+            dedup_key = "{{ host }}/{{ check }}"
+            component = "{{ check }}"
+            group = "{{ team }}"
+            "#,
+        );
+
+        let encoded = encode(
+            &config,
+            alert(&[("check", "disk"), ("severity", "warn"), ("team", "dba")]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            encoded,
+            json!({
+                "routing_key": "abc123",
+                "event_action": "trigger",
+                "dedup_key": "db-1/disk",
+                "client": "Vector",
+                "payload": {
+                    "summary": "Disk is full",
+                    "source": "db-1",
+                    "severity": "warning",
+                    "timestamp": "2020-09-28T10:30:00.000Z",
+                    "component": "disk",
+                    "group": "dba",
+                    "custom_details": {
+                        "check": "disk",
+                        "host": "db-1",
+                        "message": "Disk is full",
+                        "severity": "warn",
+                        "team": "dba",
+                        "timestamp": "2020-09-28T10:30:00Z",
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn pagerduty_maps_severities() {
+        let defaults = config("");
+        let severity = |level: Option<&str>| {
+            let fields = level.map(|level| vec![("severity", level)]);
+            let encoded = encode(&defaults, alert(&fields.unwrap_or_default())).unwrap();
+            encoded["payload"]["severity"].as_str().unwrap().to_owned()
+        };
+
+        assert_eq!(severity(Some("EMERG")), "critical");
+        assert_eq!(severity(Some("fatal")), "critical");
+        assert_eq!(severity(Some("err")), "error");
+        assert_eq!(severity(Some("warning")), "warning");
+        assert_eq!(severity(Some("notice")), "info");
+        assert_eq!(severity(Some("bogus")), "error");
+        assert_eq!(severity(None), "error");
+
+        let config = config(
+            r#"
+            severity_field = "level"
+            default_severity = "critical"
+            "#,
+        );
+        let encoded = encode(&config, alert(&[("level", "info")])).unwrap();
+        assert_eq!(encoded["payload"]["severity"], "info");
+        let encoded = encode(&config, alert(&[])).unwrap();
+        assert_eq!(encoded["payload"]["severity"], "critical");
+    }
+
+    #[test]
+    fn pagerduty_maps_actions() {
+        let config = config(
+            r#"
+            dedup_key = "{{ host }}/{{ check }}"
+            action_field = "state"
+            "#,
+        );
+        let action = |state: &str| {
+            encode(&config, alert(&[("check", "disk"), ("state", state)]))
+                .map(|encoded| encoded["event_action"].as_str().unwrap().to_owned())
+        };
+
+        assert_eq!(action("trigger").as_deref(), Some("trigger"));
+        assert_eq!(action("ACK").as_deref(), Some("acknowledge"));
+        assert_eq!(action("acknowledged").as_deref(), Some("acknowledge"));
+        assert_eq!(action("resolved").as_deref(), Some("resolve"));
+        assert_eq!(action("snoozed"), None);
+
+        // Events without the field trigger an alert.
+        let encoded = encode(&config, alert(&[("check", "disk")])).unwrap();
+        assert_eq!(encoded["event_action"], "trigger");
+
+        // Only triggers carry a payload.
+        let encoded = encode(&config, alert(&[("check", "disk"), ("state", "resolve")]));
+        assert_eq!(
+            encoded.unwrap(),
+            json!({
+                "routing_key": "abc123",
+                "event_action": "resolve",
+                "dedup_key": "db-1/disk",
+                "client": "Vector",
+            })
+        );
+    }
+
+    #[test]
+    fn pagerduty_requires_dedup_keys() {
+        // Resolving needs a dedup key.
+        let without_key = config(r#"action_field = "state""#);
+        assert!(encode(&without_key, alert(&[("state", "resolve")])).is_none());
+        let encoded = encode(&without_key, alert(&[])).unwrap();
+        assert!(encoded.get("dedup_key").is_none());
+
+        // A configured dedup key that can't be rendered drops the event,
+        // rather than opening an alert nothing can resolve.
+        let with_key = config(r#"dedup_key = "{{ check }}""#);
+        assert!(encode(&with_key, alert(&[])).is_none());
+
+        let long = "x".repeat(MAX_DEDUP_KEY_LENGTH + 1);
+        assert!(encode(&with_key, alert(&[("check", &long)])).is_none());
+    }
+
+    #[test]
+    fn pagerduty_requires_summary() {
+        let config = config(r#"summary = "{{ title }}""#);
+        assert!(encode(&config, alert(&[])).is_none());
+        assert!(encode(&config, alert(&[("title", " ")])).is_none());
+
+        let long = "é".repeat(MAX_SUMMARY_LENGTH + 1);
+        let encoded = encode(&config, alert(&[("title", &long)])).unwrap();
+        assert_eq!(
+            encoded["payload"]["summary"]
+                .as_str()
+                .unwrap()
+                .chars()
+                .count(),
+            MAX_SUMMARY_LENGTH
+        );
+    }
+
+    #[test]
+    fn pagerduty_retries_rate_limited_requests() {
+        let logic = PagerDutyRetryLogic {
+            inner: HttpRetryLogic,
+        };
+        let response = |status: u16, body: &'static str| {
+            http::Response::builder()
+                .status(status)
+                .body(Bytes::from(body))
+                .unwrap()
+        };
+
+        assert!(logic
+            .should_retry_response(&response(429, ""))
+            .is_retryable());
+        assert!(logic
+            .should_retry_response(&response(503, ""))
+            .is_retryable());
+        assert!(logic
+            .should_retry_response(&response(202, r#"{"status":"success"}"#))
+            .is_successful());
+        match logic.should_retry_response(&response(
+            400,
+            r#"{"status":"invalid event","errors":["'routing_key' is missing"]}"#,
+        )) {
+            RetryAction::DontRetry(reason) => assert!(reason.contains("'routing_key' is missing")),
+            _ => panic!("invalid events shouldn't be retried"),
+        }
+    }
+
+    #[test]
+    fn pagerduty_posts_events() {
+        let addr = next_addr();
+        let config = config(&format!(r#"endpoint = "http://{}/""#, addr));
+
+        let mut rt = Runtime::new().unwrap();
+        let cx = SinkContext::new_test(rt.executor());
+        let (sink, _) = config.build(cx).unwrap();
+
+        let (rx, trigger, server) = build_test_server(&addr);
+        rt.spawn(server);
+
+        let events = vec![alert(&[]), Event::from("Replication is lagging")];
+        let pump = sink.send_all(stream::iter_ok(events));
+        let _ = rt.block_on(pump).unwrap();
+        drop(trigger);
+
+        let requests = rx.take(2).wait().map(Result::unwrap).collect::<Vec<_>>();
+        shutdown_on_idle(rt);
+
+        let summaries = requests
+            .into_iter()
+            .map(|(parts, body)| {
+                assert_eq!(parts.method, http::Method::POST);
+                assert_eq!(parts.uri.path(), "/v2/enqueue");
+                assert_eq!(parts.headers["Content-Type"], "application/json");
+                let event: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(event["routing_key"], "abc123");
+                event["payload"]["summary"].as_str().unwrap().to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(summaries, vec!["Disk is full", "Replication is lagging"]);
+    }
+}
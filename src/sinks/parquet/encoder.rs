@@ -0,0 +1,439 @@
+//! Encodes batches of log events as Parquet files.
+//!
+//! Each leaf field becomes an optional column named by its path, such as
+//! `request.method` or `tags[0]`. Without a configured schema the columns are
+//! inferred from the whole batch: a column takes the type all its values
+//! share, integers mixed with floats widen to doubles and any other mix falls
+//! back to strings. Events without a field get a null in its column.
+
+use crate::event::{Event, Value};
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use parquet::{
+    basic::{Compression as ParquetCompression, LogicalType, Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    errors::Result,
+    file::{
+        properties::WriterProperties,
+        writer::{FileWriter, RowGroupWriter, SerializedFileWriter},
+    },
+    schema::types::Type,
+    util::cursor::InMemoryWriteableCursor,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    Boolean,
+    Int64,
+    Double,
+    String,
+    Timestamp,
+}
+
+impl ColumnType {
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(_) => Some(ColumnType::Boolean),
+            Value::Integer(_) => Some(ColumnType::Int64),
+            Value::Float(_) => Some(ColumnType::Double),
+            Value::Bytes(_) => Some(ColumnType::String),
+            Value::Timestamp(_) => Some(ColumnType::Timestamp),
+            // Empty maps and arrays hold no values to make a column of.
+            Value::Map(_) | Value::Array(_) | Value::Null => None,
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Int64, ColumnType::Double) | (ColumnType::Double, ColumnType::Int64) => {
+                ColumnType::Double
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    fn parquet_type(self, name: &str) -> Result<Type> {
+        let (physical, logical) = match self {
+            ColumnType::Boolean => (PhysicalType::BOOLEAN, LogicalType::NONE),
+            ColumnType::Int64 => (PhysicalType::INT64, LogicalType::NONE),
+            ColumnType::Double => (PhysicalType::DOUBLE, LogicalType::NONE),
+            ColumnType::String => (PhysicalType::BYTE_ARRAY, LogicalType::UTF8),
+            ColumnType::Timestamp => (PhysicalType::INT64, LogicalType::TIMESTAMP_MICROS),
+        };
+
+        Type::primitive_type_builder(name, physical)
+            .with_logical_type(logical)
+            .with_repetition(Repetition::OPTIONAL)
+            .build()
+    }
+
+    /// `value` as stored in a column of this type, if it can be.
+    fn convert(self, value: &Value) -> Option<Cell> {
+        let parse = |bytes: &[u8]| std::str::from_utf8(bytes).ok().map(str::trim);
+
+        match (self, value) {
+            (_, Value::Null) => None,
+            (ColumnType::Boolean, Value::Boolean(value)) => Some(Cell::Boolean(*value)),
+            (ColumnType::Boolean, Value::Bytes(bytes)) => {
+                parse(bytes)?.parse().ok().map(Cell::Boolean)
+            }
+            (ColumnType::Int64, Value::Integer(value)) => Some(Cell::Int64(*value)),
+            (ColumnType::Int64, Value::Bytes(bytes)) => parse(bytes)?.parse().ok().map(Cell::Int64),
+            (ColumnType::Double, Value::Float(value)) => Some(Cell::Double(*value)),
+            (ColumnType::Double, Value::Integer(value)) => Some(Cell::Double(*value as f64)),
+            (ColumnType::Double, Value::Bytes(bytes)) => {
+                parse(bytes)?.parse().ok().map(Cell::Double)
+            }
+            (ColumnType::String, Value::Bytes(bytes)) => Some(Cell::String(bytes.to_vec())),
+            (ColumnType::String, value) => Some(Cell::String(value.to_string_lossy().into_bytes())),
+            (ColumnType::Timestamp, Value::Timestamp(ts)) => Some(Cell::Timestamp(micros(ts))),
+            (ColumnType::Timestamp, Value::Bytes(bytes)) => {
+                DateTime::parse_from_rfc3339(parse(bytes)?)
+                    .ok()
+                    .map(|ts| Cell::Timestamp(micros(&ts.with_timezone(&Utc))))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn micros(ts: &DateTime<Utc>) -> i64 {
+    ts.timestamp() * 1_000_000 + i64::from(ts.timestamp_subsec_micros())
+}
+
+enum Cell {
+    Boolean(bool),
+    Int64(i64),
+    Double(f64),
+    String(Vec<u8>),
+    Timestamp(i64),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    #[derivative(Default)]
+    Snappy,
+    Gzip,
+}
+
+impl From<Compression> for ParquetCompression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => ParquetCompression::UNCOMPRESSED,
+            Compression::Snappy => ParquetCompression::SNAPPY,
+            Compression::Gzip => ParquetCompression::GZIP,
+        }
+    }
+}
+
+pub struct Encoder {
+    schema: Option<IndexMap<String, ColumnType>>,
+    properties: Arc<WriterProperties>,
+    row_group_size: usize,
+}
+
+impl Encoder {
+    pub fn new(
+        schema: Option<IndexMap<String, ColumnType>>,
+        compression: Compression,
+        row_group_size: usize,
+    ) -> Self {
+        let properties = WriterProperties::builder()
+            .set_compression(compression.into())
+            .build();
+
+        Self {
+            schema,
+            properties: Arc::new(properties),
+            row_group_size,
+        }
+    }
+
+    /// The configured columns, or those inferred from `rows`.
+    fn columns(&self, rows: &[HashMap<String, &Value>]) -> IndexMap<String, ColumnType> {
+        if let Some(schema) = &self.schema {
+            return schema.clone();
+        }
+
+        let mut columns = BTreeMap::<String, ColumnType>::new();
+        for (name, value) in rows.iter().flatten() {
+            if let Some(column_type) = ColumnType::of(value) {
+                columns
+                    .entry(name.clone())
+                    .and_modify(|existing| *existing = existing.merge(column_type))
+                    .or_insert(column_type);
+            }
+        }
+        columns.into_iter().collect()
+    }
+
+    /// Writes `events` as one file, starting a new row group every
+    /// `row_group_size` events.
+    pub fn encode(&self, events: &[Event]) -> Result<Vec<u8>> {
+        let rows = events
+            .iter()
+            .map(|event| event.as_log().all_fields().collect::<HashMap<_, _>>())
+            .collect::<Vec<_>>();
+
+        let columns = self.columns(&rows);
+        let mut fields = columns
+            .iter()
+            .map(|(name, column_type)| column_type.parquet_type(name).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Type::group_type_builder("event")
+            .with_fields(&mut fields)
+            .build()?;
+
+        let cursor = InMemoryWriteableCursor::default();
+        let mut writer = SerializedFileWriter::new(
+            cursor.clone(),
+            Arc::new(schema),
+            Arc::clone(&self.properties),
+        )?;
+
+        for rows in rows.chunks(self.row_group_size) {
+            let mut row_group = writer.next_row_group()?;
+            for (name, column_type) in &columns {
+                let mut column = row_group
+                    .next_column()?
+                    .expect("There is a column writer for each field of the schema.");
+                let cells = rows
+                    .iter()
+                    .map(|row| {
+                        let value = row.get(name)?;
+                        let cell = column_type.convert(value);
+                        if cell.is_none() && !matches!(value, Value::Null) {
+                            warn!(
+                                message = "Field doesn't match its column type; writing null.",
+                                field = %name,
+                                column_type = ?column_type,
+                                rate_limit_secs = 30
+                            );
+                        }
+                        cell
+                    })
+                    .collect::<Vec<_>>();
+                write_column(&mut column, cells)?;
+                row_group.close_column(column)?;
+            }
+            writer.close_row_group(row_group)?;
+        }
+
+        writer.close()?;
+        Ok(cursor.data())
+    }
+}
+
+fn write_column(column: &mut ColumnWriter, cells: Vec<Option<Cell>>) -> Result<()> {
+    // Definition level 1 marks a value, 0 a null.
+    let levels = cells
+        .iter()
+        .map(|cell| cell.is_some() as i16)
+        .collect::<Vec<_>>();
+    let cells = cells.into_iter().flatten();
+
+    match column {
+        ColumnWriter::BoolColumnWriter(writer) => {
+            let values = cells
+                .filter_map(|cell| match cell {
+                    Cell::Boolean(value) => Some(value),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            writer.write_batch(&values, Some(&levels), None)?;
+        }
+        ColumnWriter::Int64ColumnWriter(writer) => {
+            let values = cells
+                .filter_map(|cell| match cell {
+                    Cell::Int64(value) | Cell::Timestamp(value) => Some(value),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            writer.write_batch(&values, Some(&levels), None)?;
+        }
+        ColumnWriter::DoubleColumnWriter(writer) => {
+            let values = cells
+                .filter_map(|cell| match cell {
+                    Cell::Double(value) => Some(value),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            writer.write_batch(&values, Some(&levels), None)?;
+        }
+        ColumnWriter::ByteArrayColumnWriter(writer) => {
+            let values = cells
+                .filter_map(|cell| match cell {
+                    Cell::String(value) => Some(ByteArray::from(value)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            writer.write_batch(&values, Some(&levels), None)?;
+        }
+        _ => unreachable!("Columns only have the physical types of `ColumnType`."),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::{Field, Row},
+        util::cursor::SliceableCursor,
+    };
+
+    fn read(bytes: Vec<u8>) -> (SerializedFileReader<SliceableCursor>, Vec<Row>) {
+        let reader = SerializedFileReader::new(SliceableCursor::new(bytes)).unwrap();
+        let rows = reader.get_row_iter(None).unwrap().collect();
+        (reader, rows)
+    }
+
+    fn columns(row: &Row) -> Vec<(String, Field)> {
+        row.get_column_iter()
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn parquet_infers_union_of_fields() {
+        let mut first = Event::new_empty_log();
+        first.as_mut_log().insert("message", "first");
+        first.as_mut_log().insert("status", 200);
+        first.as_mut_log().insert("request.method", "GET");
+        first
+            .as_mut_log()
+            .insert("timestamp", Utc.ymd(2020, 5, 1).and_hms_micro(1, 2, 3, 4));
+        let mut second = Event::new_empty_log();
+        second.as_mut_log().insert("message", "second");
+        second.as_mut_log().insert("latency", 1.5);
+        second.as_mut_log().insert("ok", true);
+
+        let encoder = Encoder::new(None, Compression::Snappy, 100);
+        let (_, rows) = read(encoder.encode(&[first, second]).unwrap());
+
+        assert_eq!(
+            columns(&rows[0]),
+            vec![
+                ("latency".into(), Field::Null),
+                ("message".into(), Field::Str("first".into())),
+                ("ok".into(), Field::Null),
+                ("request.method".into(), Field::Str("GET".into())),
+                ("status".into(), Field::Long(200)),
+                (
+                    "timestamp".into(),
+                    Field::TimestampMicros(1_588_294_923_000_004)
+                ),
+            ]
+        );
+        assert_eq!(
+            columns(&rows[1]),
+            vec![
+                ("latency".into(), Field::Double(1.5)),
+                ("message".into(), Field::Str("second".into())),
+                ("ok".into(), Field::Bool(true)),
+                ("request.method".into(), Field::Null),
+                ("status".into(), Field::Null),
+                ("timestamp".into(), Field::Null),
+            ]
+        );
+    }
+
+    #[test]
+    fn parquet_widens_mixed_types() {
+        let values: Vec<(Value, Value)> = vec![
+            (Value::Integer(1), Value::Integer(1)),
+            (Value::Float(2.5), "two".into()),
+        ];
+        let events = values
+            .into_iter()
+            .map(|(number, mixed)| {
+                let mut event = Event::new_empty_log();
+                event.as_mut_log().insert("number", number);
+                event.as_mut_log().insert("mixed", mixed);
+                event
+            })
+            .collect::<Vec<_>>();
+
+        let encoder = Encoder::new(None, Compression::None, 100);
+        let (_, rows) = read(encoder.encode(&events).unwrap());
+
+        assert_eq!(
+            columns(&rows[0]),
+            vec![
+                ("mixed".into(), Field::Str("1".into())),
+                ("number".into(), Field::Double(1.0)),
+            ]
+        );
+        assert_eq!(
+            columns(&rows[1]),
+            vec![
+                ("mixed".into(), Field::Str("two".into())),
+                ("number".into(), Field::Double(2.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parquet_uses_configured_schema() {
+        let schema = vec![
+            ("status".to_string(), ColumnType::Int64),
+            ("message".to_string(), ColumnType::String),
+            ("ok".to_string(), ColumnType::Boolean),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("message", "hello");
+        event.as_mut_log().insert("status", "404");
+        event.as_mut_log().insert("ok", "maybe");
+        event.as_mut_log().insert("dropped", "not in the schema");
+
+        let encoder = Encoder::new(Some(schema), Compression::Gzip, 100);
+        let (_, rows) = read(encoder.encode(&[event]).unwrap());
+
+        assert_eq!(
+            columns(&rows[0]),
+            vec![
+                ("status".into(), Field::Long(404)),
+                ("message".into(), Field::Str("hello".into())),
+                ("ok".into(), Field::Null),
+            ]
+        );
+    }
+
+    #[test]
+    fn parquet_splits_row_groups() {
+        let events = (0..10)
+            .map(|n| {
+                let mut event = Event::new_empty_log();
+                event.as_mut_log().insert("message", format!("line {}", n));
+                event
+            })
+            .collect::<Vec<_>>();
+
+        let encoder = Encoder::new(None, Compression::Snappy, 4);
+        let (reader, rows) = read(encoder.encode(&events).unwrap());
+
+        let metadata = reader.metadata();
+        assert_eq!(metadata.num_row_groups(), 3);
+        assert_eq!(metadata.row_group(0).num_rows(), 4);
+        assert_eq!(metadata.row_group(2).num_rows(), 2);
+        assert_eq!(rows.len(), 10);
+        assert_eq!(rows[9].get_string(0).unwrap(), "line 9");
+    }
+}
@@ -0,0 +1,375 @@
+mod encoder;
+
+pub use encoder::{ColumnType, Compression};
+
+use crate::{
+    event::Event,
+    region::RegionOrEndpoint,
+    sinks::{
+        aws_s3::{Request as S3Request, S3Options, S3RetryLogic, S3Sink},
+        util::{
+            BatchConfig, BatchSettings, DeadLetter, PartitionBatchSink, PartitionBuffer,
+            PartitionInnerBuffer, ProxyConfig, ServiceBuilderExt, TowerRequestConfig,
+        },
+    },
+    template::Template,
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use bytes::Bytes;
+use chrono::Utc;
+use encoder::Encoder;
+use futures::{FutureExt, TryFutureExt};
+use futures01::{future, stream::iter_ok, Future, Poll, Sink};
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{convert::TryInto, io, path::PathBuf, sync::Arc};
+use tower::{Service, ServiceBuilder};
+use uuid::Uuid;
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ParquetSinkConfig {
+    /// Local directory files are written to.
+    pub path: Option<Template>,
+    /// S3 bucket files are uploaded to, instead of a local directory.
+    pub bucket: Option<String>,
+    pub key_prefix: Option<Template>,
+    pub filename_time_format: Option<String>,
+    /// Columns to write, in order. Inferred from each batch when unset.
+    pub schema: Option<IndexMap<String, ColumnType>>,
+    #[serde(default)]
+    pub compression: Compression,
+    pub row_group_size: Option<usize>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    #[serde(flatten)]
+    options: S3Options,
+    #[serde(flatten)]
+    pub region: RegionOrEndpoint,
+    pub assume_role: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+}
+
+lazy_static! {
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        in_flight_limit: Some(50),
+        rate_limit_num: Some(250),
+        ..Default::default()
+    };
+}
+
+inventory::submit! {
+    SinkDescription::new::<ParquetSinkConfig>("parquet")
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("Must set either 'path' or 'bucket'"))]
+    MissingDestination,
+    #[snafu(display("Only one of 'path' or 'bucket' can be specified"))]
+    BothPathAndBucket,
+    #[snafu(display("'row_group_size' must be greater than 0"))]
+    EmptyRowGroups,
+}
+
+#[typetag::serde(name = "parquet")]
+impl SinkConfig for ParquetSinkConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let row_group_size = self.row_group_size.unwrap_or(10_000);
+        if row_group_size == 0 {
+            return Err(BuildError::EmptyRowGroups.into());
+        }
+
+        let encoder = Arc::new(Encoder::new(
+            self.schema.clone(),
+            self.compression,
+            row_group_size,
+        ));
+        let filename_time_format = self
+            .filename_time_format
+            .clone()
+            .unwrap_or_else(|| "%s".into());
+        let batch = self
+            .batch
+            .unwrap_events_only(BatchSettings::default().events(100_000).timeout(300))?;
+
+        match (&self.path, &self.bucket) {
+            (Some(path), None) => {
+                let svc = ServiceBuilder::new()
+                    .request_metrics(&cx)
+                    .service(ParquetService {
+                        inner: LocalFiles,
+                        encoder,
+                        filename_time_format,
+                        dead_letter: cx.dead_letter(),
+                        request: |name: String, body| (PathBuf::from(name), body),
+                    });
+                let sink = batch_sink(svc, path.clone(), batch, &cx);
+
+                Ok((sink, Box::new(future::ok(()))))
+            }
+            (None, Some(bucket)) => {
+                let client = S3Sink::create_client(
+                    self.region.clone().try_into()?,
+                    self.assume_role.clone(),
+                    cx.resolver(),
+                    &self.proxy,
+                )?;
+                let healthcheck = S3Sink::bucket_healthcheck(client.clone(), bucket.clone());
+
+                let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+                let bucket = bucket.clone();
+                let options = self.options.clone();
+                let s3 = ServiceBuilder::new()
+                    .settings(request, S3RetryLogic)
+                    .request_metrics(&cx)
+                    .service(S3Sink::with_client(client));
+                let svc = ParquetService {
+                    inner: s3,
+                    encoder,
+                    filename_time_format,
+                    dead_letter: cx.dead_letter(),
+                    request: move |key, body| S3Request {
+                        body,
+                        bucket: bucket.clone(),
+                        key,
+                        content_encoding: None,
+                        options: options.clone(),
+                    },
+                };
+                let key_prefix = self
+                    .key_prefix
+                    .clone()
+                    .unwrap_or_else(|| Template::from("date=%F/"));
+                let sink = batch_sink(svc, key_prefix, batch, &cx);
+
+                Ok((sink, healthcheck))
+            }
+            (Some(_), Some(_)) => Err(BuildError::BothPathAndBucket.into()),
+            (None, None) => Err(BuildError::MissingDestination.into()),
+        }
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "parquet"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+/// Batches events per rendered `prefix`, which the file names are appended to.
+fn batch_sink<S>(
+    svc: S,
+    prefix: Template,
+    batch: BatchSettings,
+    cx: &SinkContext,
+) -> super::RouterSink
+where
+    S: Service<PartitionInnerBuffer<Vec<Event>, Bytes>> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<crate::Error> + Send + 'static,
+    S::Response: std::fmt::Debug,
+{
+    let buffer = PartitionBuffer::new(Vec::new());
+    let sink = PartitionBatchSink::new(svc, buffer, batch, cx.acker())
+        .component(cx.name())
+        .with_flat_map(move |event| iter_ok(partition(event, &prefix)))
+        .sink_map_err(|error| error!("Sink failed to flush: {}", error));
+
+    Box::new(sink)
+}
+
+fn partition(event: Event, prefix: &Template) -> Option<PartitionInnerBuffer<Event, Bytes>> {
+    let prefix = prefix
+        .render(&event)
+        .map_err(|missing_keys| {
+            warn!(
+                message = "Keys do not exist on the event. Dropping event.",
+                ?missing_keys,
+                rate_limit_secs = 30,
+            );
+        })
+        .ok()?;
+
+    Some(PartitionInnerBuffer::new(event, prefix))
+}
+
+/// Encodes each batch as a Parquet file and hands it to `inner`, built into
+/// a request by `request` from the file's name and contents.
+struct ParquetService<S, F> {
+    inner: S,
+    encoder: Arc<Encoder>,
+    filename_time_format: String,
+    dead_letter: DeadLetter,
+    request: F,
+}
+
+impl<S, F, R> Service<PartitionInnerBuffer<Vec<Event>, Bytes>> for ParquetService<S, F>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+    S::Error: Into<crate::Error>,
+    F: Fn(String, Vec<u8>) -> R,
+{
+    type Response = ();
+    type Error = crate::Error;
+    type Future = Box<dyn Future<Item = (), Error = crate::Error> + Send>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: PartitionInnerBuffer<Vec<Event>, Bytes>) -> Self::Future {
+        let (events, prefix) = req.into_parts();
+
+        let body = match self.encoder.encode(&events) {
+            Ok(body) => body,
+            Err(error) => {
+                error!(message = "Failed to encode events as Parquet; dropping them.", %error);
+                for event in events {
+                    self.dead_letter
+                        .send(event, "Events could not be encoded as Parquet.");
+                }
+                return Box::new(future::ok(()));
+            }
+        };
+
+        let name = format!(
+            "{}{}-{}.parquet",
+            String::from_utf8_lossy(&prefix[..]),
+            Utc::now().format(&self.filename_time_format),
+            Uuid::new_v4().to_hyphenated()
+        );
+        debug!(message = "writing Parquet file.", %name, events = events.len(), bytes = body.len());
+
+        let response = self.inner.call((self.request)(name, body));
+        Box::new(response.map(|_| ()).map_err(Into::into))
+    }
+}
+
+/// Writes files to the local file system, creating missing directories.
+struct LocalFiles;
+
+impl Service<(PathBuf, Vec<u8>)> for LocalFiles {
+    type Response = ();
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = (), Error = io::Error> + Send>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(().into())
+    }
+
+    fn call(&mut self, (path, body): (PathBuf, Vec<u8>)) -> Self::Future {
+        let write = async move {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, body).await
+        };
+
+        Box::new(write.boxed().compat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event,
+        test_util::{random_events_with_stream, runtime},
+    };
+    use futures01::Stream;
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+    use std::fs::{self, File};
+
+    #[test]
+    fn parquet_requires_one_destination() {
+        let rt = runtime();
+
+        let config: ParquetSinkConfig = toml::from_str("").unwrap();
+        assert!(config.build(SinkContext::new_test(rt.executor())).is_err());
+
+        let config: ParquetSinkConfig = toml::from_str(
+            r#"
+            path = "/tmp/parquet/"
+            bucket = "bucket"
+            "#,
+        )
+        .unwrap();
+        assert!(config.build(SinkContext::new_test(rt.executor())).is_err());
+
+        let config: ParquetSinkConfig = toml::from_str(
+            r#"
+            path = "/tmp/parquet/"
+            row_group_size = 0
+            "#,
+        )
+        .unwrap();
+        assert!(config.build(SinkContext::new_test(rt.executor())).is_err());
+    }
+
+    #[test]
+    fn parquet_writes_local_files() {
+        let mut rt = runtime();
+        let directory = tempfile::tempdir().unwrap();
+
+        let config: ParquetSinkConfig = toml::from_str(&format!(
+            r#"
+            path = "{}/{{{{ source }}}}/"
+            row_group_size = 3
+            "#,
+            directory.path().display()
+        ))
+        .unwrap();
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+
+        let (input, events) = random_events_with_stream(20, 10);
+        let events = events.map(|mut event| {
+            event.as_mut_log().insert("source", "app");
+            event
+        });
+        rt.block_on(sink.send_all(events)).unwrap();
+
+        let files = fs::read_dir(directory.path().join("app"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "parquet");
+
+        let reader = SerializedFileReader::new(File::open(&files[0]).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 4);
+
+        let messages = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.get_column_iter()
+                    .find(|(name, _)| name.as_str() == &*event::log_schema().message_key())
+                    .map(|(_, field)| field.clone())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let expected = input
+            .iter()
+            .map(|input| {
+                Field::Str(input.as_log()[&event::log_schema().message_key()].to_string_lossy())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(messages, expected);
+    }
+}
@@ -0,0 +1,611 @@
+//! Prometheus remote write sink
+//!
+//! Encodes metrics as a snappy compressed `WriteRequest` protobuf and sends
+//! it to any receiver of the remote write protocol, like Cortex, Thanos or
+//! Mimir.
+//!
+//! https://prometheus.io/docs/prometheus/latest/storage/#remote-storage-integrations
+//!
+//! Receivers reject series whose labels aren't sorted by name, and samples
+//! that aren't sorted by timestamp or repeat one, so each request groups the
+//! batch by label set in `build_request`. Prometheus counters are
+//! cumulative, which incremental counters are turned into in `encode_event`,
+//! before the batch is built, so retried requests don't count them twice.
+
+use crate::{
+    dns::Resolver,
+    event::metric::{Metric, MetricKind, MetricValue},
+    runtime::FutureExt,
+    sinks::util::{
+        http::{Auth, BatchedHttpSink, HttpClient, HttpSink},
+        BatchConfig, BatchSettings, TowerRequestConfig, UriSerde,
+    },
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+    Event,
+};
+use chrono::Utc;
+use futures01::Sink;
+use http::Uri;
+use lazy_static::lazy_static;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/prometheus.rs"));
+}
+
+const NAME_LABEL: &str = "__name__";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteWriteConfig {
+    pub endpoint: UriSerde,
+    pub namespace: Option<String>,
+    pub auth: Option<Auth>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    pub tls: Option<TlsOptions>,
+}
+
+lazy_static! {
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        retry_attempts: Some(5),
+        ..Default::default()
+    };
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<RemoteWriteConfig>("prometheus_remote_write")
+}
+
+#[typetag::serde(name = "prometheus_remote_write")]
+impl SinkConfig for RemoteWriteConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+        let batch = self
+            .batch
+            .unwrap_events_only(BatchSettings::default().events(1_000).timeout(1))?;
+        let tls = TlsSettings::from_options(&self.tls)?;
+
+        let sink = BatchedHttpSink::new(
+            RemoteWriteSink::new(self.clone()),
+            Vec::new(),
+            request,
+            batch,
+            Some(tls),
+            &cx,
+        )
+        .sink_map_err(|e| error!("Fatal prometheus_remote_write sink error: {}", e));
+
+        let healthcheck = healthcheck(self.clone(), cx.resolver()).boxed_compat();
+
+        Ok((Box::new(sink), Box::new(healthcheck)))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "prometheus_remote_write"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+/// A series, by name and tags, and whether it is a counter.
+type SeriesKey = (String, Option<BTreeMap<String, String>>, bool);
+
+struct RemoteWriteSink {
+    config: RemoteWriteConfig,
+    /// Running totals of incremental counters and gauges.
+    totals: Mutex<HashMap<SeriesKey, f64>>,
+}
+
+impl RemoteWriteSink {
+    fn new(config: RemoteWriteConfig) -> Self {
+        Self {
+            config,
+            totals: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl HttpSink for RemoteWriteSink {
+    type Input = Metric;
+    type Output = Vec<Metric>;
+
+    fn encode_event(&self, event: Event) -> Option<Self::Input> {
+        let mut metric = event.into_metric();
+
+        let (value, is_counter) = match &mut metric.value {
+            MetricValue::Counter { value } => (value, true),
+            MetricValue::Gauge { value } => (value, false),
+            MetricValue::Distribution { .. } => {
+                warn!(
+                    message = "Distributions can't be written to Prometheus; dropping metric. Convert them to aggregated histograms first.",
+                    name = &metric.name[..],
+                    rate_limit_secs = 30,
+                );
+                return None;
+            }
+            _ => return Some(metric),
+        };
+
+        let key = (metric.name.clone(), metric.tags.clone(), is_counter);
+        let mut totals = self.totals.lock().expect("lock poisoned");
+        match metric.kind {
+            MetricKind::Incremental => {
+                let total = totals.entry(key).or_insert(0.0);
+                *total += *value;
+                *value = *total;
+                metric.kind = MetricKind::Absolute;
+            }
+            // Later incremental values carry on from here.
+            MetricKind::Absolute => {
+                totals.insert(key, *value);
+            }
+        }
+
+        Some(metric)
+    }
+
+    fn build_request(&self, metrics: Self::Output) -> http::Request<Vec<u8>> {
+        let request = encode_request(
+            self.config.namespace.as_deref(),
+            metrics,
+            Utc::now().timestamp_millis(),
+        );
+
+        build_request(&self.config, &request)
+    }
+}
+
+fn build_request(
+    config: &RemoteWriteConfig,
+    request: &proto::WriteRequest,
+) -> http::Request<Vec<u8>> {
+    let mut body = Vec::with_capacity(request.encoded_len());
+    request.encode(&mut body).expect("vec grows as needed");
+    let body = snap::raw::Encoder::new()
+        .compress_vec(&body)
+        .expect("batches are far below the snappy size limit");
+
+    let mut builder = http::Request::post(Uri::clone(&config.endpoint));
+    builder.header("Content-Type", "application/x-protobuf");
+    builder.header("Content-Encoding", "snappy");
+    builder.header("X-Prometheus-Remote-Write-Version", "0.1.0");
+
+    let mut request = builder.body(body).unwrap();
+    if let Some(auth) = &config.auth {
+        auth.apply(&mut request);
+    }
+
+    request
+}
+
+/// Groups `metrics` into one time series per label set. Metrics without a
+/// timestamp are sampled at `now`, in milliseconds.
+fn encode_request(namespace: Option<&str>, metrics: Vec<Metric>, now: i64) -> proto::WriteRequest {
+    // Both maps keep their keys sorted and unique, as receivers require.
+    // The last of several samples of a series at the same time wins.
+    let mut series: BTreeMap<Vec<(String, String)>, BTreeMap<i64, f64>> = BTreeMap::new();
+
+    for metric in metrics {
+        let timestamp = metric
+            .timestamp
+            .map(|ts| ts.timestamp_millis())
+            .unwrap_or(now);
+        let name = encode_name(namespace, &metric.name);
+        let mut push = |suffix: &str, extra: Option<(&str, String)>, value: f64| {
+            let labels = encode_labels(format!("{}{}", name, suffix), &metric.tags, extra);
+            series
+                .entry(labels)
+                .or_default()
+                .insert(timestamp, encode_value(value));
+        };
+
+        match &metric.value {
+            MetricValue::Counter { value } | MetricValue::Gauge { value } => push("", None, *value),
+            MetricValue::Set { values } => push("", None, values.len() as f64),
+            MetricValue::AggregatedHistogram {
+                buckets,
+                counts,
+                count,
+                sum,
+            } => {
+                for (b, c) in buckets.iter().zip(counts.iter()) {
+                    push("_bucket", Some(("le", b.to_string())), *c as f64);
+                }
+                push("_bucket", Some(("le", "+Inf".to_owned())), *count as f64);
+                push("_sum", None, *sum);
+                push("_count", None, *count as f64);
+            }
+            MetricValue::AggregatedSummary {
+                quantiles,
+                values,
+                count,
+                sum,
+            } => {
+                for (q, v) in quantiles.iter().zip(values.iter()) {
+                    push("", Some(("quantile", q.to_string())), *v);
+                }
+                push("_sum", None, *sum);
+                push("_count", None, *count as f64);
+            }
+            // Dropped in `encode_event`.
+            MetricValue::Distribution { .. } => (),
+        }
+    }
+
+    let timeseries = series
+        .into_iter()
+        .map(|(labels, samples)| proto::TimeSeries {
+            labels: labels
+                .into_iter()
+                .map(|(name, value)| proto::Label { name, value })
+                .collect(),
+            samples: samples
+                .into_iter()
+                .map(|(timestamp, value)| proto::Sample { value, timestamp })
+                .collect(),
+        })
+        .collect();
+
+    proto::WriteRequest { timeseries }
+}
+
+fn encode_name(namespace: Option<&str>, name: &str) -> String {
+    let name = match namespace {
+        Some(namespace) if !namespace.is_empty() => format!("{}_{}", namespace, name),
+        _ => name.to_owned(),
+    };
+    sanitize(&name, true)
+}
+
+/// Builds the label set of a series, sorted by name. The `__name__` label
+/// always holds the metric name, even if the metric has a tag of that name,
+/// and tags with an empty value are left out, as Prometheus treats those as
+/// missing.
+fn encode_labels(
+    name: String,
+    tags: &Option<BTreeMap<String, String>>,
+    extra: Option<(&str, String)>,
+) -> Vec<(String, String)> {
+    let mut labels = BTreeMap::new();
+    for (tag, value) in tags.iter().flatten() {
+        if !value.is_empty() {
+            labels.insert(sanitize(tag, false), value.clone());
+        }
+    }
+    if let Some((label, value)) = extra {
+        labels.insert(label.to_owned(), value);
+    }
+    labels.insert(NAME_LABEL.to_owned(), name);
+
+    labels.into_iter().collect()
+}
+
+/// Replaces the characters Prometheus doesn't allow in metric names, or
+/// label names if `colons` is false, with underscores.
+fn sanitize(name: &str, colons: bool) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+            ':' if colons => c,
+            _ => '_',
+        })
+        .collect();
+    if sanitized
+        .chars()
+        .next()
+        .map_or(true, |c| c.is_ascii_digit())
+    {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Prometheus marks a series as stale with a NaN of a particular bit
+/// pattern. Any NaN coming from upstream is sent as the ordinary NaN, so
+/// that a series can't be ended by accident.
+fn encode_value(value: f64) -> f64 {
+    if value.is_nan() {
+        std::f64::NAN
+    } else {
+        value
+    }
+}
+
+async fn healthcheck(config: RemoteWriteConfig, resolver: Resolver) -> crate::Result<()> {
+    let tls = TlsSettings::from_options(&config.tls)?;
+    let mut client = HttpClient::new(resolver, tls)?;
+
+    // There is no standard health endpoint, but all receivers accept an
+    // empty write.
+    let request = build_request(&config, &proto::WriteRequest::default()).map(hyper::Body::from);
+    let response = client.send(request).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(super::HealthcheckError::UnexpectedStatus { status }.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{offset::TimeZone, DateTime};
+
+    const STALE_NAN: u64 = 0x7ff0_0000_0000_0002;
+
+    fn sink() -> RemoteWriteSink {
+        RemoteWriteSink::new(
+            toml::from_str(
+                r#"
+                endpoint = "http://localhost:9009/api/v1/push"
+                namespace = "vector"
+                auth.strategy = "bearer"
+                auth.token = "secret"
+                "#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn ts(secs: u32) -> DateTime<Utc> {
+        Utc.ymd(2020, 1, 1).and_hms(0, 0, secs)
+    }
+
+    fn metric(
+        name: &str,
+        host: &str,
+        timestamp: u32,
+        kind: MetricKind,
+        value: MetricValue,
+    ) -> Event {
+        let mut tags = BTreeMap::new();
+        tags.insert("host".to_owned(), host.to_owned());
+        Event::Metric(Metric {
+            name: name.to_owned(),
+            timestamp: Some(ts(timestamp)),
+            tags: Some(tags),
+            kind,
+            value,
+        })
+    }
+
+    fn send(sink: &RemoteWriteSink, events: Vec<Event>) -> proto::WriteRequest {
+        let metrics = events
+            .into_iter()
+            .filter_map(|event| sink.encode_event(event))
+            .collect();
+        let request = sink.build_request(metrics);
+
+        let headers = request.headers();
+        assert_eq!(headers["Content-Encoding"], "snappy");
+        assert_eq!(headers["Content-Type"], "application/x-protobuf");
+        assert_eq!(headers["Authorization"], "Bearer secret");
+
+        let body = snap::raw::Decoder::new()
+            .decompress_vec(request.body())
+            .unwrap();
+        proto::WriteRequest::decode(&body[..]).unwrap()
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Vec<proto::Label> {
+        pairs
+            .iter()
+            .map(|&(name, value)| proto::Label {
+                name: name.to_owned(),
+                value: value.to_owned(),
+            })
+            .collect()
+    }
+
+    fn samples(pairs: &[(u32, f64)]) -> Vec<proto::Sample> {
+        pairs
+            .iter()
+            .map(|&(secs, value)| proto::Sample {
+                value,
+                timestamp: ts(secs).timestamp_millis(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prometheus_remote_write_groups_and_sorts_series() {
+        let sink = sink();
+        let gauge = |host, secs, value| {
+            metric(
+                "memory",
+                host,
+                secs,
+                MetricKind::Absolute,
+                MetricValue::Gauge { value },
+            )
+        };
+
+        let request = send(
+            &sink,
+            vec![
+                gauge("web-2", 3, 30.0),
+                gauge("web-1", 2, 20.0),
+                gauge("web-1", 1, 10.0),
+                gauge("web-1", 2, 25.0),
+            ],
+        );
+
+        assert_eq!(
+            request.timeseries,
+            vec![
+                proto::TimeSeries {
+                    labels: labels(&[("__name__", "vector_memory"), ("host", "web-1")]),
+                    samples: samples(&[(1, 10.0), (2, 25.0)]),
+                },
+                proto::TimeSeries {
+                    labels: labels(&[("__name__", "vector_memory"), ("host", "web-2")]),
+                    samples: samples(&[(3, 30.0)]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn prometheus_remote_write_accumulates_incremental_counters() {
+        let sink = sink();
+        let counter = |secs, kind, value| {
+            metric(
+                "requests",
+                "web-1",
+                secs,
+                kind,
+                MetricValue::Counter { value },
+            )
+        };
+
+        let first = send(
+            &sink,
+            vec![
+                counter(1, MetricKind::Incremental, 1.0),
+                counter(2, MetricKind::Incremental, 2.0),
+            ],
+        );
+        assert_eq!(first.timeseries[0].samples, samples(&[(1, 1.0), (2, 3.0)]));
+
+        // Totals carry over to the next batch, and from absolute values.
+        let second = send(
+            &sink,
+            vec![
+                counter(3, MetricKind::Incremental, 4.0),
+                counter(4, MetricKind::Absolute, 100.0),
+                counter(5, MetricKind::Incremental, 1.0),
+            ],
+        );
+        assert_eq!(
+            second.timeseries[0].samples,
+            samples(&[(3, 7.0), (4, 100.0), (5, 101.0)])
+        );
+    }
+
+    #[test]
+    fn prometheus_remote_write_encodes_histograms() {
+        let request = send(
+            &sink(),
+            vec![metric(
+                "latency",
+                "web-1",
+                1,
+                MetricKind::Absolute,
+                MetricValue::AggregatedHistogram {
+                    buckets: vec![0.5, 1.0],
+                    counts: vec![4, 6],
+                    count: 7,
+                    sum: 5.5,
+                },
+            )],
+        );
+
+        let series: Vec<_> = request
+            .timeseries
+            .iter()
+            .map(|series| (series.labels.clone(), series.samples[0].value))
+            .collect();
+        assert_eq!(
+            series,
+            vec![
+                (
+                    labels(&[
+                        ("__name__", "vector_latency_bucket"),
+                        ("host", "web-1"),
+                        ("le", "+Inf")
+                    ]),
+                    7.0
+                ),
+                (
+                    labels(&[
+                        ("__name__", "vector_latency_bucket"),
+                        ("host", "web-1"),
+                        ("le", "0.5")
+                    ]),
+                    4.0
+                ),
+                (
+                    labels(&[
+                        ("__name__", "vector_latency_bucket"),
+                        ("host", "web-1"),
+                        ("le", "1")
+                    ]),
+                    6.0
+                ),
+                (
+                    labels(&[("__name__", "vector_latency_count"), ("host", "web-1")]),
+                    7.0
+                ),
+                (
+                    labels(&[("__name__", "vector_latency_sum"), ("host", "web-1")]),
+                    5.5
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn prometheus_remote_write_sanitizes_names_and_labels() {
+        let mut tags = BTreeMap::new();
+        tags.insert("__name__".to_owned(), "spoofed".to_owned());
+        tags.insert("Zone".to_owned(), "eu".to_owned());
+        tags.insert("pod.name".to_owned(), "api-0".to_owned());
+        tags.insert("empty".to_owned(), "".to_owned());
+        let event = Event::Metric(Metric {
+            name: "http.requests-total".to_owned(),
+            timestamp: Some(ts(1)),
+            tags: Some(tags),
+            kind: MetricKind::Absolute,
+            value: MetricValue::Gauge {
+                value: f64::from_bits(STALE_NAN),
+            },
+        });
+
+        let request = send(&sink(), vec![event]);
+        let series = &request.timeseries[0];
+        assert_eq!(
+            series.labels,
+            labels(&[
+                ("Zone", "eu"),
+                ("__name__", "vector_http_requests_total"),
+                ("pod_name", "api-0"),
+            ])
+        );
+        assert!(series.samples[0].value.is_nan());
+        assert_ne!(series.samples[0].value.to_bits(), STALE_NAN);
+    }
+
+    #[test]
+    fn prometheus_remote_write_drops_distributions() {
+        let event = metric(
+            "latency",
+            "web-1",
+            1,
+            MetricKind::Incremental,
+            MetricValue::Distribution {
+                values: vec![1.0],
+                sample_rates: vec![1],
+            },
+        );
+
+        assert!(sink().encode_event(event).is_none());
+    }
+}
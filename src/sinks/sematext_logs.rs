@@ -1,7 +1,7 @@
 use crate::{
     sinks::elasticsearch::{ElasticSearchConfig, Encoding},
     sinks::util::{
-        encoding::EncodingConfigWithDefault, BatchBytesConfig, Compression, TowerRequestConfig,
+        encoding::EncodingConfigWithDefault, BatchConfig, Compression, TowerRequestConfig,
     },
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
     Event,
@@ -26,7 +26,7 @@ pub struct SematextLogsConfig {
     request: TowerRequestConfig,
 
     #[serde(default)]
-    batch: BatchBytesConfig,
+    batch: BatchConfig,
 }
 
 inventory::submit! {
@@ -79,6 +79,10 @@ impl SinkConfig for SematextLogsConfig {
     fn sink_type(&self) -> &'static str {
         "sematext"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 /// Used to map `timestamp` to `@timestamp`.
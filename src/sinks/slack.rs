@@ -0,0 +1,519 @@
+//! Posts messages to a Slack [incoming webhook], rendered from log events.
+//!
+//! [incoming webhook]: https://api.slack.com/messaging/webhooks
+
+use crate::{
+    dns::Resolver,
+    event::{log_schema, Event, LogEvent, Value},
+    sinks::util::{
+        http::{BatchedHttpSink, HttpClient, HttpRetryLogic, HttpSink, Response},
+        retries::{RetryAction, RetryLogic},
+        BatchConfig, BatchSettings, TowerRequestConfig, UriSerde, VecBuffer,
+    },
+    template::Template,
+    tls::{TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use futures::{compat::Future01CompatExt, TryFutureExt};
+use futures01::{Sink, Stream};
+use http::{Request, StatusCode, Uri};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map};
+use string_cache::DefaultAtom as Atom;
+
+/// Slack rejects messages with more blocks than this.
+const MAX_BLOCKS: usize = 50;
+
+lazy_static! {
+    // Slack allows about one message a second per webhook, and posting them
+    // one at a time keeps them in order.
+    static ref REQUEST_DEFAULTS: TowerRequestConfig = TowerRequestConfig {
+        in_flight_limit: Some(1),
+        rate_limit_duration_secs: Some(1),
+        rate_limit_num: Some(1),
+        ..Default::default()
+    };
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SlackConfig {
+    pub webhook_url: UriSerde,
+    /// The message, or the notification text of messages with blocks.
+    #[serde(default = "default_text")]
+    pub text: Template,
+    /// Block Kit blocks, where every string is a template.
+    pub blocks: Option<Vec<serde_json::Value>>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub request: TowerRequestConfig,
+    pub tls: Option<TlsOptions>,
+}
+
+fn default_text() -> Template {
+    Template::from("{{ message }}")
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<SlackConfig>("slack")
+}
+
+#[typetag::serde(name = "slack")]
+impl SinkConfig for SlackConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
+        let mut batch = self
+            .batch
+            .unwrap_or(BatchSettings::default().events(20).bytes(4000).timeout(1));
+        // Batched messages are merged into one, which must stay within
+        // Slack's limit on blocks.
+        let blocks_per_event = self.blocks.as_ref().map_or(0, Vec::len);
+        if blocks_per_event > 0 {
+            batch.size.events = batch.size.events.min(MAX_BLOCKS / blocks_per_event).max(1);
+        }
+        let tls_settings = TlsSettings::from_options(&self.tls)?;
+
+        let sink = BatchedHttpSink::with_retry_logic(
+            SlackSink::new(self),
+            VecBuffer::new(|message: &Message| message.text.len()),
+            SlackRetryLogic {
+                inner: HttpRetryLogic,
+            },
+            request,
+            batch,
+            tls_settings.clone(),
+            &Default::default(),
+            &cx,
+        )
+        .sink_map_err(|e| error!("Fatal slack sink error: {}", e));
+
+        let healthcheck = healthcheck(self.webhook_url.clone(), tls_settings, cx.resolver());
+        let healthcheck = Box::new(Box::pin(healthcheck).compat());
+
+        Ok((Box::new(sink), healthcheck))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "slack"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+/// A template of a JSON value, whose strings are templates.
+#[derive(Debug)]
+enum JsonTemplate {
+    String(Template),
+    Array(Vec<JsonTemplate>),
+    Object(Vec<(String, JsonTemplate)>),
+    Other(serde_json::Value),
+}
+
+impl JsonTemplate {
+    fn new(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(string) => JsonTemplate::String(string.as_str().into()),
+            serde_json::Value::Array(values) => {
+                JsonTemplate::Array(values.iter().map(JsonTemplate::new).collect())
+            }
+            serde_json::Value::Object(map) => JsonTemplate::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), JsonTemplate::new(value)))
+                    .collect(),
+            ),
+            value => JsonTemplate::Other(value.clone()),
+        }
+    }
+
+    fn fields(&self, fields: &mut Vec<Atom>) {
+        match self {
+            JsonTemplate::String(template) => {
+                fields.extend(template.get_fields().unwrap_or_default())
+            }
+            JsonTemplate::Array(values) => values.iter().for_each(|value| value.fields(fields)),
+            JsonTemplate::Object(map) => map.iter().for_each(|(_, value)| value.fields(fields)),
+            JsonTemplate::Other(_) => (),
+        }
+    }
+
+    /// Rendering builds a JSON value, rather than text, so whatever the
+    /// rendered strings contain is escaped when it's serialized.
+    fn render(&self, event: &Event) -> Result<serde_json::Value, Vec<Atom>> {
+        Ok(match self {
+            JsonTemplate::String(template) => template.render_string(event)?.into(),
+            JsonTemplate::Array(values) => values
+                .iter()
+                .map(|value| value.render(event))
+                .collect::<Result<_, _>>()?,
+            JsonTemplate::Object(map) => map
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), value.render(event)?)))
+                .collect::<Result<Map<_, _>, _>>()?
+                .into(),
+            JsonTemplate::Other(value) => value.clone(),
+        })
+    }
+}
+
+pub struct SlackSink {
+    webhook_url: Uri,
+    text: Template,
+    blocks: Vec<JsonTemplate>,
+    /// The fields the templates refer to.
+    fields: Vec<Atom>,
+}
+
+impl SlackSink {
+    fn new(config: &SlackConfig) -> Self {
+        let blocks = config
+            .blocks
+            .iter()
+            .flatten()
+            .map(JsonTemplate::new)
+            .collect::<Vec<_>>();
+
+        let mut fields = config.text.get_fields().unwrap_or_default();
+        blocks.iter().for_each(|block| block.fields(&mut fields));
+        fields.sort();
+        fields.dedup();
+
+        SlackSink {
+            webhook_url: config.webhook_url.clone().into(),
+            text: config.text.clone(),
+            blocks,
+            fields,
+        }
+    }
+
+    /// Escapes the fields the templates refer to, so that they're shown as
+    /// they are instead of being read as links or mentions, like
+    /// `<!channel>`.
+    fn escape(&self, event: &Event) -> Event {
+        let log = event.as_log();
+        let mut escaped = LogEvent::new();
+        for field in &self.fields {
+            if let Some(value) = log.get(field) {
+                escaped.insert(field.as_ref(), escape(&value.to_string_lossy()));
+            }
+        }
+        // Templates format the timestamp themselves.
+        if let Some(timestamp) = log.get(log_schema().timestamp_key()) {
+            escaped.insert(log_schema().timestamp_key().as_ref(), timestamp.clone());
+        }
+        escaped.into()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The message of a single event.
+#[derive(Debug, Clone)]
+pub struct Message {
+    text: String,
+    blocks: Vec<serde_json::Value>,
+}
+
+impl HttpSink for SlackSink {
+    type Input = Message;
+    type Output = Vec<Message>;
+
+    fn encode_event(&self, event: Event) -> Option<Self::Input> {
+        let event = self.escape(&event);
+
+        let rendered = self.text.render_string(&event).and_then(|text| {
+            let blocks = self
+                .blocks
+                .iter()
+                .map(|block| block.render(&event))
+                .collect::<Result<_, _>>()?;
+            Ok(Message { text, blocks })
+        });
+
+        match rendered {
+            Ok(message) => Some(message),
+            // Posting a message with parts missing would be misleading, so
+            // the event is skipped instead.
+            Err(missing_keys) => {
+                warn!(
+                    message = "Keys do not exist on the event; dropping event.",
+                    ?missing_keys,
+                    rate_limit_secs = 30,
+                );
+                None
+            }
+        }
+    }
+
+    fn build_request(&self, messages: Self::Output) -> http::Request<Vec<u8>> {
+        let text = messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let blocks = messages
+            .into_iter()
+            .flat_map(|message| message.blocks)
+            .collect::<Vec<_>>();
+
+        let body = if blocks.is_empty() {
+            json!({ "text": text })
+        } else {
+            json!({ "text": text, "blocks": blocks })
+        };
+
+        Request::post(self.webhook_url.clone())
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&body).unwrap())
+            .unwrap()
+    }
+}
+
+#[derive(Clone)]
+struct SlackRetryLogic {
+    inner: HttpRetryLogic,
+}
+
+impl RetryLogic for SlackRetryLogic {
+    type Response = Response;
+    type Error = hyper::Error;
+
+    fn is_retriable_error(&self, error: &Self::Error) -> bool {
+        self.inner.is_retriable_error(error)
+    }
+
+    fn should_retry_response(&self, response: &Self::Response) -> RetryAction {
+        let status = response.status();
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => RetryAction::Retry("Rate limited by Slack".into()),
+            // Slack explains why, like `invalid_blocks` or
+            // `channel_is_archived`, in the body.
+            _ if status.is_client_error() => RetryAction::DontRetry(
+                format!("{}: {}", status, String::from_utf8_lossy(response.body())).into(),
+            ),
+            _ => self.inner.should_retry_response(response),
+        }
+    }
+}
+
+/// Posting an empty message is rejected as a bad request by a working
+/// webhook, rather than with a not found or forbidden error.
+async fn healthcheck(
+    webhook_url: UriSerde,
+    tls_settings: TlsSettings,
+    resolver: Resolver,
+) -> crate::Result<()> {
+    let mut client = HttpClient::new(resolver, tls_settings)?;
+
+    let request = Request::post(Uri::from(webhook_url))
+        .header("Content-Type", "application/json")
+        .body(hyper::Body::from("{}"))
+        .unwrap();
+
+    let response = client.send(request).await?;
+
+    let status = response.status();
+    if status == StatusCode::BAD_REQUEST || status.is_success() {
+        Ok(())
+    } else {
+        let body = response.into_body().concat2().compat().await?;
+        Err(format!(
+            "Webhook returned {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runtime::Runtime,
+        sinks::util::test::build_test_server,
+        test_util::{next_addr, shutdown_on_idle},
+    };
+    use futures01::stream;
+
+    fn sink(config: &str) -> SlackSink {
+        let config: SlackConfig = toml::from_str(&format!(
+            "webhook_url = \"https://hooks.slack.com/services/T0/B0/X\"\n{}",
+            config
+        ))
+        .unwrap();
+        SlackSink::new(&config)
+    }
+
+    fn body(sink: &SlackSink, events: Vec<Event>) -> serde_json::Value {
+        let messages = events
+            .into_iter()
+            .map(|event| sink.encode_event(event).unwrap())
+            .collect();
+        serde_json::from_slice(sink.build_request(messages).body()).unwrap()
+    }
+
+    #[test]
+    fn slack_renders_text() {
+        let sink = sink(r#"text = "*{{ host }}*: {{ message }}""#);
+
+        let mut event = Event::from("Disk is \"full\"\nagain");
+        event.as_mut_log().insert("host", "db-1");
+
+        assert_eq!(
+            body(&sink, vec![event]),
+            json!({ "text": "*db-1*: Disk is \"full\"\nagain" })
+        );
+    }
+
+    #[test]
+    fn slack_escapes_field_values() {
+        let sink = sink(r#"text = "<https://status.example.com|Status>: {{ message }}""#);
+
+        let event = Event::from("<!channel> R&D is <down>");
+
+        assert_eq!(
+            body(&sink, vec![event]),
+            json!({
+                "text": "<https://status.example.com|Status>: &lt;!channel&gt; R&amp;D is &lt;down&gt;"
+            })
+        );
+    }
+
+    #[test]
+    fn slack_renders_blocks() {
+        let sink = sink(
+            r#"
+            text = "{{ host }} alert"
+
+            [[blocks]]
+            type = "section"
+            text = { type = "mrkdwn", text = "*{{ host }}*\n{{ message }}" }
+
+            [[blocks]]
+            type = "divider"
+            "#,
+        );
+
+        let mut first = Event::from("Disk \"/\" is full");
+        first.as_mut_log().insert("host", "db-1");
+        let mut second = Event::from("Load is high");
+        second.as_mut_log().insert("host", "db-2");
+
+        assert_eq!(
+            body(&sink, vec![first, second]),
+            json!({
+                "text": "db-1 alert\ndb-2 alert",
+                "blocks": [
+                    {
+                        "type": "section",
+                        "text": { "type": "mrkdwn", "text": "*db-1*\nDisk \"/\" is full" },
+                    },
+                    { "type": "divider" },
+                    {
+                        "type": "section",
+                        "text": { "type": "mrkdwn", "text": "*db-2*\nLoad is high" },
+                    },
+                    { "type": "divider" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn slack_skips_events_missing_fields() {
+        let sink = sink(
+            r#"
+            [[blocks]]
+            type = "section"
+            text = { type = "plain_text", text = "{{ service }}" }
+            "#,
+        );
+
+        assert!(sink.encode_event(Event::from("hello")).is_none());
+
+        let mut event = Event::from("hello");
+        event.as_mut_log().insert("service", "api");
+        assert!(sink.encode_event(event).is_some());
+    }
+
+    #[test]
+    fn slack_does_not_retry_rejected_messages() {
+        let logic = SlackRetryLogic {
+            inner: HttpRetryLogic,
+        };
+        let response = |status: u16, body: &'static str| {
+            http::Response::builder()
+                .status(status)
+                .body(bytes::Bytes::from(body))
+                .unwrap()
+        };
+
+        assert!(logic
+            .should_retry_response(&response(429, "rate_limited"))
+            .is_retryable());
+        assert!(logic
+            .should_retry_response(&response(500, ""))
+            .is_retryable());
+        assert!(logic
+            .should_retry_response(&response(200, "ok"))
+            .is_successful());
+        match logic.should_retry_response(&response(400, "invalid_blocks")) {
+            RetryAction::DontRetry(reason) => assert!(reason.contains("invalid_blocks")),
+            _ => panic!("rejected messages shouldn't be retried"),
+        }
+    }
+
+    #[test]
+    fn slack_posts_batched_messages() {
+        let addr = next_addr();
+        let config: SlackConfig = toml::from_str(&format!(
+            r#"
+            webhook_url = "http://{}/services/T0/B0/X"
+            batch.max_events = 2
+            "#,
+            addr
+        ))
+        .unwrap();
+
+        let mut rt = Runtime::new().unwrap();
+        let cx = SinkContext::new_test(rt.executor());
+        let (sink, _) = config.build(cx).unwrap();
+
+        let (rx, trigger, server) = build_test_server(&addr);
+        rt.spawn(server);
+
+        let events = vec!["one", "two", "three"]
+            .into_iter()
+            .map(Event::from)
+            .collect::<Vec<_>>();
+        let pump = sink.send_all(stream::iter_ok(events));
+        let _ = rt.block_on(pump).unwrap();
+        drop(trigger);
+
+        let requests = rx.take(2).wait().map(Result::unwrap).collect::<Vec<_>>();
+        shutdown_on_idle(rt);
+
+        let texts = requests
+            .into_iter()
+            .map(|(parts, body)| {
+                assert_eq!(parts.method, http::Method::POST);
+                assert_eq!(parts.uri.path(), "/services/T0/B0/X");
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                body["text"].as_str().unwrap().to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["one\ntwo", "three"]);
+    }
+}
@@ -35,9 +35,8 @@ impl SocketSinkConfig {
         tls: Option<TlsConfig>,
     ) -> Self {
         TcpSinkConfig {
-            address,
-            encoding,
             tls,
+            ..TcpSinkConfig::new(address, encoding)
         }
         .into()
     }
@@ -131,11 +130,7 @@ mod test {
     fn tcp_stream() {
         let addr = next_addr();
         let config = SocketSinkConfig {
-            mode: Mode::Tcp(TcpSinkConfig {
-                address: addr.to_string(),
-                encoding: Encoding::Json.into(),
-                tls: None,
-            }),
+            mode: Mode::Tcp(TcpSinkConfig::new(addr.to_string(), Encoding::Json.into())),
         };
         let mut rt = runtime();
         let context = SinkContext::new_test(rt.executor());
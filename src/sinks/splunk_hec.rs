@@ -4,7 +4,7 @@ use crate::{
     sinks::util::{
         encoding::{EncodingConfigWithDefault, EncodingConfiguration},
         http::{BatchedHttpSink, HttpClient, HttpSink},
-        BatchBytesConfig, Buffer, Compression, TowerRequestConfig,
+        BatchConfig, BatchSettings, Buffer, Compression, TowerRequestConfig,
     },
     tls::{TlsOptions, TlsSettings},
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
@@ -43,7 +43,7 @@ pub struct HecSinkConfig {
     pub encoding: EncodingConfigWithDefault<Encoding>,
     pub compression: Option<Compression>,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
     #[serde(default)]
     pub request: TowerRequestConfig,
     pub tls: Option<TlsOptions>,
@@ -80,7 +80,11 @@ impl SinkConfig for HecSinkConfig {
         validate_host(&self.host)?;
         let healthcheck = healthcheck(&self, cx.resolver())?;
 
-        let batch = self.batch.unwrap_or(bytesize::mib(1u64), 1);
+        let batch = self.batch.unwrap_or(
+            BatchSettings::default()
+                .bytes(bytesize::mib(1u64))
+                .timeout(1),
+        );
         let request = self.request.unwrap_with(&REQUEST_DEFAULTS);
         let tls_settings = TlsSettings::from_options(&self.tls)?;
 
@@ -104,6 +108,10 @@ impl SinkConfig for HecSinkConfig {
     fn sink_type(&self) -> &'static str {
         "splunk_hec"
     }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
 }
 
 impl HttpSink for HecSinkConfig {
@@ -621,9 +629,10 @@ mod integration_tests {
             host_key: "host".into(),
             compression: Some(Compression::None),
             encoding: encoding.into(),
-            batch: BatchBytesConfig {
-                max_size: Some(1),
+            batch: BatchConfig {
+                max_bytes: Some(1),
                 timeout_secs: None,
+                ..Default::default()
             },
             indexed_fields,
             ..Default::default()
@@ -0,0 +1,308 @@
+use crate::{
+    event::Event,
+    sinks::util::{BatchConfig, BatchSettings, BatchSink, DeadLetter},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use futures::{FutureExt, TryFutureExt};
+use futures01::{future, Future, Poll, Sink};
+use rusqlite::{params, Connection, NO_PARAMS};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tower::{limit::concurrency::ConcurrencyLimit, Service};
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SqliteSinkConfig {
+    pub path: PathBuf,
+    #[serde(default = "default_table")]
+    pub table: String,
+    /// Lets readers query the database while events are written to it.
+    #[serde(default = "crate::serde::default_true")]
+    pub wal: bool,
+    #[serde(default)]
+    pub batch: BatchConfig,
+}
+
+fn default_table() -> String {
+    "events".into()
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<SqliteSinkConfig>("sqlite")
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("'table' can't be empty"))]
+    EmptyTable,
+    #[snafu(display("Unable to open {:?}: {}", path, source))]
+    Open {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+}
+
+/// How long a write waits for another connection to release its lock on
+/// the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[typetag::serde(name = "sqlite")]
+impl SinkConfig for SqliteSinkConfig {
+    fn build(&self, cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        if self.table.is_empty() {
+            return Err(BuildError::EmptyTable.into());
+        }
+
+        let writer = Writer::open(&self.path, &self.table, self.wal).context(Open {
+            path: self.path.clone(),
+        })?;
+        // Batches are inserted one at a time, so rows are in the order
+        // events came in.
+        let svc = ConcurrencyLimit::new(
+            SqliteService {
+                writer: Arc::new(Mutex::new(writer)),
+                dead_letter: cx.dead_letter(),
+            },
+            1,
+        );
+
+        let batch = self
+            .batch
+            .unwrap_events_only(BatchSettings::default().events(1000).timeout(1))?;
+        let sink = BatchSink::new(svc, Vec::new(), batch, cx.acker())
+            .component(cx.name())
+            .sink_map_err(|error| error!("Sink failed to flush: {}", error));
+
+        Ok((Box::new(sink), Box::new(future::ok(()))))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+}
+
+/// Inserts the events of a batch as rows of a table, one JSON column each.
+struct Writer {
+    conn: Connection,
+    table: String,
+    /// Whether the table is known to exist.
+    created: bool,
+}
+
+impl Writer {
+    fn open(path: &Path, table: &str, wal: bool) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        if wal {
+            // The pragma answers with the journal mode now in use.
+            conn.query_row("PRAGMA journal_mode = WAL", NO_PARAMS, |row| {
+                row.get::<_, String>(0)
+            })?;
+        }
+
+        Ok(Self {
+            conn,
+            table: quote_identifier(table),
+            created: false,
+        })
+    }
+
+    /// Inserts the events in a transaction, so a batch is inserted in full or
+    /// not at all. The table is created along with the first batch.
+    fn insert(&mut self, events: &[Event]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        if !self.created {
+            tx.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, event TEXT NOT NULL)",
+                    self.table
+                ),
+                NO_PARAMS,
+            )?;
+        }
+
+        {
+            let mut insert =
+                tx.prepare_cached(&format!("INSERT INTO {} (event) VALUES (?1)", self.table))?;
+            for event in events {
+                insert.execute(params![encode_event(event)])?;
+            }
+        }
+
+        // Dropping the transaction on an error above rolls it back.
+        tx.commit()?;
+        self.created = true;
+        Ok(())
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn encode_event(event: &Event) -> String {
+    match event {
+        Event::Log(log) => serde_json::to_string(log),
+        Event::Metric(metric) => serde_json::to_string(metric),
+        Event::Trace(trace) => serde_json::to_string(trace),
+    }
+    .expect("Events are always serializable")
+}
+
+/// Inserts each batch on a blocking thread.
+struct SqliteService {
+    writer: Arc<Mutex<Writer>>,
+    dead_letter: DeadLetter,
+}
+
+impl Service<Vec<Event>> for SqliteService {
+    type Response = ();
+    type Error = crate::Error;
+    type Future = Box<dyn Future<Item = (), Error = crate::Error> + Send>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(().into())
+    }
+
+    fn call(&mut self, events: Vec<Event>) -> Self::Future {
+        let writer = Arc::clone(&self.writer);
+        let mut dead_letter = self.dead_letter.clone();
+
+        let insert = async move {
+            let (events, result) = tokio::task::spawn_blocking(move || {
+                let result = writer.lock().unwrap().insert(&events);
+                (events, result)
+            })
+            .await?;
+
+            match result {
+                Ok(()) => debug!(message = "inserted events.", count = events.len()),
+                Err(error) => {
+                    error!(message = "Failed to insert events; dropping them.", %error);
+                    for event in events {
+                        dead_letter.send(event, "Events could not be inserted into SQLite.");
+                    }
+                }
+            }
+            Ok::<_, crate::Error>(())
+        };
+
+        Box::new(insert.boxed().compat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event,
+        test_util::{random_events_with_stream, runtime},
+    };
+
+    fn messages(path: &Path, table: &str) -> Vec<String> {
+        let conn = Connection::open(path).unwrap();
+        let mut select = conn
+            .prepare(&format!("SELECT event FROM {} ORDER BY id", table))
+            .unwrap();
+        let rows = select
+            .query_map(NO_PARAMS, |row| row.get::<_, String>(0))
+            .unwrap();
+        rows.map(|row| {
+            let event: serde_json::Value = serde_json::from_str(&row.unwrap()).unwrap();
+            event[&*event::log_schema().message_key()]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn sqlite_inserts_events() {
+        let mut rt = runtime();
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("events.db");
+
+        let config: SqliteSinkConfig = toml::from_str(&format!(
+            r#"
+            path = "{}"
+            table = "logs"
+            batch.max_events = 3
+            "#,
+            path.display()
+        ))
+        .unwrap();
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+
+        let (input, events) = random_events_with_stream(20, 10);
+        rt.block_on(sink.send_all(events)).unwrap();
+
+        let expected = input
+            .iter()
+            .map(|event| event.as_log()[&event::log_schema().message_key()].to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(messages(&path, "logs"), expected);
+
+        let conn = Connection::open(&path).unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn sqlite_rolls_back_failed_batches() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("events.db");
+
+        Connection::open(&path)
+            .unwrap()
+            .execute(
+                r#"CREATE TABLE events (
+                    id INTEGER PRIMARY KEY,
+                    event TEXT NOT NULL CHECK (event NOT LIKE '%"rejected"%')
+                )"#,
+                NO_PARAMS,
+            )
+            .unwrap();
+
+        let mut writer = Writer::open(&path, "events", true).unwrap();
+        writer
+            .insert(&[Event::from("first"), Event::from("second")])
+            .unwrap();
+        assert!(writer
+            .insert(&[
+                Event::from("third"),
+                Event::from("rejected"),
+                Event::from("fourth")
+            ])
+            .is_err());
+        writer.insert(&[Event::from("fifth")]).unwrap();
+
+        assert_eq!(messages(&path, "events"), vec!["first", "second", "fifth"]);
+    }
+
+    #[test]
+    fn sqlite_quotes_table_names() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("events.db");
+
+        let mut writer = Writer::open(&path, "my \"events\"", false).unwrap();
+        writer.insert(&[Event::from("hello")]).unwrap();
+
+        assert_eq!(messages(&path, "\"my \"\"events\"\"\""), vec!["hello"]);
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     buffers::Acker,
     event::metric::{MetricKind, MetricValue},
     event::Event,
-    sinks::util::{BatchBytesConfig, BatchSink, Buffer},
+    sinks::util::{BatchConfig, BatchSettings, BatchSink, Buffer},
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
 };
 use futures01::{future, stream::iter_ok, Future, Poll, Sink};
@@ -49,7 +49,7 @@ pub struct StatsdSinkConfig {
     #[serde(default = "default_address")]
     pub address: SocketAddr,
     #[serde(default)]
-    pub batch: BatchBytesConfig,
+    pub batch: BatchConfig,
 }
 
 pub fn default_address() -> SocketAddr {
@@ -84,7 +84,9 @@ impl StatsdSvc {
         // However we need to leave some space for +1 extra trailing event in the buffer.
         // Also one might keep an eye on server side limitations, like
         // mentioned here https://github.com/DataDog/dd-agent/issues/2638
-        let batch = config.batch.unwrap_or(1300, 1);
+        let batch = config
+            .batch
+            .unwrap_or(BatchSettings::default().bytes(1300).timeout(1));
         let namespace = config.namespace.clone();
 
         let client = Client::new(config.address)?;
@@ -323,9 +325,10 @@ mod test {
         let config = StatsdSinkConfig {
             namespace: "vector".into(),
             address: default_address(),
-            batch: BatchBytesConfig {
-                max_size: Some(512),
+            batch: BatchConfig {
+                max_bytes: Some(512),
                 timeout_secs: Some(1),
+                ..Default::default()
             },
         };
 
@@ -0,0 +1,633 @@
+//! Encodes log events as Avro datums against a user provided schema.
+//!
+//! Sinks offering an `avro` codec take an `avro` table of `AvroConfig`. When a
+//! schema id is configured, or registered with a Confluent Schema Registry,
+//! each datum is prefixed with the registry's wire format header: a zero magic
+//! byte followed by the big endian schema id.
+
+use super::{http::HttpClient, UriSerde};
+use crate::{
+    event::{LogEvent, Value},
+    sinks::RouterSink,
+    tls::TlsSettings,
+    topology::config::SinkContext,
+};
+use avro_rs::{schema::RecordField, types::Value as AvroValue, Schema};
+use futures::{compat::Future01CompatExt, FutureExt, TryFutureExt};
+use futures01::{try_ready, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use http::{Request, Uri};
+use hyper::Body;
+use lazy_static::lazy_static;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+use snafu::Snafu;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use string_cache::DefaultAtom as Atom;
+
+const MAGIC_BYTE: u8 = 0;
+
+lazy_static! {
+    /// Schema ids already registered, by registry URL, subject and the
+    /// schema's canonical form, so reloading a sink doesn't register again.
+    static ref REGISTERED_IDS: Mutex<HashMap<(String, String, String), u32>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AvroConfig {
+    /// The record schema events are encoded against, as JSON.
+    pub schema: AvroSchema,
+    #[serde(default)]
+    pub missing_fields: MissingFields,
+    /// The id written in the wire format header, for schemas already known
+    /// to the consumers' registry.
+    pub schema_id: Option<u32>,
+    pub schema_registry: Option<SchemaRegistryConfig>,
+    #[serde(skip)]
+    registered_id: RegisteredId,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaRegistryConfig {
+    pub url: UriSerde,
+    pub subject: String,
+}
+
+/// What to do with schema fields an event doesn't have.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum MissingFields {
+    /// Use the field's default, or null when the field is nullable.
+    #[derivative(Default)]
+    Default,
+    /// Fail to encode the event.
+    Error,
+}
+
+/// A parsed Avro schema, (de)serialized as its JSON definition.
+#[derive(Clone, Debug)]
+pub struct AvroSchema {
+    schema: Arc<Schema>,
+    definition: String,
+}
+
+impl AvroSchema {
+    pub fn parse(definition: &str) -> Result<Self, String> {
+        let schema = Schema::parse_str(definition).map_err(|error| error.to_string())?;
+        match schema {
+            Schema::Record { .. } => Ok(Self {
+                schema: Arc::new(schema),
+                definition: definition.into(),
+            }),
+            _ => Err("Avro schema must describe a record".into()),
+        }
+    }
+}
+
+impl Serialize for AvroSchema {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.definition)
+    }
+}
+
+impl<'de> Deserialize<'de> for AvroSchema {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let definition = String::deserialize(deserializer)?;
+        Self::parse(&definition).map_err(de::Error::custom)
+    }
+}
+
+/// The schema id returned by the registry, shared by every clone of a config.
+#[derive(Clone, Debug, Default)]
+struct RegisteredId(Arc<RwLock<Option<u32>>>);
+
+impl RegisteredId {
+    fn get(&self) -> Option<u32> {
+        *self.0.read().unwrap()
+    }
+
+    fn set(&self, id: u32) {
+        *self.0.write().unwrap() = Some(id);
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("The avro codec requires an `avro` table with a schema"))]
+    MissingAvroConfig,
+    #[snafu(display("An `avro` table is only used with the avro codec"))]
+    UnusedAvroConfig,
+    #[snafu(display("Only one of `schema_id` or `schema_registry` can be set"))]
+    BothSchemaIdAndRegistry,
+}
+
+#[derive(Debug, Snafu)]
+pub enum EncodeError {
+    #[snafu(display("Event is missing field {:?} required by the Avro schema", field))]
+    MissingField { field: String },
+    #[snafu(display("Field {:?} does not match Avro schema type {:?}", field, expected))]
+    Mismatch { field: String, expected: Schema },
+    #[snafu(display("Default of field {:?} is invalid: {}", field, message))]
+    InvalidDefault { field: String, message: String },
+    #[snafu(display("Schema id has not been resolved from the schema registry yet"))]
+    UnresolvedSchemaId,
+    #[snafu(display("Avro serialization failed: {}", message))]
+    AvroSerialization { message: String },
+}
+
+#[derive(Debug, Snafu)]
+enum RegistryError {
+    #[snafu(display("Schema registry returned {}: {}", status, body))]
+    UnexpectedStatus {
+        status: http::StatusCode,
+        body: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct Registered {
+    id: u32,
+}
+
+/// Checks that an `avro` table is configured exactly when the avro codec is
+/// in use, and that it doesn't set conflicting schema ids.
+pub fn validate(uses_avro: bool, config: &Option<AvroConfig>) -> crate::Result<()> {
+    match (uses_avro, config) {
+        (true, None) => Err(BuildError::MissingAvroConfig.into()),
+        (false, Some(_)) => Err(BuildError::UnusedAvroConfig.into()),
+        (_, Some(config)) if config.schema_id.is_some() && config.schema_registry.is_some() => {
+            Err(BuildError::BothSchemaIdAndRegistry.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+impl AvroConfig {
+    pub fn encode(&self, log: &LogEvent) -> Result<Vec<u8>, EncodeError> {
+        let fields = match &*self.schema.schema {
+            Schema::Record { fields, .. } => fields,
+            _ => unreachable!("AvroSchema only holds records"),
+        };
+        let record = self.encode_record(fields, |name| log.get(&Atom::from(name)), "")?;
+
+        let datum = avro_rs::to_avro_datum(&self.schema.schema, AvroValue::Record(record))
+            .map_err(|error| EncodeError::AvroSerialization {
+                message: error.to_string(),
+            })?;
+
+        let schema_id = match (self.schema_id, &self.schema_registry) {
+            (Some(id), _) => Some(id),
+            (None, Some(_)) => Some(
+                self.registered_id
+                    .get()
+                    .ok_or(EncodeError::UnresolvedSchemaId)?,
+            ),
+            (None, None) => None,
+        };
+
+        Ok(match schema_id {
+            Some(id) => {
+                let mut framed = Vec::with_capacity(datum.len() + 5);
+                framed.push(MAGIC_BYTE);
+                framed.extend_from_slice(&id.to_be_bytes());
+                framed.extend_from_slice(&datum);
+                framed
+            }
+            None => datum,
+        })
+    }
+
+    fn encode_record<'a>(
+        &self,
+        fields: &[RecordField],
+        lookup: impl Fn(&str) -> Option<&'a Value>,
+        parent: &str,
+    ) -> Result<Vec<(String, AvroValue)>, EncodeError> {
+        fields
+            .iter()
+            .map(|field| {
+                let path = if parent.is_empty() {
+                    field.name.clone()
+                } else {
+                    format!("{}.{}", parent, field.name)
+                };
+                let value = match lookup(&field.name) {
+                    Some(value) => self.encode_value(value, &field.schema, &path)?,
+                    None => self.missing_value(field, path)?,
+                };
+                Ok((field.name.clone(), value))
+            })
+            .collect()
+    }
+
+    fn missing_value(&self, field: &RecordField, path: String) -> Result<AvroValue, EncodeError> {
+        if self.missing_fields == MissingFields::Error {
+            return Err(EncodeError::MissingField { field: path });
+        }
+
+        match (&field.default, &field.schema) {
+            (Some(default), schema) => {
+                AvroValue::from(default.clone())
+                    .resolve(schema)
+                    .map_err(|error| EncodeError::InvalidDefault {
+                        field: path,
+                        message: error.to_string(),
+                    })
+            }
+            (None, Schema::Union(union)) if union.variants().contains(&Schema::Null) => {
+                Ok(AvroValue::Union(Box::new(AvroValue::Null)))
+            }
+            (None, _) => Err(EncodeError::MissingField { field: path }),
+        }
+    }
+
+    fn encode_value(
+        &self,
+        value: &Value,
+        schema: &Schema,
+        path: &str,
+    ) -> Result<AvroValue, EncodeError> {
+        let mismatch = || EncodeError::Mismatch {
+            field: path.into(),
+            expected: schema.clone(),
+        };
+
+        Ok(match (schema, value) {
+            (Schema::Null, Value::Null) => AvroValue::Null,
+            (Schema::Boolean, Value::Boolean(b)) => AvroValue::Boolean(*b),
+            (Schema::Int, Value::Integer(i)) => {
+                AvroValue::Int(std::convert::TryFrom::try_from(*i).map_err(|_| mismatch())?)
+            }
+            (Schema::Long, Value::Integer(i)) => AvroValue::Long(*i),
+            (Schema::Float, Value::Integer(i)) => AvroValue::Float(*i as f32),
+            (Schema::Float, Value::Float(f)) => AvroValue::Float(*f as f32),
+            (Schema::Double, Value::Integer(i)) => AvroValue::Double(*i as f64),
+            (Schema::Double, Value::Float(f)) => AvroValue::Double(*f),
+            (Schema::String, Value::Bytes(_)) | (Schema::String, Value::Timestamp(_)) => {
+                AvroValue::String(value.to_string_lossy())
+            }
+            (Schema::Bytes, Value::Bytes(bytes)) => AvroValue::Bytes(bytes.to_vec()),
+            (Schema::Fixed { size, .. }, Value::Bytes(bytes)) if bytes.len() == *size => {
+                AvroValue::Fixed(*size, bytes.to_vec())
+            }
+            (Schema::Enum { symbols, .. }, Value::Bytes(_)) => {
+                let symbol = value.to_string_lossy();
+                let index = symbols
+                    .iter()
+                    .position(|s| *s == symbol)
+                    .ok_or_else(mismatch)?;
+                AvroValue::Enum(index as i32, symbol)
+            }
+            (Schema::TimestampMillis, Value::Timestamp(ts)) => {
+                AvroValue::TimestampMillis(ts.timestamp_millis())
+            }
+            (Schema::TimestampMillis, Value::Integer(i)) => AvroValue::TimestampMillis(*i),
+            (Schema::TimestampMicros, Value::Timestamp(ts)) => {
+                AvroValue::TimestampMicros(ts.timestamp_nanos() / 1_000)
+            }
+            (Schema::TimestampMicros, Value::Integer(i)) => AvroValue::TimestampMicros(*i),
+            (Schema::Array(items), Value::Array(values)) => AvroValue::Array(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| self.encode_value(value, items, &format!("{}[{}]", path, i)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Schema::Map(values), Value::Map(map)) => AvroValue::Map(
+                map.iter()
+                    .map(|(key, value)| {
+                        let value =
+                            self.encode_value(value, values, &format!("{}.{}", path, key))?;
+                        Ok((key.clone(), value))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Schema::Record { fields, .. }, Value::Map(map)) => {
+                AvroValue::Record(self.encode_record(fields, |name| map.get(name), path)?)
+            }
+            (Schema::Union(union), value) => union
+                .variants()
+                .iter()
+                .find_map(|variant| self.encode_value(value, variant, path).ok())
+                .map(|value| AvroValue::Union(Box::new(value)))
+                .ok_or_else(mismatch)?,
+            _ => return Err(mismatch()),
+        })
+    }
+
+    /// Holds events back from `sink` until the schema has been registered
+    /// with the configured schema registry, retrying failed registrations.
+    pub fn await_schema_id(&self, sink: RouterSink, cx: &SinkContext) -> crate::Result<RouterSink> {
+        let registry = match &self.schema_registry {
+            Some(registry) => registry,
+            None => return Ok(sink),
+        };
+
+        let key = (
+            registry.url.to_string(),
+            registry.subject.clone(),
+            self.schema.schema.canonical_form(),
+        );
+        if let Some(id) = REGISTERED_IDS.lock().unwrap().get(&key) {
+            self.registered_id.set(*id);
+            return Ok(sink);
+        }
+
+        let base = registry.url.to_string();
+        let uri = format!(
+            "{}/subjects/{}/versions",
+            base.trim_end_matches('/'),
+            registry.subject
+        )
+        .parse::<Uri>()?;
+        let client = HttpClient::new(cx.resolver(), TlsSettings::from_options(&None)?)?;
+        let resolve = register_until_success(client, uri, key);
+
+        Ok(Box::new(AwaitSchemaId {
+            inner: sink,
+            resolve: Some(Box::new(resolve.boxed().compat())),
+            registered_id: self.registered_id.clone(),
+        }))
+    }
+}
+
+async fn register_until_success(
+    mut client: HttpClient,
+    uri: Uri,
+    key: (String, String, String),
+) -> Result<u32, ()> {
+    loop {
+        match register(&mut client, &uri, &key.2).await {
+            Ok(id) => {
+                info!(message = "registered Avro schema.", subject = %key.1, schema_id = id);
+                REGISTERED_IDS.lock().unwrap().insert(key, id);
+                return Ok(id);
+            }
+            Err(error) => {
+                error!(message = "Failed to register Avro schema; retrying.", %error);
+                tokio::time::delay_for(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
+
+async fn register(client: &mut HttpClient, uri: &Uri, schema: &str) -> crate::Result<u32> {
+    let body = serde_json::to_vec(&json!({ "schema": schema }))?;
+    let request = Request::post(uri)
+        .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+        .body(Body::from(body))?;
+
+    let response = client.send(request).await?;
+    let status = response.status();
+    let body = response.into_body().concat2().compat().await?;
+
+    if !status.is_success() {
+        let body = String::from_utf8_lossy(&body[..]).into_owned();
+        return Err(RegistryError::UnexpectedStatus { status, body }.into());
+    }
+
+    let registered: Registered = serde_json::from_slice(&body[..])?;
+    Ok(registered.id)
+}
+
+/// Passes events on once the registry has returned the schema id, so that
+/// none are encoded without their wire format header.
+struct AwaitSchemaId {
+    inner: RouterSink,
+    resolve: Option<Box<dyn Future<Item = u32, Error = ()> + Send>>,
+    registered_id: RegisteredId,
+}
+
+impl AwaitSchemaId {
+    fn poll_resolved(&mut self) -> Poll<(), ()> {
+        if let Some(resolve) = &mut self.resolve {
+            let id = try_ready!(resolve.poll());
+            self.registered_id.set(id);
+            self.resolve = None;
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Sink for AwaitSchemaId {
+    type SinkItem = crate::Event;
+    type SinkError = ();
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.poll_resolved()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(item));
+        }
+        self.inner.start_send(item)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        try_ready!(self.poll_resolved());
+        self.inner.poll_complete()
+    }
+}
+
+impl fmt::Debug for AwaitSchemaId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AwaitSchemaId")
+            .field("registered_id", &self.registered_id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        test_util::{next_addr, runtime},
+    };
+    use chrono::{TimeZone, Utc};
+    use futures01::sync::mpsc;
+    use hyper::{service::service_fn_ok, Response, Server};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Log",
+        "fields": [
+            {"name": "message", "type": "string"},
+            {"name": "status", "type": "int", "default": 200},
+            {"name": "host", "type": ["null", "string"]},
+            {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "tags", "type": {"type": "array", "items": "string"}, "default": []},
+            {"name": "request", "type": ["null", {
+                "type": "record",
+                "name": "Request",
+                "fields": [
+                    {"name": "method", "type": {"type": "enum", "name": "Method", "symbols": ["GET", "POST"]}},
+                    {"name": "bytes", "type": "long"}
+                ]
+            }], "default": null}
+        ]
+    }"#;
+
+    fn config(extra: &str) -> AvroConfig {
+        toml::from_str(&format!("schema = '''{}'''\n{}", SCHEMA, extra)).unwrap()
+    }
+
+    fn decode(config: &AvroConfig, bytes: &[u8]) -> Vec<(String, AvroValue)> {
+        match avro_rs::from_avro_datum(&config.schema.schema, &mut &bytes[..], None).unwrap() {
+            AvroValue::Record(fields) => fields,
+            value => panic!("decoded {:?}", value),
+        }
+    }
+
+    fn event() -> Event {
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+        log.insert("message", "hello");
+        log.insert("status", 404);
+        log.insert("timestamp", Utc.timestamp(1_580_000_000, 123_000_000));
+        log.insert("request.method", "POST");
+        log.insert("request.bytes", 512);
+        log.insert("extra", "not in the schema");
+        event
+    }
+
+    #[test]
+    fn avro_encodes_event_against_schema() {
+        let config = config("");
+        let bytes = config.encode(event().as_log()).unwrap();
+
+        assert_eq!(
+            decode(&config, &bytes),
+            vec![
+                ("message".into(), AvroValue::String("hello".into())),
+                ("status".into(), AvroValue::Int(404)),
+                ("host".into(), AvroValue::Union(Box::new(AvroValue::Null))),
+                (
+                    "timestamp".into(),
+                    AvroValue::TimestampMillis(1_580_000_000_123)
+                ),
+                ("tags".into(), AvroValue::Array(vec![])),
+                (
+                    "request".into(),
+                    AvroValue::Union(Box::new(AvroValue::Record(vec![
+                        ("method".into(), AvroValue::Enum(1, "POST".into())),
+                        ("bytes".into(), AvroValue::Long(512)),
+                    ])))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn avro_uses_schema_defaults() {
+        let config = config("");
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("message", "hello");
+        event.as_mut_log().insert("timestamp", 1_000);
+
+        let decoded = decode(&config, &config.encode(event.as_log()).unwrap());
+        assert_eq!(decoded[1], ("status".into(), AvroValue::Int(200)));
+        assert_eq!(
+            decoded[5],
+            (
+                "request".into(),
+                AvroValue::Union(Box::new(AvroValue::Null))
+            )
+        );
+    }
+
+    #[test]
+    fn avro_rejects_missing_and_mismatched_fields() {
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("timestamp", 1_000);
+        match config("").encode(event.as_log()) {
+            Err(EncodeError::MissingField { field }) => assert_eq!(field, "message"),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let strict = config("missing_fields = \"error\"");
+        match strict.encode(self::event().as_log()) {
+            Err(EncodeError::MissingField { field }) => assert_eq!(field, "host"),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let mut event = self::event();
+        event.as_mut_log().insert("request.method", "DELETE");
+        match config("").encode(event.as_log()) {
+            Err(EncodeError::Mismatch { field, .. }) => assert_eq!(field, "request"),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn avro_prefixes_schema_id() {
+        let config = config("schema_id = 258");
+        let bytes = config.encode(event().as_log()).unwrap();
+
+        assert_eq!(&bytes[..5], &[0, 0, 0, 1, 2]);
+        assert_eq!(
+            decode(&config, &bytes[5..])[0].1,
+            AvroValue::String("hello".into())
+        );
+    }
+
+    #[test]
+    fn avro_validates_config() {
+        assert!(validate(true, &None).is_err());
+        assert!(validate(false, &Some(config(""))).is_err());
+        assert!(validate(true, &Some(config(""))).is_ok());
+
+        let both = config("schema_id = 1\nschema_registry = { url = \"http://localhost:8081\", subject = \"logs\" }");
+        assert!(validate(true, &Some(both)).is_err());
+
+        let not_record: Result<AvroConfig, _> = toml::from_str("schema = '\"string\"'");
+        assert!(not_record.is_err());
+    }
+
+    #[test]
+    fn avro_registers_schema_once() {
+        let mut rt = runtime();
+        let addr = next_addr();
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&requests);
+        let server = Server::bind(&addr)
+            .serve(move || {
+                let counter = Arc::clone(&counter);
+                service_fn_ok(move |_: Request<Body>| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Response::new(Body::from(r#"{"id":7}"#))
+                })
+            })
+            .map_err(|error| panic!("server error: {}", error));
+        rt.spawn(server);
+
+        let config = config(&format!(
+            "schema_registry = {{ url = \"http://{}/\", subject = \"logs-value\" }}",
+            addr
+        ));
+        let cx = SinkContext::new_test(rt.executor());
+
+        for _ in 0..2 {
+            let (tx, rx) = mpsc::channel(1);
+            let sink: RouterSink = Box::new(tx.sink_map_err(|_| ()));
+            let sink = config.await_schema_id(sink, &cx).unwrap();
+
+            rt.block_on(sink.send(event())).unwrap();
+            let (received, _) = rt.block_on(rx.into_future()).ok().unwrap();
+            let bytes = config.encode(received.unwrap().as_log()).unwrap();
+            assert_eq!(&bytes[..5], &[0, 0, 0, 0, 7]);
+        }
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,50 +1,139 @@
 use serde::{Deserialize, Serialize};
+use snafu::Snafu;
 use std::time::Duration;
 
+#[derive(Debug, Snafu)]
+pub enum BatchError {
+    #[snafu(display(
+        "This sink can't measure its batches in bytes, so `batch.max_bytes` is not supported"
+    ))]
+    BytesNotSupported,
+}
+
+/// Batch limits shared by all batching sinks.
+///
+/// A batch is flushed as soon as it holds `max_events` events or `max_bytes`
+/// bytes, whichever comes first, or once `timeout_secs` have passed since its
+/// first event arrived. Limits left unset fall back to the sink's defaults.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct BatchBytesConfig {
-    pub max_size: Option<usize>,
+pub struct BatchConfig {
+    #[serde(alias = "max_size")]
+    pub max_bytes: Option<usize>,
+    pub max_events: Option<usize>,
     pub timeout_secs: Option<u64>,
 }
 
-impl BatchBytesConfig {
-    pub fn unwrap_or(&self, size: u64, timeout: u64) -> BatchSettings {
+impl BatchConfig {
+    pub fn unwrap_or(&self, defaults: BatchSettings) -> BatchSettings {
         BatchSettings {
-            size: self.max_size.unwrap_or(size as usize),
-            timeout: Duration::from_secs(self.timeout_secs.unwrap_or(timeout)),
+            size: BatchSize {
+                bytes: self.max_bytes.unwrap_or(defaults.size.bytes),
+                events: self.max_events.unwrap_or(defaults.size.events),
+            },
+            timeout: self
+                .timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+        }
+    }
+
+    /// Like `unwrap_or`, for sinks whose batches only count events, such as
+    /// plain `Vec`s. Setting `max_bytes` for them is rejected rather than
+    /// silently ignored.
+    pub fn unwrap_events_only(&self, defaults: BatchSettings) -> crate::Result<BatchSettings> {
+        if self.max_bytes.is_some() {
+            return Err(BatchError::BytesNotSupported.into());
         }
+        Ok(self.unwrap_or(defaults))
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct BatchEventsConfig {
-    pub max_events: Option<usize>,
-    pub timeout_secs: Option<u64>,
+#[derive(Clone, Copy, Debug)]
+pub struct BatchSettings {
+    pub size: BatchSize,
+    pub timeout: Duration,
 }
 
-impl BatchEventsConfig {
-    pub fn unwrap_or(&self, size: u64, timeout: u64) -> BatchSettings {
-        BatchSettings {
-            size: self.max_events.unwrap_or(size as usize),
-            timeout: Duration::from_secs(self.timeout_secs.unwrap_or(timeout)),
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            size: BatchSize {
+                bytes: usize::max_value(),
+                events: usize::max_value(),
+            },
+            timeout: Duration::from_secs(1),
         }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct BatchSettings {
-    pub size: usize,
-    pub timeout: Duration,
+impl BatchSettings {
+    pub fn bytes(mut self, bytes: u64) -> Self {
+        self.size.bytes = bytes as usize;
+        self
+    }
+
+    pub fn events(mut self, events: usize) -> Self {
+        self.size.events = events;
+        self
+    }
+
+    pub fn timeout(mut self, secs: u64) -> Self {
+        self.timeout = Duration::from_secs(secs);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BatchSize {
+    pub bytes: usize,
+    pub events: usize,
+}
+
+impl BatchSize {
+    /// Whether `batch` has reached either limit and must be flushed.
+    pub fn is_reached<B: Batch>(&self, batch: &B) -> bool {
+        batch.num_items() >= self.events || batch.num_bytes() >= self.bytes
+    }
+
+    /// Whether pushing `item` would take a non-empty `batch` past either
+    /// limit. An empty batch always accepts its first item.
+    pub fn overflows<B: Batch>(&self, batch: &B, item: &B::Input) -> bool {
+        !batch.is_empty()
+            && (batch.num_items() + 1 > self.events
+                || batch.num_bytes() + batch.item_bytes(item) > self.bytes)
+    }
 }
 
 pub trait Batch {
     type Input;
     type Output;
-    fn len(&self) -> usize;
+
+    /// Size of the batch in bytes, as counted against `max_bytes`.
+    fn num_bytes(&self) -> usize;
+
+    /// Bytes `item` will add to the batch once pushed. Batches that can't
+    /// tell upfront, such as compressed ones, report zero and so only
+    /// enforce `max_bytes` after the fact.
+    fn item_bytes(&self, _item: &Self::Input) -> usize {
+        0
+    }
+
     fn push(&mut self, item: Self::Input);
     fn is_empty(&self) -> bool;
     fn fresh(&self) -> Self;
     fn finish(self) -> Self::Output;
+
+    /// Finishes the batch, along with its size in bytes as it's sent, for
+    /// batches that know it. Unlike `num_bytes`, this is the size once
+    /// finished, compression included.
+    fn finish_sized(self) -> (Self::Output, Option<usize>)
+    where
+        Self: Sized,
+    {
+        (self.finish(), None)
+    }
+
+    /// Number of events in the batch, as counted against `max_events`.
     fn num_items(&self) -> usize;
 
     /// Replace the current batch with a fresh one, returning the old one.
@@ -57,12 +146,13 @@ pub trait Batch {
     }
 }
 
+/// A plain `Vec` batch only counts events, it has no notion of bytes.
 impl<T> Batch for Vec<T> {
     type Input = T;
     type Output = Self;
 
-    fn len(&self) -> usize {
-        self.len()
+    fn num_bytes(&self) -> usize {
+        0
     }
 
     fn push(&mut self, item: Self::Input) {
@@ -85,3 +175,116 @@ impl<T> Batch for Vec<T> {
         self.len()
     }
 }
+
+/// A `Vec` batch that measures each item with `byte_size`, for sinks whose
+/// API enforces a hard per-request payload limit.
+#[derive(Debug)]
+pub struct VecBuffer<T> {
+    items: Vec<T>,
+    bytes: usize,
+    byte_size: fn(&T) -> usize,
+}
+
+impl<T> VecBuffer<T> {
+    pub fn new(byte_size: fn(&T) -> usize) -> Self {
+        Self {
+            items: Vec::new(),
+            bytes: 0,
+            byte_size,
+        }
+    }
+}
+
+impl<T> Batch for VecBuffer<T> {
+    type Input = T;
+    type Output = Vec<T>;
+
+    fn num_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn item_bytes(&self, item: &Self::Input) -> usize {
+        (self.byte_size)(item)
+    }
+
+    fn push(&mut self, item: Self::Input) {
+        self.bytes += (self.byte_size)(&item);
+        self.items.push(item)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn fresh(&self) -> Self {
+        Self::new(self.byte_size)
+    }
+
+    fn finish(self) -> Self::Output {
+        self.items
+    }
+
+    fn finish_sized(self) -> (Self::Output, Option<usize>) {
+        let bytes = self.bytes;
+        (self.items, Some(bytes))
+    }
+
+    fn num_items(&self) -> usize {
+        self.items.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_config_falls_back_to_defaults() {
+        let defaults = BatchSettings::default().events(500).bytes(1024).timeout(5);
+
+        let config: BatchConfig = toml::from_str("max_events = 10").unwrap();
+        let settings = config.unwrap_or(defaults);
+        assert_eq!(
+            settings.size,
+            BatchSize {
+                bytes: 1024,
+                events: 10
+            }
+        );
+        assert_eq!(settings.timeout, Duration::from_secs(5));
+
+        let config: BatchConfig = toml::from_str("max_size = 42\ntimeout_secs = 1").unwrap();
+        let settings = config.unwrap_or(defaults);
+        assert_eq!(settings.size.bytes, 42);
+        assert_eq!(settings.size.events, 500);
+        assert_eq!(settings.timeout, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn events_only_batches_reject_max_bytes() {
+        let defaults = BatchSettings::default().events(500);
+
+        let config: BatchConfig = toml::from_str("max_events = 10").unwrap();
+        assert_eq!(config.unwrap_events_only(defaults).unwrap().size.events, 10);
+
+        let config: BatchConfig = toml::from_str("max_bytes = 1024").unwrap();
+        assert!(config.unwrap_events_only(defaults).is_err());
+    }
+
+    #[test]
+    fn vec_buffer_overflows_on_bytes() {
+        let size = BatchSize {
+            bytes: 10,
+            events: 100,
+        };
+        let mut batch = VecBuffer::new(|s: &String| s.len());
+
+        assert!(!size.overflows(&batch, &"0123456789ab".to_string()));
+        batch.push("01234".into());
+        assert!(!size.overflows(&batch, &"56789".to_string()));
+        assert!(size.overflows(&batch, &"56789a".to_string()));
+        batch.push("56789".into());
+        assert!(size.is_reached(&batch));
+        assert_eq!(batch.num_bytes(), 10);
+    }
+}
@@ -15,7 +15,7 @@ impl Batch for JsonArrayBuffer {
     type Input = Value;
     type Output = Vec<BoxedRawValue>;
 
-    fn len(&self) -> usize {
+    fn num_bytes(&self) -> usize {
         self.total_bytes
     }
 
@@ -38,6 +38,10 @@ impl Batch for JsonArrayBuffer {
         self.buffer
     }
 
+    fn finish_sized(self) -> (Self::Output, Option<usize>) {
+        (self.buffer, Some(self.total_bytes))
+    }
+
     fn num_items(&self) -> usize {
         self.buffer.len()
     }
@@ -61,7 +65,7 @@ mod tests {
         }));
 
         assert_eq!(buffer.num_items(), 2);
-        assert_eq!(buffer.len(), 34);
+        assert_eq!(buffer.num_bytes(), 34);
 
         let json = buffer.finish();
 
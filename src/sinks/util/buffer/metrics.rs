@@ -108,8 +108,8 @@ impl Batch for MetricBuffer {
     type Input = Event;
     type Output = Vec<Metric>;
 
-    fn len(&self) -> usize {
-        self.num_items()
+    fn num_bytes(&self) -> usize {
+        0
     }
 
     fn push(&mut self, item: Self::Input) {
@@ -278,7 +278,6 @@ mod test {
     use pretty_assertions::assert_eq;
     use std::collections::BTreeMap;
     use std::sync::{Arc, Mutex};
-    use std::time::Duration;
     use tokio01_test::clock::MockClock;
 
     fn tag(name: &str) -> BTreeMap<String, String> {
@@ -316,10 +315,7 @@ mod test {
         let buffered = BatchSink::with_executor(
             svc,
             MetricBuffer::new(),
-            BatchSettings {
-                timeout: Duration::from_secs(0),
-                size: 6,
-            },
+            BatchSettings::default().events(6).timeout(0),
             acker,
             rt.executor(),
         );
@@ -20,6 +20,7 @@ pub enum Compression {
 pub struct Buffer {
     inner: InnerBuffer,
     num_items: usize,
+    gzip_level: Option<flate2::Compression>,
 }
 
 #[derive(Debug)]
@@ -30,14 +31,24 @@ pub enum InnerBuffer {
 
 impl Buffer {
     pub fn new(gzip: bool) -> Self {
-        let inner = if gzip {
-            InnerBuffer::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::fast()))
+        Self::with_gzip_level(if gzip {
+            Some(flate2::Compression::default())
         } else {
-            InnerBuffer::Plain(Vec::new())
+            None
+        })
+    }
+
+    /// A buffer compressing with gzip at the given level, for all of its
+    /// batches, or not compressing without one.
+    pub fn with_gzip_level(gzip_level: Option<flate2::Compression>) -> Self {
+        let inner = match gzip_level {
+            Some(level) => InnerBuffer::Gzip(GzEncoder::new(Vec::new(), level)),
+            None => InnerBuffer::Plain(Vec::new()),
         };
         Self {
             inner,
             num_items: 0,
+            gzip_level,
         }
     }
 
@@ -74,7 +85,7 @@ impl Batch for Buffer {
     type Input = Vec<u8>;
     type Output = Vec<u8>;
 
-    fn len(&self) -> usize {
+    fn num_bytes(&self) -> usize {
         self.size()
     }
 
@@ -87,16 +98,7 @@ impl Batch for Buffer {
     }
 
     fn fresh(&self) -> Self {
-        let inner = match &self.inner {
-            InnerBuffer::Plain(_) => InnerBuffer::Plain(Vec::new()),
-            InnerBuffer::Gzip(_) => {
-                InnerBuffer::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::default()))
-            }
-        };
-        Self {
-            inner,
-            num_items: 0,
-        }
+        Self::with_gzip_level(self.gzip_level)
     }
 
     fn finish(self) -> Self::Output {
@@ -108,6 +110,12 @@ impl Batch for Buffer {
         }
     }
 
+    fn finish_sized(self) -> (Self::Output, Option<usize>) {
+        let output = self.finish();
+        let bytes = output.len();
+        (output, Some(bytes))
+    }
+
     fn num_items(&self) -> usize {
         self.num_items
     }
@@ -122,7 +130,6 @@ mod test {
     use futures01::{future, Future, Sink};
     use std::io::Read;
     use std::sync::{Arc, Mutex};
-    use std::time::Duration;
     use tokio01_test::clock::MockClock;
 
     #[test]
@@ -145,10 +152,7 @@ mod test {
         let buffered = BatchSink::with_executor(
             svc,
             Buffer::new(true),
-            BatchSettings {
-                timeout: Duration::from_secs(0),
-                size: 1000,
-            },
+            BatchSettings::default().bytes(1000).timeout(0),
             acker,
             rt.executor(),
         );
@@ -192,4 +196,25 @@ mod test {
         .take(100_000)
         .flatten()));
     }
+
+    #[test]
+    fn gzip_level_is_applied_to_every_batch() {
+        use crate::sinks::util::Batch;
+
+        let line = b"It's going down, I'm yelling timber, You better move, you better dance";
+        let compress = |buffer: Buffer| {
+            let mut buffer = buffer.fresh();
+            for i in 0..10_000 {
+                buffer.push(line);
+                buffer.push(i.to_string().as_bytes());
+            }
+            buffer.finish().len()
+        };
+
+        let none = compress(Buffer::with_gzip_level(Some(flate2::Compression::none())));
+        let fast = compress(Buffer::with_gzip_level(Some(flate2::Compression::fast())));
+        let best = compress(Buffer::with_gzip_level(Some(flate2::Compression::best())));
+        assert!(none > fast, "{} <= {}", none, fast);
+        assert!(fast > best, "{} <= {}", fast, best);
+    }
 }
@@ -29,8 +29,12 @@ where
     type Input = PartitionInnerBuffer<T::Input, K>;
     type Output = PartitionInnerBuffer<T::Output, K>;
 
-    fn len(&self) -> usize {
-        self.inner.len()
+    fn num_bytes(&self) -> usize {
+        self.inner.num_bytes()
+    }
+
+    fn item_bytes(&self, item: &Self::Input) -> usize {
+        self.inner.item_bytes(&item.inner)
     }
 
     fn push(&mut self, item: Self::Input) {
@@ -56,6 +60,12 @@ where
         PartitionInnerBuffer { inner, key }
     }
 
+    fn finish_sized(mut self) -> (Self::Output, Option<usize>) {
+        let key = self.key.take().unwrap();
+        let (inner, bytes) = self.inner.finish_sized();
+        (PartitionInnerBuffer { inner, key }, bytes)
+    }
+
     fn num_items(&self) -> usize {
         self.inner.num_items()
     }
@@ -0,0 +1,193 @@
+//! The `compression` option of sinks compressing what they send, written as
+//! the algorithm, like `gzip`, or with the level to compress at, like
+//! `gzip(9)` or `zstd(3)`. Levels are checked against the algorithm's range
+//! when the config is loaded.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use snafu::Snafu;
+use std::{fmt, str::FromStr};
+
+const GZIP_LEVELS: (u32, u32) = (0, 9);
+const ZSTD_LEVELS: (u32, u32) = (1, 21);
+
+/// The level `zstd` itself defaults to.
+const ZSTD_DEFAULT_LEVEL: u32 = 3;
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum ParseCompressionError {
+    #[snafu(display("unknown compression {:?}, expected `none`, `gzip` or `zstd`", name))]
+    UnknownAlgorithm { name: String },
+    #[snafu(display("invalid compression level in {:?}", input))]
+    InvalidLevel { input: String },
+    #[snafu(display(
+        "{} compression level {} is out of range, expected {} to {}",
+        algorithm,
+        level,
+        min,
+        max
+    ))]
+    LevelOutOfRange {
+        algorithm: &'static str,
+        level: u32,
+        min: u32,
+        max: u32,
+    },
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Compression {
+    None,
+    /// Without a level, `flate2`'s default of 6 is used.
+    Gzip(Option<u32>),
+    /// Without a level, `zstd`'s default of 3 is used.
+    Zstd(Option<u32>),
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::None
+    }
+}
+
+impl Compression {
+    pub fn gzip_default() -> Self {
+        Compression::Gzip(None)
+    }
+
+    /// The `Content-Encoding` of data compressed this way.
+    pub fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip(_) => Some("gzip"),
+            Compression::Zstd(_) => Some("zstd"),
+        }
+    }
+
+    /// The level to give `flate2`, if this is gzip compression.
+    pub fn gzip_level(self) -> Option<flate2::Compression> {
+        match self {
+            Compression::Gzip(level) => {
+                Some(level.map(flate2::Compression::new).unwrap_or_default())
+            }
+            _ => None,
+        }
+    }
+
+    /// The level to give `zstd`, if this is zstd compression.
+    pub fn zstd_level(self) -> Option<i32> {
+        match self {
+            Compression::Zstd(level) => Some(level.unwrap_or(ZSTD_DEFAULT_LEVEL) as i32),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = ParseCompressionError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (name, level) = match input.find('(') {
+            Some(open) if input.ends_with(')') => {
+                let level = input[open + 1..input.len() - 1]
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| ParseCompressionError::InvalidLevel {
+                        input: input.into(),
+                    })?;
+                (input[..open].trim(), Some(level))
+            }
+            Some(_) => {
+                return Err(ParseCompressionError::InvalidLevel {
+                    input: input.into(),
+                })
+            }
+            None => (input.trim(), None),
+        };
+
+        let check = |algorithm, (min, max)| match level {
+            Some(level) if level < min || level > max => {
+                Err(ParseCompressionError::LevelOutOfRange {
+                    algorithm,
+                    level,
+                    min,
+                    max,
+                })
+            }
+            _ => Ok(level),
+        };
+
+        match name {
+            "none" if level.is_none() => Ok(Compression::None),
+            "none" => Err(ParseCompressionError::InvalidLevel {
+                input: input.into(),
+            }),
+            "gzip" => check("gzip", GZIP_LEVELS).map(Compression::Gzip),
+            "zstd" => check("zstd", ZSTD_LEVELS).map(Compression::Zstd),
+            _ => Err(ParseCompressionError::UnknownAlgorithm { name: name.into() }),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Gzip(None) => write!(f, "gzip"),
+            Compression::Gzip(Some(level)) => write!(f, "gzip({})", level),
+            Compression::Zstd(None) => write!(f, "zstd"),
+            Compression::Zstd(Some(level)) => write!(f, "zstd({})", level),
+        }
+    }
+}
+
+impl Serialize for Compression {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Compression {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let input = String::deserialize(deserializer)?;
+        input.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_parses_with_and_without_level() {
+        assert_eq!("none".parse(), Ok(Compression::None));
+        assert_eq!("gzip".parse(), Ok(Compression::Gzip(None)));
+        assert_eq!("gzip(0)".parse(), Ok(Compression::Gzip(Some(0))));
+        assert_eq!("gzip(9)".parse(), Ok(Compression::Gzip(Some(9))));
+        assert_eq!("zstd".parse(), Ok(Compression::Zstd(None)));
+        assert_eq!("zstd(21)".parse(), Ok(Compression::Zstd(Some(21))));
+
+        for compression in &["none", "gzip", "gzip(6)", "zstd(3)"] {
+            assert_eq!(
+                compression.parse::<Compression>().unwrap().to_string(),
+                *compression
+            );
+        }
+    }
+
+    #[test]
+    fn compression_rejects_out_of_range_levels() {
+        for input in &[
+            "gzip(10)", "zstd(0)", "zstd(22)", "gzip(-1)", "gzip(x)", "none(1)", "gzip(6", "lz4",
+        ] {
+            assert!(input.parse::<Compression>().is_err(), "{}", input);
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Config {
+            #[allow(dead_code)]
+            compression: Compression,
+        }
+        let error = toml::from_str::<Config>(r#"compression = "gzip(10)""#).unwrap_err();
+        assert!(error.to_string().contains("out of range"), "{}", error);
+    }
+}
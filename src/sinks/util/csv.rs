@@ -0,0 +1,201 @@
+//! Encodes log events as CSV records, one column per configured field.
+
+use crate::event::LogEvent;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CsvConfig {
+    /// The field written to each column, in order.
+    pub fields: Vec<Atom>,
+    /// Whether the field names are written as a header row at the start of
+    /// each file or batch.
+    #[serde(default)]
+    pub header: bool,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    #[serde(default = "default_quote")]
+    pub quote: char,
+    #[serde(default)]
+    pub quote_style: QuoteStyle,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_quote() -> char {
+    '"'
+}
+
+/// Which cells are quoted. Quotes inside quoted cells are always escaped by
+/// doubling them.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum QuoteStyle {
+    /// Only cells containing the delimiter, a quote or a line break.
+    #[derivative(Default)]
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("The csv codec requires a `csv` table"))]
+    MissingCsvConfig,
+    #[snafu(display("A `csv` table is only used with the csv codec"))]
+    UnusedCsvConfig,
+    #[snafu(display("`csv.fields` must name at least one field"))]
+    NoFields,
+    #[snafu(display("`csv.{}` must be a single ASCII character, got {:?}", option, value))]
+    NonAsciiCharacter { option: &'static str, value: char },
+}
+
+/// Checks that a `csv` table is configured exactly when the csv codec is in
+/// use, and that it can be written.
+pub fn validate(uses_csv: bool, config: &Option<CsvConfig>) -> crate::Result<()> {
+    match (uses_csv, config) {
+        (true, None) => Err(BuildError::MissingCsvConfig.into()),
+        (false, Some(_)) => Err(BuildError::UnusedCsvConfig.into()),
+        (true, Some(config)) if config.fields.is_empty() => Err(BuildError::NoFields.into()),
+        (true, Some(config)) => {
+            for &(option, value) in &[("delimiter", config.delimiter), ("quote", config.quote)] {
+                if !value.is_ascii() {
+                    return Err(BuildError::NonAsciiCharacter { option, value }.into());
+                }
+            }
+            Ok(())
+        }
+        (false, None) => Ok(()),
+    }
+}
+
+impl CsvConfig {
+    /// Encodes the configured fields of `log` as one record, ending in a
+    /// newline. Missing fields are written as empty cells.
+    pub fn encode(&self, log: &LogEvent) -> Vec<u8> {
+        let cells = self.fields.iter().map(|field| {
+            log.get(field)
+                .map(|value| value.to_string_lossy())
+                .unwrap_or_default()
+        });
+        self.write(cells)
+    }
+
+    /// The header row naming each column's field.
+    pub fn header(&self) -> Vec<u8> {
+        self.write(self.fields.iter().map(|field| field.to_string()))
+    }
+
+    fn write(&self, cells: impl Iterator<Item = String>) -> Vec<u8> {
+        let quote_style = match self.quote_style {
+            QuoteStyle::Necessary => ::csv::QuoteStyle::Necessary,
+            QuoteStyle::Always => ::csv::QuoteStyle::Always,
+            QuoteStyle::NonNumeric => ::csv::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => ::csv::QuoteStyle::Never,
+        };
+        let mut writer = ::csv::WriterBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .quote(self.quote as u8)
+            .quote_style(quote_style)
+            .buffer_capacity(256)
+            .from_writer(Vec::new());
+
+        writer
+            .write_record(cells)
+            .expect("Writing to Vec can't fail");
+        writer
+            .into_inner()
+            .map_err(|_| ())
+            .expect("Writing to Vec can't fail")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    fn config(extra: &str) -> CsvConfig {
+        toml::from_str(&format!(
+            r#"
+            fields = ["message", "status", "request.path"]
+            {}
+            "#,
+            extra
+        ))
+        .unwrap()
+    }
+
+    fn event(message: &str) -> Event {
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("message", message);
+        event.as_mut_log().insert("status", 200);
+        event.as_mut_log().insert("request.path", "/");
+        event
+    }
+
+    fn encode(config: &CsvConfig, event: Event) -> String {
+        String::from_utf8(config.encode(event.as_log())).unwrap()
+    }
+
+    #[test]
+    fn csv_encodes_fields_in_order() {
+        let config = config("");
+        assert_eq!(encode(&config, event("hello")), "hello,200,/\n");
+        assert_eq!(
+            String::from_utf8(config.header()).unwrap(),
+            "message,status,request.path\n"
+        );
+    }
+
+    #[test]
+    fn csv_writes_missing_fields_as_empty_cells() {
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("status", 500);
+        assert_eq!(encode(&config(""), event), ",500,\n");
+    }
+
+    #[test]
+    fn csv_quotes_and_escapes_cells() {
+        let config = config("");
+        assert_eq!(encode(&config, event("a,b")), "\"a,b\",200,/\n");
+        assert_eq!(
+            encode(&config, event(r#"say "hi""#)),
+            "\"say \"\"hi\"\"\",200,/\n"
+        );
+        assert_eq!(
+            encode(&config, event("two\nlines")),
+            "\"two\nlines\",200,/\n"
+        );
+        assert_eq!(encode(&config, event("\r")), "\"\r\",200,/\n");
+    }
+
+    #[test]
+    fn csv_uses_configured_delimiter_and_quoting() {
+        let tabs = config("delimiter = \"\\t\"\nquote = \"'\"\nquote_style = \"non_numeric\"");
+        assert_eq!(
+            encode(&tabs, event("it's, fine")),
+            "'it''s, fine'\t200\t'/'\n"
+        );
+
+        let always = config("quote_style = \"always\"");
+        assert_eq!(encode(&always, event("hi")), "\"hi\",\"200\",\"/\"\n");
+    }
+
+    #[test]
+    fn csv_validates_config() {
+        assert!(validate(true, &None).is_err());
+        assert!(validate(false, &Some(config(""))).is_err());
+        assert!(validate(true, &Some(config(""))).is_ok());
+        assert!(validate(true, &Some(config("delimiter = \"§\""))).is_err());
+
+        let empty: CsvConfig = toml::from_str("fields = []").unwrap();
+        assert!(validate(true, &Some(empty)).is_err());
+    }
+}
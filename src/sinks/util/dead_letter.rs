@@ -0,0 +1,119 @@
+//! Hands events a sink has given up on to the sink configured as its
+//! `dead_letter`, tagged with why they failed. Transforms that isolate their
+//! panics hand over the events they panicked on the same way.
+//!
+//! The topology wires one `DeadLetter` per sink into its `SinkContext`. Sinks
+//! without a `dead_letter` get a disabled handle, so call sites never need to
+//! check whether one is configured. Sending never blocks: when the dead-letter
+//! sink can't keep up, the event is dropped and logged like before.
+
+use crate::event::{Event, TraceEvent};
+use futures01::sync::mpsc;
+
+/// Name of the component that gave up on a dead-lettered log or trace event.
+pub const SINK_FIELD: &str = "dead_letter.sink";
+/// Why the sink gave up on a dead-lettered log or trace event.
+pub const REASON_FIELD: &str = "dead_letter.reason";
+/// Metrics have no fields, so the same details are set as these tags.
+pub const SINK_TAG: &str = "dead_letter_sink";
+pub const REASON_TAG: &str = "dead_letter_reason";
+
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    inner: Option<(String, mpsc::Sender<Event>)>,
+}
+
+impl DeadLetter {
+    pub fn new(sink: &str, tx: mpsc::Sender<Event>) -> Self {
+        Self {
+            inner: Some((sink.into(), tx)),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self { inner: None }
+    }
+
+    /// Whether events sent here go anywhere. Sinks use this to skip keeping
+    /// hold of events they would only need for dead-lettering.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub fn send(&mut self, mut event: Event, reason: &str) {
+        let (sink, tx) = match &mut self.inner {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        match &mut event {
+            Event::Log(log) | Event::Trace(TraceEvent(log)) => {
+                log.insert(SINK_FIELD, sink.clone());
+                log.insert(REASON_FIELD, reason.to_string());
+            }
+            Event::Metric(metric) => {
+                let tags = metric.tags.get_or_insert_with(Default::default);
+                tags.insert(SINK_TAG.into(), sink.clone());
+                tags.insert(REASON_TAG.into(), reason.into());
+            }
+        }
+
+        if let Err(error) = tx.try_send(event) {
+            warn!(
+                message = "Could not hand event to dead-letter sink; dropping event.",
+                %error,
+                %reason,
+                rate_limit_secs = 30
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::metric::{Metric, MetricKind, MetricValue};
+    use futures01::{Future, Stream};
+
+    #[test]
+    fn dead_letter_tags_logs_and_metrics() {
+        let (tx, rx) = mpsc::channel(10);
+        let mut dead_letter = DeadLetter::new("out", tx);
+
+        dead_letter.send(Event::from("hello"), "bad request");
+        dead_letter.send(
+            Event::Metric(Metric {
+                name: "counter".into(),
+                timestamp: None,
+                tags: None,
+                kind: MetricKind::Incremental,
+                value: MetricValue::Counter { value: 1.0 },
+            }),
+            "bad request",
+        );
+        drop(dead_letter);
+
+        let events = rx.collect().wait().unwrap();
+        let log = events[0].as_log();
+        assert_eq!(log[&SINK_FIELD.into()], "out".into());
+        assert_eq!(log[&REASON_FIELD.into()], "bad request".into());
+
+        let tags = events[1].as_metric().tags.as_ref().unwrap();
+        assert_eq!(tags[SINK_TAG], "out");
+        assert_eq!(tags[REASON_TAG], "bad request");
+    }
+
+    #[test]
+    fn dead_letter_drops_when_full() {
+        let (tx, rx) = mpsc::channel(0);
+        let mut dead_letter = DeadLetter::new("out", tx);
+
+        for _ in 0..10 {
+            dead_letter.send(Event::from("hello"), "bad request");
+        }
+        drop(dead_letter);
+
+        // A zero-sized channel still holds one message per sender.
+        assert_eq!(rx.collect().wait().unwrap().len(), 1);
+    }
+}
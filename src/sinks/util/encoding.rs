@@ -29,11 +29,18 @@
 //       `Encoder` that defines some `encode` function which this config then calls internally as
 //       part of it's own (yet to be written) `encode() -> Vec<u8>` function.
 
-use crate::{event::Value, Event, Result};
+use crate::{
+    event::{TraceEvent, Value},
+    Event, Result,
+};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, SecondsFormat, Utc,
+};
 use serde::de::{MapAccess, Visitor};
 use serde::{
     de::{self, DeserializeOwned, Deserializer, IntoDeserializer},
-    Deserialize, Serialize,
+    Deserialize, Serialize, Serializer,
 };
 use std::collections::VecDeque;
 use std::fmt::{self, Debug};
@@ -150,16 +157,19 @@ pub trait EncodingConfiguration<E> {
     fn except_fields(&self) -> &Option<Vec<Atom>>;
     fn timestamp_format(&self) -> &Option<TimestampFormat>;
 
+    /// Keeps only the fields listed in `only_fields`. Listing a nested path
+    /// such as `a.b` keeps everything below it, while its siblings under `a`
+    /// are dropped. Containers emptied along the way are removed.
     fn apply_only_fields(&self, event: &mut Event) {
         if let Some(only_fields) = &self.only_fields() {
             match event {
-                Event::Log(log_event) => {
+                Event::Log(log_event) | Event::Trace(TraceEvent(log_event)) => {
                     let to_remove = log_event
                         .keys()
-                        .filter(|f| !only_fields.contains(f))
+                        .filter(|key| !only_fields.iter().any(|f| is_path_within(key, f)))
                         .collect::<VecDeque<_>>();
                     for removal in to_remove {
-                        log_event.remove(&Atom::from(removal));
+                        log_event.remove_prune(&Atom::from(removal), true);
                     }
                 }
                 Event::Metric(_) => {
@@ -168,12 +178,14 @@ pub trait EncodingConfiguration<E> {
             }
         }
     }
+    /// Removes the fields listed in `except_fields`, which may be nested
+    /// paths. Containers emptied along the way are removed.
     fn apply_except_fields(&self, event: &mut Event) {
         if let Some(except_fields) = &self.except_fields() {
             match event {
-                Event::Log(log_event) => {
+                Event::Log(log_event) | Event::Trace(TraceEvent(log_event)) => {
                     for field in except_fields {
-                        log_event.remove(field);
+                        log_event.remove_prune(field, true);
                     }
                 }
                 Event::Metric(_) => (), // Metrics don't get affected by this one!
@@ -183,22 +195,15 @@ pub trait EncodingConfiguration<E> {
     fn apply_timestamp_format(&self, event: &mut Event) {
         if let Some(timestamp_format) = &self.timestamp_format() {
             match event {
-                Event::Log(log_event) => {
-                    match timestamp_format {
-                        TimestampFormat::Unix => {
-                            let mut unix_timestamps = Vec::new();
-                            for (k, v) in log_event.all_fields() {
-                                if let Value::Timestamp(ts) = v {
-                                    unix_timestamps
-                                        .push((k.clone(), Value::Integer(ts.timestamp())));
-                                }
-                            }
-                            for (k, v) in unix_timestamps {
-                                log_event.insert(k, v);
-                            }
+                Event::Log(log_event) | Event::Trace(TraceEvent(log_event)) => {
+                    let mut timestamps = Vec::new();
+                    for (k, v) in log_event.all_fields() {
+                        if let Value::Timestamp(ts) = v {
+                            timestamps.push((k.clone(), timestamp_format.render(ts)));
                         }
-                        // RFC3339 is the default serialization of a timestamp.
-                        TimestampFormat::RFC3339 => (),
+                    }
+                    for (k, v) in timestamps {
+                        log_event.insert(k, v);
                     }
                 }
                 Event::Metric(_) => (), // Metrics don't get affected by this one!
@@ -236,11 +241,70 @@ pub trait EncodingConfiguration<E> {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
+/// Whether `key` is the field path `path` itself or one nested below it.
+fn is_path_within(key: &str, path: &str) -> bool {
+    key.starts_with(path)
+        && match key.as_bytes().get(path.len()) {
+            None | Some(b'.') | Some(b'[') => true,
+            _ => false,
+        }
+}
+
+/// How timestamps are written, always in UTC. Configured as `rfc3339`,
+/// `unix`, `unix_ms`, or any other string as a `strftime` format.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TimestampFormat {
+    /// Seconds since the Unix epoch.
     Unix,
+    /// Milliseconds since the Unix epoch.
+    UnixMs,
+    /// Written with as many fractional digits, by threes, as the timestamp
+    /// needs, and a `Z` offset.
     RFC3339,
+    Strftime(String),
+}
+
+impl TimestampFormat {
+    pub fn render(&self, timestamp: &DateTime<Utc>) -> Value {
+        match self {
+            TimestampFormat::Unix => Value::Integer(timestamp.timestamp()),
+            TimestampFormat::UnixMs => Value::Integer(timestamp.timestamp_millis()),
+            TimestampFormat::RFC3339 => timestamp
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+                .into(),
+            TimestampFormat::Strftime(format) => timestamp.format(format).to_string().into(),
+        }
+    }
+}
+
+impl Serialize for TimestampFormat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            TimestampFormat::Unix => "unix",
+            TimestampFormat::UnixMs => "unix_ms",
+            TimestampFormat::RFC3339 => "rfc3339",
+            TimestampFormat::Strftime(format) => format,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for TimestampFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let format = String::deserialize(deserializer)?;
+        match format.as_str() {
+            "unix" => Ok(TimestampFormat::Unix),
+            "unix_ms" => Ok(TimestampFormat::UnixMs),
+            "rfc3339" => Ok(TimestampFormat::RFC3339),
+            _ if !format.contains('%') => Err(de::Error::custom(format!(
+                "unknown timestamp format {:?}, expected `rfc3339`, `unix`, `unix_ms` or a strftime format",
+                format
+            ))),
+            _ if StrftimeItems::new(&format).any(|item| item == Item::Error) => Err(
+                de::Error::custom(format!("invalid strftime format {:?}", format)),
+            ),
+            _ => Ok(TimestampFormat::Strftime(format)),
+        }
+    }
 }
 
 impl<E> From<E> for EncodingConfig<E> {
@@ -512,6 +576,72 @@ mod tests {
         assert!(!event.as_mut_log().contains(&Atom::from("Beep")));
     }
 
+    fn nested_event() -> Event {
+        let mut event = Event::new_empty_log();
+        {
+            let log = event.as_mut_log();
+            log.insert("message", "hello");
+            log.insert("a.b.c", 1);
+            log.insert("a.b.d", 2);
+            log.insert("a.e", 3);
+            log.insert("a.list[0].x", 4);
+            log.insert("ab", 5);
+            log.insert("internal.secret", "hunter2");
+        }
+        event
+    }
+
+    fn encode(event: Event) -> serde_json::Value {
+        serde_json::to_value(event.into_log()).unwrap()
+    }
+
+    const TOML_NESTED_ONLY_FIELDS: &str = "
+        encoding.codec = \"Snoot\"
+        encoding.only_fields = [\"message\", \"a.b\", \"a.list\"]
+    ";
+    #[test]
+    fn test_only_nested() {
+        let config: TestConfig = toml::from_str(TOML_NESTED_ONLY_FIELDS).unwrap();
+        let mut event = nested_event();
+
+        config.encoding.apply_rules(&mut event);
+
+        assert_eq!(
+            encode(event),
+            serde_json::json!({
+                "message": "hello",
+                "a": {
+                    "b": { "c": 1, "d": 2 },
+                    "list": [{ "x": 4 }],
+                },
+            })
+        );
+    }
+
+    const TOML_NESTED_EXCEPT_FIELDS: &str = "
+        encoding.codec = \"Snoot\"
+        encoding.except_fields = [\"a.b.c\", \"a.e\", \"internal.secret\"]
+    ";
+    #[test]
+    fn test_except_nested() {
+        let config: TestConfig = toml::from_str(TOML_NESTED_EXCEPT_FIELDS).unwrap();
+        let mut event = nested_event();
+
+        config.encoding.apply_rules(&mut event);
+
+        assert_eq!(
+            encode(event),
+            serde_json::json!({
+                "message": "hello",
+                "a": {
+                    "b": { "d": 2 },
+                    "list": [{ "x": 4 }],
+                },
+                "ab": 5,
+            })
+        );
+    }
+
     const TOML_TIMESTAMP_FORMAT: &str = "
         encoding.codec = \"Snoot\"
         encoding.timestamp_format = \"unix\"
@@ -552,4 +682,47 @@ mod tests {
             ),
         }
     }
+
+    #[test]
+    fn timestamp_formats() {
+        use chrono::TimeZone;
+
+        let timestamp = Utc.ymd(2020, 2, 29).and_hms_micro(23, 59, 58, 123_456);
+        let render = |format: &str| {
+            let config: TestConfig = toml::from_str(&format!(
+                r#"
+                encoding.codec = "Snoot"
+                encoding.timestamp_format = "{}"
+                "#,
+                format
+            ))
+            .unwrap();
+            let mut event = Event::new_empty_log();
+            event.as_mut_log().insert("timestamp", timestamp);
+            config.encoding.apply_rules(&mut event);
+            event.as_log()[&Atom::from("timestamp")].clone()
+        };
+
+        assert_eq!(
+            render("rfc3339"),
+            Value::from("2020-02-29T23:59:58.123456Z")
+        );
+        assert_eq!(render("unix"), Value::Integer(1_583_020_798));
+        assert_eq!(render("unix_ms"), Value::Integer(1_583_020_798_123));
+        assert_eq!(
+            render("%d/%m/%Y %H:%M:%S%.3f %z"),
+            Value::from("29/02/2020 23:59:58.123 +0000")
+        );
+
+        for format in &["unix_seconds", "%Q"] {
+            let config = toml::from_str::<TestConfig>(&format!(
+                r#"
+                encoding.codec = "Snoot"
+                encoding.timestamp_format = "{}"
+                "#,
+                format
+            ));
+            assert!(config.is_err(), "{}", format);
+        }
+    }
 }
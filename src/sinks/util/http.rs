@@ -1,4 +1,6 @@
 use super::{
+    dead_letter::DeadLetter,
+    proxy::{Proxies, ProxyConfig, ProxyConnector},
     retries::{RetryAction, RetryLogic},
     service::{TowerBatchedSink, TowerRequestSettings},
     Batch, BatchSettings,
@@ -12,14 +14,14 @@ use crate::{
 use bytes::Bytes;
 use futures::compat::Future01CompatExt;
 use futures01::{Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
-use http::header::HeaderValue;
+use http::header::{HeaderValue, PROXY_AUTHORIZATION};
 use http::{Request, StatusCode};
 use hyper::body::{Body, Payload};
 use hyper::client::HttpConnector;
 use hyper::Client;
 use hyper_openssl::HttpsConnector;
 use serde::{Deserialize, Serialize};
-use std::{fmt, sync::Arc};
+use std::{fmt, sync::Arc, time::Duration};
 use tokio01::executor::DefaultExecutor;
 use tower::Service;
 use tracing::Span;
@@ -62,6 +64,7 @@ where
     // the inner sink is applying back pressure. This trick is used in the `WithFlatMap`
     // sink combinator. https://docs.rs/futures/0.1.29/src/futures/sink/with_flat_map.rs.html#20
     slot: Option<B::Input>,
+    dead_letter: DeadLetter,
 }
 
 impl<T, B> BatchedHttpSink<T, B, HttpRetryLogic>
@@ -85,6 +88,7 @@ where
             request_settings,
             batch_settings,
             tls_settings,
+            &ProxyConfig::default(),
             cx,
         )
     }
@@ -97,6 +101,7 @@ where
     L: RetryLogic<Response = hyper::Response<Bytes>, Error = hyper::Error> + Send + 'static,
     T: HttpSink<Input = B::Input, Output = B::Output>,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn with_retry_logic(
         sink: T,
         batch: B,
@@ -104,19 +109,22 @@ where
         request_settings: TowerRequestSettings,
         batch_settings: BatchSettings,
         tls_settings: impl Into<MaybeTlsSettings>,
+        proxy: &ProxyConfig,
         cx: &SinkContext,
     ) -> Self {
         let sink = Arc::new(sink);
         let sink1 = sink.clone();
-        let svc =
-            HttpBatchService::new(cx.resolver(), tls_settings, move |b| sink1.build_request(b));
+        let svc = HttpBatchService::with_proxy(cx.resolver(), tls_settings, proxy, move |b| {
+            sink1.build_request(b)
+        });
 
-        let inner = request_settings.batch_sink(logic, svc, batch, batch_settings, cx.acker());
+        let inner = request_settings.batch_sink(logic, svc, batch, batch_settings, cx);
 
         Self {
             sink,
             inner,
             slot: None,
+            dead_letter: cx.dead_letter(),
         }
     }
 }
@@ -137,10 +145,25 @@ where
             return Ok(AsyncSink::NotReady(item));
         }
 
-        if let Some(item) = self.sink.encode_event(item) {
-            if let AsyncSink::NotReady(item) = self.inner.start_send(item)? {
-                self.poll_complete()?;
-                self.slot = Some(item);
+        // Only hold on to a copy of the event if there is somewhere to send it
+        // should encoding fail.
+        let original = if self.dead_letter.is_enabled() {
+            Some(item.clone())
+        } else {
+            None
+        };
+
+        match self.sink.encode_event(item) {
+            Some(item) => {
+                if let AsyncSink::NotReady(item) = self.inner.start_send(item)? {
+                    self.poll_complete()?;
+                    self.slot = Some(item);
+                }
+            }
+            None => {
+                if let Some(event) = original {
+                    self.dead_letter.send(event, "Event could not be encoded.");
+                }
             }
         }
 
@@ -160,7 +183,8 @@ where
 }
 
 pub struct HttpClient<B = Body> {
-    client: Client<HttpsConnector<HttpConnector<Resolver>>, B>,
+    client: Client<HttpsConnector<ProxyConnector<HttpConnector<Resolver>>>, B>,
+    proxies: Option<Arc<Proxies>>,
     span: Span,
     user_agent: HeaderValue,
 }
@@ -170,16 +194,41 @@ where
     B: Payload + Send + 'static,
     B::Data: Send,
 {
+    /// Builds a client that only proxies as directed by the environment.
     pub fn new(
         resolver: Resolver,
         tls_settings: impl Into<MaybeTlsSettings>,
+    ) -> crate::Result<HttpClient<B>> {
+        Self::with_proxy(resolver, tls_settings, &ProxyConfig::default())
+    }
+
+    pub fn with_proxy(
+        resolver: Resolver,
+        tls_settings: impl Into<MaybeTlsSettings>,
+        proxy: &ProxyConfig,
+    ) -> crate::Result<HttpClient<B>> {
+        Self::with_connect_timeout(resolver, tls_settings, proxy, None)
+    }
+
+    /// Like `with_proxy`, but gives up on establishing a TCP connection after
+    /// `connect_timeout`.
+    pub fn with_connect_timeout(
+        resolver: Resolver,
+        tls_settings: impl Into<MaybeTlsSettings>,
+        proxy: &ProxyConfig,
+        connect_timeout: Option<Duration>,
     ) -> crate::Result<HttpClient<B>> {
         let mut http = HttpConnector::new_with_resolver(resolver.clone());
         http.enforce_http(false);
+        http.set_connect_timeout(connect_timeout);
+        http.set_happy_eyeballs_timeout(Some(resolver.happy_eyeballs_delay()));
+
+        let proxies = proxy.build()?.map(Arc::new);
+        let proxied = ProxyConnector::new(http, proxies.clone());
 
         let settings = tls_settings.into();
         let tls = tls_connector_builder(&settings)?;
-        let mut https = HttpsConnector::with_connector(http, tls)?;
+        let mut https = HttpsConnector::with_connector(proxied, tls)?;
 
         let settings = settings.tls().cloned();
         https.set_callback(move |c, _uri| {
@@ -202,6 +251,7 @@ where
 
         Ok(HttpClient {
             client,
+            proxies,
             span,
             user_agent,
         })
@@ -234,6 +284,14 @@ where
                 .insert("User-Agent", self.user_agent.clone());
         }
 
+        if let Some(auth) = self
+            .proxies
+            .as_ref()
+            .and_then(|proxies| proxies.http_authorization(request.uri()))
+        {
+            request.headers_mut().insert(PROXY_AUTHORIZATION, auth);
+        }
+
         debug!(message = "sending request.", uri = %request.uri(), method = %request.method());
 
         let fut = self
@@ -256,6 +314,7 @@ impl<B> Clone for HttpClient<B> {
     fn clone(&self) -> Self {
         Self {
             client: self.client.clone(),
+            proxies: self.proxies.clone(),
             span: self.span.clone(),
             user_agent: self.user_agent.clone(),
         }
@@ -283,8 +342,22 @@ impl<B> HttpBatchService<B> {
         tls_settings: impl Into<MaybeTlsSettings>,
         request_builder: impl Fn(B) -> hyper::Request<Vec<u8>> + Sync + Send + 'static,
     ) -> HttpBatchService<B> {
-        let inner =
-            HttpClient::new(resolver, tls_settings).expect("Unable to initialize http client");
+        Self::with_proxy(
+            resolver,
+            tls_settings,
+            &ProxyConfig::default(),
+            request_builder,
+        )
+    }
+
+    pub fn with_proxy(
+        resolver: Resolver,
+        tls_settings: impl Into<MaybeTlsSettings>,
+        proxy: &ProxyConfig,
+        request_builder: impl Fn(B) -> hyper::Request<Vec<u8>> + Sync + Send + 'static,
+    ) -> HttpBatchService<B> {
+        let inner = HttpClient::with_proxy(resolver, tls_settings, proxy)
+            .expect("Unable to initialize http client");
 
         HttpBatchService {
             inner,
@@ -346,6 +419,7 @@ impl RetryLogic for HttpRetryLogic {
 #[serde(deny_unknown_fields, rename_all = "snake_case", tag = "strategy")]
 pub enum Auth {
     Basic { user: String, password: String },
+    Bearer { token: String },
 }
 
 impl Auth {
@@ -356,6 +430,13 @@ impl Auth {
                 let auth = headers::Authorization::basic(&user, &password);
                 req.headers_mut().typed_insert(auth);
             }
+            Auth::Bearer { token } => match headers::Authorization::bearer(&token) {
+                Ok(auth) => {
+                    use headers::HeaderMapExt;
+                    req.headers_mut().typed_insert(auth);
+                }
+                Err(error) => error!(message = "Invalid bearer token.", %error),
+            },
         }
     }
 }
@@ -443,4 +524,84 @@ mod test {
         let (body, _rest) = rx.into_future().wait().unwrap();
         assert_eq!(body.unwrap().unwrap(), "hello");
     }
+
+    #[test]
+    fn util_http_client_sends_through_proxy() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+        let proxy_addr = crate::test_util::next_addr();
+        let target = crate::test_util::next_addr();
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+
+        let (tx, rx) = futures01::sync::mpsc::channel(10);
+        let new_service = move || {
+            let tx = tx.clone();
+            service_fn(move |req: hyper::Request<Body>| {
+                let seen = (
+                    req.uri().to_string(),
+                    req.headers().get(PROXY_AUTHORIZATION).cloned(),
+                );
+                tx.clone()
+                    .send(seen)
+                    .map_err(|_| "Send error".to_string())
+                    .map(|_| Response::new(Body::empty()))
+            })
+        };
+        let server = Server::bind(&proxy_addr)
+            .serve(new_service)
+            .map_err(|e| eprintln!("server error: {}", e));
+        rt.spawn(server);
+
+        let proxy = ProxyConfig {
+            http: Some(format!("http://user:pass@{}", proxy_addr)),
+            ..Default::default()
+        };
+        let mut client = HttpClient::with_proxy(resolver, None, &proxy).unwrap();
+        let uri = format!("http://{}/path", target).parse::<Uri>().unwrap();
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        rt.block_on(client.call(request)).unwrap();
+        let _ = rt.shutdown_now();
+
+        let (seen, _rest) = rx.into_future().wait().unwrap();
+        let (uri, auth) = seen.unwrap();
+        assert_eq!(uri, format!("http://{}/path", target));
+        assert_eq!(auth.unwrap(), "Basic dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn util_http_client_bypasses_proxy_for_no_proxy() {
+        let mut rt = crate::runtime::Runtime::new().unwrap();
+        let target = crate::test_util::next_addr();
+        let resolver = Resolver::new(Vec::new(), rt.executor()).unwrap();
+
+        let (tx, rx) = futures01::sync::mpsc::channel(10);
+        let new_service = move || {
+            let tx = tx.clone();
+            service_fn(move |req: hyper::Request<Body>| {
+                tx.clone()
+                    .send(req.uri().to_string())
+                    .map_err(|_| "Send error".to_string())
+                    .map(|_| Response::new(Body::empty()))
+            })
+        };
+        let server = Server::bind(&target)
+            .serve(new_service)
+            .map_err(|e| eprintln!("server error: {}", e));
+        rt.spawn(server);
+
+        // Nothing listens on the proxy address, so the request can only
+        // succeed by going straight to the target.
+        let proxy = ProxyConfig {
+            http: Some(format!("http://{}", crate::test_util::next_addr())),
+            no_proxy: vec![target.ip().to_string()],
+            ..Default::default()
+        };
+        let mut client = HttpClient::with_proxy(resolver, None, &proxy).unwrap();
+        let uri = format!("http://{}/path", target).parse::<Uri>().unwrap();
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        rt.block_on(client.call(request)).unwrap();
+        let _ = rt.shutdown_now();
+
+        let (uri, _rest) = rx.into_future().wait().unwrap();
+        assert_eq!(uri.unwrap(), "/path");
+    }
 }
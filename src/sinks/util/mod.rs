@@ -1,7 +1,16 @@
+#[cfg(feature = "avro-rs")]
+pub mod avro;
 pub mod batch;
 pub mod buffer;
+pub mod compression;
+#[cfg(feature = "csv")]
+pub mod csv;
+pub mod dead_letter;
 pub mod encoding;
 pub mod http;
+pub mod partition_limit;
+pub mod protobuf;
+pub mod proxy;
 pub mod retries;
 #[cfg(feature = "rusoto_core")]
 pub mod rusoto;
@@ -21,11 +30,13 @@ use encoding::{EncodingConfig, EncodingConfiguration};
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
 
-pub use batch::{Batch, BatchBytesConfig, BatchEventsConfig, BatchSettings};
+pub use batch::{Batch, BatchConfig, BatchSettings, VecBuffer};
 pub use buffer::json::{BoxedRawValue, JsonArrayBuffer};
 pub use buffer::metrics::{MetricBuffer, MetricEntry};
 pub use buffer::partition::Partition;
 pub use buffer::{Buffer, Compression, PartitionBuffer, PartitionInnerBuffer};
+pub use dead_letter::DeadLetter;
+pub use proxy::ProxyConfig;
 pub use service::{ServiceBuilderExt, TowerRequestConfig, TowerRequestLayer, TowerRequestSettings};
 pub use sink::{BatchSink, PartitionBatchSink, StreamSink};
 pub use uri::UriSerde;
@@ -75,3 +86,19 @@ pub fn encode_event(mut event: Event, encoding: &EncodingConfig<Encoding>) -> Op
     .map_err(|error| error!(message = "Unable to encode.", %error))
     .ok()
 }
+
+/// Names an object written to object storage by a sink, after the batch's
+/// `prefix`, the current time in `time_format` and, with `append_uuid`, a
+/// random UUID, so that batches sent within the same second don't collide.
+#[cfg(feature = "uuid")]
+pub fn object_name(prefix: &[u8], time_format: &str, append_uuid: bool, extension: &str) -> String {
+    // TODO: pull the seconds from the last event
+    let time = chrono::Utc::now().format(time_format);
+    let name = if append_uuid {
+        format!("{}-{}", time, uuid::Uuid::new_v4().to_hyphenated())
+    } else {
+        time.to_string()
+    };
+
+    format!("{}{}.{}", String::from_utf8_lossy(prefix), name, extension)
+}
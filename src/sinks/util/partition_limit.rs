@@ -0,0 +1,202 @@
+//! Caps how many partitions a sink with templated partition keys, like an S3
+//! key prefix or a CloudWatch stream name, batches events into at once, so a
+//! template rendering a field with many distinct values can't have it create
+//! a flood of tiny batches.
+
+use crate::internal_events::PartitionCardinalityExceeded;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Snafu)]
+enum PartitionLimitError {
+    #[snafu(display("`partition_limit.max_partitions` must be at least 1"))]
+    ZeroMaxPartitions,
+    #[snafu(display("`partition_limit.default_partition` can't be empty"))]
+    EmptyDefaultPartition,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PartitionLimitConfig {
+    /// How many distinct partition keys can be used within a batch timeout.
+    pub max_partitions: usize,
+    /// Where events go whose partition key is over the limit.
+    pub default_partition: String,
+}
+
+impl PartitionLimitConfig {
+    /// Keys used within `window`, the sink's batch timeout, hold on to their
+    /// place, as their batches may not have been flushed yet.
+    pub fn build<K>(&self, component: &str, window: Duration) -> crate::Result<PartitionLimiter<K>>
+    where
+        K: Clone + Hash + Eq,
+    {
+        if self.max_partitions == 0 {
+            return Err(PartitionLimitError::ZeroMaxPartitions.into());
+        }
+        if self.default_partition.is_empty() {
+            return Err(PartitionLimitError::EmptyDefaultPartition.into());
+        }
+
+        Ok(PartitionLimiter {
+            component: component.into(),
+            max_partitions: self.max_partitions,
+            window,
+            window_start: None,
+            window_id: 0,
+            uses: 0,
+            keys: HashMap::new(),
+            by_use: BTreeMap::new(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct PartitionLimiter<K> {
+    component: String,
+    max_partitions: usize,
+    window: Duration,
+    window_start: Option<Instant>,
+    window_id: u64,
+    /// Counts uses of keys, to order them by when they were last used.
+    uses: u64,
+    /// The keys in use, with their last use and the window it was in.
+    keys: HashMap<K, (u64, u64)>,
+    /// The keys in use by their last use, least recently used first.
+    by_use: BTreeMap<u64, K>,
+}
+
+impl<K> PartitionLimiter<K>
+where
+    K: Clone + Hash + Eq,
+{
+    /// The key to partition an event by: `key` itself, unless `max_partitions`
+    /// other keys were all used within the current window, in which case the
+    /// key `overflow` makes from it.
+    ///
+    /// Once there are `max_partitions` keys, a new one takes the place of the
+    /// least recently used key, if that wasn't used within the current window.
+    pub fn partition(&mut self, key: K, overflow: impl FnOnce(K) -> K) -> K {
+        self.partition_at(key, overflow, Instant::now())
+    }
+
+    fn partition_at(&mut self, key: K, overflow: impl FnOnce(K) -> K, now: Instant) -> K {
+        match self.window_start {
+            Some(start) if now < start + self.window => (),
+            _ => {
+                self.window_start = Some(now);
+                self.window_id += 1;
+            }
+        }
+        self.uses += 1;
+        let this_use = (self.uses, self.window_id);
+
+        if let Some(last_use) = self.keys.get_mut(&key) {
+            self.by_use.remove(&last_use.0);
+            *last_use = this_use;
+            self.by_use.insert(this_use.0, key.clone());
+            return key;
+        }
+
+        if self.keys.len() >= self.max_partitions {
+            let least_recent = self
+                .by_use
+                .iter()
+                .next()
+                .map(|(_, key)| (key.clone(), self.keys[key]));
+            match least_recent {
+                Some((least_recent, (last_use, window_id))) if window_id < self.window_id => {
+                    self.keys.remove(&least_recent);
+                    self.by_use.remove(&last_use);
+                }
+                _ => {
+                    emit!(PartitionCardinalityExceeded {
+                        component: &self.component,
+                        max_partitions: self.max_partitions,
+                    });
+                    return overflow(key);
+                }
+            }
+        }
+
+        self.keys.insert(key.clone(), this_use);
+        self.by_use.insert(this_use.0, key.clone());
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(10);
+
+    fn limiter(max_partitions: usize) -> PartitionLimiter<String> {
+        let config = PartitionLimitConfig {
+            max_partitions,
+            default_partition: "overflow".into(),
+        };
+        config.build("test", WINDOW).unwrap()
+    }
+
+    fn partition(limiter: &mut PartitionLimiter<String>, key: &str, now: Instant) -> String {
+        limiter.partition_at(key.into(), |_| "overflow".into(), now)
+    }
+
+    #[test]
+    fn partition_limit_routes_overflow_to_default() {
+        let mut limiter = limiter(10);
+        let now = Instant::now();
+
+        let partitions = (0..1000)
+            .map(|i| partition(&mut limiter, &format!("key-{}", i % 100), now))
+            .collect::<Vec<_>>();
+
+        for (i, partition) in partitions.iter().enumerate() {
+            if i % 100 < 10 {
+                assert_eq!(partition, &format!("key-{}", i % 100));
+            } else {
+                assert_eq!(partition, "overflow");
+            }
+        }
+        assert_eq!(limiter.keys.len(), 10);
+    }
+
+    #[test]
+    fn partition_limit_evicts_least_recently_used() {
+        let mut limiter = limiter(2);
+        let start = Instant::now();
+
+        assert_eq!(partition(&mut limiter, "a", start), "a");
+        assert_eq!(partition(&mut limiter, "b", start), "b");
+        assert_eq!(partition(&mut limiter, "c", start), "overflow");
+
+        // In the next window, `a` is used again, so `b` is the one to make
+        // room for `c`, after which neither can make room for `d`.
+        let next = start + WINDOW;
+        assert_eq!(partition(&mut limiter, "a", next), "a");
+        assert_eq!(partition(&mut limiter, "c", next), "c");
+        assert_eq!(partition(&mut limiter, "d", next), "overflow");
+        assert_eq!(partition(&mut limiter, "b", next), "overflow");
+        assert_eq!(partition(&mut limiter, "a", next), "a");
+    }
+
+    #[test]
+    fn partition_limit_invalid_config() {
+        let config = PartitionLimitConfig {
+            max_partitions: 0,
+            default_partition: "overflow".into(),
+        };
+        assert!(config.build::<String>("test", WINDOW).is_err());
+        let config = PartitionLimitConfig {
+            max_partitions: 1,
+            default_partition: "".into(),
+        };
+        assert!(config.build::<String>("test", WINDOW).is_err());
+    }
+}
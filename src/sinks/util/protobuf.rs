@@ -0,0 +1,638 @@
+//! Encodes log events as protobuf messages described by a compiled
+//! `FileDescriptorSet`, as produced by `protoc --include_imports -o`.
+//!
+//! Fields are matched to event fields by their name, or their JSON name.
+//! Nested messages and maps are read from nested event fields, and fields the
+//! event doesn't have are left out, so consumers read their default values.
+
+use crate::event::{LogEvent, Value};
+use prost::{
+    encoding::{encode_key, encode_varint, WireType},
+    Message,
+};
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet,
+};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{collections::BTreeMap, collections::HashMap, path::PathBuf, sync::Arc};
+
+const TIMESTAMP: &str = ".google.protobuf.Timestamp";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProtobufConfig {
+    pub descriptor_set_file: PathBuf,
+    /// The fully qualified name of the message events are encoded as.
+    pub message_type: String,
+    #[serde(default)]
+    pub unknown_fields: UnknownFields,
+    #[serde(skip)]
+    descriptors: Option<Arc<Descriptors>>,
+}
+
+/// What to do with event fields the message has no field for.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum UnknownFields {
+    #[derivative(Default)]
+    Drop,
+    Error,
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("The protobuf codec requires a `protobuf` table"))]
+    MissingProtobufConfig,
+    #[snafu(display("A `protobuf` table is only used with the protobuf codec"))]
+    UnusedProtobufConfig,
+    #[snafu(display("Could not read descriptor set {:?}: {}", path, source))]
+    ReadDescriptorSet {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not decode descriptor set: {}", source))]
+    DecodeDescriptorSet { source: prost::DecodeError },
+    #[snafu(display("Message type {:?} is not in the descriptor set", name))]
+    UnknownMessageType { name: String },
+}
+
+#[derive(Debug, Snafu)]
+pub enum EncodeError {
+    #[snafu(display("Event field {:?} is not a field of the message", field))]
+    UnknownField { field: String },
+    #[snafu(display("Field {:?} can't be encoded as protobuf type {}", field, expected))]
+    Mismatch { field: String, expected: String },
+}
+
+/// The messages and enums of a descriptor set, by their fully qualified name
+/// with a leading dot, as fields refer to them.
+#[derive(Debug, Default)]
+struct Descriptors {
+    messages: HashMap<String, MessageType>,
+    enums: HashMap<String, EnumDescriptorProto>,
+}
+
+#[derive(Debug)]
+struct MessageType {
+    descriptor: DescriptorProto,
+    /// Repeated scalars are packed unless told otherwise in proto3.
+    proto3: bool,
+}
+
+impl Descriptors {
+    fn new(set: FileDescriptorSet) -> Self {
+        let mut descriptors = Self::default();
+        for file in set.file {
+            let scope = match &file.package {
+                Some(package) if !package.is_empty() => format!(".{}", package),
+                _ => String::new(),
+            };
+            let proto3 = file.syntax.as_ref().map(String::as_str) == Some("proto3");
+            descriptors.add_enums(&scope, file.enum_type);
+            for message in file.message_type {
+                descriptors.add_message(&scope, message, proto3);
+            }
+        }
+        descriptors
+    }
+
+    fn add_message(&mut self, scope: &str, mut message: DescriptorProto, proto3: bool) {
+        let name = format!("{}.{}", scope, message.name.clone().unwrap_or_default());
+        self.add_enums(&name, std::mem::replace(&mut message.enum_type, Vec::new()));
+        for nested in std::mem::replace(&mut message.nested_type, Vec::new()) {
+            self.add_message(&name, nested, proto3);
+        }
+        self.messages.insert(
+            name,
+            MessageType {
+                descriptor: message,
+                proto3,
+            },
+        );
+    }
+
+    fn add_enums(&mut self, scope: &str, enums: Vec<EnumDescriptorProto>) {
+        for descriptor in enums {
+            let name = format!("{}.{}", scope, descriptor.name.clone().unwrap_or_default());
+            self.enums.insert(name, descriptor);
+        }
+    }
+}
+
+/// Checks that a `protobuf` table is configured exactly when the protobuf
+/// codec is in use.
+pub fn validate(uses_protobuf: bool, config: &Option<ProtobufConfig>) -> crate::Result<()> {
+    match (uses_protobuf, config) {
+        (true, None) => Err(BuildError::MissingProtobufConfig.into()),
+        (false, Some(_)) => Err(BuildError::UnusedProtobufConfig.into()),
+        _ => Ok(()),
+    }
+}
+
+impl ProtobufConfig {
+    /// Reads the descriptor set, which has to be done before encoding.
+    pub fn load(&mut self) -> crate::Result<()> {
+        let bytes = std::fs::read(&self.descriptor_set_file).context(ReadDescriptorSet {
+            path: self.descriptor_set_file.clone(),
+        })?;
+        let set = FileDescriptorSet::decode(&bytes[..]).context(DecodeDescriptorSet)?;
+        let descriptors = Descriptors::new(set);
+
+        if !descriptors.messages.contains_key(&self.full_name()) {
+            return Err(BuildError::UnknownMessageType {
+                name: self.message_type.clone(),
+            }
+            .into());
+        }
+        self.descriptors = Some(Arc::new(descriptors));
+        Ok(())
+    }
+
+    fn full_name(&self) -> String {
+        format!(".{}", self.message_type.trim_start_matches('.'))
+    }
+
+    pub fn encode(&self, log: LogEvent) -> Result<Vec<u8>, EncodeError> {
+        let descriptors = self
+            .descriptors
+            .as_ref()
+            .expect("protobuf descriptors are loaded when the sink is built");
+        let fields = log.into_iter().collect::<BTreeMap<_, _>>();
+
+        let mut buf = Vec::new();
+        Encoder {
+            descriptors,
+            unknown_fields: self.unknown_fields,
+        }
+        .message(&self.full_name(), &fields, "", &mut buf)?;
+        Ok(buf)
+    }
+}
+
+struct Encoder<'a> {
+    descriptors: &'a Descriptors,
+    unknown_fields: UnknownFields,
+}
+
+/// A single value in its wire representation.
+enum Scalar {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Bytes(Vec<u8>),
+}
+
+impl<'a> Encoder<'a> {
+    fn message(
+        &self,
+        name: &str,
+        fields: &BTreeMap<String, Value>,
+        parent: &str,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        let message = &self.descriptors.messages[name];
+
+        let mut used = 0;
+        for field in &message.descriptor.field {
+            let field_name = field.name.as_ref().map(String::as_str).unwrap_or_default();
+            let value = fields.get(field_name).or_else(|| {
+                field
+                    .json_name
+                    .as_ref()
+                    .and_then(|json_name| fields.get(json_name))
+            });
+            if let Some(value) = value {
+                used += 1;
+                let path = join(parent, field_name);
+                self.field(field, message.proto3, value, &path, buf)?;
+            }
+        }
+
+        if self.unknown_fields == UnknownFields::Error && used < fields.len() {
+            let known = |key: &String| {
+                message.descriptor.field.iter().any(|field| {
+                    field.name.as_ref() == Some(key) || field.json_name.as_ref() == Some(key)
+                })
+            };
+            if let Some(key) = fields.keys().find(|key| !known(key)) {
+                return Err(EncodeError::UnknownField {
+                    field: join(parent, key),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn field(
+        &self,
+        field: &FieldDescriptorProto,
+        proto3: bool,
+        value: &Value,
+        path: &str,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        let tag = field.number.unwrap_or_default() as u32;
+        let field_type = Type::from_i32(field.r#type.unwrap_or_default()).unwrap_or(Type::Bytes);
+
+        if field.label == Some(Label::Repeated as i32) {
+            if let Some(entry) = self.map_entry(field) {
+                return match value {
+                    Value::Map(map) => map.iter().try_for_each(|(key, value)| {
+                        let mut entry_fields = BTreeMap::new();
+                        entry_fields.insert("key".to_string(), Value::from(key.as_str()));
+                        entry_fields.insert("value".to_string(), value.clone());
+                        self.nested(entry, &entry_fields, &join(path, key), tag, buf)
+                    }),
+                    _ => Err(mismatch(path, field)),
+                };
+            }
+
+            let values = match value {
+                Value::Array(values) => values.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            let packed = field
+                .options
+                .as_ref()
+                .and_then(|options| options.packed)
+                .unwrap_or(proto3);
+            if packed && is_packable(field_type) {
+                let mut packed = Vec::new();
+                for (i, value) in values.iter().enumerate() {
+                    let path = format!("{}[{}]", path, i);
+                    match self.scalar(field, field_type, value, &path)? {
+                        Scalar::Varint(v) => encode_varint(v, &mut packed),
+                        Scalar::Fixed64(v) => packed.extend_from_slice(&v.to_le_bytes()),
+                        Scalar::Fixed32(v) => packed.extend_from_slice(&v.to_le_bytes()),
+                        Scalar::Bytes(_) => unreachable!("length delimited types aren't packed"),
+                    }
+                }
+                write(tag, Scalar::Bytes(packed), buf);
+                return Ok(());
+            }
+
+            return values.iter().enumerate().try_for_each(|(i, value)| {
+                self.single(field, field_type, value, &format!("{}[{}]", path, i), buf)
+            });
+        }
+
+        self.single(field, field_type, value, path, buf)
+    }
+
+    fn single(
+        &self,
+        field: &FieldDescriptorProto,
+        field_type: Type,
+        value: &Value,
+        path: &str,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        let tag = field.number.unwrap_or_default() as u32;
+        let type_name = field
+            .type_name
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or_default();
+
+        match (field_type, value) {
+            (Type::Message, Value::Timestamp(ts)) if type_name == TIMESTAMP => {
+                let mut message = Vec::new();
+                write(1, Scalar::Varint(ts.timestamp() as u64), &mut message);
+                write(
+                    2,
+                    Scalar::Varint(u64::from(ts.timestamp_subsec_nanos())),
+                    &mut message,
+                );
+                write(tag, Scalar::Bytes(message), buf);
+                Ok(())
+            }
+            (Type::Message, Value::Map(fields))
+                if self.descriptors.messages.contains_key(type_name) =>
+            {
+                self.nested(type_name, fields, path, tag, buf)
+            }
+            (Type::Message, _) | (Type::Group, _) => Err(mismatch(path, field)),
+            _ => {
+                let scalar = self.scalar(field, field_type, value, path)?;
+                write(tag, scalar, buf);
+                Ok(())
+            }
+        }
+    }
+
+    fn nested(
+        &self,
+        name: &str,
+        fields: &BTreeMap<String, Value>,
+        path: &str,
+        tag: u32,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        let mut message = Vec::new();
+        self.message(name, fields, path, &mut message)?;
+        write(tag, Scalar::Bytes(message), buf);
+        Ok(())
+    }
+
+    fn scalar(
+        &self,
+        field: &FieldDescriptorProto,
+        field_type: Type,
+        value: &Value,
+        path: &str,
+    ) -> Result<Scalar, EncodeError> {
+        use std::convert::TryFrom;
+
+        let scalar = match (field_type, value) {
+            (Type::Double, Value::Float(f)) => Some(Scalar::Fixed64(f.to_bits())),
+            (Type::Double, Value::Integer(i)) => Some(Scalar::Fixed64((*i as f64).to_bits())),
+            (Type::Float, Value::Float(f)) => Some(Scalar::Fixed32((*f as f32).to_bits())),
+            (Type::Float, Value::Integer(i)) => Some(Scalar::Fixed32((*i as f32).to_bits())),
+            (Type::Int64, Value::Integer(i)) => Some(Scalar::Varint(*i as u64)),
+            (Type::Uint64, Value::Integer(i)) => u64::try_from(*i).ok().map(Scalar::Varint),
+            (Type::Int32, Value::Integer(i)) => i32::try_from(*i)
+                .ok()
+                .map(|i| Scalar::Varint(i64::from(i) as u64)),
+            (Type::Uint32, Value::Integer(i)) => {
+                u32::try_from(*i).ok().map(|i| Scalar::Varint(u64::from(i)))
+            }
+            (Type::Sint64, Value::Integer(i)) => {
+                Some(Scalar::Varint(((i << 1) ^ (i >> 63)) as u64))
+            }
+            (Type::Sint32, Value::Integer(i)) => i32::try_from(*i)
+                .ok()
+                .map(|i| Scalar::Varint(u64::from(((i << 1) ^ (i >> 31)) as u32))),
+            (Type::Fixed64, Value::Integer(i)) => u64::try_from(*i).ok().map(Scalar::Fixed64),
+            (Type::Sfixed64, Value::Integer(i)) => Some(Scalar::Fixed64(*i as u64)),
+            (Type::Fixed32, Value::Integer(i)) => u32::try_from(*i).ok().map(Scalar::Fixed32),
+            (Type::Sfixed32, Value::Integer(i)) => {
+                i32::try_from(*i).ok().map(|i| Scalar::Fixed32(i as u32))
+            }
+            (Type::Bool, Value::Boolean(b)) => Some(Scalar::Varint(*b as u64)),
+            (Type::String, Value::Bytes(_)) | (Type::String, Value::Timestamp(_)) => {
+                Some(Scalar::Bytes(value.to_string_lossy().into_bytes()))
+            }
+            (Type::Bytes, Value::Bytes(bytes)) => Some(Scalar::Bytes(bytes.to_vec())),
+            (Type::Enum, Value::Integer(i)) => i32::try_from(*i)
+                .ok()
+                .map(|i| Scalar::Varint(i64::from(i) as u64)),
+            (Type::Enum, Value::Bytes(_)) => {
+                let symbol = value.to_string_lossy();
+                field
+                    .type_name
+                    .as_ref()
+                    .and_then(|name| self.descriptors.enums.get(name))
+                    .and_then(|descriptor| {
+                        descriptor
+                            .value
+                            .iter()
+                            .find(|value| value.name.as_ref() == Some(&symbol))
+                    })
+                    .map(|value| Scalar::Varint(i64::from(value.number.unwrap_or_default()) as u64))
+            }
+            _ => None,
+        };
+
+        scalar.ok_or_else(|| mismatch(path, field))
+    }
+
+    /// The entry message of a `map<K, V>` field.
+    fn map_entry(&self, field: &FieldDescriptorProto) -> Option<&str> {
+        let name = field.type_name.as_ref()?;
+        let message = self.descriptors.messages.get(name)?;
+        let is_entry = message
+            .descriptor
+            .options
+            .as_ref()
+            .and_then(|options| options.map_entry)
+            .unwrap_or(false);
+        if is_entry {
+            Some(name)
+        } else {
+            None
+        }
+    }
+}
+
+fn write(tag: u32, scalar: Scalar, buf: &mut Vec<u8>) {
+    match scalar {
+        Scalar::Varint(v) => {
+            encode_key(tag, WireType::Varint, buf);
+            encode_varint(v, buf);
+        }
+        Scalar::Fixed64(v) => {
+            encode_key(tag, WireType::SixtyFourBit, buf);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        Scalar::Fixed32(v) => {
+            encode_key(tag, WireType::ThirtyTwoBit, buf);
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        Scalar::Bytes(bytes) => {
+            encode_key(tag, WireType::LengthDelimited, buf);
+            encode_varint(bytes.len() as u64, buf);
+            buf.extend_from_slice(&bytes);
+        }
+    }
+}
+
+fn is_packable(field_type: Type) -> bool {
+    match field_type {
+        Type::String | Type::Bytes | Type::Message | Type::Group => false,
+        _ => true,
+    }
+}
+
+fn join(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", parent, name)
+    }
+}
+
+fn mismatch(path: &str, field: &FieldDescriptorProto) -> EncodeError {
+    let expected = match field.type_name.as_ref() {
+        Some(name) => name.trim_start_matches('.').to_string(),
+        None => Type::from_i32(field.r#type.unwrap_or_default())
+            .map(|field_type| format!("{:?}", field_type).to_lowercase())
+            .unwrap_or_default(),
+    };
+    EncodeError::Mismatch {
+        field: path.into(),
+        expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, prost_derive::Message)]
+    struct Log {
+        #[prost(string, tag = "1")]
+        message: String,
+        #[prost(message, optional, tag = "2")]
+        timestamp: Option<prost_types::Timestamp>,
+        #[prost(int64, tag = "3")]
+        status: i64,
+        #[prost(double, tag = "4")]
+        duration: f64,
+        #[prost(bool, tag = "5")]
+        success: bool,
+        #[prost(int32, tag = "6")]
+        level: i32,
+        #[prost(string, repeated, tag = "7")]
+        tags: Vec<String>,
+        #[prost(sint64, repeated, tag = "8")]
+        offsets: Vec<i64>,
+        #[prost(message, optional, tag = "9")]
+        request: Option<Request>,
+        #[prost(map = "string, string", tag = "10")]
+        labels: HashMap<String, String>,
+        #[prost(string, tag = "11")]
+        source_type: String,
+    }
+
+    #[derive(Clone, PartialEq, prost_derive::Message)]
+    struct Request {
+        #[prost(string, tag = "1")]
+        method: String,
+        #[prost(uint32, tag = "2")]
+        bytes: u32,
+    }
+
+    fn config(extra: &str) -> ProtobufConfig {
+        let mut config: ProtobufConfig = toml::from_str(&format!(
+            r#"
+            descriptor_set_file = "tests/data/protobuf/log.desc"
+            message_type = "test.Log"
+            {}
+            "#,
+            extra
+        ))
+        .unwrap();
+        config.load().unwrap();
+        config
+    }
+
+    fn encode(config: &ProtobufConfig, event: Event) -> Result<Log, EncodeError> {
+        let bytes = config.encode(event.into_log())?;
+        Ok(Log::decode(&bytes[..]).unwrap())
+    }
+
+    #[test]
+    fn protobuf_encodes_event_as_message() {
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+        log.insert("message", "hello");
+        log.insert("timestamp", Utc.timestamp(1_580_000_000, 123));
+        log.insert("status", 404);
+        log.insert("duration", 1.5);
+        log.insert("success", true);
+        log.insert("level", "WARN");
+        log.insert("tags", vec![Value::from("a"), Value::from("b")]);
+        log.insert("offsets", vec![Value::from(-1), Value::from(300)]);
+        log.insert("request.method", "GET");
+        log.insert("request.bytes", 512);
+        log.insert("labels.app", "web");
+        log.insert("labels.zone", "b");
+        log.insert("sourceType", "file");
+
+        let decoded = encode(&config(""), event).unwrap();
+
+        assert_eq!(
+            decoded,
+            Log {
+                message: "hello".into(),
+                timestamp: Some(prost_types::Timestamp {
+                    seconds: 1_580_000_000,
+                    nanos: 123,
+                }),
+                status: 404,
+                duration: 1.5,
+                success: true,
+                level: 1,
+                tags: vec!["a".into(), "b".into()],
+                offsets: vec![-1, 300],
+                request: Some(Request {
+                    method: "GET".into(),
+                    bytes: 512,
+                }),
+                labels: vec![("app".into(), "web".into()), ("zone".into(), "b".into())]
+                    .into_iter()
+                    .collect(),
+                source_type: "file".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn protobuf_leaves_out_missing_fields() {
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("message", "hello");
+        event.as_mut_log().insert("unknown", "dropped");
+
+        let config = config("");
+        let bytes = config.encode(event.into_log()).unwrap();
+
+        // Only the message field is written.
+        assert_eq!(bytes, b"\x0a\x05hello".to_vec());
+    }
+
+    #[test]
+    fn protobuf_rejects_unknown_and_mismatched_fields() {
+        let config = config("unknown_fields = \"error\"");
+
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("message", "hello");
+        event.as_mut_log().insert("request.path", "/");
+        match encode(&config, event) {
+            Err(EncodeError::UnknownField { field }) => assert_eq!(field, "request.path"),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("status", "not a number");
+        match encode(&config, event) {
+            Err(EncodeError::Mismatch { field, expected }) => {
+                assert_eq!(field, "status");
+                assert_eq!(expected, "int64");
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let mut event = Event::new_empty_log();
+        event.as_mut_log().insert("level", "DEBUG");
+        match encode(&config, event) {
+            Err(EncodeError::Mismatch { field, expected }) => {
+                assert_eq!(field, "level");
+                assert_eq!(expected, "test.Log.Level");
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn protobuf_requires_known_message_type() {
+        let mut config: ProtobufConfig = toml::from_str(
+            r#"
+            descriptor_set_file = "tests/data/protobuf/log.desc"
+            message_type = "test.Missing"
+            "#,
+        )
+        .unwrap();
+        assert!(config.load().is_err());
+
+        assert!(validate(true, &None).is_err());
+        assert!(validate(false, &Some(config.clone())).is_err());
+        assert!(validate(true, &Some(config)).is_ok());
+    }
+}
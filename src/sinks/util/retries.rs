@@ -1,13 +1,20 @@
 use super::service::Elapsed;
 use crate::Error;
 use futures01::{try_ready, Async, Future, Poll};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
 use std::{
     cmp,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use tokio01::timer::Delay;
+use tokio01::{clock, timer::Delay};
 use tower::retry::Policy;
 
+/// The budget shared by the sinks without one of their own, from the global
+/// `retry_budget` option. Like the log schema, it's set once at startup.
+pub static GLOBAL_RETRY_BUDGET: OnceCell<RetryBudget> = OnceCell::new();
+
 pub enum RetryAction {
     /// Indicate that this request should be retried with a reason
     Retry(String),
@@ -29,6 +36,100 @@ pub trait RetryLogic: Clone {
     }
 }
 
+/// Bounds the retries made overall, on top of the attempts of each request,
+/// so a broadly failing downstream isn't sent ever more retries. Each retry
+/// takes a token; tokens come back with successful requests and, slowly,
+/// over time.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RetryBudgetConfig {
+    /// Tokens the budget holds at most, and starts with.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Tokens earned by each successful request.
+    #[serde(default = "default_retry_ratio")]
+    pub retry_ratio: f64,
+    /// Tokens earned each second whatever happens, so a downstream failing
+    /// every request is still retried now and then.
+    #[serde(default = "default_min_retries_per_sec")]
+    pub min_retries_per_sec: f64,
+}
+
+fn default_max_retries() -> u32 {
+    100
+}
+
+fn default_retry_ratio() -> f64 {
+    0.2
+}
+
+fn default_min_retries_per_sec() -> f64 {
+    1.0
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            retry_ratio: default_retry_ratio(),
+            min_retries_per_sec: default_min_retries_per_sec(),
+        }
+    }
+}
+
+/// A token bucket over retries, shared by all the requests of the sinks
+/// using it, however many are in flight.
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    config: RetryBudgetConfig,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RetryBudget {
+    pub fn new(config: RetryBudgetConfig) -> Self {
+        Self {
+            config,
+            state: Arc::new(Mutex::new(BudgetState {
+                tokens: config.max_retries as f64,
+                refilled_at: clock::now(),
+            })),
+        }
+    }
+
+    fn max_tokens(&self) -> f64 {
+        self.config.max_retries as f64
+    }
+
+    /// Credits a successful request.
+    pub fn deposit(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens = (state.tokens + self.config.retry_ratio.max(0.0)).min(self.max_tokens());
+    }
+
+    /// Takes a token for a retry, if there's one left.
+    pub fn withdraw(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = clock::now();
+        let elapsed = now.saturating_duration_since(state.refilled_at);
+        let refill = elapsed.as_secs_f64() * self.config.min_retries_per_sec.max(0.0);
+        state.tokens = (state.tokens + refill).min(self.max_tokens());
+        state.refilled_at = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FixedRetryPolicy<L> {
     remaining_attempts: usize,
@@ -36,6 +137,7 @@ pub struct FixedRetryPolicy<L> {
     current_duration: Duration,
     max_duration: Duration,
     logic: L,
+    budget: Option<RetryBudget>,
 }
 
 pub struct RetryPolicyFuture<L: RetryLogic> {
@@ -56,9 +158,16 @@ impl<L: RetryLogic> FixedRetryPolicy<L> {
             current_duration: initial_backoff,
             max_duration,
             logic,
+            budget: None,
         }
     }
 
+    /// Only retries while `budget` has tokens left.
+    pub fn with_budget(mut self, budget: Option<RetryBudget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
     fn advance(&self) -> FixedRetryPolicy<L> {
         let next_duration: Duration = self.previous_duration + self.current_duration;
 
@@ -68,6 +177,7 @@ impl<L: RetryLogic> FixedRetryPolicy<L> {
             current_duration: cmp::min(next_duration, self.max_duration),
             max_duration: self.max_duration,
             logic: self.logic.clone(),
+            budget: self.budget.clone(),
         }
     }
 
@@ -75,13 +185,23 @@ impl<L: RetryLogic> FixedRetryPolicy<L> {
         self.current_duration
     }
 
-    fn build_retry(&self) -> RetryPolicyFuture<L> {
+    fn build_retry(&self) -> Option<RetryPolicyFuture<L>> {
+        if let Some(budget) = &self.budget {
+            if !budget.withdraw() {
+                warn!(
+                    message = "retry budget exhausted; dropping the request.",
+                    rate_limit_secs = 10,
+                );
+                return None;
+            }
+        }
+
         let policy = self.advance();
         let next = Instant::now() + policy.backoff();
         let delay = Delay::new(next);
 
         debug!(message = "retrying request.", delay_ms = %self.backoff().as_millis());
-        RetryPolicyFuture { delay, policy }
+        Some(RetryPolicyFuture { delay, policy })
     }
 }
 
@@ -95,15 +215,23 @@ where
     fn retry(&self, _: &Req, result: Result<&Res, &Error>) -> Option<Self::Future> {
         match result {
             Ok(response) => {
+                let action = self.logic.should_retry_response(response);
+                if action.is_successful() {
+                    if let Some(budget) = &self.budget {
+                        budget.deposit();
+                    }
+                    return None;
+                }
+
                 if self.remaining_attempts == 0 {
                     error!("retries exhausted");
                     return None;
                 }
 
-                match self.logic.should_retry_response(response) {
+                match action {
                     RetryAction::Retry(reason) => {
                         warn!(message = "retrying after response.", %reason);
-                        Some(self.build_retry())
+                        self.build_retry()
                     }
 
                     RetryAction::DontRetry(reason) => {
@@ -123,14 +251,14 @@ where
                 if let Some(expected) = error.downcast_ref::<L::Error>() {
                     if self.logic.is_retriable_error(expected) {
                         warn!("retrying after error: {}", expected);
-                        Some(self.build_retry())
+                        self.build_retry()
                     } else {
                         error!(message = "encountered non-retriable error.", %error);
                         None
                     }
                 } else if error.downcast_ref::<Elapsed>().is_some() {
                     warn!("request timedout.");
-                    Some(self.build_retry())
+                    self.build_retry()
                 } else {
                     warn!(message = "unexpected error type.", %error);
                     None
@@ -304,6 +432,100 @@ mod tests {
         assert_eq!(Duration::from_secs(10), policy.backoff());
     }
 
+    fn budget(max_retries: u32, retry_ratio: f64, min_retries_per_sec: f64) -> RetryBudget {
+        RetryBudget::new(RetryBudgetConfig {
+            max_retries,
+            retry_ratio,
+            min_retries_per_sec,
+        })
+    }
+
+    #[test]
+    fn retry_budget_caps_retries_under_sustained_failure() {
+        clock::mock(|_| {
+            trace_init();
+
+            let policy = FixedRetryPolicy::new(
+                5,
+                Duration::from_secs(1),
+                Duration::from_secs(10),
+                SvcRetryLogic,
+            )
+            .with_budget(Some(budget(3, 0.5, 0.0)));
+            let error: crate::Error = Box::new(Error(true));
+            let retries = || {
+                (0..10)
+                    .filter(|_| {
+                        Policy::<_, &'static str, _>::retry(&policy, &"hello", Err(&error))
+                            .is_some()
+                    })
+                    .count()
+            };
+
+            assert_eq!(retries(), 3);
+            assert_eq!(retries(), 0);
+
+            // Each success earns half a retry.
+            for _ in 0..4 {
+                assert!(policy.retry(&"hello", Ok(&"world")).is_none());
+            }
+            assert_eq!(retries(), 2);
+        });
+    }
+
+    #[test]
+    fn retry_budget_refills_over_time() {
+        clock::mock(|clock| {
+            trace_init();
+
+            let budget = budget(2, 0.0, 1.0);
+            assert!(budget.withdraw());
+            assert!(budget.withdraw());
+            assert!(!budget.withdraw());
+
+            clock.advance(Duration::from_millis(1500));
+            assert!(budget.withdraw());
+            assert!(!budget.withdraw());
+
+            // The budget never holds more than `max_retries`.
+            clock.advance(Duration::from_secs(60));
+            assert!(budget.withdraw());
+            assert!(budget.withdraw());
+            assert!(!budget.withdraw());
+        });
+    }
+
+    #[test]
+    fn retry_budget_fails_request_once_exhausted() {
+        clock::mock(|clock| {
+            trace_init();
+
+            let policy = FixedRetryPolicy::new(
+                5,
+                Duration::from_secs(1),
+                Duration::from_secs(10),
+                SvcRetryLogic,
+            )
+            .with_budget(Some(budget(1, 0.0, 0.0)));
+
+            let (service, mut handle) = mock::pair();
+            let mut svc = Retry::new(policy, service);
+
+            assert_ready!(svc.poll_ready());
+
+            let mut fut = svc.call("hello");
+            assert_request_eq!(handle, "hello").send_error(Error(true));
+            assert_not_ready!(fut.poll());
+
+            clock.advance(Duration::from_secs(2));
+            assert_not_ready!(fut.poll());
+
+            // Attempts are left, but the budget isn't.
+            assert_request_eq!(handle, "hello").send_error(Error(true));
+            assert_err!(fut.poll());
+        });
+    }
+
     #[derive(Debug, Clone)]
     struct SvcRetryLogic;
 
@@ -1,8 +1,11 @@
 #![cfg(feature = "rusoto_core")]
 
-use crate::{dns::Resolver, sinks::util};
+use crate::{
+    dns::Resolver,
+    sinks::util::{self, ProxyConfig},
+};
 use futures01::{
-    future::{self, Future, FutureResult},
+    future::{self, Future, Shared},
     Async, Poll, Stream,
 };
 use http::{
@@ -15,34 +18,102 @@ use rusoto_core::{
     signature::{SignedRequest, SignedRequestPayload},
     ByteStream, CredentialsError, Region,
 };
-use rusoto_credential::{
-    AutoRefreshingProvider, AutoRefreshingProviderFuture, AwsCredentials,
-    DefaultCredentialsProvider, DefaultCredentialsProviderFuture, ProvideAwsCredentials,
-    StaticProvider,
-};
+use rusoto_credential::{AwsCredentials, ChainProvider, ProvideAwsCredentials, StaticProvider};
 use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
-use snafu::{ResultExt, Snafu};
-use std::{io, time::Duration};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio01::timer::Timeout;
 use tower::Service;
 
 pub type Client = HttpClient<util::http::HttpClient<RusotoBody>>;
 
-pub fn client(resolver: Resolver) -> crate::Result<Client> {
-    let client = util::http::HttpClient::new(resolver, None)?;
+pub fn client(resolver: Resolver, proxy: &ProxyConfig) -> crate::Result<Client> {
+    client_with_connect_timeout(resolver, proxy, None)
+}
+
+pub fn client_with_connect_timeout(
+    resolver: Resolver,
+    proxy: &ProxyConfig,
+    connect_timeout: Option<Duration>,
+) -> crate::Result<Client> {
+    let client =
+        util::http::HttpClient::with_connect_timeout(resolver, None, proxy, connect_timeout)?;
     Ok(HttpClient { client })
 }
 
-#[derive(Debug, Snafu)]
-enum RusotoError {
-    #[snafu(display("Invalid AWS credentials: {}", source))]
-    InvalidAWSCredentials { source: CredentialsError },
+/// Error codes AWS answers with once temporary credentials have expired.
+const EXPIRED_CREDENTIALS_CODES: &[&str] = &[
+    "ExpiredToken",
+    "ExpiredTokenException",
+    "TokenRefreshRequired",
+];
+
+/// Whether AWS rejected a request because its credentials expired. The same
+/// request can succeed once retried after `AwsCredentialsProvider::refresh`.
+pub fn is_expired_credentials<E>(error: &rusoto_core::RusotoError<E>) -> bool {
+    has_error_code(error, EXPIRED_CREDENTIALS_CODES)
+}
+
+/// Error codes AWS answers with when the credentials lack a permission.
+const ACCESS_DENIED_CODES: &[&str] = &[
+    "AccessDenied",
+    "AccessDeniedException",
+    "UnauthorizedOperation",
+];
+
+/// Whether AWS rejected a request because its credentials aren't allowed to
+/// make it.
+pub fn is_access_denied<E>(error: &rusoto_core::RusotoError<E>) -> bool {
+    has_error_code(error, ACCESS_DENIED_CODES)
+}
+
+fn has_error_code<E>(error: &rusoto_core::RusotoError<E>, codes: &[&str]) -> bool {
+    match error {
+        rusoto_core::RusotoError::Unknown(response) if response.status.is_client_error() => {
+            error_code(&response.body[..]).map_or(false, |code| codes.contains(&code.as_str()))
+        }
+        _ => false,
+    }
+}
+
+/// The error code of an AWS error response, either the `__type` of a JSON
+/// body (without its `namespace#` prefix) or the `<Code>` of an XML one.
+fn error_code(body: &[u8]) -> Option<String> {
+    if let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) {
+        let code = value.get("__type")?.as_str()?;
+        return code.rsplit('#').next().map(Into::into);
+    }
+
+    let body = std::str::from_utf8(body).ok()?;
+    let start = body.find("<Code>")? + "<Code>".len();
+    let end = start + body[start..].find("</Code>")?;
+    Some(body[start..end].trim().into())
+}
+
+type CredentialsFuture = Box<dyn Future<Item = AwsCredentials, Error = CredentialsError> + Send>;
+
+/// AWS credentials from the default chain, an assumed role or static keys.
+///
+/// Credentials are cached until they are about to expire. Clones share the
+/// cache, so a `refresh` after AWS rejected expired credentials applies to
+/// every client built with them. Requests made while new credentials are
+/// being fetched wait for that one fetch instead of starting their own.
+#[derive(Clone)]
+pub struct AwsCredentialsProvider {
+    fetch: Arc<dyn Fn() -> CredentialsFuture + Send + Sync>,
+    state: Arc<Mutex<CredentialsState>>,
 }
 
-// A place-holder for the types of AWS credentials we support
-pub enum AwsCredentialsProvider {
-    Default(DefaultCredentialsProvider),
-    Role(AutoRefreshingProvider<StsAssumeRoleSessionCredentialsProvider>),
-    Static(StaticProvider),
+#[derive(Default)]
+struct CredentialsState {
+    cached: Option<AwsCredentials>,
+    /// The fetch in flight, tagged with its number so that only it clears
+    /// itself once done.
+    pending: Option<(u64, Shared<CredentialsFuture>)>,
+    fetches: u64,
 }
 
 impl AwsCredentialsProvider {
@@ -60,48 +131,73 @@ impl AwsCredentialsProvider {
                 None,
             );
 
-            let creds = AutoRefreshingProvider::new(provider).context(InvalidAWSCredentials)?;
-            Ok(Self::Role(creds))
+            Ok(Self::from_provider(provider))
         } else {
-            let creds = DefaultCredentialsProvider::new().context(InvalidAWSCredentials)?;
-            Ok(Self::Default(creds))
+            Ok(Self::from_provider(ChainProvider::new()))
         }
     }
 
     pub fn new_minimal<A: Into<String>, S: Into<String>>(access_key: A, secret_key: S) -> Self {
-        Self::Static(StaticProvider::new_minimal(
+        Self::from_provider(StaticProvider::new_minimal(
             access_key.into(),
             secret_key.into(),
         ))
     }
-}
 
-impl ProvideAwsCredentials for AwsCredentialsProvider {
-    type Future = AwsCredentialsProviderFuture;
-    fn credentials(&self) -> Self::Future {
-        match self {
-            Self::Default(p) => AwsCredentialsProviderFuture::Default(p.credentials()),
-            Self::Role(p) => AwsCredentialsProviderFuture::Role(p.credentials()),
-            Self::Static(p) => AwsCredentialsProviderFuture::Static(p.credentials()),
+    pub fn from_provider<P>(provider: P) -> Self
+    where
+        P: ProvideAwsCredentials + Send + Sync + 'static,
+        P::Future: Send + 'static,
+    {
+        Self {
+            fetch: Arc::new(move || -> CredentialsFuture { Box::new(provider.credentials()) }),
+            state: Arc::new(Mutex::new(CredentialsState::default())),
         }
     }
-}
 
-pub enum AwsCredentialsProviderFuture {
-    Default(DefaultCredentialsProviderFuture),
-    Role(AutoRefreshingProviderFuture<StsAssumeRoleSessionCredentialsProvider>),
-    Static(FutureResult<AwsCredentials, CredentialsError>),
+    /// Drops the cached credentials, so the next request fetches new ones.
+    /// A fetch already in flight is kept, it yields new credentials anyway.
+    pub fn refresh(&self) {
+        self.state.lock().unwrap().cached = None;
+    }
 }
 
-impl Future for AwsCredentialsProviderFuture {
-    type Item = AwsCredentials;
-    type Error = CredentialsError;
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        match self {
-            Self::Default(f) => f.poll(),
-            Self::Role(f) => f.poll(),
-            Self::Static(f) => f.poll(),
+impl ProvideAwsCredentials for AwsCredentialsProvider {
+    type Future = CredentialsFuture;
+
+    fn credentials(&self) -> Self::Future {
+        let mut state = self.state.lock().unwrap();
+        if let Some(credentials) = &state.cached {
+            if !credentials.credentials_are_expired() {
+                return Box::new(future::ok(credentials.clone()));
+            }
         }
+
+        let (id, pending) = match &state.pending {
+            Some(pending) => pending.clone(),
+            None => {
+                state.fetches += 1;
+                let pending = (state.fetches, (self.fetch)().shared());
+                state.pending = Some(pending.clone());
+                pending
+            }
+        };
+        drop(state);
+
+        let state = Arc::clone(&self.state);
+        Box::new(pending.then(move |result| {
+            let mut state = state.lock().unwrap();
+            if state.pending.as_ref().map(|(pending, _)| *pending) == Some(id) {
+                state.pending = None;
+                if let Ok(credentials) = &result {
+                    state.cached = Some((**credentials).clone());
+                }
+            }
+
+            result
+                .map(|credentials| (*credentials).clone())
+                .map_err(|error| CredentialsError::new(&error.message))
+        }))
     }
 }
 
@@ -137,8 +233,6 @@ where
 
     // Adaptation of https://docs.rs/rusoto_core/0.41.0/src/rusoto_core/request.rs.html#409-522
     fn dispatch(&self, request: SignedRequest, timeout: Option<Duration>) -> Self::Future {
-        assert!(timeout.is_none(), "timeout is not supported at this level");
-
         let method = match request.method().as_ref() {
             "POST" => Method::POST,
             "PUT" => Method::PUT,
@@ -220,7 +314,22 @@ where
             })
             .map_err(|e| HttpDispatchError::new(format!("DispatchError: {}", e)));
 
-        Box::new(fut)
+        match timeout {
+            // The timeout covers the request up to the response head, the
+            // body is streamed afterwards.
+            Some(timeout) => Box::new(Timeout::new(fut, timeout).map_err(move |error| {
+                if error.is_elapsed() {
+                    HttpDispatchError::new(format!("Request timed out after {:?}", timeout))
+                } else if error.is_timer() {
+                    HttpDispatchError::new(format!("TimerError: {}", error))
+                } else {
+                    error
+                        .into_inner()
+                        .expect("Error is neither elapsed nor timer")
+                }
+            })),
+            None => Box::new(fut),
+        }
     }
 }
 
@@ -279,3 +388,106 @@ impl Stream for BodyStream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http::StatusCode;
+    use rusoto_core::request::BufferedHttpResponse;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Hands out `key-1`, `key-2`, ... as access keys, one per fetch.
+    struct CountingProvider(Arc<AtomicUsize>);
+
+    impl ProvideAwsCredentials for CountingProvider {
+        type Future = future::FutureResult<AwsCredentials, CredentialsError>;
+
+        fn credentials(&self) -> Self::Future {
+            let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            future::ok(AwsCredentials::new(
+                format!("key-{}", n),
+                "secret",
+                None,
+                None,
+            ))
+        }
+    }
+
+    #[test]
+    fn credentials_cached_until_refreshed() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let provider = AwsCredentialsProvider::from_provider(CountingProvider(fetches.clone()));
+        let clone = provider.clone();
+
+        assert_eq!(
+            provider.credentials().wait().unwrap().aws_access_key_id(),
+            "key-1"
+        );
+        assert_eq!(
+            clone.credentials().wait().unwrap().aws_access_key_id(),
+            "key-1"
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        clone.refresh();
+        assert_eq!(
+            provider.credentials().wait().unwrap().aws_access_key_id(),
+            "key-2"
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn concurrent_requests_share_one_fetch() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let provider = AwsCredentialsProvider::from_provider(CountingProvider(fetches.clone()));
+        provider.credentials().wait().unwrap();
+
+        // Every request failing with the expired credentials refreshes them.
+        provider.refresh();
+        let first = provider.credentials();
+        provider.refresh();
+        let second = provider.clone().credentials();
+
+        assert_eq!(first.wait().unwrap().aws_access_key_id(), "key-2");
+        assert_eq!(second.wait().unwrap().aws_access_key_id(), "key-2");
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn expired_credentials_detected() {
+        let response = |status, body: &'static str| {
+            rusoto_core::RusotoError::<()>::Unknown(BufferedHttpResponse {
+                status,
+                body: Bytes::from_static(body.as_bytes()),
+                headers: Default::default(),
+            })
+        };
+
+        assert!(is_expired_credentials(&response(
+            StatusCode::BAD_REQUEST,
+            r#"{"__type":"ExpiredTokenException","message":"The security token included in the request is expired"}"#
+        )));
+        assert!(is_expired_credentials(&response(
+            StatusCode::FORBIDDEN,
+            "<Error><Code>ExpiredToken</Code></Error>"
+        )));
+        assert!(!is_expired_credentials(&response(
+            StatusCode::BAD_REQUEST,
+            r#"{"__type":"InvalidParameterException"}"#
+        )));
+        assert!(is_expired_credentials(&response(
+            StatusCode::BAD_REQUEST,
+            r#"{"__type":"com.amazonaws.logs#ExpiredTokenException"}"#
+        )));
+        assert!(!is_expired_credentials(&response(
+            StatusCode::BAD_REQUEST,
+            r#"{"__type":"InvalidParameterException","message":"ExpiredToken"}"#
+        )));
+        assert!(!is_expired_credentials(&response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "<Error><Code>ExpiredToken</Code></Error>"
+        )));
+    }
+}
@@ -1,11 +1,17 @@
 use super::{
-    retries::{FixedRetryPolicy, RetryLogic},
+    retries::{FixedRetryPolicy, RetryBudget, RetryBudgetConfig, RetryLogic, GLOBAL_RETRY_BUDGET},
     Batch, BatchSettings, BatchSink,
 };
-use crate::buffers::Acker;
+use crate::{
+    internal_events::{SinkRequestFinished, SinkRequestStarted},
+    topology::config::SinkContext,
+};
 use futures01::{Async, Future, Poll};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
 use std::time::{Duration, Instant};
 use std::{error, fmt};
 use tokio01::timer::Delay;
@@ -17,8 +23,11 @@ use tower::{
     Service, ServiceBuilder,
 };
 
-pub type TowerBatchedSink<S, B, L, Request> =
-    BatchSink<ConcurrencyLimit<RateLimit<Retry<FixedRetryPolicy<L>, Timeout<S>>>>, B, Request>;
+pub type TowerBatchedSink<S, B, L, Request> = BatchSink<
+    ConcurrencyLimit<RateLimit<Retry<FixedRetryPolicy<L>, Timeout<RequestMetrics<S>>>>>,
+    B,
+    Request,
+>;
 
 pub trait ServiceBuilderExt<L> {
     fn map<R1, R2, F>(self, f: F) -> ServiceBuilder<Stack<MapLayer<R1, R2>, L>>
@@ -30,6 +39,8 @@ pub trait ServiceBuilderExt<L> {
         settings: TowerRequestSettings,
         retry_logic: RL,
     ) -> ServiceBuilder<Stack<TowerRequestLayer<RL, Request>, L>>;
+
+    fn request_metrics(self, cx: &SinkContext) -> ServiceBuilder<Stack<RequestMetricsLayer, L>>;
 }
 
 impl<L> ServiceBuilderExt<L> for ServiceBuilder<L> {
@@ -51,6 +62,10 @@ impl<L> ServiceBuilderExt<L> for ServiceBuilder<L> {
             _pd: std::marker::PhantomData,
         })
     }
+
+    fn request_metrics(self, cx: &SinkContext) -> ServiceBuilder<Stack<RequestMetricsLayer, L>> {
+        self.layer(RequestMetricsLayer::new(cx.name()))
+    }
 }
 
 /// Tower Request based configuration
@@ -63,6 +78,7 @@ pub struct TowerRequestConfig {
     pub retry_attempts: Option<usize>,         // max_value()
     pub retry_max_duration_secs: Option<u64>,
     pub retry_initial_backoff_secs: Option<u64>, // 1
+    pub retry_budget: Option<RetryBudgetConfig>,
 }
 
 impl TowerRequestConfig {
@@ -93,6 +109,10 @@ impl TowerRequestConfig {
                     .or(defaults.retry_initial_backoff_secs)
                     .unwrap_or(1),
             ),
+            retry_budget: self
+                .retry_budget
+                .or(defaults.retry_budget)
+                .map(RetryBudget::new),
         }
     }
 }
@@ -106,6 +126,9 @@ pub struct TowerRequestSettings {
     pub retry_attempts: usize,
     pub retry_max_duration_secs: Duration,
     pub retry_initial_backoff_secs: Duration,
+    /// The sink's own retry budget, shared by all its requests. Without one,
+    /// the global budget applies, if any.
+    pub retry_budget: Option<RetryBudget>,
 }
 
 impl TowerRequestSettings {
@@ -116,6 +139,11 @@ impl TowerRequestSettings {
             self.retry_max_duration_secs,
             logic,
         )
+        .with_budget(
+            self.retry_budget
+                .clone()
+                .or_else(|| GLOBAL_RETRY_BUDGET.get().cloned()),
+        )
     }
 
     pub fn batch_sink<B, L, S, Request>(
@@ -124,7 +152,7 @@ impl TowerRequestSettings {
         service: S,
         batch: B,
         batch_settings: BatchSettings,
-        acker: Acker,
+        cx: &SinkContext,
     ) -> TowerBatchedSink<S, B, L, Request>
     // Would like to return `impl Sink + SinkExt<T>` here, but that
     // doesn't work with later calls to `batched_with_min` etc (via
@@ -147,9 +175,10 @@ impl TowerRequestSettings {
             .layer(TimeoutLayer {
                 timeout: self.timeout,
             })
+            .request_metrics(cx)
             .service(service);
 
-        BatchSink::new(service, batch, batch_settings, acker)
+        BatchSink::new(service, batch, batch_settings, cx.acker()).component(cx.name())
     }
 }
 
@@ -243,6 +272,119 @@ impl<S: Clone, R1, R2> Clone for Map<S, R1, R2> {
     }
 }
 
+// === metrics ===
+
+/// Reports how many requests a sink has in flight and how long each one
+/// takes, tagged with the sink's component id.
+///
+/// All services built from one layer share the in-flight count, so sinks
+/// that keep a service per partition still report a single gauge.
+#[derive(Debug, Clone)]
+pub struct RequestMetricsLayer {
+    component: Arc<str>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl RequestMetricsLayer {
+    pub fn new(component: &str) -> Self {
+        Self {
+            component: component.into(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestMetricsLayer {
+    type Service = RequestMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestMetrics {
+            inner,
+            component: self.component.clone(),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestMetrics<S> {
+    inner: S,
+    component: Arc<str>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl<S, Request> Service<Request> for RequestMetrics<S>
+where
+    S: Service<Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RequestMetricsFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        emit!(SinkRequestStarted {
+            component: &self.component,
+            in_flight,
+        });
+
+        RequestMetricsFuture {
+            inner: self.inner.call(request),
+            in_flight: Some(InFlightRequest {
+                component: self.component.clone(),
+                in_flight: self.in_flight.clone(),
+                start: Instant::now(),
+            }),
+        }
+    }
+}
+
+/// `RequestMetrics` response future
+#[derive(Debug)]
+pub struct RequestMetricsFuture<F> {
+    inner: F,
+    in_flight: Option<InFlightRequest>,
+}
+
+impl<F: Future> Future for RequestMetricsFuture<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let result = self.inner.poll();
+        if let Ok(Async::NotReady) = result {
+            return result;
+        }
+
+        self.in_flight.take();
+        result
+    }
+}
+
+/// Counts a request as finished when dropped, so requests that are abandoned,
+/// say by a timeout, are accounted for too.
+#[derive(Debug)]
+struct InFlightRequest {
+    component: Arc<str>,
+    in_flight: Arc<AtomicUsize>,
+    start: Instant,
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        let in_flight = self.in_flight.fetch_sub(1, Ordering::SeqCst) - 1;
+        emit!(SinkRequestFinished {
+            component: &self.component,
+            in_flight,
+            duration: self.start.elapsed(),
+        });
+    }
+}
+
 // === timeout ===
 
 /// Applies a timeout to requests.
@@ -356,6 +498,7 @@ impl error::Error for Elapsed {}
 mod tests {
     use super::*;
     use futures01::Future;
+    use metrics_runtime::Measurement;
     use std::sync::Arc;
     use tokio01_test::{assert_ready, task::MockTask};
     use tower::layer::Layer;
@@ -380,4 +523,50 @@ mod tests {
 
         res.wait().unwrap();
     }
+
+    #[test]
+    fn request_metrics() {
+        crate::metrics::init_test();
+        let controller = crate::metrics::CONTROLLER.get().unwrap();
+        let measurement = |name: &str| {
+            controller
+                .snapshot()
+                .into_measurements()
+                .into_iter()
+                .find(|(key, _)| {
+                    key.name() == name
+                        && key.labels().any(|label| {
+                            label.key() == "component_id" && label.value() == "request_metrics"
+                        })
+                })
+                .map(|(_, measurement)| measurement)
+        };
+        let in_flight = || match measurement("component_in_flight_requests") {
+            Some(Measurement::Gauge(in_flight)) => in_flight,
+            _ => panic!("No in-flight requests gauge"),
+        };
+        let durations = || match measurement("component_request_duration_seconds") {
+            Some(Measurement::Histogram(durations)) => durations.decompress(),
+            None => Vec::new(),
+            _ => panic!("Request durations are not a histogram"),
+        };
+
+        let mut task = MockTask::new();
+        let (mock, mut handle) = mock::pair();
+        let mut svc = RequestMetricsLayer::new("request_metrics").layer(mock);
+
+        task.enter(|| assert_ready!(svc.poll_ready()));
+        let res = svc.call("hello world");
+        assert_eq!(in_flight(), 1);
+        assert!(durations().is_empty());
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert_request_eq!(handle, "hello world").send_response("world bye");
+        res.wait().unwrap();
+
+        assert_eq!(in_flight(), 0);
+        let durations = durations();
+        assert_eq!(durations.len(), 1);
+        assert!(durations[0] >= Duration::from_millis(10).as_nanos() as u64);
+    }
 }
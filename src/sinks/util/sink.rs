@@ -34,21 +34,24 @@
 
 use super::batch::{Batch, BatchSettings};
 use super::buffer::partition::Partition;
-use crate::buffers::Acker;
+use crate::{buffers::Acker, internal_events::SinkBatchSent};
 use futures01::{
     future::Either,
     stream::FuturesUnordered,
-    sync::oneshot::{self, Receiver},
+    sync::{
+        mpsc,
+        oneshot::{self, Receiver},
+    },
     try_ready, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream,
 };
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fmt,
     hash::Hash,
     marker::PhantomData,
-    time::Instant,
 };
 use tokio01::{
+    clock,
     executor::{DefaultExecutor, Executor},
     timer::Delay,
 };
@@ -125,8 +128,8 @@ impl<T: Sink> Sink for StreamSink<T> {
 ///
 /// Provided a batching scheme, a service and batch settings
 /// this type will handle buffering events via the batching scheme
-/// and dispatching requests via the service based on either the size
-/// of the batch or a batch linger timeout.
+/// and dispatching requests via the service as soon as the batch reaches
+/// either its event or byte limit, or its linger timeout elapses.
 ///
 /// # Acking
 ///
@@ -139,8 +142,11 @@ pub struct BatchSink<S, B, Request, E = DefaultExecutor> {
     batch: B,
     settings: BatchSettings,
     linger: Option<Delay>,
+    full: bool,
     closing: bool,
     exec: E,
+    /// The sink's name, labelling the batch size metrics.
+    component: Option<String>,
     _pd: PhantomData<Request>,
 }
 
@@ -180,14 +186,25 @@ where
             batch,
             settings,
             linger: None,
+            full: false,
             closing: false,
             exec,
+            component: None,
             _pd: PhantomData,
         }
     }
 
+    /// Record the size of each batch sent under the sink's name.
+    pub fn component(mut self, name: &str) -> Self {
+        self.component = Some(name.to_owned());
+        self
+    }
+
     fn should_send(&mut self) -> bool {
-        self.closing || self.batch.len() >= self.settings.size || self.linger_elapsed()
+        self.closing
+            || self.full
+            || self.settings.size.is_reached(&self.batch)
+            || self.linger_elapsed()
     }
 
     fn linger_elapsed(&mut self) -> bool {
@@ -212,21 +229,25 @@ where
     type SinkError = crate::Error;
 
     fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
-        if self.batch.len() >= self.settings.size {
+        if self.settings.size.is_reached(&self.batch)
+            || self.settings.size.overflows(&self.batch, &item)
+        {
             trace!("batch full.");
+            self.full = true;
             self.poll_complete()?;
 
-            if self.batch.len() > self.settings.size {
-                debug!(message = "Batch full; applying back pressure.", size = %self.settings.size, rate_limit_secs = 10);
+            if !self.batch.is_empty() {
+                debug!(message = "Batch full; applying back pressure.", size = ?self.settings.size, rate_limit_secs = 10);
                 return Ok(AsyncSink::NotReady(item));
             }
         }
 
-        if self.batch.len() == 0 {
+        if self.batch.is_empty() {
             trace!("Creating new batch.");
             // We just inserted the first item of a new batch, so set our delay to the longest time
-            // we want to allow that item to linger in the batch before being flushed.
-            let deadline = Instant::now() + self.settings.timeout;
+            // we want to allow that item to linger in the batch before being flushed. The deadline
+            // is taken from the timer's clock, which is the one the delay is measured against.
+            let deadline = clock::now() + self.settings.timeout;
             self.linger = Some(Delay::new(deadline));
         }
 
@@ -251,7 +272,14 @@ where
                     let batch = self.batch.fresh_replace();
 
                     let batch_size = batch.num_items();
-                    let request = batch.finish();
+                    let (request, byte_size) = batch.finish_sized();
+                    if let Some(component) = &self.component {
+                        emit!(SinkBatchSent {
+                            component,
+                            events: batch_size,
+                            byte_size,
+                        });
+                    }
 
                     let fut = self.service.call(request, batch_size);
 
@@ -259,6 +287,7 @@ where
 
                     // Disable linger timeout
                     self.linger.take();
+                    self.full = false;
                 } else {
                     // We have a batch but we can't send any items
                     // most likely because we have not hit either
@@ -316,16 +345,35 @@ type LingerDelay<K> = Box<dyn Future<Item = LingerState<K>, Error = ()> + Send +
 /// batches have been acked. This means if sequential requests r1, r2,
 /// and r3 are dispatched and r2 and r3 complete, all events contained
 /// in all requests will not be acked until r1 has completed.
+///
+/// # Ordering
+///
+/// Requests are sent concurrently, so batches of the same partition may land
+/// out of order, a retried batch after later ones. With `preserve_order`, a
+/// partition's batches are sent one at a time, each once the previous one,
+/// retries included, has completed, while different partitions are still
+/// sent concurrently.
 pub struct PartitionBatchSink<B, S, K, Request, E = DefaultExecutor> {
     batch: B,
     service: ServiceSink<S, Request>,
     exec: E,
     partitions: HashMap<K, B>,
+    full: HashSet<K>,
     settings: BatchSettings,
     closing: bool,
-    sending: VecDeque<B>,
+    sending: VecDeque<(K, B)>,
     lingers: FuturesUnordered<LingerDelay<K>>,
     linger_handles: HashMap<K, oneshot::Sender<K>>,
+    preserve_order: bool,
+    /// Partitions with a request in flight or about to be, when preserving
+    /// order.
+    busy: HashSet<K>,
+    /// Batches waiting on an earlier batch of their partition.
+    waiting: HashMap<K, VecDeque<B>>,
+    completed_tx: mpsc::UnboundedSender<K>,
+    completed_rx: mpsc::UnboundedReceiver<K>,
+    /// The sink's name, labelling the batch size metrics.
+    component: Option<String>,
 }
 
 enum LingerState<K> {
@@ -373,25 +421,45 @@ where
         exec: E,
     ) -> Self {
         let service = ServiceSink::new(service, acker);
+        let (completed_tx, completed_rx) = mpsc::unbounded();
 
         Self {
             batch,
             service,
             exec,
             partitions: HashMap::new(),
+            full: HashSet::new(),
             settings,
             closing: false,
             sending: VecDeque::new(),
             lingers: FuturesUnordered::new(),
             linger_handles: HashMap::new(),
+            preserve_order: false,
+            busy: HashSet::new(),
+            waiting: HashMap::new(),
+            completed_tx,
+            completed_rx,
+            component: None,
         }
     }
 
+    /// Record the size of each batch sent under the sink's name.
+    pub fn component(mut self, name: &str) -> Self {
+        self.component = Some(name.to_owned());
+        self
+    }
+
+    /// Send the batches of each partition in order, one request at a time.
+    pub fn preserve_order(mut self, preserve_order: bool) -> Self {
+        self.preserve_order = preserve_order;
+        self
+    }
+
     fn set_linger(&mut self, partition: K) {
         let (tx, rx) = oneshot::channel();
         let partition_clone = partition.clone();
 
-        let deadline = Instant::now() + self.settings.timeout;
+        let deadline = clock::now() + self.settings.timeout;
         let delay = Delay::new(deadline)
             .map(move |_| LingerState::Elapsed(partition_clone))
             .map_err(|_| ());
@@ -410,20 +478,79 @@ where
         self.lingers.push(Box::new(fut));
     }
 
-    fn poll_send(&mut self, batch: B) -> Poll<(), crate::Error> {
+    fn poll_send(&mut self, partition: K, batch: B) -> Poll<(), crate::Error> {
+        if self.preserve_order && !self.busy.insert(partition.clone()) {
+            trace!("waiting on an earlier batch of the partition.");
+            self.waiting
+                .entry(partition)
+                .or_insert_with(VecDeque::new)
+                .push_back(batch);
+            return Ok(Async::NotReady);
+        }
+
+        self.dispatch(partition, batch)
+    }
+
+    fn dispatch(&mut self, partition: K, batch: B) -> Poll<(), crate::Error> {
         if let Async::NotReady = self.service.poll_ready()? {
-            self.sending.push_front(batch);
+            self.sending.push_back((partition, batch));
             Ok(Async::NotReady)
         } else {
             let batch_size = batch.num_items();
-            let batch = batch.finish();
-            let fut = self.service.call(batch, batch_size);
+            let (batch, byte_size) = batch.finish_sized();
+            if let Some(component) = &self.component {
+                emit!(SinkBatchSent {
+                    component,
+                    events: batch_size,
+                    byte_size,
+                });
+            }
+            let mut fut = self.service.call(batch, batch_size);
+
+            if self.preserve_order {
+                let completed = self.completed_tx.clone();
+                fut = Box::new(fut.then(move |_| {
+                    let _ = completed.unbounded_send(partition);
+                    Ok::<_, ()>(())
+                }));
+            }
 
             self.exec.spawn(fut).expect("Spawn service future");
 
             self.service.poll_complete()
         }
     }
+
+    /// Sends the next batch of each partition whose request has completed.
+    fn poll_completed(&mut self) -> Result<(), crate::Error> {
+        while let Ok(Async::Ready(Some(partition))) = self.completed_rx.poll() {
+            let next = self
+                .waiting
+                .get_mut(&partition)
+                .and_then(VecDeque::pop_front);
+            if self
+                .waiting
+                .get(&partition)
+                .map_or(false, VecDeque::is_empty)
+            {
+                self.waiting.remove(&partition);
+            }
+
+            match next {
+                Some(batch) => {
+                    self.dispatch(partition, batch)?;
+                }
+                None => {
+                    self.busy.remove(&partition);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn queued(&self) -> usize {
+        self.sending.len() + self.waiting.values().map(VecDeque::len).sum::<usize>()
+    }
 }
 
 impl<B, S, K, Request, E> Sink for PartitionBatchSink<B, S, K, Request, E>
@@ -444,14 +571,14 @@ where
         // Apply back pressure if we are buffering more than
         // 5 batches, this should only happen if the inner sink
         // is apply back pressure.
-        if self.sending.len() > 5 {
+        if self.queued() > 5 {
             trace!(
                 message = "too many sending batches.",
-                amount = self.sending.len()
+                amount = self.queued()
             );
             self.poll_complete()?;
 
-            if self.sending.len() > 5 {
+            if self.queued() > 5 {
                 debug!(
                     message = "Too many open batches; applying back pressure.",
                     max_batch_size = 5,
@@ -463,23 +590,20 @@ where
 
         let partition = item.partition();
 
+        let size = self.settings.size;
         if let Some(batch) = self.partitions.get_mut(&partition) {
-            if batch.len() >= self.settings.size {
+            if size.is_reached(batch) || size.overflows(batch, &item) {
                 trace!("Batch full; driving service to completion.");
+                self.full.insert(partition.clone());
                 self.poll_complete()?;
 
-                if let Some(batch) = self.partitions.get_mut(&partition) {
-                    if batch.len() >= self.settings.size {
-                        debug!(
-                            message = "Buffer full; applying back pressure.",
-                            max_size = %self.settings.size,
-                            rate_limit_secs = 10
-                        );
-                        return Ok(AsyncSink::NotReady(item));
-                    } else {
-                        batch.push(item);
-                        return Ok(AsyncSink::Ready);
-                    }
+                if self.partitions.contains_key(&partition) {
+                    debug!(
+                        message = "Buffer full; applying back pressure.",
+                        max_size = ?size,
+                        rate_limit_secs = 10
+                    );
+                    return Ok(AsyncSink::NotReady(item));
                 }
             } else {
                 trace!("adding event to batch.");
@@ -503,13 +627,23 @@ where
 
     fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
         self.service.poll_complete()?;
-
-        while let Some(batch) = self.sending.pop_front() {
-            self.poll_send(batch)?;
+        self.poll_completed()?;
+
+        // Stop at the first batch the service isn't ready for, it'll wake
+        // this task up once it is. Retrying in a loop would keep the task
+        // from ever getting to the lingers below.
+        while !self.sending.is_empty() {
+            if let Async::NotReady = self.service.poll_ready()? {
+                break;
+            }
+            if let Some((partition, batch)) = self.sending.pop_front() {
+                self.dispatch(partition, batch)?;
+            }
         }
 
         let closing = self.closing;
-        let max_size = self.settings.size;
+        let size = self.settings.size;
+        let full = std::mem::replace(&mut self.full, HashSet::new());
 
         let mut partitions = Vec::new();
 
@@ -520,7 +654,7 @@ where
                 self.linger_handles.remove(&partition);
 
                 if let Some(batch) = self.partitions.remove(&partition) {
-                    partitions.push(batch);
+                    partitions.push((partition, batch));
                 }
             }
         }
@@ -528,7 +662,7 @@ where
         let ready = self
             .partitions
             .iter()
-            .filter(|(_, b)| closing || b.len() >= max_size)
+            .filter(|(p, b)| closing || full.contains(*p) || size.is_reached(*b))
             .map(|(p, _)| p.clone())
             .collect::<Vec<_>>();
 
@@ -541,12 +675,20 @@ where
                     let _ = linger_cancel.send(partition.clone());
                 }
 
-                ready_batches.push(batch);
+                ready_batches.push((partition, batch));
             }
         }
 
-        for batch in ready_batches.into_iter().chain(partitions) {
-            self.poll_send(batch)?;
+        for (partition, batch) in ready_batches.into_iter().chain(partitions) {
+            self.poll_send(partition, batch)?;
+        }
+
+        // Batches waiting on the service or on their partition are sent once
+        // the service is ready or the request before them completes, either
+        // of which wakes up this task.
+        if !self.sending.is_empty() || !self.waiting.is_empty() {
+            self.service.poll_complete()?;
+            return Ok(Async::NotReady);
         }
 
         // If we still have an inflight partition then
@@ -581,6 +723,7 @@ where
             .field("batch", &self.batch)
             .field("service", &self.service)
             .field("settings", &self.settings)
+            .field("preserve_order", &self.preserve_order)
             .finish()
     }
 }
@@ -709,18 +852,24 @@ where
 mod tests {
     use super::*;
     use crate::buffers::Acker;
-    use crate::sinks::util::{buffer::partition::Partition, BatchSettings, Buffer};
+    use crate::sinks::util::{
+        batch::BatchSize, buffer::partition::Partition, BatchSettings, Buffer, VecBuffer,
+    };
     use crate::test_util::runtime;
     use bytes::Bytes;
     use futures01::{future, Sink};
+    use metrics_runtime::Measurement;
     use std::{
         sync::{atomic::Ordering::Relaxed, Arc, Mutex},
-        time::Duration,
+        time::{Duration, Instant},
     };
     use tokio01_test::clock::MockClock;
 
     const SETTINGS: BatchSettings = BatchSettings {
-        size: 10,
+        size: BatchSize {
+            bytes: 10,
+            events: 10,
+        },
         timeout: Duration::from_secs(10),
     };
 
@@ -778,7 +927,10 @@ mod tests {
         });
 
         let settings = BatchSettings {
-            size: 1,
+            size: BatchSize {
+                events: 1,
+                ..SETTINGS.size
+            },
             ..SETTINGS
         };
 
@@ -929,6 +1081,40 @@ mod tests {
         assert_eq!(&*output, &vec![vec![0, 1]]);
     }
 
+    #[test]
+    fn batch_sink_flushes_partial_batch_within_timeout_when_idle() {
+        let rt = runtime();
+        let mut clock = MockClock::new();
+
+        let (acker, _) = Acker::new_for_testing();
+        let sent_requests = Arc::new(Mutex::new(Vec::new()));
+
+        let svc = tower::service_fn(|req| {
+            let sent_requests = sent_requests.clone();
+
+            sent_requests.lock().unwrap().push(req);
+
+            future::ok::<_, std::io::Error>(())
+        });
+        let mut buffered =
+            BatchSink::with_executor(svc, Vec::new(), SETTINGS, acker, rt.executor());
+
+        clock.enter(|handle| {
+            assert!(buffered.start_send(0).unwrap().is_ready());
+
+            // Polling with nothing new coming in doesn't push the flush back.
+            for _ in 0..2 {
+                assert!(!buffered.poll_complete().unwrap().is_ready());
+                handle.advance(SETTINGS.timeout / 2);
+            }
+
+            buffered.poll_complete().unwrap();
+        });
+
+        let output = sent_requests.lock().unwrap();
+        assert_eq!(&*output, &vec![vec![0]]);
+    }
+
     #[test]
     fn batch_sink_allows_the_final_item_to_exceed_the_buffer_size() {
         let rt = runtime();
@@ -974,6 +1160,208 @@ mod tests {
         );
     }
 
+    #[test]
+    fn batch_sink_flushes_before_exceeding_max_bytes() {
+        let rt = runtime();
+        let mut clock = MockClock::new();
+
+        let (acker, ack_counter) = Acker::new_for_testing();
+        let sent_requests = Arc::new(Mutex::new(Vec::new()));
+
+        let svc = tower::service_fn(|req| {
+            let sent_requests = sent_requests.clone();
+
+            sent_requests.lock().unwrap().push(req);
+
+            future::ok::<_, std::io::Error>(())
+        });
+        let buffered = BatchSink::with_executor(
+            svc,
+            VecBuffer::new(|s: &&str| s.len()),
+            SETTINGS,
+            acker,
+            rt.executor(),
+        );
+
+        let input = vec!["abcd", "efgh", "ij", "klm", "nopqrstuvwxyz", "z"];
+        let _ = clock.enter(|_| {
+            buffered
+                .sink_map_err(drop)
+                .send_all(futures01::stream::iter_ok(input))
+                .wait()
+                .unwrap()
+        });
+
+        let output = sent_requests.lock().unwrap();
+        assert_eq!(
+            &*output,
+            &vec![
+                vec!["abcd", "efgh", "ij"],
+                vec!["klm"],
+                vec!["nopqrstuvwxyz"],
+                vec!["z"],
+            ]
+        );
+        assert_eq!(ack_counter.load(Relaxed), 6);
+    }
+
+    #[test]
+    fn partition_batch_sink_flushes_before_exceeding_max_bytes() {
+        let rt = runtime();
+        let (acker, _) = Acker::new_for_testing();
+        let sent_requests = Arc::new(Mutex::new(Vec::new()));
+
+        let svc = tower::service_fn(|req| {
+            let sent_requests = sent_requests.clone();
+
+            sent_requests.lock().unwrap().push(req);
+
+            future::ok::<_, std::io::Error>(())
+        });
+        let buffered = PartitionBatchSink::with_executor(
+            svc,
+            VecBuffer::new(|_: &Partitions| 4),
+            SETTINGS,
+            acker,
+            rt.executor(),
+        );
+
+        let input = vec![Partitions::A, Partitions::A, Partitions::A];
+
+        let (_buffered, _) = buffered
+            .sink_map_err(drop)
+            .send_all(futures01::stream::iter_ok(input))
+            .wait()
+            .unwrap();
+
+        let output = sent_requests.lock().unwrap();
+        assert_eq!(
+            &*output,
+            &vec![vec![Partitions::A, Partitions::A], vec![Partitions::A]]
+        );
+    }
+
+    fn sent_batch_sizes(name: &str, component: &str) -> Vec<u64> {
+        crate::metrics::CONTROLLER
+            .get()
+            .unwrap()
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .find(|(key, _)| {
+                key.name() == name
+                    && key
+                        .labels()
+                        .any(|label| label.key() == "component_id" && label.value() == component)
+            })
+            .map(|(_, measurement)| match measurement {
+                Measurement::Histogram(sizes) => sizes.decompress(),
+                _ => panic!("{} is not a histogram", name),
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn batch_sink_records_batch_sizes() {
+        crate::metrics::init_test();
+        let rt = runtime();
+        let mut clock = MockClock::new();
+
+        let (acker, _) = Acker::new_for_testing();
+        let sent_requests = Arc::new(Mutex::new(Vec::new()));
+
+        let svc = tower::service_fn(|req: Vec<u8>| {
+            let sent_requests = sent_requests.clone();
+
+            sent_requests.lock().unwrap().push(req);
+
+            future::ok::<_, std::io::Error>(())
+        });
+        let settings = BatchSettings {
+            size: BatchSize {
+                bytes: 1000,
+                events: 2,
+            },
+            ..SETTINGS
+        };
+        let buffered =
+            BatchSink::with_executor(svc, Buffer::new(true), settings, acker, rt.executor())
+                .component("batch_sink_records_batch_sizes");
+
+        let input = (0..5).map(|i| format!("event number {}\n", i).into_bytes());
+        let _ = clock.enter(|_| {
+            buffered
+                .sink_map_err(drop)
+                .send_all(futures01::stream::iter_ok(input))
+                .wait()
+                .unwrap()
+        });
+
+        // The bytes are those of the gzipped requests, not of the events.
+        let sent_bytes = sent_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.len() as u64)
+            .collect::<Vec<_>>();
+        assert_eq!(sent_bytes.len(), 3);
+        assert_ne!(sent_bytes[0], 2 * "event number 0\n".len() as u64);
+
+        let mut events = sent_batch_sizes(
+            "component_sent_batch_events",
+            "batch_sink_records_batch_sizes",
+        );
+        events.sort();
+        assert_eq!(events, vec![1, 2, 2]);
+
+        let mut bytes = sent_batch_sizes(
+            "component_sent_batch_bytes",
+            "batch_sink_records_batch_sizes",
+        );
+        bytes.sort();
+        let mut expected = sent_bytes;
+        expected.sort();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn partition_batch_sink_records_batch_sizes() {
+        crate::metrics::init_test();
+        let rt = runtime();
+        let (acker, _) = Acker::new_for_testing();
+
+        let svc = tower::service_fn(|_| future::ok::<_, std::io::Error>(()));
+        let buffered = PartitionBatchSink::with_executor(
+            svc,
+            VecBuffer::new(|_: &Partitions| 4),
+            SETTINGS,
+            acker,
+            rt.executor(),
+        )
+        .component("partition_batch_sink_records_batch_sizes");
+
+        let input = vec![Partitions::A, Partitions::A, Partitions::A, Partitions::B];
+        let (_buffered, _) = buffered
+            .sink_map_err(drop)
+            .send_all(futures01::stream::iter_ok(input))
+            .wait()
+            .unwrap();
+
+        let mut events = sent_batch_sizes(
+            "component_sent_batch_events",
+            "partition_batch_sink_records_batch_sizes",
+        );
+        events.sort();
+        assert_eq!(events, vec![1, 1, 2]);
+
+        let mut bytes = sent_batch_sizes(
+            "component_sent_batch_bytes",
+            "partition_batch_sink_records_batch_sizes",
+        );
+        bytes.sort();
+        assert_eq!(bytes, vec![4, 4, 8]);
+    }
+
     #[test]
     fn partition_batch_sink_buffers_messages_until_limit() {
         let rt = runtime();
@@ -1022,7 +1410,10 @@ mod tests {
         });
 
         let settings = BatchSettings {
-            size: 1,
+            size: BatchSize {
+                events: 1,
+                ..SETTINGS.size
+            },
             ..SETTINGS
         };
 
@@ -1057,7 +1448,10 @@ mod tests {
         });
 
         let settings = BatchSettings {
-            size: 2,
+            size: BatchSize {
+                events: 2,
+                ..SETTINGS.size
+            },
             ..SETTINGS
         };
 
@@ -1114,6 +1508,98 @@ mod tests {
         assert_eq!(&*output, &vec![vec![1]]);
     }
 
+    #[test]
+    fn partition_batch_sink_flushes_partial_batch_within_timeout_when_idle() {
+        let mut clock = MockClock::new();
+        let rt = runtime();
+        let (acker, _) = Acker::new_for_testing();
+        let sent_requests = Arc::new(Mutex::new(Vec::new()));
+
+        let svc = tower::service_fn(|req| {
+            let sent_requests = sent_requests.clone();
+
+            sent_requests.lock().unwrap().push(req);
+
+            future::ok::<_, std::io::Error>(())
+        });
+
+        let mut buffered =
+            PartitionBatchSink::with_executor(svc, Vec::new(), SETTINGS, acker, rt.executor());
+
+        clock.enter(|handle| {
+            buffered.start_send(1 as usize).unwrap();
+
+            // Polling with nothing new coming in doesn't push the flush back.
+            for _ in 0..2 {
+                assert!(!buffered.poll_complete().unwrap().is_ready());
+                handle.advance(SETTINGS.timeout / 2);
+            }
+
+            buffered.poll_complete().unwrap();
+        });
+
+        let output = sent_requests.lock().unwrap();
+        assert_eq!(&*output, &vec![vec![1]]);
+    }
+
+    /// The order the requests of three batches of one partition complete
+    /// in, when the first takes longer, as it would being retried.
+    fn partition_batch_sink_delivery_order(preserve_order: bool) -> Vec<usize> {
+        let mut exec = MockExec::default();
+        let mut clock = MockClock::new();
+        let (acker, ack_counter) = Acker::new_for_testing();
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+
+        let svc = tower::service_fn(|req: Vec<usize>| {
+            let dur = match req[0] {
+                0 => Duration::from_secs(5),
+                _ => Duration::from_secs(1),
+            };
+            let delivered = delivered.clone();
+
+            Delay::new(Instant::now() + dur).map(move |_| delivered.lock().unwrap().push(req[0]))
+        });
+
+        let settings = BatchSettings {
+            size: BatchSize {
+                events: 1,
+                ..SETTINGS.size
+            },
+            ..SETTINGS
+        };
+
+        let mut sink =
+            PartitionBatchSink::with_executor(svc, Vec::new(), settings, acker, exec.clone())
+                .preserve_order(preserve_order);
+
+        clock.enter(|handle| {
+            for i in 0..3 {
+                assert!(sink.start_send(i as usize).unwrap().is_ready());
+            }
+
+            for _ in 0..15 {
+                sink.poll_complete().unwrap();
+                exec.poll().unwrap();
+                handle.advance(Duration::from_secs(1));
+            }
+            sink.poll_complete().unwrap();
+        });
+
+        assert_eq!(ack_counter.load(Relaxed), 3);
+        let delivered = delivered.lock().unwrap();
+        delivered.clone()
+    }
+
+    #[test]
+    fn partition_batch_sink_preserves_order() {
+        assert_eq!(partition_batch_sink_delivery_order(true), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn partition_batch_sink_reorders_without_preserve_order() {
+        assert_eq!(partition_batch_sink_delivery_order(false), vec![1, 2, 0]);
+    }
+
     #[derive(Default, Clone)]
     struct MockExec(
         Arc<
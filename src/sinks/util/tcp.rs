@@ -1,9 +1,9 @@
 use crate::{
-    dns::Resolver,
+    dns::{interleave, HappyEyeballs, Resolver},
     emit,
     internal_events::{
-        TcpConnectionDisconnected, TcpConnectionEstablished, TcpConnectionFailed, TcpEventSent,
-        TcpFlushError,
+        TcpConnectionDisconnected, TcpConnectionEstablished, TcpConnectionFailed,
+        TcpEndpointEjected, TcpEndpointReadmitted, TcpEventSent, TcpFlushError,
     },
     sinks::util::{encode_event, encoding::EncodingConfig, Encoding, SinkBuildError, StreamSink},
     sinks::{Healthcheck, RouterSink},
@@ -29,32 +29,103 @@ use tokio_retry::strategy::ExponentialBackoff;
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct TcpSinkConfig {
+    #[serde(default)]
     pub address: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub load_balancing: LoadBalancing,
+    #[serde(default = "default_max_failures")]
+    pub max_failures: u32,
+    #[serde(default = "default_ejection_cooldown_secs")]
+    pub ejection_cooldown_secs: u64,
     pub encoding: EncodingConfig<Encoding>,
     pub tls: Option<TlsConfig>,
 }
 
+fn default_max_failures() -> u32 {
+    3
+}
+
+fn default_ejection_cooldown_secs() -> u64 {
+    30
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Derivative)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadBalancing {
+    #[derivative(Default)]
+    RoundRobin,
+    LeastConnections,
+}
+
+#[derive(Debug, Snafu)]
+enum TcpBuildError {
+    #[snafu(display("At least one of `address` or `addresses` must be set"))]
+    MissingAddress,
+}
+
 impl TcpSinkConfig {
     pub fn new(address: String, encoding: EncodingConfig<Encoding>) -> Self {
         Self {
             address,
+            addresses: Vec::new(),
+            load_balancing: LoadBalancing::default(),
+            max_failures: default_max_failures(),
+            ejection_cooldown_secs: default_ejection_cooldown_secs(),
             encoding,
             tls: None,
         }
     }
 
-    pub fn build(&self, cx: SinkContext) -> crate::Result<(RouterSink, Healthcheck)> {
-        let uri = self.address.parse::<http::Uri>()?;
+    fn endpoints(&self) -> crate::Result<Vec<(String, u16)>> {
+        let addresses = Some(&self.address)
+            .filter(|address| !address.is_empty())
+            .into_iter()
+            .chain(self.addresses.iter());
 
-        let host = uri.host().ok_or(SinkBuildError::MissingHost)?.to_string();
-        let port = uri.port_u16().ok_or(SinkBuildError::MissingPort)?;
+        let mut endpoints = Vec::new();
+        for address in addresses {
+            let uri = address.parse::<http::Uri>()?;
+            let host = uri.host().ok_or(SinkBuildError::MissingHost)?.to_string();
+            let port = uri.port_u16().ok_or(SinkBuildError::MissingPort)?;
+            endpoints.push((host, port));
+        }
+
+        if endpoints.is_empty() {
+            return Err(TcpBuildError::MissingAddress.into());
+        }
+        Ok(endpoints)
+    }
 
+    pub fn build(&self, cx: SinkContext) -> crate::Result<(RouterSink, Healthcheck)> {
+        let mut endpoints = self.endpoints()?;
         let tls = MaybeTlsSettings::from_config(&self.tls, false)?;
 
-        let sink = raw_tcp(host.clone(), port, cx.clone(), self.encoding.clone(), tls);
-        let healthcheck = tcp_healthcheck(host, port, cx.resolver());
+        if endpoints.len() == 1 {
+            let (host, port) = endpoints.remove(0);
+            let sink = raw_tcp(host.clone(), port, cx.clone(), self.encoding.clone(), tls);
+            let healthcheck = tcp_healthcheck(host, port, cx.resolver());
+            return Ok((sink, healthcheck));
+        }
+
+        let healthcheck = pool_healthcheck(endpoints.clone(), cx.resolver());
+        let sinks = endpoints
+            .into_iter()
+            .map(|(host, port)| TcpSink::new(host, port, cx.resolver(), tls.clone()))
+            .collect();
+        let pool = TcpPoolSink::new(
+            sinks,
+            self.load_balancing,
+            self.max_failures,
+            Duration::from_secs(self.ejection_cooldown_secs),
+        );
+        let sink = StreamSink::new(pool, cx.acker());
+        let encoding = self.encoding.clone();
+        let sink = sink.with_flat_map(move |event| iter_ok(encode_event(event, &encoding)));
 
-        Ok((sink, healthcheck))
+        Ok((Box::new(sink), healthcheck))
     }
 }
 
@@ -65,6 +136,7 @@ pub struct TcpSink {
     tls: MaybeTlsSettings,
     state: TcpSinkState,
     backoff: ExponentialBackoff,
+    failures: u32,
     span: tracing::Span,
 }
 
@@ -88,10 +160,24 @@ impl TcpSink {
             tls,
             state: TcpSinkState::Disconnected,
             backoff: Self::fresh_backoff(),
+            failures: 0,
             span,
         }
     }
 
+    /// Number of connection attempts or connections that failed since the
+    /// last successful connection.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Drops the connection and forgets about past failures.
+    pub fn reset(&mut self) {
+        self.state = TcpSinkState::Disconnected;
+        self.backoff = Self::fresh_backoff();
+        self.failures = 0;
+    }
+
     fn fresh_backoff() -> ExponentialBackoff {
         // TODO: make configurable
         ExponentialBackoff::from_millis(2)
@@ -100,9 +186,15 @@ impl TcpSink {
     }
 
     fn next_delay(&mut self) -> Delay {
+        self.failures += 1;
         Delay::new(Instant::now() + self.backoff.next().unwrap())
     }
 
+    fn disconnected(&mut self) {
+        self.failures += 1;
+        self.state = TcpSinkState::Disconnected;
+    }
+
     fn poll_connection(&mut self) -> Poll<&mut TcpOrTlsStream, ()> {
         loop {
             self.state = match self.state {
@@ -113,12 +205,12 @@ impl TcpSink {
                     TcpSinkState::ResolvingDns(fut)
                 }
                 TcpSinkState::ResolvingDns(ref mut dns) => match dns.poll() {
-                    Ok(Async::Ready(mut ips)) => {
-                        if let Some(ip) = ips.next() {
-                            let addr = SocketAddr::new(ip, self.port);
-
-                            debug!(message = "connecting", %addr);
-                            match self.tls.connect(self.host.clone(), addr) {
+                    Ok(Async::Ready(ips)) => {
+                        let port = self.port;
+                        let addrs = interleave(ips.map(|ip| SocketAddr::new(ip, port)));
+                        if !addrs.is_empty() {
+                            let delay = self.resolver.happy_eyeballs_delay();
+                            match self.tls.connect(self.host.clone(), addrs, delay) {
                                 Ok(connector) => TcpSinkState::Connecting(connector),
                                 Err(error) => {
                                     error!(message = "unable to connect", %error);
@@ -151,6 +243,7 @@ impl TcpSink {
                             peer_addr: stream.peer_addr().ok(),
                         });
                         self.backoff = Self::fresh_backoff();
+                        self.failures = 0;
                         TcpSinkState::Connected(FramedWrite::new(stream, BytesCodec::new()))
                     }
                     Ok(Async::NotReady) => return Ok(Async::NotReady),
@@ -180,7 +273,7 @@ impl Sink for TcpSink {
                 match connection.get_mut().read(&mut dummy) {
                     Err(error) if error.kind() != ErrorKind::WouldBlock => {
                         emit!(TcpConnectionDisconnected { error });
-                        self.state = TcpSinkState::Disconnected;
+                        self.disconnected();
                         Ok(AsyncSink::NotReady(line))
                     }
                     _ => {
@@ -190,7 +283,7 @@ impl Sink for TcpSink {
                         match connection.start_send(line) {
                             Err(error) => {
                                 error!(message = "connection disconnected.", %error);
-                                self.state = TcpSinkState::Disconnected;
+                                self.disconnected();
                                 Ok(AsyncSink::Ready)
                             }
                             Ok(ok) => Ok(ok),
@@ -218,7 +311,7 @@ impl Sink for TcpSink {
         match connection.poll_complete() {
             Err(error) => {
                 emit!(TcpFlushError { error });
-                self.state = TcpSinkState::Disconnected;
+                self.disconnected();
                 Ok(Async::Ready(()))
             }
             Ok(ok) => Ok(ok),
@@ -226,6 +319,203 @@ impl Sink for TcpSink {
     }
 }
 
+/// Health of a pooled endpoint, ejected from the pool for a cooldown
+/// period once it failed too many times in a row.
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    ejected_until: Option<Instant>,
+}
+
+impl EndpointHealth {
+    fn is_available(&self, now: Instant) -> bool {
+        self.ejected_until.map_or(true, |until| now >= until)
+    }
+
+    fn eject(&mut self, now: Instant, cooldown: Duration) {
+        self.ejected_until = Some(now + cooldown);
+    }
+
+    /// Re-admits the endpoint once its cooldown elapsed, returns whether it
+    /// was re-admitted.
+    fn readmit(&mut self, now: Instant) -> bool {
+        match self.ejected_until {
+            Some(until) if now >= until => {
+                self.ejected_until = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Endpoint {
+    sink: TcpSink,
+    health: EndpointHealth,
+    /// Events sent since the connection was last flushed.
+    pending: usize,
+}
+
+/// Spreads events over several `TcpSink`s. Endpoints that keep failing are
+/// ejected and their events rerouted to the remaining ones, when none is
+/// left events are held back until the first one is re-admitted.
+pub struct TcpPoolSink {
+    endpoints: Vec<Endpoint>,
+    load_balancing: LoadBalancing,
+    max_failures: u32,
+    cooldown: Duration,
+    next: usize,
+    readmission: Option<Delay>,
+}
+
+impl TcpPoolSink {
+    pub fn new(
+        sinks: Vec<TcpSink>,
+        load_balancing: LoadBalancing,
+        max_failures: u32,
+        cooldown: Duration,
+    ) -> Self {
+        let endpoints = sinks
+            .into_iter()
+            .map(|sink| Endpoint {
+                sink,
+                health: EndpointHealth::default(),
+                pending: 0,
+            })
+            .collect();
+        Self {
+            endpoints,
+            load_balancing,
+            max_failures: max_failures.max(1),
+            cooldown,
+            next: 0,
+            readmission: None,
+        }
+    }
+
+    fn readmit(&mut self, now: Instant) {
+        for endpoint in &mut self.endpoints {
+            if endpoint.health.readmit(now) {
+                emit!(TcpEndpointReadmitted {
+                    host: &endpoint.sink.host,
+                    port: endpoint.sink.port,
+                });
+                endpoint.sink.reset();
+            }
+        }
+    }
+
+    fn eject_failing(&mut self, index: usize, now: Instant) {
+        let endpoint = &mut self.endpoints[index];
+        if endpoint.sink.consecutive_failures() >= self.max_failures {
+            emit!(TcpEndpointEjected {
+                host: &endpoint.sink.host,
+                port: endpoint.sink.port,
+                cooldown: self.cooldown,
+            });
+            endpoint.health.eject(now, self.cooldown);
+            // Whatever was written to the failed connection is gone.
+            endpoint.pending = 0;
+        }
+    }
+
+    /// Makes sure the task is woken up when the first ejected endpoint can
+    /// be re-admitted, since no connection is left to do so.
+    fn schedule_readmission(&mut self) {
+        let earliest = self
+            .endpoints
+            .iter()
+            .filter_map(|endpoint| endpoint.health.ejected_until)
+            .min();
+        if let Some(earliest) = earliest {
+            let mut delay = Delay::new(earliest);
+            match delay.poll() {
+                Ok(Async::NotReady) => self.readmission = Some(delay),
+                _ => futures01::task::current().notify(),
+            }
+        }
+    }
+}
+
+/// The order in which endpoints are tried for the next event.
+fn candidates(
+    load_balancing: LoadBalancing,
+    next: usize,
+    pending: &[usize],
+    available: &[bool],
+) -> Vec<usize> {
+    let count = pending.len();
+    let mut order: Vec<usize> = (0..count)
+        .map(|offset| (next + offset) % count)
+        .filter(|&index| available[index])
+        .collect();
+    if load_balancing == LoadBalancing::LeastConnections {
+        // Stable, so ties are still broken round robin.
+        order.sort_by_key(|&index| pending[index]);
+    }
+    order
+}
+
+impl Sink for TcpPoolSink {
+    type SinkItem = Bytes;
+    type SinkError = ();
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        let now = Instant::now();
+        self.readmit(now);
+
+        let pending: Vec<usize> = self.endpoints.iter().map(|e| e.pending).collect();
+        let available: Vec<bool> = self
+            .endpoints
+            .iter()
+            .map(|e| e.health.is_available(now))
+            .collect();
+
+        let mut item = item;
+        for index in candidates(self.load_balancing, self.next, &pending, &available) {
+            match self.endpoints[index].sink.start_send(item)? {
+                AsyncSink::Ready => {
+                    self.endpoints[index].pending += 1;
+                    self.next = (index + 1) % self.endpoints.len();
+                    return Ok(AsyncSink::Ready);
+                }
+                // Not connected, try the next endpoint instead of waiting.
+                AsyncSink::NotReady(rejected) => {
+                    item = rejected;
+                    self.eject_failing(index, now);
+                }
+            }
+        }
+
+        self.schedule_readmission();
+        Ok(AsyncSink::NotReady(item))
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        let now = Instant::now();
+        let mut ready = true;
+
+        for index in 0..self.endpoints.len() {
+            let endpoint = &mut self.endpoints[index];
+            if !endpoint.health.is_available(now) || endpoint.pending == 0 {
+                continue;
+            }
+            match endpoint.sink.poll_complete()? {
+                Async::Ready(()) => endpoint.pending = 0,
+                Async::NotReady => {
+                    ready = false;
+                    self.eject_failing(index, now);
+                }
+            }
+        }
+
+        if ready {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
 pub fn raw_tcp(
     host: String,
     port: u16,
@@ -248,23 +538,157 @@ enum HealthcheckError {
     NoAddresses,
 }
 
+/// Healthy as long as one of the endpoints accepts connections.
+fn pool_healthcheck(endpoints: Vec<(String, u16)>, resolver: Resolver) -> Healthcheck {
+    let checks = endpoints
+        .into_iter()
+        .map(|(host, port)| tcp_healthcheck(host, port, resolver.clone()));
+    Box::new(future::select_ok(checks).map(|_| ()))
+}
+
 pub fn tcp_healthcheck(host: String, port: u16, resolver: Resolver) -> Healthcheck {
     // Lazy to avoid immediately connecting
     let check = future::lazy(move || {
+        let delay = resolver.happy_eyeballs_delay();
         resolver
             .lookup_ip(host)
             .map_err(|source| HealthcheckError::DnsError { source }.into())
-            .and_then(|mut ip| {
-                ip.next()
-                    .ok_or_else(|| HealthcheckError::NoAddresses.into())
-            })
-            .and_then(move |ip| {
-                let addr = SocketAddr::new(ip, port);
-                TcpStream::connect(&addr)
-                    .map(|_| ())
-                    .map_err(|source| HealthcheckError::ConnectError { source }.into())
+            .and_then(move |ips| {
+                let addrs = interleave(ips.map(|ip| SocketAddr::new(ip, port)));
+                if addrs.is_empty() {
+                    let error: crate::Error = HealthcheckError::NoAddresses.into();
+                    return future::Either::A(future::err(error));
+                }
+                future::Either::B(
+                    HappyEyeballs::new(addrs, delay, TcpStream::connect)
+                        .map(|_| ())
+                        .map_err(|source| HealthcheckError::ConnectError { source }.into()),
+                )
             })
     });
 
     Box::new(check)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{next_addr, random_lines_with_stream, receive, runtime};
+
+    #[test]
+    fn round_robin_candidates() {
+        let pending = [5, 0, 3];
+        assert_eq!(
+            candidates(LoadBalancing::RoundRobin, 1, &pending, &[true; 3]),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            candidates(LoadBalancing::RoundRobin, 1, &pending, &[true, false, true]),
+            vec![2, 0]
+        );
+    }
+
+    #[test]
+    fn least_connections_candidates() {
+        let pending = [5, 0, 3];
+        assert_eq!(
+            candidates(LoadBalancing::LeastConnections, 0, &pending, &[true; 3]),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            candidates(
+                LoadBalancing::LeastConnections,
+                0,
+                &pending,
+                &[true, false, true]
+            ),
+            vec![2, 0]
+        );
+        // Ties are broken in round robin order.
+        assert_eq!(
+            candidates(LoadBalancing::LeastConnections, 2, &[1, 1, 1], &[true; 3]),
+            vec![2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn endpoint_ejection_and_readmission() {
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(30);
+        let mut health = EndpointHealth::default();
+        assert!(health.is_available(now));
+
+        health.eject(now, cooldown);
+        assert!(!health.is_available(now));
+        assert!(!health.readmit(now + Duration::from_secs(10)));
+        assert!(!health.is_available(now + Duration::from_secs(10)));
+
+        assert!(health.readmit(now + cooldown));
+        assert!(health.is_available(now + cooldown));
+        assert!(!health.readmit(now + cooldown));
+    }
+
+    fn pool_config(addresses: Vec<String>, load_balancing: LoadBalancing) -> TcpSinkConfig {
+        TcpSinkConfig {
+            addresses,
+            load_balancing,
+            max_failures: 1,
+            ..TcpSinkConfig::new(String::new(), Encoding::Text.into())
+        }
+    }
+
+    #[test]
+    fn requires_an_address() {
+        let rt = runtime();
+        let config = pool_config(Vec::new(), LoadBalancing::RoundRobin);
+        assert!(config.build(SinkContext::new_test(rt.executor())).is_err());
+    }
+
+    #[test]
+    fn distributes_events_over_endpoints() {
+        let (addr1, addr2) = (next_addr(), next_addr());
+        let receiver1 = receive(&addr1);
+        let receiver2 = receive(&addr2);
+
+        let config = pool_config(
+            vec![addr1.to_string(), addr2.to_string()],
+            LoadBalancing::RoundRobin,
+        );
+        let mut rt = runtime();
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+
+        let (lines, events) = random_lines_with_stream(10, 100);
+        let _ = rt.block_on(sink.send_all(events)).unwrap();
+
+        let output1 = receiver1.wait();
+        let output2 = receiver2.wait();
+        assert!(!output1.is_empty());
+        assert!(!output2.is_empty());
+
+        let mut output = output1;
+        output.extend(output2);
+        output.sort();
+        let mut lines = lines;
+        lines.sort();
+        assert_eq!(output, lines);
+    }
+
+    #[test]
+    fn reroutes_events_from_failing_endpoints() {
+        // Nothing listens on the first address.
+        let (dead, alive) = (next_addr(), next_addr());
+        let receiver = receive(&alive);
+
+        let config = pool_config(
+            vec![dead.to_string(), alive.to_string()],
+            LoadBalancing::LeastConnections,
+        );
+        let mut rt = runtime();
+        let (sink, _) = config.build(SinkContext::new_test(rt.executor())).unwrap();
+
+        let (lines, events) = random_lines_with_stream(10, 100);
+        let _ = rt.block_on(sink.send_all(events)).unwrap();
+
+        assert_eq!(receiver.wait(), lines);
+    }
+}
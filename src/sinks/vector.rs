@@ -1,6 +1,6 @@
 use crate::{
-    event::proto,
-    internal_events::VectorEventSent,
+    event::frame::{self, Compression, PROTOCOL_VERSION},
+    internal_events::{VectorEventEncodeFailed, VectorEventSent},
     sinks::util::{tcp::TcpSink, StreamSink},
     tls::{MaybeTlsSettings, TlsConfig},
     topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
@@ -8,7 +8,6 @@ use crate::{
 };
 use bytes::{BufMut, Bytes, BytesMut};
 use futures01::{stream::iter_ok, Sink};
-use prost::Message;
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
 
@@ -17,11 +16,24 @@ use snafu::Snafu;
 pub struct VectorSinkConfig {
     pub address: String,
     pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u8,
+}
+
+fn default_protocol_version() -> u8 {
+    PROTOCOL_VERSION
 }
 
 impl VectorSinkConfig {
     pub fn new(address: String) -> Self {
-        Self { address, tls: None }
+        Self {
+            address,
+            tls: None,
+            compression: Compression::default(),
+            protocol_version: PROTOCOL_VERSION,
+        }
     }
 }
 
@@ -31,6 +43,14 @@ enum BuildError {
     MissingHost,
     #[snafu(display("Missing port in address field"))]
     MissingPort,
+    #[snafu(display(
+        "Unsupported protocol version {}, supported versions are 1 to {}",
+        version,
+        PROTOCOL_VERSION
+    ))]
+    UnsupportedProtocolVersion { version: u8 },
+    #[snafu(display("Compression requires protocol version 2 or higher"))]
+    CompressionUnsupported,
 }
 
 inventory::submit! {
@@ -45,11 +65,20 @@ impl SinkConfig for VectorSinkConfig {
         let host = uri.host().ok_or(BuildError::MissingHost)?.to_string();
         let port = uri.port_u16().ok_or(BuildError::MissingPort)?;
 
+        let version = self.protocol_version;
+        if version == 0 || version > PROTOCOL_VERSION {
+            return Err(BuildError::UnsupportedProtocolVersion { version }.into());
+        }
+        let compression = self.compression;
+        if version < 2 && compression != Compression::None {
+            return Err(BuildError::CompressionUnsupported.into());
+        }
+
         let tls = MaybeTlsSettings::from_config(&self.tls, false)?;
 
         let sink = TcpSink::new(host.clone(), port, cx.resolver(), tls);
         let sink = StreamSink::new(sink, cx.acker())
-            .with_flat_map(move |event| iter_ok(encode_event(event)));
+            .with_flat_map(move |event| iter_ok(encode_event(event, version, compression)));
         let healthcheck = super::util::tcp::tcp_healthcheck(host, port, cx.resolver());
 
         Ok((Box::new(sink), healthcheck))
@@ -70,17 +99,69 @@ enum HealthcheckError {
     ConnectError { source: std::io::Error },
 }
 
-fn encode_event(event: Event) -> Option<Bytes> {
-    let event = proto::EventWrapper::from(event);
-    let event_len = event.encoded_len();
-    let full_len = event_len + 4;
+fn encode_event(event: Event, version: u8, compression: Compression) -> Option<Bytes> {
+    let payload = match frame::encode(event, version, compression) {
+        Ok(payload) => payload,
+        Err(error) => {
+            emit!(VectorEventEncodeFailed { error });
+            return None;
+        }
+    };
+    let full_len = payload.len() + 4;
 
     emit!(VectorEventSent {
         byte_size: full_len
     });
 
     let mut out = BytesMut::with_capacity(full_len);
-    out.put_u32_be(event_len as u32);
-    event.encode(&mut out).unwrap();
+    out.put_u32_be(payload.len() as u32);
+    out.put_slice(&payload);
     Some(out.freeze())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::runtime;
+
+    fn build(config: &str) -> crate::Result<()> {
+        let config: VectorSinkConfig = toml::from_str(config).unwrap();
+        let rt = runtime();
+        config
+            .build(SinkContext::new_test(rt.executor()))
+            .map(|_| ())
+    }
+
+    #[test]
+    fn rejects_unsupported_protocol_version() {
+        assert!(build(
+            r#"
+            address = "127.0.0.1:9000"
+            protocol_version = 3
+            "#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_compression_with_version_1() {
+        assert!(build(
+            r#"
+            address = "127.0.0.1:9000"
+            protocol_version = 1
+            compression = "gzip"
+            "#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn frames_are_length_delimited() {
+        let event = Event::from("hello");
+        let frame = encode_event(event.clone(), PROTOCOL_VERSION, Compression::Gzip).unwrap();
+        let len = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+
+        assert_eq!(len, frame.len() - 4);
+        assert_eq!(frame::decode(&frame[4..]).unwrap(), event);
+    }
+}
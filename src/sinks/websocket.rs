@@ -0,0 +1,602 @@
+use crate::{
+    dns::{DnsError, Resolver},
+    event::{self, Event},
+    internal_events::{
+        WebSocketConnectionEstablished, WebSocketConnectionFailed, WebSocketEventSent,
+    },
+    runtime::FutureExt,
+    sinks::util::{
+        encoding::{EncodingConfig, EncodingConfiguration},
+        http::Auth,
+        StreamSink, UriSerde,
+    },
+    tls::{self, MaybeTlsSettings, TlsError, TlsOptions, TlsSettings},
+    topology::config::{DataType, SinkConfig, SinkContext, SinkDescription},
+};
+use async_trait::async_trait;
+use futures::{compat::Future01CompatExt, pin_mut, stream::Stream, SinkExt, StreamExt};
+use indexmap::IndexMap;
+use openssl::ssl::HandshakeError;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    time::{delay_for, interval_at, timeout, Instant},
+};
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_tungstenite::{
+    tungstenite::{self, Message},
+    WebSocketStream,
+};
+
+use super::streaming_sink::{self, StreamingSink};
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("URI scheme must be \"ws\" or \"wss\", found {:?}", scheme))]
+    InvalidScheme { scheme: String },
+    #[snafu(display("URI has no host: {}", uri))]
+    MissingHost { uri: String },
+    #[snafu(display("TLS options require a \"wss\" URI"))]
+    TlsWithoutWss,
+    #[snafu(display("ping_interval_secs must be greater than zero"))]
+    ZeroPingInterval,
+    #[snafu(display("Invalid upgrade request: {}", source))]
+    InvalidRequest { source: tungstenite::http::Error },
+}
+
+#[derive(Debug, Snafu)]
+enum ConnectError {
+    #[snafu(display("Unable to resolve {}: {}", host, source))]
+    Resolve { host: String, source: DnsError },
+    #[snafu(display("No addresses found for {}", host))]
+    NoAddresses { host: String },
+    #[snafu(display("Unable to connect: {}", source))]
+    Connect { source: std::io::Error },
+    #[snafu(display("{}", source))]
+    TlsSetup { source: TlsError },
+    #[snafu(display("TLS handshake failed: {}", source))]
+    TlsHandshake { source: HandshakeError<TcpStream> },
+    #[snafu(display("WebSocket handshake failed: {}", source))]
+    Handshake { source: tungstenite::Error },
+    #[snafu(display("Timed out connecting"))]
+    Timeout,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketSinkConfig {
+    pub uri: UriSerde,
+    pub encoding: EncodingConfig<Encoding>,
+    #[serde(default)]
+    pub message_type: MessageType,
+    pub auth: Option<Auth>,
+    pub headers: Option<IndexMap<String, String>>,
+    pub tls: Option<TlsOptions>,
+    #[serde(default = "default_ping_interval_secs")]
+    pub ping_interval_secs: u64,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_ping_interval_secs() -> u64 {
+    30
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Clone, Copy, Debug, Derivative, Deserialize, Serialize, Eq, PartialEq)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[derivative(Default)]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Derivative, Deserialize, Serialize, Eq, PartialEq)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    #[derivative(Default)]
+    Text,
+    Binary,
+}
+
+inventory::submit! {
+    SinkDescription::new_without_default::<WebSocketSinkConfig>("websocket")
+}
+
+#[typetag::serde(name = "websocket")]
+impl SinkConfig for WebSocketSinkConfig {
+    fn build(&self, mut cx: SinkContext) -> crate::Result<(super::RouterSink, super::Healthcheck)> {
+        if self.ping_interval_secs == 0 {
+            return Err(BuildError::ZeroPingInterval.into());
+        }
+
+        let sink = WebSocketSink {
+            config: self.clone(),
+            connector: Connector::new(self, cx.resolver())?,
+        };
+        let healthcheck = healthcheck(Connector::new(self, cx.resolver())?).boxed_compat();
+        let sink = streaming_sink::compat::adapt_to_topology(&mut cx, sink);
+        let sink = StreamSink::new(sink, cx.acker());
+
+        Ok((Box::new(sink), Box::new(healthcheck)))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "websocket"
+    }
+}
+
+async fn healthcheck(connector: Connector) -> crate::Result<()> {
+    let mut ws = connector.connect().await?;
+    let _ = ws.close(None).await;
+    Ok(())
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+type WsStream = WebSocketStream<Box<dyn Io>>;
+
+/// Opens connections, resolving the host and negotiating TLS for `wss`
+/// URIs before sending the upgrade request.
+struct Connector {
+    uri: String,
+    headers: tungstenite::http::HeaderMap,
+    host: String,
+    port: u16,
+    tls: MaybeTlsSettings,
+    resolver: Resolver,
+    timeout: Duration,
+}
+
+impl Connector {
+    fn new(config: &WebSocketSinkConfig, resolver: Resolver) -> crate::Result<Self> {
+        let uri = &config.uri;
+        let secure = match uri.scheme_str() {
+            Some("ws") => false,
+            Some("wss") => true,
+            scheme => {
+                return Err(BuildError::InvalidScheme {
+                    scheme: scheme.unwrap_or_default().to_owned(),
+                }
+                .into())
+            }
+        };
+        let host = uri
+            .host()
+            .ok_or_else(|| BuildError::MissingHost {
+                uri: uri.to_string(),
+            })?
+            .to_owned();
+        let tls = match (secure, &config.tls) {
+            (false, Some(_)) => return Err(BuildError::TlsWithoutWss.into()),
+            (false, None) => MaybeTlsSettings::Raw(()),
+            (true, options) => TlsSettings::from_options(options)?.into(),
+        };
+
+        Ok(Self {
+            uri: uri.to_string(),
+            headers: upgrade_headers(config)?,
+            port: uri.port_u16().unwrap_or(if secure { 443 } else { 80 }),
+            host,
+            tls,
+            resolver,
+            timeout: Duration::from_secs(config.timeout_secs),
+        })
+    }
+
+    async fn connect(&self) -> Result<WsStream, ConnectError> {
+        timeout(self.timeout, self.connect_inner())
+            .await
+            .map_err(|_| ConnectError::Timeout)?
+    }
+
+    async fn connect_inner(&self) -> Result<WsStream, ConnectError> {
+        let ip = self
+            .resolver
+            .lookup_ip(&self.host)
+            .compat()
+            .await
+            .with_context(|| Resolve { host: &self.host })?
+            .next()
+            .ok_or_else(|| ConnectError::NoAddresses {
+                host: self.host.clone(),
+            })?;
+        let stream = TcpStream::connect(SocketAddr::new(ip, self.port))
+            .await
+            .context(Connect)?;
+
+        let stream: Box<dyn Io> = match &self.tls {
+            MaybeTlsSettings::Raw(()) => Box::new(stream),
+            MaybeTlsSettings::Tls(_) => {
+                let config = tls::tls_connector(&self.tls).context(TlsSetup)?;
+                let stream = tokio_openssl02::connect(config, &self.host, stream)
+                    .await
+                    .context(TlsHandshake)?;
+                Box::new(stream)
+            }
+        };
+
+        let mut request = tungstenite::http::Request::get(&self.uri)
+            .body(())
+            .expect("request was validated");
+        *request.headers_mut() = self.headers.clone();
+        let (ws, _response) = tokio_tungstenite::client_async(request, stream)
+            .await
+            .context(Handshake)?;
+        emit!(WebSocketConnectionEstablished);
+        Ok(ws)
+    }
+}
+
+/// Builds the headers of the upgrade request, with the configured
+/// authentication.
+fn upgrade_headers(config: &WebSocketSinkConfig) -> crate::Result<tungstenite::http::HeaderMap> {
+    // `Auth` works with the `http` version of the rest of the sinks, so its
+    // headers are carried over to the one used by `tungstenite`.
+    let mut auth_request = http::Request::get(config.uri.to_string())
+        .body(())
+        .expect("URI was parsed");
+    if let Some(auth) = &config.auth {
+        auth.apply(&mut auth_request);
+    }
+
+    let mut builder = tungstenite::http::Request::get(config.uri.to_string());
+    for (name, value) in auth_request.headers() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    for (name, value) in config.headers.iter().flatten() {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let request = builder.body(()).context(InvalidRequest)?;
+
+    Ok(request.headers().clone())
+}
+
+struct WebSocketSink {
+    config: WebSocketSinkConfig,
+    connector: Connector,
+}
+
+/// Why a connection was given up on.
+enum Disconnect {
+    /// The input is done, and the connection was closed.
+    Shutdown,
+    /// The connection failed, along with the message that couldn't be sent
+    /// over it, if any.
+    Failed(Option<Message>),
+}
+
+impl WebSocketSink {
+    fn fresh_backoff() -> ExponentialBackoff {
+        ExponentialBackoff::from_millis(2)
+            .factor(250)
+            .max_delay(Duration::from_secs(60))
+    }
+
+    fn encode_event(&self, event: Event) -> Message {
+        let payload = encode_event(event, &self.config.encoding);
+        match self.config.message_type {
+            MessageType::Text => Message::Text(String::from_utf8_lossy(&payload).into_owned()),
+            MessageType::Binary => Message::Binary(payload),
+        }
+    }
+
+    /// Sends events over `ws` until the input ends or the connection fails.
+    ///
+    /// Messages are written and flushed one at a time, so a slow peer holds
+    /// back the input instead of messages piling up in memory. A send that
+    /// doesn't complete within `timeout_secs`, or a ping that isn't answered
+    /// before the next one, fails the connection.
+    async fn send_events(
+        &self,
+        ws: &mut WsStream,
+        input: &mut (impl Stream<Item = Event> + Unpin),
+        pending: Option<Message>,
+    ) -> Disconnect {
+        let send_timeout = Duration::from_secs(self.config.timeout_secs);
+
+        if let Some(message) = pending {
+            if let Err(error) = self.send(ws, message.clone(), send_timeout).await {
+                emit!(WebSocketConnectionFailed { error });
+                return Disconnect::Failed(Some(message));
+            }
+        }
+
+        let ping_interval = Duration::from_secs(self.config.ping_interval_secs);
+        let mut ping = interval_at(Instant::now() + ping_interval, ping_interval);
+        let mut awaiting_pong = false;
+
+        loop {
+            tokio::select! {
+                event = input.next() => {
+                    let message = match event {
+                        Some(event) => self.encode_event(event),
+                        None => {
+                            close(ws, send_timeout).await;
+                            return Disconnect::Shutdown;
+                        }
+                    };
+                    if let Err(error) = self.send(ws, message.clone(), send_timeout).await {
+                        emit!(WebSocketConnectionFailed { error });
+                        return Disconnect::Failed(Some(message));
+                    }
+                }
+                _ = ping.tick() => {
+                    if awaiting_pong {
+                        emit!(WebSocketConnectionFailed { error: "no pong received in time" });
+                        return Disconnect::Failed(None);
+                    }
+                    awaiting_pong = true;
+                    if let Err(error) = self.send(ws, Message::Ping(Vec::new()), send_timeout).await {
+                        emit!(WebSocketConnectionFailed { error });
+                        return Disconnect::Failed(None);
+                    }
+                }
+                // Pings from the peer are answered, and its close frames
+                // replied to, by `tungstenite` while reading.
+                message = ws.next() => match message {
+                    Some(Ok(Message::Pong(_))) => awaiting_pong = false,
+                    Some(Ok(Message::Close(frame))) => {
+                        emit!(WebSocketConnectionFailed {
+                            error: format!("closed by peer: {:?}", frame),
+                        });
+                        return Disconnect::Failed(None);
+                    }
+                    Some(Ok(_)) => (),
+                    Some(Err(error)) => {
+                        emit!(WebSocketConnectionFailed { error });
+                        return Disconnect::Failed(None);
+                    }
+                    None => {
+                        emit!(WebSocketConnectionFailed { error: "connection closed" });
+                        return Disconnect::Failed(None);
+                    }
+                },
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        ws: &mut WsStream,
+        message: Message,
+        send_timeout: Duration,
+    ) -> Result<(), String> {
+        let byte_size = message.len();
+        let is_ping = message.is_ping();
+        match timeout(send_timeout, ws.send(message)).await {
+            Ok(Ok(())) => {
+                if !is_ping {
+                    emit!(WebSocketEventSent { byte_size });
+                }
+                Ok(())
+            }
+            Ok(Err(error)) => Err(error.to_string()),
+            Err(_) => Err("timed out sending message".to_owned()),
+        }
+    }
+}
+
+/// Sends a close frame and waits for the peer to reply with its own.
+async fn close(ws: &mut WsStream, close_timeout: Duration) {
+    let handshake = async {
+        ws.close(None).await?;
+        while let Some(message) = ws.next().await {
+            message?;
+        }
+        Ok::<(), tungstenite::Error>(())
+    };
+    match timeout(close_timeout, handshake).await {
+        Ok(Ok(())) => debug!(message = "connection closed."),
+        Ok(Err(error)) => debug!(message = "error closing connection.", %error),
+        Err(_) => debug!(message = "timed out closing connection."),
+    }
+}
+
+#[async_trait]
+impl StreamingSink for WebSocketSink {
+    async fn run(
+        &mut self,
+        input: impl Stream<Item = Event> + Send + Sync + 'static,
+    ) -> crate::Result<()> {
+        pin_mut!(input);
+        let mut backoff = Self::fresh_backoff();
+        let mut pending = None;
+
+        loop {
+            let disconnect = match self.connector.connect().await {
+                Ok(mut ws) => {
+                    backoff = Self::fresh_backoff();
+                    self.send_events(&mut ws, &mut input, pending.take()).await
+                }
+                Err(error) => {
+                    emit!(WebSocketConnectionFailed { error });
+                    Disconnect::Failed(pending.take())
+                }
+            };
+
+            match disconnect {
+                Disconnect::Shutdown => return Ok(()),
+                Disconnect::Failed(message) => pending = message,
+            }
+            delay_for(backoff.next().unwrap()).await;
+        }
+    }
+}
+
+fn encode_event(mut event: Event, encoding: &EncodingConfig<Encoding>) -> Vec<u8> {
+    encoding.apply_rules(&mut event);
+    let log = event.into_log();
+    match encoding.codec {
+        Encoding::Json => serde_json::to_vec(&log).expect("Unable to encode event as JSON."),
+        Encoding::Text => log
+            .get(&event::log_schema().message_key())
+            .map(|v| v.as_bytes().to_vec())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{next_addr, runtime};
+    use futures::channel::{mpsc, oneshot};
+    use tokio::net::TcpListener;
+
+    fn sink(addr: SocketAddr, extra: &str) -> (WebSocketSink, crate::runtime::Runtime) {
+        let rt = runtime();
+        let config: WebSocketSinkConfig = toml::from_str(&format!(
+            r#"
+            uri = "ws://{}/events"
+            encoding = "text"
+            auth.strategy = "bearer"
+            auth.token = "secret"
+            {}
+            "#,
+            addr, extra
+        ))
+        .unwrap();
+        let cx = SinkContext::new_test(rt.executor());
+        let connector = Connector::new(&config, cx.resolver()).unwrap();
+        (WebSocketSink { config, connector }, rt)
+    }
+
+    /// Accepts a connection, checking the upgrade request, and reads `count`
+    /// messages from it, or all of them if there is no count.
+    async fn accept(
+        listener: &mut TcpListener,
+        count: Option<usize>,
+    ) -> (WebSocketStream<TcpStream>, Vec<Message>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let check = |request: &tungstenite::handshake::server::Request,
+                     response: tungstenite::handshake::server::Response| {
+            assert_eq!(request.uri().path(), "/events");
+            assert_eq!(request.headers()["Authorization"], "Bearer secret");
+            Ok(response)
+        };
+        let mut ws = tokio_tungstenite::accept_hdr_async(stream, check)
+            .await
+            .unwrap();
+
+        let mut messages = Vec::new();
+        while count.map_or(true, |count| messages.len() < count) {
+            match ws.next().await {
+                Some(Ok(message)) if message.is_text() || message.is_binary() => {
+                    messages.push(message)
+                }
+                None => break,
+                Some(Ok(_)) => (),
+                Some(Err(error)) => panic!("{}", error),
+            }
+        }
+        (ws, messages)
+    }
+
+    fn text(lines: &[&str]) -> Vec<Message> {
+        lines
+            .iter()
+            .map(|&line| Message::Text(line.into()))
+            .collect()
+    }
+
+    #[test]
+    fn websocket_sends_messages_and_closes() {
+        let addr = next_addr();
+        let (mut sink, mut rt) = sink(addr, r#"message_type = "binary""#);
+
+        let received = rt.block_on_std(async move {
+            let mut listener = TcpListener::bind(addr).await.unwrap();
+            let server = tokio::spawn(async move { accept(&mut listener, None).await.1 });
+
+            let events = vec![Event::from("one"), Event::from("two")];
+            sink.run(futures::stream::iter(events)).await.unwrap();
+            server.await.unwrap()
+        });
+
+        assert_eq!(
+            received,
+            vec![
+                Message::Binary(b"one".to_vec()),
+                Message::Binary(b"two".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn websocket_reconnects_and_resends() {
+        let addr = next_addr();
+        let (mut sink, mut rt) = sink(addr, "");
+
+        let (first, second) = rt.block_on_std(async move {
+            let mut listener = TcpListener::bind(addr).await.unwrap();
+            let (closed_tx, closed_rx) = oneshot::channel();
+            let server = tokio::spawn(async move {
+                let (mut ws, first) = accept(&mut listener, Some(1)).await;
+                ws.close(None).await.unwrap();
+                drop(ws);
+                closed_tx.send(()).unwrap();
+
+                let (_, second) = accept(&mut listener, None).await;
+                (first, second)
+            });
+
+            let (mut tx, rx) = mpsc::channel(10);
+            let run = tokio::spawn(async move { sink.run(rx).await.unwrap() });
+
+            tx.send(Event::from("before")).await.unwrap();
+            closed_rx.await.unwrap();
+            // Give the sink time to notice.
+            delay_for(Duration::from_millis(100)).await;
+            tx.send(Event::from("after")).await.unwrap();
+            tx.send(Event::from("again")).await.unwrap();
+            drop(tx);
+
+            run.await.unwrap();
+            server.await.unwrap()
+        });
+
+        assert_eq!(first, text(&["before"]));
+        assert_eq!(second, text(&["after", "again"]));
+    }
+
+    #[test]
+    fn websocket_rejects_invalid_configs() {
+        let rt = runtime();
+        let cx = SinkContext::new_test(rt.executor());
+        let connector = |config: &str| {
+            let config: WebSocketSinkConfig = toml::from_str(config).unwrap();
+            Connector::new(&config, cx.resolver()).map(|_| ())
+        };
+
+        assert!(connector(
+            r#"uri = "ws://localhost:9000"
+                              encoding = "json""#
+        )
+        .is_ok());
+        assert!(connector(
+            r#"uri = "http://localhost:9000"
+                              encoding = "json""#
+        )
+        .is_err());
+        assert!(connector(
+            r#"uri = "ws://localhost:9000"
+               encoding = "json"
+               tls.verify_certificate = false"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{event::metric::Metric, sources::util::MetricsBuilder};
+use chrono::{DateTime, Utc};
+use snafu::Snafu;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Snafu, PartialEq)]
+pub enum ParseError {
+    #[snafu(display("status page contains no known fields"))]
+    NoKnownFields,
+}
+
+/// Scoreboard characters as documented for `mod_status`, in display order.
+const SCOREBOARD: [(char, &str); 11] = [
+    ('_', "waiting"),
+    ('S', "starting"),
+    ('R', "reading"),
+    ('W', "sending"),
+    ('K', "keepalive"),
+    ('D', "dnslookup"),
+    ('C', "closing"),
+    ('L', "logging"),
+    ('G', "finishing"),
+    ('I', "idle_cleanup"),
+    ('.', "open"),
+];
+
+/// Parses the machine readable `mod_status` page (`/server-status?auto`).
+///
+/// The set of fields differs between Apache versions and MPMs, so unknown
+/// fields are ignored and missing fields simply produce no metric.
+pub fn parse(
+    packet: &str,
+    namespace: &str,
+    now: DateTime<Utc>,
+    tags: &BTreeMap<String, String>,
+) -> Result<Vec<Metric>, ParseError> {
+    let fields = packet
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => Some((key.trim(), value.trim())),
+                _ => None,
+            }
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let number = |key: &str| fields.get(key).and_then(|value| value.parse::<f64>().ok());
+
+    let mut builder = MetricsBuilder::at(Some(namespace), tags, now);
+
+    // Apache 2.2 only reports `Uptime`, 2.4 adds `ServerUptimeSeconds`
+    if let Some(value) = number("ServerUptimeSeconds").or_else(|| number("Uptime")) {
+        builder.counter("uptime_seconds_total", value, &[]);
+    }
+    if let Some(value) = number("Total Accesses") {
+        builder.counter("accesses_total", value, &[]);
+    }
+    if let Some(value) = number("Total kBytes") {
+        builder.counter("sent_bytes_total", value * 1024.0, &[]);
+    }
+    if let Some(value) = number("Total Duration") {
+        builder.counter("duration_seconds_total", value / 1000.0, &[]);
+    }
+    if let Some(value) = number("CPULoad") {
+        builder.gauge("cpu_load", value, &[]);
+    }
+    if let Some(value) = number("BusyWorkers") {
+        builder.gauge("workers", value, &[("state", "busy")]);
+    }
+    if let Some(value) = number("IdleWorkers") {
+        builder.gauge("workers", value, &[("state", "idle")]);
+    }
+    for (field, state) in &[
+        ("ConnsTotal", "total"),
+        ("ConnsAsyncWriting", "writing"),
+        ("ConnsAsyncKeepAlive", "keepalive"),
+        ("ConnsAsyncClosing", "closing"),
+    ] {
+        if let Some(value) = number(field) {
+            builder.gauge("connections", value, &[("state", *state)]);
+        }
+    }
+    if let Some(scoreboard) = fields.get("Scoreboard") {
+        for (key, state) in SCOREBOARD.iter() {
+            let count = scoreboard.chars().filter(|c| c == key).count();
+            builder.gauge("scoreboard", count as f64, &[("state", *state)]);
+        }
+    }
+
+    if builder.metrics.is_empty() {
+        return Err(ParseError::NoKnownFields);
+    }
+
+    Ok(builder.metrics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::metric::MetricValue;
+    use pretty_assertions::assert_eq;
+
+    fn server_tags() -> BTreeMap<String, String> {
+        vec![("server".to_owned(), "localhost:8080".to_owned())]
+            .into_iter()
+            .collect()
+    }
+
+    fn find<'a>(metrics: &'a [Metric], name: &str, state: Option<&str>) -> &'a Metric {
+        metrics
+            .iter()
+            .find(|m| {
+                m.name == name && m.tags.as_ref().unwrap().get("state").map(String::as_str) == state
+            })
+            .unwrap_or_else(|| panic!("missing {} {:?}", name, state))
+    }
+
+    #[test]
+    fn parses_apache_2_4_status() {
+        let status = r#"localhost
+ServerVersion: Apache/2.4.46 (Unix)
+ServerMPM: event
+Server Built: Aug  5 2020 23:20:17
+CurrentTime: Thursday, 03-Sep-2020 20:48:54 UTC
+ServerUptimeSeconds: 43
+ServerUptime: 43 seconds
+Load1: 0.01
+Total Accesses: 25
+Total kBytes: 3
+Total Duration: 1500
+CPUUser: .2
+CPULoad: .7
+Uptime: 43
+ReqPerSec: .581395
+BytesPerSec: 71.4419
+BytesPerReq: 122.88
+BusyWorkers: 1
+IdleWorkers: 74
+Processes: 3
+ConnsTotal: 1
+ConnsAsyncWriting: 0
+ConnsAsyncKeepAlive: 0
+ConnsAsyncClosing: 0
+Scoreboard: __W_K...
+"#;
+        let now = Utc::now();
+        let metrics = parse(status, "apache", now, &server_tags()).unwrap();
+
+        assert_eq!(
+            find(&metrics, "apache_uptime_seconds_total", None).value,
+            MetricValue::Counter { value: 43.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_accesses_total", None).value,
+            MetricValue::Counter { value: 25.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_sent_bytes_total", None).value,
+            MetricValue::Counter { value: 3072.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_duration_seconds_total", None).value,
+            MetricValue::Counter { value: 1.5 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_workers", Some("busy")).value,
+            MetricValue::Gauge { value: 1.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_workers", Some("idle")).value,
+            MetricValue::Gauge { value: 74.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_connections", Some("total")).value,
+            MetricValue::Gauge { value: 1.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_scoreboard", Some("waiting")).value,
+            MetricValue::Gauge { value: 3.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_scoreboard", Some("open")).value,
+            MetricValue::Gauge { value: 3.0 }
+        );
+        assert!(metrics
+            .iter()
+            .all(|m| m.timestamp == Some(now)
+                && m.tags.as_ref().unwrap()["server"] == "localhost:8080"));
+    }
+
+    #[test]
+    fn parses_apache_2_2_status() {
+        let status = r#"Total Accesses: 302
+Total kBytes: 1682
+Uptime: 1125
+ReqPerSec: .268444
+BytesPerSec: 1531.02
+BytesPerReq: 5703.31
+BusyWorkers: 2
+IdleWorkers: 8
+Scoreboard: _W_______W......
+"#;
+        let metrics = parse(status, "apache", Utc::now(), &server_tags()).unwrap();
+
+        assert_eq!(
+            find(&metrics, "apache_uptime_seconds_total", None).value,
+            MetricValue::Counter { value: 1125.0 }
+        );
+        assert_eq!(
+            find(&metrics, "apache_scoreboard", Some("sending")).value,
+            MetricValue::Gauge { value: 2.0 }
+        );
+        assert!(metrics
+            .iter()
+            .all(|m| m.name != "apache_connections" && m.name != "apache_cpu_load"));
+    }
+
+    #[test]
+    fn rejects_unrelated_page() {
+        assert_eq!(
+            parse(
+                "<html><body>It works!</body></html>",
+                "apache",
+                Utc::now(),
+                &server_tags()
+            ),
+            Err(ParseError::NoKnownFields)
+        );
+    }
+}
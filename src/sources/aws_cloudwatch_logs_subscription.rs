@@ -0,0 +1,395 @@
+use crate::{
+    event::{self, Event, Value},
+    internal_events::{
+        AwsCloudwatchLogsSubscriptionEventsReceived, AwsCloudwatchLogsSubscriptionRequestError,
+    },
+    shutdown::ShutdownSignal,
+    tls::{MaybeTlsSettings, TlsConfig},
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+};
+use bytes::Buf;
+use chrono::{TimeZone, Utc};
+use flate2::read::MultiGzDecoder;
+use futures01::{future, sync::mpsc, Future, Sink};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{io::Read, net::SocketAddr};
+use warp::{
+    filters::body::FullBody,
+    http::StatusCode,
+    reply::{Json, WithStatus},
+    Filter,
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AwsCloudwatchLogsSubscriptionConfig {
+    address: SocketAddr,
+    access_key: Option<String>,
+    tls: Option<TlsConfig>,
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<AwsCloudwatchLogsSubscriptionConfig>("aws_cloudwatch_logs_subscription")
+}
+
+#[typetag::serde(name = "aws_cloudwatch_logs_subscription")]
+impl SourceConfig for AwsCloudwatchLogsSubscriptionConfig {
+    fn build(
+        &self,
+        _: &str,
+        _: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        let access_key = self.access_key.clone();
+
+        let svc = warp::post2()
+            .and(warp::path::end())
+            .and(warp::header::optional::<String>(
+                "X-Amz-Firehose-Access-Key",
+            ))
+            .and(warp::body::concat())
+            .and_then(move |key: Option<String>, body: FullBody| {
+                let out = out.clone();
+                match handle_request(access_key.as_ref(), key, body) {
+                    Err(reply) => future::Either::A(future::ok(reply)),
+                    Ok((request_id, events)) => {
+                        emit!(AwsCloudwatchLogsSubscriptionEventsReceived {
+                            count: events.len(),
+                        });
+                        future::Either::B(
+                            out.send_all(futures01::stream::iter_ok(events))
+                                .map_err(|_| {
+                                    error!("Failed to forward events, downstream is closed");
+                                    warp::reject::custom("shutting down")
+                                })
+                                .map(move |_| reply(request_id, None, StatusCode::OK)),
+                        )
+                    }
+                }
+            });
+
+        info!(message = "building http server", addr = %self.address);
+
+        let tls = MaybeTlsSettings::from_config(&self.tls, true)?;
+        let incoming = tls.bind(&self.address)?.incoming();
+
+        let server =
+            warp::serve(svc).serve_incoming_with_graceful_shutdown(incoming, shutdown.map(|_| ()));
+
+        Ok(Box::new(server))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn source_type(&self) -> &'static str {
+        "aws_cloudwatch_logs_subscription"
+    }
+}
+
+/// Envelope of a Kinesis Firehose HTTP endpoint delivery.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FirehoseRequest {
+    request_id: String,
+    records: Vec<FirehoseRecord>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FirehoseRecord {
+    data: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FirehoseResponse {
+    request_id: String,
+    timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
+}
+
+/// Payload produced by a CloudWatch Logs subscription filter.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SubscriptionMessage {
+    message_type: MessageType,
+    owner: String,
+    log_group: String,
+    log_stream: String,
+    subscription_filters: Vec<String>,
+    log_events: Vec<SubscriptionLogEvent>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum MessageType {
+    DataMessage,
+    ControlMessage,
+}
+
+#[derive(Deserialize, Debug)]
+struct SubscriptionLogEvent {
+    id: String,
+    timestamp: i64,
+    message: String,
+}
+
+#[derive(Debug, Snafu)]
+enum RequestError {
+    #[snafu(display("Invalid access key"))]
+    AccessKey,
+    #[snafu(display("Could not parse request body: {}", source))]
+    Parse { source: serde_json::Error },
+    #[snafu(display("Could not decode record {}: {}", index, source))]
+    Record { index: usize, source: RecordError },
+}
+
+#[derive(Debug, Snafu)]
+enum RecordError {
+    #[snafu(display("invalid base64: {}", source))]
+    Base64 { source: base64::DecodeError },
+    #[snafu(display("invalid gzip: {}", source))]
+    Gzip { source: std::io::Error },
+    #[snafu(display("invalid subscription message: {}", source))]
+    Message { source: serde_json::Error },
+}
+
+fn handle_request(
+    access_key: Option<&String>,
+    key: Option<String>,
+    body: FullBody,
+) -> Result<(String, Vec<Event>), WithStatus<Json>> {
+    let request = serde_json::from_reader::<_, FirehoseRequest>(body.reader())
+        .context(Parse)
+        .map_err(|error| error_reply(String::new(), error))?;
+    let request_id = request.request_id;
+
+    if access_key.is_some() && access_key != key.as_ref() {
+        return Err(error_reply(request_id, RequestError::AccessKey));
+    }
+
+    let mut events = Vec::new();
+    for (index, record) in request.records.iter().enumerate() {
+        match decode_record(&record.data).context(Record { index }) {
+            Ok(decoded) => events.extend(decoded),
+            Err(error) => return Err(error_reply(request_id, error)),
+        }
+    }
+
+    Ok((request_id, events))
+}
+
+/// Decodes a single base64 encoded, gzipped subscription message into log
+/// events. Control messages, sent by CloudWatch to verify the destination, are
+/// skipped.
+fn decode_record(data: &str) -> Result<Vec<Event>, RecordError> {
+    let compressed = base64::decode(data).context(Base64)?;
+    let mut decompressed = Vec::new();
+    MultiGzDecoder::new(&compressed[..])
+        .read_to_end(&mut decompressed)
+        .context(Gzip)?;
+    let message = serde_json::from_slice::<SubscriptionMessage>(&decompressed).context(Message)?;
+
+    if message.message_type == MessageType::ControlMessage {
+        return Ok(Vec::new());
+    }
+
+    let SubscriptionMessage {
+        owner,
+        log_group,
+        log_stream,
+        subscription_filters,
+        log_events,
+        ..
+    } = message;
+    let subscription_filters = subscription_filters
+        .into_iter()
+        .map(Value::from)
+        .collect::<Vec<_>>();
+
+    Ok(log_events
+        .into_iter()
+        .map(|log_event| {
+            let mut event = Event::from(log_event.message);
+            let log = event.as_mut_log();
+            log.insert(
+                event::log_schema().timestamp_key().clone(),
+                Utc.timestamp_millis(log_event.timestamp),
+            );
+            log.insert("id", log_event.id);
+            log.insert("log_group", log_group.clone());
+            log.insert("log_stream", log_stream.clone());
+            log.insert("owner", owner.clone());
+            log.insert("subscription_filters", subscription_filters.clone());
+            event
+        })
+        .collect())
+}
+
+fn error_reply(request_id: String, error: RequestError) -> WithStatus<Json> {
+    emit!(AwsCloudwatchLogsSubscriptionRequestError { error: &error });
+    let status = match error {
+        RequestError::AccessKey => StatusCode::UNAUTHORIZED,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    reply(request_id, Some(error.to_string()), status)
+}
+
+fn reply(
+    request_id: String,
+    error_message: Option<String>,
+    status: StatusCode,
+) -> WithStatus<Json> {
+    let response = FirehoseResponse {
+        request_id,
+        timestamp: Utc::now().timestamp_millis(),
+        error_message,
+    };
+    warp::reply::with_status(warp::reply::json(&response), status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, collect_n};
+    use flate2::{write::GzEncoder, Compression};
+    use http::Method;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    const DATA_MESSAGE: &str = r#"{
+        "messageType": "DATA_MESSAGE",
+        "owner": "111111111111",
+        "logGroup": "test-group",
+        "logStream": "test-stream",
+        "subscriptionFilters": ["test-filter"],
+        "logEvents": [
+            {"id": "35683658089614582423604394983260738922885519999578275840", "timestamp": 1600110569039, "message": "first"},
+            {"id": "35683658089659183914001456229543810359430816722590236673", "timestamp": 1600110569041, "message": "second"}
+        ]
+    }"#;
+
+    const CONTROL_MESSAGE: &str = r#"{
+        "messageType": "CONTROL_MESSAGE",
+        "owner": "CloudwatchLogs",
+        "logGroup": "",
+        "logStream": "",
+        "subscriptionFilters": [],
+        "logEvents": [
+            {"id": "", "timestamp": 1600110003794, "message": "CWL CONTROL MESSAGE: Checking health of destination Firehose."}
+        ]
+    }"#;
+
+    fn encode(message: &str) -> String {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(message.as_bytes()).unwrap();
+        base64::encode(&encoder.finish().unwrap())
+    }
+
+    fn request(records: &[&str]) -> String {
+        let records = records
+            .iter()
+            .map(|record| serde_json::json!({ "data": encode(record) }))
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "requestId": "ed4acda5-034f-9f42-bba1-f29aea6d7d8f",
+            "timestamp": 1600110760008i64,
+            "records": records,
+        })
+        .to_string()
+    }
+
+    fn send(address: SocketAddr, body: String, key: Option<&str>) -> u16 {
+        let mut request = reqwest::Client::new()
+            .request(Method::POST, &format!("http://{}/", address))
+            .header("Content-Type", "application/json");
+        if let Some(key) = key {
+            request = request.header("X-Amz-Firehose-Access-Key", key);
+        }
+        request.body(body).send().unwrap().status().as_u16()
+    }
+
+    #[test]
+    fn aws_cloudwatch_logs_subscription_decodes_data_message() {
+        let events = decode_record(&encode(DATA_MESSAGE)).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let log = events[0].as_log();
+        assert_eq!(log[&event::log_schema().message_key()], "first".into());
+        assert_eq!(
+            log[&event::log_schema().timestamp_key()],
+            Utc.timestamp_millis(1600110569039).into()
+        );
+        assert_eq!(log[&"log_group".into()], "test-group".into());
+        assert_eq!(log[&"log_stream".into()], "test-stream".into());
+        assert_eq!(log[&"owner".into()], "111111111111".into());
+        assert_eq!(
+            log[&"subscription_filters".into()],
+            Value::from(vec![Value::from("test-filter")])
+        );
+        assert_eq!(
+            events[1].as_log()[&event::log_schema().message_key()],
+            "second".into()
+        );
+    }
+
+    #[test]
+    fn aws_cloudwatch_logs_subscription_skips_control_message() {
+        assert!(decode_record(&encode(CONTROL_MESSAGE)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn aws_cloudwatch_logs_subscription_rejects_malformed_record() {
+        assert!(decode_record("not base64!").is_err());
+        assert!(decode_record(&base64::encode("not gzip")).is_err());
+        assert!(decode_record(&encode("{}")).is_err());
+    }
+
+    #[test]
+    fn aws_cloudwatch_logs_subscription_receives_over_http() {
+        test_util::trace_init();
+        let mut rt = test_util::runtime();
+        let (sender, rx) = mpsc::channel(100);
+        let address = test_util::next_addr();
+        rt.spawn(
+            AwsCloudwatchLogsSubscriptionConfig {
+                address,
+                access_key: Some("secret".to_string()),
+                tls: None,
+            }
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                sender,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(401, send(address, request(&[DATA_MESSAGE]), None));
+        assert_eq!(401, send(address, request(&[DATA_MESSAGE]), Some("wrong")));
+        assert_eq!(400, send(address, "{".to_string(), Some("secret")));
+        assert_eq!(
+            200,
+            send(
+                address,
+                request(&[CONTROL_MESSAGE, DATA_MESSAGE]),
+                Some("secret")
+            )
+        );
+
+        let events = rt.block_on(collect_n(rx, 2)).unwrap();
+        assert_eq!(
+            events[0].as_log()[&event::log_schema().message_key()],
+            "first".into()
+        );
+        assert_eq!(
+            events[1].as_log()[&event::log_schema().message_key()],
+            "second".into()
+        );
+    }
+}
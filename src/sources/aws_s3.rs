@@ -0,0 +1,705 @@
+use crate::{
+    event::{self, Event},
+    internal_events::{
+        AwsS3ObjectProcessed, AwsS3ObjectProcessingFailed, AwsSqsMessageDeleteFailed,
+        AwsSqsMessageInvalid, AwsSqsReceiveFailed,
+    },
+    region::RegionOrEndpoint,
+    shutdown::ShutdownSignal,
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+};
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use futures::{
+    compat::Future01CompatExt,
+    future::{FutureExt, TryFutureExt},
+};
+use futures01::{sync::mpsc, Sink};
+use rusoto_core::Region;
+use rusoto_s3::{GetObjectRequest, S3Client, S3};
+use rusoto_sqs::{DeleteMessageRequest, Message, ReceiveMessageRequest, Sqs, SqsClient};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{
+    convert::TryInto,
+    io::{self, BufRead, BufReader, Read},
+    time::Duration,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AwsS3Config {
+    #[serde(flatten)]
+    region: RegionOrEndpoint,
+    #[serde(default)]
+    compression: Compression,
+    sqs: SqsConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct SqsConfig {
+    queue_url: String,
+    #[serde(default = "default_poll_secs")]
+    poll_secs: u32,
+    #[serde(default = "default_visibility_timeout_secs")]
+    visibility_timeout_secs: u32,
+    #[serde(default = "default_true")]
+    delete_message: bool,
+}
+
+fn default_poll_secs() -> u32 {
+    15
+}
+
+fn default_visibility_timeout_secs() -> u32 {
+    300
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Derivative)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Compression {
+    #[derivative(Default)]
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<AwsS3Config>("aws_s3")
+}
+
+#[typetag::serde(name = "aws_s3")]
+impl SourceConfig for AwsS3Config {
+    fn build(
+        &self,
+        _name: &str,
+        _globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        let region: Region = (&self.region).try_into()?;
+
+        let ingestor = Ingestor {
+            s3: S3Client::new(region.clone()),
+            sqs: SqsClient::new(region),
+            config: self.clone(),
+        };
+
+        let fut = ingestor.run(out, shutdown).boxed().compat();
+        Ok(Box::new(fut))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn source_type(&self) -> &'static str {
+        "aws_s3"
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum ProcessingError {
+    #[snafu(display("Could not fetch s3://{}/{}: {}", bucket, key, source))]
+    GetObject {
+        bucket: String,
+        key: String,
+        source: rusoto_core::RusotoError<rusoto_s3::GetObjectError>,
+    },
+    #[snafu(display("Object s3://{}/{} has no body", bucket, key))]
+    MissingBody { bucket: String, key: String },
+    #[snafu(display("Could not read s3://{}/{}: {}", bucket, key, source))]
+    ReadObject {
+        bucket: String,
+        key: String,
+        source: io::Error,
+    },
+    #[snafu(display("Pipeline closed while reading s3://{}/{}", bucket, key))]
+    PipelineClosed { bucket: String, key: String },
+}
+
+/// The body of an SQS message sent by S3 bucket notifications.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum SqsMessageBody {
+    /// Sent once when notifications are configured for a bucket.
+    Test(S3TestEvent),
+    Event(S3EventNotification),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct S3TestEvent {
+    service: String,
+    event: String,
+    bucket: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct S3EventNotification {
+    records: Vec<S3EventRecord>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct S3EventRecord {
+    event_name: String,
+    aws_region: String,
+    event_time: DateTime<Utc>,
+    s3: S3Message,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Message {
+    bucket: S3Bucket,
+    object: S3Object,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Bucket {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Object {
+    key: String,
+}
+
+impl S3Object {
+    /// Keys in notifications are form url-encoded, e.g. spaces become `+`.
+    fn decoded_key(&self) -> String {
+        let key = self.key.replace('+', " ");
+        url::percent_encoding::percent_decode(key.as_bytes())
+            .decode_utf8_lossy()
+            .into_owned()
+    }
+}
+
+struct Ingestor {
+    s3: S3Client,
+    sqs: SqsClient,
+    config: AwsS3Config,
+}
+
+impl Ingestor {
+    async fn run(self, out: mpsc::Sender<Event>, shutdown: ShutdownSignal) -> Result<(), ()> {
+        let mut shutdown = shutdown.compat();
+        let backoff = Duration::from_secs(self.config.sqs.poll_secs as u64);
+
+        loop {
+            let request = ReceiveMessageRequest {
+                queue_url: self.config.sqs.queue_url.clone(),
+                max_number_of_messages: Some(10),
+                visibility_timeout: Some(self.config.sqs.visibility_timeout_secs as i64),
+                wait_time_seconds: Some(self.config.sqs.poll_secs as i64),
+                ..Default::default()
+            };
+            let result = tokio::select! {
+                result = self.sqs.receive_message(request).compat() => result,
+                _ = &mut shutdown => break,
+            };
+            let messages = match result {
+                Ok(result) => result.messages.unwrap_or_default(),
+                Err(error) => {
+                    emit!(AwsSqsReceiveFailed { error });
+                    // Don't hammer SQS while it is unreachable or refusing us
+                    tokio::select! {
+                        _ = tokio::time::delay_for(backoff) => continue,
+                        _ = &mut shutdown => break,
+                    }
+                }
+            };
+
+            for message in messages {
+                self.handle_message(message, out.clone()).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ingests every object referenced by the message. The message is
+    /// deleted once all events have been handed to the pipeline, otherwise it
+    /// becomes visible again after the visibility timeout and is retried.
+    ///
+    /// Deletion doesn't wait for the sinks to deliver the events, so events
+    /// still buffered in the pipeline are lost if Vector stops before sending
+    /// them. Messages that aren't bucket notifications are deleted right
+    /// away, as no retry would ever make them readable.
+    async fn handle_message(&self, message: Message, out: mpsc::Sender<Event>) {
+        let body = message.body.as_ref().map(String::as_str).unwrap_or("");
+        let records = match serde_json::from_str::<SqsMessageBody>(body) {
+            Ok(SqsMessageBody::Test(test)) => {
+                debug!(
+                    message = "received s3 test event.",
+                    service = %test.service,
+                    event = %test.event,
+                    bucket = %test.bucket
+                );
+                Vec::new()
+            }
+            Ok(SqsMessageBody::Event(notification)) => notification.records,
+            Err(error) => {
+                emit!(AwsSqsMessageInvalid {
+                    message_id: message.message_id.as_ref().map(String::as_str),
+                    error
+                });
+                Vec::new()
+            }
+        };
+
+        for record in records {
+            if !record.event_name.starts_with("ObjectCreated") {
+                continue;
+            }
+            let bucket = record.s3.bucket.name.clone();
+            let key = record.s3.object.decoded_key();
+            match self.process_object(record, out.clone()).await {
+                Ok(count) => emit!(AwsS3ObjectProcessed {
+                    bucket: &bucket,
+                    key: &key,
+                    count
+                }),
+                Err(error) => {
+                    emit!(AwsS3ObjectProcessingFailed { error });
+                    return;
+                }
+            }
+        }
+
+        if self.config.sqs.delete_message {
+            self.delete_message(message).await;
+        }
+    }
+
+    async fn process_object(
+        &self,
+        record: S3EventRecord,
+        out: mpsc::Sender<Event>,
+    ) -> Result<usize, ProcessingError> {
+        let bucket = record.s3.bucket.name.clone();
+        let key = record.s3.object.decoded_key();
+
+        let object = self
+            .s3
+            .get_object(GetObjectRequest {
+                bucket: bucket.clone(),
+                key: key.clone(),
+                ..Default::default()
+            })
+            .compat()
+            .await
+            .with_context(|| GetObject {
+                bucket: bucket.clone(),
+                key: key.clone(),
+            })?;
+        let body = object.body.ok_or_else(|| ProcessingError::MissingBody {
+            bucket: bucket.clone(),
+            key: key.clone(),
+        })?;
+
+        let compression = self.config.compression;
+        let content_encoding = object.content_encoding;
+        let context = ObjectContext {
+            bucket,
+            key,
+            region: record.aws_region,
+            timestamp: record.event_time,
+        };
+
+        // Objects can be arbitrarily large, so they are streamed through
+        // decompression line by line on a blocking thread instead of being
+        // buffered in memory.
+        tokio::task::spawn_blocking(move || {
+            let reader = decompress(
+                body.into_blocking_read(),
+                compression,
+                content_encoding.as_ref().map(String::as_str),
+                &context.key,
+            )
+            .with_context(|| ReadObject {
+                bucket: context.bucket.clone(),
+                key: context.key.clone(),
+            })?;
+            read_events(reader, &context, out)
+        })
+        .await
+        .expect("object reader panicked")
+    }
+
+    async fn delete_message(&self, message: Message) {
+        let receipt_handle = match message.receipt_handle {
+            Some(receipt_handle) => receipt_handle,
+            None => return,
+        };
+        let request = DeleteMessageRequest {
+            queue_url: self.config.sqs.queue_url.clone(),
+            receipt_handle,
+        };
+        if let Err(error) = self.sqs.delete_message(request).compat().await {
+            emit!(AwsSqsMessageDeleteFailed { error });
+        }
+    }
+}
+
+struct ObjectContext {
+    bucket: String,
+    key: String,
+    region: String,
+    timestamp: DateTime<Utc>,
+}
+
+fn read_events(
+    reader: Box<dyn BufRead + Send>,
+    context: &ObjectContext,
+    out: mpsc::Sender<Event>,
+) -> Result<usize, ProcessingError> {
+    let mut out = out.wait();
+    let mut count = 0;
+
+    for line in reader.split(b'\n') {
+        let mut line = line.with_context(|| ReadObject {
+            bucket: context.bucket.clone(),
+            key: context.key.clone(),
+        })?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        let mut event = Event::from(String::from_utf8_lossy(&line).as_ref());
+        let log = event.as_mut_log();
+        log.insert(event::log_schema().timestamp_key(), context.timestamp);
+        log.insert("bucket", context.bucket.clone());
+        log.insert("object", context.key.clone());
+        log.insert("region", context.region.clone());
+
+        out.send(event)
+            .map_err(|_| ProcessingError::PipelineClosed {
+                bucket: context.bucket.clone(),
+                key: context.key.clone(),
+            })?;
+        count += 1;
+    }
+
+    out.flush().map_err(|_| ProcessingError::PipelineClosed {
+        bucket: context.bucket.clone(),
+        key: context.key.clone(),
+    })?;
+    Ok(count)
+}
+
+fn decompress(
+    reader: impl Read + Send + 'static,
+    compression: Compression,
+    content_encoding: Option<&str>,
+    key: &str,
+) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader = BufReader::new(reader);
+    let compression = match compression {
+        Compression::Auto => detect_compression(content_encoding, key, reader.fill_buf()?),
+        compression => compression,
+    };
+
+    Ok(match compression {
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(
+            reader,
+        )?)),
+        Compression::None | Compression::Auto => Box::new(reader),
+    })
+}
+
+/// Picks the compression from the content encoding, then the key extension,
+/// and finally the magic bytes at the start of the object.
+fn detect_compression(content_encoding: Option<&str>, key: &str, prefix: &[u8]) -> Compression {
+    match content_encoding {
+        Some("gzip") => return Compression::Gzip,
+        Some("zstd") => return Compression::Zstd,
+        _ => {}
+    }
+
+    if key.ends_with(".gz") {
+        Compression::Gzip
+    } else if key.ends_with(".zst") {
+        Compression::Zstd
+    } else if prefix.starts_with(GZIP_MAGIC) {
+        Compression::Gzip
+    } else if prefix.starts_with(ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression as GzCompression};
+    use futures01::Stream;
+    use std::io::Write;
+
+    #[test]
+    fn detects_compression() {
+        assert_eq!(
+            detect_compression(Some("gzip"), "logs.txt", b""),
+            Compression::Gzip
+        );
+        assert_eq!(detect_compression(None, "logs.zst", b""), Compression::Zstd);
+        assert_eq!(
+            detect_compression(None, "logs", &[0x1f, 0x8b, 0x08]),
+            Compression::Gzip
+        );
+        assert_eq!(
+            detect_compression(None, "logs", &[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Compression::Zstd
+        );
+        assert_eq!(
+            detect_compression(None, "logs.log", b"plain text"),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn decompresses_gzip_by_content() {
+        let mut encoder = GzEncoder::new(Vec::new(), GzCompression::fast());
+        encoder.write_all(b"one\ntwo\n").unwrap();
+        let data = encoder.finish().unwrap();
+
+        let reader = decompress(io::Cursor::new(data), Compression::Auto, None, "object").unwrap();
+        let lines = reader.lines().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn decompresses_zstd() {
+        let data = zstd::stream::encode_all(io::Cursor::new(b"one\ntwo".to_vec()), 0).unwrap();
+
+        let reader = decompress(io::Cursor::new(data), Compression::Auto, None, "a.zst").unwrap();
+        let lines = reader.lines().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn parses_test_event() {
+        let body = r#"{"Service":"Amazon S3","Event":"s3:TestEvent","Time":"2020-06-01T12:00:00.000Z","Bucket":"logs","RequestId":"5582815E1AEA5ADF","HostId":"8cLeGAmw098X5cv4Zkwcmo8vvZa3eH3eKxsPzbB9wrR+YstdA6Knx4Ip8EXAMPLE"}"#;
+        match serde_json::from_str::<SqsMessageBody>(body).unwrap() {
+            SqsMessageBody::Test(test) => assert_eq!(test.bucket, "logs"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_notification_with_multiple_records() {
+        let body = r#"{"Records":[
+            {"eventVersion":"2.1","eventSource":"aws:s3","awsRegion":"us-east-1","eventTime":"2020-06-01T12:00:00.000Z","eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"logs"},"object":{"key":"app/my+log%3D1.gz","size":10}}},
+            {"eventVersion":"2.1","eventSource":"aws:s3","awsRegion":"us-east-1","eventTime":"2020-06-01T12:00:01.000Z","eventName":"ObjectRemoved:Delete","s3":{"bucket":{"name":"logs"},"object":{"key":"old.log"}}}
+        ]}"#;
+        let records = match serde_json::from_str::<SqsMessageBody>(body).unwrap() {
+            SqsMessageBody::Event(notification) => notification.records,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].event_name, "ObjectCreated:Put");
+        assert_eq!(records[0].s3.object.decoded_key(), "app/my log=1.gz");
+        assert_eq!(records[1].event_name, "ObjectRemoved:Delete");
+    }
+
+    #[test]
+    fn reads_lines_into_events() {
+        let context = ObjectContext {
+            bucket: "logs".into(),
+            key: "app.log".into(),
+            region: "us-east-1".into(),
+            timestamp: Utc::now(),
+        };
+        let (tx, rx) = mpsc::channel(10);
+        let reader: Box<dyn BufRead + Send> = Box::new(io::Cursor::new(b"one\r\ntwo\n".to_vec()));
+
+        assert_eq!(read_events(reader, &context, tx).unwrap(), 2);
+
+        let events = rx.wait().collect::<Result<Vec<_>, _>>().unwrap();
+        let log = events[0].as_log();
+        assert_eq!(log[&event::log_schema().message_key()], "one".into());
+        assert_eq!(log[&"bucket".into()], "logs".into());
+        assert_eq!(log[&"object".into()], "app.log".into());
+        assert_eq!(
+            events[1].as_log()[&event::log_schema().message_key()],
+            "two".into()
+        );
+    }
+}
+
+#[cfg(feature = "aws-s3-source-integration-tests")]
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_util::{collect_n, random_lines, random_string, runtime};
+    use rusoto_s3::{
+        CreateBucketRequest, NotificationConfiguration, PutBucketNotificationConfigurationRequest,
+        PutObjectRequest, QueueConfiguration,
+    };
+    use rusoto_sqs::{CreateQueueRequest, GetQueueAttributesRequest, SendMessageRequest};
+
+    const ENDPOINT: &str = "http://localhost:4566";
+
+    fn region() -> Region {
+        Region::Custom {
+            name: "us-east-1".into(),
+            endpoint: ENDPOINT.into(),
+        }
+    }
+
+    fn config(queue_url: &str) -> AwsS3Config {
+        AwsS3Config {
+            region: RegionOrEndpoint::with_endpoint(ENDPOINT.into()),
+            compression: Compression::Auto,
+            sqs: SqsConfig {
+                queue_url: queue_url.into(),
+                poll_secs: 1,
+                visibility_timeout_secs: 10,
+                delete_message: true,
+            },
+        }
+    }
+
+    fn queued_messages(sqs: &SqsClient, queue_url: &str) -> (String, String) {
+        let attributes = sqs
+            .get_queue_attributes(GetQueueAttributesRequest {
+                queue_url: queue_url.into(),
+                attribute_names: Some(vec![
+                    "ApproximateNumberOfMessages".into(),
+                    "ApproximateNumberOfMessagesNotVisible".into(),
+                ]),
+            })
+            .sync()
+            .unwrap()
+            .attributes
+            .unwrap();
+        (
+            attributes["ApproximateNumberOfMessages"].clone(),
+            attributes["ApproximateNumberOfMessagesNotVisible"].clone(),
+        )
+    }
+
+    #[test]
+    fn s3_put_notification_ingest_and_ack() {
+        let mut rt = runtime();
+        let s3 = S3Client::new(region());
+        let sqs = SqsClient::new(region());
+
+        let bucket = format!("s3-source-{}", random_string(10).to_lowercase());
+        let queue = format!("s3-source-{}", random_string(10));
+
+        s3.create_bucket(CreateBucketRequest {
+            bucket: bucket.clone(),
+            ..Default::default()
+        })
+        .sync()
+        .unwrap();
+        let queue_url = sqs
+            .create_queue(CreateQueueRequest {
+                queue_name: queue.clone(),
+                ..Default::default()
+            })
+            .sync()
+            .unwrap()
+            .queue_url
+            .unwrap();
+        s3.put_bucket_notification_configuration(PutBucketNotificationConfigurationRequest {
+            bucket: bucket.clone(),
+            notification_configuration: NotificationConfiguration {
+                queue_configurations: Some(vec![QueueConfiguration {
+                    events: vec!["s3:ObjectCreated:*".into()],
+                    queue_arn: format!("arn:aws:sqs:us-east-1:000000000000:{}", queue),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            },
+        })
+        .sync()
+        .unwrap();
+
+        let lines = random_lines(100).take(10).collect::<Vec<_>>();
+        s3.put_object(PutObjectRequest {
+            bucket: bucket.clone(),
+            key: "test object.log".into(),
+            body: Some(lines.join("\n").into_bytes().into()),
+            ..Default::default()
+        })
+        .sync()
+        .unwrap();
+
+        let (tx, rx) = mpsc::channel(100);
+        let source = config(&queue_url)
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        rt.spawn(source);
+
+        let events = rt.block_on(collect_n(rx, lines.len())).unwrap();
+        let received = events
+            .iter()
+            .map(|event| event.as_log()[&event::log_schema().message_key()].to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(received, lines);
+        assert_eq!(
+            events[0].as_log()[&"object".into()],
+            "test object.log".into()
+        );
+
+        // The message is acked once all lines have been delivered
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert_eq!(queued_messages(&sqs, &queue_url), ("0".into(), "0".into()));
+    }
+    #[test]
+    fn deletes_messages_that_are_not_notifications() {
+        let mut rt = runtime();
+        let sqs = SqsClient::new(region());
+
+        let queue_url = sqs
+            .create_queue(CreateQueueRequest {
+                queue_name: format!("s3-source-{}", random_string(10)),
+                ..Default::default()
+            })
+            .sync()
+            .unwrap()
+            .queue_url
+            .unwrap();
+        sqs.send_message(SendMessageRequest {
+            queue_url: queue_url.clone(),
+            message_body: "not a notification".into(),
+            ..Default::default()
+        })
+        .sync()
+        .unwrap();
+
+        let (tx, _rx) = mpsc::channel(100);
+        let source = config(&queue_url)
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        rt.spawn(source);
+
+        std::thread::sleep(std::time::Duration::from_secs(3));
+        assert_eq!(queued_messages(&sqs, &queue_url), ("0".into(), "0".into()));
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{
+    internal_events::DemoLogsEventsGenerated,
+    shutdown::ShutdownSignal,
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+    Event,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{compat::Future01CompatExt, FutureExt, TryFutureExt};
+use futures01::{sync::mpsc, Sink};
+use rand::{prng::ChaChaRng, FromEntropy, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::time::Duration;
+use tokio::time::{delay_until, Instant};
+
+/// Most events generated and sent at once, so an unlimited rate still
+/// yields to shutdown and to the rest of the runtime.
+const MAX_BATCH_SIZE: u64 = 1000;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("rate must be a positive number, found {}", rate))]
+    InvalidRate { rate: f64 },
+    #[snafu(display("The shuffle format requires at least one line"))]
+    MissingLines,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DemoLogsConfig {
+    #[serde(default)]
+    pub format: Format,
+    /// The lines picked from by the `shuffle` format.
+    #[serde(default)]
+    pub lines: Vec<String>,
+    /// Events generated per second, as fast as possible if unset.
+    pub rate: Option<f64>,
+    /// Total number of events to generate before the source stops.
+    pub count: Option<u64>,
+    /// Seeds the generator, so the same messages are generated on every run.
+    pub seed: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Format {
+    ApacheCommon,
+    #[derivative(Default)]
+    Json,
+    Syslog,
+    Shuffle,
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<DemoLogsConfig>("demo_logs")
+}
+
+#[typetag::serde(name = "demo_logs")]
+impl SourceConfig for DemoLogsConfig {
+    fn build(
+        &self,
+        _name: &str,
+        _globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        if let Some(rate) = self.rate {
+            if !(rate.is_finite() && rate > 0.0) {
+                return Err(BuildError::InvalidRate { rate }.into());
+            }
+        }
+        if self.format == Format::Shuffle && self.lines.is_empty() {
+            return Err(BuildError::MissingLines.into());
+        }
+
+        let generator = Generator::new(self);
+        Ok(Box::new(
+            run(generator, self.rate, self.count, shutdown, out)
+                .boxed()
+                .compat(),
+        ))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn source_type(&self) -> &'static str {
+        "demo_logs"
+    }
+}
+
+/// Sends generated events, `rate` per second, until `count` were sent.
+///
+/// Rather than sleeping between events, which can't be done accurately
+/// at high rates, each event is scheduled relative to the start. Every
+/// wake up sends all the events that came due since the previous one, so
+/// timer resolution and time spent blocked on downstream don't accumulate
+/// as drift.
+async fn run(
+    mut generator: Generator,
+    rate: Option<f64>,
+    count: Option<u64>,
+    shutdown: ShutdownSignal,
+    mut out: mpsc::Sender<Event>,
+) -> Result<(), ()> {
+    let mut shutdown = shutdown.compat();
+    let start = Instant::now();
+    let mut sent = 0;
+
+    loop {
+        let remaining = count.map_or(MAX_BATCH_SIZE, |count| count - sent);
+        if remaining == 0 {
+            break;
+        }
+
+        let due = match rate {
+            // The first event is due right away.
+            Some(rate) => {
+                let due_by_now = (start.elapsed().as_secs_f64() * rate) as u64 + 1;
+                due_by_now.saturating_sub(sent)
+            }
+            None => MAX_BATCH_SIZE,
+        };
+        if due == 0 {
+            let rate = rate.expect("only limited rates can be ahead");
+            let next = start + Duration::from_secs_f64(sent as f64 / rate);
+            tokio::select! {
+                _ = delay_until(next) => continue,
+                _ = &mut shutdown => break,
+            }
+        }
+
+        let batch = due.min(remaining).min(MAX_BATCH_SIZE);
+        let now = Utc::now();
+        let events = (0..batch)
+            .map(|_| Event::from(generator.generate(now)))
+            .collect::<Vec<_>>();
+        emit!(DemoLogsEventsGenerated {
+            count: batch as usize
+        });
+        sent += batch;
+
+        tokio::select! {
+            result = out.send_all(futures01::stream::iter_ok(events)).compat() => {
+                let (sink, _) = result.map_err(|_| {
+                    error!(message = "Failed to forward events, downstream is closed.")
+                })?;
+                out = sink;
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    Ok(())
+}
+
+const HOSTS: &[&str] = &[
+    "10.0.0.12",
+    "10.0.3.201",
+    "172.16.4.9",
+    "192.168.1.37",
+    "203.0.113.50",
+    "198.51.100.7",
+];
+const USERS: &[&str] = &["-", "alice", "bob", "carol", "dave", "erin"];
+const METHODS: &[&str] = &["GET", "GET", "GET", "POST", "PUT", "DELETE", "HEAD"];
+const PATHS: &[&str] = &[
+    "/",
+    "/index.html",
+    "/login",
+    "/api/v1/users",
+    "/api/v1/orders",
+    "/static/app.js",
+    "/static/style.css",
+    "/healthz",
+];
+const STATUSES: &[u16] = &[200, 200, 200, 201, 204, 301, 304, 400, 403, 404, 500, 503];
+const APPS: &[&str] = &["nginx", "sshd", "cron", "kernel", "postgres", "systemd"];
+const MESSAGES: &[&str] = &[
+    "Connection accepted",
+    "Connection closed by remote host",
+    "Session opened for user",
+    "Configuration reloaded",
+    "Disk usage above threshold",
+    "Request took longer than expected",
+    "Cache miss, fetching from origin",
+    "Worker restarted after failure",
+];
+
+/// Generates messages from the configured format.
+struct Generator {
+    format: Format,
+    lines: Vec<String>,
+    rng: ChaChaRng,
+}
+
+impl Generator {
+    fn new(config: &DemoLogsConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => {
+                let mut bytes = [0; 32];
+                bytes[..8].copy_from_slice(&seed.to_le_bytes());
+                ChaChaRng::from_seed(bytes)
+            }
+            None => ChaChaRng::from_entropy(),
+        };
+        Generator {
+            format: config.format,
+            lines: config.lines.clone(),
+            rng,
+        }
+    }
+
+    fn generate(&mut self, now: DateTime<Utc>) -> String {
+        match self.format {
+            Format::ApacheCommon => format!(
+                "{} - {} [{}] \"{} {} HTTP/1.1\" {} {}",
+                self.pick(HOSTS),
+                self.pick(USERS),
+                now.format("%d/%b/%Y:%H:%M:%S %z"),
+                self.pick(METHODS),
+                self.pick(PATHS),
+                self.pick(STATUSES),
+                self.rng.gen_range(0, 50_000),
+            ),
+            Format::Json => serde_json::json!({
+                "host": self.pick(HOSTS),
+                "user-identifier": self.pick(USERS),
+                "datetime": now.to_rfc3339_opts(SecondsFormat::Millis, true),
+                "method": self.pick(METHODS),
+                "request": self.pick(PATHS),
+                "protocol": "HTTP/1.1",
+                "status": self.pick(STATUSES),
+                "bytes": self.rng.gen_range(0, 50_000),
+            })
+            .to_string(),
+            Format::Syslog => format!(
+                "<{}>1 {} {} {} {} ID{} - {}",
+                self.rng.gen_range(0, 192),
+                now.to_rfc3339_opts(SecondsFormat::Millis, true),
+                self.pick(HOSTS),
+                self.pick(APPS),
+                self.rng.gen_range(100, 10_000),
+                self.rng.gen_range(1, 1000),
+                self.pick(MESSAGES),
+            ),
+            Format::Shuffle => {
+                let index = self.rng.gen_range(0, self.lines.len());
+                self.lines[index].clone()
+            }
+        }
+    }
+
+    fn pick<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.rng.gen_range(0, values.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event, test_util::runtime};
+    use chrono::TimeZone;
+    use futures01::{Future, Stream};
+
+    fn config(config: &str) -> DemoLogsConfig {
+        toml::from_str(config).unwrap()
+    }
+
+    fn generate(config: &str, count: usize) -> Vec<String> {
+        let now = Utc.ymd(2020, 6, 1).and_hms(12, 0, 0);
+        let mut generator = Generator::new(&self::config(config));
+        (0..count).map(|_| generator.generate(now)).collect()
+    }
+
+    /// Runs the source to completion, returning its events and how long
+    /// it took.
+    fn run_source(config: &str, capacity: usize) -> (Vec<Event>, Duration) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let source = self::config(config)
+            .build(
+                "demo_logs",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+
+        let mut rt = runtime();
+        let start = std::time::Instant::now();
+        rt.block_on(source).unwrap();
+        let elapsed = start.elapsed();
+
+        (rx.collect().wait().unwrap(), elapsed)
+    }
+
+    #[test]
+    fn demo_logs_stops_after_count() {
+        let (events, _) = run_source(
+            r#"
+            format = "shuffle"
+            lines = ["one", "two", "three"]
+            count = 2500
+            "#,
+            3000,
+        );
+
+        assert_eq!(events.len(), 2500);
+        for event in events {
+            let message = event.as_log()[&event::log_schema().message_key()].to_string_lossy();
+            assert!(["one", "two", "three"].contains(&&message[..]));
+        }
+    }
+
+    #[test]
+    fn demo_logs_limits_rate() {
+        for &(rate, count) in &[(40, 20), (20_000, 10_000)] {
+            let (events, elapsed) =
+                run_source(&format!("rate = {}\ncount = {}", rate, count), count);
+
+            // The first event goes out right away, the last one is due
+            // after (count - 1) / rate seconds.
+            let expected = (count - 1) as f64 / rate as f64;
+            assert_eq!(events.len(), count);
+            assert!(
+                elapsed.as_secs_f64() >= expected * 0.95,
+                "rate {} took {:?}",
+                rate,
+                elapsed
+            );
+            assert!(
+                elapsed.as_secs_f64() < expected + 0.5,
+                "rate {} took {:?}",
+                rate,
+                elapsed
+            );
+        }
+    }
+
+    #[test]
+    fn demo_logs_is_deterministic_with_a_seed() {
+        for format in &["apache_common", "json", "syslog"] {
+            let seeded = format!("format = {:?}\nseed = 42", format);
+            let first = generate(&seeded, 20);
+            assert_eq!(first, generate(&seeded, 20));
+
+            let reseeded = format!("format = {:?}\nseed = 43", format);
+            assert_ne!(first, generate(&reseeded, 20));
+        }
+    }
+
+    #[test]
+    fn demo_logs_generates_formats() {
+        let apache = generate(r#"format = "apache_common""#, 1).remove(0);
+        assert!(
+            apache.contains("[01/Jun/2020:12:00:00 +0000]"),
+            "{}",
+            apache
+        );
+
+        let json = generate(r#"format = "json""#, 1).remove(0);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["datetime"], "2020-06-01T12:00:00.000Z");
+
+        let syslog = generate(r#"format = "syslog""#, 1).remove(0);
+        assert!(
+            syslog.contains(">1 2020-06-01T12:00:00.000Z "),
+            "{}",
+            syslog
+        );
+    }
+
+    #[test]
+    fn demo_logs_rejects_invalid_configs() {
+        for config in &["rate = 0.0", "rate = -1.0", r#"format = "shuffle""#] {
+            let (tx, _rx) = mpsc::channel(1);
+            assert!(self::config(config)
+                .build(
+                    "demo_logs",
+                    &GlobalOptions::default(),
+                    ShutdownSignal::noop(),
+                    tx
+                )
+                .is_err());
+        }
+    }
+}
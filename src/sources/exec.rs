@@ -0,0 +1,556 @@
+use crate::{
+    event::{self, Event},
+    internal_events::{ExecCommandExited, ExecEventReceived, ExecFailed, ExecTimedOut},
+    shutdown::ShutdownSignal,
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+};
+use bytes::Bytes;
+use futures::{
+    compat::{Compat01As03, Future01CompatExt},
+    stream::{self, BoxStream},
+    FutureExt, StreamExt, TryFutureExt,
+};
+use futures01::{sync::mpsc, Sink};
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{
+    io,
+    path::PathBuf,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::{Child, Command},
+    time::{delay_for, delay_until, interval, timeout_at, Instant},
+};
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("command must not be empty"))]
+    EmptyCommand,
+    #[snafu(display("exec_interval_secs must be greater than zero"))]
+    ZeroInterval,
+    #[snafu(display("timeout_secs must be greater than zero"))]
+    ZeroTimeout,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ExecConfig {
+    /// The program to run, followed by its arguments.
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub mode: Mode,
+    pub working_directory: Option<PathBuf>,
+    #[serde(default = "default_true")]
+    pub include_stderr: bool,
+    /// Time between the starts of two runs of a scheduled command.
+    #[serde(default = "default_exec_interval_secs")]
+    pub exec_interval_secs: u64,
+    /// Time a scheduled command may run for before it is killed.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Whether a streaming command is started again once it exits.
+    #[serde(default = "default_true")]
+    pub respawn_on_exit: bool,
+    #[serde(default = "default_respawn_interval_secs")]
+    pub respawn_interval_secs: u64,
+    pub host_key: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Mode {
+    /// The command is run every `exec_interval_secs`, and its output is
+    /// sent once it exits.
+    #[derivative(Default)]
+    Scheduled,
+    /// The command runs for as long as Vector does, and its output is sent
+    /// as it is written.
+    Streaming,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_exec_interval_secs() -> u64 {
+    60
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_respawn_interval_secs() -> u64 {
+    5
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<ExecConfig>("exec")
+}
+
+#[typetag::serde(name = "exec")]
+impl SourceConfig for ExecConfig {
+    fn build(
+        &self,
+        _name: &str,
+        _globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        if self.command.is_empty() {
+            return Err(BuildError::EmptyCommand.into());
+        }
+        if self.mode == Mode::Scheduled {
+            if self.exec_interval_secs == 0 {
+                return Err(BuildError::ZeroInterval.into());
+            }
+            if self.timeout_secs == 0 {
+                return Err(BuildError::ZeroTimeout.into());
+            }
+        }
+
+        let exec = Exec::new(self.clone());
+        let shutdown = shutdown.compat();
+        Ok(match self.mode {
+            Mode::Scheduled => Box::new(exec.run_scheduled(shutdown, out).boxed().compat()),
+            Mode::Streaming => Box::new(exec.run_streaming(shutdown, out).boxed().compat()),
+        })
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn source_type(&self) -> &'static str {
+        "exec"
+    }
+}
+
+/// A line of output, and the stream it was written to.
+struct Line {
+    stream: &'static str,
+    bytes: Bytes,
+}
+
+struct Exec {
+    config: ExecConfig,
+    /// The command as a single string, for events and logs.
+    command_line: String,
+    host_key: String,
+    hostname: Option<String>,
+}
+
+impl Exec {
+    fn new(config: ExecConfig) -> Self {
+        let host_key = config
+            .host_key
+            .clone()
+            .unwrap_or_else(|| event::log_schema().host_key().to_string());
+        Exec {
+            command_line: config.command.join(" "),
+            host_key,
+            hostname: hostname::get_hostname(),
+            config,
+        }
+    }
+
+    async fn run_scheduled(
+        self,
+        mut shutdown: Compat01As03<ShutdownSignal>,
+        mut out: mpsc::Sender<Event>,
+    ) -> Result<(), ()> {
+        // Runs never overlap: a command that is still running when the next
+        // one is due delays it.
+        let mut interval = interval(Duration::from_secs(self.config.exec_interval_secs));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = &mut shutdown => break,
+            }
+
+            let events = match self.run_to_completion(&mut shutdown).await {
+                Some(events) => events,
+                None => break,
+            };
+            for event in events {
+                out = send(out, event).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the command until it exits or times out, and returns its output
+    /// with the exit code of the command. Returns `None` if shutdown began
+    /// while it was running.
+    async fn run_to_completion(
+        &self,
+        shutdown: &mut Compat01As03<ShutdownSignal>,
+    ) -> Option<Vec<Event>> {
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(self.config.timeout_secs);
+        let (mut child, mut output) = match self.spawn() {
+            Ok(spawned) => spawned,
+            Err(error) => {
+                emit!(ExecFailed {
+                    command: &self.command_line,
+                    error,
+                });
+                return Some(Vec::new());
+            }
+        };
+        let pid = child.id();
+
+        let mut events = Vec::new();
+        let mut timed_out = false;
+        loop {
+            tokio::select! {
+                line = output.next() => match line {
+                    Some(Ok(line)) => events.push(self.create_event(line, pid)),
+                    Some(Err(error)) => emit!(ExecFailed { command: &self.command_line, error }),
+                    None => break,
+                },
+                _ = delay_until(deadline) => {
+                    timed_out = true;
+                    break;
+                }
+                _ = &mut *shutdown => {
+                    let _ = kill(&mut child).await;
+                    return None;
+                }
+            }
+        }
+        // The output of a command that timed out is kept, but any of its
+        // children still holding on to the pipes are not waited for.
+        drop(output);
+
+        // The command may also close its output and keep running.
+        let mut shutting_down = false;
+        let status = if timed_out {
+            None
+        } else {
+            tokio::select! {
+                status = timeout_at(deadline, &mut child) => status.ok(),
+                _ = &mut *shutdown => {
+                    shutting_down = true;
+                    None
+                }
+            }
+        };
+        if shutting_down {
+            let _ = kill(&mut child).await;
+            return None;
+        }
+        let status = match status {
+            Some(status) => status,
+            None => {
+                emit!(ExecTimedOut {
+                    command: &self.command_line,
+                    elapsed: start.elapsed(),
+                });
+                kill(&mut child).await
+            }
+        };
+
+        let exit_code = self.exited(status, start);
+        if let Some(exit_code) = exit_code {
+            for event in &mut events {
+                event.as_mut_log().insert("exit_code", exit_code as i64);
+            }
+        }
+        Some(events)
+    }
+
+    async fn run_streaming(
+        self,
+        mut shutdown: Compat01As03<ShutdownSignal>,
+        mut out: mpsc::Sender<Event>,
+    ) -> Result<(), ()> {
+        loop {
+            let start = Instant::now();
+            match self.spawn() {
+                Ok((mut child, mut output)) => {
+                    let pid = child.id();
+                    loop {
+                        tokio::select! {
+                            line = output.next() => match line {
+                                Some(Ok(line)) => out = send(out, self.create_event(line, pid)).await?,
+                                Some(Err(error)) => emit!(ExecFailed { command: &self.command_line, error }),
+                                None => break,
+                            },
+                            _ = &mut shutdown => {
+                                let _ = kill(&mut child).await;
+                                return Ok(());
+                            }
+                        }
+                    }
+
+                    let status = tokio::select! {
+                        status = &mut child => Some(status),
+                        _ = &mut shutdown => None,
+                    };
+                    match status {
+                        Some(status) => {
+                            self.exited(status, start);
+                        }
+                        None => {
+                            let _ = kill(&mut child).await;
+                            return Ok(());
+                        }
+                    }
+                }
+                Err(error) => emit!(ExecFailed {
+                    command: &self.command_line,
+                    error,
+                }),
+            }
+
+            if !self.config.respawn_on_exit {
+                return Ok(());
+            }
+            tokio::select! {
+                _ = delay_for(Duration::from_secs(self.config.respawn_interval_secs)) => {},
+                _ = &mut shutdown => return Ok(()),
+            }
+        }
+    }
+
+    fn spawn(&self) -> io::Result<(Child, BoxStream<'static, io::Result<Line>>)> {
+        let mut command = Command::new(&self.config.command[0]);
+        command
+            .args(&self.config.command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            // Unread pipes would eventually block the command, so stderr is
+            // only piped when it is captured.
+            .stderr(if self.config.include_stderr {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .kill_on_drop(true);
+        if let Some(directory) = &self.config.working_directory {
+            command.current_dir(directory);
+        }
+
+        let mut child = command.spawn()?;
+        let stdout = lines(child.stdout.take().expect("stdout is piped"), "stdout");
+        let output = match child.stderr.take() {
+            Some(stderr) => stream::select(stdout, lines(stderr, "stderr")).boxed(),
+            None => stdout.boxed(),
+        };
+        Ok((child, output))
+    }
+
+    fn exited(&self, status: io::Result<ExitStatus>, start: Instant) -> Option<i32> {
+        match status {
+            Ok(status) => {
+                emit!(ExecCommandExited {
+                    command: &self.command_line,
+                    exit_code: status.code(),
+                    elapsed: start.elapsed(),
+                });
+                status.code()
+            }
+            Err(error) => {
+                emit!(ExecFailed {
+                    command: &self.command_line,
+                    error,
+                });
+                None
+            }
+        }
+    }
+
+    fn create_event(&self, line: Line, pid: u32) -> Event {
+        emit!(ExecEventReceived {
+            command: &self.command_line,
+            byte_size: line.bytes.len(),
+        });
+
+        let mut event = Event::from(line.bytes);
+        let log = event.as_mut_log();
+        log.insert("command", self.command_line.clone());
+        log.insert("stream", line.stream);
+        log.insert("pid", pid as i64);
+        if let Some(hostname) = &self.hostname {
+            log.insert(self.host_key.clone(), hostname.clone());
+        }
+        event
+    }
+}
+
+/// Reads lines from `reader`, without their line endings. The stream ends
+/// after the first error.
+fn lines<R>(reader: R, stream: &'static str) -> impl futures::Stream<Item = io::Result<Line>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    stream::unfold(Some(BufReader::new(reader)), move |reader| async move {
+        let mut reader = reader?;
+        let mut buf = Vec::new();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) => None,
+            Ok(_) => {
+                if buf.ends_with(b"\n") {
+                    buf.pop();
+                    if buf.ends_with(b"\r") {
+                        buf.pop();
+                    }
+                }
+                let line = Line {
+                    stream,
+                    bytes: buf.into(),
+                };
+                Some((Ok(line), Some(reader)))
+            }
+            Err(error) => Some((Err(error), None)),
+        }
+    })
+}
+
+/// Kills the command and waits for it, so it doesn't linger as a zombie.
+async fn kill(child: &mut Child) -> io::Result<ExitStatus> {
+    // The command may have exited already.
+    let _ = child.kill();
+    child.await
+}
+
+async fn send(out: mpsc::Sender<Event>, event: Event) -> Result<mpsc::Sender<Event>, ()> {
+    out.send(event)
+        .compat()
+        .await
+        .map_err(|_| error!(message = "Failed to forward events, downstream is closed."))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::runtime;
+    use futures01::{Future, Stream};
+    use std::time::Instant as StdInstant;
+
+    fn config(command: &[&str], extra: &str) -> ExecConfig {
+        let mut config: ExecConfig = toml::from_str(&format!("command = []\n{}", extra)).unwrap();
+        config.command = command.iter().map(|&arg| arg.to_owned()).collect();
+        config
+    }
+
+    fn run_once(config: ExecConfig) -> Vec<Event> {
+        let mut rt = runtime();
+        rt.block_on_std(async move {
+            let exec = Exec::new(config);
+            let mut shutdown = ShutdownSignal::noop().compat();
+            exec.run_to_completion(&mut shutdown).await.unwrap()
+        })
+    }
+
+    fn field(event: &Event, name: &str) -> Option<String> {
+        event
+            .as_log()
+            .get(&name.into())
+            .map(|value| value.to_string_lossy())
+    }
+
+    fn message(event: &Event) -> String {
+        field(event, &event::log_schema().message_key()).unwrap()
+    }
+
+    #[test]
+    fn exec_scheduled_captures_output_and_exit_code() {
+        let mut events = run_once(config(
+            &["sh", "-c", "echo hello; echo oops >&2; exit 3"],
+            "",
+        ));
+        events.sort_by_key(|event| field(event, "stream"));
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(message(&events[0]), "oops");
+        assert_eq!(field(&events[0], "stream").unwrap(), "stderr");
+        assert_eq!(message(&events[1]), "hello");
+        assert_eq!(field(&events[1], "stream").unwrap(), "stdout");
+        for event in &events {
+            assert_eq!(field(event, "exit_code").unwrap(), "3");
+            assert!(field(event, "pid").is_some());
+        }
+    }
+
+    #[test]
+    fn exec_captures_stderr_only_commands() {
+        let command = ["sh", "-c", "echo only errors >&2"];
+
+        let events = run_once(config(&command, ""));
+        assert_eq!(events.len(), 1);
+        assert_eq!(message(&events[0]), "only errors");
+        assert_eq!(field(&events[0], "exit_code").unwrap(), "0");
+
+        let events = run_once(config(&command, "include_stderr = false"));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn exec_kills_commands_that_time_out() {
+        let start = StdInstant::now();
+        let events = run_once(config(
+            &["sh", "-c", "echo started; exec sleep 30"],
+            "timeout_secs = 1",
+        ));
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(events.len(), 1);
+        assert_eq!(message(&events[0]), "started");
+        assert!(field(&events[0], "exit_code").is_none());
+    }
+
+    #[test]
+    fn exec_streams_output() {
+        let config = config(
+            &[
+                "sh",
+                "-c",
+                "echo one; sleep 0.1; echo two; sleep 0.1; echo three",
+            ],
+            r#"
+            mode = "streaming"
+            respawn_on_exit = false
+            "#,
+        );
+        let (tx, rx) = mpsc::channel(10);
+
+        let mut rt = runtime();
+        let source = config
+            .build(
+                "exec",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        rt.block_on(source).unwrap();
+
+        let events = rx.collect().wait().unwrap();
+        let messages = events.iter().map(message).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["one", "two", "three"]);
+        assert!(events
+            .iter()
+            .all(|event| field(event, "exit_code").is_none()));
+    }
+
+    #[test]
+    fn exec_rejects_empty_commands() {
+        let (tx, _rx) = mpsc::channel(1);
+        assert!(config(&[], "")
+            .build(
+                "exec",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! Timestamps derived from the dates in file paths, like those of rotated
+//! `app-2020-06-11.log` files, for lines that don't carry their own.
+//!
+//! A date in a path gives the period the file covers, a day for
+//! `%Y-%m-%d`, an hour for `%Y%m%d%H`. Lines read from a file while its
+//! period is current are stamped with the time they're read, as is every
+//! line read from it after, so files written to past the end of their period,
+//! like a daily file rotated shortly after midnight, keep accurate
+//! timestamps. Lines of files only read once their period is over, when
+//! backfilling old files, are stamped with the start of the period.
+
+use crate::types::DefaultTimezone;
+use chrono::{
+    format::{parse, Parsed, StrftimeItems},
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::collections::HashMap;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FilenameTimestampConfig {
+    /// A strftime format, searched for in file paths.
+    pub format: String,
+    /// The time zone of the dates in paths, local by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("filename_timestamp format {:?} has no year", format))]
+    NoYear { format: String },
+    #[snafu(display(
+        "filename_timestamp format {:?} uses unsupported specifier {:?}",
+        format,
+        specifier
+    ))]
+    UnsupportedSpecifier { format: String, specifier: String },
+    #[snafu(display(
+        "Invalid time zone {:?}, expected \"local\", \"UTC\" or an offset like \"+02:00\"",
+        timezone
+    ))]
+    InvalidTimezone { timezone: String },
+}
+
+/// How long the period a file covers is, from the finest field of its date.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Granularity {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Month,
+    Year,
+}
+
+impl Granularity {
+    fn end_of(self, start: NaiveDateTime) -> Option<NaiveDateTime> {
+        let date = start.date();
+        match self {
+            Granularity::Second => Some(start + Duration::seconds(1)),
+            Granularity::Minute => Some(start + Duration::minutes(1)),
+            Granularity::Hour => Some(start + Duration::hours(1)),
+            Granularity::Day => Some(start + Duration::days(1)),
+            Granularity::Month => {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                NaiveDate::from_ymd_opt(year, month, 1).map(|date| date.and_time(start.time()))
+            }
+            Granularity::Year => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+                .map(|date| date.and_time(start.time())),
+        }
+    }
+}
+
+/// What's known about a file.
+struct FileState {
+    /// The start and end of the period the file covers, if its path has a
+    /// date.
+    period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Whether the file was read from while its period was current.
+    live: bool,
+}
+
+pub struct FilenameTimestamps {
+    format: String,
+    pattern: Regex,
+    granularity: Granularity,
+    timezone: DefaultTimezone,
+    files: HashMap<String, FileState>,
+}
+
+impl FilenameTimestamps {
+    pub fn new(config: &FilenameTimestampConfig) -> crate::Result<Self> {
+        let (pattern, granularity) = pattern_for(&config.format)?;
+        let timezone = match &config.timezone {
+            Some(timezone) => {
+                DefaultTimezone::parse(timezone).ok_or_else(|| BuildError::InvalidTimezone {
+                    timezone: timezone.clone(),
+                })?
+            }
+            None => DefaultTimezone::Local,
+        };
+
+        Ok(Self {
+            format: config.format.clone(),
+            pattern,
+            granularity,
+            timezone,
+            files: HashMap::new(),
+        })
+    }
+
+    /// The timestamp of a line of `file` read at `now`. That's `now` if the
+    /// file's path has no date matching the format.
+    pub fn timestamp(&mut self, file: &str, now: DateTime<Utc>) -> DateTime<Utc> {
+        if !self.files.contains_key(file) {
+            let period = self.period_of(file);
+            if period.is_none() {
+                debug!(
+                    message = "no date found in file path.",
+                    %file,
+                    format = %self.format,
+                    rate_limit_secs = 30
+                );
+            }
+            let state = FileState {
+                period,
+                live: false,
+            };
+            self.files.insert(file.to_owned(), state);
+        }
+        let state = self.files.get_mut(file).unwrap();
+
+        match state.period {
+            Some((start, end)) if !state.live => {
+                if now < start {
+                    // A date ahead of the clock can't be trusted over it.
+                    now
+                } else if now < end {
+                    state.live = true;
+                    now
+                } else {
+                    start
+                }
+            }
+            _ => now,
+        }
+    }
+
+    fn period_of(&self, file: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let found = self.pattern.find(file)?;
+
+        let mut parsed = Parsed::new();
+        parse(
+            &mut parsed,
+            found.as_str(),
+            StrftimeItems::new(&self.format),
+        )
+        .ok()?;
+        // The fields finer than the format's are at their start.
+        if parsed.ordinal.is_none() {
+            if parsed.month.is_none() {
+                parsed.set_month(1).ok()?;
+            }
+            if parsed.day.is_none() {
+                parsed.set_day(1).ok()?;
+            }
+        }
+        if parsed.hour_div_12.is_none() {
+            parsed.set_hour(0).ok()?;
+        }
+        if parsed.minute.is_none() {
+            parsed.set_minute(0).ok()?;
+        }
+
+        let start = parsed
+            .to_naive_date()
+            .ok()?
+            .and_time(parsed.to_naive_time().ok()?);
+        let end = self.granularity.end_of(start)?;
+        Some((
+            self.timezone.from_naive(&start)?,
+            self.timezone.from_naive(&end)?,
+        ))
+    }
+}
+
+/// A regex matching what `format` formats dates to, and the granularity of
+/// those dates.
+fn pattern_for(format: &str) -> crate::Result<(Regex, Granularity)> {
+    let mut pattern = String::new();
+    let mut granularity = None;
+    let mut has_year = false;
+    let mut literal = String::new();
+
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        pattern.push_str(&regex::escape(&literal));
+        literal.clear();
+
+        let specifier = chars.next();
+        let (matches, field) = match specifier {
+            Some('Y') => (r"\d{4}", Granularity::Year),
+            Some('y') => (r"\d{2}", Granularity::Year),
+            Some('m') => (r"\d{2}", Granularity::Month),
+            Some('b') | Some('h') => (r"[A-Za-z]{3}", Granularity::Month),
+            Some('B') => (r"[A-Za-z]+", Granularity::Month),
+            Some('d') => (r"\d{2}", Granularity::Day),
+            Some('e') => (r"[ \d]?\d", Granularity::Day),
+            Some('j') => (r"\d{3}", Granularity::Day),
+            Some('F') => (r"\d{4}-\d{2}-\d{2}", Granularity::Day),
+            Some('H') => (r"\d{2}", Granularity::Hour),
+            Some('M') => (r"\d{2}", Granularity::Minute),
+            Some('R') => (r"\d{2}:\d{2}", Granularity::Minute),
+            Some('S') => (r"\d{2}", Granularity::Second),
+            Some('T') => (r"\d{2}:\d{2}:\d{2}", Granularity::Second),
+            Some('%') => {
+                pattern.push('%');
+                continue;
+            }
+            other => {
+                return Err(Box::new(BuildError::UnsupportedSpecifier {
+                    format: format.to_owned(),
+                    specifier: other.map_or("%".into(), |c| format!("%{}", c)),
+                }))
+            }
+        };
+        has_year |= matches!(specifier, Some('Y') | Some('y') | Some('F'));
+        granularity = Some(match granularity {
+            Some(granularity) if granularity < field => granularity,
+            _ => field,
+        });
+        pattern.push_str(matches);
+    }
+    pattern.push_str(&regex::escape(&literal));
+
+    match granularity {
+        Some(granularity) if has_year => Ok((Regex::new(&pattern)?, granularity)),
+        _ => Err(Box::new(BuildError::NoYear {
+            format: format.to_owned(),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn timestamps(format: &str) -> FilenameTimestamps {
+        FilenameTimestamps::new(&FilenameTimestampConfig {
+            format: format.into(),
+            timezone: Some("UTC".into()),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn filename_timestamp_backfills_from_date() {
+        let mut timestamps = timestamps("%Y-%m-%d");
+        let now = Utc.ymd(2020, 6, 20).and_hms(9, 30, 0);
+
+        assert_eq!(
+            timestamps.timestamp("/var/log/app-2020-06-11.log", now),
+            Utc.ymd(2020, 6, 11).and_hms(0, 0, 0)
+        );
+
+        let mut timestamps = timestamps("%Y%m%d%H");
+        assert_eq!(
+            timestamps.timestamp("/var/log/app.log.2020061113", now),
+            Utc.ymd(2020, 6, 11).and_hms(13, 0, 0)
+        );
+
+        let mut timestamps = timestamps("%Y/%b");
+        assert_eq!(
+            timestamps.timestamp("/var/log/2020/Feb/app.log", now),
+            Utc.ymd(2020, 2, 1).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn filename_timestamp_falls_back_to_now() {
+        let mut timestamps = timestamps("%Y-%m-%d");
+        let now = Utc.ymd(2020, 6, 20).and_hms(9, 30, 0);
+
+        assert_eq!(timestamps.timestamp("/var/log/app.log", now), now);
+        // Matches the pattern, but isn't a date.
+        assert_eq!(
+            timestamps.timestamp("/var/log/app-2020-13-45.log", now),
+            now
+        );
+        // Ahead of the clock.
+        assert_eq!(
+            timestamps.timestamp("/var/log/app-2020-07-01.log", now),
+            now
+        );
+    }
+
+    #[test]
+    fn filename_timestamp_across_day_boundary() {
+        let mut timestamps = timestamps("%Y-%m-%d");
+        let file = "/var/log/app-2020-06-11.log";
+
+        // Read while the day is current, and after midnight, before the
+        // file is rotated.
+        let before = Utc.ymd(2020, 6, 11).and_hms(23, 59, 58);
+        let after = Utc.ymd(2020, 6, 12).and_hms(0, 0, 2);
+        assert_eq!(timestamps.timestamp(file, before), before);
+        assert_eq!(timestamps.timestamp(file, after), after);
+
+        // Never read while current.
+        let other = "/var/log/app-2020-06-10.log";
+        assert_eq!(
+            timestamps.timestamp(other, after),
+            Utc.ymd(2020, 6, 10).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn filename_timestamp_invalid_formats() {
+        let build = |format: &str| {
+            FilenameTimestamps::new(&FilenameTimestampConfig {
+                format: format.into(),
+                timezone: None,
+            })
+        };
+
+        assert!(build("%m-%d").is_err());
+        assert!(build("%Y-%Q").is_err());
+        assert!(build("%Y-%").is_err());
+        assert!(build("%Y.%m.%d").is_ok());
+        assert!(FilenameTimestamps::new(&FilenameTimestampConfig {
+            format: "%Y".into(),
+            timezone: Some("Mars/Olympus".into()),
+        })
+        .is_err());
+    }
+}
@@ -2,11 +2,13 @@ use crate::{
     event::{self, Event},
     internal_events::FileEventReceived,
     shutdown::ShutdownSignal,
+    sources::util::invalid_bytes::InvalidBytes,
     topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
     trace::{current_span, Instrument},
 };
 use bytes::Bytes;
-use file_source::{FileServer, Fingerprinter};
+use chrono::Utc;
+use file_source::{FileServer, Fingerprinter, ReadFrom};
 use futures01::{future, sync::mpsc, Future, Sink, Stream};
 use regex::bytes::Regex;
 use serde::{Deserialize, Serialize};
@@ -16,13 +18,17 @@ use std::path::PathBuf;
 use std::thread;
 use std::time::{Duration, SystemTime};
 
+mod filename_timestamp;
 mod line_agg;
+use filename_timestamp::{FilenameTimestampConfig, FilenameTimestamps};
 use line_agg::LineAgg;
 
 #[derive(Debug, Snafu)]
 enum BuildError {
     #[snafu(display("data_dir option required, but not given here or globally"))]
     NoDataDir,
+    #[snafu(display("`start_at_beginning` can't be combined with `read_from = \"end\"`"))]
+    StartAtBeginningReadFromEnd,
     #[snafu(display(
         "could not create subdirectory {:?} inside of data_dir {:?}",
         subdir,
@@ -73,9 +79,13 @@ pub struct FileConfig {
     pub exclude: Vec<PathBuf>,
     pub file_key: Option<String>,
     pub start_at_beginning: bool,
+    pub read_from: ReadFromConfig,
+    /// Replay files from `read_from`, neither reading nor writing checkpoints.
+    pub ignore_checkpoints: bool,
     pub ignore_older: Option<u64>, // secs
     #[serde(default = "default_max_line_bytes")]
     pub max_line_bytes: usize,
+    pub invalid_bytes: InvalidBytes,
     pub host_key: Option<String>,
     pub data_dir: Option<PathBuf>,
     pub glob_minimum_cooldown: u64, // millis
@@ -85,6 +95,7 @@ pub struct FileConfig {
     pub multiline: Option<MultilineConfig>,
     pub max_read_bytes: usize,
     pub oldest_first: bool,
+    pub filename_timestamp: Option<FilenameTimestampConfig>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -149,6 +160,22 @@ impl From<FingerprintingConfig> for Fingerprinter {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadFromConfig {
+    Beginning,
+    End,
+}
+
+impl From<ReadFromConfig> for ReadFrom {
+    fn from(config: ReadFromConfig) -> ReadFrom {
+        match config {
+            ReadFromConfig::Beginning => ReadFrom::Beginning,
+            ReadFromConfig::End => ReadFrom::End,
+        }
+    }
+}
+
 fn default_max_line_bytes() -> usize {
     bytesize::kib(100u64) as usize
 }
@@ -160,8 +187,11 @@ impl Default for FileConfig {
             exclude: vec![],
             file_key: Some("file".to_string()),
             start_at_beginning: false,
+            read_from: ReadFromConfig::Beginning,
+            ignore_checkpoints: false,
             ignore_older: None,
             max_line_bytes: default_max_line_bytes(),
+            invalid_bytes: InvalidBytes::default(),
             fingerprinting: FingerprintingConfig::Checksum {
                 fingerprint_bytes: 256,
                 ignored_header_bytes: 0,
@@ -174,6 +204,7 @@ impl Default for FileConfig {
             multiline: None,
             max_read_bytes: 2048,
             oldest_first: false,
+            filename_timestamp: None,
         }
     }
 }
@@ -197,6 +228,10 @@ impl SourceConfig for FileConfig {
         // other
         let data_dir = globals.resolve_and_make_data_subdir(self.data_dir.as_ref(), name)?;
 
+        if self.start_at_beginning && self.read_from == ReadFromConfig::End {
+            return Err(BuildError::StartAtBeginningReadFromEnd.into());
+        }
+
         if let Some(ref config) = self.multiline {
             let _: line_agg::Config = config.try_into()?;
         }
@@ -205,6 +240,10 @@ impl SourceConfig for FileConfig {
             Regex::new(indicator).with_context(|| InvalidMessageStartIndicator { indicator })?;
         }
 
+        if let Some(ref config) = self.filename_timestamp {
+            FilenameTimestamps::new(config)?;
+        }
+
         Ok(file_source(self, data_dir, out))
     }
 
@@ -234,6 +273,8 @@ pub fn file_source(
         exclude: config.exclude.clone(),
         max_read_bytes: config.max_read_bytes,
         start_at_beginning: config.start_at_beginning,
+        read_from: config.read_from.into(),
+        ignore_checkpoints: config.ignore_checkpoints,
         ignore_before,
         max_line_bytes: config.max_line_bytes,
         data_dir,
@@ -248,12 +289,17 @@ pub fn file_source(
         .clone()
         .unwrap_or(event::log_schema().host_key().to_string());
     let hostname = hostname::get_hostname();
+    let invalid_bytes = config.invalid_bytes;
 
     let include = config.include.clone();
     let exclude = config.exclude.clone();
     let multiline_config = config.multiline.clone();
     let message_start_indicator = config.message_start_indicator.clone();
     let multi_line_timeout = config.multi_line_timeout;
+    let mut filename_timestamps = config
+        .filename_timestamp
+        .as_ref()
+        .map(|config| FilenameTimestamps::new(config).unwrap()); // validated in build
     Box::new(future::lazy(move || {
         info!(message = "Starting file server.", ?include, ?exclude);
 
@@ -282,13 +328,23 @@ pub fn file_source(
         let span2 = span.clone();
         tokio01::spawn(
             messages
-                .map(move |(msg, file): (Bytes, String)| {
+                .filter_map(move |(msg, file): (Bytes, String)| {
                     let _enter = span2.enter();
                     emit!(FileEventReceived {
                         file: &file,
                         byte_size: msg.len(),
                     });
-                    create_event(msg, file, &host_key, &hostname, &file_key)
+                    let timestamp = filename_timestamps
+                        .as_mut()
+                        .map(|timestamps| timestamps.timestamp(&file, Utc::now()));
+                    let mut event =
+                        create_event(msg, file, invalid_bytes, &host_key, &hostname, &file_key)?;
+                    if let Some(timestamp) = timestamp {
+                        event
+                            .as_mut_log()
+                            .insert(event::log_schema().timestamp_key().clone(), timestamp);
+                    }
+                    Some(event)
                 })
                 .forward(out.sink_map_err(|e| error!(%e)))
                 .map(|_| ())
@@ -313,11 +369,12 @@ pub fn file_source(
 fn create_event(
     line: Bytes,
     file: String,
+    invalid_bytes: InvalidBytes,
     host_key: &str,
     hostname: &Option<String>,
     file_key: &Option<String>,
-) -> Event {
-    let mut event = Event::from(line);
+) -> Option<Event> {
+    let mut event = invalid_bytes.event(line, "file")?;
 
     if let Some(file_key) = &file_key {
         event.as_mut_log().insert(file_key.clone(), file);
@@ -327,7 +384,7 @@ fn create_event(
         event.as_mut_log().insert(host_key, hostname.clone());
     }
 
-    event
+    Some(event)
 }
 
 #[cfg(test)]
@@ -451,7 +508,15 @@ mod tests {
         let hostname = Some("Some.Machine".to_string());
         let file_key = Some("file".to_string());
 
-        let event = create_event(line, file, &host_key, &hostname, &file_key);
+        let event = create_event(
+            line,
+            file,
+            InvalidBytes::Replace,
+            &host_key,
+            &hostname,
+            &file_key,
+        )
+        .unwrap();
         let log = event.into_log();
 
         assert_eq!(log[&"file".into()], "some_file.rs".into());
@@ -916,6 +981,119 @@ mod tests {
             );
         }
     }
+    /// Runs the source for `config`, calling `while_running` once it's
+    /// started, and returns the messages it read.
+    fn run_file_source(config: &file::FileConfig, while_running: impl FnOnce()) -> Vec<String> {
+        let (tx, rx) = futures01::sync::mpsc::channel(10);
+        let source = file::file_source(config, config.data_dir.clone().unwrap(), tx);
+        let mut rt = runtime::Runtime::new().unwrap();
+        let (trigger, tripwire) = Tripwire::new();
+        rt.spawn(source.select(tripwire).map(|_| ()).map_err(|_| ()));
+
+        sleep();
+        while_running();
+        sleep();
+
+        drop(trigger);
+        shutdown_on_idle(rt);
+
+        wait_with_timeout(rx.collect())
+            .into_iter()
+            .map(|event| event.as_log()[&event::log_schema().message_key()].to_string_lossy())
+            .collect()
+    }
+
+    #[test]
+    fn file_read_from_end() {
+        let dir = tempdir().unwrap();
+        let config = file::FileConfig {
+            include: vec![dir.path().join("*")],
+            read_from: file::ReadFromConfig::End,
+            ..test_default_file_config(&dir)
+        };
+
+        let path = dir.path().join("file");
+        let mut file = File::create(&path).unwrap();
+        writeln!(&mut file, "old line").unwrap();
+        sleep();
+
+        let mut lines = run_file_source(&config, || {
+            writeln!(&mut file, "new line").unwrap();
+            // Files found after startup are read from the beginning.
+            let mut new_file = File::create(dir.path().join("new_file")).unwrap();
+            writeln!(&mut new_file, "line of new file").unwrap();
+        });
+        lines.sort();
+        assert_eq!(lines, vec!["line of new file", "new line"]);
+
+        let config = file::FileConfig {
+            start_at_beginning: true,
+            ..config
+        };
+        let (tx, _rx) = futures01::sync::mpsc::channel(10);
+        let globals = GlobalOptions::default();
+        assert!(config
+            .build("file", &globals, ShutdownSignal::noop(), tx)
+            .is_err());
+    }
+
+    #[test]
+    fn file_invalid_bytes() {
+        let cases = vec![
+            (InvalidBytes::Replace, vec!["valid", "in\u{fffd}valid"]),
+            (InvalidBytes::Base64, vec!["valid", "aW7/dmFsaWQ="]),
+            (InvalidBytes::Drop, vec!["valid"]),
+        ];
+        for (invalid_bytes, expected) in cases {
+            let dir = tempdir().unwrap();
+            let config = file::FileConfig {
+                include: vec![dir.path().join("*")],
+                invalid_bytes,
+                ..test_default_file_config(&dir)
+            };
+
+            let lines = run_file_source(&config, || {
+                let mut file = File::create(dir.path().join("file")).unwrap();
+                file.write_all(b"valid\nin\xffvalid\n").unwrap();
+            });
+            assert_eq!(lines, expected, "{:?}", invalid_bytes);
+        }
+    }
+
+    #[test]
+    fn file_replay_ignores_checkpoints() {
+        let dir = tempdir().unwrap();
+        let config = file::FileConfig {
+            include: vec![dir.path().join("*")],
+            ..test_default_file_config(&dir)
+        };
+
+        let path = dir.path().join("file");
+        let mut file = File::create(&path).unwrap();
+        writeln!(&mut file, "first line").unwrap();
+        sleep();
+
+        let lines = run_file_source(&config, || ());
+        assert_eq!(lines, vec!["first line"]);
+
+        // Replaying reads the file from the beginning despite its checkpoint.
+        let replay = file::FileConfig {
+            include: config.include.clone(),
+            ignore_checkpoints: true,
+            ..test_default_file_config(&dir)
+        };
+        let lines = run_file_source(&replay, || {
+            writeln!(&mut file, "second line").unwrap();
+        });
+        assert_eq!(lines, vec!["first line", "second line"]);
+
+        // The replay left the checkpoint as it was.
+        let lines = run_file_source(&config, || {
+            writeln!(&mut file, "third line").unwrap();
+        });
+        assert_eq!(lines, vec!["second line", "third line"]);
+    }
+
     #[test]
     fn file_start_position_server_restart_with_file_rotation() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,589 @@
+use crate::{
+    internal_events::{
+        HostMetricsCollectorFailed, HostMetricsCollectorUnsupported, HostMetricsEventsCollected,
+    },
+    shutdown::ShutdownSignal,
+    sources::util::MetricsBuilder,
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+    Event,
+};
+use futures::{
+    compat::Future01CompatExt,
+    future::{FutureExt, TryFutureExt},
+    stream::StreamExt,
+};
+use futures01::{sync::mpsc, Future, Sink};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+use tokio::time::interval;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Collector {
+    Cpu,
+    Disk,
+    Filesystem,
+    Load,
+    Memory,
+    Network,
+}
+
+impl Collector {
+    /// Whether the collector can read its data on this platform. Only load
+    /// and file system usage are available outside of Linux.
+    fn is_supported(self) -> bool {
+        match self {
+            Collector::Load | Collector::Filesystem => cfg!(unix),
+            Collector::Cpu | Collector::Disk | Collector::Memory | Collector::Network => {
+                cfg!(target_os = "linux")
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Derivative)]
+#[derivative(Default)]
+#[serde(deny_unknown_fields)]
+pub struct HostMetricsConfig {
+    #[serde(default = "default_collectors")]
+    #[derivative(Default(value = "default_collectors()"))]
+    pub collectors: Vec<Collector>,
+    #[serde(default = "default_namespace")]
+    #[derivative(Default(value = "default_namespace()"))]
+    pub namespace: Option<String>,
+    #[serde(default = "default_scrape_interval_secs")]
+    #[derivative(Default(value = "default_scrape_interval_secs()"))]
+    pub scrape_interval_secs: u64,
+    #[serde(default = "default_procfs_root")]
+    #[derivative(Default(value = "default_procfs_root()"))]
+    pub procfs_root: PathBuf,
+}
+
+fn default_collectors() -> Vec<Collector> {
+    vec![
+        Collector::Cpu,
+        Collector::Disk,
+        Collector::Filesystem,
+        Collector::Load,
+        Collector::Memory,
+        Collector::Network,
+    ]
+    .into_iter()
+    .filter(|collector| collector.is_supported())
+    .collect()
+}
+
+fn default_namespace() -> Option<String> {
+    Some("host".into())
+}
+
+fn default_scrape_interval_secs() -> u64 {
+    15
+}
+
+fn default_procfs_root() -> PathBuf {
+    PathBuf::from("/proc")
+}
+
+inventory::submit! {
+    SourceDescription::new::<HostMetricsConfig>("host_metrics")
+}
+
+#[typetag::serde(name = "host_metrics")]
+impl SourceConfig for HostMetricsConfig {
+    fn build(
+        &self,
+        _name: &str,
+        _globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        let mut config = self.clone();
+        config.collectors = self.supported_collectors();
+        let fut = run(config, out, shutdown).boxed().compat();
+        Ok(Box::new(fut))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn source_type(&self) -> &'static str {
+        "host_metrics"
+    }
+}
+
+async fn run(
+    config: HostMetricsConfig,
+    mut out: mpsc::Sender<Event>,
+    mut shutdown: ShutdownSignal,
+) -> Result<(), ()> {
+    let duration = Duration::from_secs(config.scrape_interval_secs);
+    let mut interval = interval(duration).map(|_| ());
+
+    while let Some(()) = interval.next().await {
+        // Check for shutdown signal
+        if shutdown.poll().expect("polling shutdown").is_ready() {
+            break;
+        }
+
+        let metrics = config.capture_metrics();
+        emit!(HostMetricsEventsCollected {
+            count: metrics.len()
+        });
+
+        let (sink, _) = out
+            .send_all(futures01::stream::iter_ok(metrics))
+            .compat()
+            .await
+            .map_err(|error| error!(message = "error sending host metrics", %error))?;
+        out = sink;
+    }
+
+    Ok(())
+}
+
+impl HostMetricsConfig {
+    /// The configured collectors that this platform can provide. The others
+    /// are left out with a warning, so a config shared across platforms
+    /// still collects what it can.
+    fn supported_collectors(&self) -> Vec<Collector> {
+        self.collectors
+            .iter()
+            .copied()
+            .filter(|collector| {
+                let supported = collector.is_supported();
+                if !supported {
+                    emit!(HostMetricsCollectorUnsupported {
+                        collector: *collector
+                    });
+                }
+                supported
+            })
+            .collect()
+    }
+
+    fn capture_metrics(&self) -> Vec<Event> {
+        let no_tags = BTreeMap::new();
+        let mut builder = MetricsBuilder::new(self.namespace.as_deref(), &no_tags);
+
+        for collector in &self.collectors {
+            // A collector that fails to read its data must not prevent the
+            // others from reporting.
+            if let Err(error) = self.collect(*collector, &mut builder) {
+                emit!(HostMetricsCollectorFailed {
+                    collector: *collector,
+                    error
+                });
+            }
+        }
+
+        builder.metrics.into_iter().map(Event::Metric).collect()
+    }
+
+    fn collect(&self, collector: Collector, builder: &mut MetricsBuilder) -> crate::Result<()> {
+        match collector {
+            Collector::Load => collect_load(builder),
+            Collector::Filesystem => collect_filesystem(&self.procfs_root, builder),
+            Collector::Cpu => collect_cpu(&self.procfs_root, builder),
+            Collector::Disk => collect_disk(&self.procfs_root, builder),
+            Collector::Memory => collect_memory(&self.procfs_root, builder),
+            Collector::Network => collect_network(&self.procfs_root, builder),
+        }
+    }
+}
+
+fn read_proc(root: &PathBuf, file: &str) -> crate::Result<String> {
+    let path = root.join(file);
+    std::fs::read_to_string(&path)
+        .map_err(|error| format!("could not read {:?}: {}", path, error).into())
+}
+
+#[cfg(unix)]
+fn collect_load(builder: &mut MetricsBuilder) -> crate::Result<()> {
+    let mut loads = [0f64; 3];
+    let res = unsafe { nix::libc::getloadavg(loads.as_mut_ptr(), 3) };
+    if res != 3 {
+        return Err("getloadavg failed".into());
+    }
+    builder.gauge("load1", loads[0], &[]);
+    builder.gauge("load5", loads[1], &[]);
+    builder.gauge("load15", loads[2], &[]);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn collect_load(_builder: &mut MetricsBuilder) -> crate::Result<()> {
+    Err("load average is not available on this platform".into())
+}
+
+#[cfg(unix)]
+fn collect_filesystem(root: &PathBuf, builder: &mut MetricsBuilder) -> crate::Result<()> {
+    let mounts = if cfg!(target_os = "linux") {
+        parse_mounts(&read_proc(root, "mounts")?)
+    } else {
+        vec![Mount {
+            device: "root".into(),
+            mountpoint: "/".into(),
+            filesystem: "unknown".into(),
+        }]
+    };
+
+    for mount in mounts {
+        let stat = match nix::sys::statvfs::statvfs(mount.mountpoint.as_str()) {
+            Ok(stat) => stat,
+            // Mounts may vanish or be unreadable by this user, skip them.
+            Err(_) => continue,
+        };
+        let fragment_size = stat.fragment_size() as f64;
+        let total = stat.blocks() as f64 * fragment_size;
+        let free = stat.blocks_available() as f64 * fragment_size;
+        // Pseudo file systems report no blocks at all
+        if total == 0.0 {
+            continue;
+        }
+
+        let tags = [
+            ("device", mount.device.as_str()),
+            ("mountpoint", mount.mountpoint.as_str()),
+            ("filesystem", mount.filesystem.as_str()),
+        ];
+        builder.gauge("filesystem_total_bytes", total, &tags);
+        builder.gauge("filesystem_free_bytes", free, &tags);
+        builder.gauge("filesystem_used_bytes", total - free, &tags);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn collect_filesystem(_root: &PathBuf, _builder: &mut MetricsBuilder) -> crate::Result<()> {
+    Err("file system usage is not available on this platform".into())
+}
+
+#[cfg(target_os = "linux")]
+fn collect_cpu(root: &PathBuf, builder: &mut MetricsBuilder) -> crate::Result<()> {
+    let ticks = nix::unistd::sysconf(nix::unistd::SysconfVar::CLK_TCK)
+        .ok()
+        .and_then(|ticks| ticks)
+        .unwrap_or(100) as f64;
+    parse_cpu(&read_proc(root, "stat")?, ticks, builder);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn collect_disk(root: &PathBuf, builder: &mut MetricsBuilder) -> crate::Result<()> {
+    parse_diskstats(&read_proc(root, "diskstats")?, builder);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn collect_memory(root: &PathBuf, builder: &mut MetricsBuilder) -> crate::Result<()> {
+    parse_meminfo(&read_proc(root, "meminfo")?, builder);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn collect_network(root: &PathBuf, builder: &mut MetricsBuilder) -> crate::Result<()> {
+    parse_net_dev(&read_proc(root, "net/dev")?, builder);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_cpu(_root: &PathBuf, _builder: &mut MetricsBuilder) -> crate::Result<()> {
+    Err("cpu statistics are only available on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_disk(_root: &PathBuf, _builder: &mut MetricsBuilder) -> crate::Result<()> {
+    Err("disk statistics are only available on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_memory(_root: &PathBuf, _builder: &mut MetricsBuilder) -> crate::Result<()> {
+    Err("memory statistics are only available on Linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn collect_network(_root: &PathBuf, _builder: &mut MetricsBuilder) -> crate::Result<()> {
+    Err("network statistics are only available on Linux".into())
+}
+
+struct Mount {
+    device: String,
+    mountpoint: String,
+    filesystem: String,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_mounts(input: &str) -> Vec<Mount> {
+    input
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_ascii_whitespace();
+            Some(Mount {
+                device: parts.next()?.into(),
+                mountpoint: parts.next()?.into(),
+                filesystem: parts.next()?.into(),
+            })
+        })
+        .collect()
+}
+
+const CPU_MODES: [&str; 8] = [
+    "user", "nice", "system", "idle", "iowait", "irq", "softirq", "steal",
+];
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_cpu(input: &str, ticks_per_second: f64, builder: &mut MetricsBuilder) {
+    for line in input.lines() {
+        let mut parts = line.split_ascii_whitespace();
+        let cpu = match parts.next() {
+            // The aggregated "cpu" line is skipped, it is the sum of the cores
+            Some(name) if name.starts_with("cpu") && name.len() > 3 => &name[3..],
+            _ => continue,
+        };
+        for (mode, ticks) in CPU_MODES.iter().zip(parts) {
+            if let Ok(ticks) = ticks.parse::<f64>() {
+                builder.counter(
+                    "cpu_seconds_total",
+                    ticks / ticks_per_second,
+                    &[("cpu", cpu), ("mode", *mode)],
+                );
+            }
+        }
+    }
+}
+
+const SECTOR_SIZE: f64 = 512.0;
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_diskstats(input: &str, builder: &mut MetricsBuilder) {
+    for line in input.lines() {
+        let parts = line.split_ascii_whitespace().collect::<Vec<_>>();
+        if parts.len() < 14 {
+            continue;
+        }
+        let device = parts[2];
+        let field = |index: usize| parts[index].parse::<f64>().unwrap_or(0.0);
+        let tags = [("device", device)];
+
+        builder.counter("disk_reads_completed_total", field(3), &tags);
+        builder.counter("disk_read_bytes_total", field(5) * SECTOR_SIZE, &tags);
+        builder.counter("disk_writes_completed_total", field(7), &tags);
+        builder.counter("disk_written_bytes_total", field(9) * SECTOR_SIZE, &tags);
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_meminfo(input: &str, builder: &mut MetricsBuilder) {
+    let mut fields = BTreeMap::new();
+    for line in input.lines() {
+        let mut parts = line.split_ascii_whitespace();
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            if let Ok(value) = value.parse::<f64>() {
+                let multiplier = match parts.next() {
+                    Some("kB") => 1024.0,
+                    _ => 1.0,
+                };
+                fields.insert(key.trim_end_matches(':'), value * multiplier);
+            }
+        }
+    }
+
+    let names = [
+        ("MemTotal", "memory_total_bytes"),
+        ("MemFree", "memory_free_bytes"),
+        ("MemAvailable", "memory_available_bytes"),
+        ("Buffers", "memory_buffers_bytes"),
+        ("Cached", "memory_cached_bytes"),
+        ("SwapTotal", "memory_swap_total_bytes"),
+        ("SwapFree", "memory_swap_free_bytes"),
+    ];
+    for (field, name) in names.iter() {
+        if let Some(value) = fields.get(field) {
+            builder.gauge(name, *value, &[]);
+        }
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_net_dev(input: &str, builder: &mut MetricsBuilder) {
+    // The first two lines are column headers
+    for line in input.lines().skip(2) {
+        let mut halves = line.splitn(2, ':');
+        let (device, stats) = match (halves.next(), halves.next()) {
+            (Some(device), Some(stats)) => (device.trim(), stats),
+            _ => continue,
+        };
+        let parts = stats.split_ascii_whitespace().collect::<Vec<_>>();
+        if parts.len() < 16 {
+            continue;
+        }
+        let field = |index: usize| parts[index].parse::<f64>().unwrap_or(0.0);
+        let tags = [("device", device)];
+
+        builder.counter("network_receive_bytes_total", field(0), &tags);
+        builder.counter("network_receive_packets_total", field(1), &tags);
+        builder.counter("network_receive_errs_total", field(2), &tags);
+        builder.counter("network_transmit_bytes_total", field(8), &tags);
+        builder.counter("network_transmit_packets_total", field(9), &tags);
+        builder.counter("network_transmit_errs_total", field(10), &tags);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::metric::MetricValue;
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_cpu_stat() {
+        let no_tags = BTreeMap::new();
+        let mut builder = MetricsBuilder::new(Some("host"), &no_tags);
+        parse_cpu(
+            "cpu  200 0 100 1000 0 0 0 0 0 0\ncpu0 100 0 50 500 0 0 0 0 0 0\ncpu1 100 0 50 500 0 0 0 0 0 0\nintr 12345\n",
+            100.0,
+            &mut builder,
+        );
+
+        assert_eq!(builder.metrics.len(), 2 * CPU_MODES.len());
+        let user = &builder.metrics[0];
+        assert_eq!(user.name, "host_cpu_seconds_total");
+        assert_eq!(user.value, MetricValue::Counter { value: 1.0 });
+        assert_eq!(user.tags, Some(tags(&[("cpu", "0"), ("mode", "user")])));
+    }
+
+    #[test]
+    fn parses_meminfo() {
+        let no_tags = BTreeMap::new();
+        let mut builder = MetricsBuilder::new(None, &no_tags);
+        parse_meminfo(
+            "MemTotal:       16384 kB\nMemFree:         1024 kB\nHugePages_Total:       0\n",
+            &mut builder,
+        );
+
+        assert_eq!(builder.metrics.len(), 2);
+        assert_eq!(builder.metrics[0].name, "memory_total_bytes");
+        assert_eq!(
+            builder.metrics[0].value,
+            MetricValue::Gauge {
+                value: 16384.0 * 1024.0
+            }
+        );
+    }
+
+    #[test]
+    fn parses_diskstats() {
+        let no_tags = BTreeMap::new();
+        let mut builder = MetricsBuilder::new(None, &no_tags);
+        parse_diskstats(
+            "   8       0 sda 100 0 2000 10 50 0 4000 20 0 30 30 0 0 0 0\n",
+            &mut builder,
+        );
+
+        let read = builder
+            .metrics
+            .iter()
+            .find(|m| m.name == "disk_read_bytes_total")
+            .unwrap();
+        assert_eq!(
+            read.value,
+            MetricValue::Counter {
+                value: 2000.0 * 512.0
+            }
+        );
+        assert_eq!(read.tags, Some(tags(&[("device", "sda")])));
+    }
+
+    #[test]
+    fn parses_net_dev() {
+        let no_tags = BTreeMap::new();
+        let mut builder = MetricsBuilder::new(None, &no_tags);
+        parse_net_dev(
+            "Inter-|   Receive                                                |  Transmit\n \
+             face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n  \
+             eth0: 1000 10 0 0 0 0 0 0 2000 20 1 0 0 0 0 0\n",
+            &mut builder,
+        );
+
+        assert_eq!(builder.metrics.len(), 6);
+        let transmit = builder
+            .metrics
+            .iter()
+            .find(|m| m.name == "network_transmit_bytes_total")
+            .unwrap();
+        assert_eq!(transmit.value, MetricValue::Counter { value: 2000.0 });
+        assert_eq!(transmit.tags, Some(tags(&[("device", "eth0")])));
+    }
+
+    #[test]
+    fn skips_unsupported_collectors() {
+        let config = HostMetricsConfig {
+            collectors: vec![Collector::Load, Collector::Cpu],
+            ..Default::default()
+        };
+        let (tx, _rx) = mpsc::channel(1);
+        assert!(config
+            .build(
+                "host_metrics",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .is_ok());
+
+        let expected = if cfg!(target_os = "linux") {
+            vec![Collector::Load, Collector::Cpu]
+        } else {
+            vec![Collector::Load]
+        };
+        assert_eq!(config.supported_collectors(), expected);
+        assert!(default_collectors().iter().all(|c| c.is_supported()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn collects_linux_host_metrics() {
+        let config = HostMetricsConfig::default();
+        let no_tags = BTreeMap::new();
+        let mut builder = MetricsBuilder::new(config.namespace.as_deref(), &no_tags);
+        for collector in &config.collectors {
+            config.collect(*collector, &mut builder).unwrap();
+        }
+
+        let names = builder
+            .metrics
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<std::collections::HashSet<_>>();
+        for name in &[
+            "host_cpu_seconds_total",
+            "host_memory_total_bytes",
+            "host_load1",
+            "host_load5",
+            "host_load15",
+            "host_network_receive_bytes_total",
+        ] {
+            assert!(names.contains(name), "missing metric {}", name);
+        }
+
+        assert!(builder
+            .metrics
+            .iter()
+            .filter(|m| m.name == "host_cpu_seconds_total")
+            .all(|m| m.tags.as_ref().unwrap().contains_key("cpu")
+                && m.tags.as_ref().unwrap().contains_key("mode")));
+        assert!(builder
+            .metrics
+            .iter()
+            .filter(|m| m.name.starts_with("host_network_"))
+            .all(|m| m.tags.as_ref().unwrap().contains_key("device")));
+    }
+}
@@ -1,20 +1,27 @@
 use crate::{
-    event::{self, Event},
+    event::{self, metadata, Event},
     shutdown::ShutdownSignal,
-    sources::util::{ErrorMessage, HttpSource},
+    sources::util::{ErrorMessage, HttpSource, HttpSourceAuthConfig},
     tls::TlsConfig,
     topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
 };
 use bytes::{Buf, Bytes, BytesMut};
 use chrono::Utc;
 use codec::{self, BytesDelimitedCodec};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
 use futures01::sync::mpsc;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use std::net::SocketAddr;
+use std::{
+    io::{self, Read},
+    net::SocketAddr,
+};
 use tokio_codec::Decoder;
 use warp::filters::body::FullBody;
-use warp::http::{HeaderMap, HeaderValue, StatusCode};
+use warp::http::{
+    header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    HeaderMap, HeaderValue, StatusCode,
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SimpleHttpConfig {
@@ -24,6 +31,13 @@ pub struct SimpleHttpConfig {
     #[serde(default)]
     headers: Vec<String>,
     tls: Option<TlsConfig>,
+    auth: Option<HttpSourceAuthConfig>,
+    #[serde(default = "default_max_body_size")]
+    max_body_size: usize,
+}
+
+fn default_max_body_size() -> usize {
+    10 * 1024 * 1024 // 10MiB
 }
 
 inventory::submit! {
@@ -34,6 +48,7 @@ inventory::submit! {
 struct SimpleHttpSource {
     encoding: Encoding,
     headers: Vec<String>,
+    max_body_size: usize,
 }
 
 #[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Clone, Derivative, Copy)]
@@ -52,6 +67,8 @@ impl HttpSource for SimpleHttpSource {
         body: FullBody,
         header_map: HeaderMap,
     ) -> Result<Vec<Event>, ErrorMessage> {
+        check_content_length(&header_map, self.max_body_size)?;
+        let body = decompress_body(body, &header_map, self.max_body_size)?;
         decode_body(body, self.encoding)
             .map(|events| add_headers(events, &self.headers, header_map))
     }
@@ -69,8 +86,9 @@ impl SourceConfig for SimpleHttpConfig {
         let source = SimpleHttpSource {
             encoding: self.encoding,
             headers: self.headers.clone(),
+            max_body_size: self.max_body_size,
         };
-        source.run(self.address, "", &self.tls, out)
+        source.run(self.address, "", &self.tls, &self.auth, out)
     }
 
     fn output_type(&self) -> DataType {
@@ -100,6 +118,71 @@ fn add_headers(
     events
 }
 
+fn check_content_length(headers: &HeaderMap, max_body_size: usize) -> Result<(), ErrorMessage> {
+    let length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    match length {
+        Some(length) if length > max_body_size => Err(body_too_large(max_body_size)),
+        _ => Ok(()),
+    }
+}
+
+fn decompress_body(
+    body: FullBody,
+    headers: &HeaderMap,
+    max_body_size: usize,
+) -> Result<BytesMut, ErrorMessage> {
+    let encoding = headers
+        .get(CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap_or("").trim().to_ascii_lowercase());
+
+    let body = match encoding.as_ref().map(String::as_str) {
+        None | Some("") | Some("identity") => body.collect::<BytesMut>(),
+        Some("gzip") => read_limited(MultiGzDecoder::new(body.reader()), max_body_size)?,
+        Some("deflate") => read_limited(DeflateDecoder::new(body.reader()), max_body_size)?,
+        Some(other) => {
+            return Err(ErrorMessage::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported encoding {}", other),
+            ))
+        }
+    };
+
+    if body.len() > max_body_size {
+        Err(body_too_large(max_body_size))
+    } else {
+        Ok(body)
+    }
+}
+
+/// Reads at most one byte past `max_body_size` so oversized payloads are
+/// detected without fully inflating them.
+fn read_limited(reader: impl Read, max_body_size: usize) -> Result<BytesMut, ErrorMessage> {
+    let mut decoded = Vec::new();
+    reader
+        .take(max_body_size as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|error: io::Error| {
+            ErrorMessage::new(
+                StatusCode::BAD_REQUEST,
+                format!("Failed decompressing payload: {}", error),
+            )
+        })?;
+    Ok(decoded.into())
+}
+
+fn body_too_large(max_body_size: usize) -> ErrorMessage {
+    ErrorMessage::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!(
+            "Payload exceeds the maximum size of {} bytes",
+            max_body_size
+        ),
+    )
+}
+
 fn body_to_lines(mut body: BytesMut) -> impl Iterator<Item = Result<Bytes, ErrorMessage>> {
     let mut decoder = BytesDelimitedCodec::new(b'\n');
     std::iter::from_fn(move || {
@@ -119,18 +202,22 @@ fn body_to_lines(mut body: BytesMut) -> impl Iterator<Item = Result<Bytes, Error
     })
 }
 
-fn decode_body(buf: FullBody, enc: Encoding) -> Result<Vec<Event>, ErrorMessage> {
-    let body = buf.collect::<BytesMut>();
-
+fn decode_body(body: BytesMut, enc: Encoding) -> Result<Vec<Event>, ErrorMessage> {
     match enc {
         Encoding::Text => body_to_lines(body)
             .map(|r| Ok(Event::from(r?)))
             .collect::<Result<_, _>>(),
         Encoding::Ndjson => body_to_lines(body)
             .map(|j| {
-                let parsed_json = serde_json::from_slice(&j?)
+                let j = j?;
+                let parsed_json = serde_json::from_slice(&j)
                     .map_err(|e| json_error(format!("Error parsing Ndjson: {:?}", e)))?;
-                json_parse_object(parsed_json)
+                let mut event = json_parse_object(parsed_json)?;
+                event
+                    .as_mut_log()
+                    .metadata_mut()
+                    .insert(metadata::RECEIVED_BYTES, j.len() as i64);
+                Ok(event)
             })
             .collect::<Result<_, _>>(),
         Encoding::Json => {
@@ -194,6 +281,12 @@ fn json_value_to_type_string(value: &JsonValue) -> &'static str {
 #[cfg(test)]
 mod tests {
     use super::{Encoding, SimpleHttpConfig};
+    use crate::sources::util::HttpSourceAuthConfig;
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder},
+        Compression,
+    };
+    use std::io::Write;
     use warp::http::HeaderMap;
 
     use crate::shutdown::ShutdownSignal;
@@ -213,6 +306,16 @@ mod tests {
         rt: &mut Runtime,
         encoding: Encoding,
         headers: Vec<String>,
+    ) -> (mpsc::Receiver<Event>, SocketAddr) {
+        source_with(rt, encoding, headers, None, super::default_max_body_size())
+    }
+
+    fn source_with(
+        rt: &mut Runtime,
+        encoding: Encoding,
+        headers: Vec<String>,
+        auth: Option<HttpSourceAuthConfig>,
+        max_body_size: usize,
     ) -> (mpsc::Receiver<Event>, SocketAddr) {
         test_util::trace_init();
         let (sender, recv) = mpsc::channel(100);
@@ -223,6 +326,8 @@ mod tests {
                 encoding,
                 headers,
                 tls: None,
+                auth,
+                max_body_size,
             }
             .build(
                 "default",
@@ -246,10 +351,14 @@ mod tests {
     }
 
     fn send_with_headers(address: SocketAddr, body: &str, headers: HeaderMap) -> u16 {
+        send_bytes(address, body.as_bytes().to_vec(), headers)
+    }
+
+    fn send_bytes(address: SocketAddr, body: Vec<u8>, headers: HeaderMap) -> u16 {
         reqwest::Client::new()
             .request(Method::POST, &format!("http://{}/", address))
             .headers(headers)
-            .body(body.to_owned())
+            .body(body)
             .send()
             .unwrap()
             .status()
@@ -421,4 +530,123 @@ mod tests {
             assert!(log.get(&event::log_schema().timestamp_key()).is_some());
         }
     }
+
+    #[test]
+    fn http_gzip_deflate() {
+        let body = "test body";
+
+        let mut rt = test_util::runtime();
+        let (rx, addr) = source(&mut rt, Encoding::Text, vec![]);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Encoding", "gzip".parse().unwrap());
+        assert_eq!(200, send_bytes(addr, encoder.finish().unwrap(), headers));
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Encoding", "deflate".parse().unwrap());
+        assert_eq!(200, send_bytes(addr, encoder.finish().unwrap(), headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Encoding", "br".parse().unwrap());
+        assert_eq!(415, send_with_headers(addr, body, headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Encoding", "gzip".parse().unwrap());
+        assert_eq!(400, send_with_headers(addr, body, headers)); //not gzipped
+
+        let events = rt.block_on(collect_n(rx, 2)).unwrap();
+        for event in events {
+            assert_eq!(
+                event.as_log()[&event::log_schema().message_key()],
+                body.into()
+            );
+        }
+    }
+
+    #[test]
+    fn http_max_body_size() {
+        let mut rt = test_util::runtime();
+        let (rx, addr) = source_with(&mut rt, Encoding::Text, vec![], None, 64);
+
+        assert_eq!(413, send(addr, &"over the limit ".repeat(10)));
+
+        // compressed payload is under the limit, but inflates past it
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'a'; 1024]).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Encoding", "gzip".parse().unwrap());
+        assert_eq!(413, send_bytes(addr, encoder.finish().unwrap(), headers));
+
+        assert_eq!(200, send(addr, "small body"));
+
+        let mut events = rt.block_on(collect_n(rx, 1)).unwrap();
+        assert_eq!(
+            events.remove(0).as_log()[&event::log_schema().message_key()],
+            "small body".into()
+        );
+    }
+
+    #[test]
+    fn http_basic_auth() {
+        let mut rt = test_util::runtime();
+        let (rx, addr) = source_with(
+            &mut rt,
+            Encoding::Text,
+            vec![],
+            Some(HttpSourceAuthConfig::Basic {
+                user: "user".to_string(),
+                password: "pass".to_string(),
+            }),
+            super::default_max_body_size(),
+        );
+
+        assert_eq!(401, send(addr, "no auth"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Basic dXNlcjp3cm9uZw==".parse().unwrap()); // user:wrong
+        assert_eq!(401, send_with_headers(addr, "wrong auth", headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Basic dXNlcjpwYXNz".parse().unwrap()); // user:pass
+        assert_eq!(200, send_with_headers(addr, "good auth", headers));
+
+        let mut events = rt.block_on(collect_n(rx, 1)).unwrap();
+        assert_eq!(
+            events.remove(0).as_log()[&event::log_schema().message_key()],
+            "good auth".into()
+        );
+    }
+
+    #[test]
+    fn http_bearer_auth() {
+        let mut rt = test_util::runtime();
+        let (rx, addr) = source_with(
+            &mut rt,
+            Encoding::Text,
+            vec![],
+            Some(HttpSourceAuthConfig::Bearer {
+                token: "s3cr3t".to_string(),
+            }),
+            super::default_max_body_size(),
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Bearer nope".parse().unwrap());
+        assert_eq!(401, send_with_headers(addr, "wrong token", headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Bearer s3cr3t".parse().unwrap());
+        assert_eq!(200, send_with_headers(addr, "good token", headers));
+
+        let mut events = rt.block_on(collect_n(rx, 1)).unwrap();
+        assert_eq!(
+            events.remove(0).as_log()[&event::log_schema().message_key()],
+            "good token".into()
+        );
+    }
 }
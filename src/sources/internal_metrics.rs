@@ -92,10 +92,17 @@ fn into_event(key: Key, measurement: Measurement) -> Event {
         Measurement::Counter(v) => MetricValue::Counter { value: v as f64 },
         Measurement::Gauge(v) => MetricValue::Gauge { value: v as f64 },
         Measurement::Histogram(packed) => {
+            // `timing!` records nanoseconds, so histograms named in seconds
+            // are scaled to match.
+            let scale = if key.name().ends_with("_seconds") {
+                1e-9
+            } else {
+                1.0
+            };
             let values = packed
                 .decompress()
                 .into_iter()
-                .map(|i| i as f64)
+                .map(|i| i as f64 * scale)
                 .collect::<Vec<_>>();
             let sample_rates = vec![1; values.len()];
             MetricValue::Distribution {
@@ -134,7 +141,7 @@ mod tests {
 
     #[test]
     fn captures_internal_metrics() {
-        crate::metrics::init().unwrap();
+        crate::metrics::init_test();
 
         let controller = get_controller().expect("no controller");
 
@@ -144,6 +151,7 @@ mod tests {
         counter!("bar", 4);
         timing!("baz", 5);
         timing!("baz", 6);
+        timing!("qux_seconds", 1_500_000_000);
         value!("quux", 7, "host" => "foo");
         value!("quux", 8, "host" => "foo");
 
@@ -163,6 +171,13 @@ mod tests {
             },
             output["baz"].value
         );
+        assert_eq!(
+            MetricValue::Distribution {
+                values: vec![1.5],
+                sample_rates: vec![1]
+            },
+            output["qux_seconds"].value
+        );
         assert_eq!(
             MetricValue::Distribution {
                 values: vec![7.0, 8.0],
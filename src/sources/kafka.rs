@@ -1,7 +1,8 @@
 use crate::{
     event::Event,
-    kafka::{KafkaCompression, KafkaTlsConfig},
+    kafka::{self, KafkaCompression, KafkaSaslConfig, KafkaTlsConfig},
     shutdown::ShutdownSignal,
+    sources::util::invalid_bytes::InvalidBytes,
     topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
 };
 use bytes::Bytes;
@@ -45,7 +46,10 @@ pub struct KafkaSourceConfig {
     commit_interval_ms: u64,
     host_key: Option<String>,
     key_field: Option<String>,
+    #[serde(default)]
+    invalid_bytes: InvalidBytes,
     librdkafka_options: Option<HashMap<String, String>>,
+    sasl: Option<KafkaSaslConfig>,
     tls: Option<KafkaTlsConfig>,
 }
 
@@ -122,9 +126,9 @@ fn kafka_source(
                             }
                             Some(Ok(payload)) => Bytes::from(payload),
                         };
-                        let mut event = Event::from(payload);
+                        let mut event = config.invalid_bytes.event(payload, "kafka");
 
-                        if let Some(key_field) = &config.key_field {
+                        if let (Some(event), Some(key_field)) = (&mut event, &config.key_field) {
                             match msg.key_view::<[u8]>() {
                                 None => (),
                                 Some(Err(e)) => {
@@ -135,6 +139,7 @@ fn kafka_source(
                                 }
                             }
                         }
+                        // Dropped messages are done with as well.
                         consumer_ref.store_offset(&msg).map_err(
                             |e| error!(message = "Cannot store offset for the message", error = ?e),
                         )?;
@@ -142,6 +147,7 @@ fn kafka_source(
                     }
                 }
             })
+            .filter_map(|event| event)
             .forward(out.sink_map_err(|e| error!(message = "Error sending to sink", error = ?e)))
             .map(|_| ())
     });
@@ -167,15 +173,12 @@ fn create_consumer(config: KafkaSourceConfig) -> crate::Result<StreamConsumer> {
         .set("enable.auto.offset.store", "false")
         .set("client.id", "vector");
 
-    if let Some(tls) = &config.tls {
-        tls.apply(&mut client_config)?;
-    }
-
-    if let Some(librdkafka_options) = config.librdkafka_options {
-        for (key, value) in librdkafka_options.into_iter() {
-            client_config.set(key.as_str(), value.as_str());
-        }
-    }
+    kafka::apply_client_options(
+        &mut client_config,
+        config.sasl.as_ref(),
+        config.tls.as_ref(),
+        config.librdkafka_options.as_ref(),
+    )?;
 
     let consumer: StreamConsumer = client_config.create().context(KafkaCreateError)?;
     let topics: Vec<&str> = config.topics.iter().map(|s| s.as_str()).collect();
@@ -46,7 +46,7 @@ impl SourceConfig for LogplexConfig {
         out: mpsc::Sender<Event>,
     ) -> crate::Result<super::Source> {
         let source = LogplexSource::default();
-        source.run(self.address, "events", &self.tls, out)
+        source.run(self.address, "events", &self.tls, &None, out)
     }
 
     fn output_type(&self) -> DataType {
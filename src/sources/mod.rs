@@ -1,10 +1,24 @@
 use futures01::Future;
 use snafu::Snafu;
 
+#[cfg(feature = "sources-amqp")]
+pub mod amqp;
+#[cfg(feature = "sources-apache_metrics")]
+pub mod apache_metrics;
+#[cfg(feature = "sources-aws_cloudwatch_logs_subscription")]
+pub mod aws_cloudwatch_logs_subscription;
+#[cfg(feature = "sources-aws_s3")]
+pub mod aws_s3;
+#[cfg(feature = "sources-demo_logs")]
+pub mod demo_logs;
 #[cfg(feature = "sources-docker")]
 pub mod docker;
+#[cfg(feature = "sources-exec")]
+pub mod exec;
 #[cfg(feature = "sources-file")]
 pub mod file;
+#[cfg(feature = "sources-host_metrics")]
+pub mod host_metrics;
 #[cfg(feature = "sources-http")]
 pub mod http;
 #[cfg(feature = "sources-internal_metrics")]
@@ -17,6 +31,18 @@ pub mod kafka;
 pub mod kubernetes;
 #[cfg(feature = "sources-logplex")]
 pub mod logplex;
+#[cfg(feature = "sources-mongodb_metrics")]
+pub mod mongodb_metrics;
+#[cfg(feature = "sources-mqtt")]
+pub mod mqtt;
+#[cfg(feature = "sources-mysql_metrics")]
+pub mod mysql_metrics;
+#[cfg(feature = "sources-nginx_metrics")]
+pub mod nginx_metrics;
+#[cfg(feature = "sources-opentelemetry")]
+pub mod opentelemetry;
+#[cfg(feature = "sources-postgresql_metrics")]
+pub mod postgresql_metrics;
 #[cfg(feature = "sources-prometheus")]
 pub mod prometheus;
 #[cfg(feature = "sources-socket")]
@@ -0,0 +1,252 @@
+use crate::{
+    event::Event,
+    internal_events::{MqttConnectionFailed, MqttEventReceived, MqttSubscribeFailed},
+    mqtt::{MqttConfig, MqttQos},
+    shutdown::ShutdownSignal,
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+};
+use bytes::Bytes;
+use futures::{compat::Future01CompatExt, FutureExt, TryFutureExt};
+use futures01::{sync::mpsc, Sink};
+use rumqttc::{AsyncClient, Event as MqttEvent, Incoming, Publish};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Derivative, Deserialize, Serialize)]
+#[derivative(Default)]
+pub struct MqttSourceConfig {
+    #[serde(flatten)]
+    connection: MqttConfig,
+    topics: Vec<String>,
+    #[serde(default)]
+    qos: MqttQos,
+    #[derivative(Default(value = "default_topic_key()"))]
+    #[serde(default = "default_topic_key")]
+    topic_key: String,
+}
+
+fn default_topic_key() -> String {
+    "topic".into()
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<MqttSourceConfig>("mqtt")
+}
+
+#[typetag::serde(name = "mqtt")]
+impl SourceConfig for MqttSourceConfig {
+    fn build(
+        &self,
+        _name: &str,
+        _globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        if self.topics.is_empty() {
+            return Err("At least one topic filter must be configured.".into());
+        }
+        Ok(Box::new(run(self.clone(), shutdown, out).boxed().compat()))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn source_type(&self) -> &'static str {
+        "mqtt"
+    }
+}
+
+async fn run(
+    config: MqttSourceConfig,
+    shutdown: ShutdownSignal,
+    mut out: mpsc::Sender<Event>,
+) -> Result<(), ()> {
+    let mut shutdown = shutdown.compat();
+    let reconnect = Duration::from_secs(config.connection.reconnect_secs);
+
+    // Subscriptions are sent through the request channel, so it has to be
+    // able to hold all of them without the event loop being polled.
+    let (client, mut eventloop) =
+        AsyncClient::new(config.connection.options(), config.topics.len().max(10));
+
+    loop {
+        let event = tokio::select! {
+            event = eventloop.poll() => event,
+            _ = &mut shutdown => break,
+        };
+
+        match event {
+            // The broker only remembers our subscriptions for persistent
+            // sessions, in every other case they are sent again after
+            // (re)connecting.
+            Ok(MqttEvent::Incoming(Incoming::ConnAck(connack))) if !connack.session_present => {
+                for topic in &config.topics {
+                    if let Err(error) = client.subscribe(topic, config.qos.into()).await {
+                        emit!(MqttSubscribeFailed { topic, error });
+                    }
+                }
+            }
+            // QoS 1 and 2 publishes are acknowledged by the event loop as
+            // they are read. Since it isn't polled again until the event is
+            // in the pipeline, at most one delivery is acked ahead of it.
+            Ok(MqttEvent::Incoming(Incoming::Publish(publish))) => {
+                let event = create_event(&config, publish);
+                out = match out.send(event).compat().await {
+                    Ok(out) => out,
+                    Err(_) => {
+                        error!(message = "Failed to forward events, downstream is closed.");
+                        break;
+                    }
+                };
+            }
+            Ok(_) => {}
+            Err(error) => {
+                emit!(MqttConnectionFailed { error });
+                // The next poll reconnects.
+                tokio::select! {
+                    _ = tokio::time::delay_for(reconnect) => {},
+                    _ = &mut shutdown => break,
+                }
+            }
+        }
+    }
+
+    let _ = client.disconnect().await;
+    Ok(())
+}
+
+fn create_event(config: &MqttSourceConfig, publish: Publish) -> Event {
+    emit!(MqttEventReceived {
+        byte_size: publish.payload.len()
+    });
+
+    let mut event = Event::from(Bytes::from(&publish.payload[..]));
+    event
+        .as_mut_log()
+        .insert(config.topic_key.clone(), publish.topic);
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event;
+    use rumqttc::QoS;
+
+    #[test]
+    fn parse_config() {
+        let config: MqttSourceConfig = toml::from_str(
+            r#"
+            host = "broker"
+            topics = ["sensors/+/temperature", "logs/#"]
+            qos = "exactly_once"
+            clean_session = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.connection.host, "broker");
+        assert_eq!(config.connection.port, 1883);
+        assert_eq!(config.connection.client_id, "vector");
+        assert!(!config.connection.clean_session);
+        assert_eq!(config.qos, MqttQos::ExactlyOnce);
+        assert_eq!(config.topic_key, "topic");
+    }
+
+    #[test]
+    fn build_requires_topics() {
+        let (tx, _rx) = mpsc::channel(1);
+        let config = MqttSourceConfig::default();
+        assert!(config
+            .build(
+                "mqtt",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn publish_to_event() {
+        let config = MqttSourceConfig {
+            topic_key: "mqtt_topic".into(),
+            ..Default::default()
+        };
+        let publish = Publish::new("sensors/kitchen", QoS::AtLeastOnce, "21.5");
+        let event = create_event(&config, publish);
+        let log = event.as_log();
+
+        assert_eq!(log[&event::log_schema().message_key()], "21.5".into());
+        assert_eq!(log[&"mqtt_topic".into()], "sensors/kitchen".into());
+    }
+}
+
+#[cfg(feature = "mqtt-integration-tests")]
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::{
+        event,
+        test_util::{collect_n, random_lines, random_string, runtime},
+    };
+    use rumqttc::QoS;
+
+    #[test]
+    fn mqtt_subscribe_and_receive() {
+        let mut rt = runtime();
+        let prefix = format!("test-{}", random_string(10));
+        let config = MqttSourceConfig {
+            connection: MqttConfig {
+                client_id: format!("{}-source", prefix),
+                ..Default::default()
+            },
+            topics: vec![format!("{}/+/logs", prefix)],
+            ..Default::default()
+        };
+
+        let (tx, rx) = mpsc::channel(10);
+        let source = config
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        rt.spawn(source);
+
+        let lines: Vec<String> = random_lines(100).take(10).collect();
+        let topic = format!("{}/app/logs", prefix);
+        rt.block_on_std({
+            let lines = lines.clone();
+            let topic = topic.clone();
+            let mut options = MqttConfig::default();
+            options.client_id = format!("{}-publisher", prefix);
+            async move {
+                // Give the source time to subscribe.
+                tokio::time::delay_for(Duration::from_secs(1)).await;
+                let (client, mut eventloop) = AsyncClient::new(options.options(), 10);
+                for line in lines {
+                    client
+                        .publish(&topic, QoS::AtLeastOnce, false, line)
+                        .await
+                        .unwrap();
+                }
+                client.disconnect().await.unwrap();
+                while eventloop.poll().await.is_ok() {}
+            }
+        });
+
+        let events = rt.block_on(collect_n(rx, lines.len())).unwrap();
+        for (event, line) in events.iter().zip(lines.iter()) {
+            let log = event.as_log();
+            assert_eq!(
+                log[&event::log_schema().message_key()],
+                line.as_str().into()
+            );
+            assert_eq!(log[&"topic".into()], topic.as_str().into());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    shutdown::ShutdownSignal,
+    sources::util::http_scrape::{parse_status_page, scrape},
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+    Event,
+};
+use chrono::Utc;
+use futures01::sync::mpsc;
+use http::Uri;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::time::Duration;
+
+mod parser;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct NginxMetricsConfig {
+    endpoints: Vec<String>,
+    #[serde(default = "default_scrape_interval_secs")]
+    scrape_interval_secs: u64,
+    #[serde(default = "default_namespace")]
+    namespace: String,
+}
+
+pub fn default_scrape_interval_secs() -> u64 {
+    15
+}
+
+pub fn default_namespace() -> String {
+    "nginx".to_string()
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<NginxMetricsConfig>("nginx_metrics")
+}
+
+#[typetag::serde(name = "nginx_metrics")]
+impl SourceConfig for NginxMetricsConfig {
+    fn build(
+        &self,
+        _name: &str,
+        _globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        let urls = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.parse::<Uri>().context(super::UriParseError))
+            .collect::<Result<Vec<_>, _>>()?;
+        let namespace = self.namespace.clone();
+        let interval = Duration::from_secs(self.scrape_interval_secs);
+
+        Ok(scrape(
+            urls,
+            interval,
+            interval,
+            "nginx_metrics",
+            move |url, body| {
+                parse_status_page("nginx_metrics", url, body, |packet, tags| {
+                    parser::parse(packet, &namespace, Utc::now(), tags)
+                })
+            },
+            out,
+            shutdown,
+        ))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn source_type(&self) -> &'static str {
+        "nginx_metrics"
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{event::metric::Metric, sources::util::MetricsBuilder};
+use chrono::{DateTime, Utc};
+use snafu::Snafu;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Snafu, PartialEq)]
+pub enum ParseError {
+    #[snafu(display("missing or malformed field {:?}", field))]
+    MissingField { field: &'static str },
+}
+
+/// Parses the `ngx_http_stub_status_module` page, which looks like
+///
+/// ```text
+/// Active connections: 291
+/// server accepts handled requests
+///  16630948 16630948 31070465
+/// Reading: 6 Writing: 179 Waiting: 106
+/// ```
+///
+/// Whitespace is treated loosely since it varies between versions and
+/// proxies in front of nginx.
+pub fn parse(
+    packet: &str,
+    namespace: &str,
+    now: DateTime<Utc>,
+    tags: &BTreeMap<String, String>,
+) -> Result<Vec<Metric>, ParseError> {
+    let tokens = packet.split_ascii_whitespace().collect::<Vec<_>>();
+
+    let after = |label: &str, offset: usize, field: &'static str| {
+        tokens
+            .iter()
+            .position(|token| *token == label)
+            .and_then(|index| tokens.get(index + offset))
+            .and_then(|value| value.parse::<f64>().ok())
+            .ok_or(ParseError::MissingField { field })
+    };
+
+    let active = after("connections:", 1, "active")?;
+    // The three totals follow the `server accepts handled requests` header
+    let accepts = after("requests", 1, "accepts")?;
+    let handled = after("requests", 2, "handled")?;
+    let requests = after("requests", 3, "requests")?;
+    let reading = after("Reading:", 1, "reading")?;
+    let writing = after("Writing:", 1, "writing")?;
+    let waiting = after("Waiting:", 1, "waiting")?;
+
+    let mut builder = MetricsBuilder::at(Some(namespace), tags, now);
+    builder.gauge("connections_active", active, &[]);
+    builder.counter("connections_accepted_total", accepts, &[]);
+    builder.counter("connections_handled_total", handled, &[]);
+    builder.counter("http_requests_total", requests, &[]);
+    builder.gauge("connections_reading", reading, &[]);
+    builder.gauge("connections_writing", writing, &[]);
+    builder.gauge("connections_waiting", waiting, &[]);
+
+    Ok(builder.metrics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::metric::MetricValue;
+    use pretty_assertions::assert_eq;
+
+    fn server_tags() -> BTreeMap<String, String> {
+        vec![("server".to_owned(), "localhost:8000".to_owned())]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn parses_stub_status() {
+        let status = "Active connections: 291 \nserver accepts handled requests\n 16630948 16630948 31070465 \nReading: 6 Writing: 179 Waiting: 106 \n";
+        let now = Utc::now();
+        let metrics = parse(status, "nginx", now, &server_tags()).unwrap();
+
+        let values = metrics
+            .iter()
+            .map(|m| (m.name.as_str(), m.value.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                (
+                    "nginx_connections_active",
+                    MetricValue::Gauge { value: 291.0 }
+                ),
+                (
+                    "nginx_connections_accepted_total",
+                    MetricValue::Counter { value: 16630948.0 }
+                ),
+                (
+                    "nginx_connections_handled_total",
+                    MetricValue::Counter { value: 16630948.0 }
+                ),
+                (
+                    "nginx_http_requests_total",
+                    MetricValue::Counter { value: 31070465.0 }
+                ),
+                (
+                    "nginx_connections_reading",
+                    MetricValue::Gauge { value: 6.0 }
+                ),
+                (
+                    "nginx_connections_writing",
+                    MetricValue::Gauge { value: 179.0 }
+                ),
+                (
+                    "nginx_connections_waiting",
+                    MetricValue::Gauge { value: 106.0 }
+                ),
+            ]
+        );
+        assert!(metrics
+            .iter()
+            .all(|m| m.timestamp == Some(now)
+                && m.tags.as_ref().unwrap()["server"] == "localhost:8000"));
+    }
+
+    #[test]
+    fn parses_compact_stub_status() {
+        // Some builds and proxies collapse the line breaks
+        let status = "Active connections: 1 server accepts handled requests 2 3 4 Reading: 0 Writing: 1 Waiting: 0";
+        let metrics = parse(status, "nginx", Utc::now(), &server_tags()).unwrap();
+
+        assert_eq!(metrics[3].value, MetricValue::Counter { value: 4.0 });
+    }
+
+    #[test]
+    fn rejects_truncated_stub_status() {
+        let status = "Active connections: 291\nserver accepts handled requests\n 1 2 3\n";
+        assert_eq!(
+            parse(status, "nginx", Utc::now(), &server_tags()),
+            Err(ParseError::MissingField { field: "reading" })
+        );
+    }
+}
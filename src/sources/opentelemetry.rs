@@ -0,0 +1,885 @@
+//! OpenTelemetry source
+//!
+//! Receives logs and metrics from OpenTelemetry SDKs and collectors over the
+//! OpenTelemetry protocol (OTLP), as protobuf over HTTP.
+//!
+//! https://opentelemetry.io/docs/specs/otlp/#otlphttp
+//!
+//! Log records become log events and data points metric events. Data points
+//! that can't be represented as Vector metrics, like exponential histograms,
+//! are counted in the partial success of the response, and the rest of the
+//! request is still accepted. OTLP over gRPC isn't supported.
+
+use crate::{
+    event::{
+        self,
+        metric::{Metric, MetricKind, MetricValue},
+        Event, LogEvent, Value,
+    },
+    internal_events::{
+        OpenTelemetryDataPointsRejected, OpenTelemetryEventsReceived, OpenTelemetryRequestError,
+    },
+    shutdown::ShutdownSignal,
+    tls::{MaybeTlsSettings, TlsConfig},
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
+};
+use bytes::Buf;
+use chrono::{DateTime, TimeZone, Utc};
+use flate2::read::MultiGzDecoder;
+use futures01::{future, stream::iter_ok, sync::mpsc, Future, Sink};
+use hyper::{Body, Response, StatusCode};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Read},
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+use warp::{
+    body::FullBody,
+    filters::BoxedFilter,
+    http::{
+        header::{CONTENT_ENCODING, CONTENT_TYPE},
+        HeaderMap,
+    },
+    Filter,
+};
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/opentelemetry.rs"));
+}
+
+const PROTOBUF: &str = "application/x-protobuf";
+
+// `AggregationTemporality` values.
+const DELTA: i32 = 1;
+const CUMULATIVE: i32 = 2;
+
+// gRPC status codes, which error responses carry.
+const INVALID_ARGUMENT: i32 = 3;
+const UNAVAILABLE: i32 = 14;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OpenTelemetryConfig {
+    #[serde(default = "default_address")]
+    address: SocketAddr,
+    /// The largest request body accepted, after decompression.
+    #[serde(default = "default_max_body_size")]
+    max_body_size: usize,
+    tls: Option<TlsConfig>,
+}
+
+fn default_address() -> SocketAddr {
+    SocketAddr::new(Ipv4Addr::new(0, 0, 0, 0).into(), 4318)
+}
+
+fn default_max_body_size() -> usize {
+    10 * 1024 * 1024 // 10MiB
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<OpenTelemetryConfig>("opentelemetry")
+}
+
+#[typetag::serde(name = "opentelemetry")]
+impl SourceConfig for OpenTelemetryConfig {
+    fn build(
+        &self,
+        _: &str,
+        _: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        let source = Arc::new(OpenTelemetrySource {
+            out,
+            max_body_size: self.max_body_size,
+        });
+
+        let routes = OpenTelemetrySource::export_service(Arc::clone(&source), Signal::Logs)
+            .or(OpenTelemetrySource::export_service(source, Signal::Metrics))
+            .unify();
+
+        info!(message = "building http server", addr = %self.address);
+
+        let tls = MaybeTlsSettings::from_config(&self.tls, true)?;
+        let incoming = tls.bind(&self.address)?.incoming();
+
+        let server = warp::serve(routes)
+            .serve_incoming_with_graceful_shutdown(incoming, shutdown.map(|_| ()));
+
+        Ok(Box::new(server))
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn source_type(&self) -> &'static str {
+        "opentelemetry"
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Signal {
+    Logs,
+    Metrics,
+}
+
+#[derive(Debug, Snafu)]
+enum RequestError {
+    #[snafu(display(
+        "Content type {:?} is not supported, only {:?} is",
+        content_type,
+        PROTOBUF
+    ))]
+    UnsupportedContentType { content_type: String },
+    #[snafu(display("Content encoding {:?} is not supported", encoding))]
+    UnsupportedEncoding { encoding: String },
+    #[snafu(display("Request body exceeds the maximum size of {} bytes", max_body_size))]
+    TooLarge { max_body_size: usize },
+    #[snafu(display("Could not decompress request body: {}", source))]
+    Decompress { source: io::Error },
+    #[snafu(display("Could not decode request body: {}", source))]
+    Decode { source: prost::DecodeError },
+}
+
+impl RequestError {
+    fn status(&self) -> StatusCode {
+        match self {
+            RequestError::UnsupportedContentType { .. }
+            | RequestError::UnsupportedEncoding { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            RequestError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            RequestError::Decompress { .. } | RequestError::Decode { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+        }
+    }
+}
+
+/// The events of a request, and the response to it once they were sent.
+struct Export {
+    events: Vec<Event>,
+    response: Vec<u8>,
+}
+
+struct OpenTelemetrySource {
+    out: mpsc::Sender<Event>,
+    max_body_size: usize,
+}
+
+impl OpenTelemetrySource {
+    fn export_service(source: Arc<Self>, signal: Signal) -> BoxedFilter<(Response<Body>,)> {
+        let path = match signal {
+            Signal::Logs => "logs",
+            Signal::Metrics => "metrics",
+        };
+
+        warp::post2()
+            .and(warp::path("v1"))
+            .and(warp::path(path))
+            .and(warp::path::end())
+            .and(warp::header::headers_cloned())
+            .and(warp::body::concat())
+            .and_then(move |headers: HeaderMap, body: FullBody| {
+                let now = Utc::now();
+                let export =
+                    decode_body(&headers, body, source.max_body_size).and_then(
+                        |body| match signal {
+                            Signal::Logs => decode_logs(&body, now),
+                            Signal::Metrics => decode_metrics(&body, now),
+                        },
+                    );
+
+                match export {
+                    Err(error) => {
+                        emit!(OpenTelemetryRequestError { error: &error });
+                        future::Either::A(future::ok(status_response(
+                            error.status(),
+                            INVALID_ARGUMENT,
+                            error.to_string(),
+                        )))
+                    }
+                    Ok(Export { events, response }) => {
+                        emit!(OpenTelemetryEventsReceived {
+                            count: events.len(),
+                        });
+                        future::Either::B(
+                            source
+                                .out
+                                .clone()
+                                .send_all(iter_ok(events))
+                                .map(move |_| protobuf_response(StatusCode::OK, response))
+                                .or_else(|_| {
+                                    error!("Failed to forward events, downstream is closed");
+                                    // Clients retry on this status.
+                                    Ok::<_, warp::Rejection>(status_response(
+                                        StatusCode::SERVICE_UNAVAILABLE,
+                                        UNAVAILABLE,
+                                        "Shutting down".into(),
+                                    ))
+                                }),
+                        )
+                    }
+                }
+            })
+            .boxed()
+    }
+}
+
+fn protobuf_response(status: StatusCode, body: Vec<u8>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, PROTOBUF)
+        .body(body.into())
+        .expect("static response parts are valid")
+}
+
+fn status_response(status: StatusCode, code: i32, message: String) -> Response<Body> {
+    protobuf_response(status, encode(&proto::Status { code, message }))
+}
+
+fn encode(message: &impl Message) -> Vec<u8> {
+    let mut body = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut body).expect("vec grows as needed");
+    body
+}
+
+fn decode_body(
+    headers: &HeaderMap,
+    body: impl Buf,
+    max_body_size: usize,
+) -> Result<Vec<u8>, RequestError> {
+    let header = |name| {
+        headers
+            .get(name)
+            .map(|value| value.to_str().unwrap_or("").trim().to_ascii_lowercase())
+    };
+
+    // The JSON encoding of OTLP isn't supported.
+    if let Some(content_type) = header(CONTENT_TYPE) {
+        if !content_type.starts_with(PROTOBUF) {
+            return Err(RequestError::UnsupportedContentType { content_type });
+        }
+    }
+
+    let body = match header(CONTENT_ENCODING).as_ref().map(String::as_str) {
+        None | Some("") | Some("identity") => body.collect::<Vec<u8>>(),
+        Some("gzip") => {
+            // Reads at most one byte past the limit, so compressed bodies
+            // that inflate past it aren't inflated any further.
+            let mut decoded = Vec::new();
+            MultiGzDecoder::new(body.reader())
+                .take(max_body_size as u64 + 1)
+                .read_to_end(&mut decoded)
+                .context(Decompress)?;
+            decoded
+        }
+        Some(encoding) => {
+            return Err(RequestError::UnsupportedEncoding {
+                encoding: encoding.to_owned(),
+            })
+        }
+    };
+
+    if body.len() > max_body_size {
+        Err(RequestError::TooLarge { max_body_size })
+    } else {
+        Ok(body)
+    }
+}
+
+/// Log records without a time are stamped with the time they were observed
+/// at, or else received at, `now`.
+fn decode_logs(body: &[u8], now: DateTime<Utc>) -> Result<Export, RequestError> {
+    let request = proto::ExportLogsServiceRequest::decode(body).context(Decode)?;
+
+    let mut events = Vec::new();
+    for resource_logs in request.resource_logs {
+        let resource = resource_logs
+            .resource
+            .map(|resource| decode_attributes(resource.attributes))
+            .unwrap_or_default();
+        for scope_logs in resource_logs.scope_logs {
+            for record in scope_logs.log_records {
+                events.push(Event::Log(decode_log(record, &resource, now)));
+            }
+        }
+    }
+
+    let response = proto::ExportLogsServiceResponse {
+        partial_success: None,
+    };
+    Ok(Export {
+        events,
+        response: encode(&response),
+    })
+}
+
+/// Attributes become fields, under their full name, and the attributes of
+/// the resource the `resource` field. The body of the record becomes the
+/// message, and its severity the `severity` field.
+fn decode_log(
+    record: proto::LogRecord,
+    resource: &BTreeMap<String, Value>,
+    now: DateTime<Utc>,
+) -> LogEvent {
+    let mut log = LogEvent::new();
+    for (key, value) in decode_attributes(record.attributes) {
+        log.insert_flat(key, value);
+    }
+    if !resource.is_empty() {
+        log.insert_flat("resource", resource.clone());
+    }
+
+    if let Some(body) = record.body {
+        log.insert(
+            event::log_schema().message_key().clone(),
+            decode_value(body),
+        );
+    }
+    let timestamp = decode_timestamp(record.time_unix_nano)
+        .or_else(|| decode_timestamp(record.observed_time_unix_nano))
+        .unwrap_or(now);
+    log.insert(event::log_schema().timestamp_key().clone(), timestamp);
+
+    if !record.severity_text.is_empty() {
+        log.insert_flat("severity", record.severity_text);
+    } else if let Some(severity) = severity_name(record.severity_number) {
+        log.insert_flat("severity", severity);
+    }
+
+    log
+}
+
+/// The short names of `SeverityNumber` ranges.
+fn severity_name(severity_number: i32) -> Option<&'static str> {
+    match severity_number {
+        1..=4 => Some("TRACE"),
+        5..=8 => Some("DEBUG"),
+        9..=12 => Some("INFO"),
+        13..=16 => Some("WARN"),
+        17..=20 => Some("ERROR"),
+        21..=24 => Some("FATAL"),
+        _ => None,
+    }
+}
+
+/// Data points that can't be converted, and why.
+#[derive(Debug, Default)]
+struct Rejected {
+    count: usize,
+    reasons: BTreeSet<String>,
+}
+
+impl Rejected {
+    fn add(&mut self, count: usize, reason: impl Into<String>) {
+        let reason = reason.into();
+        emit!(OpenTelemetryDataPointsRejected {
+            count,
+            reason: &reason,
+        });
+        self.count += count;
+        self.reasons.insert(reason);
+    }
+}
+
+/// Every data point becomes a metric event, tagged with the attributes of
+/// both the resource and the data point, which take precedence.
+fn decode_metrics(body: &[u8], now: DateTime<Utc>) -> Result<Export, RequestError> {
+    let request = proto::ExportMetricsServiceRequest::decode(body).context(Decode)?;
+
+    let mut events = Vec::new();
+    let mut rejected = Rejected::default();
+    for resource_metrics in request.resource_metrics {
+        let resource = resource_metrics
+            .resource
+            .map(|resource| decode_tags(resource.attributes))
+            .unwrap_or_default();
+        for scope_metrics in resource_metrics.scope_metrics {
+            for metric in scope_metrics.metrics {
+                decode_metric(metric, &resource, now, &mut events, &mut rejected);
+            }
+        }
+    }
+
+    let partial_success = if rejected.reasons.is_empty() {
+        None
+    } else {
+        Some(proto::ExportMetricsPartialSuccess {
+            rejected_data_points: rejected.count as i64,
+            error_message: rejected.reasons.into_iter().collect::<Vec<_>>().join("; "),
+        })
+    };
+    let response = proto::ExportMetricsServiceResponse { partial_success };
+    Ok(Export {
+        events,
+        response: encode(&response),
+    })
+}
+
+fn decode_metric(
+    metric: proto::Metric,
+    resource: &BTreeMap<String, String>,
+    now: DateTime<Utc>,
+    events: &mut Vec<Event>,
+    rejected: &mut Rejected,
+) {
+    use proto::metric::Data;
+
+    let name = metric.name;
+    let mut push = |attributes, time_unix_nano, kind, value| {
+        let mut tags = resource.clone();
+        tags.extend(decode_tags(attributes));
+        events.push(Event::Metric(Metric {
+            name: name.clone(),
+            timestamp: Some(decode_timestamp(time_unix_nano).unwrap_or(now)),
+            tags: if tags.is_empty() { None } else { Some(tags) },
+            kind,
+            value,
+        }));
+    };
+
+    match metric.data {
+        Some(Data::Gauge(gauge)) => {
+            for point in gauge.data_points {
+                match number(&point) {
+                    Some(value) => push(
+                        point.attributes,
+                        point.time_unix_nano,
+                        MetricKind::Absolute,
+                        MetricValue::Gauge { value },
+                    ),
+                    None => rejected.add(1, "Data point has no value"),
+                }
+            }
+        }
+        Some(Data::Sum(sum)) => {
+            let kind = match metric_kind(sum.aggregation_temporality) {
+                Some(kind) => kind,
+                None => {
+                    return rejected
+                        .add(sum.data_points.len(), "Sum has no aggregation temporality")
+                }
+            };
+            for point in sum.data_points {
+                // Sums that aren't monotonic can go down, like gauges.
+                let value = match number(&point) {
+                    Some(value) if sum.is_monotonic => MetricValue::Counter { value },
+                    Some(value) => MetricValue::Gauge { value },
+                    None => {
+                        rejected.add(1, "Data point has no value");
+                        continue;
+                    }
+                };
+                push(point.attributes, point.time_unix_nano, kind, value);
+            }
+        }
+        Some(Data::Histogram(histogram)) => {
+            let kind = match metric_kind(histogram.aggregation_temporality) {
+                Some(kind) => kind,
+                None => {
+                    return rejected.add(
+                        histogram.data_points.len(),
+                        "Histogram has no aggregation temporality",
+                    )
+                }
+            };
+            for point in histogram.data_points {
+                let buckets = point.explicit_bounds;
+                if !point.bucket_counts.is_empty() && point.bucket_counts.len() != buckets.len() + 1
+                {
+                    rejected.add(1, "Histogram bucket counts don't match its bounds");
+                    continue;
+                }
+                // OTLP counts each bucket on its own, while Vector's bucket
+                // counts are cumulative. The bucket above the highest bound
+                // is the total count.
+                let mut total = 0u64;
+                let counts = point
+                    .bucket_counts
+                    .iter()
+                    .take(buckets.len())
+                    .map(|count| {
+                        total += count;
+                        saturating_u32(total)
+                    })
+                    .collect();
+                push(
+                    point.attributes,
+                    point.time_unix_nano,
+                    kind,
+                    MetricValue::AggregatedHistogram {
+                        buckets,
+                        counts,
+                        count: saturating_u32(point.count),
+                        sum: point.sum,
+                    },
+                );
+            }
+        }
+        Some(Data::ExponentialHistogram(histogram)) => rejected.add(
+            histogram.data_points.len(),
+            "Exponential histograms are not supported",
+        ),
+        Some(Data::Summary(summary)) => {
+            for point in summary.data_points {
+                let (quantiles, values): (Vec<_>, Vec<_>) = point
+                    .quantile_values
+                    .iter()
+                    .map(|quantile| (quantile.quantile, quantile.value))
+                    .unzip();
+                push(
+                    point.attributes,
+                    point.time_unix_nano,
+                    MetricKind::Absolute,
+                    MetricValue::AggregatedSummary {
+                        quantiles,
+                        values,
+                        count: saturating_u32(point.count),
+                        sum: point.sum,
+                    },
+                );
+            }
+        }
+        // The data is of a type this source doesn't know about, so there's
+        // no telling how many data points it has.
+        None => rejected.add(0, format!("Metric {:?} has no supported data", name)),
+    }
+}
+
+fn metric_kind(aggregation_temporality: i32) -> Option<MetricKind> {
+    match aggregation_temporality {
+        DELTA => Some(MetricKind::Incremental),
+        CUMULATIVE => Some(MetricKind::Absolute),
+        _ => None,
+    }
+}
+
+fn number(point: &proto::NumberDataPoint) -> Option<f64> {
+    use proto::number_data_point::Value;
+
+    match point.value.as_ref()? {
+        Value::AsDouble(value) => Some(*value),
+        Value::AsInt(value) => Some(*value as f64),
+    }
+}
+
+fn saturating_u32(value: u64) -> u32 {
+    value.min(u64::from(u32::max_value())) as u32
+}
+
+/// Zero marks the time as unknown.
+fn decode_timestamp(unix_nanos: u64) -> Option<DateTime<Utc>> {
+    if unix_nanos == 0 {
+        None
+    } else {
+        Some(Utc.timestamp_nanos(unix_nanos as i64))
+    }
+}
+
+fn decode_attributes(attributes: Vec<proto::KeyValue>) -> BTreeMap<String, Value> {
+    attributes
+        .into_iter()
+        .map(|attribute| {
+            let value = attribute.value.map(decode_value).unwrap_or(Value::Null);
+            (attribute.key, value)
+        })
+        .collect()
+}
+
+fn decode_tags(attributes: Vec<proto::KeyValue>) -> BTreeMap<String, String> {
+    decode_attributes(attributes)
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string_lossy()))
+        .collect()
+}
+
+fn decode_value(value: proto::AnyValue) -> Value {
+    use proto::any_value::Value as AnyValue;
+
+    match value.value {
+        Some(AnyValue::StringValue(value)) => Value::from(value),
+        Some(AnyValue::BoolValue(value)) => Value::Boolean(value),
+        Some(AnyValue::IntValue(value)) => Value::Integer(value),
+        Some(AnyValue::DoubleValue(value)) => Value::Float(value),
+        Some(AnyValue::ArrayValue(array)) => {
+            Value::Array(array.values.into_iter().map(decode_value).collect())
+        }
+        Some(AnyValue::KvlistValue(list)) => Value::Map(decode_attributes(list.values)),
+        Some(AnyValue::BytesValue(value)) => Value::from(value),
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, collect_n};
+    use flate2::{write::GzEncoder, Compression};
+    use http::Method;
+    use pretty_assertions::assert_eq;
+    use std::io::{Cursor, Write};
+
+    fn attribute(key: &str, value: proto::any_value::Value) -> proto::KeyValue {
+        proto::KeyValue {
+            key: key.into(),
+            value: Some(proto::AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn string(key: &str, value: &str) -> proto::KeyValue {
+        attribute(key, proto::any_value::Value::StringValue(value.into()))
+    }
+
+    fn resource() -> Option<proto::Resource> {
+        Some(proto::Resource {
+            attributes: vec![string("service.name", "checkout")],
+        })
+    }
+
+    fn logs_request() -> proto::ExportLogsServiceRequest {
+        proto::ExportLogsServiceRequest {
+            resource_logs: vec![proto::ResourceLogs {
+                resource: resource(),
+                scope_logs: vec![proto::ScopeLogs {
+                    scope: None,
+                    log_records: vec![proto::LogRecord {
+                        time_unix_nano: 1_600_000_000_000_000_001,
+                        observed_time_unix_nano: 0,
+                        severity_number: 13,
+                        severity_text: "".into(),
+                        body: Some(proto::AnyValue {
+                            value: Some(proto::any_value::Value::StringValue(
+                                "card declined".into(),
+                            )),
+                        }),
+                        attributes: vec![
+                            attribute("http.status_code", proto::any_value::Value::IntValue(402)),
+                            string("message", "overridden by the body"),
+                        ],
+                    }],
+                }],
+            }],
+        }
+    }
+
+    fn number_point(value: f64) -> proto::NumberDataPoint {
+        proto::NumberDataPoint {
+            attributes: vec![string("host", "web-1")],
+            start_time_unix_nano: 0,
+            time_unix_nano: 1_600_000_000_000_000_000,
+            value: Some(proto::number_data_point::Value::AsDouble(value)),
+        }
+    }
+
+    fn metrics_request(metrics: Vec<proto::Metric>) -> proto::ExportMetricsServiceRequest {
+        proto::ExportMetricsServiceRequest {
+            resource_metrics: vec![proto::ResourceMetrics {
+                resource: resource(),
+                scope_metrics: vec![proto::ScopeMetrics {
+                    scope: None,
+                    metrics,
+                }],
+            }],
+        }
+    }
+
+    fn metric(name: &str, data: proto::metric::Data) -> proto::Metric {
+        proto::Metric {
+            name: name.into(),
+            description: String::new(),
+            unit: String::new(),
+            data: Some(data),
+        }
+    }
+
+    fn tags() -> Option<BTreeMap<String, String>> {
+        let mut tags = BTreeMap::new();
+        tags.insert("host".to_owned(), "web-1".to_owned());
+        tags.insert("service.name".to_owned(), "checkout".to_owned());
+        Some(tags)
+    }
+
+    fn send(address: SocketAddr, path: &str, body: Vec<u8>, gzip: bool) -> (u16, Vec<u8>) {
+        let mut request = reqwest::Client::new()
+            .request(Method::POST, &format!("http://{}{}", address, path))
+            .header("Content-Type", PROTOBUF);
+        let body = if gzip {
+            request = request.header("Content-Encoding", "gzip");
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&body).unwrap();
+            encoder.finish().unwrap()
+        } else {
+            body
+        };
+
+        let mut response = request.body(body).send().unwrap();
+        let mut body = Vec::new();
+        response.read_to_end(&mut body).unwrap();
+        (response.status().as_u16(), body)
+    }
+
+    #[test]
+    fn opentelemetry_receives_logs_and_metrics_over_http() {
+        test_util::trace_init();
+        let mut rt = test_util::runtime();
+        let (sender, rx) = mpsc::channel(100);
+        let address = test_util::next_addr();
+        rt.spawn(
+            OpenTelemetryConfig {
+                address,
+                max_body_size: default_max_body_size(),
+                tls: None,
+            }
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                sender,
+            )
+            .unwrap(),
+        );
+
+        let (status, body) = send(address, "/v1/logs", encode(&logs_request()), true);
+        assert_eq!(status, 200);
+        assert_eq!(
+            proto::ExportLogsServiceResponse::decode(&body[..]).unwrap(),
+            proto::ExportLogsServiceResponse {
+                partial_success: None
+            }
+        );
+
+        let counter = metric(
+            "requests",
+            proto::metric::Data::Sum(proto::Sum {
+                data_points: vec![number_point(3.0)],
+                aggregation_temporality: CUMULATIVE,
+                is_monotonic: true,
+            }),
+        );
+        let (status, _) = send(
+            address,
+            "/v1/metrics",
+            encode(&metrics_request(vec![counter])),
+            false,
+        );
+        assert_eq!(status, 200);
+
+        let events = rt.block_on(collect_n(rx, 2)).unwrap();
+
+        let log = events[0].as_log();
+        assert_eq!(
+            log[&event::log_schema().message_key()],
+            "card declined".into()
+        );
+        assert_eq!(
+            log[&event::log_schema().timestamp_key()],
+            Utc.timestamp(1_600_000_000, 1).into()
+        );
+        assert_eq!(log[&"severity".into()], "WARN".into());
+        assert_eq!(log[&"http\\.status_code".into()], 402.into());
+        assert_eq!(log[&"resource.service\\.name".into()], "checkout".into());
+
+        assert_eq!(
+            events[1].as_metric(),
+            &Metric {
+                name: "requests".into(),
+                timestamp: Some(Utc.timestamp(1_600_000_000, 0)),
+                tags: tags(),
+                kind: MetricKind::Absolute,
+                value: MetricValue::Counter { value: 3.0 },
+            }
+        );
+    }
+
+    #[test]
+    fn opentelemetry_rejects_malformed_requests() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let error = decode_body(&headers, Cursor::new(vec![]), 64).unwrap_err();
+        assert_eq!(error.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 1024]).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+        let body = Cursor::new(encoder.finish().unwrap());
+        let error = decode_body(&headers, body, 64).unwrap_err();
+        assert_eq!(error.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let error = decode_logs(b"\xff\xff", Utc::now()).err().unwrap();
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn opentelemetry_decodes_histograms_and_reports_partial_success() {
+        let histogram = metric(
+            "latency",
+            proto::metric::Data::Histogram(proto::Histogram {
+                data_points: vec![
+                    proto::HistogramDataPoint {
+                        attributes: vec![string("host", "web-1")],
+                        start_time_unix_nano: 0,
+                        time_unix_nano: 1_600_000_000_000_000_000,
+                        count: 7,
+                        sum: 5.5,
+                        bucket_counts: vec![4, 2, 1],
+                        explicit_bounds: vec![0.5, 1.0],
+                    },
+                    proto::HistogramDataPoint {
+                        attributes: vec![],
+                        start_time_unix_nano: 0,
+                        time_unix_nano: 0,
+                        count: 1,
+                        sum: 1.0,
+                        bucket_counts: vec![1],
+                        explicit_bounds: vec![0.5, 1.0],
+                    },
+                ],
+                aggregation_temporality: DELTA,
+            }),
+        );
+        let exponential = metric(
+            "sizes",
+            proto::metric::Data::ExponentialHistogram(proto::ExponentialHistogram {
+                data_points: vec![
+                    proto::ExponentialHistogramDataPoint {},
+                    proto::ExponentialHistogramDataPoint {},
+                ],
+            }),
+        );
+
+        let body = encode(&metrics_request(vec![histogram, exponential]));
+        let export = decode_metrics(&body, Utc::now()).unwrap();
+
+        assert_eq!(
+            export.events,
+            vec![Event::Metric(Metric {
+                name: "latency".into(),
+                timestamp: Some(Utc.timestamp(1_600_000_000, 0)),
+                tags: tags(),
+                kind: MetricKind::Incremental,
+                value: MetricValue::AggregatedHistogram {
+                    buckets: vec![0.5, 1.0],
+                    counts: vec![4, 6],
+                    count: 7,
+                    sum: 5.5,
+                },
+            })]
+        );
+        assert_eq!(
+            proto::ExportMetricsServiceResponse::decode(&export.response[..]).unwrap(),
+            proto::ExportMetricsServiceResponse {
+                partial_success: Some(proto::ExportMetricsPartialSuccess {
+                    rejected_data_points: 3,
+                    error_message: "Exponential histograms are not supported; \
+                                    Histogram bucket counts don't match its bounds"
+                        .into(),
+                }),
+            }
+        );
+    }
+}
@@ -1,37 +1,57 @@
 use crate::{
-    internal_events::{PrometheusHttpError, PrometheusParseError, PrometheusRequestCompleted},
+    event::metric::{Metric, MetricValue},
+    internal_events::{
+        PrometheusHttpError, PrometheusParseError, PrometheusRequestCompleted,
+        PrometheusScrapeTimeout, PrometheusStaleSeries,
+    },
     shutdown::ShutdownSignal,
-    topology::config::GlobalOptions,
+    stream::StreamExt,
+    topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
     Event,
 };
+use chrono::Utc;
 use futures01::{sync::mpsc, Future, Sink, Stream};
 use http::Uri;
 use hyper_openssl::HttpsConnector;
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
-use std::time::{Duration, Instant};
-use tokio01::timer::Interval;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio01::{timer::Interval, util::FutureExt};
 
 pub mod parser;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
-struct PrometheusConfig {
+struct PrometheusScrapeConfig {
     hosts: Vec<String>,
     #[serde(default = "default_scrape_interval_secs")]
     scrape_interval_secs: u64,
+    #[serde(default = "default_scrape_timeout_secs")]
+    scrape_timeout_secs: u64,
 }
 
 pub fn default_scrape_interval_secs() -> u64 {
     15
 }
 
-#[typetag::serde(name = "prometheus")]
-impl crate::topology::config::SourceConfig for PrometheusConfig {
+pub fn default_scrape_timeout_secs() -> u64 {
+    10
+}
+
+inventory::submit! {
+    SourceDescription::new_without_default::<PrometheusScrapeConfig>("prometheus_scrape")
+}
+
+#[typetag::serde(name = "prometheus_scrape")]
+impl SourceConfig for PrometheusScrapeConfig {
     fn build(
         &self,
         _name: &str,
         _globals: &GlobalOptions,
-        _shutdown: ShutdownSignal,
+        shutdown: ShutdownSignal,
         out: mpsc::Sender<Event>,
     ) -> crate::Result<super::Source> {
         let mut urls = Vec::new();
@@ -39,28 +59,164 @@ impl crate::topology::config::SourceConfig for PrometheusConfig {
             let base_uri = host.parse::<Uri>().context(super::UriParseError)?;
             urls.push(format!("{}metrics", base_uri));
         }
-        Ok(prometheus(urls, self.scrape_interval_secs, out))
+        // A scrape must never outlive the interval, otherwise slow endpoints
+        // would pile up concurrent requests against themselves.
+        let timeout = self.scrape_timeout_secs.min(self.scrape_interval_secs);
+        Ok(prometheus(
+            urls,
+            self.scrape_interval_secs,
+            timeout,
+            out,
+            shutdown,
+        ))
     }
 
-    fn output_type(&self) -> crate::topology::config::DataType {
-        crate::topology::config::DataType::Metric
+    fn output_type(&self) -> DataType {
+        DataType::Metric
     }
 
     fn source_type(&self) -> &'static str {
-        "prometheus"
+        "prometheus_scrape"
     }
 }
 
-fn prometheus(urls: Vec<String>, interval: u64, out: mpsc::Sender<Event>) -> super::Source {
+// Add a compatibility alias to avoid breaking existing configs
+#[derive(Deserialize, Serialize, Clone, Debug)]
+struct PrometheusCompatConfig {
+    #[serde(flatten)]
+    config: PrometheusScrapeConfig,
+}
+
+#[typetag::serde(name = "prometheus")]
+impl SourceConfig for PrometheusCompatConfig {
+    fn build(
+        &self,
+        name: &str,
+        globals: &GlobalOptions,
+        shutdown: ShutdownSignal,
+        out: mpsc::Sender<Event>,
+    ) -> crate::Result<super::Source> {
+        self.config.build(name, globals, shutdown, out)
+    }
+
+    fn output_type(&self) -> DataType {
+        self.config.output_type()
+    }
+
+    fn source_type(&self) -> &'static str {
+        self.config.source_type()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SeriesKey {
+    name: String,
+    tags: Option<BTreeMap<String, String>>,
+}
+
+impl From<&Metric> for SeriesKey {
+    fn from(metric: &Metric) -> Self {
+        Self {
+            name: metric.name.clone(),
+            tags: metric.tags.clone(),
+        }
+    }
+}
+
+/// Prometheus marks a series as stale with a NaN of this particular bit
+/// pattern.
+const STALE_NAN: u64 = 0x7ff0_0000_0000_0002;
+
+/// Remembers which series every endpoint exposed on its last scrape, so
+/// series that disappear between scrapes can be marked stale. A failed
+/// scrape exposes no series, so all of the endpoint's series go stale.
+#[derive(Debug, Default)]
+struct SeriesTracker {
+    endpoints: HashMap<String, HashMap<SeriesKey, Metric>>,
+}
+
+impl SeriesTracker {
+    /// Records the series of the latest scrape and returns a staleness marker
+    /// for every series seen on the previous scrape that is now gone.
+    fn update(&mut self, url: &str, metrics: &[Metric]) -> Vec<Metric> {
+        let current = metrics
+            .iter()
+            .map(|metric| (SeriesKey::from(metric), metric.clone()))
+            .collect::<HashMap<_, _>>();
+        let stale = self
+            .endpoints
+            .get(url)
+            .map(|previous| {
+                previous
+                    .iter()
+                    .filter(|(key, _)| !current.contains_key(key))
+                    .map(|(_, metric)| stale_marker(metric))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.endpoints.insert(url.to_owned(), current);
+        stale
+    }
+}
+
+/// Repeats the last sample of a series with the values replaced by the
+/// staleness NaN, the way Prometheus itself ends a series.
+fn stale_marker(metric: &Metric) -> Metric {
+    let stale = f64::from_bits(STALE_NAN);
+    let value = match &metric.value {
+        MetricValue::Counter { .. } => MetricValue::Counter { value: stale },
+        MetricValue::Gauge { .. } => MetricValue::Gauge { value: stale },
+        MetricValue::AggregatedHistogram {
+            buckets,
+            counts,
+            count,
+            ..
+        } => MetricValue::AggregatedHistogram {
+            buckets: buckets.clone(),
+            counts: counts.clone(),
+            count: *count,
+            sum: stale,
+        },
+        MetricValue::AggregatedSummary {
+            quantiles, count, ..
+        } => MetricValue::AggregatedSummary {
+            quantiles: quantiles.clone(),
+            values: vec![stale; quantiles.len()],
+            count: *count,
+            sum: stale,
+        },
+        // The text format has no sets or distributions.
+        value => value.clone(),
+    };
+
+    Metric {
+        name: metric.name.clone(),
+        timestamp: Some(Utc::now()),
+        tags: metric.tags.clone(),
+        kind: metric.kind.clone(),
+        value,
+    }
+}
+
+fn prometheus(
+    urls: Vec<String>,
+    interval: u64,
+    timeout: u64,
+    out: mpsc::Sender<Event>,
+    shutdown: ShutdownSignal,
+) -> super::Source {
     let out = out.sink_map_err(|e| error!("error sending metric: {:?}", e));
+    let tracker = Arc::new(Mutex::new(SeriesTracker::default()));
 
     let task = Interval::new(Instant::now(), Duration::from_secs(interval))
         .map_err(|e| error!("timer error: {:?}", e))
+        .take_until(shutdown)
         .map(move |_| futures01::stream::iter_ok(urls.clone()))
         .flatten()
         .map(move |url| {
             let https = HttpsConnector::new(4).expect("TLS initialization failed");
             let client = hyper::Client::builder().build(https);
+            let tracker = Arc::clone(&tracker);
 
             let request = hyper::Request::get(&url)
                 .body(hyper::Body::empty())
@@ -69,24 +225,44 @@ fn prometheus(urls: Vec<String>, interval: u64, out: mpsc::Sender<Event>) -> sup
             client
                 .request(request)
                 .and_then(|response| response.into_body().concat2())
-                .map(|body| {
-                    emit!(PrometheusRequestCompleted);
-
-                    let packet = String::from_utf8_lossy(&body);
-                    let metrics = parser::parse(&packet)
-                        .map_err(|error| {
-                            emit!(PrometheusParseError { error });
-                        })
-                        .unwrap_or_default()
-                        .into_iter()
-                        .map(Event::Metric);
-
-                    futures01::stream::iter_ok(metrics)
+                .timeout(Duration::from_secs(timeout))
+                .then(move |result| {
+                    let metrics = match result {
+                        Ok(body) => {
+                            emit!(PrometheusRequestCompleted);
+
+                            let packet = String::from_utf8_lossy(&body);
+                            parser::parse(&packet)
+                                .map_err(|error| {
+                                    emit!(PrometheusParseError { error });
+                                })
+                                .unwrap_or_default()
+                        }
+                        Err(error) => {
+                            if error.is_elapsed() {
+                                emit!(PrometheusScrapeTimeout { timeout });
+                            } else if let Some(error) = error.into_inner() {
+                                emit!(PrometheusHttpError { error });
+                            } else {
+                                error!("timer error during scrape");
+                            }
+                            Vec::new()
+                        }
+                    };
+
+                    let stale = tracker.lock().unwrap().update(&url, &metrics);
+                    if !stale.is_empty() {
+                        emit!(PrometheusStaleSeries {
+                            url: &url,
+                            count: stale.len()
+                        });
+                    }
+
+                    Ok::<_, ()>(futures01::stream::iter_ok(
+                        metrics.into_iter().chain(stale).map(Event::Metric),
+                    ))
                 })
                 .flatten_stream()
-                .map_err(|error| {
-                    emit!(PrometheusHttpError { error });
-                })
         })
         .flatten()
         .forward(out)
@@ -95,9 +271,187 @@ fn prometheus(urls: Vec<String>, interval: u64, out: mpsc::Sender<Event>) -> sup
     Box::new(task)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::metric::{MetricKind, MetricValue},
+        test_util::{next_addr, runtime},
+    };
+    use futures01::{future, Async};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, Server};
+    use metrics_runtime::Measurement;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+    use stream_cancel::Tripwire;
+
+    fn gauge(name: &str, tags: Option<BTreeMap<String, String>>) -> Metric {
+        Metric {
+            name: name.into(),
+            timestamp: None,
+            tags,
+            kind: MetricKind::Absolute,
+            value: MetricValue::Gauge { value: 1.0 },
+        }
+    }
+
+    #[test]
+    fn series_tracker_marks_vanished_series_stale() {
+        let mut tracker = SeriesTracker::default();
+        let mut tags = BTreeMap::new();
+        tags.insert("code".to_owned(), "200".to_owned());
+
+        let first = vec![gauge("up", None), gauge("requests", Some(tags.clone()))];
+        assert!(tracker.update("http://a/metrics", &first).is_empty());
+
+        let second = vec![gauge("up", None)];
+        let stale = tracker.update("http://a/metrics", &second);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].name, "requests");
+        assert_eq!(stale[0].tags, Some(tags));
+        match stale[0].value {
+            MetricValue::Gauge { value } => assert_eq!(value.to_bits(), STALE_NAN),
+            ref value => panic!("unexpected value {:?}", value),
+        }
+
+        // Endpoints are tracked independently of each other
+        assert!(tracker.update("http://b/metrics", &[]).is_empty());
+        assert!(tracker.update("http://a/metrics", &second).is_empty());
+    }
+
+    fn scrape_timeouts() -> u64 {
+        crate::metrics::CONTROLLER
+            .get()
+            .unwrap()
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .filter(|(key, _)| key.name() == "scrape_timeouts")
+            .map(|(_, measurement)| match measurement {
+                Measurement::Counter(count) => count,
+                _ => panic!("scrape_timeouts is not a counter"),
+            })
+            .sum()
+    }
+
+    #[test]
+    fn scrape_timeout_drops_slow_responses() {
+        crate::metrics::init_test();
+        let timeouts = scrape_timeouts();
+        let mut rt = runtime();
+        let in_addr = next_addr();
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        let make_svc = make_service_fn(move |_| {
+            let counter = Arc::clone(&counter);
+            service_fn(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio01::timer::Delay::new(Instant::now() + Duration::from_secs(3))
+                    .map(|_| Response::new(Body::from("up 1\n")))
+            })
+        });
+        let server = Server::bind(&in_addr).serve(make_svc);
+        rt.spawn(server.map_err(|e| {
+            error!("server error: {:?}", e);
+        }));
+
+        let (tx, rx) = mpsc::channel(10);
+        let source = prometheus(
+            vec![format!("http://{}/metrics", in_addr)],
+            5,
+            1,
+            tx,
+            ShutdownSignal::noop(),
+        );
+        rt.spawn(source);
+        thread::sleep(Duration::from_secs(2));
+
+        let mut rx = rx;
+        let empty = rt
+            .block_on(future::poll_fn(move || {
+                Ok::<_, ()>(Async::Ready(rx.poll()?.is_not_ready()))
+            }))
+            .unwrap();
+        assert!(empty);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(scrape_timeouts() > timeouts);
+    }
+
+    #[test]
+    fn failed_scrape_marks_series_stale() {
+        let mut rt = runtime();
+        let in_addr = next_addr();
+
+        // Only the first scrape gets an answer in time.
+        let requests = Arc::new(AtomicUsize::new(0));
+        let make_svc = make_service_fn(move |_| {
+            let requests = Arc::clone(&requests);
+            service_fn(move |_| {
+                let delay = match requests.fetch_add(1, Ordering::SeqCst) {
+                    0 => Duration::from_secs(0),
+                    _ => Duration::from_secs(3),
+                };
+                tokio01::timer::Delay::new(Instant::now() + delay)
+                    .map(|_| Response::new(Body::from("up 1\n")))
+            })
+        });
+        let server = Server::bind(&in_addr).serve(make_svc);
+        rt.spawn(server.map_err(|e| {
+            error!("server error: {:?}", e);
+        }));
+
+        let (tx, rx) = mpsc::channel(10);
+        let source = prometheus(
+            vec![format!("http://{}/metrics", in_addr)],
+            1,
+            1,
+            tx,
+            ShutdownSignal::noop(),
+        );
+        rt.spawn(source);
+
+        let events = rt
+            .block_on(rx.take(2).collect().timeout(Duration::from_secs(5)))
+            .unwrap();
+        let values = events
+            .iter()
+            .map(|event| match event.as_metric().value {
+                MetricValue::Gauge { value } => value.to_bits(),
+                ref value => panic!("unexpected value {:?}", value),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1f64.to_bits(), STALE_NAN]);
+    }
+
+    #[test]
+    fn scrape_stops_on_shutdown() {
+        let mut rt = runtime();
+        let (begin_shutdown, tripwire) = Tripwire::new();
+        let (shutdown_complete, _) = Tripwire::new();
+        let shutdown = ShutdownSignal::new(tripwire, shutdown_complete);
+
+        let (tx, _rx) = mpsc::channel(10);
+        let source = prometheus(
+            vec![format!("http://{}/metrics", next_addr())],
+            60,
+            1,
+            tx,
+            shutdown,
+        );
+
+        drop(begin_shutdown);
+        assert!(rt.block_on(source.timeout(Duration::from_secs(5))).is_ok());
+    }
+}
+
 #[cfg(feature = "sinks-prometheus")]
 #[cfg(test)]
-mod test {
+mod integration_tests {
     use super::*;
     use crate::{
         sinks::prometheus::PrometheusSinkConfig,
@@ -158,9 +512,10 @@ mod test {
         let mut config = config::Config::empty();
         config.add_source(
             "in",
-            PrometheusConfig {
+            PrometheusScrapeConfig {
                 hosts: vec![format!("http://{}", in_addr)],
                 scrape_interval_secs: 1,
+                scrape_timeout_secs: 1,
             },
         );
         config.add_sink(
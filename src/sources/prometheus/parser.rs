@@ -1091,4 +1091,71 @@ mod test {
             ]),
         );
     }
+
+    #[test]
+    fn test_all_types_in_one_scrape() {
+        let exp = r##"
+            # TYPE jobs_total counter
+            jobs_total{queue="default"} 12
+            # TYPE temperature gauge
+            temperature 21.5
+            # TYPE latency_seconds histogram
+            latency_seconds_bucket{le="0.5"} 3
+            latency_seconds_bucket{le="+Inf"} 4
+            latency_seconds_sum 1.5
+            latency_seconds_count 4
+            # TYPE size_bytes summary
+            size_bytes{quantile="0.5"} 100
+            size_bytes_sum 350
+            size_bytes_count 3
+            "##;
+
+        assert_eq!(
+            parse(exp),
+            Ok(vec![
+                Metric {
+                    name: "jobs_total".into(),
+                    timestamp: None,
+                    tags: Some(
+                        vec![("queue".into(), "default".into())]
+                            .into_iter()
+                            .collect()
+                    ),
+                    kind: MetricKind::Absolute,
+                    value: MetricValue::Counter { value: 12.0 },
+                },
+                Metric {
+                    name: "temperature".into(),
+                    timestamp: None,
+                    tags: None,
+                    kind: MetricKind::Absolute,
+                    value: MetricValue::Gauge { value: 21.5 },
+                },
+                Metric {
+                    name: "latency_seconds".into(),
+                    timestamp: None,
+                    tags: None,
+                    kind: MetricKind::Absolute,
+                    value: MetricValue::AggregatedHistogram {
+                        buckets: vec![0.5],
+                        counts: vec![3],
+                        count: 4,
+                        sum: 1.5,
+                    },
+                },
+                Metric {
+                    name: "size_bytes".into(),
+                    timestamp: None,
+                    tags: None,
+                    kind: MetricKind::Absolute,
+                    value: MetricValue::AggregatedSummary {
+                        quantiles: vec![0.5],
+                        values: vec![100.0],
+                        count: 3,
+                        sum: 350.0,
+                    },
+                },
+            ]),
+        );
+    }
 }
@@ -94,7 +94,14 @@ impl SourceConfig for SocketConfig {
                     .host_key
                     .clone()
                     .unwrap_or(event::log_schema().host_key().clone());
-                Ok(udp::udp(config.address, host_key, shutdown, out))
+                Ok(udp::udp(
+                    config.address,
+                    host_key,
+                    config.port_key,
+                    config.invalid_bytes,
+                    shutdown,
+                    out,
+                ))
             }
             #[cfg(unix)]
             Mode::Unix(config) => {
@@ -143,6 +150,7 @@ mod test {
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
     use std::{net::SocketAddr, thread, time::Duration, time::Instant};
+    use string_cache::DefaultAtom as Atom;
     #[cfg(unix)]
     use tokio01::codec::{FramedWrite, LinesCodec};
     #[cfg(unix)]
@@ -177,6 +185,47 @@ mod test {
         );
     }
 
+    #[test]
+    fn tcp_it_includes_peer_address() {
+        use std::io::Write;
+
+        let (tx, rx) = mpsc::channel(10);
+
+        let addr = next_addr();
+
+        let mut config = TcpConfig::new(addr.into());
+        config.port_key = Some("port".into());
+        config.connection_id_key = Some("connection_id".into());
+        let server = SocketConfig::from(config)
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        let mut rt = runtime::Runtime::new().unwrap();
+        rt.spawn(server);
+        wait_for_tcp(addr);
+
+        let mut peers = Vec::new();
+        for line in &["first\n", "second\n"] {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.write_all(line.as_bytes()).unwrap();
+            peers.push(stream.local_addr().unwrap().port() as i64);
+        }
+
+        let mut events = rt.block_on(collect_n(rx, 2)).ok().unwrap();
+        events.sort_by_key(|event| event.as_log()[&Atom::from("connection_id")].to_string_lossy());
+        for (id, (event, port)) in events.iter().zip(peers).enumerate() {
+            let log = event.as_log();
+            assert_eq!(log[&event::log_schema().host_key()], "127.0.0.1".into());
+            assert_eq!(log[&Atom::from("port")], port.into());
+            assert_eq!(log[&Atom::from("connection_id")], (id as i64 + 1).into());
+            assert!(!log.contains(&Atom::from("tls_peer_subject")));
+        }
+    }
+
     #[test]
     fn tcp_continue_after_long_line() {
         let (tx, rx) = mpsc::channel(10);
@@ -219,6 +268,48 @@ mod test {
         );
     }
 
+    #[test]
+    fn tcp_invalid_bytes() {
+        use crate::sources::util::invalid_bytes::InvalidBytes;
+        use std::io::Write;
+
+        let cases = vec![
+            (InvalidBytes::Replace, vec!["in\u{fffd}valid", "valid"]),
+            (InvalidBytes::Base64, vec!["aW7/dmFsaWQ=", "valid"]),
+            (InvalidBytes::Drop, vec!["valid"]),
+        ];
+        for (invalid_bytes, expected) in cases {
+            let (tx, rx) = mpsc::channel(10);
+            let addr = next_addr();
+
+            let mut config = TcpConfig::new(addr.into());
+            config.invalid_bytes = invalid_bytes;
+            let server = SocketConfig::from(config)
+                .build(
+                    "default",
+                    &GlobalOptions::default(),
+                    ShutdownSignal::noop(),
+                    tx,
+                )
+                .unwrap();
+            let mut rt = runtime::Runtime::new().unwrap();
+            rt.spawn(server);
+            wait_for_tcp(addr);
+
+            std::net::TcpStream::connect(addr)
+                .unwrap()
+                .write_all(b"in\xffvalid\nvalid\n")
+                .unwrap();
+
+            let events = rt.block_on(collect_n(rx, expected.len())).ok().unwrap();
+            let messages = events
+                .iter()
+                .map(|event| event.as_log()[&event::log_schema().message_key()].to_string_lossy())
+                .collect::<Vec<_>>();
+            assert_eq!(messages, expected, "{:?}", invalid_bytes);
+        }
+    }
+
     #[test]
     fn tcp_with_tls() {
         let (tx, rx) = mpsc::channel(10);
@@ -270,6 +361,58 @@ mod test {
         );
     }
 
+    #[test]
+    fn tcp_with_mtls_includes_peer_subject() {
+        use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
+        use std::io::Write;
+
+        let (tx, rx) = mpsc::channel(10);
+
+        let addr = next_addr();
+
+        let mut config = TcpConfig::new(addr.into());
+        config.tls = Some(TlsConfig {
+            enabled: Some(true),
+            options: TlsOptions {
+                crt_path: Some("tests/data/localhost.crt".into()),
+                key_path: Some("tests/data/localhost.key".into()),
+                ca_path: Some("tests/data/Vector_CA.crt".into()),
+                verify_certificate: Some(true),
+                ..Default::default()
+            },
+        });
+
+        let server = SocketConfig::from(config)
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        let mut rt = runtime::Runtime::new().unwrap();
+        rt.spawn(server);
+        wait_for_tcp(addr);
+
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        connector
+            .set_certificate_file("tests/data/localhost.crt", SslFiletype::PEM)
+            .unwrap();
+        connector
+            .set_private_key_file("tests/data/localhost.key", SslFiletype::PEM)
+            .unwrap();
+        let stream = std::net::TcpStream::connect(addr).unwrap();
+        let mut stream = connector.build().connect("localhost", stream).unwrap();
+        stream.write_all(b"hello\n").unwrap();
+
+        let events = rt.block_on(collect_n(rx, 1)).ok().unwrap();
+        assert_eq!(
+            events[0].as_log()[&Atom::from("tls_peer_subject")],
+            "CN=localhost".into()
+        );
+    }
+
     #[test]
     fn tcp_shutdown_simple() {
         let source_name = "tcp_shutdown_simple";
@@ -499,6 +642,33 @@ mod test {
         );
     }
 
+    #[test]
+    fn udp_it_includes_port() {
+        let (tx, rx) = mpsc::channel(2);
+
+        let addr = next_addr();
+        let mut config = UdpConfig::new(addr);
+        config.port_key = Some("port".into());
+        let server = SocketConfig::from(config)
+            .build(
+                "default",
+                &GlobalOptions::default(),
+                ShutdownSignal::noop(),
+                tx,
+            )
+            .unwrap();
+        let mut rt = runtime::Runtime::new().unwrap();
+        rt.spawn(server);
+        thread::sleep(Duration::from_millis(100));
+
+        let from = send_lines_udp(addr, vec!["test".to_string()]);
+        let events = rt.block_on(collect_n(rx, 1)).ok().unwrap();
+
+        let log = events[0].as_log();
+        assert_eq!(log[&event::log_schema().host_key()], "127.0.0.1".into());
+        assert_eq!(log[&Atom::from("port")], (from.port() as i64).into());
+    }
+
     #[test]
     fn udp_shutdown_simple() {
         let (tx, rx) = mpsc::channel(2);
@@ -1,7 +1,7 @@
 use crate::{
     event::{self, Event},
     internal_events::TcpEventReceived,
-    sources::util::{SocketListenAddr, TcpSource},
+    sources::util::{invalid_bytes::InvalidBytes, Connection, SocketListenAddr, TcpSource},
     tls::TlsConfig,
 };
 use bytes::Bytes;
@@ -19,6 +19,12 @@ pub struct TcpConfig {
     #[serde(default = "default_shutdown_timeout_secs")]
     pub shutdown_timeout_secs: u64,
     pub host_key: Option<Atom>,
+    pub port_key: Option<Atom>,
+    pub connection_id_key: Option<Atom>,
+    #[serde(default = "default_tls_peer_subject_key")]
+    pub tls_peer_subject_key: Atom,
+    #[serde(default)]
+    pub invalid_bytes: InvalidBytes,
     pub tls: Option<TlsConfig>,
 }
 
@@ -30,12 +36,20 @@ fn default_shutdown_timeout_secs() -> u64 {
     30
 }
 
+fn default_tls_peer_subject_key() -> Atom {
+    Atom::from("tls_peer_subject")
+}
+
 impl TcpConfig {
     pub fn new(address: SocketListenAddr) -> Self {
         Self {
             address,
             max_length: default_max_length(),
             host_key: None,
+            port_key: None,
+            connection_id_key: None,
+            tls_peer_subject_key: default_tls_peer_subject_key(),
+            invalid_bytes: InvalidBytes::default(),
             shutdown_timeout_secs: default_shutdown_timeout_secs(),
             tls: Default::default(),
         }
@@ -54,9 +68,10 @@ impl TcpSource for RawTcpSource {
         BytesDelimitedCodec::new_with_max_length(b'\n', self.config.max_length)
     }
 
-    fn build_event(&self, frame: Bytes, host: Bytes) -> Option<Event> {
+    fn build_event(&self, frame: Bytes, connection: &Connection) -> Option<Event> {
         let byte_size = frame.len();
-        let mut event = Event::from(frame);
+        let mut event = self.config.invalid_bytes.event(frame, "socket")?;
+        let log = event.as_mut_log();
 
         let host_key = if let Some(key) = &self.config.host_key {
             key
@@ -64,7 +79,16 @@ impl TcpSource for RawTcpSource {
             &event::log_schema().host_key()
         };
 
-        event.as_mut_log().insert(host_key.clone(), host);
+        log.insert(host_key.clone(), connection.host());
+        if let Some(port_key) = &self.config.port_key {
+            log.insert(port_key.clone(), connection.peer_addr.port() as i64);
+        }
+        if let Some(connection_id_key) = &self.config.connection_id_key {
+            log.insert(connection_id_key.clone(), connection.id as i64);
+        }
+        if let Some(subject) = &connection.peer_subject {
+            log.insert(self.config.tls_peer_subject_key.clone(), subject.clone());
+        }
 
         trace!(
             message = "Received one event.",
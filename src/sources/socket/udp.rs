@@ -2,7 +2,10 @@ use crate::{
     event::Event,
     internal_events::{UdpEventReceived, UdpSocketError},
     shutdown::ShutdownSignal,
-    sources::Source,
+    sources::{
+        util::{invalid_bytes::InvalidBytes, peer_ip},
+        Source,
+    },
     stream::StreamExt,
 };
 use bytes::Bytes;
@@ -19,6 +22,9 @@ use tokio01::net::udp::{UdpFramed, UdpSocket};
 pub struct UdpConfig {
     pub address: SocketAddr,
     pub host_key: Option<Atom>,
+    pub port_key: Option<Atom>,
+    #[serde(default)]
+    pub invalid_bytes: InvalidBytes,
 }
 
 impl UdpConfig {
@@ -26,6 +32,8 @@ impl UdpConfig {
         Self {
             address,
             host_key: None,
+            port_key: None,
+            invalid_bytes: InvalidBytes::default(),
         }
     }
 }
@@ -33,6 +41,8 @@ impl UdpConfig {
 pub fn udp(
     address: SocketAddr,
     host_key: Atom,
+    port_key: Option<Atom>,
+    invalid_bytes: InvalidBytes,
     shutdown: ShutdownSignal,
     out: mpsc::Sender<Event>,
 ) -> Source {
@@ -48,20 +58,30 @@ pub fn udp(
         })
         .and_then(move |socket| {
             let host_key = host_key.clone();
+            let port_key = port_key.clone();
             // UDP processes messages per packet, where messages are separated by newline.
             // And stretch to end of packet.
             UdpFramed::with_decode(socket, BytesDelimitedCodec::new(b'\n'), true)
                 .take_until(shutdown)
-                .map(move |(line, addr): (Bytes, _)| {
+                .filter_map(move |(line, addr): (Bytes, SocketAddr)| {
                     let byte_size = line.len();
-                    let mut event = Event::from(line);
+                    let mut event = invalid_bytes.event(line, "socket")?;
+                    let log = event.as_mut_log();
 
-                    event
-                        .as_mut_log()
-                        .insert(host_key.clone(), addr.to_string());
+                    // The host keeps the port, unless it's given its own field.
+                    match &port_key {
+                        Some(port_key) => {
+                            log.insert(host_key.clone(), peer_ip(addr).to_string());
+                            log.insert(port_key.clone(), addr.port() as i64);
+                        }
+                        None => {
+                            let addr = SocketAddr::new(peer_ip(addr), addr.port());
+                            log.insert(host_key.clone(), addr.to_string());
+                        }
+                    }
 
                     emit!(UdpEventReceived { byte_size });
-                    event
+                    Some(event)
                 })
                 // Error from Decoder or UdpSocket
                 .map_err(|error: io::Error| {
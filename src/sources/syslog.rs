@@ -1,8 +1,8 @@
-use super::util::{SocketListenAddr, TcpSource};
+use super::util::{Connection, SocketListenAddr, TcpSource};
 #[cfg(unix)]
 use crate::sources::util::build_unix_source;
 use crate::{
-    event::{self, Event, Value},
+    event::{self, metadata, Event, Value},
     internal_events::{SyslogEventReceived, SyslogUdpReadError},
     shutdown::ShutdownSignal,
     tls::{MaybeTlsSettings, TlsConfig},
@@ -127,8 +127,8 @@ impl TcpSource for SyslogTcpSource {
         LinesCodec::new_with_max_length(self.max_length)
     }
 
-    fn build_event(&self, frame: String, host: Bytes) -> Option<Event> {
-        event_from_str(&self.host_key, Some(host), &frame).map(|event| {
+    fn build_event(&self, frame: String, connection: &Connection) -> Option<Event> {
+        event_from_str(&self.host_key, Some(connection.host()), &frame).map(|event| {
             trace!(
                 message = "Received one event.",
                 event = field::debug(&event)
@@ -202,9 +202,14 @@ fn event_from_str(host_key: &str, default_host: Option<Bytes>, line: &str) -> Op
         byte_size: line.len()
     });
 
+    let byte_size = line.len();
     let line = line.trim();
     let parsed = syslog_loose::parse_message_with_year(line, resolve_year);
     let mut event = Event::from(&parsed.msg[..]);
+    event
+        .as_mut_log()
+        .metadata_mut()
+        .insert(metadata::RECEIVED_BYTES, byte_size as i64);
 
     if let Some(host) = &parsed.hostname {
         event.as_mut_log().insert(host_key, host.clone());
@@ -1,14 +1,15 @@
 use crate::event::Event;
 use crate::tls::{MaybeTlsSettings, TlsConfig};
 use futures01::{sync::mpsc, Future, IntoFuture, Sink};
-use serde::Serialize;
+use headers::{Authorization, HeaderMapExt};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{self, Display};
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use stream_cancel::Tripwire;
 use warp::filters::{body::FullBody, BoxedFilter};
-use warp::http::{HeaderMap, StatusCode};
+use warp::http::{header::AUTHORIZATION, HeaderMap, HeaderValue, StatusCode};
 use warp::{Filter, Rejection};
 
 #[derive(Serialize, Debug)]
@@ -31,6 +32,62 @@ impl Display for ErrorMessage {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", tag = "strategy")]
+pub enum HttpSourceAuthConfig {
+    Basic { user: String, password: String },
+    Bearer { token: String },
+}
+
+impl HttpSourceAuthConfig {
+    fn header_value(&self) -> crate::Result<HeaderValue> {
+        let mut map = HeaderMap::new();
+        match self {
+            HttpSourceAuthConfig::Basic { user, password } => {
+                map.typed_insert(Authorization::basic(user, password))
+            }
+            HttpSourceAuthConfig::Bearer { token } => {
+                map.typed_insert(Authorization::bearer(token).map_err(|_| "Invalid bearer token")?)
+            }
+        }
+        Ok(map
+            .remove(AUTHORIZATION)
+            .expect("Authorization header was just inserted"))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct HttpSourceAuth {
+    expected: Option<HeaderValue>,
+}
+
+impl HttpSourceAuth {
+    fn new(config: &Option<HttpSourceAuthConfig>) -> crate::Result<Self> {
+        let expected = config
+            .as_ref()
+            .map(HttpSourceAuthConfig::header_value)
+            .transpose()?;
+        Ok(Self { expected })
+    }
+
+    fn validate(&self, headers: &HeaderMap) -> Result<(), ErrorMessage> {
+        match &self.expected {
+            None => Ok(()),
+            Some(expected) => match headers.get(AUTHORIZATION) {
+                Some(header) if header == expected => Ok(()),
+                Some(_) => Err(ErrorMessage::new(
+                    StatusCode::UNAUTHORIZED,
+                    "Invalid authorization".to_string(),
+                )),
+                None => Err(ErrorMessage::new(
+                    StatusCode::UNAUTHORIZED,
+                    "No authorization header".to_string(),
+                )),
+            },
+        }
+    }
+}
+
 pub trait HttpSource: Clone + Send + Sync + 'static {
     fn build_event(
         &self,
@@ -43,8 +100,10 @@ pub trait HttpSource: Clone + Send + Sync + 'static {
         address: SocketAddr,
         path: &'static str,
         tls: &Option<TlsConfig>,
+        auth: &Option<HttpSourceAuthConfig>,
         out: mpsc::Sender<Event>,
     ) -> crate::Result<crate::sources::Source> {
+        let auth = HttpSourceAuth::new(auth)?;
         let (trigger, tripwire) = Tripwire::new();
         let trigger = Arc::new(Mutex::new(Some(trigger)));
 
@@ -63,7 +122,8 @@ pub trait HttpSource: Clone + Send + Sync + 'static {
                 let trigger = trigger.clone();
                 info!("Handling http request: {:?}", headers);
 
-                self.build_event(body, headers)
+                auth.validate(&headers)
+                    .and_then(|()| self.build_event(body, headers))
                     .map_err(warp::reject::custom)
                     .into_future()
                     .and_then(|events| {
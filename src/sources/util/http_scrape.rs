@@ -0,0 +1,150 @@
+use crate::{
+    event::metric::Metric,
+    internal_events::{HttpScrapeError, HttpScrapeParseError, HttpScrapeRequestCompleted},
+    shutdown::ShutdownSignal,
+    stream::StreamExt,
+    Event,
+};
+use futures01::{sync::mpsc, Future, Sink, Stream};
+use http::Uri;
+use hyper_openssl::HttpsConnector;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+use tokio01::{timer::Interval, util::FutureExt};
+
+/// Periodically GETs every url and converts each response body into metric
+/// events with `parse`. Failed scrapes are reported and simply retried on the
+/// next tick. No new scrape is started once `shutdown` fires.
+pub fn scrape<F>(
+    urls: Vec<Uri>,
+    interval: Duration,
+    timeout: Duration,
+    component_type: &'static str,
+    parse: F,
+    out: mpsc::Sender<Event>,
+    shutdown: ShutdownSignal,
+) -> crate::sources::Source
+where
+    F: Fn(&Uri, &[u8]) -> Vec<Metric> + Clone + Send + 'static,
+{
+    let out = out.sink_map_err(|e| error!("error sending metric: {:?}", e));
+
+    let task = Interval::new(Instant::now(), interval)
+        .map_err(|e| error!("timer error: {:?}", e))
+        .take_until(shutdown)
+        .map(move |_| futures01::stream::iter_ok(urls.clone()))
+        .flatten()
+        .map(move |url| {
+            let https = HttpsConnector::new(4).expect("TLS initialization failed");
+            let client = hyper::Client::builder().build(https);
+            let parse = parse.clone();
+            let error_url = url.clone();
+
+            let request = hyper::Request::get(&url)
+                .body(hyper::Body::empty())
+                .expect("error creating request");
+
+            client
+                .request(request)
+                .and_then(|response| {
+                    let status = response.status();
+                    response
+                        .into_body()
+                        .concat2()
+                        .map(move |body| (status, body))
+                })
+                .timeout(timeout)
+                .map_err(|error| error.to_string())
+                .and_then(move |(status, body)| {
+                    if !status.is_success() {
+                        return Err(format!("unexpected status {}", status));
+                    }
+                    emit!(HttpScrapeRequestCompleted { component_type });
+                    Ok(futures01::stream::iter_ok(
+                        parse(&url, &body).into_iter().map(Event::Metric),
+                    ))
+                })
+                .flatten_stream()
+                .map_err(move |error| {
+                    emit!(HttpScrapeError {
+                        component_type,
+                        url: &error_url,
+                        error,
+                    });
+                })
+        })
+        .flatten()
+        .forward(out)
+        .map(|_| info!("finished sending"));
+
+    Box::new(task)
+}
+
+/// Parses a scraped status page with `parse`, which is given the page and the
+/// `server` tag of the url. Pages that fail to parse are reported and yield
+/// no metrics.
+pub fn parse_status_page<P, E>(
+    component_type: &'static str,
+    url: &Uri,
+    body: &[u8],
+    parse: P,
+) -> Vec<Metric>
+where
+    P: FnOnce(&str, &BTreeMap<String, String>) -> Result<Vec<Metric>, E>,
+    E: fmt::Display + fmt::Debug,
+{
+    let mut tags = BTreeMap::new();
+    tags.insert("server".to_owned(), server_tag(url));
+    let packet = String::from_utf8_lossy(body);
+    parse(&packet, &tags)
+        .map_err(|error| {
+            emit!(HttpScrapeParseError {
+                component_type,
+                url,
+                error,
+            });
+        })
+        .unwrap_or_default()
+}
+
+/// The `host:port` of the scraped url, used to tag scraped metrics.
+fn server_tag(url: &Uri) -> String {
+    url.authority_part()
+        .map(|authority| authority.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{next_addr, runtime};
+    use stream_cancel::Tripwire;
+
+    #[test]
+    fn scrape_stops_on_shutdown() {
+        let mut rt = runtime();
+        let (begin_shutdown, tripwire) = Tripwire::new();
+        let (shutdown_complete, _) = Tripwire::new();
+        let shutdown = ShutdownSignal::new(tripwire, shutdown_complete);
+
+        // Nothing listens on the address, so the only scrape fails and the
+        // source then waits for the next tick.
+        let url = format!("http://{}/status", next_addr()).parse().unwrap();
+        let (tx, _rx) = mpsc::channel(10);
+        let source = scrape(
+            vec![url],
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+            "http_scrape",
+            |_, _| Vec::new(),
+            tx,
+            shutdown,
+        );
+
+        drop(begin_shutdown);
+        assert!(rt.block_on(source.timeout(Duration::from_secs(5))).is_ok());
+    }
+}
@@ -0,0 +1,113 @@
+//! The `invalid_bytes` option of sources reading raw bytes, for what to do
+//! with messages that aren't valid UTF-8.
+//!
+//! Messages are checked whole, once framed, so a multi-byte character is
+//! never split. Newline delimiters can't cut one in two, as `\n` is never part
+//! of a multi-byte sequence, and frames over the maximum length are discarded
+//! rather than truncated.
+
+use crate::{
+    event::{self, Event},
+    internal_events::InvalidUtf8Message,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// The field telling how the message was encoded, when it isn't text.
+pub const MESSAGE_ENCODING_KEY: &str = "message_encoding";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum InvalidBytes {
+    /// Replaces invalid sequences with U+FFFD, the replacement character.
+    #[derivative(Default)]
+    Replace,
+    /// Encodes the whole message in base64, setting `message_encoding` to
+    /// `base64`.
+    Base64,
+    /// Drops the event.
+    Drop,
+}
+
+impl InvalidBytes {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InvalidBytes::Replace => "replace",
+            InvalidBytes::Base64 => "base64",
+            InvalidBytes::Drop => "drop",
+        }
+    }
+
+    /// Makes an event out of `message`, unless it's invalid and to be
+    /// dropped.
+    pub fn event(self, message: Bytes, component_type: &'static str) -> Option<Event> {
+        if std::str::from_utf8(&message).is_ok() {
+            return Some(Event::from(message));
+        }
+
+        emit!(InvalidUtf8Message {
+            component_type,
+            action: self.as_str(),
+            byte_size: message.len(),
+        });
+        match self {
+            InvalidBytes::Replace => {
+                Some(Event::from(String::from_utf8_lossy(&message).into_owned()))
+            }
+            InvalidBytes::Base64 => {
+                let mut event = Event::from(base64::encode(&message[..]));
+                event.as_mut_log().insert(MESSAGE_ENCODING_KEY, "base64");
+                Some(event)
+            }
+            InvalidBytes::Drop => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use string_cache::DefaultAtom as Atom;
+
+    fn message(event: &Event) -> &event::Value {
+        &event.as_log()[&event::log_schema().message_key()]
+    }
+
+    #[test]
+    fn invalid_bytes_policies() {
+        // "café" with its "é" cut short, then a stray continuation byte.
+        let invalid = Bytes::from(&b"caf\xc3 \xa9t\xc3\xa9"[..]);
+
+        let event = InvalidBytes::Replace
+            .event(invalid.clone(), "test")
+            .unwrap();
+        assert_eq!(message(&event), &"caf\u{fffd} \u{fffd}té".into());
+        assert!(!event.as_log().contains(&Atom::from(MESSAGE_ENCODING_KEY)));
+
+        let event = InvalidBytes::Base64.event(invalid.clone(), "test").unwrap();
+        assert_eq!(
+            base64::decode(&message(&event).as_bytes()[..]).unwrap(),
+            &invalid[..]
+        );
+        assert_eq!(
+            event.as_log()[&Atom::from(MESSAGE_ENCODING_KEY)],
+            "base64".into()
+        );
+
+        assert!(InvalidBytes::Drop.event(invalid, "test").is_none());
+    }
+
+    #[test]
+    fn invalid_bytes_keeps_valid_messages() {
+        for &policy in &[
+            InvalidBytes::Replace,
+            InvalidBytes::Base64,
+            InvalidBytes::Drop,
+        ] {
+            let event = policy.event(Bytes::from("café"), "test").unwrap();
+            assert_eq!(message(&event), &"café".into());
+            assert!(!event.as_log().contains(&Atom::from(MESSAGE_ENCODING_KEY)));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::event::metric::{Metric, MetricKind, MetricValue};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+/// Collects the metrics of one scrape, all under the same namespace and
+/// timestamp, and tagged with the tags of the scraped endpoint plus those of
+/// the metric itself.
+pub struct MetricsBuilder<'a> {
+    namespace: Option<&'a str>,
+    tags: &'a BTreeMap<String, String>,
+    now: DateTime<Utc>,
+    pub metrics: Vec<Metric>,
+}
+
+impl<'a> MetricsBuilder<'a> {
+    pub fn new(namespace: Option<&'a str>, tags: &'a BTreeMap<String, String>) -> Self {
+        Self::at(namespace, tags, Utc::now())
+    }
+
+    /// Timestamps the metrics with `now`, so that parsers can be tested
+    /// against fixed output.
+    pub fn at(
+        namespace: Option<&'a str>,
+        tags: &'a BTreeMap<String, String>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            namespace,
+            tags,
+            now,
+            metrics: Vec::new(),
+        }
+    }
+
+    pub fn counter(&mut self, name: &str, value: f64, tags: &[(&str, &str)]) {
+        self.push(name, MetricValue::Counter { value }, tags);
+    }
+
+    pub fn gauge(&mut self, name: &str, value: f64, tags: &[(&str, &str)]) {
+        self.push(name, MetricValue::Gauge { value }, tags);
+    }
+
+    fn push(&mut self, name: &str, value: MetricValue, tags: &[(&str, &str)]) {
+        let name = match self.namespace {
+            Some(namespace) => format!("{}_{}", namespace, name),
+            None => name.to_owned(),
+        };
+        let mut all_tags = self.tags.clone();
+        for (key, value) in tags {
+            all_tags.insert((*key).to_owned(), (*value).to_owned());
+        }
+        self.metrics.push(Metric {
+            name,
+            timestamp: Some(self.now),
+            tags: if all_tags.is_empty() {
+                None
+            } else {
+                Some(all_tags)
+            },
+            // Scraped values are running totals or point-in-time readings,
+            // so they're always absolute.
+            kind: MetricKind::Absolute,
+            value,
+        });
+    }
+}
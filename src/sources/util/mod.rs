@@ -1,14 +1,40 @@
 #[cfg(feature = "sources-http")]
 mod http;
+#[cfg(any(feature = "sources-apache_metrics", feature = "sources-nginx_metrics"))]
+pub mod http_scrape;
+#[cfg(any(
+    feature = "sources-file",
+    feature = "sources-kafka",
+    feature = "sources-socket"
+))]
+pub mod invalid_bytes;
+#[cfg(any(
+    feature = "sources-apache_metrics",
+    feature = "sources-host_metrics",
+    feature = "sources-mongodb_metrics",
+    feature = "sources-mysql_metrics",
+    feature = "sources-nginx_metrics",
+    feature = "sources-postgresql_metrics"
+))]
+mod metrics_builder;
 #[cfg(feature = "sources-socket")]
 mod tcp;
 #[cfg(all(unix, feature = "sources-socket"))]
 mod unix;
 
 #[cfg(feature = "sources-http")]
-pub use self::http::{ErrorMessage, HttpSource};
+pub use self::http::{ErrorMessage, HttpSource, HttpSourceAuthConfig};
+#[cfg(any(
+    feature = "sources-apache_metrics",
+    feature = "sources-host_metrics",
+    feature = "sources-mongodb_metrics",
+    feature = "sources-mysql_metrics",
+    feature = "sources-nginx_metrics",
+    feature = "sources-postgresql_metrics"
+))]
+pub use metrics_builder::MetricsBuilder;
 #[cfg(feature = "sources-socket")]
-pub use tcp::{SocketListenAddr, TcpSource};
+pub use tcp::{peer_ip, Connection, SocketListenAddr, TcpSource};
 
 #[cfg(all(unix, feature = "sources-socket"))]
 pub use unix::build_unix_source;
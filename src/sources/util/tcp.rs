@@ -2,22 +2,26 @@ use crate::{
     internal_events::TcpConnectionError,
     shutdown::ShutdownSignal,
     stream::StreamExt,
-    tls::{MaybeTlsListener, MaybeTlsSettings},
+    tls::{MaybeTlsIncomingStream, MaybeTlsListener, MaybeTlsSettings},
     Event,
 };
 use bytes::Bytes;
-use futures01::{future, sync::mpsc, Future, Sink, Stream};
+use futures01::{
+    future::{self, Either},
+    sync::mpsc,
+    try_ready, Async, Future, Sink, Stream,
+};
 use listenfd::ListenFd;
 use serde::{de, Deserialize, Deserializer, Serialize};
 use std::{
     fmt, io,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     time::{Duration, Instant},
 };
 use stream_cancel::Tripwire;
 use tokio01::{
     codec::{Decoder, FramedRead},
-    net::TcpListener,
+    net::{TcpListener, TcpStream},
     prelude::AsyncRead,
     reactor::Handle,
     timer,
@@ -58,6 +62,36 @@ fn make_listener(
     }
 }
 
+/// The connection a source reads events from.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub peer_addr: SocketAddr,
+    /// Numbers the connections the source accepted, from 1.
+    pub id: u64,
+    /// The subject of the certificate the peer authenticated with, when the
+    /// source verifies client certificates.
+    pub peer_subject: Option<String>,
+}
+
+impl Connection {
+    /// The peer's IP, without the port, as the event's host.
+    pub fn host(&self) -> Bytes {
+        Bytes::from(peer_ip(self.peer_addr).to_string())
+    }
+}
+
+/// The peer's IP, with IPv4 peers of dual-stack listeners, which show as
+/// IPv4-mapped IPv6 addresses like `::ffff:10.0.0.1`, given as plain IPv4.
+pub fn peer_ip(addr: SocketAddr) -> IpAddr {
+    match addr.ip() {
+        IpAddr::V6(ip) => match ip.segments() {
+            [0, 0, 0, 0, 0, 0xffff, ..] => ip.to_ipv4().map(IpAddr::V4).unwrap_or(IpAddr::V6(ip)),
+            _ => IpAddr::V6(ip),
+        },
+        ip => ip,
+    }
+}
+
 pub trait TcpSource: Clone + Send + 'static {
     type Decoder: Decoder<Error = io::Error> + Send + 'static;
 
@@ -66,7 +100,7 @@ pub trait TcpSource: Clone + Send + 'static {
     fn build_event(
         &self,
         frame: <Self::Decoder as tokio01::codec::Decoder>::Item,
-        host: Bytes,
+        connection: &Connection,
     ) -> Option<Event>;
 
     fn run(
@@ -106,6 +140,7 @@ pub trait TcpSource: Clone + Send + 'static {
                 })
                 .shared();
 
+            let mut connections = 0;
             let future = listener
                 .incoming()
                 .take_until(shutdown)
@@ -116,27 +151,25 @@ pub trait TcpSource: Clone + Send + 'static {
                     )
                 })
                 .for_each(move |socket| {
-                    let peer_addr = socket.peer_addr().ip().to_string();
+                    connections += 1;
+                    let peer_addr = peer_ip(socket.peer_addr()).to_string();
 
                     let span = info_span!("connection", %peer_addr);
 
-                    let host = Bytes::from(peer_addr);
-
-                    let tripwire = tripwire
-                        .clone()
-                        .map(move |_| {
-                            info!(
-                                "Resetting connection (still open after {} seconds).",
-                                shutdown_timeout_secs
-                            )
-                        })
-                        .map_err(|_| ());
-
                     let source = self.clone();
+                    let id = connections;
                     span.in_scope(|| {
                         let peer_addr = socket.peer_addr();
                         debug!(message = "accepted a new connection", %peer_addr);
-                        handle_stream(span.clone(), socket, source, tripwire, host, out.clone())
+                        handle_stream(
+                            span.clone(),
+                            socket,
+                            id,
+                            source,
+                            tripwire.clone(),
+                            shutdown_timeout_secs,
+                            out.clone(),
+                        )
                     });
                     Ok(())
                 })
@@ -148,26 +181,63 @@ pub trait TcpSource: Clone + Send + 'static {
     }
 }
 
-fn handle_stream(
+fn handle_stream<T>(
     span: Span,
-    socket: impl AsyncRead + Send + 'static,
+    socket: MaybeTlsIncomingStream<TcpStream>,
+    id: u64,
     source: impl TcpSource,
-    tripwire: impl Future<Item = (), Error = ()> + Send + 'static,
-    host: Bytes,
+    tripwire: T,
+    shutdown_timeout_secs: u64,
     out: impl Sink<SinkItem = Event, SinkError = ()> + Send + 'static,
-) {
-    let handler = FramedRead::new(socket, source.decoder())
-        .take_until(tripwire)
-        .filter_map(move |frame| {
-            let host = host.clone();
-            source.build_event(frame, host)
+) where
+    T: Future + Clone + Send + 'static,
+{
+    // The handshake is done upfront, so the peer's certificate is known
+    // before the first event is built.
+    let mut socket = Some(socket);
+    let handshake = future::poll_fn(move || {
+        try_ready!(socket.as_mut().expect("polled after ready").poll_accept());
+        Ok(Async::Ready(socket.take().expect("polled after ready")))
+    });
+    let handshake_tripwire = tripwire.clone().map(|_| ()).map_err(|_| ());
+
+    let tripwire = tripwire
+        .map(move |_| {
+            info!(
+                "Resetting connection (still open after {} seconds).",
+                shutdown_timeout_secs
+            )
         })
-        .map_err(|error| {
-            emit!(TcpConnectionError { error });
+        .map_err(|_| ());
+
+    let handler = handshake
+        .map_err(|error| emit!(TcpConnectionError { error }))
+        .select2(handshake_tripwire)
+        .map_err(|_| ())
+        .and_then(|accepted| match accepted {
+            Either::A((socket, _)) => Ok(socket),
+            Either::B(_) => {
+                debug!("connection closed during handshake.");
+                Err(())
+            }
         })
-        .forward(out)
-        .map(|_| debug!("connection closed."))
-        .map_err(|_| warn!("Error received while processing TCP source"));
+        .and_then(move |socket| {
+            let connection = Connection {
+                peer_addr: socket.peer_addr(),
+                id,
+                peer_subject: socket.peer_subject(),
+            };
+
+            FramedRead::new(socket, source.decoder())
+                .take_until(tripwire)
+                .filter_map(move |frame| source.build_event(frame, &connection))
+                .map_err(|error| {
+                    emit!(TcpConnectionError { error });
+                })
+                .forward(out)
+                .map(|_| debug!("connection closed."))
+                .map_err(|_| warn!("Error received while processing TCP source"))
+        });
     tokio01::spawn(handler.instrument(span));
 }
 
@@ -234,4 +304,14 @@ mod test {
         let test: Config = toml::from_str(r#"addr="systemd#3""#).unwrap();
         assert_eq!(test.addr, SocketListenAddr::SystemdFd(2));
     }
+
+    #[test]
+    fn peer_ip_unmaps_ipv4() {
+        let ip = |addr: &str| peer_ip(addr.parse().unwrap()).to_string();
+
+        assert_eq!(ip("10.0.0.1:9000"), "10.0.0.1");
+        assert_eq!(ip("[::ffff:10.0.0.1]:9000"), "10.0.0.1");
+        assert_eq!(ip("[2001:db8::1]:9000"), "2001:db8::1");
+        assert_eq!(ip("[::1]:9000"), "::1");
+    }
 }
@@ -1,15 +1,14 @@
-use super::util::{SocketListenAddr, TcpSource};
+use super::util::{Connection, SocketListenAddr, TcpSource};
 use crate::{
-    event::proto,
-    internal_events::{VectorEventReceived, VectorProtoDecodeError},
+    event::frame,
+    internal_events::{VectorEventReceived, VectorFrameDecodeError},
     shutdown::ShutdownSignal,
     tls::{MaybeTlsSettings, TlsConfig},
     topology::config::{DataType, GlobalOptions, SourceConfig, SourceDescription},
     Event,
 };
-use bytes::{Bytes, BytesMut};
+use bytes::BytesMut;
 use futures01::sync::mpsc;
-use prost::Message;
 use serde::{Deserialize, Serialize};
 use tokio01::codec::LengthDelimitedCodec;
 
@@ -74,15 +73,15 @@ impl TcpSource for VectorSource {
         LengthDelimitedCodec::new()
     }
 
-    fn build_event(&self, frame: BytesMut, _host: Bytes) -> Option<Event> {
+    fn build_event(&self, frame: BytesMut, _connection: &Connection) -> Option<Event> {
         let byte_size = frame.len();
-        match proto::EventWrapper::decode(frame).map(Event::from) {
+        match frame::decode(&frame) {
             Ok(event) => {
                 emit!(VectorEventReceived { byte_size });
                 Some(event)
             }
             Err(error) => {
-                emit!(VectorProtoDecodeError { error });
+                emit!(VectorFrameDecodeError { error });
                 None
             }
         }
@@ -96,8 +95,9 @@ mod test {
     use crate::shutdown::ShutdownSignal;
     use crate::{
         event::{
+            frame::Compression,
             metric::{MetricKind, MetricValue},
-            Metric,
+            Metric, Value,
         },
         sinks::vector::VectorSinkConfig,
         test_util::{next_addr, wait_for_tcp, CollectCurrent},
@@ -109,6 +109,16 @@ mod test {
     use std::net::SocketAddr;
 
     fn stream_test(addr: SocketAddr, source: VectorConfig, sink: VectorSinkConfig) {
+        let mut nested = Event::from("nested fields");
+        let log = nested.as_mut_log();
+        log.insert("integer", 42);
+        log.insert("float", 3.5);
+        log.insert("boolean", false);
+        log.insert("null", Value::Null);
+        log.insert("map.nested.deeply", "value");
+        log.insert("array[0]", "first");
+        log.insert("array[1].key", 1);
+
         let (tx, rx) = mpsc::channel(100);
 
         let server = source
@@ -135,6 +145,7 @@ mod test {
             Event::from("sink"),
             Event::from("and"),
             Event::from("source"),
+            nested,
             Event::Metric(Metric {
                 name: String::from("also test a metric"),
                 timestamp: None,
@@ -156,13 +167,36 @@ mod test {
 
     #[test]
     fn it_works_with_vector_sink() {
+        let addr = next_addr();
+        stream_test(
+            addr,
+            VectorConfig::new(addr.into(), None),
+            VectorSinkConfig::new(format!("localhost:{}", addr.port())),
+        );
+    }
+
+    #[test]
+    fn it_works_with_vector_sink_gzip() {
+        let addr = next_addr();
+        stream_test(
+            addr,
+            VectorConfig::new(addr.into(), None),
+            VectorSinkConfig {
+                compression: Compression::Gzip,
+                ..VectorSinkConfig::new(format!("localhost:{}", addr.port()))
+            },
+        );
+    }
+
+    #[test]
+    fn it_works_with_protocol_version_1() {
         let addr = next_addr();
         stream_test(
             addr,
             VectorConfig::new(addr.into(), None),
             VectorSinkConfig {
-                address: format!("localhost:{}", addr.port()),
-                tls: None,
+                protocol_version: 1,
+                ..VectorSinkConfig::new(format!("localhost:{}", addr.port()))
             },
         );
     }
@@ -184,7 +218,6 @@ mod test {
                 }),
             ),
             VectorSinkConfig {
-                address: format!("localhost:{}", addr.port()),
                 tls: Some(TlsConfig {
                     enabled: Some(true),
                     options: TlsOptions {
@@ -192,6 +225,7 @@ mod test {
                         ..Default::default()
                     },
                 }),
+                ..VectorSinkConfig::new(format!("localhost:{}", addr.port()))
             },
         );
     }
@@ -1,5 +1,5 @@
 use crate::{
-    event::{self, Value},
+    event::{self, TraceEvent, Value},
     Event,
 };
 use bytes::Bytes;
@@ -112,11 +112,12 @@ fn render_fields(src: &str, event: &Event) -> Result<String, Vec<Atom>> {
                 .get(1)
                 .map(|s| Atom::from(s.as_str().trim()))
                 .expect("src should match regex");
-            if let Some(val) = event.as_log().get(&key) {
-                val.to_string_lossy()
-            } else {
-                missing_fields.push(key.clone());
-                String::new()
+            match field_value(event, &key) {
+                Some(val) => val,
+                None => {
+                    missing_fields.push(key.clone());
+                    String::new()
+                }
             }
         })
         .into_owned();
@@ -127,12 +128,30 @@ fn render_fields(src: &str, event: &Event) -> Result<String, Vec<Atom>> {
     }
 }
 
+/// Log and trace events are rendered from their fields. Metric events have a
+/// `name` and their tags, as `tags.<tag>`.
+fn field_value(event: &Event, key: &Atom) -> Option<String> {
+    match event {
+        Event::Log(log) | Event::Trace(TraceEvent(log)) => log.get(key).map(Value::to_string_lossy),
+        Event::Metric(metric) => match &key[..] {
+            "name" => Some(metric.name.clone()),
+            key if key.starts_with("tags.") => metric
+                .tags
+                .as_ref()
+                .and_then(|tags| tags.get(&key["tags.".len()..]))
+                .cloned(),
+            _ => None,
+        },
+    }
+}
+
 fn render_timestamp(src: &str, event: &Event) -> String {
     let timestamp = match event {
-        Event::Log(log) => log
+        Event::Log(log) | Event::Trace(TraceEvent(log)) => log
             .get(&event::log_schema().timestamp_key())
-            .and_then(Value::as_timestamp),
-        _ => None,
+            .and_then(Value::as_timestamp)
+            .cloned(),
+        Event::Metric(metric) => metric.timestamp,
     };
     if let Some(ts) = timestamp {
         ts.format(src).to_string()
@@ -181,6 +200,7 @@ impl Serialize for Template {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::event::metric::{Metric, MetricKind, MetricValue};
     use chrono::TimeZone;
 
     #[test]
@@ -276,6 +296,33 @@ mod tests {
         )
     }
 
+    #[test]
+    fn render_metric_name_and_tags() {
+        let event = Event::Metric(Metric {
+            name: "requests".into(),
+            timestamp: Some(Utc.ymd(2001, 2, 3).and_hms(4, 5, 6)),
+            tags: Some(
+                vec![("host".to_owned(), "web-1".to_owned())]
+                    .into_iter()
+                    .collect(),
+            ),
+            kind: MetricKind::Incremental,
+            value: MetricValue::Counter { value: 1.0 },
+        });
+
+        let template = Template::from("{{ name }}-{{ tags.host }}-%Y");
+        assert_eq!(
+            Ok(Bytes::from("requests-web-1-2001")),
+            template.render(&event)
+        );
+
+        let template = Template::from("{{ tags.region }}");
+        assert_eq!(
+            Err(vec![Atom::from("tags.region")]),
+            template.render(&event)
+        );
+    }
+
     #[test]
     fn render_timestamp_strftime_style() {
         let ts = Utc.ymd(2001, 2, 3).and_hms(4, 5, 6);
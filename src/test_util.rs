@@ -201,6 +201,15 @@ pub fn collect_n<T>(mut rx: mpsc::Receiver<T>, n: usize) -> impl Future<Item = V
     })
 }
 
+/// Asserts that two events are equal, including the metadata of log events
+/// that their `PartialEq` leaves out.
+pub fn assert_event_eq_with_metadata(left: &Event, right: &Event) {
+    assert_eq!(left, right);
+    if let (Event::Log(left), Event::Log(right)) = (left, right) {
+        assert_eq!(left.metadata(), right.metadata());
+    }
+}
+
 pub fn lines_from_file<P: AsRef<Path>>(path: P) -> Vec<String> {
     trace!(message = "Reading file.", path = %path.as_ref().display());
     let mut file = File::open(path).unwrap();
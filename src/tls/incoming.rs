@@ -2,8 +2,11 @@ use super::{
     CreateAcceptor, IncomingListener, MaybeTlsSettings, MaybeTlsStream, PeerAddress, Result,
     TcpBind, TlsError, TlsSettings,
 };
-use futures01::{try_ready, Async, Future, Stream};
-use openssl::ssl::{HandshakeError, SslAcceptor, SslMethod};
+use futures01::{try_ready, Async, Future, Poll, Stream};
+use openssl::{
+    ssl::{HandshakeError, SslAcceptor, SslMethod},
+    x509::X509NameRef,
+};
 use snafu::ResultExt;
 use std::{
     fmt::{self, Debug, Formatter},
@@ -123,6 +126,54 @@ impl<S> MaybeTlsIncomingStream<S> {
     pub fn peer_addr(&self) -> SocketAddr {
         self.peer_addr
     }
+
+    /// The subject of the certificate the peer presented, like
+    /// `CN=client,O=Vector`. Only known once the handshake is done, and only
+    /// asked for by sources verifying client certificates.
+    pub fn peer_subject(&self) -> Option<String> {
+        match &self.state {
+            StreamState::Accepted(MaybeTlsStream::Tls(stream)) => stream
+                .get_ref()
+                .ssl()
+                .peer_certificate()
+                .map(|certificate| format_name(certificate.subject_name())),
+            _ => None,
+        }
+    }
+}
+
+impl<S: Read + Write> MaybeTlsIncomingStream<S> {
+    /// Drives the TLS handshake, if any, to completion, which otherwise
+    /// happens on the first read or write.
+    pub fn poll_accept(&mut self) -> Poll<(), io::Error> {
+        loop {
+            match &mut self.state {
+                StreamState::Accepted(_) => return Ok(Async::Ready(())),
+                StreamState::Accepting(acceptor) => match poll_handshake(acceptor) {
+                    Ok(state) => self.state = state,
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(error) => return Err(error),
+                },
+            }
+        }
+    }
+}
+
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|value| value.to_string())
+                .unwrap_or_else(|_| String::from_utf8_lossy(entry.data().as_slice()).into_owned());
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 impl MaybeTlsIncomingStream<TcpStream> {
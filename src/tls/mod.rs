@@ -17,7 +17,7 @@ mod outgoing;
 mod settings;
 
 #[cfg(feature = "sources-tls")]
-pub(crate) use incoming::MaybeTlsListener;
+pub(crate) use incoming::{MaybeTlsIncomingStream, MaybeTlsListener};
 pub(crate) use maybe_tls::MaybeTls;
 pub(crate) use outgoing::MaybeTlsConnector;
 pub use settings::{MaybeTlsSettings, TlsConfig, TlsOptions, TlsSettings};
@@ -121,7 +121,7 @@ pub(crate) fn tls_connector_builder(settings: &MaybeTlsSettings) -> Result<SslCo
     Ok(builder)
 }
 
-fn tls_connector(settings: &MaybeTlsSettings) -> Result<ConnectConfiguration> {
+pub(crate) fn tls_connector(settings: &MaybeTlsSettings) -> Result<ConnectConfiguration> {
     let verify_hostname = settings
         .tls()
         .map(|settings| settings.verify_hostname)
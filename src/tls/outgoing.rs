@@ -1,12 +1,15 @@
 use super::{tls_connector, MaybeTlsSettings, MaybeTlsStream, Result, TlsError};
+use crate::dns::HappyEyeballs;
 use futures01::{Async, Future};
 use openssl::ssl::{ConnectConfiguration, HandshakeError};
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Duration};
 use tokio01::net::tcp::{ConnectFuture, TcpStream};
 use tokio_openssl::{ConnectAsync, ConnectConfigurationExt};
 
+type TcpConnector = HappyEyeballs<fn(&SocketAddr) -> ConnectFuture, ConnectFuture>;
+
 enum State {
-    Connecting(ConnectFuture, Option<ConnectConfiguration>),
+    Connecting(TcpConnector, Option<ConnectConfiguration>),
     Negotiating(ConnectAsync<TcpStream>),
 }
 
@@ -18,8 +21,14 @@ pub(crate) struct MaybeTlsConnector {
 }
 
 impl MaybeTlsConnector {
-    fn new(host: String, addr: SocketAddr, tls: &MaybeTlsSettings) -> Result<Self> {
-        let connector = TcpStream::connect(&addr);
+    fn new(
+        host: String,
+        addrs: Vec<SocketAddr>,
+        delay: Duration,
+        tls: &MaybeTlsSettings,
+    ) -> Result<Self> {
+        let connect: fn(&SocketAddr) -> ConnectFuture = TcpStream::connect;
+        let connector = HappyEyeballs::new(addrs, delay, connect);
         let tls_connector = match tls {
             MaybeTlsSettings::Raw(()) => None,
             MaybeTlsSettings::Tls(_) => Some(tls_connector(tls)?),
@@ -75,7 +84,14 @@ impl Future for MaybeTlsConnector {
 }
 
 impl MaybeTlsSettings {
-    pub(crate) fn connect(&self, host: String, addr: SocketAddr) -> Result<MaybeTlsConnector> {
-        MaybeTlsConnector::new(host, addr, self)
+    /// Connects to whichever of `addrs` accepts first, starting a connection
+    /// attempt to the next address every `delay`, see `HappyEyeballs`.
+    pub(crate) fn connect(
+        &self,
+        host: String,
+        addrs: Vec<SocketAddr>,
+        delay: Duration,
+    ) -> Result<MaybeTlsConnector> {
+        MaybeTlsConnector::new(host, addrs, delay, self)
     }
 }
@@ -1,19 +1,35 @@
 use super::{
-    config::{DataType, SinkContext, TransformContext},
+    config::{DataType, OnPanic, SinkContext, TransformContext},
     fanout::{self, Fanout},
+    isolate::IsolatePanics,
     task::Task,
 };
-use crate::{buffers, dns::Resolver, event::Event, runtime, shutdown::SourceShutdownCoordinator};
+use crate::{
+    buffers::{self, Acker},
+    conditions::Condition,
+    dns::Resolver,
+    event::{self, metadata, Event, TraceEvent, Value},
+    internal_events::{
+        ComponentEventReceived, ComponentEventSent, SinkHealthcheckFinished, SourceEventReceived,
+    },
+    runtime,
+    shutdown::SourceShutdownCoordinator,
+    sinks::{util::DeadLetter, RouterSink},
+    topology::size_limit::{self, SizeLimiter},
+};
+use chrono::Utc;
 use futures01::{
     future::{lazy, Either},
+    stream::iter_ok,
     sync::mpsc,
-    Future, Stream,
+    Future, Sink, Stream,
 };
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio01::util::FutureExt;
+use tracing_futures::Instrument;
 
 pub struct Pieces {
-    pub inputs: HashMap<String, (buffers::BufferInputCloner, Vec<String>)>,
+    pub inputs: HashMap<String, (Input, Vec<String>)>,
     pub outputs: HashMap<String, fanout::ControlChannel>,
     pub tasks: HashMap<String, Task>,
     pub source_tasks: HashMap<String, Task>,
@@ -35,7 +51,17 @@ pub fn check(config: &super::Config) -> Result<Vec<String>, Vec<String>> {
         .iter()
         .map(|(name, transform)| ("transform", name.clone(), transform.inputs.clone()));
     for (output_type, name, inputs) in sink_inputs.chain(transform_inputs) {
-        if inputs.is_empty() {
+        // Dead-letter sinks may get all their events from the sinks using them.
+        let is_dead_letter = output_type == "sink"
+            && (config
+                .sinks
+                .values()
+                .any(|sink| sink.dead_letter.as_ref() == Some(&name))
+                || config
+                    .transforms
+                    .values()
+                    .any(|transform| transform.dead_letter.as_ref() == Some(&name)));
+        if inputs.is_empty() && !is_dead_letter {
             errors.push(format!(
                 "{} {:?} has no inputs",
                 capitalize(output_type),
@@ -53,6 +79,57 @@ pub fn check(config: &super::Config) -> Result<Vec<String>, Vec<String>> {
         }
     }
 
+    let sink_dead_letters = config
+        .sinks
+        .iter()
+        .map(|(name, sink)| ("sink", name, &sink.dead_letter));
+    let transform_dead_letters = config
+        .transforms
+        .iter()
+        .map(|(name, transform)| ("transform", name, &transform.dead_letter));
+    for (kind, name, target) in sink_dead_letters.chain(transform_dead_letters) {
+        let target = match target {
+            Some(target) => target,
+            None => continue,
+        };
+        match config.sinks.get(target) {
+            None => errors.push(format!(
+                "Dead-letter sink {:?} for {} {:?} doesn't exist.",
+                target, kind, name
+            )),
+            Some(_) if kind == "sink" && target == name => errors.push(format!(
+                "Sink {:?} can't be its own dead-letter sink.",
+                name
+            )),
+            // Dead-letter sinks drop what they fail on, so a failing one can't
+            // start events going round in circles.
+            Some(dead_letter) if dead_letter.dead_letter.is_some() => errors.push(format!(
+                "Dead-letter sink {:?} for {} {:?} can't have a dead-letter sink of its own.",
+                target, kind, name
+            )),
+            Some(_) => (),
+        }
+    }
+
+    for (name, transform) in &config.transforms {
+        if transform.dead_letter.is_some() && transform.on_panic != OnPanic::DropEvent {
+            warnings.push(format!(
+                "Transform {:?} has a dead-letter sink, but only sends it events with `on_panic = \"drop_event\"`.",
+                name
+            ));
+        }
+    }
+
+    for (name, sink) in &config.sinks {
+        if sink.acknowledgements.enabled == Some(true) && !sink.inner.can_acknowledge() {
+            errors.push(format!(
+                "Sink {:?} can't enable acknowledgements, as {} sinks can't confirm delivery.",
+                name,
+                sink.inner.sink_type()
+            ));
+        }
+    }
+
     let source_names = config.sources.keys().map(|name| ("source", name.clone()));
     let transform_names = config
         .transforms
@@ -87,6 +164,22 @@ pub fn check(config: &super::Config) -> Result<Vec<String>, Vec<String>> {
     }
 }
 
+/// The size of an event as its source received it, in bytes: what the source
+/// recorded under `RECEIVED_BYTES` if it decoded the event, or the size of
+/// its message, which most sources take as is from what they receive, or
+/// else the size of the event encoded as JSON.
+fn take_received_bytes(event: &mut Event) -> usize {
+    if let Event::Log(log) | Event::Trace(TraceEvent(log)) = event {
+        if let Some(Value::Integer(size)) = log.metadata_mut().remove(metadata::RECEIVED_BYTES) {
+            return size as usize;
+        }
+        if let Some(Value::Bytes(message)) = log.get(event::log_schema().message_key()) {
+            return message.len();
+        }
+    }
+    size_limit::serialized_size(event)
+}
+
 pub fn build_pieces(
     config: &super::Config,
     exec: runtime::TaskExecutor,
@@ -96,6 +189,7 @@ pub fn build_pieces(
     let mut tasks = HashMap::new();
     let mut source_tasks = HashMap::new();
     let mut healthchecks = HashMap::new();
+    let mut dead_letters = Vec::new();
     let mut shutdown_coordinator = SourceShutdownCoordinator::new();
 
     let mut errors = vec![];
@@ -109,7 +203,12 @@ pub fn build_pieces(
     }
 
     // TODO: remove the unimplemented
-    let resolver = Resolver::new(config.global.dns_servers.clone(), exec.clone()).unwrap();
+    let resolver = Resolver::with_options(
+        config.global.dns_servers.clone(),
+        config.global.dns.clone(),
+        exec.clone(),
+    )
+    .unwrap();
 
     // Build sources
     for (name, source) in &config.sources {
@@ -119,16 +218,44 @@ pub fn build_pieces(
 
         let (shutdown_signal, force_shutdown_tripwire) = shutdown_coordinator.register_source(name);
 
-        let server = match source.build(&name, &config.global, shutdown_signal, tx) {
-            Err(error) => {
-                errors.push(format!("Source \"{}\": {}", name, error));
-                continue;
-            }
-            Ok(server) => server,
-        };
+        let span = info_span!(
+            "source",
+            component_kind = "source",
+            component_id = %name,
+            component_type = %typetag,
+        );
+        let server =
+            match span.in_scope(|| source.build(&name, &config.global, shutdown_signal, tx)) {
+                Err(error) => {
+                    errors.push(format!("Source \"{}\": {}", name, error));
+                    continue;
+                }
+                Ok(server) => server,
+            };
 
         let (output, control) = Fanout::new();
-        let pump = rx.forward(output).map(|_| ());
+        let source_id: Arc<str> = Arc::from(name.as_str());
+        let pump = rx
+            .map(move |mut event| {
+                // Counted as events leave the channel, so events a source
+                // retries sending are counted once.
+                emit!(SourceEventReceived {
+                    component: &source_id,
+                    byte_size: take_received_bytes(&mut event),
+                });
+                if let Event::Log(log) = &mut event {
+                    log.metadata_mut().set_source_id(Arc::clone(&source_id));
+                    log.metadata_mut().insert(metadata::INGESTED_AT, Utc::now());
+                }
+                emit!(ComponentEventSent {
+                    kind: "source",
+                    component: &source_id
+                });
+                event
+            })
+            .forward(output)
+            .map(|_| ())
+            .instrument(span.clone());
         let pump = Task::new(&name, &typetag, pump);
 
         // The force_shutdown_tripwire is a Future that when it resolves means that this source
@@ -139,7 +266,8 @@ pub fn build_pieces(
         let server = server
             .select(force_shutdown_tripwire)
             .map(|_| ())
-            .map_err(|_| ());
+            .map_err(|_| ())
+            .instrument(span);
         let server = Task::new(&name, &typetag, server);
 
         outputs.insert(name.clone(), control);
@@ -158,8 +286,23 @@ pub fn build_pieces(
             exec: exec.clone(),
         };
 
+        let span = info_span!(
+            "transform",
+            component_kind = "transform",
+            component_id = %name,
+            component_type = %typetag,
+        );
         let input_type = transform.inner.input_type();
-        let transform = match transform.inner.build(cx) {
+        let on_panic = transform.on_panic;
+        let dead_letter = match &transform.dead_letter {
+            Some(target) => {
+                let (tx, rx) = mpsc::channel(100);
+                dead_letters.push((name.clone(), target.clone(), rx, span.clone()));
+                DeadLetter::new(&name, tx)
+            }
+            None => DeadLetter::disabled(),
+        };
+        let transform = match span.in_scope(|| transform.inner.build(cx)) {
             Err(error) => {
                 errors.push(format!("Transform \"{}\": {}", name, error));
                 continue;
@@ -168,14 +311,40 @@ pub fn build_pieces(
         };
 
         let (input_tx, input_rx) = futures01::sync::mpsc::channel(100);
-        let input_tx = buffers::BufferInputCloner::Memory(input_tx, buffers::WhenFull::Block);
+        let input_tx = Input::new(buffers::BufferInputCloner::Memory(
+            input_tx,
+            buffers::WhenFull::Block,
+        ));
 
         let (output, control) = Fanout::new();
 
-        let transform = transform
-            .transform_stream(filter_event_type(input_rx, input_type))
+        let (received_id, sent_id) = (name.clone(), name.clone());
+        let input_rx = input_rx.inspect(move |_| {
+            emit!(ComponentEventReceived {
+                kind: "transform",
+                component: &received_id
+            })
+        });
+        let input_rx = filter_event_type(input_rx, input_type);
+        let transformed: Box<dyn Stream<Item = Event, Error = ()> + Send> = match on_panic {
+            OnPanic::Abort => transform.transform_stream(input_rx),
+            OnPanic::DropEvent => Box::new(IsolatePanics::new(
+                |input| transform.transform_stream(input),
+                input_rx,
+                &name,
+                dead_letter,
+            )),
+        };
+        let transform = transformed
+            .inspect(move |_| {
+                emit!(ComponentEventSent {
+                    kind: "transform",
+                    component: &sent_id
+                })
+            })
             .forward(output)
-            .map(|_| ());
+            .map(|_| ())
+            .instrument(span);
         let task = Task::new(&name, &typetag, transform);
 
         inputs.insert(name.clone(), (input_tx, trans_inputs.clone()));
@@ -191,6 +360,13 @@ pub fn build_pieces(
         let typetag = sink.inner.sink_type();
         let input_type = sink.inner.input_type();
 
+        let span = info_span!(
+            "sink",
+            component_kind = "sink",
+            component_id = %name,
+            component_type = %typetag,
+        );
+
         let buffer = sink.buffer.build(&config.global.data_dir, &name);
         let (tx, rx, acker) = match buffer {
             Err(error) => {
@@ -200,13 +376,58 @@ pub fn build_pieces(
             Ok(buffer) => buffer,
         };
 
+        // Acking events as they're received lets the buffer let go of them
+        // before the sink is done with them.
+        let (rx, acker) = match sink.acknowledgements.enabled {
+            Some(false) => (ack_on_receive(rx, acker), Acker::Null),
+            _ => (rx, acker),
+        };
+
+        // Expired events are dropped as they leave the buffer, so that time
+        // spent in it counts toward their age. They're acked as they're
+        // dropped, as the sink never sees them.
+        let rx: Box<dyn Stream<Item = Event, Error = ()> + Send> =
+            match sink.max_age.as_ref().map(|max_age| max_age.build(&name)) {
+                Some(max_age) => {
+                    let acker = acker.clone();
+                    Box::new(rx.filter(move |event| {
+                        let keep = max_age.check(event);
+                        if !keep {
+                            acker.ack(1);
+                        }
+                        keep
+                    }))
+                }
+                None => rx,
+            };
+
+        let condition = match sink.condition.as_ref().map(|condition| condition.build()) {
+            Some(Err(error)) => {
+                errors.push(format!("Sink \"{}\": Invalid condition: {}", name, error));
+                continue;
+            }
+            Some(Ok(condition)) => Some(Arc::from(condition)),
+            None => None,
+        };
+
+        let dead_letter = match &sink.dead_letter {
+            Some(target) => {
+                let (tx, rx) = mpsc::channel(100);
+                dead_letters.push((name.clone(), target.clone(), rx, span.clone()));
+                DeadLetter::new(&name, tx)
+            }
+            None => DeadLetter::disabled(),
+        };
+
         let cx = SinkContext {
+            name: name.clone(),
             resolver: resolver.clone(),
             acker,
             exec: exec.clone(),
+            dead_letter,
         };
 
-        let (sink, healthcheck) = match sink.inner.build(cx) {
+        let (sink, healthcheck) = match span.in_scope(|| sink.inner.build(cx)) {
             Err(error) => {
                 errors.push(format!("Sink \"{}\": {}", name, error));
                 continue;
@@ -214,15 +435,38 @@ pub fn build_pieces(
             Ok((sink, healthcheck)) => (sink, healthcheck),
         };
 
-        let sink = filter_event_type(rx, input_type).forward(sink).map(|_| ());
+        let received_id = name.clone();
+        let sink = filter_event_type(rx, input_type)
+            .inspect(move |_| {
+                emit!(ComponentEventReceived {
+                    kind: "sink",
+                    component: &received_id
+                })
+            })
+            .forward(sink)
+            .map(|_| ())
+            .instrument(span.clone());
         let task = Task::new(&name, &typetag, sink);
 
         let healthcheck_task = if enable_healthcheck {
+            let (passed_id, failed_id) = (name.clone(), name.clone());
             let healthcheck_task = healthcheck
                 // TODO: Add healthcheck timeouts per sink
                 .timeout(Duration::from_secs(10))
-                .map(move |_| info!("Healthcheck: Passed."))
-                .map_err(move |err| error!("Healthcheck: Failed Reason: {}", err));
+                .map(move |_| {
+                    info!("Healthcheck: Passed.");
+                    emit!(SinkHealthcheckFinished {
+                        component: &passed_id,
+                        healthy: true
+                    });
+                })
+                .map_err(move |err| {
+                    error!("Healthcheck: Failed Reason: {}", err);
+                    emit!(SinkHealthcheckFinished {
+                        component: &failed_id,
+                        healthy: false
+                    });
+                });
             Either::A(healthcheck_task)
         } else {
             Either::B(lazy(|| {
@@ -230,13 +474,38 @@ pub fn build_pieces(
                 Ok(())
             }))
         };
-        let healthcheck_task = Task::new(&name, &typetag, healthcheck_task);
+        let healthcheck_task = Task::new(&name, &typetag, healthcheck_task.instrument(span));
+
+        let size_limit = sink
+            .max_event_size
+            .as_ref()
+            .map(|config| Arc::new(config.build(&name)));
 
+        let tx = Input {
+            buffer: tx,
+            condition,
+            size_limit,
+        };
         inputs.insert(name.clone(), (tx, sink_inputs.clone()));
         healthchecks.insert(name.clone(), healthcheck_task);
         tasks.insert(name.clone(), task);
     }
 
+    // Feed each component's dead letters into the input of its dead-letter
+    // sink. The pump runs as part of the failing component's task, and
+    // finishes with it.
+    for (name, target, rx, span) in dead_letters {
+        if let (Some(task), Some((target_tx, _))) = (tasks.remove(&name), inputs.get(&target)) {
+            let typetag = task.typetag().to_owned();
+            let pump = rx
+                .forward(target_tx.get())
+                .then(|_| Ok::<_, ()>(()))
+                .instrument(span);
+            let task = Task::new(&name, &typetag, task.join(pump).map(|_| ()));
+            tasks.insert(name, task);
+        }
+    }
+
     // Warnings and errors
     match check(&config) {
         Err(check_errors) => {
@@ -263,6 +532,46 @@ pub fn build_pieces(
     }
 }
 
+/// Where the fanouts of a component's inputs send events to: its buffer, or
+/// the channel of a transform, behind the sink's `condition` and
+/// `max_event_size`, if any.
+pub struct Input {
+    buffer: buffers::BufferInputCloner,
+    condition: Option<Arc<dyn Condition>>,
+    size_limit: Option<Arc<SizeLimiter>>,
+}
+
+impl Input {
+    pub fn new(buffer: buffers::BufferInputCloner) -> Self {
+        Input {
+            buffer,
+            condition: None,
+            size_limit: None,
+        }
+    }
+
+    pub fn get(&self) -> RouterSink {
+        let mut buffer = self.buffer.get();
+        // Oversized events are trimmed, split or dropped before reaching the
+        // buffer, so the sink acks what it's actually given.
+        if let Some(size_limit) = &self.size_limit {
+            let size_limit = Arc::clone(size_limit);
+            buffer = Box::new(buffer.with_flat_map(move |event| iter_ok(size_limit.limit(event))));
+        }
+        match &self.condition {
+            // Events failing the condition are dropped before reaching the
+            // buffer, so they're never acked or retried by the sink.
+            Some(condition) => {
+                let condition = Arc::clone(condition);
+                Box::new(buffer.with_flat_map(move |event| {
+                    iter_ok(Some(event).filter(|event| condition.check(event)))
+                }))
+            }
+            None => buffer,
+        }
+    }
+}
+
 fn capitalize(s: &str) -> String {
     let mut s = s.to_owned();
     if let Some(r) = s.get_mut(0..1) {
@@ -271,6 +580,13 @@ fn capitalize(s: &str) -> String {
     s
 }
 
+fn ack_on_receive<S>(stream: S, acker: Acker) -> Box<dyn Stream<Item = Event, Error = ()> + Send>
+where
+    S: Stream<Item = Event, Error = ()> + Send + 'static,
+{
+    Box::new(stream.inspect(move |_| acker.ack(1)))
+}
+
 fn filter_event_type<S>(
     stream: S,
     data_type: DataType,
@@ -288,5 +604,9 @@ where
             Event::Metric(_) => true,
             _ => false,
         })),
+        DataType::Trace => Box::new(stream.filter(|event| match event {
+            Event::Trace(_) => true,
+            _ => false,
+        })),
     }
 }
@@ -5,7 +5,11 @@ use crate::{
     event::{self, Event, Metric},
     runtime::TaskExecutor,
     shutdown::ShutdownSignal,
-    sinks, sources, transforms,
+    sinks::{
+        self,
+        util::{retries::RetryBudgetConfig, DeadLetter},
+    },
+    sources, transforms,
 };
 use component::ComponentDescription;
 use futures01::sync::mpsc;
@@ -45,7 +49,20 @@ pub struct GlobalOptions {
         skip_serializing_if = "crate::serde::skip_serializing_if_default",
         default
     )]
+    pub dns: crate::dns::Options,
+    #[serde(
+        skip_serializing_if = "crate::serde::skip_serializing_if_default",
+        default
+    )]
     pub log_schema: event::LogSchema,
+    #[serde(
+        skip_serializing_if = "crate::serde::skip_serializing_if_default",
+        default
+    )]
+    pub api: crate::api::Options,
+    /// The retry budget shared by the sinks without one of their own.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub retry_budget: Option<RetryBudgetConfig>,
 }
 
 pub fn default_data_dir() -> Option<PathBuf> {
@@ -123,6 +140,7 @@ pub enum DataType {
     Any,
     Log,
     Metric,
+    Trace,
 }
 
 #[typetag::serde(tag = "type")]
@@ -151,10 +169,47 @@ pub struct SinkOuter {
     #[serde(default = "healthcheck_default")]
     pub healthcheck: bool,
     pub inputs: Vec<String>,
+    /// Sink that receives the events this sink gives up on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dead_letter: Option<String>,
+    /// Only events matching the condition are sent to the sink, the others
+    /// are dropped before reaching its buffer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<conditions::AnyCondition>,
+    #[serde(default, skip_serializing_if = "AcknowledgementsConfig::is_unset")]
+    pub acknowledgements: AcknowledgementsConfig,
+    /// Events over the size are trimmed, split or dropped before reaching
+    /// the sink.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_event_size: Option<crate::topology::size_limit::MaxEventSizeConfig>,
+    /// Events older than the age are dropped as they leave the buffer,
+    /// before reaching the sink.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<crate::topology::max_age::MaxAgeConfig>,
     #[serde(flatten)]
     pub inner: Box<dyn SinkConfig>,
 }
 
+/// When a sink acknowledges the events it receives, which lets its buffer
+/// let go of them.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AcknowledgementsConfig {
+    /// If unset, the sink acknowledges events when it's done with them,
+    /// which for sinks that can confirm delivery already means once it's
+    /// confirmed. If true, the sink must be one of those, so a config that
+    /// relies on confirmation fails to load rather than silently acking
+    /// events as they're written out. If false, events are acknowledged as
+    /// soon as the sink receives them.
+    pub enabled: Option<bool>,
+}
+
+impl AcknowledgementsConfig {
+    fn is_unset(&self) -> bool {
+        self.enabled.is_none()
+    }
+}
+
 #[typetag::serde(tag = "type")]
 pub trait SinkConfig: core::fmt::Debug {
     fn build(&self, cx: SinkContext) -> crate::Result<(sinks::RouterSink, sinks::Healthcheck)>;
@@ -162,25 +217,47 @@ pub trait SinkConfig: core::fmt::Debug {
     fn input_type(&self) -> DataType;
 
     fn sink_type(&self) -> &'static str;
+
+    /// Whether the sink only acks events once their delivery is confirmed,
+    /// like by the response of the service it sends them to, rather than
+    /// once it has written them out.
+    fn can_acknowledge(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SinkContext {
+    pub(super) name: String,
     pub(super) acker: Acker,
     pub(super) resolver: Resolver,
     pub(super) exec: TaskExecutor,
+    pub(super) dead_letter: DeadLetter,
 }
 
 impl SinkContext {
     #[cfg(test)]
     pub fn new_test(exec: TaskExecutor) -> Self {
         Self {
+            name: "test".into(),
             acker: Acker::Null,
             resolver: Resolver::new(Vec::new(), exec.clone()).unwrap(),
             exec,
+            dead_letter: DeadLetter::disabled(),
         }
     }
 
+    #[cfg(test)]
+    pub fn with_dead_letter(mut self, dead_letter: DeadLetter) -> Self {
+        self.dead_letter = dead_letter;
+        self
+    }
+
+    /// The sink's component id, as used for its `[sinks.<id>]` table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn acker(&self) -> Acker {
         self.acker.clone()
     }
@@ -196,6 +273,10 @@ impl SinkContext {
     pub fn executor(&self) -> &TaskExecutor {
         &self.exec
     }
+
+    pub fn dead_letter(&self) -> DeadLetter {
+        self.dead_letter.clone()
+    }
 }
 
 pub type SinkDescription = ComponentDescription<Box<dyn SinkConfig>>;
@@ -205,10 +286,39 @@ inventory::collect!(SinkDescription);
 #[derive(Deserialize, Serialize, Debug)]
 pub struct TransformOuter {
     pub inputs: Vec<String>,
+    #[serde(default, skip_serializing_if = "OnPanic::is_abort")]
+    pub on_panic: OnPanic,
+    /// Sink that receives the events the transform panicked on, when they
+    /// are dropped rather than aborting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dead_letter: Option<String>,
     #[serde(flatten)]
     pub inner: Box<dyn TransformConfig>,
 }
 
+/// What happens when a transform panics on an event.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OnPanic {
+    /// Vector shuts down, as on any other crashed component.
+    Abort,
+    /// The event is dropped, or handed to the `dead_letter` sink, and the
+    /// transform goes on with the next one.
+    DropEvent,
+}
+
+impl OnPanic {
+    fn is_abort(&self) -> bool {
+        *self == OnPanic::Abort
+    }
+}
+
+impl Default for OnPanic {
+    fn default() -> Self {
+        OnPanic::Abort
+    }
+}
+
 #[typetag::serde(tag = "type")]
 pub trait TransformConfig: core::fmt::Debug {
     fn build(&self, cx: TransformContext) -> crate::Result<Box<dyn transforms::Transform>>;
@@ -313,7 +423,10 @@ impl Config {
             global: GlobalOptions {
                 data_dir: None,
                 dns_servers: Vec::new(),
+                dns: Default::default(),
                 log_schema: event::LogSchema::default(),
+                api: Default::default(),
+                retry_budget: None,
             },
             sources: IndexMap::new(),
             sinks: IndexMap::new(),
@@ -333,6 +446,11 @@ impl Config {
             healthcheck: true,
             inner: Box::new(sink),
             inputs,
+            dead_letter: None,
+            condition: None,
+            acknowledgements: Default::default(),
+            max_event_size: None,
+            max_age: None,
         };
 
         self.sinks.insert(name.to_string(), sink);
@@ -348,6 +466,8 @@ impl Config {
         let transform = TransformOuter {
             inner: Box::new(transform),
             inputs,
+            on_panic: Default::default(),
+            dead_letter: None,
         };
 
         self.transforms.insert(name.to_string(), transform);
@@ -376,6 +496,8 @@ impl Config {
                         full_name.clone(),
                         TransformOuter {
                             inputs: t.inputs.clone(),
+                            on_panic: t.on_panic,
+                            dead_letter: t.dead_letter.clone(),
                             inner: child,
                         },
                     );
@@ -409,7 +531,9 @@ impl Config {
         }
         let with_vars = vars::interpolate(&source_string, &vars);
 
-        toml::from_str(&with_vars).map_err(|e| vec![e.to_string()])
+        let config: Self = toml::from_str(&with_vars).map_err(|e| vec![e.to_string()])?;
+        config.global.log_schema.validate()?;
+        Ok(config)
     }
 
     pub fn append(&mut self, mut with: Self) -> Result<(), Vec<String>> {
@@ -461,6 +585,31 @@ impl Config {
                     .set_timestamp_key(with.global.log_schema.timestamp_key().clone());
             }
         }
+        let mut redacted_keys = self.global.log_schema.redacted_keys().clone();
+        redacted_keys.extend(with.global.log_schema.redacted_keys().iter().cloned());
+        redacted_keys.sort();
+        redacted_keys.dedup();
+        self.global.log_schema.set_redacted_keys(redacted_keys);
+
+        if self.global.dns == Default::default() {
+            self.global.dns = with.global.dns;
+        } else if with.global.dns != Default::default() && self.global.dns != with.global.dns {
+            errors.push("conflicting values for 'dns' found".to_owned());
+        }
+
+        if self.global.api == Default::default() {
+            self.global.api = with.global.api;
+        } else if with.global.api != Default::default() && self.global.api != with.global.api {
+            errors.push("conflicting values for 'api' found".to_owned());
+        }
+
+        if self.global.retry_budget.is_none() {
+            self.global.retry_budget = with.global.retry_budget;
+        } else if with.global.retry_budget.is_some()
+            && self.global.retry_budget != with.global.retry_budget
+        {
+            errors.push("conflicting values for 'retry_budget' found".to_owned());
+        }
 
         with.sources.keys().for_each(|k| {
             if self.sources.contains_key(k) {
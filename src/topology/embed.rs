@@ -0,0 +1,211 @@
+//! Builds and runs a topology from Rust, for embedding Vector as a library
+//! without a config file.
+//!
+//! ```no_run
+//! # use vector::topology::{Topology, config::SinkConfig, config::SourceConfig};
+//! # fn example(source: impl SourceConfig + 'static, sink: impl SinkConfig + 'static) {
+//! let handle = Topology::builder()
+//!     .add_source("in", source)
+//!     .add_sink("out", &["in"], sink)
+//!     .build()
+//!     .expect("invalid topology")
+//!     .run()
+//!     .expect("topology failed to start");
+//!
+//! // ...
+//!
+//! handle.shutdown();
+//! # }
+//! ```
+
+use super::{
+    builder,
+    config::{GlobalOptions, SinkConfig, SourceConfig, TransformConfig},
+    graph::Graph,
+    tap::Taps,
+    Config, RunningTopology,
+};
+use crate::runtime::{Runtime, TaskExecutor};
+use futures01::{future, sync::mpsc, Async, Future, Stream};
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum RunError {
+    #[snafu(display("Unable to create the runtime: {}", source))]
+    CreateRuntime { source: std::io::Error },
+    #[snafu(display("Topology failed to start; see the logged errors"))]
+    Start,
+}
+
+/// A topology put together in code, checked and ready to run.
+pub struct Topology {
+    config: Config,
+    require_healthy: bool,
+    threads: Option<usize>,
+}
+
+/// Puts a topology together component by component, as a config file would.
+pub struct TopologyBuilder {
+    config: Config,
+    require_healthy: bool,
+    threads: Option<usize>,
+}
+
+impl Topology {
+    pub fn builder() -> TopologyBuilder {
+        TopologyBuilder {
+            config: Config::empty(),
+            require_healthy: false,
+            threads: None,
+        }
+    }
+
+    /// Starts the topology on a runtime of its own, once the healthchecks
+    /// have run. The topology runs until the returned handle is shut down or
+    /// dropped.
+    pub fn run(self) -> Result<TopologyHandle, RunError> {
+        let mut rt = match self.threads {
+            Some(threads) => Runtime::with_thread_count(threads),
+            None => Runtime::new(),
+        }
+        .context(CreateRuntime)?;
+
+        let (topology, crash) =
+            super::start(self.config, &mut rt, self.require_healthy).ok_or(RunError::Start)?;
+
+        Ok(TopologyHandle {
+            topology: Some(topology),
+            rt: Some(rt),
+            crash,
+        })
+    }
+}
+
+impl TopologyBuilder {
+    pub fn add_source<S: SourceConfig + 'static>(mut self, name: &str, source: S) -> Self {
+        self.config.add_source(name, source);
+        self
+    }
+
+    pub fn add_transform<T: TransformConfig + 'static>(
+        mut self,
+        name: &str,
+        inputs: &[&str],
+        transform: T,
+    ) -> Self {
+        self.config.add_transform(name, inputs, transform);
+        self
+    }
+
+    pub fn add_sink<S: SinkConfig + 'static>(
+        mut self,
+        name: &str,
+        inputs: &[&str],
+        sink: S,
+    ) -> Self {
+        self.config.add_sink(name, inputs, sink);
+        self
+    }
+
+    /// Sets the global options, as the top level of a config file does. The
+    /// log schema and the retry budget are process-wide though, and are left
+    /// to `event::LOG_SCHEMA` and `retries::GLOBAL_RETRY_BUDGET`.
+    pub fn global(mut self, global: GlobalOptions) -> Self {
+        self.config.global = global;
+        self
+    }
+
+    /// Fails `run` if a sink fails its healthcheck, rather than only logging
+    /// it.
+    pub fn require_healthy(mut self, require_healthy: bool) -> Self {
+        self.require_healthy = require_healthy;
+        self
+    }
+
+    /// The number of worker threads of the runtime the topology runs on,
+    /// which defaults to the number of CPUs.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Checks how the components are wired together, logging the warnings.
+    pub fn build(self) -> Result<Topology, Vec<String>> {
+        let warnings = builder::check(&self.config)?;
+        for warning in warnings {
+            warn!("Configuration warning: {}", warning);
+        }
+
+        Ok(Topology {
+            config: self.config,
+            require_healthy: self.require_healthy,
+            threads: self.threads,
+        })
+    }
+}
+
+/// A running topology, along with the runtime it runs on.
+///
+/// Shutting it down, or dropping it, blocks until the components have
+/// finished, so it mustn't be done from a task on that runtime.
+pub struct TopologyHandle {
+    topology: Option<RunningTopology>,
+    rt: Option<Runtime>,
+    crash: mpsc::UnboundedReceiver<()>,
+}
+
+impl TopologyHandle {
+    /// Runs more tasks alongside the topology.
+    pub fn executor(&self) -> TaskExecutor {
+        self.rt.as_ref().expect("runtime is gone").executor()
+    }
+
+    pub fn taps(&self) -> Taps {
+        self.topology().taps()
+    }
+
+    pub fn graph(&self) -> Graph {
+        self.topology().graph()
+    }
+
+    /// Describes the components like `GET /topology` of the API does, with
+    /// their health and metrics once `metrics::init` has been called.
+    pub fn describe(&self) -> serde_json::Value {
+        crate::api::describe_topology(&self.graph())
+    }
+
+    /// Whether a component has crashed since this was last asked.
+    pub fn crashed(&mut self) -> bool {
+        let crash = &mut self.crash;
+        future::poll_fn(|| Ok::<_, ()>(Async::Ready(crash.poll())))
+            .wait()
+            .map(|polled| match polled {
+                Ok(Async::Ready(Some(()))) => true,
+                _ => false,
+            })
+            .unwrap_or(false)
+    }
+
+    /// Shuts the sources down, waits for the events in flight to make it
+    /// through the sinks, then shuts the runtime down.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn topology(&self) -> &RunningTopology {
+        self.topology.as_ref().expect("topology is gone")
+    }
+
+    fn stop(&mut self) {
+        if let (Some(topology), Some(mut rt)) = (self.topology.take(), self.rt.take()) {
+            let _ = rt.block_on(topology.stop());
+            let _ = rt.shutdown_now().wait();
+        }
+    }
+}
+
+impl Drop for TopologyHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,3 +1,4 @@
+use super::tap::TapSender;
 use crate::sinks::RouterSink;
 use crate::Event;
 use futures01::sync::mpsc;
@@ -6,6 +7,9 @@ use futures01::{future, Async, AsyncSink, Poll, Sink, StartSend, Stream};
 pub struct Fanout {
     sinks: Vec<(String, RouterSink)>,
     i: usize,
+    taps: Vec<TapSender>,
+    /// Whether the taps have seen the event being sent.
+    tapped: bool,
     control_channel: mpsc::UnboundedReceiver<ControlMessage>,
 }
 
@@ -13,6 +17,7 @@ pub enum ControlMessage {
     Add(String, RouterSink),
     Remove(String),
     Replace(String, RouterSink),
+    Tap(TapSender),
 }
 
 pub type ControlChannel = mpsc::UnboundedSender<ControlMessage>;
@@ -24,6 +29,8 @@ impl Fanout {
         let fanout = Self {
             sinks: vec![],
             i: 0,
+            taps: vec![],
+            tapped: false,
             control_channel: control_rx,
         };
 
@@ -66,10 +73,20 @@ impl Fanout {
                 ControlMessage::Add(name, sink) => self.add(name, sink),
                 ControlMessage::Remove(name) => self.remove(&name),
                 ControlMessage::Replace(name, sink) => self.replace(name, sink),
+                ControlMessage::Tap(tap) => self.taps.push(tap),
             }
         }
     }
 
+    /// Taps never hold up the sinks, they skip the events they can't keep up
+    /// with instead.
+    fn tap(&mut self, item: &Event) {
+        if !self.tapped {
+            self.taps.retain(|tap| tap.send(item));
+            self.tapped = true;
+        }
+    }
+
     fn handle_sink_error(&mut self) -> Result<(), ()> {
         // If there's only one sink, propagate the error to the source ASAP
         // so it stops reading from its input. If there are multiple sinks,
@@ -90,8 +107,10 @@ impl Sink for Fanout {
 
     fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
         self.process_control_messages();
+        self.tap(&item);
 
         if self.sinks.is_empty() {
+            self.tapped = false;
             return Ok(AsyncSink::Ready);
         }
 
@@ -112,6 +131,7 @@ impl Sink for Fanout {
         }
 
         self.i = 0;
+        self.tapped = false;
 
         Ok(AsyncSink::Ready)
     }
@@ -145,6 +165,7 @@ mod tests {
     use super::{ControlMessage, Fanout};
     use crate::runtime;
     use crate::test_util::{self, CollectCurrent};
+    use crate::topology::tap;
     use crate::Event;
     use futures01::sync::mpsc;
     use futures01::{stream, Future, Sink, Stream};
@@ -214,6 +235,33 @@ mod tests {
         assert_eq!(collect_c.wait().unwrap(), recs.clone());
     }
 
+    #[test]
+    fn fanout_taps_without_backpressure() {
+        let (tx_a, rx_a) = mpsc::unbounded();
+        let tx_a = Box::new(tx_a.sink_map_err(|_| unreachable!()));
+        let (tap, tap_rx) = tap::tap(1);
+
+        let (mut fanout, fanout_control) = Fanout::new();
+
+        fanout.add("a".to_string(), tx_a);
+        fanout_control
+            .unbounded_send(ControlMessage::Tap(tap))
+            .unwrap();
+
+        let recs = (0..5)
+            .map(|i| Event::from(format!("line {}", i)))
+            .collect::<Vec<_>>();
+        let _fanout = fanout
+            .send_all(stream::iter_ok(recs.clone()))
+            .wait()
+            .unwrap();
+
+        assert_eq!(CollectCurrent::new(rx_a).wait().unwrap().1, recs);
+        let tapped = CollectCurrent::new(tap_rx).wait().unwrap().1;
+        assert_eq!(tapped.len(), 1);
+        assert_eq!(tapped[0].event, recs[0]);
+    }
+
     #[test]
     fn fanout_grow() {
         let (tx_a, rx_a) = mpsc::unbounded();
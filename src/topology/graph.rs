@@ -0,0 +1,94 @@
+//! A read-only view of how the components of the running topology are wired
+//! together, for introspection.
+
+use super::config::Config;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentKind {
+    Source,
+    Transform,
+    Sink,
+}
+
+impl ComponentKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ComponentKind::Source => "source",
+            ComponentKind::Transform => "transform",
+            ComponentKind::Sink => "sink",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Component {
+    pub id: String,
+    pub kind: ComponentKind,
+    #[serde(rename = "type")]
+    pub component_type: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+/// The components of the running topology, kept up to date as the config is
+/// reloaded.
+#[derive(Clone, Default)]
+pub struct Graph {
+    components: Arc<Mutex<Vec<Component>>>,
+}
+
+impl Graph {
+    pub(super) fn update(&self, config: &Config) {
+        *self.components.lock().unwrap() = components(config);
+    }
+
+    /// The components as of the last reload, sources first, then transforms
+    /// and sinks, each in config order.
+    pub fn components(&self) -> Vec<Component> {
+        self.components.lock().unwrap().clone()
+    }
+}
+
+fn components(config: &Config) -> Vec<Component> {
+    let sources = config.sources.iter().map(|(name, source)| Component {
+        id: name.clone(),
+        kind: ComponentKind::Source,
+        component_type: source.source_type().into(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let transforms = config.transforms.iter().map(|(name, transform)| Component {
+        id: name.clone(),
+        kind: ComponentKind::Transform,
+        component_type: transform.inner.transform_type().into(),
+        inputs: transform.inputs.clone(),
+        outputs: Vec::new(),
+    });
+    let sinks = config.sinks.iter().map(|(name, sink)| Component {
+        id: name.clone(),
+        kind: ComponentKind::Sink,
+        component_type: sink.inner.sink_type().into(),
+        inputs: sink.inputs.clone(),
+        outputs: Vec::new(),
+    });
+
+    let mut components = sources.chain(transforms).chain(sinks).collect::<Vec<_>>();
+    let wiring = components
+        .iter()
+        .flat_map(|component| {
+            component
+                .inputs
+                .iter()
+                .map(move |input| (input.clone(), component.id.clone()))
+        })
+        .collect::<Vec<_>>();
+    for (input, id) in wiring {
+        if let Some(upstream) = components.iter_mut().find(|c| c.id == input) {
+            upstream.outputs.push(id);
+        }
+    }
+    components
+}
@@ -0,0 +1,219 @@
+//! Keeps a transform with `on_panic = "drop_event"` going when it panics on
+//! an event, which is then dropped, or handed to the transform's dead-letter
+//! sink.
+//!
+//! Panics are caught around each poll of the transform's output, and blamed
+//! on the last event the transform took in. The panic is still logged with
+//! its backtrace, so a bug doesn't go unnoticed. A transform that keeps
+//! panicking without taking in new events would otherwise be polled forever,
+//! so it's stopped instead.
+
+use crate::{
+    event::Event,
+    internal_events::{TransformPanicLoop, TransformPanicked},
+    sinks::util::DeadLetter,
+};
+use backtrace::Backtrace;
+use futures01::{Poll, Stream};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, Once},
+};
+
+type EventStream = Box<dyn Stream<Item = Event, Error = ()> + Send>;
+
+/// How many times in a row the transform may panic without taking in an
+/// event before it's stopped.
+const MAX_PANICS_WITHOUT_INPUT: usize = 3;
+
+thread_local! {
+    /// Whether an isolated transform is being polled on this thread.
+    static ISOLATING: Cell<bool> = Cell::new(false);
+    /// Where the last isolated panic on this thread happened.
+    static PANIC_TRACE: RefCell<Option<String>> = RefCell::new(None);
+}
+
+pub struct IsolatePanics {
+    inner: EventStream,
+    current: Arc<Mutex<Option<Event>>>,
+    component: String,
+    dead_letter: DeadLetter,
+    panics_without_input: usize,
+}
+
+impl IsolatePanics {
+    /// Transforms `input` with `transform_stream`, keeping a copy of each
+    /// event until the next one comes in.
+    pub fn new<F>(
+        transform_stream: F,
+        input: EventStream,
+        component: &str,
+        dead_letter: DeadLetter,
+    ) -> Self
+    where
+        F: FnOnce(EventStream) -> EventStream,
+    {
+        install_panic_hook();
+
+        let current = Arc::new(Mutex::new(None));
+        let tracked = Arc::clone(&current);
+        let input = input.inspect(move |event| *tracked.lock().unwrap() = Some(event.clone()));
+
+        Self {
+            inner: transform_stream(Box::new(input)),
+            current,
+            component: component.to_owned(),
+            dead_letter,
+            panics_without_input: 0,
+        }
+    }
+}
+
+impl Stream for IsolatePanics {
+    type Item = Event;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Event>, ()> {
+        loop {
+            let inner = &mut self.inner;
+            let was_isolating = ISOLATING.with(|isolating| isolating.replace(true));
+            let result = panic::catch_unwind(AssertUnwindSafe(|| inner.poll()));
+            ISOLATING.with(|isolating| isolating.set(was_isolating));
+
+            let payload = match result {
+                Ok(poll) => return poll,
+                Err(payload) => payload,
+            };
+
+            let backtrace = PANIC_TRACE.with(|trace| trace.borrow_mut().take());
+            emit!(TransformPanicked {
+                component: &self.component,
+                message: panic_message(&*payload),
+                backtrace: backtrace.as_deref(),
+            });
+            match self.current.lock().unwrap().take() {
+                Some(event) => {
+                    self.panics_without_input = 0;
+                    self.dead_letter
+                        .send(event, "The transform panicked on the event.");
+                }
+                None => self.panics_without_input += 1,
+            }
+
+            if self.panics_without_input >= MAX_PANICS_WITHOUT_INPUT {
+                emit!(TransformPanicLoop {
+                    component: &self.component,
+                    panics: self.panics_without_input,
+                });
+                return Err(());
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<Any>"
+    }
+}
+
+/// Keeps where isolated panics happen for them to be logged, rather than
+/// printing them like the previous hook, which still handles all others.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if ISOLATING.with(Cell::get) {
+                let location = info.location().map(ToString::to_string).unwrap_or_default();
+                let trace = format!("at {}\n{:?}", location, Backtrace::new());
+                PANIC_TRACE.with(|last| *last.borrow_mut() = Some(trace));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures01::{stream::iter_ok, Future};
+
+    fn message(event: &Event) -> String {
+        event.as_log()[&crate::event::log_schema().message_key()].to_string_lossy()
+    }
+
+    #[test]
+    fn isolate_panics_skips_events_panicked_on() {
+        let (tx, rx) = futures01::sync::mpsc::channel(10);
+        let input: EventStream = Box::new(iter_ok(vec![
+            Event::from("one"),
+            Event::from("boom"),
+            Event::from("two"),
+        ]));
+
+        let output = IsolatePanics::new(
+            |input| {
+                Box::new(input.map(|event| {
+                    if message(&event) == "boom" {
+                        panic!("boom");
+                    }
+                    event
+                }))
+            },
+            input,
+            "panicky",
+            DeadLetter::new("panicky", tx),
+        )
+        .collect()
+        .wait()
+        .unwrap();
+
+        assert_eq!(
+            output.iter().map(message).collect::<Vec<_>>(),
+            vec!["one", "two"]
+        );
+
+        let dead = rx.take(1).collect().wait().unwrap();
+        assert_eq!(message(&dead[0]), "boom");
+        assert_eq!(
+            dead[0].as_log()[&crate::sinks::util::dead_letter::REASON_FIELD.into()]
+                .to_string_lossy(),
+            "The transform panicked on the event."
+        );
+    }
+
+    #[test]
+    fn isolate_panics_stops_transform_always_panicking() {
+        let (tx, _rx) = futures01::sync::mpsc::channel(10);
+        let input: EventStream = Box::new(iter_ok(vec![Event::from("one")]));
+
+        let polls = Arc::new(Mutex::new(0));
+        let counted = Arc::clone(&polls);
+        let result = IsolatePanics::new(
+            |_input| {
+                Box::new(futures01::stream::poll_fn(
+                    move || -> Poll<Option<Event>, ()> {
+                        *counted.lock().unwrap() += 1;
+                        panic!("always")
+                    },
+                ))
+            },
+            input,
+            "panicky",
+            DeadLetter::new("panicky", tx),
+        )
+        .collect()
+        .wait();
+
+        assert_eq!(result, Err(()));
+        assert_eq!(*polls.lock().unwrap(), MAX_PANICS_WITHOUT_INPUT);
+    }
+}
@@ -0,0 +1,208 @@
+//! Drops events older than a maximum age before they reach a sink, so a sink
+//! catching up on a backed up buffer doesn't ship stale data, or data its
+//! service would reject anyway, like CloudWatch Logs does events over 14
+//! days old. An event's age is the time since its timestamp.
+
+use crate::{
+    emit,
+    event::{self, metadata, Event, TraceEvent, Value},
+    internal_events::ExpiredEventDropped,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MaxAgeConfig {
+    pub secs: u64,
+    #[serde(default)]
+    pub missing_timestamp: MissingTimestampPolicy,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum MissingTimestampPolicy {
+    /// Never drop events without a timestamp.
+    #[derivative(Default)]
+    Keep,
+    /// Age log events without a timestamp from when they left their source.
+    /// Metrics don't record it, and are kept.
+    IngestTime,
+}
+
+impl MaxAgeConfig {
+    pub fn build(&self, component: &str) -> AgeLimiter {
+        AgeLimiter {
+            component: component.to_owned(),
+            max_age: Duration::from_secs(self.secs),
+            missing_timestamp: self.missing_timestamp,
+        }
+    }
+}
+
+pub struct AgeLimiter {
+    component: String,
+    max_age: Duration,
+    missing_timestamp: MissingTimestampPolicy,
+}
+
+impl AgeLimiter {
+    /// Whether `event` is young enough to be sent on. Events that aren't are
+    /// counted as they're dropped.
+    pub fn check(&self, event: &Event) -> bool {
+        self.check_at(event, Utc::now())
+    }
+
+    fn check_at(&self, event: &Event, now: DateTime<Utc>) -> bool {
+        let timestamp = match self.timestamp(event) {
+            Some(timestamp) => timestamp,
+            None => return true,
+        };
+
+        // Timestamps in the future make for a negative age, which is fine.
+        match now.signed_duration_since(timestamp).to_std() {
+            Ok(age) if age > self.max_age => {
+                emit!(ExpiredEventDropped {
+                    component: &self.component,
+                    age_secs: age.as_secs(),
+                    max_age_secs: self.max_age.as_secs(),
+                });
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn timestamp(&self, event: &Event) -> Option<DateTime<Utc>> {
+        match event {
+            Event::Log(log) | Event::Trace(TraceEvent(log)) => {
+                match log.get(event::log_schema().timestamp_key()) {
+                    Some(Value::Timestamp(timestamp)) => Some(*timestamp),
+                    _ if self.missing_timestamp == MissingTimestampPolicy::IngestTime => {
+                        match log.metadata().get(metadata::INGESTED_AT) {
+                            Some(Value::Timestamp(timestamp)) => Some(*timestamp),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            Event::Metric(metric) => metric.timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::metric::{Metric, MetricKind, MetricValue};
+    use chrono::TimeZone;
+
+    fn limiter(secs: u64, missing_timestamp: MissingTimestampPolicy) -> AgeLimiter {
+        MaxAgeConfig {
+            secs,
+            missing_timestamp,
+        }
+        .build("test")
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2020, 10, 28).and_hms(12, 0, 0)
+    }
+
+    fn event_at(timestamp: DateTime<Utc>) -> Event {
+        let mut event = Event::from("message");
+        event
+            .as_mut_log()
+            .insert(event::log_schema().timestamp_key().clone(), timestamp);
+        event
+    }
+
+    fn event_without_timestamp() -> Event {
+        let mut event = Event::from("message");
+        event
+            .as_mut_log()
+            .remove(event::log_schema().timestamp_key());
+        event
+    }
+
+    #[test]
+    fn max_age_drops_expired_events() {
+        let limiter = limiter(3600, MissingTimestampPolicy::Keep);
+
+        assert!(limiter.check_at(&event_at(now() - chrono::Duration::seconds(3600)), now()));
+        assert!(!limiter.check_at(&event_at(now() - chrono::Duration::seconds(3601)), now()));
+        assert!(!limiter.check_at(&event_at(now() - chrono::Duration::days(14)), now()));
+    }
+
+    #[test]
+    fn max_age_keeps_events_from_the_future() {
+        let limiter = limiter(0, MissingTimestampPolicy::Keep);
+
+        assert!(limiter.check_at(&event_at(now() + chrono::Duration::hours(1)), now()));
+    }
+
+    #[test]
+    fn max_age_keeps_events_without_timestamp() {
+        let mut event = event_without_timestamp();
+        let ingested_at = now() - chrono::Duration::days(1);
+        event
+            .as_mut_log()
+            .metadata_mut()
+            .insert(metadata::INGESTED_AT, ingested_at);
+
+        assert!(limiter(3600, MissingTimestampPolicy::Keep).check_at(&event, now()));
+    }
+
+    #[test]
+    fn max_age_ages_events_without_timestamp_from_ingestion() {
+        let limiter = limiter(3600, MissingTimestampPolicy::IngestTime);
+
+        let mut event = event_without_timestamp();
+        let ingested_at = now() - chrono::Duration::days(1);
+        event
+            .as_mut_log()
+            .metadata_mut()
+            .insert(metadata::INGESTED_AT, ingested_at);
+        assert!(!limiter.check_at(&event, now()));
+
+        let mut event = event_without_timestamp();
+        let ingested_at = now() - chrono::Duration::minutes(1);
+        event
+            .as_mut_log()
+            .metadata_mut()
+            .insert(metadata::INGESTED_AT, ingested_at);
+        assert!(limiter.check_at(&event, now()));
+
+        // Events that never went through a source have nothing to age from.
+        assert!(limiter.check_at(&event_without_timestamp(), now()));
+
+        // The timestamp wins over the ingestion time.
+        let mut event = event_at(now() - chrono::Duration::days(1));
+        event
+            .as_mut_log()
+            .metadata_mut()
+            .insert(metadata::INGESTED_AT, now());
+        assert!(!limiter.check_at(&event, now()));
+    }
+
+    #[test]
+    fn max_age_checks_metric_timestamps() {
+        let metric = |timestamp| {
+            Event::Metric(Metric {
+                name: "requests".into(),
+                timestamp,
+                tags: None,
+                kind: MetricKind::Incremental,
+                value: MetricValue::Counter { value: 1.0 },
+            })
+        };
+        let limiter = limiter(3600, MissingTimestampPolicy::IngestTime);
+
+        assert!(!limiter.check_at(&metric(Some(now() - chrono::Duration::days(1))), now()));
+        assert!(limiter.check_at(&metric(Some(now())), now()));
+        assert!(limiter.check_at(&metric(None), now()));
+    }
+}
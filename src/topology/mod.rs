@@ -8,16 +8,24 @@
 
 pub mod builder;
 pub mod config;
+mod embed;
 mod fanout;
+pub mod graph;
+mod isolate;
+pub mod max_age;
+pub mod size_limit;
+pub mod tap;
 mod task;
 pub mod unit_test;
 
 pub use self::config::Config;
 pub use self::config::SinkContext;
+pub use self::embed::{RunError, Topology, TopologyBuilder, TopologyHandle};
 
-use crate::topology::builder::Pieces;
+use crate::topology::builder::{Input, Pieces};
+use crate::topology::graph::Graph;
+use crate::topology::tap::Taps;
 
-use crate::buffers;
 use crate::runtime;
 use crate::shutdown::SourceShutdownCoordinator;
 use futures::compat::Future01CompatExt;
@@ -31,17 +39,18 @@ use std::collections::{HashMap, HashSet};
 use std::panic::AssertUnwindSafe;
 use std::time::{Duration, Instant};
 use tokio01::timer;
-use tracing_futures::Instrument;
 
 #[allow(dead_code)]
 pub struct RunningTopology {
-    inputs: HashMap<String, buffers::BufferInputCloner>,
+    inputs: HashMap<String, Input>,
     outputs: HashMap<String, fanout::ControlChannel>,
     source_tasks: HashMap<String, oneshot::SpawnHandle<(), ()>>,
     tasks: HashMap<String, oneshot::SpawnHandle<(), ()>>,
     shutdown_coordinator: SourceShutdownCoordinator,
     config: Config,
     abort_tx: mpsc::UnboundedSender<()>,
+    taps: Taps,
+    graph: Graph,
 }
 
 pub fn start(
@@ -69,6 +78,8 @@ pub fn start_validated(
         source_tasks: HashMap::new(),
         tasks: HashMap::new(),
         abort_tx,
+        taps: Taps::default(),
+        graph: Graph::default(),
     };
 
     if !running_topology.run_healthchecks(&config, &mut pieces, rt, require_healthy) {
@@ -190,6 +201,17 @@ impl RunningTopology {
             .map(|_| ())
     }
 
+    /// Lets taps be attached to the components of this topology, as it's
+    /// reloaded.
+    pub fn taps(&self) -> Taps {
+        self.taps.clone()
+    }
+
+    /// Describes the components of this topology, as it's reloaded.
+    pub fn graph(&self) -> Graph {
+        self.graph.clone()
+    }
+
     pub fn reload_config_and_respawn(
         &mut self,
         new_config: Config,
@@ -206,6 +228,16 @@ impl RunningTopology {
             return false;
         }
 
+        if self.config.global.dns != new_config.global.dns {
+            error!("dns cannot be changed while reloading config file; reload aborted. Current value: {:?}", self.config.global.dns);
+            return false;
+        }
+
+        if self.config.global.api != new_config.global.api {
+            error!("api cannot be changed while reloading config file; reload aborted. Current value: {:?}", self.config.global.api);
+            return false;
+        }
+
         match validate(&new_config, rt.executor()) {
             Some(mut new_pieces) => {
                 if !self.run_healthchecks(&new_config, &mut new_pieces, rt, require_healthy) {
@@ -344,9 +376,21 @@ impl RunningTopology {
         }
 
         // Sinks
-        let (sinks_to_remove, sinks_to_change, sinks_to_add) =
+        let (sinks_to_remove, mut sinks_to_change, sinks_to_add) =
             to_remove_change_add(&self.config.sinks, &new_config.sinks);
 
+        // Sinks hand their dead letters straight to their dead-letter sink's
+        // buffer, so they have to be rebuilt along with it.
+        let rebuilt = &sinks_to_change | &sinks_to_add;
+        for (name, sink) in &new_config.sinks {
+            let target = sink.dead_letter.as_ref();
+            if target.map_or(false, |target| rebuilt.contains(target))
+                && self.config.sinks.contains_key(name)
+            {
+                sinks_to_change.insert(name.clone());
+            }
+        }
+
         for name in sinks_to_remove {
             info!("Removing sink {:?}", name);
 
@@ -370,6 +414,8 @@ impl RunningTopology {
         }
 
         self.config = new_config;
+        self.taps.update(&self.outputs, &self.config);
+        self.graph.update(&self.config);
     }
 
     fn spawn_sink(
@@ -379,8 +425,7 @@ impl RunningTopology {
         rt: &mut runtime::Runtime,
     ) {
         let task = new_pieces.tasks.remove(name).unwrap();
-        let span = info_span!("sink", name = %task.name(), r#type = %task.typetag());
-        let task = handle_errors(task.instrument(span), self.abort_tx.clone());
+        let task = handle_errors(task, self.abort_tx.clone());
         let spawned = oneshot::spawn(task, &rt.executor());
         if let Some(previous) = self.tasks.insert(name.to_string(), spawned) {
             previous.forget();
@@ -394,8 +439,7 @@ impl RunningTopology {
         rt: &mut runtime::Runtime,
     ) {
         let task = new_pieces.tasks.remove(name).unwrap();
-        let span = info_span!("transform", name = %task.name(), r#type = %task.typetag());
-        let task = handle_errors(task.instrument(span), self.abort_tx.clone());
+        let task = handle_errors(task, self.abort_tx.clone());
         let spawned = oneshot::spawn(task, &rt.executor());
         if let Some(previous) = self.tasks.insert(name.to_string(), spawned) {
             previous.forget();
@@ -409,9 +453,7 @@ impl RunningTopology {
         rt: &mut runtime::Runtime,
     ) {
         let task = new_pieces.tasks.remove(name).unwrap();
-        let span = info_span!("source", name = %task.name(), r#type = %task.typetag());
-
-        let task = handle_errors(task.instrument(span.clone()), self.abort_tx.clone());
+        let task = handle_errors(task, self.abort_tx.clone());
         let spawned = oneshot::spawn(task, &rt.executor());
         if let Some(previous) = self.tasks.insert(name.to_string(), spawned) {
             previous.forget();
@@ -421,7 +463,7 @@ impl RunningTopology {
             .takeover_source(name, &mut new_pieces.shutdown_coordinator);
 
         let source_task = new_pieces.source_tasks.remove(name).unwrap();
-        let source_task = handle_errors(source_task.instrument(span), self.abort_tx.clone());
+        let source_task = handle_errors(source_task, self.abort_tx.clone());
         self.source_tasks.insert(
             name.to_string(),
             oneshot::spawn(source_task, &rt.executor()),
@@ -0,0 +1,313 @@
+//! Enforces a maximum size on the events a sink receives, for sinks whose
+//! services reject events over a size, like CloudWatch Logs does events over
+//! 256KiB. An event's size is the length of its JSON encoding.
+
+use crate::{
+    emit,
+    event::{self, Event, LogEvent, Value},
+    internal_events::OversizedEventHandled,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::io;
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MaxEventSizeConfig {
+    pub max_bytes: usize,
+    #[serde(default)]
+    pub policy: OversizedPolicy,
+    /// The field trimmed or split to make events fit, the message by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<Atom>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum OversizedPolicy {
+    /// Cut the end off the field, or drop the event if that isn't enough.
+    #[derivative(Default)]
+    Truncate,
+    Drop,
+    /// Split the field across as many copies of the event as it takes, or
+    /// drop the event if even an empty field is too much. Metrics can't be
+    /// split, and are dropped.
+    Split,
+}
+
+impl MaxEventSizeConfig {
+    pub fn build(&self, component: &str) -> SizeLimiter {
+        SizeLimiter {
+            component: component.to_owned(),
+            max_bytes: self.max_bytes,
+            policy: self.policy,
+            field: self
+                .field
+                .clone()
+                .unwrap_or_else(|| event::log_schema().message_key().clone()),
+        }
+    }
+}
+
+pub struct SizeLimiter {
+    component: String,
+    max_bytes: usize,
+    policy: OversizedPolicy,
+    field: Atom,
+}
+
+impl SizeLimiter {
+    /// The events to send on in place of `event`: itself if it fits, none if
+    /// it's dropped, or the events that it was cut down or split into.
+    pub fn limit(&self, mut event: Event) -> Vec<Event> {
+        let size = serialized_size(&event);
+        if size <= self.max_bytes {
+            return vec![event];
+        }
+
+        let (action, events) = match (self.policy, log_of(&mut event)) {
+            (OversizedPolicy::Truncate, Some(log)) if self.truncate(log) => {
+                ("truncated", vec![event])
+            }
+            (OversizedPolicy::Split, Some(log)) => match self.split(log) {
+                Some(pieces) => (
+                    "split",
+                    pieces
+                        .into_iter()
+                        .map(|piece| with_log(&event, piece))
+                        .collect(),
+                ),
+                None => ("dropped", Vec::new()),
+            },
+            _ => ("dropped", Vec::new()),
+        };
+
+        emit!(OversizedEventHandled {
+            component: &self.component,
+            action,
+            size,
+            max_bytes: self.max_bytes,
+        });
+        events
+    }
+
+    /// Cuts bytes off the end of the field until the event fits, never in the
+    /// middle of a character. False if it can't be made to fit.
+    fn truncate(&self, log: &mut LogEvent) -> bool {
+        loop {
+            let size = json_len(log);
+            if size <= self.max_bytes {
+                return true;
+            }
+
+            match log.get_mut(&self.field) {
+                Some(Value::Bytes(bytes)) if !bytes.is_empty() => {
+                    // Removing `n` bytes from a string shortens its encoding
+                    // by at least `n`, so this fits unless escapes or
+                    // invalid UTF-8 made the encoding longer.
+                    let end = bytes.len().saturating_sub(size - self.max_bytes);
+                    let end = char_boundary(bytes, end);
+                    bytes.truncate(end);
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    /// Splits the field into pieces, each in a copy of the event that fits,
+    /// in order. `None` if the event can't be split to fit.
+    fn split(&self, log: &LogEvent) -> Option<Vec<LogEvent>> {
+        let bytes = match log.get(&self.field) {
+            Some(Value::Bytes(bytes)) => bytes.clone(),
+            _ => return None,
+        };
+
+        let piece_of = |piece: Bytes| {
+            let mut log = log.clone();
+            *log.get_mut(&self.field).unwrap() = Value::Bytes(piece);
+            log
+        };
+        let overhead = json_len(&piece_of(Bytes::new()));
+        if overhead >= self.max_bytes {
+            return None;
+        }
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < bytes.len() {
+            let mut end = bytes.len().min(start + self.max_bytes - overhead);
+            loop {
+                end = char_boundary(&bytes, end);
+                if end <= start {
+                    // Not even a single character fits.
+                    return None;
+                }
+
+                let piece = piece_of(bytes.slice(start, end));
+                let size = json_len(&piece);
+                if size <= self.max_bytes {
+                    pieces.push(piece);
+                    start = end;
+                    break;
+                }
+                end -= (size - self.max_bytes).min(end - start);
+            }
+        }
+
+        Some(pieces)
+    }
+}
+
+fn log_of(event: &mut Event) -> Option<&mut LogEvent> {
+    match event {
+        Event::Log(log) => Some(log),
+        Event::Trace(trace) => Some(trace.as_mut_log()),
+        Event::Metric(_) => None,
+    }
+}
+
+fn with_log(event: &Event, log: LogEvent) -> Event {
+    match event {
+        Event::Trace(_) => Event::Trace(log.into()),
+        _ => Event::Log(log),
+    }
+}
+
+/// The closest offset at or before `end` that isn't in the middle of a UTF-8
+/// encoded character.
+fn char_boundary(bytes: &[u8], mut end: usize) -> usize {
+    while end > 0 && end < bytes.len() && bytes[end] & 0xC0 == 0x80 {
+        end -= 1;
+    }
+    end
+}
+
+pub(super) fn serialized_size(event: &Event) -> usize {
+    match event {
+        Event::Log(log) => json_len(log),
+        Event::Metric(metric) => json_len(metric),
+        Event::Trace(trace) => json_len(trace),
+    }
+}
+
+fn json_len(value: &impl Serialize) -> usize {
+    let mut counter = ByteCounter(0);
+    // Events always encode to JSON, and counting can't fail.
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::metric::{Metric, MetricKind, MetricValue};
+
+    fn limiter(max_bytes: usize, policy: OversizedPolicy) -> SizeLimiter {
+        MaxEventSizeConfig {
+            max_bytes,
+            policy,
+            field: None,
+        }
+        .build("test")
+    }
+
+    fn message(event: &Event) -> String {
+        event.as_log()[event::log_schema().message_key()].to_string_lossy()
+    }
+
+    #[test]
+    fn size_limit_boundary() {
+        let event = Event::from("0123456789");
+        let size = serialized_size(&event);
+
+        for policy in &[
+            OversizedPolicy::Truncate,
+            OversizedPolicy::Drop,
+            OversizedPolicy::Split,
+        ] {
+            let kept = limiter(size, *policy).limit(event.clone());
+            assert_eq!(kept, vec![event.clone()]);
+        }
+
+        assert!(limiter(size - 1, OversizedPolicy::Drop)
+            .limit(event.clone())
+            .is_empty());
+    }
+
+    #[test]
+    fn size_limit_truncates_to_fit() {
+        let event = Event::from("0123456789");
+        let size = serialized_size(&event);
+
+        let truncated = limiter(size - 3, OversizedPolicy::Truncate).limit(event);
+        assert_eq!(truncated.len(), 1);
+        assert_eq!(message(&truncated[0]), "0123456");
+        assert_eq!(serialized_size(&truncated[0]), size - 3);
+    }
+
+    #[test]
+    fn size_limit_truncates_on_char_boundaries() {
+        // Each of these takes 3 bytes.
+        let event = Event::from("日本語");
+        let size = serialized_size(&event);
+
+        for cut in 1..=3 {
+            let truncated = limiter(size - cut, OversizedPolicy::Truncate).limit(event.clone());
+            assert_eq!(message(&truncated[0]), "日本");
+        }
+        let truncated = limiter(size - 4, OversizedPolicy::Truncate).limit(event);
+        assert_eq!(message(&truncated[0]), "日");
+    }
+
+    #[test]
+    fn size_limit_truncate_drops_what_cannot_fit() {
+        let mut event = Event::from("message");
+        event.as_mut_log().insert("other", "x".repeat(100));
+
+        assert!(limiter(50, OversizedPolicy::Truncate)
+            .limit(event)
+            .is_empty());
+
+        let metric = Event::Metric(Metric {
+            name: "a_metric_with_a_long_name".into(),
+            timestamp: None,
+            tags: None,
+            kind: MetricKind::Incremental,
+            value: MetricValue::Counter { value: 1.0 },
+        });
+        assert!(limiter(10, OversizedPolicy::Truncate)
+            .limit(metric)
+            .is_empty());
+    }
+
+    #[test]
+    fn size_limit_splits_in_order() {
+        let event = Event::from("0123456789");
+        let overhead = serialized_size(&Event::from(""));
+
+        let pieces = limiter(overhead + 4, OversizedPolicy::Split).limit(event);
+        let messages = pieces.iter().map(message).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["0123", "4567", "89"]);
+
+        // Escapes count toward the size.
+        let pieces = limiter(overhead + 4, OversizedPolicy::Split).limit(Event::from("a\"b\"c"));
+        let messages = pieces.iter().map(message).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["a\"", "b\"c"]);
+    }
+}
@@ -0,0 +1,268 @@
+//! Taps get a live sample of the events flowing through a component, for
+//! debugging, without slowing the pipeline down.
+
+use super::{config::Config, fanout};
+use crate::event::Event;
+use futures01::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Whether a tap gets the events a component receives or the ones it sends
+/// on.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TapKind {
+    Input,
+    Output,
+}
+
+/// An event seen by a tap, along with how many events it skipped since the
+/// previous one, to keep within its limit.
+#[derive(Debug, PartialEq)]
+pub struct TapEvent {
+    pub event: Event,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Snafu, PartialEq)]
+pub enum TapError {
+    #[snafu(display("Component {:?} doesn't exist", component))]
+    ComponentNotFound { component: String },
+    #[snafu(display("Component {:?} has no outputs", component))]
+    NoOutputs { component: String },
+    #[snafu(display("Component {:?} has no inputs", component))]
+    NoInputs { component: String },
+}
+
+/// Creates a tap that passes on at most `limit` events a second.
+pub fn tap(limit: usize) -> (TapSender, mpsc::Receiver<TapEvent>) {
+    let limit = limit.max(1);
+    let (tx, rx) = mpsc::channel(limit);
+    let inner = Inner {
+        tx,
+        limit,
+        window_start: Instant::now(),
+        sent: 0,
+        skipped: 0,
+    };
+    let sender = TapSender {
+        inner: Arc::new(Mutex::new(inner)),
+    };
+    (sender, rx)
+}
+
+/// The sending half of a tap. Clones share the limit, so a tap on several
+/// outputs passes on at most as many events as a tap on one.
+#[derive(Clone)]
+pub struct TapSender {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    tx: mpsc::Sender<TapEvent>,
+    limit: usize,
+    window_start: Instant,
+    sent: usize,
+    skipped: usize,
+}
+
+impl TapSender {
+    /// Passes a copy of the event on, unless the tap is over its limit or
+    /// its receiver is falling behind, in which case it's skipped instead of
+    /// holding up the component. Returns false once the receiver is found
+    /// to be gone.
+    pub(super) fn send(&self, event: &Event) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+
+        let now = Instant::now();
+        if now.duration_since(inner.window_start) >= Duration::from_secs(1) {
+            inner.window_start = now;
+            inner.sent = 0;
+        }
+        if inner.sent >= inner.limit {
+            inner.skipped += 1;
+            return true;
+        }
+
+        let tapped = TapEvent {
+            event: event.clone(),
+            skipped: inner.skipped,
+        };
+        match inner.tx.try_send(tapped) {
+            Ok(()) => {
+                inner.sent += 1;
+                inner.skipped = 0;
+                true
+            }
+            Err(error) if error.is_full() => {
+                inner.skipped += 1;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Where taps can be attached in the running topology, kept up to date as
+/// the config is reloaded.
+#[derive(Clone, Default)]
+pub struct Taps {
+    targets: Arc<Mutex<Targets>>,
+}
+
+#[derive(Default)]
+struct Targets {
+    outputs: HashMap<String, fanout::ControlChannel>,
+    inputs: HashMap<String, Vec<String>>,
+}
+
+impl Taps {
+    pub(super) fn update(
+        &self,
+        outputs: &HashMap<String, fanout::ControlChannel>,
+        config: &Config,
+    ) {
+        let sink_inputs = config
+            .sinks
+            .iter()
+            .map(|(name, sink)| (name.clone(), sink.inputs.clone()));
+        let transform_inputs = config
+            .transforms
+            .iter()
+            .map(|(name, transform)| (name.clone(), transform.inputs.clone()));
+        let source_inputs = config.sources.keys().map(|name| (name.clone(), Vec::new()));
+
+        let mut targets = self.targets.lock().unwrap();
+        targets.outputs = outputs.clone();
+        targets.inputs = sink_inputs
+            .chain(transform_inputs)
+            .chain(source_inputs)
+            .collect();
+    }
+
+    /// Taps the input or output of a component, passing on at most `limit`
+    /// events a second. The tap ends when the component is removed or
+    /// rebuilt by a reload.
+    pub fn tap(
+        &self,
+        component: &str,
+        kind: TapKind,
+        limit: usize,
+    ) -> Result<mpsc::Receiver<TapEvent>, TapError> {
+        let targets = self.targets.lock().unwrap();
+
+        let inputs = targets
+            .inputs
+            .get(component)
+            .ok_or_else(|| TapError::ComponentNotFound {
+                component: component.into(),
+            })?;
+        let outputs = match kind {
+            TapKind::Output => {
+                vec![targets
+                    .outputs
+                    .get(component)
+                    .ok_or_else(|| TapError::NoOutputs {
+                        component: component.into(),
+                    })?]
+            }
+            TapKind::Input if inputs.is_empty() => {
+                return Err(TapError::NoInputs {
+                    component: component.into(),
+                })
+            }
+            TapKind::Input => inputs
+                .iter()
+                .filter_map(|input| targets.outputs.get(input))
+                .collect(),
+        };
+
+        let (sender, rx) = tap(limit);
+        for output in outputs {
+            // The fanout is gone if its component is being shut down, and
+            // then so is the tap.
+            let _ = output.unbounded_send(fanout::ControlMessage::Tap(sender.clone()));
+        }
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::CollectCurrent;
+    use futures01::{Future, Stream};
+
+    #[test]
+    fn tap_limits_events_per_second() {
+        let (sender, rx) = tap(2);
+        let events = (0..5)
+            .map(|i| Event::from(format!("line {}", i)))
+            .collect::<Vec<_>>();
+
+        for event in &events {
+            assert!(sender.send(event));
+        }
+        // Starts the next second.
+        sender.inner.lock().unwrap().window_start -= Duration::from_secs(1);
+        assert!(sender.send(&events[0]));
+        drop(sender);
+
+        let tapped = CollectCurrent::new(rx).wait().unwrap().1;
+        assert_eq!(
+            tapped,
+            vec![
+                TapEvent {
+                    event: events[0].clone(),
+                    skipped: 0
+                },
+                TapEvent {
+                    event: events[1].clone(),
+                    skipped: 0
+                },
+                TapEvent {
+                    event: events[0].clone(),
+                    skipped: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tap_skips_events_when_receiver_falls_behind() {
+        let (sender, rx) = tap(1);
+        sender.inner.lock().unwrap().limit = 100;
+
+        let event = Event::from("line");
+        for _ in 0..10 {
+            assert!(sender.send(&event));
+        }
+        assert!(sender.inner.lock().unwrap().skipped > 0);
+
+        drop(rx);
+        assert!(!sender.send(&event));
+    }
+
+    #[test]
+    fn tap_unknown_component() {
+        let taps = Taps::default();
+        assert_eq!(
+            taps.tap("nope", TapKind::Output, 10).map(|_| ()),
+            Err(TapError::ComponentNotFound {
+                component: "nope".into()
+            })
+        );
+    }
+
+    #[test]
+    fn tap_receiver_ends_with_sender() {
+        let (sender, rx) = tap(1);
+        drop(sender);
+        assert_eq!(rx.collect().wait().unwrap(), Vec::new());
+    }
+}
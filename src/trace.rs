@@ -1,22 +1,38 @@
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value as JsonValue};
+use std::{fmt, io};
 use tracing::{
     dispatcher::{set_global_default, Dispatch},
-    span::Span,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record, Span},
+    Event, Subscriber,
 };
 use tracing_limit::Limit;
 use tracing_log::LogTracer;
-use tracing_subscriber::{layer::SubscriberExt, FmtSubscriber};
+use tracing_subscriber::{
+    layer::{Context, Layer, SubscriberExt},
+    registry::LookupSpan,
+    EnvFilter, FmtSubscriber, Registry,
+};
 
 pub use tracing_futures::Instrument;
 pub use tracing_tower::{InstrumentableService, InstrumentedService};
 
-pub fn init(color: bool, json: bool, levels: &str) {
+/// Sets up internal logging. Events with a `rate_limit_secs` field are always
+/// limited, and when `rate_limit_secs` isn't zero so are all warnings and
+/// errors repeated from the same callsite within that many seconds.
+pub fn init(color: bool, json: bool, levels: &str, rate_limit_secs: u64) {
+    let limit = if rate_limit_secs > 0 {
+        Limit::with_default_limit(rate_limit_secs)
+    } else {
+        Limit::default()
+    };
+
     let dispatch = if json {
-        let subscriber = FmtSubscriber::builder()
-            .with_env_filter(levels)
-            .json()
-            .flatten_event(true)
-            .finish()
-            .with(Limit::default());
+        let subscriber = Registry::default()
+            .with(EnvFilter::new(levels))
+            .with(JsonLayer::new(io::stdout))
+            .with(limit);
 
         Dispatch::new(subscriber)
     } else {
@@ -24,7 +40,7 @@ pub fn init(color: bool, json: bool, levels: &str) {
             .with_ansi(color)
             .with_env_filter(levels)
             .finish()
-            .with(Limit::default());
+            .with(limit);
 
         Dispatch::new(subscriber)
     };
@@ -36,3 +52,182 @@ pub fn init(color: bool, json: bool, levels: &str) {
 pub fn current_span() -> Span {
     Span::current()
 }
+
+/// Writes each event as a line of JSON holding its `timestamp`, in RFC 3339
+/// with microseconds in UTC, its `level`, as in the text format, its
+/// `target` and its fields. The fields of the spans the event is in, like
+/// `component_id`, are under `span`, those of inner spans taking precedence.
+pub struct JsonLayer<W> {
+    make_writer: W,
+}
+
+impl<W> JsonLayer<W> {
+    pub fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+/// The fields of a span, as recorded so far.
+struct SpanFields(Map<String, JsonValue>);
+
+/// Records fields as JSON values. Values only known by their `Debug` or
+/// `Display` formatting, like `?token`, become strings.
+struct JsonVisitor<'a>(&'a mut Map<String, JsonValue>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+impl<S, W, T> Layer<S> for JsonLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: Fn() -> T + 'static,
+    T: io::Write,
+{
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(SpanFields(fields)) = span.extensions_mut().get_mut::<SpanFields>() {
+                values.record(&mut JsonVisitor(fields));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".into(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+                .into(),
+        );
+        object.insert("level".into(), metadata.level().to_string().into());
+        object.insert("target".into(), metadata.target().into());
+        event.record(&mut JsonVisitor(&mut object));
+
+        let mut span_fields = Map::new();
+        if let Some(leaf) = ctx.lookup_current() {
+            let mut merge = |extensions: &SpanFields| {
+                for (name, value) in &extensions.0 {
+                    span_fields.insert(name.clone(), value.clone());
+                }
+            };
+            for span in leaf.from_root() {
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    merge(fields);
+                }
+            }
+            if let Some(fields) = leaf.extensions().get::<SpanFields>() {
+                merge(fields);
+            }
+        }
+        if !span_fields.is_empty() {
+            object.insert("span".into(), JsonValue::Object(span_fields));
+        }
+
+        let mut line = match serde_json::to_vec(&JsonValue::Object(object)) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        line.push(b'\n');
+        // There's nowhere left to report a failure to log.
+        let _ = (self.make_writer)().write_all(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_logs_have_event_and_span_fields() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = Registry::default()
+            .with(EnvFilter::new("trace"))
+            .with(JsonLayer::new(move || writer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!(
+                "sink",
+                component_kind = "sink",
+                component_id = %"out",
+                component_type = tracing::field::Empty,
+            );
+            span.record("component_type", &"aws_cloudwatch_logs");
+            let _enter = span.enter();
+            let token: Option<String> = Some("token1".into());
+            info!(
+                message = "putting logs.",
+                ?token,
+                events = 3u64,
+                retry = false
+            );
+            warn!("Request failed: {}", "timeout");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines = output
+            .lines()
+            .map(|line| serde_json::from_str::<JsonValue>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        let line = &lines[0];
+        assert!(chrono::DateTime::parse_from_rfc3339(line["timestamp"].as_str().unwrap()).is_ok());
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], module_path!());
+        assert_eq!(line["message"], "putting logs.");
+        assert_eq!(line["token"], r#"Some("token1")"#);
+        assert_eq!(line["events"], 3);
+        assert_eq!(line["retry"], false);
+        assert_eq!(line["span"]["component_kind"], "sink");
+        assert_eq!(line["span"]["component_id"], "out");
+        assert_eq!(line["span"]["component_type"], "aws_cloudwatch_logs");
+
+        assert_eq!(lines[1]["level"], "WARN");
+        assert_eq!(lines[1]["message"], "Request failed: timeout");
+    }
+}
@@ -8,6 +8,7 @@ use crate::{
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use string_cache::DefaultAtom as Atom;
 use toml::value::Value as TomlValue;
 
@@ -15,12 +16,18 @@ use toml::value::Value as TomlValue;
 #[serde(deny_unknown_fields)]
 pub struct AddFieldsConfig {
     pub fields: Fields<TomlValue>,
+    #[serde(default)]
+    pub append: bool,
 }
 
 #[derive(Clone)]
 enum TemplateOrValue {
     Template(Template),
     Value(Value),
+    /// A list with templated elements, rendered one by one.
+    Array(Vec<TemplateOrValue>),
+    /// A table nested in a list, which can't be flattened into field paths.
+    Map(BTreeMap<String, TemplateOrValue>),
 }
 
 impl From<Template> for TemplateOrValue {
@@ -35,8 +42,55 @@ impl From<Value> for TemplateOrValue {
     }
 }
 
+impl From<TomlValue> for TemplateOrValue {
+    fn from(value: TomlValue) -> Self {
+        match value {
+            TomlValue::String(s) => Template::from(s).into(),
+            TomlValue::Integer(i) => Value::from(i).into(),
+            TomlValue::Float(f) => Value::from(f).into(),
+            TomlValue::Boolean(b) => Value::from(b).into(),
+            TomlValue::Datetime(dt) => {
+                let dt = dt.to_string();
+                if let Ok(ts) = dt.parse::<DateTime<Utc>>() {
+                    Value::from(ts).into()
+                } else {
+                    Value::from(dt).into()
+                }
+            }
+            TomlValue::Array(vals) => {
+                TemplateOrValue::Array(vals.into_iter().map(Into::into).collect())
+            }
+            TomlValue::Table(map) => TemplateOrValue::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl TemplateOrValue {
+    fn render(&self, event: &Event) -> Result<Value, Vec<Atom>> {
+        match self {
+            TemplateOrValue::Template(v) => v.render_string(event).map(Into::into),
+            TemplateOrValue::Value(v) => Ok(v.clone()),
+            TemplateOrValue::Array(vals) => vals
+                .iter()
+                .map(|val| val.render(event))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            TemplateOrValue::Map(map) => map
+                .iter()
+                .map(|(key, val)| Ok((key.clone(), val.render(event)?)))
+                .collect::<Result<BTreeMap<_, _>, _>>()
+                .map(Value::Map),
+        }
+    }
+}
+
 pub struct AddFields {
     fields: IndexMap<Atom, TemplateOrValue>,
+    append: bool,
 }
 
 inventory::submit! {
@@ -52,6 +106,7 @@ impl TransformConfig for AddFieldsConfig {
                 .all_fields()
                 .map(|(k, v)| (k.into(), v.into()))
                 .collect(),
+            self.append,
         )))
     }
 
@@ -69,35 +124,46 @@ impl TransformConfig for AddFieldsConfig {
 }
 
 impl AddFields {
-    pub fn new(fields: IndexMap<Atom, TomlValue>) -> Self {
+    pub fn new(fields: IndexMap<Atom, TomlValue>, append: bool) -> Self {
         let mut new_fields = IndexMap::new();
 
         for (k, v) in fields {
             flatten_field(k.into(), v, &mut new_fields);
         }
 
-        AddFields { fields: new_fields }
+        AddFields {
+            fields: new_fields,
+            append,
+        }
     }
 }
 
 impl Transform for AddFields {
     fn transform(&mut self, mut event: Event) -> Option<Event> {
-        for (key, value_or_template) in self.fields.clone() {
-            let value = match value_or_template {
-                TemplateOrValue::Template(v) => match v.render_string(&event) {
-                    Ok(v) => v,
-                    Err(_) => {
-                        warn!(
-                            "Failed to render templated value at key `{}`, dropping.",
-                            key
-                        );
-                        continue;
-                    }
+        for (key, value_or_template) in &self.fields {
+            let value = match value_or_template.render(&event) {
+                Ok(v) => v,
+                Err(_) => {
+                    warn!(
+                        "Failed to render templated value at key `{}`, dropping.",
+                        key
+                    );
+                    continue;
                 }
-                .into(),
-                TemplateOrValue::Value(v) => v,
             };
-            if let Some(_) = event.as_mut_log().insert(&key, value) {
+
+            let log = event.as_mut_log();
+            if self.append {
+                if let Some(Value::Array(existing)) = log.get_mut(key) {
+                    match value {
+                        Value::Array(values) => existing.extend(values),
+                        value => existing.push(value),
+                    }
+                    continue;
+                }
+            }
+
+            if let Some(_) = log.insert(key, value) {
                 debug!(
                     message = "Field overwritten",
                     field = key.as_ref(),
@@ -110,57 +176,30 @@ impl Transform for AddFields {
     }
 }
 
+/// Tables are flattened into field paths, so they merge into the fields the
+/// event already has. Lists are added whole, replacing any list already there
+/// rather than only the elements they overlap.
 fn flatten_field(key: Atom, value: TomlValue, new_fields: &mut IndexMap<Atom, TemplateOrValue>) {
     match value {
-        TomlValue::String(s) => {
-            let t = Template::from(s);
-            new_fields.insert(key, t.into())
-        }
-        TomlValue::Integer(i) => {
-            let i = Value::from(i);
-            new_fields.insert(key, i.into())
-        }
-        TomlValue::Float(f) => {
-            let f = Value::from(f);
-            new_fields.insert(key, f.into())
-        }
-        TomlValue::Boolean(b) => {
-            let b = Value::from(b);
-            new_fields.insert(key, b.into())
-        }
-        TomlValue::Datetime(dt) => {
-            let dt = dt.to_string();
-            if let Ok(ts) = dt.parse::<DateTime<Utc>>() {
-                let ts = Value::from(ts);
-                new_fields.insert(key, ts.into())
-            } else {
-                let dt = Value::from(dt);
-                new_fields.insert(key, dt.into())
-            }
-        }
-        TomlValue::Array(vals) => {
-            for (i, val) in vals.into_iter().enumerate() {
-                let key = format!("{}[{}]", key, i);
-                flatten_field(key.into(), val, new_fields);
-            }
-
-            None
-        }
         TomlValue::Table(map) => {
             for (table_key, value) in map {
                 let key = format!("{}.{}", key, table_key);
                 flatten_field(key.into(), value, new_fields);
             }
-
-            None
         }
-    };
+        value => {
+            new_fields.insert(key, value.into());
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::AddFields;
-    use crate::{event::Event, transforms::Transform};
+    use crate::{
+        event::{Event, Value},
+        transforms::Transform,
+    };
     use indexmap::IndexMap;
     use std::collections::HashMap;
     use string_cache::DefaultAtom as Atom;
@@ -170,7 +209,7 @@ mod tests {
         let event = Event::from("augment me");
         let mut fields = IndexMap::new();
         fields.insert("some_key".into(), "some_val".into());
-        let mut augment = AddFields::new(fields);
+        let mut augment = AddFields::new(fields, false);
 
         let new_event = augment.transform(event).unwrap();
 
@@ -181,12 +220,26 @@ mod tests {
         assert_eq!(kv, Some(&val.into()));
     }
 
+    #[test]
+    fn add_fields_preserves_metadata() {
+        let mut event = Event::from("augment me");
+        event.as_mut_log().metadata_mut().set_source_id("in".into());
+        let mut fields = IndexMap::new();
+        fields.insert("some_key".into(), "some_val".into());
+        let mut augment = AddFields::new(fields, false);
+
+        let new_event = augment.transform(event).unwrap();
+
+        assert_eq!(new_event.as_log().metadata().source_id(), Some("in"));
+        assert!(!new_event.as_log().contains(&Atom::from("source_id")));
+    }
+
     #[test]
     fn add_fields_templating() {
         let event = Event::from("augment me");
         let mut fields = IndexMap::new();
         fields.insert("some_key".into(), "{{message}} {{message}}".into());
-        let mut augment = AddFields::new(fields);
+        let mut augment = AddFields::new(fields, false);
 
         let new_event = augment.transform(event).unwrap();
 
@@ -213,7 +266,7 @@ mod tests {
 
         fields.insert("table".into(), map.into());
 
-        let mut transform = AddFields::new(fields);
+        let mut transform = AddFields::new(fields, false);
 
         let event = transform.transform(event).unwrap().into_log();
 
@@ -226,4 +279,56 @@ mod tests {
         assert_eq!(event[&"array[2]".into()], 3.into());
         assert_eq!(event[&"table.key".into()], "value".into());
     }
+
+    fn tags(fields: &[(&str, toml::Value)], append: bool) -> Value {
+        let mut event = Event::from("augment me");
+        event
+            .as_mut_log()
+            .insert("tags", vec![Value::from("a"), "b".into(), "c".into()]);
+
+        let fields = fields
+            .iter()
+            .map(|(key, value)| (Atom::from(*key), value.clone()))
+            .collect();
+        let mut transform = AddFields::new(fields, append);
+
+        let event = transform.transform(event).unwrap();
+        event.as_log()[&"tags".into()].clone()
+    }
+
+    #[test]
+    fn add_fields_replaces_arrays() {
+        let tags = tags(&[("tags", vec!["{{message}}"].into())], false);
+        assert_eq!(tags, Value::Array(vec!["augment me".into()]));
+    }
+
+    #[test]
+    fn add_fields_replaces_array_elements() {
+        let tags = tags(&[("tags[1]", "x".into()), ("tags[4]", "y".into())], false);
+        assert_eq!(
+            tags,
+            Value::Array(vec![
+                "a".into(),
+                "x".into(),
+                "c".into(),
+                Value::Null,
+                "y".into()
+            ])
+        );
+    }
+
+    #[test]
+    fn add_fields_appends_to_arrays() {
+        let tags = tags(&[("tags", vec!["d", "e"].into())], true);
+        assert_eq!(
+            tags,
+            Value::Array(vec![
+                "a".into(),
+                "b".into(),
+                "c".into(),
+                "d".into(),
+                "e".into()
+            ])
+        );
+    }
 }
@@ -0,0 +1,393 @@
+use super::{
+    util::runtime_transform::{RuntimeTransform, Timer},
+    Transform,
+};
+use crate::{
+    event::metric::{Metric, MetricKind, MetricValue},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    Event,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("interval_secs must be greater than zero"))]
+    ZeroInterval,
+    #[snafu(display("max_series must be greater than zero"))]
+    ZeroMaxSeries,
+}
+
+#[derive(Deserialize, Serialize, Debug, Derivative)]
+#[serde(deny_unknown_fields)]
+#[derivative(Default)]
+pub struct AggregateConfig {
+    #[serde(default = "default_interval_secs")]
+    #[derivative(Default(value = "default_interval_secs()"))]
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub gauge: GaugeAggregation,
+    #[serde(default = "default_max_series")]
+    #[derivative(Default(value = "default_max_series()"))]
+    pub max_series: usize,
+}
+
+/// How the absolute gauges of a series are combined within an interval.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum GaugeAggregation {
+    #[derivative(Default)]
+    Last,
+    Avg,
+    Min,
+    Max,
+}
+
+fn default_interval_secs() -> u64 {
+    10
+}
+
+fn default_max_series() -> usize {
+    10_000
+}
+
+inventory::submit! {
+    TransformDescription::new::<AggregateConfig>("aggregate")
+}
+
+#[typetag::serde(name = "aggregate")]
+impl TransformConfig for AggregateConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        if self.interval_secs == 0 {
+            return Err(BuildError::ZeroInterval.into());
+        }
+        if self.max_series == 0 {
+            return Err(BuildError::ZeroMaxSeries.into());
+        }
+
+        Ok(Box::new(Aggregate::new(self)))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "aggregate"
+    }
+}
+
+type SeriesKey = (String, Option<BTreeMap<String, String>>);
+
+pub struct Aggregate {
+    interval_secs: u64,
+    gauge: GaugeAggregation,
+    max_series: usize,
+    series: IndexMap<SeriesKey, Series>,
+}
+
+/// The aggregated metric of a series, and the running sum of its gauges
+/// for averages.
+struct Series {
+    metric: Metric,
+    sum: f64,
+    samples: u64,
+}
+
+impl Aggregate {
+    pub fn new(config: &AggregateConfig) -> Self {
+        Aggregate {
+            interval_secs: config.interval_secs,
+            gauge: config.gauge,
+            max_series: config.max_series,
+            series: IndexMap::new(),
+        }
+    }
+
+    /// Adds `metric` to its series. The metric is handed back if it can't
+    /// be aggregated, or a previous value of the series is if it had to be
+    /// flushed early.
+    fn aggregate(&mut self, metric: Metric) -> Option<Metric> {
+        match metric.value {
+            MetricValue::Counter { .. } | MetricValue::Gauge { .. } => (),
+            _ => return Some(metric),
+        }
+
+        let key = (metric.name.clone(), metric.tags.clone());
+        let gauge = self.gauge;
+        let series = match self.series.get_mut(&key) {
+            Some(series) => series,
+            None if self.series.len() >= self.max_series => {
+                warn!(
+                    message = "Too many series tracked; passing metric through unaggregated.",
+                    max_series = self.max_series as u64,
+                    rate_limit_secs = 30,
+                );
+                return Some(metric);
+            }
+            None => {
+                let sum = metric_value(&metric);
+                self.series.insert(
+                    key,
+                    Series {
+                        metric,
+                        sum,
+                        samples: 1,
+                    },
+                );
+                return None;
+            }
+        };
+
+        let timestamp = metric.timestamp.or(series.metric.timestamp);
+        match (
+            &series.metric.kind,
+            &mut series.metric.value,
+            &metric.kind,
+            &metric.value,
+        ) {
+            (
+                MetricKind::Incremental,
+                MetricValue::Counter { value },
+                MetricKind::Incremental,
+                MetricValue::Counter { value: other },
+            )
+            | (
+                MetricKind::Incremental,
+                MetricValue::Gauge { value },
+                MetricKind::Incremental,
+                MetricValue::Gauge { value: other },
+            ) => *value += other,
+            (
+                MetricKind::Absolute,
+                MetricValue::Counter { value },
+                MetricKind::Absolute,
+                MetricValue::Counter { value: other },
+            ) => {
+                if *other < *value {
+                    // The counter was reset. Keeping only the last value would
+                    // hide the reset from downstream if the counter caught up
+                    // before the flush, so the value so far goes out now.
+                    return Some(std::mem::replace(&mut series.metric, metric));
+                }
+                *value = *other;
+            }
+            (
+                MetricKind::Absolute,
+                MetricValue::Gauge { value },
+                MetricKind::Absolute,
+                MetricValue::Gauge { value: other },
+            ) => {
+                series.sum += other;
+                series.samples += 1;
+                *value = match gauge {
+                    GaugeAggregation::Last => *other,
+                    GaugeAggregation::Avg => series.sum / series.samples as f64,
+                    GaugeAggregation::Min => value.min(*other),
+                    GaugeAggregation::Max => value.max(*other),
+                };
+            }
+            _ => {
+                debug!(
+                    message = "Metric type changed within the interval; passing metric through unaggregated.",
+                    name = &metric.name[..],
+                    rate_limit_secs = 30,
+                );
+                return Some(metric);
+            }
+        }
+        series.metric.timestamp = timestamp;
+
+        None
+    }
+
+    fn flush<F>(&mut self, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        // Series start over every interval, so incremental counters only
+        // ever carry what was counted since the previous flush.
+        for (_, series) in self.series.drain(..) {
+            emit_fn(Event::Metric(series.metric));
+        }
+    }
+}
+
+fn metric_value(metric: &Metric) -> f64 {
+    match metric.value {
+        MetricValue::Counter { value } | MetricValue::Gauge { value } => value,
+        _ => 0.0,
+    }
+}
+
+impl RuntimeTransform for Aggregate {
+    fn hook_process<F>(&mut self, event: Event, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        if let Some(metric) = self.aggregate(event.into_metric()) {
+            emit_fn(Event::Metric(metric));
+        }
+    }
+
+    fn hook_shutdown<F>(&mut self, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.flush(emit_fn);
+    }
+
+    fn timer_handler<F>(&mut self, _timer: Timer, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.flush(emit_fn);
+    }
+
+    fn timers(&self) -> Vec<Timer> {
+        vec![Timer {
+            id: 0,
+            interval_seconds: self.interval_secs,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{offset::TimeZone, Utc};
+
+    fn metric(name: &str, host: &str, kind: MetricKind, value: MetricValue) -> Event {
+        let mut tags = BTreeMap::new();
+        tags.insert("host".to_owned(), host.to_owned());
+        Event::Metric(Metric {
+            name: name.into(),
+            timestamp: Some(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0)),
+            tags: Some(tags),
+            kind,
+            value,
+        })
+    }
+
+    fn counter(host: &str, kind: MetricKind, value: f64) -> Event {
+        metric("requests", host, kind, MetricValue::Counter { value })
+    }
+
+    fn gauge(value: f64) -> Event {
+        metric(
+            "memory",
+            "web-1",
+            MetricKind::Absolute,
+            MetricValue::Gauge { value },
+        )
+    }
+
+    fn aggregate(config: &str, events: Vec<Event>) -> (Vec<Event>, Vec<Event>) {
+        let config: AggregateConfig = toml::from_str(config).unwrap();
+        let mut aggregate = Aggregate::new(&config);
+
+        let mut passed = Vec::new();
+        for event in events {
+            aggregate.hook_process(event, |event| passed.push(event));
+        }
+        let mut flushed = Vec::new();
+        aggregate.timer_handler(aggregate.timers()[0], |event| flushed.push(event));
+
+        let mut empty = Vec::new();
+        aggregate.timer_handler(aggregate.timers()[0], |event| empty.push(event));
+        assert!(empty.is_empty());
+
+        (passed, flushed)
+    }
+
+    fn value(event: &Event) -> f64 {
+        metric_value(event.as_metric())
+    }
+
+    #[test]
+    fn aggregate_sums_incremental_counters_per_series() {
+        let events = vec![
+            counter("web-1", MetricKind::Incremental, 1.0),
+            counter("web-2", MetricKind::Incremental, 10.0),
+            counter("web-1", MetricKind::Incremental, 2.0),
+            counter("web-1", MetricKind::Incremental, 3.0),
+            counter("web-2", MetricKind::Incremental, 20.0),
+        ];
+
+        let (passed, flushed) = aggregate("", events);
+        assert!(passed.is_empty());
+        assert_eq!(flushed.len(), 2);
+        assert_eq!(flushed[0], counter("web-1", MetricKind::Incremental, 6.0));
+        assert_eq!(flushed[1], counter("web-2", MetricKind::Incremental, 30.0));
+    }
+
+    #[test]
+    fn aggregate_keeps_last_absolute_counter_and_flushes_resets() {
+        let events = vec![
+            counter("web-1", MetricKind::Absolute, 10.0),
+            counter("web-1", MetricKind::Absolute, 15.0),
+            counter("web-1", MetricKind::Absolute, 2.0),
+            counter("web-1", MetricKind::Absolute, 20.0),
+        ];
+
+        let (passed, flushed) = aggregate("", events);
+        assert_eq!(passed, vec![counter("web-1", MetricKind::Absolute, 15.0)]);
+        assert_eq!(flushed, vec![counter("web-1", MetricKind::Absolute, 20.0)]);
+    }
+
+    #[test]
+    fn aggregate_combines_gauges() {
+        let burst = || vec![gauge(4.0), gauge(1.0), gauge(7.0), gauge(2.0)];
+
+        for &(config, expected) in &[
+            ("", 2.0),
+            (r#"gauge = "avg""#, 3.5),
+            (r#"gauge = "min""#, 1.0),
+            (r#"gauge = "max""#, 7.0),
+        ] {
+            let (passed, flushed) = aggregate(config, burst());
+            assert!(passed.is_empty());
+            assert_eq!(flushed.len(), 1);
+            assert_eq!(value(&flushed[0]), expected, "{}", config);
+        }
+    }
+
+    #[test]
+    fn aggregate_passes_through_what_it_cannot_aggregate() {
+        let set = metric(
+            "users",
+            "web-1",
+            MetricKind::Incremental,
+            MetricValue::Set {
+                values: vec!["alice".to_owned()].into_iter().collect(),
+            },
+        );
+        let events = vec![
+            counter("web-1", MetricKind::Incremental, 1.0),
+            counter("web-1", MetricKind::Absolute, 5.0),
+            set.clone(),
+            counter("web-2", MetricKind::Incremental, 1.0),
+        ];
+
+        let (passed, flushed) = aggregate("max_series = 1", events);
+        assert_eq!(
+            passed,
+            vec![
+                counter("web-1", MetricKind::Absolute, 5.0),
+                set,
+                counter("web-2", MetricKind::Incremental, 1.0),
+            ]
+        );
+        assert_eq!(
+            flushed,
+            vec![counter("web-1", MetricKind::Incremental, 1.0)]
+        );
+    }
+}
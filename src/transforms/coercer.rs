@@ -1,7 +1,7 @@
 use super::Transform;
-use crate::event::Event;
+use crate::event::{Event, Value};
 use crate::topology::config::{DataType, TransformConfig, TransformContext, TransformDescription};
-use crate::types::{parse_conversion_map, Conversion};
+use crate::types::{self, parse_conversion_map, Conversion};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str;
@@ -59,7 +59,7 @@ impl Transform for Coercer {
             let new_log = new_event.as_mut_log();
             for (field, conv) in &self.types {
                 if let Some(value) = log.remove(field) {
-                    match conv.convert(value) {
+                    match coerce(conv, value) {
                         Ok(converted) => {
                             new_log.insert(field, converted);
                         }
@@ -78,7 +78,7 @@ impl Transform for Coercer {
         } else {
             for (field, conv) in &self.types {
                 if let Some(value) = log.remove(field) {
-                    match conv.convert(value) {
+                    match coerce(conv, value) {
                         Ok(converted) => {
                             log.insert(field, converted);
                         }
@@ -98,6 +98,22 @@ impl Transform for Coercer {
     }
 }
 
+/// Arrays are coerced element by element, skipping the nulls left behind by
+/// removed elements. One element failing fails the whole array.
+fn coerce(conv: &Conversion, value: Value) -> Result<Value, types::Error> {
+    match value {
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::Null => Ok(Value::Null),
+                value => coerce(conv, value),
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        value => conv.convert(value),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::CoercerConfig;
@@ -154,6 +170,36 @@ mod tests {
         assert!(log.get(&"float".into()).is_none());
     }
 
+    #[test]
+    fn converts_array_elements() {
+        let rt = crate::runtime::Runtime::single_threaded().unwrap();
+        let mut event = Event::from("dummy message");
+        event
+            .as_mut_log()
+            .insert("ports", vec![Value::from("80"), Value::Null, "443".into()]);
+        event.as_mut_log().insert("hosts[0].port", "8080");
+        event.as_mut_log().insert("hosts[1].port", "9090");
+
+        let mut coercer = toml::from_str::<CoercerConfig>(
+            r#"
+            [types]
+            ports = "int"
+            "hosts[1].port" = "int"
+            "#,
+        )
+        .unwrap()
+        .build(TransformContext::new_test(rt.executor()))
+        .unwrap();
+        let log = coercer.transform(event).unwrap().into_log();
+
+        assert_eq!(
+            log[&"ports".into()],
+            Value::Array(vec![Value::Integer(80), Value::Null, Value::Integer(443)])
+        );
+        assert_eq!(log[&"hosts[0].port".into()], Value::Bytes("8080".into()));
+        assert_eq!(log[&"hosts[1].port".into()], Value::Integer(9090));
+    }
+
     #[test]
     fn drops_unspecified_fields() {
         let log = parse_it("drop_unspecified = true");
@@ -1,4 +1,7 @@
-use super::Transform;
+use super::{
+    util::fingerprint::{Fingerprint, FingerprintConfig, Fingerprinter},
+    Transform,
+};
 use crate::{
     event,
     event::{Event, Value},
@@ -7,8 +10,15 @@ use crate::{
 use bytes::Bytes;
 use lru::LruCache;
 use serde::{Deserialize, Serialize};
+use snafu::Snafu;
 use string_cache::DefaultAtom as Atom;
 
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("`fields` and `fingerprint` can't be used together"))]
+    FieldsWithFingerprint,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub enum FieldMatchConfig {
@@ -33,6 +43,8 @@ pub struct DedupeConfig {
     pub fields: FieldMatchConfig,
     #[serde(default = "default_cache_config")]
     pub cache: CacheConfig,
+    /// Keys events by their fingerprint instead of by `fields`.
+    pub fingerprint: Option<FingerprintConfig>,
 }
 
 fn default_cache_config() -> CacheConfig {
@@ -61,12 +73,14 @@ impl DedupeConfig {
         Self {
             fields,
             cache: self.cache.clone(),
+            fingerprint: self.fingerprint.clone(),
         }
     }
 }
 
 pub struct Dedupe {
     config: DedupeConfig,
+    fingerprinter: Option<Fingerprinter>,
     cache: LruCache<CacheEntry, bool>,
 }
 
@@ -77,7 +91,14 @@ inventory::submit! {
 #[typetag::serde(name = "dedupe")]
 impl TransformConfig for DedupeConfig {
     fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
-        Ok(Box::new(Dedupe::new(self.fill_default())))
+        if self.fingerprint.is_some() {
+            if let FieldMatchConfig::MatchFields(_) | FieldMatchConfig::IgnoreFields(_) =
+                self.fields
+            {
+                return Err(Box::new(BuildError::FieldsWithFingerprint));
+            }
+        }
+        Ok(Box::new(Dedupe::new(self.fill_default())?))
     }
 
     fn input_type(&self) -> DataType {
@@ -135,12 +156,18 @@ fn type_id_for_value(val: &Value) -> TypeId {
 }
 
 impl Dedupe {
-    pub fn new(config: DedupeConfig) -> Self {
+    pub fn new(config: DedupeConfig) -> crate::Result<Self> {
         let num_entries = config.cache.num_events;
-        Self {
+        let fingerprinter = config
+            .fingerprint
+            .as_ref()
+            .map(FingerprintConfig::build)
+            .transpose()?;
+        Ok(Self {
             config,
+            fingerprinter,
             cache: LruCache::new(num_entries),
-        }
+        })
     }
 }
 
@@ -183,7 +210,12 @@ fn build_cache_entry(event: &Event, fields: &FieldMatchConfig) -> CacheEntry {
 
 impl Transform for Dedupe {
     fn transform(&mut self, event: Event) -> Option<Event> {
-        let cache_entry = build_cache_entry(&event, &self.config.fields);
+        let cache_entry = match &self.fingerprinter {
+            Some(fingerprinter) => {
+                CacheEntry::Fingerprint(fingerprinter.fingerprint(event.as_log()))
+            }
+            None => build_cache_entry(&event, &self.config.fields),
+        };
         if self.cache.put(cache_entry, true).is_some() {
             warn!(
                 message = "Encountered duplicate event; discarding",
@@ -201,7 +233,12 @@ impl Transform for Dedupe {
 mod tests {
     use super::Dedupe;
     use crate::transforms::dedupe::{CacheConfig, DedupeConfig, FieldMatchConfig};
-    use crate::{event::Event, event::Value, transforms::Transform};
+    use crate::{
+        event::Event,
+        event::Value,
+        topology::config::{TransformConfig, TransformContext},
+        transforms::Transform,
+    };
     use std::collections::BTreeMap;
     use string_cache::DefaultAtom as Atom;
 
@@ -209,7 +246,9 @@ mod tests {
         Dedupe::new(DedupeConfig {
             cache: CacheConfig { num_events },
             fields: { FieldMatchConfig::MatchFields(fields) },
+            fingerprint: None,
         })
+        .unwrap()
     }
 
     fn make_ignore_transform(num_events: usize, given_fields: Vec<String>) -> Dedupe {
@@ -220,7 +259,9 @@ mod tests {
         Dedupe::new(DedupeConfig {
             cache: CacheConfig { num_events },
             fields: { FieldMatchConfig::IgnoreFields(fields) },
+            fingerprint: None,
         })
+        .unwrap()
     }
 
     #[test]
@@ -464,4 +505,43 @@ mod tests {
         let new_event = transform.transform(event2).unwrap();
         assert_eq!(false, new_event.as_log().contains(&"matched".into()));
     }
+
+    #[test]
+    fn dedupe_by_fingerprint() {
+        let config: DedupeConfig = toml::from_str(
+            r#"
+            fingerprint.method = "fields"
+            fingerprint.fields = ["matched2", "matched1"]
+            fingerprint.algorithm = "sha256"
+            "#,
+        )
+        .unwrap();
+        let mut transform = Dedupe::new(config.fill_default()).unwrap();
+
+        let mut event1 = Event::from("message");
+        event1.as_mut_log().insert("matched1", "value1");
+        event1.as_mut_log().insert("matched2", "value2");
+
+        let mut event2 = Event::from("another message");
+        event2.as_mut_log().insert("matched2", "value2");
+        event2.as_mut_log().insert("matched1", "value1");
+
+        assert!(transform.transform(event1).is_some());
+        assert_eq!(None, transform.transform(event2));
+    }
+
+    #[test]
+    fn dedupe_fingerprint_excludes_fields() {
+        let config: DedupeConfig = toml::from_str(
+            r#"
+            fields.match = ["matched"]
+            fingerprint.method = "event"
+            "#,
+        )
+        .unwrap();
+        let rt = crate::test_util::runtime();
+        assert!(config
+            .build(TransformContext::new_test(rt.executor()))
+            .is_err());
+    }
 }
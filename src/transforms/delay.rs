@@ -0,0 +1,277 @@
+use super::{
+    util::runtime_transform::{RuntimeTransform, Timer},
+    Transform,
+};
+use crate::{
+    conditions::{AnyCondition, Condition},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    Event,
+};
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("delay_secs must be greater than zero"))]
+    ZeroDelay,
+    #[snafu(display("max_events must be greater than zero"))]
+    ZeroMaxEvents,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DelayConfig {
+    pub delay_secs: u64,
+    #[serde(default = "default_max_events")]
+    pub max_events: usize,
+    /// Releases the held events, and the event itself, as soon as an event
+    /// matching it comes in.
+    pub release_when: Option<AnyCondition>,
+}
+
+fn default_max_events() -> usize {
+    10_000
+}
+
+inventory::submit! {
+    TransformDescription::new_without_default::<DelayConfig>("delay")
+}
+
+#[typetag::serde(name = "delay")]
+impl TransformConfig for DelayConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        if self.delay_secs == 0 {
+            return Err(BuildError::ZeroDelay.into());
+        }
+        if self.max_events == 0 {
+            return Err(BuildError::ZeroMaxEvents.into());
+        }
+        let release_when = match &self.release_when {
+            Some(condition) => Some(condition.build()?),
+            None => None,
+        };
+
+        Ok(Box::new(Delay::new(
+            Duration::from_secs(self.delay_secs),
+            self.max_events,
+            release_when,
+        )))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "delay"
+    }
+}
+
+/// Holds events for `delay` and emits them in the order they came in.
+/// They're checked for release every second, so an event is held for up
+/// to a second longer than `delay`.
+pub struct Delay {
+    delay: Duration,
+    max_events: usize,
+    release_when: Option<Box<dyn Condition>>,
+    /// The events held, with when they're due, which is in the order they
+    /// came in as they're all held for the same time.
+    held: VecDeque<(Instant, Event)>,
+}
+
+impl Delay {
+    pub fn new(
+        delay: Duration,
+        max_events: usize,
+        release_when: Option<Box<dyn Condition>>,
+    ) -> Self {
+        Self {
+            delay,
+            max_events,
+            release_when,
+            held: VecDeque::new(),
+        }
+    }
+
+    fn hold<F>(&mut self, event: Event, now: Instant, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        if let Some(condition) = &self.release_when {
+            if condition.check(&event) {
+                self.flush(&mut emit_fn);
+                emit_fn(event);
+                return;
+            }
+        }
+
+        if self.held.len() >= self.max_events {
+            warn!(
+                message = "Too many events held; releasing the oldest one early.",
+                max_events = self.max_events as u64,
+                rate_limit_secs = 30,
+            );
+            if let Some((_, oldest)) = self.held.pop_front() {
+                emit_fn(oldest);
+            }
+        }
+        self.held.push_back((now + self.delay, event));
+    }
+
+    fn release<F>(&mut self, now: Instant, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        while self.held.front().map_or(false, |(due, _)| *due <= now) {
+            if let Some((_, event)) = self.held.pop_front() {
+                emit_fn(event);
+            }
+        }
+    }
+
+    fn flush<F>(&mut self, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        for (_, event) in self.held.drain(..) {
+            emit_fn(event);
+        }
+    }
+}
+
+impl RuntimeTransform for Delay {
+    fn hook_process<F>(&mut self, event: Event, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.hold(event, Instant::now(), emit_fn);
+    }
+
+    fn hook_shutdown<F>(&mut self, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.flush(emit_fn);
+    }
+
+    fn timer_handler<F>(&mut self, _timer: Timer, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.release(Instant::now(), emit_fn);
+    }
+
+    fn timers(&self) -> Vec<Timer> {
+        vec![Timer {
+            id: 0,
+            interval_seconds: 1,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event;
+
+    fn build(config: &str) -> Delay {
+        let config: DelayConfig = toml::from_str(config).unwrap();
+        let release_when = config
+            .release_when
+            .as_ref()
+            .map(|condition| condition.build().unwrap());
+        Delay::new(
+            Duration::from_secs(config.delay_secs),
+            config.max_events,
+            release_when,
+        )
+    }
+
+    fn messages(events: &[Event]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| event.as_log()[&event::log_schema().message_key()].to_string_lossy())
+            .collect()
+    }
+
+    #[test]
+    fn delay_holds_events_for_delay() {
+        let mut delay = build("delay_secs = 5");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        delay.hold("a".into(), start, |event| output.push(event));
+        delay.hold("b".into(), start + Duration::from_secs(1), |event| {
+            output.push(event)
+        });
+        delay.hold("c".into(), start + Duration::from_secs(2), |event| {
+            output.push(event)
+        });
+
+        delay.release(start + Duration::from_millis(4999), |event| {
+            output.push(event)
+        });
+        assert!(output.is_empty());
+
+        delay.release(start + Duration::from_secs(6), |event| output.push(event));
+        assert_eq!(messages(&output), vec!["a", "b"]);
+
+        delay.release(start + Duration::from_secs(7), |event| output.push(event));
+        assert_eq!(messages(&output), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn delay_flushes_held_events_on_shutdown() {
+        let mut delay = build("delay_secs = 60");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        for message in &["a", "b", "c"] {
+            delay.hold((*message).into(), start, |event| output.push(event));
+        }
+        assert!(output.is_empty());
+
+        delay.hook_shutdown(|event| output.push(event));
+        assert_eq!(messages(&output), vec!["a", "b", "c"]);
+        assert!(delay.held.is_empty());
+    }
+
+    #[test]
+    fn delay_releases_oldest_event_over_max_events() {
+        let mut delay = build("delay_secs = 60\nmax_events = 2");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        for message in &["a", "b", "c", "d"] {
+            delay.hold((*message).into(), start, |event| output.push(event));
+        }
+        assert_eq!(messages(&output), vec!["a", "b"]);
+        assert_eq!(delay.held.len(), 2);
+    }
+
+    #[test]
+    fn delay_releases_held_events_on_condition() {
+        let mut delay = build(
+            r#"
+            delay_secs = 60
+            release_when."message.eq" = "response"
+            "#,
+        );
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        for message in &["a", "b", "response", "c"] {
+            delay.hold((*message).into(), start, |event| output.push(event));
+        }
+        assert_eq!(messages(&output), vec!["a", "b", "response"]);
+        assert_eq!(delay.held.len(), 1);
+    }
+}
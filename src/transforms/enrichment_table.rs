@@ -0,0 +1,635 @@
+//! Enriches events with the columns of the row of a CSV or JSON file that
+//! matches them on one or more key fields, like the team and owner of a
+//! service by the service's id.
+//!
+//! The file is loaded into memory, indexed by key and holding just the
+//! columns merged into events, and reloaded in the background when it
+//! changes.
+
+use super::Transform;
+use crate::{
+    emit,
+    event::{Event, Value},
+    internal_events::{EnrichmentTableLoadFailed, EnrichmentTableLoaded, EnrichmentTableMissed},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+};
+use futures::compat::Future01CompatExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use snafu::{ResultExt, Snafu};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, Instant, SystemTime},
+};
+use string_cache::DefaultAtom as Atom;
+use tokio01::timer::Delay;
+use tracing_futures::Instrument;
+
+/// Separates the values of composite keys.
+const KEY_SEPARATOR: char = '\u{1f}';
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EnrichmentTableConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: TableFormat,
+    /// Columns rows are looked up by.
+    pub key_columns: Vec<String>,
+    /// Fields of events matched against `key_columns`, in the same order,
+    /// named after the columns by default.
+    pub key_fields: Option<Vec<Atom>>,
+    /// Columns merged into events, all but the key columns by default.
+    pub columns: Option<Vec<String>>,
+    /// Field the columns are inserted under, the root of events by default.
+    pub target: Option<Atom>,
+    #[serde(default = "crate::serde::default_true")]
+    pub case_sensitive: bool,
+    #[serde(default = "default_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum TableFormat {
+    /// Comma separated values, with a header row naming the columns.
+    #[derivative(Default)]
+    Csv,
+    /// An array of objects, or objects one after the other, as in NDJSON.
+    Json,
+}
+
+fn default_reload_interval_secs() -> u64 {
+    10
+}
+
+inventory::submit! {
+    TransformDescription::new_without_default::<EnrichmentTableConfig>("enrichment_table")
+}
+
+#[typetag::serde(name = "enrichment_table")]
+impl TransformConfig for EnrichmentTableConfig {
+    fn build(&self, cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        let (transform, reloader) = EnrichmentTable::new(self)?;
+        let interval = Duration::from_secs(self.reload_interval_secs);
+
+        cx.executor().spawn_std(
+            reloader
+                .run(interval)
+                .instrument(info_span!("enrichment_table: reloader")),
+        );
+
+        Ok(Box::new(transform))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "enrichment_table"
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("`key_columns` can't be empty"))]
+    NoKeyColumns,
+    #[snafu(display(
+        "`key_fields` has {} fields, but `key_columns` has {} columns",
+        fields,
+        columns
+    ))]
+    KeyFieldsMismatch { fields: usize, columns: usize },
+    #[snafu(display("Could not read enrichment table {:?}: {}", path, source))]
+    ReadTable {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not parse enrichment table {:?}: {}", path, source))]
+    ParseCsv { path: PathBuf, source: csv::Error },
+    #[snafu(display("Could not parse enrichment table {:?}: {}", path, source))]
+    ParseJson {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[snafu(display("Enrichment table {:?} has no column {:?}", path, column))]
+    MissingColumn { path: PathBuf, column: String },
+}
+
+/// The rows of a table by key, holding the values of the columns merged into
+/// events, in the order of `fields`.
+#[derive(Debug, Default)]
+struct Table {
+    fields: Vec<Atom>,
+    rows: HashMap<String, Box<[Option<Value>]>>,
+}
+
+#[derive(Debug)]
+pub struct EnrichmentTable {
+    key_fields: Vec<Atom>,
+    case_sensitive: bool,
+    table: Arc<RwLock<Table>>,
+}
+
+impl EnrichmentTable {
+    /// The transform, with its table loaded, and what reloads the table.
+    pub fn new(config: &EnrichmentTableConfig) -> crate::Result<(Self, Reloader)> {
+        if config.key_columns.is_empty() {
+            return Err(Box::new(BuildError::NoKeyColumns));
+        }
+        let key_fields = match &config.key_fields {
+            Some(fields) if fields.len() != config.key_columns.len() => {
+                return Err(Box::new(BuildError::KeyFieldsMismatch {
+                    fields: fields.len(),
+                    columns: config.key_columns.len(),
+                }))
+            }
+            Some(fields) => fields.clone(),
+            None => config.key_columns.iter().map(Atom::from).collect(),
+        };
+
+        let mut file = TableFile {
+            path: config.path.clone(),
+            format: config.format,
+            key_columns: config.key_columns.clone(),
+            columns: config.columns.clone(),
+            target: config.target.clone(),
+            case_sensitive: config.case_sensitive,
+            version: None,
+        };
+        let table = Arc::new(RwLock::new(file.load()?));
+        let reloader = Reloader {
+            file,
+            table: Arc::downgrade(&table),
+        };
+
+        let transform = Self {
+            key_fields,
+            case_sensitive: config.case_sensitive,
+            table,
+        };
+        Ok((transform, reloader))
+    }
+}
+
+impl Transform for EnrichmentTable {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        let log = event.as_mut_log();
+        let key = make_key(
+            self.key_fields
+                .iter()
+                .map(|field| log.get(field).map(Value::to_string_lossy)),
+            self.case_sensitive,
+        );
+
+        let table = self.table.read().unwrap();
+        match key.and_then(|key| table.rows.get(&key)) {
+            Some(values) => {
+                for (field, value) in table.fields.iter().zip(values.iter()) {
+                    if let Some(value) = value {
+                        log.insert(field, value.clone());
+                    }
+                }
+            }
+            None => emit!(EnrichmentTableMissed),
+        }
+
+        Some(event)
+    }
+}
+
+/// Reloads the table of a transform when its file changes, for as long as
+/// the transform exists.
+pub struct Reloader {
+    file: TableFile,
+    table: Weak<RwLock<Table>>,
+}
+
+impl Reloader {
+    async fn run(mut self, interval: Duration) {
+        while Weak::strong_count(&self.table) > 0 {
+            Delay::new(Instant::now() + interval)
+                .compat()
+                .await
+                .expect("Timer not set.");
+
+            // Large tables take a while to load, so they're loaded off the
+            // runtime's threads.
+            self = tokio::task::spawn_blocking(move || {
+                self.reload_if_changed();
+                self
+            })
+            .await
+            .expect("enrichment table reloader panicked");
+        }
+    }
+
+    /// Whether the table was reloaded. A table that fails to load is kept
+    /// as it was.
+    fn reload_if_changed(&mut self) -> bool {
+        if !self.file.changed() {
+            return false;
+        }
+        let table = match self.table.upgrade() {
+            Some(table) => table,
+            None => return false,
+        };
+
+        match self.file.load() {
+            Ok(loaded) => {
+                // The old table is dropped after the lock is released.
+                let _old = std::mem::replace(&mut *table.write().unwrap(), loaded);
+                true
+            }
+            Err(error) => {
+                emit!(EnrichmentTableLoadFailed {
+                    path: &self.file.path,
+                    error,
+                });
+                false
+            }
+        }
+    }
+}
+
+struct TableFile {
+    path: PathBuf,
+    format: TableFormat,
+    key_columns: Vec<String>,
+    columns: Option<Vec<String>>,
+    target: Option<Atom>,
+    case_sensitive: bool,
+    /// The modification time and length of the file as last loaded.
+    version: Option<(SystemTime, u64)>,
+}
+
+impl TableFile {
+    fn current_version(&self) -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(&self.path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
+
+    /// Whether the file was modified since it was last loaded. A file that's
+    /// gone hasn't, so the table loaded from it is kept.
+    fn changed(&self) -> bool {
+        match self.current_version() {
+            Some(version) => Some(version) != self.version,
+            None => false,
+        }
+    }
+
+    fn load(&mut self) -> crate::Result<Table> {
+        self.version = self.current_version();
+        let data = fs::read(&self.path).context(ReadTable {
+            path: self.path.clone(),
+        })?;
+
+        let table = match self.format {
+            TableFormat::Csv => self.load_csv(&data)?,
+            TableFormat::Json => self.load_json(&data)?,
+        };
+        Ok(table)
+    }
+
+    fn load_csv(&self, data: &[u8]) -> crate::Result<Table> {
+        let mut reader = csv::Reader::from_reader(data);
+        let headers = reader
+            .headers()
+            .context(ParseCsv {
+                path: self.path.clone(),
+            })?
+            .clone();
+        let index_of = |column: &String| {
+            headers
+                .iter()
+                .position(|header| header == column)
+                .ok_or_else(|| BuildError::MissingColumn {
+                    path: self.path.clone(),
+                    column: column.clone(),
+                })
+        };
+
+        let key_indices = self
+            .key_columns
+            .iter()
+            .map(index_of)
+            .collect::<Result<Vec<_>, _>>()?;
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => headers
+                .iter()
+                .filter(|header| !self.key_columns.iter().any(|key| key == header))
+                .map(Into::into)
+                .collect(),
+        };
+        let indices = columns
+            .iter()
+            .map(index_of)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut builder = TableBuilder::new(self.fields(&columns));
+        for record in reader.records() {
+            let record = record.context(ParseCsv {
+                path: self.path.clone(),
+            })?;
+            let key = make_key(
+                key_indices.iter().map(|index| record.get(*index)),
+                self.case_sensitive,
+            );
+            let values = indices
+                .iter()
+                .map(|index| record.get(*index).map(Value::from))
+                .collect();
+            builder.insert(key, values);
+        }
+        Ok(builder.finish(self))
+    }
+
+    fn load_json(&self, data: &[u8]) -> crate::Result<Table> {
+        let is_array = data.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[');
+        let objects: Vec<Map<String, JsonValue>> = if is_array {
+            serde_json::from_slice(data)
+        } else {
+            serde_json::Deserializer::from_slice(data)
+                .into_iter::<Map<String, JsonValue>>()
+                .collect()
+        }
+        .context(ParseJson {
+            path: self.path.clone(),
+        })?;
+
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => {
+                let mut seen = HashSet::new();
+                objects
+                    .iter()
+                    .flat_map(Map::keys)
+                    .filter(|column| !self.key_columns.contains(column))
+                    .filter(|column| seen.insert(column.as_str()))
+                    .cloned()
+                    .collect()
+            }
+        };
+
+        let mut builder = TableBuilder::new(self.fields(&columns));
+        for mut object in objects {
+            let key = make_key(
+                self.key_columns
+                    .iter()
+                    .map(|column| object.get(column).and_then(json_key)),
+                self.case_sensitive,
+            );
+            let values = columns
+                .iter()
+                .map(|column| object.remove(column).map(Value::from))
+                .collect();
+            builder.insert(key, values);
+        }
+        Ok(builder.finish(self))
+    }
+
+    /// The fields the values of `columns` are inserted into.
+    fn fields(&self, columns: &[String]) -> Vec<Atom> {
+        columns
+            .iter()
+            .map(|column| match &self.target {
+                Some(target) => Atom::from(format!("{}.{}", target, column)),
+                None => Atom::from(column.as_str()),
+            })
+            .collect()
+    }
+}
+
+struct TableBuilder {
+    table: Table,
+    duplicates: usize,
+    skipped: usize,
+}
+
+impl TableBuilder {
+    fn new(fields: Vec<Atom>) -> Self {
+        Self {
+            table: Table {
+                fields,
+                rows: HashMap::new(),
+            },
+            duplicates: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Adds a row, unless it has no key or its key was already added.
+    fn insert(&mut self, key: Option<String>, values: Box<[Option<Value>]>) {
+        match key.map(|key| self.table.rows.entry(key)) {
+            Some(Entry::Vacant(entry)) => {
+                entry.insert(values);
+            }
+            Some(Entry::Occupied(_)) => self.duplicates += 1,
+            None => self.skipped += 1,
+        }
+    }
+
+    fn finish(mut self, file: &TableFile) -> Table {
+        self.table.rows.shrink_to_fit();
+        emit!(EnrichmentTableLoaded {
+            path: &file.path,
+            rows: self.table.rows.len(),
+            duplicates: self.duplicates,
+            skipped: self.skipped,
+        });
+        self.table
+    }
+}
+
+/// The key of the values of the key columns or fields, if none are missing.
+fn make_key<S: AsRef<str>>(
+    parts: impl IntoIterator<Item = Option<S>>,
+    case_sensitive: bool,
+) -> Option<String> {
+    let mut key = String::new();
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            key.push(KEY_SEPARATOR);
+        }
+        let part = part?;
+        if case_sensitive {
+            key.push_str(part.as_ref());
+        } else {
+            key.push_str(&part.as_ref().to_lowercase());
+        }
+    }
+    Some(key)
+}
+
+/// A JSON value as a key, spelled as it would be in an event.
+fn json_key(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::String(string) => Some(string.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_file;
+    use std::path::Path;
+
+    fn enrichment_table(path: &Path, config: &str) -> (EnrichmentTable, Reloader) {
+        let mut config: EnrichmentTableConfig =
+            toml::from_str(&format!("path = \"\"\n{}", config)).unwrap();
+        config.path = path.to_owned();
+        EnrichmentTable::new(&config).unwrap()
+    }
+
+    fn enrich(transform: &mut EnrichmentTable, fields: &[(&str, &str)]) -> Event {
+        let mut event = Event::from("message");
+        for (field, value) in fields {
+            event.as_mut_log().insert(*field, *value);
+        }
+        transform.transform(event).unwrap()
+    }
+
+    fn field(event: &Event, field: &str) -> Option<String> {
+        event
+            .as_log()
+            .get(&Atom::from(field))
+            .map(Value::to_string_lossy)
+    }
+
+    #[test]
+    fn enrichment_table_single_key() {
+        let path = temp_file();
+        fs::write(
+            &path,
+            "service_id,team,owner\n\
+             checkout,payments,alice\n\
+             Search,discovery,bob\n",
+        )
+        .unwrap();
+
+        let (mut transform, _) = enrichment_table(
+            &path,
+            r#"
+            key_columns = ["service_id"]
+            key_fields = ["service.id"]
+            target = "service"
+            case_sensitive = false
+            "#,
+        );
+
+        let event = enrich(&mut transform, &[("service.id", "checkout")]);
+        assert_eq!(field(&event, "service.team"), Some("payments".into()));
+        assert_eq!(field(&event, "service.owner"), Some("alice".into()));
+
+        let event = enrich(&mut transform, &[("service.id", "SEARCH")]);
+        assert_eq!(field(&event, "service.team"), Some("discovery".into()));
+
+        let event = enrich(&mut transform, &[("service.id", "unknown")]);
+        assert_eq!(field(&event, "service.team"), None);
+        let event = enrich(&mut transform, &[]);
+        assert_eq!(field(&event, "service.team"), None);
+    }
+
+    #[test]
+    fn enrichment_table_composite_key() {
+        let path = temp_file();
+        fs::write(
+            &path,
+            r#"[
+                {"region": "eu", "service_id": 1, "team": "payments", "tier": 1},
+                {"region": "us", "service_id": 1, "team": "billing", "tier": 2},
+                {"region": "us", "service_id": 2, "team": "search"}
+            ]"#,
+        )
+        .unwrap();
+
+        let (mut transform, _) = enrichment_table(
+            &path,
+            r#"
+            format = "json"
+            key_columns = ["region", "service_id"]
+            columns = ["team"]
+            "#,
+        );
+
+        let event = enrich(&mut transform, &[("region", "us"), ("service_id", "1")]);
+        assert_eq!(field(&event, "team"), Some("billing".into()));
+        assert_eq!(field(&event, "tier"), None);
+
+        let event = enrich(&mut transform, &[("region", "eu"), ("service_id", "1")]);
+        assert_eq!(field(&event, "team"), Some("payments".into()));
+
+        // Both parts of the key have to match, and be there.
+        let event = enrich(&mut transform, &[("region", "eu"), ("service_id", "2")]);
+        assert_eq!(field(&event, "team"), None);
+        let event = enrich(&mut transform, &[("service_id", "2")]);
+        assert_eq!(field(&event, "team"), None);
+    }
+
+    #[test]
+    fn enrichment_table_reload() {
+        let path = temp_file();
+        fs::write(&path, "id,team\n1,payments\n").unwrap();
+
+        let (mut transform, mut reloader) = enrichment_table(&path, r#"key_columns = ["id"]"#);
+        assert!(!reloader.reload_if_changed());
+
+        fs::write(&path, "id,team\n1,billing\n2,search\n").unwrap();
+        assert!(reloader.reload_if_changed());
+
+        let event = enrich(&mut transform, &[("id", "1")]);
+        assert_eq!(field(&event, "team"), Some("billing".into()));
+        let event = enrich(&mut transform, &[("id", "2")]);
+        assert_eq!(field(&event, "team"), Some("search".into()));
+
+        // A table that doesn't load is kept.
+        fs::write(&path, "name,team\n1,payments\n").unwrap();
+        assert!(!reloader.reload_if_changed());
+        let event = enrich(&mut transform, &[("id", "1")]);
+        assert_eq!(field(&event, "team"), Some("billing".into()));
+    }
+
+    #[test]
+    fn enrichment_table_invalid_configs() {
+        let path = temp_file();
+        fs::write(&path, "id,team\n1,payments\n").unwrap();
+
+        let build = |config: &str| {
+            let mut config: EnrichmentTableConfig =
+                toml::from_str(&format!("path = \"\"\n{}", config)).unwrap();
+            config.path = path.clone();
+            EnrichmentTable::new(&config).map(drop)
+        };
+
+        assert!(build(r#"key_columns = ["id"]"#).is_ok());
+        assert!(build(r#"key_columns = []"#).is_err());
+        assert!(build(r#"key_columns = ["name"]"#).is_err());
+        assert!(build(
+            r#"key_columns = ["id"]
+            columns = ["owner"]"#
+        )
+        .is_err());
+        assert!(build(
+            r#"key_columns = ["id"]
+            key_fields = ["a", "b"]"#
+        )
+        .is_err());
+        assert!(build(
+            r#"key_columns = ["id"]
+            format = "json""#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use super::Transform;
+use crate::{
+    event::{self, Event, Value},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::collections::HashMap;
+use string_cache::DefaultAtom as Atom;
+
+/// Syslog severities, indexed by their numeric value.
+const SEVERITIES: [&str; 8] = [
+    "emergency",
+    "alert",
+    "critical",
+    "error",
+    "warning",
+    "notice",
+    "info",
+    "debug",
+];
+
+/// How levels are commonly spelled, and the canonical levels they mean.
+const ALIASES: &[(&str, &str)] = &[
+    ("emerg", "emergency"),
+    ("emergency", "emergency"),
+    ("panic", "emergency"),
+    ("alert", "alert"),
+    ("crit", "critical"),
+    ("critical", "critical"),
+    ("fatal", "critical"),
+    ("err", "error"),
+    ("error", "error"),
+    ("warn", "warning"),
+    ("warning", "warning"),
+    ("notice", "notice"),
+    ("info", "info"),
+    ("information", "info"),
+    ("informational", "info"),
+    ("debug", "debug"),
+    ("dbg", "debug"),
+    ("trace", "trace"),
+];
+
+/// A syslog priority at the start of the message, like `<11>`.
+const PRIORITY_PATTERN: &str = r"^\s*<(?P<level>\d{1,3})>";
+/// A level given as a key/value pair, like `level=warn` or `"severity": 3`.
+const KEY_VALUE_PATTERN: &str = r#"(?i)\b(?:level|lvl|severity)"?\s*[=:]\s*"?(?P<level>\w+)"#;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Derivative)]
+#[serde(deny_unknown_fields, default)]
+#[derivative(Default)]
+pub struct LogLevelConfig {
+    pub field: Option<Atom>,
+    #[derivative(Default(value = "Atom::from(\"level\")"))]
+    pub target_field: Atom,
+    /// Regular expressions capturing a level in a `level` group, tried in
+    /// order before the built-in ones.
+    pub patterns: Vec<String>,
+    /// Levels, as spelled in events, and what they're normalized to. These
+    /// take precedence over the built-in spellings.
+    pub mapping: HashMap<String, String>,
+    #[derivative(Default(value = "String::from(\"unknown\")"))]
+    pub fallback: String,
+}
+
+inventory::submit! {
+    TransformDescription::new::<LogLevelConfig>("log_level")
+}
+
+#[typetag::serde(name = "log_level")]
+impl TransformConfig for LogLevelConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        Ok(Box::new(LogLevel::new(self)?))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "log_level"
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("Pattern {:?} has no `level` capture group", pattern))]
+    NoLevelGroup { pattern: String },
+}
+
+#[derive(Debug)]
+pub struct LogLevel {
+    field: Atom,
+    target_field: Atom,
+    patterns: Vec<Regex>,
+    /// Lowercased spellings of levels, and their normalized levels.
+    aliases: HashMap<String, String>,
+    fallback: String,
+}
+
+impl LogLevel {
+    pub fn new(config: &LogLevelConfig) -> crate::Result<Self> {
+        let mut aliases = ALIASES
+            .iter()
+            .map(|(alias, level)| (alias.to_string(), level.to_string()))
+            .collect::<HashMap<_, _>>();
+        for (alias, level) in &config.mapping {
+            aliases.insert(alias.to_lowercase(), level.clone());
+        }
+
+        let mut patterns = Vec::with_capacity(config.patterns.len() + 3);
+        for pattern in &config.patterns {
+            let regex = Regex::new(pattern).context(super::InvalidRegex)?;
+            if !regex.capture_names().any(|name| name == Some("level")) {
+                return Err(Box::new(BuildError::NoLevelGroup {
+                    pattern: pattern.clone(),
+                }));
+            }
+            patterns.push(regex);
+        }
+
+        // Levels spelled out anywhere in the message, the longest spelling
+        // first, so `error` isn't taken for `err`.
+        let mut spellings = aliases.keys().map(|s| regex::escape(s)).collect::<Vec<_>>();
+        spellings.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        let word_pattern = format!(r"(?i)\b(?P<level>{})\b", spellings.join("|"));
+
+        for pattern in &[PRIORITY_PATTERN, KEY_VALUE_PATTERN, word_pattern.as_str()] {
+            patterns.push(Regex::new(pattern).context(super::InvalidRegex)?);
+        }
+
+        Ok(Self {
+            field: config
+                .field
+                .clone()
+                .unwrap_or_else(|| event::log_schema().message_key().clone()),
+            target_field: config.target_field.clone(),
+            patterns,
+            aliases,
+            fallback: config.fallback.clone(),
+        })
+    }
+
+    fn detect(&self, value: &Value) -> Option<String> {
+        match value {
+            Value::Integer(number) => severity(*number).map(Into::into),
+            Value::Bytes(_) => {
+                let text = value.to_string_lossy();
+                self.patterns
+                    .iter()
+                    .filter_map(|pattern| pattern.captures(&text)?.name("level"))
+                    .find_map(|level| self.normalize(level.as_str()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The normalized level for a level as captured, either a spelling or
+    /// a syslog severity or priority, of which the severity is the lowest 3
+    /// bits.
+    fn normalize(&self, level: &str) -> Option<String> {
+        match level.parse::<i64>() {
+            Ok(number) => severity(number).map(Into::into),
+            Err(_) => self.aliases.get(&level.to_lowercase()).cloned(),
+        }
+    }
+}
+
+fn severity(number: i64) -> Option<&'static str> {
+    // Priorities go up to facility 23, severity 7.
+    if 0 <= number && number <= 191 {
+        Some(SEVERITIES[number as usize % 8])
+    } else {
+        None
+    }
+}
+
+impl Transform for LogLevel {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        let log = event.as_mut_log();
+        let level = log
+            .get(&self.field)
+            .and_then(|value| self.detect(value))
+            .unwrap_or_else(|| {
+                debug!(
+                    message = "no known level found.",
+                    field = self.field.as_ref(),
+                    rate_limit_secs = 30
+                );
+                self.fallback.clone()
+            });
+        log.insert(&self.target_field, level);
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_level(config: &str) -> LogLevel {
+        LogLevel::new(&toml::from_str(config).unwrap()).unwrap()
+    }
+
+    fn level_of(transform: &mut LogLevel, message: &str) -> String {
+        let event = transform.transform(Event::from(message)).unwrap();
+        event.as_log()[&Atom::from("level")].to_string_lossy()
+    }
+
+    #[test]
+    fn log_level_normalizes_representations() {
+        let mut transform = log_level("");
+
+        let cases = [
+            ("ERROR Unable to open file", "error"),
+            ("2020-06-11 12:00:00 [err] disk full", "error"),
+            ("<11>Jun 11 12:00:00 host app: failed", "error"),
+            ("<4>kernel: low memory", "warning"),
+            ("ts=2020-06-11 level=Warn msg=retrying", "warning"),
+            (r#"{"severity": 6, "msg": "started"}"#, "info"),
+            ("FATAL: out of memory", "critical"),
+            ("emerg: system unusable", "emergency"),
+            ("Notice: new connection", "notice"),
+            ("[INFORMATION] listening on :80", "info"),
+            ("DEBUG query took 3ms", "debug"),
+            ("trace: entering loop", "trace"),
+        ];
+        for (message, level) in cases.iter() {
+            assert_eq!(&level_of(&mut transform, message), level, "{}", message);
+        }
+    }
+
+    #[test]
+    fn log_level_numeric_field() {
+        let mut transform = log_level(r#"field = "severity""#);
+
+        for (severity, level) in &[(0, "emergency"), (3, "error"), (7, "debug"), (14, "info")] {
+            let mut event = Event::from("message");
+            event.as_mut_log().insert("severity", *severity);
+            let event = transform.transform(event).unwrap();
+            assert_eq!(
+                event.as_log()[&Atom::from("level")].to_string_lossy(),
+                *level
+            );
+        }
+    }
+
+    #[test]
+    fn log_level_unknown_fallback() {
+        let mut transform = log_level("");
+        assert_eq!(level_of(&mut transform, "nothing to see here"), "unknown");
+        assert_eq!(level_of(&mut transform, "level=loud"), "unknown");
+        assert_eq!(level_of(&mut transform, "<999> not a priority"), "unknown");
+
+        let mut transform = log_level(r#"fallback = "info""#);
+        assert_eq!(level_of(&mut transform, "nothing to see here"), "info");
+
+        let mut transform = log_level(r#"field = "missing""#);
+        assert_eq!(level_of(&mut transform, "ERROR"), "unknown");
+    }
+
+    #[test]
+    fn log_level_custom_mapping_and_patterns() {
+        let mut transform = log_level(
+            r#"
+            target_field = "level"
+            patterns = ['^(?P<level>[EWID])\d{4} ']
+            [mapping]
+            E = "error"
+            W = "warning"
+            I = "info"
+            D = "debug"
+            SEVERE = "critical"
+            warn = "warn"
+            "#,
+        );
+
+        assert_eq!(level_of(&mut transform, "E0611 12:00:00 failed"), "error");
+        assert_eq!(level_of(&mut transform, "I0611 12:00:00 started"), "info");
+        assert_eq!(level_of(&mut transform, "SEVERE: giving up"), "critical");
+        // The mapping overrides built-in spellings.
+        assert_eq!(level_of(&mut transform, "WARN slow"), "warn");
+    }
+
+    #[test]
+    fn log_level_invalid_patterns() {
+        let build = |config: &str| LogLevel::new(&toml::from_str(config).unwrap());
+
+        assert!(build(r#"patterns = ["(unclosed"]"#).is_err());
+        assert!(build(r#"patterns = ["(?P<severity>\\w+)"]"#).is_err());
+    }
+}
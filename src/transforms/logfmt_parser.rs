@@ -1,9 +1,11 @@
 use super::Transform;
 use crate::{
-    event::{self, Event},
+    event::{self, Event, Value},
+    logfmt,
     topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
     types::{parse_conversion_map, Conversion},
 };
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str;
@@ -15,6 +17,22 @@ pub struct LogfmtConfig {
     pub field: Option<Atom>,
     pub drop_field: bool,
     pub types: HashMap<Atom, String>,
+    /// Converts values that look like booleans or numbers, for keys without
+    /// a configured type.
+    pub infer_types: bool,
+    pub duplicate_keys: DuplicateKeys,
+}
+
+/// Which value is kept for a key that appears more than once.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum DuplicateKeys {
+    #[derivative(Default)]
+    Last,
+    First,
+    /// All of the values, in order, as an array.
+    Array,
 }
 
 inventory::submit! {
@@ -34,6 +52,8 @@ impl TransformConfig for LogfmtConfig {
             field: field.clone(),
             drop_field: self.drop_field,
             conversions,
+            infer_types: self.infer_types,
+            duplicate_keys: self.duplicate_keys,
         }))
     }
 
@@ -54,6 +74,48 @@ pub struct Logfmt {
     field: Atom,
     drop_field: bool,
     conversions: HashMap<Atom, Conversion>,
+    infer_types: bool,
+    duplicate_keys: DuplicateKeys,
+}
+
+impl Logfmt {
+    fn convert(&self, key: &Atom, val: String) -> Option<Value> {
+        if let Some(conv) = self.conversions.get(key) {
+            match conv.convert(val.into_bytes().into()) {
+                Ok(value) => Some(value),
+                Err(error) => {
+                    debug!(
+                        message = "Could not convert types.",
+                        key = &key[..],
+                        %error,
+                        rate_limit_secs = 30
+                    );
+                    None
+                }
+            }
+        } else if self.infer_types {
+            Some(infer_type(val))
+        } else {
+            Some(val.into())
+        }
+    }
+}
+
+/// Reads booleans and numbers, leaving anything else as it is.
+fn infer_type(val: String) -> Value {
+    match val.as_str() {
+        "true" => return Value::Boolean(true),
+        "false" => return Value::Boolean(false),
+        _ => (),
+    }
+    if let Ok(integer) = val.parse::<i64>() {
+        return Value::Integer(integer);
+    }
+    match val.parse::<f64>() {
+        // Leaves out words like `inf` and `NaN`.
+        Ok(float) if float.is_finite() => Value::Float(float),
+        _ => val.into(),
+    }
 }
 
 impl Transform for Logfmt {
@@ -62,32 +124,34 @@ impl Transform for Logfmt {
 
         let mut drop_field = self.drop_field;
         if let Some(value) = &value {
-            let pairs = logfmt::parse(value)
-                .into_iter()
-                // Filter out pairs with None value (i.e. non-logfmt data)
-                .filter_map(|logfmt::Pair { key, val }| val.map(|val| (Atom::from(key), val)));
+            let mut fields = IndexMap::<Atom, Vec<String>>::new();
+            for (key, val) in logfmt::parse(value) {
+                fields.entry(key.into()).or_default().push(val);
+            }
 
-            for (key, val) in pairs {
+            for (key, mut vals) in fields {
                 if key == self.field {
                     drop_field = false;
                 }
 
-                if let Some(conv) = self.conversions.get(&key) {
-                    match conv.convert(val.as_bytes().into()) {
-                        Ok(value) => {
-                            event.as_mut_log().insert(key, value);
-                        }
-                        Err(error) => {
-                            debug!(
-                                message = "Could not convert types.",
-                                key = &key[..],
-                                %error,
-                                rate_limit_secs = 30
-                            );
-                        }
-                    }
-                } else {
-                    event.as_mut_log().insert(key, val);
+                let value = match self.duplicate_keys {
+                    DuplicateKeys::Last => vals.pop().and_then(|val| self.convert(&key, val)),
+                    DuplicateKeys::First => vals
+                        .into_iter()
+                        .next()
+                        .and_then(|val| self.convert(&key, val)),
+                    DuplicateKeys::Array if vals.len() > 1 => Some(Value::Array(
+                        vals.into_iter()
+                            .filter_map(|val| self.convert(&key, val))
+                            .collect(),
+                    )),
+                    DuplicateKeys::Array => vals.pop().and_then(|val| self.convert(&key, val)),
+                };
+
+                // Keys are field paths, so flattened nested fields like
+                // `request.path` are inserted back into place.
+                if let Some(value) = value {
+                    event.as_mut_log().insert(key, value);
                 }
             }
 
@@ -108,25 +172,32 @@ impl Transform for Logfmt {
 
 #[cfg(test)]
 mod tests {
-    use super::LogfmtConfig;
+    use super::{DuplicateKeys, LogfmtConfig};
     use crate::{
-        event::{LogEvent, Value},
-        test_util,
+        event::{self, LogEvent, Value},
+        logfmt, test_util,
         topology::config::{TransformConfig, TransformContext},
         Event,
     };
 
     fn parse_log(text: &str, drop_field: bool, types: &[(&str, &str)]) -> LogEvent {
+        parse_log_with(
+            text,
+            LogfmtConfig {
+                drop_field,
+                types: types.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn parse_log_with(text: &str, config: LogfmtConfig) -> LogEvent {
         let event = Event::from(text);
 
         let rt = test_util::runtime();
-        let mut parser = LogfmtConfig {
-            field: None,
-            drop_field,
-            types: types.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
-        }
-        .build(TransformContext::new_test(rt.executor()))
-        .unwrap();
+        let mut parser = config
+            .build(TransformContext::new_test(rt.executor()))
+            .unwrap();
 
         parser.transform(event).unwrap().into_log()
     }
@@ -211,4 +282,86 @@ mod tests {
         assert_eq!(log[&"sample#memory_pgpgin".into()], "348836pages".into());
         assert_eq!(log[&"sample#memory_pgpgout".into()], "343403pages".into());
     }
+
+    #[test]
+    fn logfmt_infers_types() {
+        let log = parse_log_with(
+            "code=1234 ok=true ratio=-0.5 big=1e3 word=inf version=1.2.3 kept=042",
+            LogfmtConfig {
+                infer_types: true,
+                types: vec![("kept".into(), "string".into())].into_iter().collect(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(log[&"code".into()], Value::Integer(1234));
+        assert_eq!(log[&"ok".into()], Value::Boolean(true));
+        assert_eq!(log[&"ratio".into()], Value::Float(-0.5));
+        assert_eq!(log[&"big".into()], Value::Float(1000.0));
+        assert_eq!(log[&"word".into()], "inf".into());
+        assert_eq!(log[&"version".into()], "1.2.3".into());
+        assert_eq!(log[&"kept".into()], "042".into());
+    }
+
+    #[test]
+    fn logfmt_handles_duplicate_keys() {
+        let text = "tag=a status=200 tag=b tag=c";
+        let config = |duplicate_keys| LogfmtConfig {
+            duplicate_keys,
+            infer_types: true,
+            ..Default::default()
+        };
+
+        let log = parse_log_with(text, config(DuplicateKeys::Last));
+        assert_eq!(log[&"tag".into()], "c".into());
+
+        let log = parse_log_with(text, config(DuplicateKeys::First));
+        assert_eq!(log[&"tag".into()], "a".into());
+
+        let log = parse_log_with(text, config(DuplicateKeys::Array));
+        assert_eq!(
+            log[&"tag".into()],
+            Value::Array(vec!["a".into(), "b".into(), "c".into()])
+        );
+        assert_eq!(log[&"status".into()], Value::Integer(200));
+    }
+
+    #[test]
+    fn logfmt_reconstructs_nested_fields() {
+        let log = parse_log(
+            r#"request.path="/a b" request.status=200 tags[0]=x tags[1]=y"#,
+            true,
+            &[("request.status", "int")],
+        );
+
+        assert_eq!(log[&"request.path".into()], "/a b".into());
+        assert_eq!(log[&"request.status".into()], Value::Integer(200));
+        assert_eq!(
+            log[&"tags".into()],
+            Value::Array(vec!["x".into(), "y".into()])
+        );
+    }
+
+    #[test]
+    fn logfmt_round_trips_encoded_events() {
+        let mut event = Event::new_empty_log();
+        let original = event.as_mut_log();
+        original.insert("msg", "say \"hi\"\n");
+        original.insert("request.path", "/a=b c");
+        original.insert("request.bytes", 512);
+        original.insert("request.cached", false);
+        original.insert("tags", vec![Value::from("a b"), Value::from(1.5)]);
+
+        let mut log = parse_log_with(
+            &logfmt::encode(event.as_log()),
+            LogfmtConfig {
+                drop_field: true,
+                infer_types: true,
+                ..Default::default()
+            },
+        );
+
+        log.remove(&event::log_schema().timestamp_key());
+        assert_eq!(&log, event.as_log());
+    }
 }
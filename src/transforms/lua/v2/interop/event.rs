@@ -1,4 +1,4 @@
-use crate::event::{Event, LogEvent, Metric};
+use crate::event::{Event, LogEvent, Metric, TraceEvent};
 use rlua::prelude::*;
 
 impl<'a> ToLua<'a> for Event {
@@ -7,6 +7,7 @@ impl<'a> ToLua<'a> for Event {
         match self {
             Event::Log(log) => table.set("log", log.to_lua(ctx)?)?,
             Event::Metric(metric) => table.set("metric", metric.to_lua(ctx)?)?,
+            Event::Trace(trace) => table.set("trace", trace.into_log().to_lua(ctx)?)?,
         }
         Ok(LuaValue::Table(table))
     }
@@ -24,19 +25,21 @@ impl<'a> FromLua<'a> for Event {
                 })
             }
         };
-        match (table.get("log")?, table.get("metric")?) {
-            (LuaValue::Table(log), LuaValue::Nil) => {
+        match (table.get("log")?, table.get("metric")?, table.get("trace")?) {
+            (LuaValue::Table(log), LuaValue::Nil, LuaValue::Nil) => {
                 Ok(Event::Log(LogEvent::from_lua(LuaValue::Table(log), ctx)?))
             }
-            (LuaValue::Nil, LuaValue::Table(metric)) => Ok(Event::Metric(Metric::from_lua(
-                LuaValue::Table(metric),
-                ctx,
-            )?)),
+            (LuaValue::Nil, LuaValue::Table(metric), LuaValue::Nil) => Ok(Event::Metric(
+                Metric::from_lua(LuaValue::Table(metric), ctx)?,
+            )),
+            (LuaValue::Nil, LuaValue::Nil, LuaValue::Table(trace)) => Ok(Event::Trace(
+                TraceEvent::from(LogEvent::from_lua(LuaValue::Table(trace), ctx)?),
+            )),
             _ => Err(LuaError::FromLuaConversionError {
                 from: value.type_name(),
                 to: "Event",
                 message: Some(
-                    "Event should contain either \"log\" or \"metric\" key at the top level"
+                    "Event should contain exactly one of \"log\", \"metric\" or \"trace\" keys at the top level"
                         .to_string(),
                 ),
             }),
@@ -19,6 +19,8 @@ enum BuildError {
     InvalidSearchDirs { source: rlua::Error },
     #[snafu(display("Cannot evaluate Lua code in \"source\": {}", source))]
     InvalidSource { source: rlua::Error },
+    #[snafu(display("Cannot load Lua module {:?}: {}", module, source))]
+    InvalidModule { module: String, source: rlua::Error },
 
     #[snafu(display("Cannot evaluate Lua code defining \"hooks.init\": {}", source))]
     InvalidHooksInit { source: rlua::Error },
@@ -47,6 +49,14 @@ enum BuildError {
 pub struct LuaConfig {
     #[serde(default = "default_config_paths")]
     search_dirs: Vec<PathBuf>,
+    /// Modules required when the transform is created, so they fail to load
+    /// then rather than on the first event.
+    #[serde(default)]
+    modules: Vec<String>,
+    /// Whether to remove the parts of the standard library that run commands,
+    /// write files or load native code.
+    #[serde(default = "crate::serde::default_true")]
+    sandbox: bool,
     hooks: HooksConfig,
     #[serde(default)]
     timers: Vec<TimerConfig>,
@@ -140,6 +150,17 @@ impl Lua {
                 package.set("path", paths)?;
             }
 
+            if config.sandbox {
+                sandbox(ctx)?;
+            }
+
+            let require = ctx.globals().get::<_, rlua::Function<'_>>("require")?;
+            for module in &config.modules {
+                require
+                    .call::<_, rlua::Value<'_>>(module.as_str())
+                    .with_context(|| InvalidModule { module })?;
+            }
+
             if let Some(source) = &config.source {
                 ctx.load(source).eval().context(InvalidSource)?;
             }
@@ -226,6 +247,25 @@ impl Lua {
     }
 }
 
+/// Leaves scripts the parts of the standard library that work on values and
+/// `require` of Lua modules, but not the `io` library, nor the functions of
+/// `os` that run commands, change files or exit the process, nor loading of
+/// native modules.
+fn sandbox(ctx: rlua::Context<'_>) -> rlua::Result<()> {
+    let globals = ctx.globals();
+    globals.set("io", rlua::Value::Nil)?;
+
+    let os = globals.get::<_, rlua::Table<'_>>("os")?;
+    for function in &["execute", "exit", "remove", "rename", "tmpname"] {
+        os.set(*function, rlua::Value::Nil)?;
+    }
+
+    let package = globals.get::<_, rlua::Table<'_>>("package")?;
+    package.set("loadlib", rlua::Value::Nil)?;
+    package.set("cpath", "")?;
+    Ok(())
+}
+
 // A helper that reduces code duplication.
 fn wrap_emit_fn<'lua, 'scope, F: 'scope>(
     scope: &rlua::Scope<'lua, 'scope>,
@@ -659,6 +699,102 @@ mod tests {
         assert_eq!(event.as_log()[&"new field".into()], "new value".into());
     }
 
+    fn write_module(dir: &std::path::Path, name: &str, source: &str) {
+        std::fs::write(dir.join(format!("{}.lua", name)), source).unwrap();
+    }
+
+    #[test]
+    fn lua_load_module_at_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        write_module(
+            dir.path(),
+            "helpers",
+            r#"
+            loads = (loads or 0) + 1
+            return { greet = function(name) return "hello " .. name end }
+            "#,
+        );
+
+        let config = format!(
+            r#"
+            hooks.process = """function (event, emit)
+                event["log"]["greeting"] = require("helpers").greet("world")
+                event["log"]["loads"] = loads
+                emit(event)
+            end
+            """
+            search_dirs = ["{}"]
+            modules = ["helpers"]
+            "#,
+            dir.path().display()
+        );
+
+        let mut transform = from_config(&config).unwrap();
+        for _ in 0..2 {
+            let event = transform.transform(Event::new_empty_log()).unwrap();
+            assert_eq!(event.as_log()[&"greeting".into()], "hello world".into());
+            assert_eq!(event.as_log()[&"loads".into()], Value::Integer(1));
+        }
+    }
+
+    #[test]
+    fn lua_module_errors_fail_at_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        write_module(dir.path(), "broken", "local function (");
+
+        let config = |module| {
+            format!(
+                r#"
+                hooks.process = """function (event, emit)
+                    emit(event)
+                end
+                """
+                search_dirs = ["{}"]
+                modules = ["{}"]
+                "#,
+                dir.path().display(),
+                module
+            )
+        };
+
+        let err = from_config(&config("broken"))
+            .map(|_| ())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Cannot load Lua module \"broken\""), err);
+        assert!(err.contains("syntax error"), err);
+
+        let err = from_config(&config("missing"))
+            .map(|_| ())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("module 'missing' not found"), err);
+    }
+
+    #[test]
+    fn lua_sandbox() {
+        let process = r#"
+            hooks.process = """function (event, emit)
+                event["log"]["io"] = io ~= nil
+                event["log"]["execute"] = os.execute ~= nil
+                event["log"]["time"] = os.time ~= nil
+                emit(event)
+            end
+            """
+        "#;
+
+        let mut sandboxed = from_config(process).unwrap();
+        let event = sandboxed.transform(Event::new_empty_log()).unwrap();
+        assert_eq!(event.as_log()[&"io".into()], Value::Boolean(false));
+        assert_eq!(event.as_log()[&"execute".into()], Value::Boolean(false));
+        assert_eq!(event.as_log()[&"time".into()], Value::Boolean(true));
+
+        let mut unsandboxed = from_config(&format!("sandbox = false\n{}", process)).unwrap();
+        let event = unsandboxed.transform(Event::new_empty_log()).unwrap();
+        assert_eq!(event.as_log()[&"io".into()], Value::Boolean(true));
+        assert_eq!(event.as_log()[&"execute".into()], Value::Boolean(true));
+    }
+
     #[test]
     fn lua_pairs() {
         let mut transform = from_config(
@@ -1,4 +1,7 @@
-use super::Transform;
+use super::{
+    util::fingerprint::{Fingerprint, FingerprintConfig, Fingerprinter},
+    Transform,
+};
 use crate::{
     event::discriminant::Discriminant,
     event::merge_state::LogEventMergeState,
@@ -28,6 +31,9 @@ pub struct MergeConfig {
     /// from unrelated sources from mixing together, as this affects partial
     /// event processing.
     pub stream_discriminant_fields: Vec<Atom>,
+    /// Distinguishes streams by the fingerprint of their events, instead of
+    /// by `stream_discriminant_fields`.
+    pub stream_fingerprint: Option<FingerprintConfig>,
 }
 
 inventory::submit! {
@@ -40,6 +46,7 @@ impl Default for MergeConfig {
             partial_event_marker_field: event::PARTIAL.clone(),
             merge_fields: vec![event::log_schema().message_key().clone()],
             stream_discriminant_fields: vec![],
+            stream_fingerprint: None,
         }
     }
 }
@@ -47,7 +54,7 @@ impl Default for MergeConfig {
 #[typetag::serde(name = "merge")]
 impl TransformConfig for MergeConfig {
     fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
-        Ok(Box::new(Merge::from(self.clone())))
+        Ok(Box::new(Merge::new(self.clone())?))
     }
 
     fn input_type(&self) -> DataType {
@@ -68,17 +75,31 @@ pub struct Merge {
     partial_event_marker_field: Atom,
     merge_fields: Vec<Atom>,
     stream_discriminant_fields: Vec<Atom>,
-    log_event_merge_states: HashMap<Discriminant, LogEventMergeState>,
+    stream_fingerprinter: Option<Fingerprinter>,
+    log_event_merge_states: HashMap<Stream, LogEventMergeState>,
 }
 
-impl From<MergeConfig> for Merge {
-    fn from(config: MergeConfig) -> Self {
-        Self {
+/// Identifies the stream an event belongs to.
+#[derive(Debug, Eq, Hash, PartialEq)]
+enum Stream {
+    Discriminant(Discriminant),
+    Fingerprint(Fingerprint),
+}
+
+impl Merge {
+    pub fn new(config: MergeConfig) -> crate::Result<Self> {
+        let stream_fingerprinter = config
+            .stream_fingerprint
+            .as_ref()
+            .map(FingerprintConfig::build)
+            .transpose()?;
+        Ok(Self {
             partial_event_marker_field: config.partial_event_marker_field,
             merge_fields: config.merge_fields,
             stream_discriminant_fields: config.stream_discriminant_fields,
+            stream_fingerprinter,
             log_event_merge_states: HashMap::new(),
-        }
+        })
     }
 }
 
@@ -86,9 +107,6 @@ impl Transform for Merge {
     fn transform(&mut self, event: Event) -> Option<Event> {
         let mut event = event.into_log();
 
-        // Prepare an event's discriminant.
-        let discriminant = Discriminant::from_log_event(&event, &self.stream_discriminant_fields);
-
         // TODO: `lua` transform doesn't support assigning non-string values.
         // Normally we'd check for the field value to be `true`, and only then
         // consider event partial, but, to simplify the integration, for now we
@@ -99,11 +117,23 @@ impl Transform for Merge {
 
         // If current event has the partial marker, consider it partial.
         // Remove the partial marker from the event and stash it.
-        if event.remove(&self.partial_event_marker_field).is_some() {
+        let partial = event.remove(&self.partial_event_marker_field).is_some();
+
+        // Prepare the event's stream, without the marker, so partial and
+        // non-partial events of a stream are fingerprinted alike.
+        let stream = match &self.stream_fingerprinter {
+            Some(fingerprinter) => Stream::Fingerprint(fingerprinter.fingerprint(&event)),
+            None => Stream::Discriminant(Discriminant::from_log_event(
+                &event,
+                &self.stream_discriminant_fields,
+            )),
+        };
+
+        if partial {
             // We got a perial event. Initialize a partial event merging state
             // if there's none available yet, or extend the existing one by
             // merging the incoming partial event in.
-            match self.log_event_merge_states.entry(discriminant) {
+            match self.log_event_merge_states.entry(stream) {
                 hash_map::Entry::Vacant(entry) => {
                     entry.insert(LogEventMergeState::new(event));
                 }
@@ -123,7 +153,7 @@ impl Transform for Merge {
         // so we just return the event as is. Otherwise we proceed to merge in
         // the final non-partial event to the partial event merge state - and
         // then return the merged event.
-        let log_event_merge_state = match self.log_event_merge_states.remove(&discriminant) {
+        let log_event_merge_state = match self.log_event_merge_states.remove(&stream) {
             Some(log_event_merge_state) => log_event_merge_state,
             None => return Some(Event::Log(event)),
         };
@@ -151,7 +181,7 @@ mod test {
 
     #[test]
     fn merge_passthorughs_non_partial_events() {
-        let mut merge = Merge::from(MergeConfig::default());
+        let mut merge = Merge::new(MergeConfig::default()).unwrap();
 
         // A non-partial event.
         let sample_event = Event::from("hello world");
@@ -165,7 +195,7 @@ mod test {
 
     #[test]
     fn merge_merges_partial_events() {
-        let mut merge = Merge::from(MergeConfig::default());
+        let mut merge = Merge::new(MergeConfig::default()).unwrap();
 
         let partial_event_1 = make_partial(Event::from("hel"));
         let partial_event_2 = make_partial(Event::from("lo "));
@@ -193,10 +223,11 @@ mod test {
     fn merge_merges_partial_events_from_separate_streams() {
         let stream_discriminant_field = Atom::from("stream_name");
 
-        let mut merge = Merge::from(MergeConfig {
+        let mut merge = Merge::new(MergeConfig {
             stream_discriminant_fields: vec![stream_discriminant_field.clone()],
             ..MergeConfig::default()
-        });
+        })
+        .unwrap();
 
         let make_event = |message, stream| {
             let mut event = Event::from(message);
@@ -246,4 +277,43 @@ mod test {
         assert!(!s1_merged_event.as_log().contains(&event::PARTIAL));
         assert!(!s2_merged_event.as_log().contains(&event::PARTIAL));
     }
+
+    #[test]
+    fn merge_merges_partial_events_from_fingerprinted_streams() {
+        let mut merge = Merge::new(
+            toml::from_str(
+                r#"
+                stream_fingerprint.method = "fields"
+                stream_fingerprint.fields = ["host", "file"]
+                "#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let make_event = |message, host, file| {
+            let mut event = Event::from(message);
+            event.as_mut_log().insert("host", host);
+            event.as_mut_log().insert("file", file);
+            event
+        };
+
+        assert!(merge
+            .transform(make_partial(make_event("hel", "a", "1.log")))
+            .is_none());
+        assert!(merge
+            .transform(make_partial(make_event("other", "a", "2.log")))
+            .is_none());
+        let merged_event = merge.transform(make_event("lo", "a", "1.log")).unwrap();
+
+        assert_eq!(
+            merged_event
+                .as_log()
+                .get(&Atom::from("message"))
+                .unwrap()
+                .as_bytes()
+                .as_ref(),
+            b"hello"
+        );
+    }
 }
@@ -0,0 +1,302 @@
+use super::Transform;
+use crate::{
+    template::Template,
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    Event,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MetricTagsConfig {
+    /// Tags to set, overwriting existing ones, with templated values.
+    #[serde(default)]
+    pub add: IndexMap<String, Template>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /// Existing tags to rename, from their old name to the new one.
+    #[serde(default)]
+    pub rename: IndexMap<String, String>,
+    #[serde(default)]
+    pub log_events: LogEvents,
+    /// Distinct values a templated tag can take before a warning is logged.
+    #[serde(default = "default_cardinality_limit")]
+    pub cardinality_limit: usize,
+}
+
+/// What happens to log events sent to the transform.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum LogEvents {
+    /// Connecting the transform to log events is a configuration error.
+    #[derivative(Default)]
+    Reject,
+    /// Log events pass through unchanged.
+    Pass,
+}
+
+fn default_cardinality_limit() -> usize {
+    500
+}
+
+inventory::submit! {
+    TransformDescription::new_without_default::<MetricTagsConfig>("metric_tags")
+}
+
+#[typetag::serde(name = "metric_tags")]
+impl TransformConfig for MetricTagsConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        Ok(Box::new(MetricTags::new(self)))
+    }
+
+    fn input_type(&self) -> DataType {
+        match self.log_events {
+            LogEvents::Reject => DataType::Metric,
+            LogEvents::Pass => DataType::Any,
+        }
+    }
+
+    fn output_type(&self) -> DataType {
+        self.input_type()
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "metric_tags"
+    }
+}
+
+pub struct MetricTags {
+    add: IndexMap<String, AddTag>,
+    remove: Vec<String>,
+    rename: IndexMap<String, String>,
+    cardinality_limit: usize,
+}
+
+impl MetricTags {
+    pub fn new(config: &MetricTagsConfig) -> Self {
+        let add = config
+            .add
+            .iter()
+            .map(|(name, value)| {
+                let tag = AddTag {
+                    value: value.clone(),
+                    values: if value.is_dynamic() {
+                        Some(HashSet::new())
+                    } else {
+                        None
+                    },
+                };
+                (name.clone(), tag)
+            })
+            .collect();
+
+        MetricTags {
+            add,
+            remove: config.remove.clone(),
+            rename: config.rename.clone(),
+            cardinality_limit: config.cardinality_limit,
+        }
+    }
+}
+
+struct AddTag {
+    value: Template,
+    /// Values rendered so far by a templated tag, until there are too many
+    /// to keep track of.
+    values: Option<HashSet<String>>,
+}
+
+impl Transform for MetricTags {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        let metric = match &mut event {
+            Event::Metric(metric) => metric,
+            Event::Log(_) | Event::Trace(_) => return Some(event),
+        };
+
+        // Templates are rendered against the tags the metric came with.
+        let mut added = Vec::with_capacity(self.add.len());
+        for (name, tag) in &mut self.add {
+            let value = match tag.value.render_string(&Event::Metric(metric.clone())) {
+                Ok(value) => value,
+                Err(missing_keys) => {
+                    warn!(
+                        message = "Keys do not exist on the metric; not adding tag.",
+                        tag = &name[..],
+                        ?missing_keys,
+                        rate_limit_secs = 30,
+                    );
+                    continue;
+                }
+            };
+
+            if let Some(values) = &mut tag.values {
+                values.insert(value.clone());
+                if values.len() > self.cardinality_limit {
+                    warn!(
+                        message = "Templated tag has a high cardinality, which is expensive to store and query downstream.",
+                        tag = &name[..],
+                        limit = self.cardinality_limit as u64,
+                    );
+                    tag.values = None;
+                }
+            }
+
+            added.push((name.clone(), value));
+        }
+
+        let tags = metric.tags.get_or_insert_with(BTreeMap::new);
+        for (from, to) in &self.rename {
+            if let Some(value) = tags.remove(from) {
+                tags.insert(to.clone(), value);
+            }
+        }
+        for name in &self.remove {
+            tags.remove(name);
+        }
+        tags.extend(added);
+
+        if tags.is_empty() {
+            metric.tags = None;
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::metric::{Metric, MetricKind, MetricValue};
+
+    fn counter(tags: &[(&str, &str)]) -> Event {
+        Event::Metric(Metric {
+            name: "requests".into(),
+            timestamp: None,
+            tags: Some(
+                tags.iter()
+                    .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                    .collect(),
+            ),
+            kind: MetricKind::Incremental,
+            value: MetricValue::Counter { value: 1.0 },
+        })
+    }
+
+    fn transform(config: &str, event: Event) -> Option<Event> {
+        let config: MetricTagsConfig = toml::from_str(config).unwrap();
+        MetricTags::new(&config).transform(event)
+    }
+
+    fn tags(event: Event) -> Vec<(String, String)> {
+        event
+            .into_metric()
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn metric_tags_adds_templated_tags() {
+        let event = transform(
+            r#"
+            add.env = "production"
+            add.source = "{{ name }}@{{ tags.host }}"
+            add.host = "overwritten"
+            "#,
+            counter(&[("host", "web-1")]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            tags(event),
+            pairs(&[
+                ("env", "production"),
+                ("host", "overwritten"),
+                ("source", "requests@web-1"),
+            ])
+        );
+    }
+
+    #[test]
+    fn metric_tags_skips_tags_with_missing_keys() {
+        let event = transform(
+            r#"add.zone = "{{ tags.zone }}""#,
+            counter(&[("host", "web-1")]),
+        )
+        .unwrap();
+
+        assert_eq!(tags(event), pairs(&[("host", "web-1")]));
+    }
+
+    #[test]
+    fn metric_tags_removes_and_renames_tags() {
+        let event = transform(
+            r#"
+            remove = ["debug", "missing"]
+            rename.host = "hostname"
+            rename.absent = "ignored"
+            "#,
+            counter(&[("host", "web-1"), ("debug", "true"), ("code", "200")]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            tags(event),
+            pairs(&[("code", "200"), ("hostname", "web-1")])
+        );
+
+        let event = transform(r#"remove = ["host"]"#, counter(&[("host", "web-1")])).unwrap();
+        assert_eq!(event.into_metric().tags, None);
+    }
+
+    #[test]
+    fn metric_tags_rejects_or_passes_log_events() {
+        let config: MetricTagsConfig = toml::from_str(r#"add.env = "production""#).unwrap();
+        assert_eq!(config.input_type(), DataType::Metric);
+
+        let log = Event::from("hello");
+        let event = transform(
+            r#"
+            add.env = "production"
+            log_events = "pass"
+            "#,
+            log.clone(),
+        );
+        assert_eq!(event, Some(log));
+    }
+
+    #[test]
+    fn metric_tags_stops_tracking_high_cardinality_tags() {
+        let config: MetricTagsConfig = toml::from_str(
+            r#"
+            add.id = "{{ tags.request_id }}"
+            add.env = "production"
+            cardinality_limit = 2
+            "#,
+        )
+        .unwrap();
+        let mut transform = MetricTags::new(&config);
+
+        for id in &["a", "b", "a"] {
+            transform.transform(counter(&[("request_id", id)])).unwrap();
+        }
+        assert_eq!(transform.add["id"].values.as_ref().unwrap().len(), 2);
+        assert!(transform.add["env"].values.is_none());
+
+        transform
+            .transform(counter(&[("request_id", "c")]))
+            .unwrap();
+        assert!(transform.add["id"].values.is_none());
+    }
+}
@@ -0,0 +1,324 @@
+use super::Transform;
+use crate::{
+    event::{self, Value},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    Event,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MetricToLogConfig {
+    pub host_tag: Option<String>,
+}
+
+pub struct MetricToLog {
+    timestamp_key: Atom,
+    host_tag: Atom,
+}
+
+inventory::submit! {
+    TransformDescription::new::<MetricToLogConfig>("metric_to_log")
+}
+
+#[typetag::serde(name = "metric_to_log")]
+impl TransformConfig for MetricToLogConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        Ok(Box::new(MetricToLog::new(self.host_tag.clone())))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Metric
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "metric_to_log"
+    }
+}
+
+impl MetricToLog {
+    pub fn new(host_tag: Option<String>) -> Self {
+        Self {
+            timestamp_key: event::log_schema().timestamp_key().clone(),
+            host_tag: Atom::from(format!(
+                "tags.{}",
+                host_tag.unwrap_or_else(|| event::log_schema().host_key().to_string())
+            )),
+        }
+    }
+}
+
+impl Transform for MetricToLog {
+    fn transform(&mut self, event: Event) -> Option<Event> {
+        let metric = event.into_metric();
+        let timestamp = metric.timestamp.unwrap_or_else(Utc::now);
+
+        let object = match serde_json::to_value(&metric) {
+            Ok(JsonValue::Object(object)) => object,
+            Ok(_) => unreachable!("Metrics always serialize to an object"),
+            Err(error) => {
+                error!(message = "Failed to serialize metric.", %error, rate_limit_secs = 30);
+                return None;
+            }
+        };
+
+        let mut event = Event::new_empty_log();
+        let log = event.as_mut_log();
+
+        for (key, value) in object {
+            // The timestamp is set below, and metrics without tags get none.
+            if key != "timestamp" && !value.is_null() {
+                log.insert_flat(key, value);
+            }
+        }
+
+        log.insert(&self.timestamp_key, Value::Timestamp(timestamp));
+        if let Some(host) = log.remove(&self.host_tag) {
+            log.insert(event::log_schema().host_key(), host);
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricToLog, MetricToLogConfig};
+    use crate::{
+        event::metric::{Metric, MetricKind, MetricValue},
+        event::{self, Event, Value},
+        transforms::Transform,
+    };
+    use chrono::{offset::TimeZone, DateTime, Utc};
+    use std::collections::BTreeMap;
+
+    fn parse_config(s: &str) -> MetricToLogConfig {
+        toml::from_str(s).unwrap()
+    }
+
+    fn ts() -> DateTime<Utc> {
+        Utc.ymd(2018, 11, 14).and_hms_nano(8, 9, 10, 11)
+    }
+
+    fn tags() -> BTreeMap<String, String> {
+        vec![
+            ("host".to_owned(), "localhost".to_owned()),
+            ("code".to_owned(), "200".to_owned()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn transform_metric(metric: Metric) -> serde_json::Value {
+        let mut transformer = MetricToLog::new(None);
+        let event = transformer.transform(metric.into()).unwrap();
+        serde_json::to_value(event.into_log()).unwrap()
+    }
+
+    #[test]
+    fn transform_counter() {
+        let log = transform_metric(Metric {
+            name: "counter".into(),
+            timestamp: Some(ts()),
+            tags: Some(tags()),
+            kind: MetricKind::Absolute,
+            value: MetricValue::Counter { value: 1.0 },
+        });
+
+        assert_eq!(
+            log,
+            serde_json::json!({
+                "name": "counter",
+                "timestamp": "2018-11-14T08:09:10.000000011Z",
+                "host": "localhost",
+                "tags": { "code": "200" },
+                "kind": "absolute",
+                "counter": { "value": 1.0 },
+            })
+        );
+    }
+
+    #[test]
+    fn transform_gauge() {
+        let log = transform_metric(Metric {
+            name: "gauge".into(),
+            timestamp: Some(ts()),
+            tags: None,
+            kind: MetricKind::Absolute,
+            value: MetricValue::Gauge { value: 1.0 },
+        });
+
+        assert_eq!(
+            log,
+            serde_json::json!({
+                "name": "gauge",
+                "timestamp": "2018-11-14T08:09:10.000000011Z",
+                "kind": "absolute",
+                "gauge": { "value": 1.0 },
+            })
+        );
+    }
+
+    #[test]
+    fn transform_set() {
+        let log = transform_metric(Metric {
+            name: "set".into(),
+            timestamp: Some(ts()),
+            tags: None,
+            kind: MetricKind::Absolute,
+            value: MetricValue::Set {
+                values: vec!["one".into(), "two".into()].into_iter().collect(),
+            },
+        });
+
+        assert_eq!(log["set"], serde_json::json!({ "values": ["one", "two"] }));
+    }
+
+    #[test]
+    fn transform_distribution() {
+        let log = transform_metric(Metric {
+            name: "distro".into(),
+            timestamp: Some(ts()),
+            tags: None,
+            kind: MetricKind::Incremental,
+            value: MetricValue::Distribution {
+                values: vec![1.0, 2.0],
+                sample_rates: vec![10, 20],
+            },
+        });
+
+        assert_eq!(log["kind"], "incremental");
+        assert_eq!(
+            log["distribution"],
+            serde_json::json!({ "values": [1.0, 2.0], "sample_rates": [10, 20] })
+        );
+    }
+
+    #[test]
+    fn transform_histogram() {
+        let log = transform_metric(Metric {
+            name: "histo".into(),
+            timestamp: Some(ts()),
+            tags: None,
+            kind: MetricKind::Absolute,
+            value: MetricValue::AggregatedHistogram {
+                buckets: vec![1.0, 2.0],
+                counts: vec![10, 20],
+                count: 30,
+                sum: 50.0,
+            },
+        });
+
+        assert_eq!(
+            log["aggregated_histogram"],
+            serde_json::json!({
+                "buckets": [1.0, 2.0],
+                "counts": [10, 20],
+                "count": 30,
+                "sum": 50.0,
+            })
+        );
+    }
+
+    #[test]
+    fn transform_summary() {
+        let log = transform_metric(Metric {
+            name: "summary".into(),
+            timestamp: Some(ts()),
+            tags: None,
+            kind: MetricKind::Absolute,
+            value: MetricValue::AggregatedSummary {
+                quantiles: vec![50.0, 90.0],
+                values: vec![10.0, 20.0],
+                count: 30,
+                sum: 50.0,
+            },
+        });
+
+        assert_eq!(
+            log["aggregated_summary"],
+            serde_json::json!({
+                "quantiles": [50.0, 90.0],
+                "values": [10.0, 20.0],
+                "count": 30,
+                "sum": 50.0,
+            })
+        );
+    }
+
+    #[test]
+    fn transform_uses_host_tag_and_current_time() {
+        let config = parse_config(r#"host_tag = "code""#);
+        let mut transformer = MetricToLog::new(config.host_tag);
+
+        let event = transformer
+            .transform(
+                Metric {
+                    name: "counter".into(),
+                    timestamp: None,
+                    tags: Some(tags()),
+                    kind: MetricKind::Incremental,
+                    value: MetricValue::Counter { value: 1.0 },
+                }
+                .into(),
+            )
+            .unwrap();
+        let log = event.as_log();
+
+        assert_eq!(
+            log.get(&event::log_schema().host_key()),
+            Some(&"200".into())
+        );
+        assert_eq!(log.get(&"tags.host".into()), Some(&"localhost".into()));
+        match log.get(&event::log_schema().timestamp_key()) {
+            Some(Value::Timestamp(_)) => (),
+            value => panic!("Unexpected timestamp {:?}", value),
+        }
+    }
+
+    #[cfg(feature = "transforms-log_to_metric")]
+    #[test]
+    fn log_to_metric_round_trip() {
+        use crate::transforms::log_to_metric::{LogToMetric, LogToMetricConfig};
+
+        let config: LogToMetricConfig = toml::from_str(
+            r#"
+            [[metrics]]
+            type = "counter"
+            field = "status"
+            name = "http_requests_total"
+            tags = {status = "{{status}}"}
+            "#,
+        )
+        .unwrap();
+        let mut log_to_metric = LogToMetric::new(config);
+        let mut metric_to_log = MetricToLog::new(None);
+
+        let mut event = Event::from("i am a log");
+        event.as_mut_log().insert("status", "404");
+        event
+            .as_mut_log()
+            .insert(event::log_schema().timestamp_key().clone(), ts());
+
+        let metric = log_to_metric.transform(event).unwrap();
+        let log = metric_to_log.transform(metric).unwrap().into_log();
+
+        assert_eq!(
+            serde_json::to_value(log).unwrap(),
+            serde_json::json!({
+                "name": "http_requests_total",
+                "timestamp": "2018-11-14T08:09:10.000000011Z",
+                "tags": { "status": "404" },
+                "kind": "incremental",
+                "counter": { "value": 1.0 },
+            })
+        );
+    }
+}
@@ -7,6 +7,8 @@ mod util;
 pub mod add_fields;
 #[cfg(feature = "transforms-add_tags")]
 pub mod add_tags;
+#[cfg(feature = "transforms-aggregate")]
+pub mod aggregate;
 #[cfg(feature = "transforms-ansi_stripper")]
 pub mod ansi_stripper;
 #[cfg(feature = "transforms-aws_ec2_metadata")]
@@ -17,6 +19,10 @@ pub mod coercer;
 pub mod concat;
 #[cfg(feature = "transforms-dedupe")]
 pub mod dedupe;
+#[cfg(feature = "transforms-delay")]
+pub mod delay;
+#[cfg(feature = "transforms-enrichment_table")]
+pub mod enrichment_table;
 #[cfg(feature = "transforms-field_filter")]
 pub mod field_filter;
 #[cfg(feature = "transforms-filter")]
@@ -29,6 +35,8 @@ pub mod grok_parser;
 pub mod json_parser;
 #[cfg(feature = "transforms-kubernetes")]
 pub mod kubernetes;
+#[cfg(feature = "transforms-log_level")]
+pub mod log_level;
 #[cfg(feature = "transforms-log_to_metric")]
 pub mod log_to_metric;
 #[cfg(feature = "transforms-logfmt_parser")]
@@ -37,14 +45,26 @@ pub mod logfmt_parser;
 pub mod lua;
 #[cfg(feature = "transforms-merge")]
 pub mod merge;
+#[cfg(feature = "transforms-metric_tags")]
+pub mod metric_tags;
+#[cfg(feature = "transforms-metric_to_log")]
+pub mod metric_to_log;
+#[cfg(feature = "transforms-nested_json_parser")]
+pub mod nested_json_parser;
+#[cfg(feature = "transforms-redact")]
+pub mod redact;
 #[cfg(feature = "transforms-regex_parser")]
 pub mod regex_parser;
+#[cfg(feature = "transforms-remap")]
+pub mod remap;
 #[cfg(feature = "transforms-remove_fields")]
 pub mod remove_fields;
 #[cfg(feature = "transforms-remove_tags")]
 pub mod remove_tags;
 #[cfg(feature = "transforms-rename_fields")]
 pub mod rename_fields;
+#[cfg(feature = "transforms-reorder")]
+pub mod reorder;
 #[cfg(feature = "transforms-sampler")]
 pub mod sampler;
 #[cfg(feature = "transforms-split")]
@@ -53,6 +73,8 @@ pub mod split;
 pub mod swimlanes;
 #[cfg(feature = "transforms-tag_cardinality_limit")]
 pub mod tag_cardinality_limit;
+#[cfg(feature = "transforms-timestamp_parser")]
+pub mod timestamp_parser;
 #[cfg(feature = "transforms-tokenizer")]
 pub mod tokenizer;
 
@@ -94,4 +116,10 @@ enum BuildError {
 
     #[snafu(display("Invalid substring expression: {}", name))]
     InvalidSubstring { name: String },
+
+    #[snafu(display(
+        "Invalid time zone {:?}, expected \"local\", \"UTC\" or an offset like \"+02:00\"",
+        timezone
+    ))]
+    InvalidTimezone { timezone: String },
 }
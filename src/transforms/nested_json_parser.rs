@@ -0,0 +1,228 @@
+use super::Transform;
+use crate::{
+    event::{self, Event},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use snafu::Snafu;
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Derivative)]
+#[serde(deny_unknown_fields, default)]
+#[derivative(Default)]
+pub struct NestedJsonParserConfig {
+    pub field: Option<Atom>,
+    /// Where the parsed value goes, the parsed field itself by default.
+    pub target_field: Option<Atom>,
+    /// How many times JSON is decoded along any path into the value, the
+    /// field's own included.
+    #[derivative(Default(value = "3"))]
+    pub max_depth: usize,
+    pub drop_invalid: bool,
+}
+
+inventory::submit! {
+    TransformDescription::new::<NestedJsonParserConfig>("nested_json_parser")
+}
+
+#[typetag::serde(name = "nested_json_parser")]
+impl TransformConfig for NestedJsonParserConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        Ok(Box::new(NestedJsonParser::new(self)?))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "nested_json_parser"
+    }
+}
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("`max_depth` must be at least 1"))]
+    ZeroMaxDepth,
+}
+
+#[derive(Debug)]
+pub struct NestedJsonParser {
+    field: Atom,
+    target_field: Atom,
+    max_depth: usize,
+    drop_invalid: bool,
+}
+
+impl NestedJsonParser {
+    pub fn new(config: &NestedJsonParserConfig) -> crate::Result<Self> {
+        if config.max_depth == 0 {
+            return Err(Box::new(BuildError::ZeroMaxDepth));
+        }
+
+        let field = config
+            .field
+            .clone()
+            .unwrap_or_else(|| event::log_schema().message_key().clone());
+        Ok(Self {
+            target_field: config.target_field.clone().unwrap_or_else(|| field.clone()),
+            field,
+            max_depth: config.max_depth,
+            drop_invalid: config.drop_invalid,
+        })
+    }
+
+    /// Replaces the strings within `value` that are JSON objects or arrays
+    /// with what they decode to, and so on within those, while fewer than
+    /// `max_depth` decodings led to `value`.
+    fn parse_nested(&self, value: &mut JsonValue, depth: usize) {
+        match value {
+            JsonValue::String(string) => {
+                if depth >= self.max_depth {
+                    return;
+                }
+                if let Some(parsed) = parse(string.as_bytes()) {
+                    *value = parsed;
+                    self.parse_nested(value, depth + 1);
+                }
+            }
+            JsonValue::Array(values) => {
+                for value in values {
+                    self.parse_nested(value, depth);
+                }
+            }
+            JsonValue::Object(object) => {
+                for value in object.values_mut() {
+                    self.parse_nested(value, depth);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// `text` decoded, if it's a JSON object or array. Other JSON, like `42` or
+/// `true`, is as likely to be a plain string that happens to be valid JSON,
+/// so it's left alone.
+fn parse(text: &[u8]) -> Option<JsonValue> {
+    match text.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') | Some(b'[') => serde_json::from_slice(text).ok(),
+        _ => None,
+    }
+}
+
+impl Transform for NestedJsonParser {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        let log = event.as_mut_log();
+        let parsed = log
+            .get(&self.field)
+            .and_then(|value| parse(&value.as_bytes()));
+
+        match parsed {
+            Some(mut parsed) => {
+                self.parse_nested(&mut parsed, 1);
+                log.insert(&self.target_field, parsed);
+            }
+            None => {
+                debug!(
+                    message = "field is not a JSON object or array.",
+                    field = self.field.as_ref(),
+                    rate_limit_secs = 30
+                );
+                if self.drop_invalid {
+                    return None;
+                }
+            }
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn build(config: &str) -> NestedJsonParser {
+        NestedJsonParser::new(&toml::from_str(config).unwrap()).unwrap()
+    }
+
+    fn parse_message(parser: &mut NestedJsonParser, message: &str) -> JsonValue {
+        let event = parser.transform(Event::from(message)).unwrap();
+        serde_json::to_value(&event.as_log()[&event::log_schema().message_key()]).unwrap()
+    }
+
+    #[test]
+    fn nested_json_parser_doubly_encoded() {
+        let inner = json!({"user": "alice", "tags": ["a", "b"], "status": 200});
+        let middle = json!({"payload": inner.to_string(), "count": "42", "note": "{oops"});
+        let outer = json!({"log": middle.to_string(), "level": "info"});
+
+        let mut parser = build("");
+        assert_eq!(
+            parse_message(&mut parser, &outer.to_string()),
+            json!({
+                "log": {
+                    "payload": {"user": "alice", "tags": ["a", "b"], "status": 200},
+                    // Strings that aren't JSON objects or arrays are kept.
+                    "count": "42",
+                    "note": "{oops",
+                },
+                "level": "info",
+            })
+        );
+    }
+
+    #[test]
+    fn nested_json_parser_stops_at_max_depth() {
+        let mut encoded = json!({"end": true});
+        for _ in 0..4 {
+            encoded = json!({ "next": encoded.to_string() });
+        }
+        let message = encoded.to_string();
+
+        let mut parser = build("max_depth = 2");
+        let parsed = parse_message(&mut parser, &message);
+        let second = &parsed["next"];
+        assert!(second.is_object());
+        assert!(second["next"].is_string());
+
+        let mut parser = build("max_depth = 1");
+        assert!(parse_message(&mut parser, &message)["next"].is_string());
+
+        let mut parser = build("max_depth = 10");
+        assert_eq!(
+            parse_message(&mut parser, &message)["next"]["next"]["next"]["next"],
+            json!({"end": true})
+        );
+    }
+
+    #[test]
+    fn nested_json_parser_target_field_and_invalid() {
+        let mut parser = build(r#"target_field = "parsed""#);
+        let event = parser.transform(Event::from(r#"{"a": "[1, 2]"}"#)).unwrap();
+        let log = event.as_log();
+        assert_eq!(
+            serde_json::to_value(&log[&Atom::from("parsed")]).unwrap(),
+            json!({"a": [1, 2]})
+        );
+        assert!(log.contains(&event::log_schema().message_key()));
+
+        let mut parser = build("");
+        let event = parser.transform(Event::from("not json")).unwrap();
+        assert_eq!(
+            event.as_log()[&event::log_schema().message_key()],
+            "not json".into()
+        );
+
+        let mut parser = build("drop_invalid = true");
+        assert!(parser.transform(Event::from("42")).is_none());
+        assert!(NestedJsonParser::new(&toml::from_str("max_depth = 0").unwrap()).is_err());
+    }
+}
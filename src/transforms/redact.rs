@@ -0,0 +1,536 @@
+use super::Transform;
+use crate::{
+    event::{self, Value},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    Event,
+};
+use bytes::Bytes;
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::{fmt::Write, ops::Range, str};
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("At least one detector or pattern is required"))]
+    NothingToRedact,
+    #[snafu(display("hash_key is required to replace matches with a hash"))]
+    MissingHashKey,
+    #[snafu(display("Invalid pattern {:?}: {}", pattern, source))]
+    InvalidPattern {
+        pattern: String,
+        source: regex::Error,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Derivative)]
+#[derivative(Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Fields to scan, defaulting to the message. Ignored when
+    /// `redact_all_strings` is set.
+    pub fields: Vec<Atom>,
+    pub redact_all_strings: bool,
+    pub detectors: Vec<Detector>,
+    pub patterns: Vec<String>,
+    pub replacement: Replacement,
+    #[derivative(Default(value = "default_mask()"))]
+    pub mask: String,
+    pub hash_key: Option<String>,
+}
+
+fn default_mask() -> String {
+    "[REDACTED]".into()
+}
+
+/// Built-in detectors of common kinds of sensitive data.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Detector {
+    /// Payment card numbers, of 13 to 19 digits optionally grouped by spaces
+    /// or dashes, that pass the Luhn check.
+    CreditCard,
+    /// Email addresses, including ones with non-ASCII names.
+    Email,
+    /// US social security numbers, written as `123-45-6789`, excluding
+    /// numbers that are never issued.
+    UsSocialSecurityNumber,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum Replacement {
+    /// Matches are replaced with the `mask`.
+    #[derivative(Default)]
+    Mask,
+    /// Matches are replaced with the hex encoded HMAC-SHA256 of their text,
+    /// so the same value is always redacted the same way.
+    Hash,
+}
+
+inventory::submit! {
+    TransformDescription::new::<RedactConfig>("redact")
+}
+
+#[typetag::serde(name = "redact")]
+impl TransformConfig for RedactConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        Ok(Box::new(Redact::new(self)?))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "redact"
+    }
+}
+
+struct Matcher {
+    regex: Regex,
+    /// Checks a match further than a regular expression can, to avoid
+    /// redacting things that merely look like sensitive data.
+    validate: Option<fn(&str) -> bool>,
+}
+
+impl Matcher {
+    fn find_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.regex
+            .find_iter(text)
+            .filter(move |m| self.validate.map_or(true, |validate| validate(m.as_str())))
+            .map(|m| m.start()..m.end())
+    }
+}
+
+impl Detector {
+    fn matcher(self) -> Matcher {
+        let (pattern, validate) = match self {
+            Detector::CreditCard => (
+                r"\b(?:\d[ -]?){12,18}\d\b",
+                Some(luhn_check as fn(&str) -> bool),
+            ),
+            Detector::Email => (r"\b[\w.%+-]+@[\w-]+(?:\.[\w-]+)*\.[A-Za-z]{2,}\b", None),
+            Detector::UsSocialSecurityNumber => (
+                r"\b\d{3}-\d{2}-\d{4}\b",
+                Some(issued_ssn as fn(&str) -> bool),
+            ),
+        };
+
+        Matcher {
+            regex: Regex::new(pattern).expect("built-in detectors are valid"),
+            validate,
+        }
+    }
+}
+
+fn luhn_check(number: &str) -> bool {
+    let digits = number
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|digit| u32::from(digit - b'0'));
+    let sum: u32 = digits
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+/// Area numbers 000, 666 and 900 to 999, group number 00 and serial number
+/// 0000 are never assigned.
+fn issued_ssn(number: &str) -> bool {
+    let mut parts = number.split('-');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(area), Some(group), Some(serial)) => {
+            area != "000"
+                && area != "666"
+                && !area.starts_with('9')
+                && group != "00"
+                && serial != "0000"
+        }
+        _ => false,
+    }
+}
+
+pub struct Redact {
+    fields: Vec<Atom>,
+    redact_all_strings: bool,
+    matchers: Vec<Matcher>,
+    replace: Replace,
+}
+
+enum Replace {
+    Mask(String),
+    Hash(PKey<Private>),
+}
+
+impl Replace {
+    fn apply(&self, text: &[u8]) -> String {
+        match self {
+            Replace::Mask(mask) => mask.clone(),
+            Replace::Hash(key) => {
+                let digest = Signer::new(MessageDigest::sha256(), key)
+                    .and_then(|mut signer| {
+                        signer.update(text)?;
+                        signer.sign_to_vec()
+                    })
+                    .expect("HMAC-SHA256 can't fail");
+                let mut hex = String::with_capacity(digest.len() * 2);
+                for byte in digest {
+                    write!(hex, "{:02x}", byte).expect("writing to a string can't fail");
+                }
+                hex
+            }
+        }
+    }
+}
+
+impl Redact {
+    fn new(config: &RedactConfig) -> crate::Result<Self> {
+        if config.detectors.is_empty() && config.patterns.is_empty() {
+            return Err(BuildError::NothingToRedact.into());
+        }
+
+        let mut matchers = config
+            .detectors
+            .iter()
+            .map(|detector| detector.matcher())
+            .collect::<Vec<_>>();
+        for pattern in &config.patterns {
+            let regex = Regex::new(pattern).context(InvalidPattern { pattern })?;
+            matchers.push(Matcher {
+                regex,
+                validate: None,
+            });
+        }
+
+        let replace = match config.replacement {
+            Replacement::Mask => Replace::Mask(config.mask.clone()),
+            Replacement::Hash => match &config.hash_key {
+                Some(key) if !key.is_empty() => Replace::Hash(PKey::hmac(key.as_bytes())?),
+                _ => return Err(BuildError::MissingHashKey.into()),
+            },
+        };
+
+        let fields = if config.fields.is_empty() {
+            vec![event::log_schema().message_key().clone()]
+        } else {
+            config.fields.clone()
+        };
+
+        Ok(Redact {
+            fields,
+            redact_all_strings: config.redact_all_strings,
+            matchers,
+            replace,
+        })
+    }
+
+    /// Redacts the strings in a value, and in any maps or arrays it holds.
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Bytes(bytes) => {
+                if let Some(redacted) = self.redact_bytes(bytes) {
+                    *bytes = redacted;
+                }
+            }
+            Value::Map(map) => map.values_mut().for_each(|value| self.redact_value(value)),
+            Value::Array(array) => array.iter_mut().for_each(|value| self.redact_value(value)),
+            _ => (),
+        }
+    }
+
+    fn redact_bytes(&self, bytes: &Bytes) -> Option<Bytes> {
+        let text = match str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(_) => {
+                // Patterns can't be reliably matched against text that isn't
+                // valid UTF-8, so it's redacted as a whole instead.
+                debug!(
+                    message = "Value is not valid UTF-8; redacting all of it.",
+                    rate_limit_secs = 30,
+                );
+                return Some(self.replace.apply(bytes).into());
+            }
+        };
+
+        let ranges = self.find(text);
+        if ranges.is_empty() {
+            return None;
+        }
+
+        // Matches always start and end on character boundaries, so slicing
+        // around them never splits a multi-byte character.
+        let mut redacted = String::with_capacity(text.len());
+        let mut end = 0;
+        for range in ranges {
+            redacted.push_str(&text[end..range.start]);
+            redacted.push_str(&self.replace.apply(text[range.clone()].as_bytes()));
+            end = range.end;
+        }
+        redacted.push_str(&text[end..]);
+
+        Some(redacted.into())
+    }
+
+    /// Finds the matches of every matcher in the original text, merging
+    /// those that overlap, so that replacements are never matched again.
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found = self
+            .matchers
+            .iter()
+            .flat_map(|matcher| matcher.find_iter(text))
+            .collect::<Vec<_>>();
+        found.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(found.len());
+        for range in found {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+impl Transform for Redact {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        let log = event.as_mut_log();
+
+        if self.redact_all_strings {
+            log.values_mut().for_each(|value| self.redact_value(value));
+        } else {
+            for field in &self.fields {
+                if let Some(value) = log.get_mut(field) {
+                    self.redact_value(value);
+                }
+            }
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::LogEvent;
+
+    fn redact(config: &str, log: LogEvent) -> LogEvent {
+        let config: RedactConfig = toml::from_str(config).unwrap();
+        let mut redact = Redact::new(&config).unwrap();
+        redact.transform(log.into()).unwrap().into_log()
+    }
+
+    fn message(config: &str, message: &str) -> String {
+        let log = redact(config, Event::from(message).into_log());
+        log[event::log_schema().message_key()].to_string_lossy()
+    }
+
+    #[test]
+    fn redact_credit_cards() {
+        let config = r#"detectors = ["credit_card"]"#;
+
+        assert_eq!(
+            message(config, "paid with 4111 1111 1111 1111, ok"),
+            "paid with [REDACTED], ok"
+        );
+        assert_eq!(
+            message(config, "card=5500-0000-0000-0004"),
+            "card=[REDACTED]"
+        );
+        // Fails the Luhn check.
+        assert_eq!(
+            message(config, "order 4111111111111112"),
+            "order 4111111111111112"
+        );
+        // Too short to be a card number.
+        assert_eq!(message(config, "id 123456789012"), "id 123456789012");
+    }
+
+    #[test]
+    fn redact_social_security_numbers() {
+        let config = r#"detectors = ["us_social_security_number"]"#;
+
+        assert_eq!(
+            message(config, "ssn 078-05-1120 on file"),
+            "ssn [REDACTED] on file"
+        );
+        assert_eq!(message(config, "ssn 666-05-1120"), "ssn 666-05-1120");
+        assert_eq!(message(config, "ssn 912-05-1120"), "ssn 912-05-1120");
+        assert_eq!(message(config, "ssn 078-00-1120"), "ssn 078-00-1120");
+        assert_eq!(message(config, "ssn 078-05-0000"), "ssn 078-05-0000");
+    }
+
+    #[test]
+    fn redact_emails() {
+        let config = r#"detectors = ["email"]"#;
+
+        assert_eq!(
+            message(
+                config,
+                "from jane.doe+vector@mail.example.com to bob@example.org"
+            ),
+            "from [REDACTED] to [REDACTED]"
+        );
+        assert_eq!(message(config, "user@localhost"), "user@localhost");
+    }
+
+    #[test]
+    fn redact_keeps_multi_byte_characters() {
+        let config = r#"
+            detectors = ["email"]
+            patterns = ["secret-\\w+"]
+            mask = "█"
+        "#;
+
+        assert_eq!(
+            message(config, "müller@example.de schrieb: «secret-straße» 🙂"),
+            "█ schrieb: «█» 🙂"
+        );
+    }
+
+    #[test]
+    fn redact_merges_overlapping_matches() {
+        let config = r#"
+            detectors = ["email"]
+            patterns = ["jane\\S*"]
+        "#;
+
+        assert_eq!(message(config, "jane.doe@example.com!!"), "[REDACTED]");
+    }
+
+    #[test]
+    fn redact_with_consistent_hashes() {
+        let config = r#"
+            detectors = ["email"]
+            replacement = "hash"
+            hash_key = "sesame"
+        "#;
+
+        let first = message(config, "jane@example.com logged in");
+        let second = message(config, "jane@example.com logged out");
+        let other = message(config, "john@example.com logged in");
+
+        let hash = first.trim_end_matches(" logged in");
+        assert_eq!(hash.len(), 64);
+        assert!(hash.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_eq!(second, format!("{} logged out", hash));
+        assert_ne!(other, first);
+
+        let other_key = message(
+            r#"
+                detectors = ["email"]
+                replacement = "hash"
+                hash_key = "open"
+            "#,
+            "jane@example.com logged in",
+        );
+        assert_ne!(other_key, first);
+    }
+
+    #[test]
+    fn redact_hash_is_hmac_sha256() {
+        let config = RedactConfig {
+            patterns: vec![".+".into()],
+            replacement: Replacement::Hash,
+            hash_key: Some("key".into()),
+            ..Default::default()
+        };
+        let redact = Redact::new(&config).unwrap();
+
+        assert_eq!(
+            redact
+                .replace
+                .apply(b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn redact_selected_fields() {
+        let mut log = LogEvent::new();
+        log.insert("message", "mail jane@example.com");
+        log.insert("user.email", "jane@example.com");
+        log.insert("user.contacts[0]", "john@example.com");
+        log.insert("other", "bob@example.com");
+
+        let log = redact(
+            r#"
+                fields = ["user"]
+                detectors = ["email"]
+            "#,
+            log,
+        );
+
+        assert_eq!(log[&"message".into()], "mail jane@example.com".into());
+        assert_eq!(log[&"user.email".into()], "[REDACTED]".into());
+        assert_eq!(log[&"user.contacts[0]".into()], "[REDACTED]".into());
+        assert_eq!(log[&"other".into()], "bob@example.com".into());
+    }
+
+    #[test]
+    fn redact_all_strings() {
+        let mut log = LogEvent::new();
+        log.insert("message", "mail jane@example.com");
+        log.insert("user.email", "jane@example.com");
+        log.insert("count", 3);
+
+        let log = redact(
+            r#"
+                redact_all_strings = true
+                detectors = ["email"]
+            "#,
+            log,
+        );
+
+        assert_eq!(log[&"message".into()], "mail [REDACTED]".into());
+        assert_eq!(log[&"user.email".into()], "[REDACTED]".into());
+        assert_eq!(log[&"count".into()], 3.into());
+    }
+
+    #[test]
+    fn redact_invalid_utf8_as_a_whole() {
+        let mut log = LogEvent::new();
+        log.insert("message", Value::from(&b"jane@example.com \xff"[..]));
+
+        let log = redact(r#"detectors = ["email"]"#, log);
+
+        assert_eq!(log[&"message".into()], "[REDACTED]".into());
+    }
+
+    #[test]
+    fn redact_requires_something_to_redact() {
+        let config: RedactConfig = toml::from_str("").unwrap();
+        assert!(Redact::new(&config).is_err());
+
+        let config: RedactConfig = toml::from_str(
+            r#"
+                detectors = ["email"]
+                replacement = "hash"
+            "#,
+        )
+        .unwrap();
+        assert!(Redact::new(&config).is_err());
+
+        let config: RedactConfig = toml::from_str(r#"patterns = ["("]"#).unwrap();
+        assert!(Redact::new(&config).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use crate::event::{LogEvent, Value};
+use bytes::Bytes;
+use chrono::Utc;
+use snafu::{ResultExt, Snafu};
+use std::cmp::Ordering;
+use string_cache::DefaultAtom as Atom;
+
+/// Errors running a program against an event.
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("`{}` is not supported between {} and {}", op, left, right))]
+    InvalidOperands {
+        op: &'static str,
+        left: &'static str,
+        right: &'static str,
+    },
+    #[snafu(display("`{}` expected a boolean, found {}", op, found))]
+    NotBoolean {
+        op: &'static str,
+        found: &'static str,
+    },
+    #[snafu(display("`{}` expected a number, found {}", op, found))]
+    NotNumber {
+        op: &'static str,
+        found: &'static str,
+    },
+    #[snafu(display("Integer overflow in `{}`", op))]
+    Overflow { op: &'static str },
+    #[snafu(display("Division by zero"))]
+    DivisionByZero,
+    #[snafu(display("`{}` expected {}, found {}", function, expected, found))]
+    InvalidArgument {
+        function: &'static str,
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[snafu(display("`{}` could not convert {:?}: {}", function, value, message))]
+    Conversion {
+        function: &'static str,
+        value: String,
+        message: String,
+    },
+    #[snafu(display("`parse_json` could not parse JSON: {}", source))]
+    ParseJson { source: serde_json::Error },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+impl Program {
+    /// Runs the program against `log`. Failing statements are skipped if
+    /// `skip_errors` is set, otherwise the program stops at the first
+    /// error. Either way the first error is returned.
+    pub fn execute(&self, log: &mut LogEvent, skip_errors: bool) -> Result<(), Error> {
+        execute_block(&self.statements, log, skip_errors)
+    }
+}
+
+fn execute_block(
+    statements: &[Statement],
+    log: &mut LogEvent,
+    skip_errors: bool,
+) -> Result<(), Error> {
+    let mut first_error = None;
+    for statement in statements {
+        if let Err(error) = statement.execute(log, skip_errors) {
+            if !skip_errors {
+                return Err(error);
+            }
+            first_error.get_or_insert(error);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Statement {
+    Assign(Atom, Expr),
+    Delete(Vec<Atom>),
+    If {
+        condition: Expr,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+}
+
+impl Statement {
+    fn execute(&self, log: &mut LogEvent, skip_errors: bool) -> Result<(), Error> {
+        match self {
+            Statement::Assign(path, expr) => {
+                let value = expr.evaluate(log)?;
+                log.insert(path, value);
+            }
+            Statement::Delete(paths) => {
+                for path in paths {
+                    log.remove(path);
+                }
+            }
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let block = if as_bool("if", condition.evaluate(log)?)? {
+                    then
+                } else {
+                    otherwise
+                };
+                execute_block(block, log, skip_errors)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// A field of the event, `null` if it is missing.
+    Path(Atom),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+        }
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, log: &LogEvent) -> Result<Value, Error> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Path(path) => Ok(log.get(path).cloned().unwrap_or(Value::Null)),
+            Expr::Not(expr) => Ok(Value::Boolean(!as_bool("!", expr.evaluate(log)?)?)),
+            Expr::Negate(expr) => match expr.evaluate(log)? {
+                Value::Integer(integer) => integer
+                    .checked_neg()
+                    .map(Value::Integer)
+                    .ok_or(Error::Overflow { op: "-" }),
+                Value::Float(float) => Ok(Value::Float(-float)),
+                value => Err(Error::NotNumber {
+                    op: "-",
+                    found: kind(&value),
+                }),
+            },
+            Expr::Binary(left, BinaryOp::Or, right) => {
+                let value =
+                    as_bool("||", left.evaluate(log)?)? || as_bool("||", right.evaluate(log)?)?;
+                Ok(Value::Boolean(value))
+            }
+            Expr::Binary(left, BinaryOp::And, right) => {
+                let value =
+                    as_bool("&&", left.evaluate(log)?)? && as_bool("&&", right.evaluate(log)?)?;
+                Ok(Value::Boolean(value))
+            }
+            Expr::Binary(left, op, right) => binary(*op, left.evaluate(log)?, right.evaluate(log)?),
+            Expr::Call(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(log))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.call(arguments)
+            }
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, Error> {
+    let invalid = |left: &Value, right: &Value| Error::InvalidOperands {
+        op: op.symbol(),
+        left: kind(left),
+        right: kind(right),
+    };
+
+    let value = match op {
+        BinaryOp::Eq => Value::Boolean(equals(&left, &right)),
+        BinaryOp::Ne => Value::Boolean(!equals(&left, &right)),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = compare(&left, &right).ok_or_else(|| invalid(&left, &right))?;
+            Value::Boolean(match op {
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::Le => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+        BinaryOp::Add => match (&left, &right) {
+            (Value::Bytes(left), Value::Bytes(right)) => {
+                let mut bytes = Vec::with_capacity(left.len() + right.len());
+                bytes.extend_from_slice(left);
+                bytes.extend_from_slice(right);
+                Value::Bytes(bytes.into())
+            }
+            (Value::Integer(a), Value::Integer(b)) => {
+                Value::Integer(a.checked_add(*b).ok_or(Error::Overflow { op: "+" })?)
+            }
+            _ => Value::Float(
+                as_float(&left).ok_or_else(|| invalid(&left, &right))?
+                    + as_float(&right).ok_or_else(|| invalid(&left, &right))?,
+            ),
+        },
+        BinaryOp::Subtract | BinaryOp::Multiply => match (&left, &right) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                let value = if op == BinaryOp::Subtract {
+                    a.checked_sub(*b)
+                } else {
+                    a.checked_mul(*b)
+                };
+                Value::Integer(value.ok_or(Error::Overflow { op: op.symbol() })?)
+            }
+            _ => {
+                let a = as_float(&left).ok_or_else(|| invalid(&left, &right))?;
+                let b = as_float(&right).ok_or_else(|| invalid(&left, &right))?;
+                Value::Float(if op == BinaryOp::Subtract {
+                    a - b
+                } else {
+                    a * b
+                })
+            }
+        },
+        // Division always results in a float, so `3 / 2` doesn't silently
+        // truncate.
+        BinaryOp::Divide => {
+            let a = as_float(&left).ok_or_else(|| invalid(&left, &right))?;
+            let b = as_float(&right).ok_or_else(|| invalid(&left, &right))?;
+            if b == 0.0 {
+                return Err(Error::DivisionByZero);
+            }
+            Value::Float(a / b)
+        }
+        BinaryOp::Or | BinaryOp::And => unreachable!("evaluated lazily"),
+    };
+    Ok(value)
+}
+
+/// Equality, where integers and floats compare by value.
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Integer(_), Value::Float(_)) | (Value::Float(_), Value::Integer(_)) => {
+            as_float(left) == as_float(right)
+        }
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Bytes(left), Value::Bytes(right)) => Some(left.cmp(right)),
+        (Value::Timestamp(left), Value::Timestamp(right)) => Some(left.cmp(right)),
+        (Value::Integer(left), Value::Integer(right)) => Some(left.cmp(right)),
+        _ => as_float(left)?.partial_cmp(&as_float(right)?),
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(integer) => Some(*integer as f64),
+        Value::Float(float) => Some(*float),
+        _ => None,
+    }
+}
+
+fn as_bool(op: &'static str, value: Value) -> Result<bool, Error> {
+    match value {
+        Value::Boolean(boolean) => Ok(boolean),
+        value => Err(Error::NotBoolean {
+            op,
+            found: kind(&value),
+        }),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Bytes(_) => "a string",
+        Value::Integer(_) => "an integer",
+        Value::Float(_) => "a float",
+        Value::Boolean(_) => "a boolean",
+        Value::Timestamp(_) => "a timestamp",
+        Value::Map(_) => "a map",
+        Value::Array(_) => "an array",
+        Value::Null => "null",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Downcase,
+    Now,
+    ParseJson,
+    ToFloat,
+    ToInt,
+    ToString,
+    Upcase,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let function = match name {
+            "downcase" => Function::Downcase,
+            "now" => Function::Now,
+            "parse_json" => Function::ParseJson,
+            "to_float" => Function::ToFloat,
+            "to_int" => Function::ToInt,
+            "to_string" => Function::ToString,
+            "upcase" => Function::Upcase,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Downcase => "downcase",
+            Function::Now => "now",
+            Function::ParseJson => "parse_json",
+            Function::ToFloat => "to_float",
+            Function::ToInt => "to_int",
+            Function::ToString => "to_string",
+            Function::Upcase => "upcase",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Function::Now => 0,
+            _ => 1,
+        }
+    }
+
+    fn call(self, mut arguments: Vec<Value>) -> Result<Value, Error> {
+        let argument = arguments.pop().unwrap_or(Value::Null);
+        let invalid = |expected, value: &Value| Error::InvalidArgument {
+            function: self.name(),
+            expected,
+            found: kind(value),
+        };
+        let conversion = |value: &[u8], message: String| Error::Conversion {
+            function: self.name(),
+            value: String::from_utf8_lossy(value).into_owned(),
+            message,
+        };
+
+        let value = match self {
+            Function::Now => Value::Timestamp(Utc::now()),
+            Function::Downcase | Function::Upcase => match &argument {
+                Value::Bytes(bytes) => {
+                    let string = String::from_utf8_lossy(bytes);
+                    Value::Bytes(Bytes::from(if self == Function::Upcase {
+                        string.to_uppercase()
+                    } else {
+                        string.to_lowercase()
+                    }))
+                }
+                value => return Err(invalid("a string", value)),
+            },
+            Function::ParseJson => match &argument {
+                Value::Bytes(bytes) => {
+                    let json: serde_json::Value =
+                        serde_json::from_slice(bytes).context(ParseJson)?;
+                    Value::from(json)
+                }
+                value => return Err(invalid("a string", value)),
+            },
+            Function::ToInt => match argument {
+                Value::Integer(_) => argument,
+                Value::Float(float) if float.is_finite() => Value::Integer(float as i64),
+                Value::Float(float) => {
+                    let message = "not a finite number".to_owned();
+                    return Err(conversion(float.to_string().as_bytes(), message));
+                }
+                Value::Boolean(boolean) => Value::Integer(boolean as i64),
+                Value::Timestamp(timestamp) => Value::Integer(timestamp.timestamp()),
+                Value::Bytes(bytes) => match String::from_utf8_lossy(&bytes).trim().parse() {
+                    Ok(integer) => Value::Integer(integer),
+                    Err(error) => return Err(conversion(&bytes, format!("{}", error))),
+                },
+                value => return Err(invalid("a string, number, boolean or timestamp", &value)),
+            },
+            Function::ToFloat => match argument {
+                Value::Integer(integer) => Value::Float(integer as f64),
+                Value::Float(_) => argument,
+                Value::Boolean(boolean) => Value::Float(if boolean { 1.0 } else { 0.0 }),
+                Value::Bytes(bytes) => match String::from_utf8_lossy(&bytes).trim().parse() {
+                    Ok(float) => Value::Float(float),
+                    Err(error) => return Err(conversion(&bytes, format!("{}", error))),
+                },
+                value => return Err(invalid("a string, number or boolean", &value)),
+            },
+            Function::ToString => match argument {
+                Value::Null => return Err(invalid("a value", &argument)),
+                value => Value::Bytes(value.as_bytes()),
+            },
+        };
+        Ok(value)
+    }
+}
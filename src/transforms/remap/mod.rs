@@ -0,0 +1,246 @@
+mod ast;
+mod parser;
+
+use self::{ast::Program, parser::ParseError};
+use super::Transform;
+use crate::{
+    event::Event,
+    internal_events::{RemapEventProcessed, RemapFailed},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+};
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+pub use self::ast::Error;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("Invalid remap program at {}", source))]
+    InvalidProgram { source: ParseError },
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RemapConfig {
+    pub source: String,
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+}
+
+/// What happens to an event when the program fails on it.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum ErrorPolicy {
+    /// The event is passed on as it was before the program ran.
+    #[derivative(Default)]
+    Abort,
+    /// The event is dropped.
+    Drop,
+    /// The failing statement is skipped, and the rest of the program runs.
+    Keep,
+}
+
+inventory::submit! {
+    TransformDescription::new_without_default::<RemapConfig>("remap")
+}
+
+#[typetag::serde(name = "remap")]
+impl TransformConfig for RemapConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        Ok(Box::new(Remap::new(self)?))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "remap"
+    }
+}
+
+pub struct Remap {
+    program: Program,
+    error_policy: ErrorPolicy,
+}
+
+impl Remap {
+    pub fn new(config: &RemapConfig) -> crate::Result<Self> {
+        let program = parser::parse(&config.source).context(InvalidProgram)?;
+        Ok(Remap {
+            program,
+            error_policy: config.error_policy,
+        })
+    }
+}
+
+impl Transform for Remap {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        emit!(RemapEventProcessed);
+
+        // Aborting needs the event as it was, which is only worth a copy
+        // when it can happen.
+        let original = match self.error_policy {
+            ErrorPolicy::Abort => Some(event.clone()),
+            ErrorPolicy::Drop | ErrorPolicy::Keep => None,
+        };
+
+        let skip_errors = self.error_policy == ErrorPolicy::Keep;
+        match self.program.execute(event.as_mut_log(), skip_errors) {
+            Ok(()) => Some(event),
+            Err(error) => {
+                emit!(RemapFailed {
+                    error,
+                    error_policy: self.error_policy,
+                });
+                match self.error_policy {
+                    ErrorPolicy::Abort => original,
+                    ErrorPolicy::Drop => None,
+                    ErrorPolicy::Keep => Some(event),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Value;
+
+    fn remap(source: &str, error_policy: &str) -> Remap {
+        let config: RemapConfig = toml::from_str(&format!(
+            "source = {:?}\nerror_policy = {:?}",
+            source, error_policy
+        ))
+        .unwrap();
+        Remap::new(&config).unwrap()
+    }
+
+    fn log(fields: &[(&str, Value)]) -> Event {
+        let mut event = Event::new_empty_log();
+        for (name, value) in fields {
+            event.as_mut_log().insert(name, value.clone());
+        }
+        event
+    }
+
+    #[test]
+    fn remap_assigns_fields() {
+        let mut remap = remap(
+            r#"
+            .host.name = "web-" + .id
+            .total = .count * 2 + 1
+            .ratio = .count / 4
+            .copied = .missing
+            "#,
+            "abort",
+        );
+
+        let event = remap
+            .transform(log(&[("id", "1".into()), ("count", Value::Integer(5))]))
+            .unwrap();
+        let log = event.as_log();
+        assert_eq!(log[&"host.name".into()], "web-1".into());
+        assert_eq!(log[&"total".into()], Value::Integer(11));
+        assert_eq!(log[&"ratio".into()], Value::Float(1.25));
+        assert_eq!(log[&"copied".into()], Value::Null);
+    }
+
+    #[test]
+    fn remap_deletes_fields_conditionally() {
+        let mut remap = remap(
+            r#"
+            if .status >= 500 && .env != "production" {
+                del(.debug, .trace)
+            } else if .status == 200 {
+                .ok = true
+            }
+            "#,
+            "abort",
+        );
+        let fields = |status: i64| {
+            log(&[
+                ("status", Value::Integer(status)),
+                ("env", "staging".into()),
+                ("debug", "x".into()),
+                ("trace", "y".into()),
+            ])
+        };
+
+        let event = remap.transform(fields(503)).unwrap();
+        assert!(!event.as_log().contains(&"debug".into()));
+        assert!(!event.as_log().contains(&"trace".into()));
+        assert!(!event.as_log().contains(&"ok".into()));
+
+        let event = remap.transform(fields(200)).unwrap();
+        assert!(event.as_log().contains(&"debug".into()));
+        assert_eq!(event.as_log()[&"ok".into()], Value::Boolean(true));
+    }
+
+    #[test]
+    fn remap_calls_functions() {
+        let mut remap = remap(
+            r#"
+            .parsed = parse_json(.message)
+            .level = upcase(.parsed.level)
+            .code = to_int(.parsed.code)
+            .processed_at = now()
+            "#,
+            "abort",
+        );
+
+        let event = remap
+            .transform(Event::from(r#"{"level": "warn", "code": "42"}"#))
+            .unwrap();
+        let log = event.as_log();
+        assert_eq!(log[&"level".into()], "WARN".into());
+        assert_eq!(log[&"code".into()], Value::Integer(42));
+        assert_eq!(log[&"parsed.level".into()], "warn".into());
+        assert!(log[&"processed_at".into()].as_timestamp().is_some());
+    }
+
+    #[test]
+    fn remap_applies_error_policy() {
+        let source = r#"
+            .before = "set"
+            .code = to_int(.message)
+            .after = "set"
+        "#;
+        let event = || log(&[("message", "not a number".into())]);
+
+        let aborted = remap(source, "abort").transform(event()).unwrap();
+        assert_eq!(aborted, event());
+
+        assert_eq!(remap(source, "drop").transform(event()), None);
+
+        let kept = remap(source, "keep").transform(event()).unwrap();
+        assert_eq!(kept.as_log()[&"before".into()], "set".into());
+        assert!(!kept.as_log().contains(&"code".into()));
+        assert_eq!(kept.as_log()[&"after".into()], "set".into());
+    }
+
+    #[test]
+    fn remap_rejects_invalid_programs_when_built() {
+        let config: RemapConfig = toml::from_str(
+            r#"
+            source = """
+            .a = 1
+            .b = upcase(.c
+            """
+            "#,
+        )
+        .unwrap();
+
+        let error = Remap::new(&config).err().unwrap().to_string();
+        assert_eq!(
+            error,
+            "Invalid remap program at line 3, column 13: expected `)`, found end of program"
+        );
+    }
+}
@@ -0,0 +1,697 @@
+//! Parser for remap programs.
+//!
+//! Programs are made of statements separated by new lines or `;`:
+//!
+//! ```text
+//! .field = <expression>
+//! del(.field, .other)
+//! if <expression> { <statements> } else { <statements> }
+//! ```
+//!
+//! Expressions are literals (`"string"`, `12`, `1.5`, `true`, `null`),
+//! paths (`.message`, `.request.headers[0]`, `."dotted.key"`), function
+//! calls (`upcase(.level)`) and the usual unary and binary operators.
+
+use super::ast::{BinaryOp, Expr, Function, Program, Statement};
+use crate::event::Value;
+use snafu::Snafu;
+use std::{iter::Peekable, str::CharIndices};
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Debug, PartialEq, Snafu)]
+#[snafu(display("line {}, column {}: {}", line, column, message))]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+pub fn parse(source: &str) -> Result<Program, ParseError> {
+    let tokens = Lexer::new(source).tokenize()?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+    let statements = parser.statements(None)?;
+    Ok(Program { statements })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Path(String),
+    Ident(String),
+    String(String),
+    Integer(i64),
+    Float(f64),
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Separator,
+    Assign,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Path(path) => format!("path `.{}`", path),
+            Token::Ident(ident) => format!("`{}`", ident),
+            Token::String(_) => "string".to_owned(),
+            Token::Integer(_) | Token::Float(_) => "number".to_owned(),
+            Token::Separator => "end of statement".to_owned(),
+            Token::End => "end of program".to_owned(),
+            token => format!("`{}`", token.symbol()),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::Comma => ",",
+            Token::Assign => "=",
+            Token::Eq => "==",
+            Token::Ne => "!=",
+            Token::Lt => "<",
+            Token::Le => "<=",
+            Token::Gt => ">",
+            Token::Ge => ">=",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Not => "!",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            _ => "",
+        }
+    }
+}
+
+/// A token and where it starts, as a line and column.
+type Spanned = (Token, usize, usize);
+
+struct Lexer<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    line_start: usize,
+    /// Open parentheses, within which new lines don't end statements.
+    depth: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Lexer {
+            source,
+            chars: source.char_indices().peekable(),
+            line: 1,
+            line_start: 0,
+            depth: 0,
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Spanned>, ParseError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            let end = token.0 == Token::End;
+            tokens.push(token);
+            if end {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn error<T>(&self, position: usize, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line,
+            column: self.column(position),
+            message: message.into(),
+        })
+    }
+
+    fn next_token(&mut self) -> Result<Spanned, ParseError> {
+        // Skip whitespace and comments, but not the new lines ending
+        // statements.
+        while let Some(&(position, c)) = self.chars.peek() {
+            match c {
+                '\n' if self.depth == 0 => break,
+                '\n' => {
+                    self.chars.next();
+                    self.line += 1;
+                    self.line_start = position + 1;
+                }
+                '#' => {
+                    while let Some(&(_, c)) = self.chars.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.chars.next();
+                    }
+                }
+                c if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+
+        let (position, c) = match self.chars.next() {
+            Some(next) => next,
+            None => return Ok((Token::End, self.line, self.column(self.source.len()))),
+        };
+        let (line, column) = (self.line, self.column(position));
+
+        let token = match c {
+            '\n' => {
+                self.line += 1;
+                self.line_start = position + 1;
+                Token::Separator
+            }
+            ';' => Token::Separator,
+            '(' => {
+                self.depth += 1;
+                Token::LParen
+            }
+            ')' => {
+                self.depth = self.depth.saturating_sub(1);
+                Token::RParen
+            }
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            ',' => Token::Comma,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '=' if self.eat('=') => Token::Eq,
+            '=' => Token::Assign,
+            '!' if self.eat('=') => Token::Ne,
+            '!' => Token::Not,
+            '<' if self.eat('=') => Token::Le,
+            '<' => Token::Lt,
+            '>' if self.eat('=') => Token::Ge,
+            '>' => Token::Gt,
+            '&' if self.eat('&') => Token::And,
+            '|' if self.eat('|') => Token::Or,
+            '"' => Token::String(self.string(position)?),
+            '.' => Token::Path(self.path(position)?),
+            c if c.is_ascii_digit() => self.number(position)?,
+            c if is_ident_char(c) => Token::Ident(self.ident(position)),
+            c => return self.error(position, format!("unexpected character `{}`", c)),
+        };
+        Ok((token, line, column))
+    }
+
+    fn column(&self, position: usize) -> usize {
+        self.source[self.line_start..position].chars().count() + 1
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        match self.chars.peek() {
+            Some(&(_, c)) if c == expected => {
+                self.chars.next();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn ident(&mut self, start: usize) -> String {
+        let mut end = self.source.len();
+        while let Some(&(position, c)) = self.chars.peek() {
+            if !is_ident_char(c) {
+                end = position;
+                break;
+            }
+            self.chars.next();
+        }
+        self.source[start..end].to_owned()
+    }
+
+    fn number(&mut self, start: usize) -> Result<Token, ParseError> {
+        let mut end = self.source.len();
+        let mut float = false;
+        while let Some(&(position, c)) = self.chars.peek() {
+            if c == '.' && !float {
+                float = true;
+            } else if !c.is_ascii_digit() && c != '_' {
+                end = position;
+                break;
+            }
+            self.chars.next();
+        }
+
+        let number = self.source[start..end].replace('_', "");
+        if float {
+            match number.parse() {
+                Ok(float) => Ok(Token::Float(float)),
+                Err(_) => self.error(start, format!("invalid number `{}`", number)),
+            }
+        } else {
+            match number.parse() {
+                Ok(integer) => Ok(Token::Integer(integer)),
+                Err(_) => self.error(start, format!("integer `{}` is too large", number)),
+            }
+        }
+    }
+
+    /// Reads a string, after its opening quote.
+    fn string(&mut self, start: usize) -> Result<String, ParseError> {
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(string),
+                Some((position, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => string.push('\n'),
+                    Some((_, 'r')) => string.push('\r'),
+                    Some((_, 't')) => string.push('\t'),
+                    Some((_, c)) if c == '"' || c == '\\' => string.push(c),
+                    _ => return self.error(position, "invalid escape sequence"),
+                },
+                Some((_, '\n')) | None => return self.error(start, "unterminated string"),
+                Some((_, c)) => string.push(c),
+            }
+        }
+    }
+
+    /// Reads a path, after its leading dot, into a key as accepted by
+    /// `LogEvent`.
+    fn path(&mut self, start: usize) -> Result<String, ParseError> {
+        let mut path = String::new();
+        loop {
+            match self.chars.peek() {
+                Some(&(position, '"')) => {
+                    self.chars.next();
+                    for c in self.string(position)?.chars() {
+                        if c == '.' || c == '[' || c == ']' || c == '\\' {
+                            path.push('\\');
+                        }
+                        path.push(c);
+                    }
+                }
+                Some(&(position, c)) if is_ident_char(c) => path.push_str(&self.ident(position)),
+                _ => return self.error(start, "expected a field name after `.`"),
+            }
+
+            // Array indexes.
+            while let Some(&(position, '[')) = self.chars.peek() {
+                self.chars.next();
+                let mut index = String::new();
+                while let Some(&(_, c)) = self.chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    index.push(c);
+                    self.chars.next();
+                }
+                if index.is_empty() || !self.eat(']') {
+                    return self.error(position, "expected an array index like `[0]`");
+                }
+                path.push('[');
+                path.push_str(&index);
+                path.push(']');
+            }
+
+            if !self.eat('.') {
+                return Ok(path);
+            }
+            path.push('.');
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '@'
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Steps back over `token`, just returned by `next`.
+    fn back(&mut self, token: &Token) {
+        if *token != Token::End {
+            self.position -= 1;
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        let (_, line, column) = self.tokens[self.position];
+        Err(ParseError {
+            line,
+            column,
+            message: message.into(),
+        })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError> {
+        self.error(format!(
+            "expected {}, found {}",
+            expected,
+            self.peek().describe()
+        ))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if *self.peek() == token {
+            self.next();
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", token.symbol()))
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while *self.peek() == Token::Separator {
+            self.next();
+        }
+    }
+
+    /// Parses statements until `end`, or until the end of the program if
+    /// there is none.
+    fn statements(&mut self, end: Option<Token>) -> Result<Vec<Statement>, ParseError> {
+        let end = end.unwrap_or(Token::End);
+        let mut statements = Vec::new();
+        let expected_end = format!("`{}`", end.symbol());
+        loop {
+            self.skip_separators();
+            match self.peek() {
+                token if *token == end => {
+                    self.next();
+                    return Ok(statements);
+                }
+                Token::End => return self.unexpected(&expected_end),
+                _ => (),
+            }
+            statements.push(self.statement()?);
+            match self.peek() {
+                Token::Separator => (),
+                token if *token == end => (),
+                Token::End => return self.unexpected(&expected_end),
+                _ => return self.unexpected("end of statement"),
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        match self.next() {
+            Token::Path(path) => {
+                self.expect(Token::Assign)?;
+                Ok(Statement::Assign(Atom::from(path), self.expression()?))
+            }
+            Token::Ident(ref ident) if ident == "del" => {
+                self.expect(Token::LParen)?;
+                let mut paths = Vec::new();
+                loop {
+                    match self.next() {
+                        Token::Path(path) => paths.push(Atom::from(path)),
+                        token => {
+                            self.back(&token);
+                            return self.unexpected("a path");
+                        }
+                    }
+                    match self.next() {
+                        Token::Comma => (),
+                        Token::RParen => return Ok(Statement::Delete(paths)),
+                        token => {
+                            self.back(&token);
+                            return self.unexpected("`,` or `)`");
+                        }
+                    }
+                }
+            }
+            Token::Ident(ref ident) if ident == "if" => self.if_statement(),
+            token => {
+                self.back(&token);
+                self.unexpected("an assignment, `del` or `if`")
+            }
+        }
+    }
+
+    /// Parses the rest of an `if` statement, after the `if`.
+    fn if_statement(&mut self) -> Result<Statement, ParseError> {
+        let condition = self.expression()?;
+        self.expect(Token::LBrace)?;
+        let then = self.statements(Some(Token::RBrace))?;
+
+        // `else` may be on the line after the closing brace.
+        let position = self.position;
+        self.skip_separators();
+        let otherwise = match self.peek() {
+            Token::Ident(ident) if ident == "else" => {
+                self.next();
+                match self.next() {
+                    Token::Ident(ref ident) if ident == "if" => vec![self.if_statement()?],
+                    Token::LBrace => self.statements(Some(Token::RBrace))?,
+                    token => {
+                        self.back(&token);
+                        return self.unexpected("`{` or `if`");
+                    }
+                }
+            }
+            _ => {
+                self.position = position;
+                Vec::new()
+            }
+        };
+
+        Ok(Statement::If {
+            condition,
+            then,
+            otherwise,
+        })
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.binary(0)
+    }
+
+    /// Parses binary operators by precedence climbing, from `||` at level 0
+    /// to `*` and `/` at level 4.
+    fn binary(&mut self, level: usize) -> Result<Expr, ParseError> {
+        if level > 4 {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let op = match (level, self.peek()) {
+                (0, Token::Or) => BinaryOp::Or,
+                (1, Token::And) => BinaryOp::And,
+                (2, Token::Eq) => BinaryOp::Eq,
+                (2, Token::Ne) => BinaryOp::Ne,
+                (2, Token::Lt) => BinaryOp::Lt,
+                (2, Token::Le) => BinaryOp::Le,
+                (2, Token::Gt) => BinaryOp::Gt,
+                (2, Token::Ge) => BinaryOp::Ge,
+                (3, Token::Plus) => BinaryOp::Add,
+                (3, Token::Minus) => BinaryOp::Subtract,
+                (4, Token::Star) => BinaryOp::Multiply,
+                (4, Token::Slash) => BinaryOp::Divide,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+
+            // Comparisons don't chain.
+            if level == 2 {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Token::Not => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Token::Minus => {
+                self.next();
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let expr = match self.next() {
+            Token::String(string) => Expr::Literal(Value::from(string)),
+            Token::Integer(integer) => Expr::Literal(Value::Integer(integer)),
+            Token::Float(float) => Expr::Literal(Value::Float(float)),
+            Token::Path(path) => Expr::Path(Atom::from(path)),
+            Token::LParen => {
+                let expr = self.expression()?;
+                self.expect(Token::RParen)?;
+                expr
+            }
+            Token::Ident(ident) => match &ident[..] {
+                "true" => Expr::Literal(Value::Boolean(true)),
+                "false" => Expr::Literal(Value::Boolean(false)),
+                "null" => Expr::Literal(Value::Null),
+                name => match Function::from_name(name) {
+                    Some(function) => self.call(function)?,
+                    None => {
+                        self.position -= 1;
+                        return self.error(format!("unknown function `{}`", name));
+                    }
+                },
+            },
+            token => {
+                self.back(&token);
+                return self.unexpected("an expression");
+            }
+        };
+        Ok(expr)
+    }
+
+    /// Parses the arguments of a function call, after its name.
+    fn call(&mut self, function: Function) -> Result<Expr, ParseError> {
+        self.expect(Token::LParen)?;
+        let mut arguments = Vec::new();
+        if *self.peek() != Token::RParen {
+            loop {
+                arguments.push(self.expression()?);
+                if *self.peek() != Token::Comma {
+                    break;
+                }
+                self.next();
+            }
+        }
+        self.expect(Token::RParen)?;
+
+        if arguments.len() != function.arity() {
+            self.position -= 1;
+            return self.error(format!(
+                "`{}` takes {} argument(s), found {}",
+                function.name(),
+                function.arity(),
+                arguments.len()
+            ));
+        }
+        Ok(Expr::Call(function, arguments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn remap_parses_statements() {
+        let program = parse(
+            r#"
+            # Comments are ignored.
+            .a.b = 1 + 2 * 3; .c = !(.d || .e) && .f != "x\"y"
+            del(.g, ."h.i", .j[0])
+            if .k >= -1.5 {
+                .l = upcase(
+                    .m
+                )
+            }
+            else if .n { } else { .o = null }
+            "#,
+        )
+        .unwrap();
+
+        let mul = Expr::Binary(
+            Box::new(Expr::Literal(Value::Integer(2))),
+            BinaryOp::Multiply,
+            Box::new(Expr::Literal(Value::Integer(3))),
+        );
+        assert_eq!(
+            program.statements[0],
+            Statement::Assign(
+                "a.b".into(),
+                Expr::Binary(
+                    Box::new(Expr::Literal(Value::Integer(1))),
+                    BinaryOp::Add,
+                    Box::new(mul)
+                )
+            )
+        );
+        assert_eq!(
+            program.statements[2],
+            Statement::Delete(vec!["g".into(), "h\\.i".into(), "j[0]".into()])
+        );
+        match &program.statements[3] {
+            Statement::If {
+                then, otherwise, ..
+            } => {
+                assert_eq!(
+                    then,
+                    &vec![Statement::Assign(
+                        "l".into(),
+                        Expr::Call(Function::Upcase, vec![Expr::Path("m".into())])
+                    )]
+                );
+                assert_eq!(otherwise.len(), 1);
+            }
+            statement => panic!("unexpected statement {:?}", statement),
+        }
+        assert_eq!(program.statements.len(), 4);
+    }
+
+    #[test]
+    fn remap_reports_parse_errors() {
+        assert_eq!(
+            error(".a = "),
+            "line 1, column 6: expected an expression, found end of program"
+        );
+        assert_eq!(
+            error(".a = 1\n.b = unknown(.c)"),
+            "line 2, column 6: unknown function `unknown`"
+        );
+        assert_eq!(
+            error(".a = upcase(.b, .c)"),
+            "line 1, column 19: `upcase` takes 1 argument(s), found 2"
+        );
+        assert_eq!(
+            error(".a = 1 .b = 2"),
+            "line 1, column 8: expected end of statement, found path `.b`"
+        );
+        assert_eq!(
+            error("if .a { .b = 1"),
+            "line 1, column 15: expected `}`, found end of program"
+        );
+        assert_eq!(error(".a = \"b"), "line 1, column 6: unterminated string");
+    }
+}
@@ -0,0 +1,358 @@
+use super::{
+    util::runtime_transform::{RuntimeTransform, Timer},
+    Transform,
+};
+use crate::{
+    event::{self, Value},
+    internal_events::{ReorderEventDropped, ReorderEventReleasedEarly},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    Event,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("window_secs must be greater than zero"))]
+    ZeroWindow,
+    #[snafu(display("max_events must be greater than zero"))]
+    ZeroMaxEvents,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReorderConfig {
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    #[serde(default = "default_max_events")]
+    pub max_events: usize,
+}
+
+fn default_window_secs() -> u64 {
+    5
+}
+
+fn default_max_events() -> usize {
+    10_000
+}
+
+inventory::submit! {
+    TransformDescription::new::<ReorderConfig>("reorder")
+}
+
+impl Default for ReorderConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: default_window_secs(),
+            max_events: default_max_events(),
+        }
+    }
+}
+
+#[typetag::serde(name = "reorder")]
+impl TransformConfig for ReorderConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        if self.window_secs == 0 {
+            return Err(BuildError::ZeroWindow.into());
+        }
+        if self.max_events == 0 {
+            return Err(BuildError::ZeroMaxEvents.into());
+        }
+
+        Ok(Box::new(Reorder::new(
+            Duration::from_secs(self.window_secs),
+            self.max_events,
+        )))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "reorder"
+    }
+}
+
+/// Holds events for `window`, then emits them sorted by timestamp. An event
+/// is released along with every held event with an earlier timestamp, and
+/// events arriving behind what was already released are dropped, as they
+/// can't be put back in order. Events without a timestamp are passed on as
+/// they come.
+pub struct Reorder {
+    window: Duration,
+    max_events: usize,
+    /// The events held, by timestamp, then by the order they came in.
+    held: BTreeMap<(DateTime<Utc>, u64), Event>,
+    /// When each held event came in, in that order, with its key in `held`.
+    /// Events released before they are due leave stale entries behind, which
+    /// are dropped once they outnumber the events held.
+    arrivals: VecDeque<(Instant, (DateTime<Utc>, u64))>,
+    /// The timestamp of the latest event released.
+    released: Option<DateTime<Utc>>,
+    sequence: u64,
+}
+
+impl Reorder {
+    pub fn new(window: Duration, max_events: usize) -> Self {
+        Self {
+            window,
+            max_events,
+            held: BTreeMap::new(),
+            arrivals: VecDeque::new(),
+            released: None,
+            sequence: 0,
+        }
+    }
+
+    fn hold<F>(&mut self, event: Event, now: Instant, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        let timestamp = match timestamp(&event) {
+            Some(timestamp) => timestamp,
+            None => return emit_fn(event),
+        };
+
+        if let Some(released) = self.released {
+            if timestamp < released {
+                emit!(ReorderEventDropped {
+                    lateness: (released - timestamp).to_std().unwrap_or_default(),
+                });
+                return;
+            }
+        }
+
+        if self.held.len() >= self.max_events {
+            emit!(ReorderEventReleasedEarly {
+                max_events: self.max_events
+            });
+            let oldest = self.held.keys().next().copied();
+            if let Some(key) = oldest {
+                self.release_to(key, &mut emit_fn);
+            }
+        }
+
+        let key = (timestamp, self.sequence);
+        self.sequence += 1;
+        self.held.insert(key, event);
+        self.arrivals.push_back((now, key));
+
+        if self.arrivals.len() > 2 * self.max_events {
+            let held = &self.held;
+            self.arrivals.retain(|(_, key)| held.contains_key(key));
+        }
+    }
+
+    /// Releases the events held for `window`, along with those with earlier
+    /// timestamps.
+    fn release<F>(&mut self, now: Instant, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        let mut due = None;
+        while let Some((arrived, key)) = self.arrivals.front().copied() {
+            if arrived + self.window > now {
+                break;
+            }
+            self.arrivals.pop_front();
+            due = due.max(Some(key));
+        }
+
+        if let Some(key) = due {
+            self.release_to(key, &mut emit_fn);
+        }
+    }
+
+    fn release_to<F>(&mut self, key: (DateTime<Utc>, u64), emit_fn: &mut F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        let later = self.held.split_off(&(key.0, key.1 + 1));
+        for ((timestamp, _), event) in std::mem::replace(&mut self.held, later) {
+            self.released = Some(timestamp);
+            emit_fn(event);
+        }
+        if self.held.is_empty() {
+            self.arrivals.clear();
+        }
+    }
+
+    fn flush<F>(&mut self, mut emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        for ((timestamp, _), event) in std::mem::replace(&mut self.held, BTreeMap::new()) {
+            self.released = Some(timestamp);
+            emit_fn(event);
+        }
+        self.arrivals.clear();
+    }
+}
+
+fn timestamp(event: &Event) -> Option<DateTime<Utc>> {
+    match event {
+        Event::Log(log) => match log.get(&event::log_schema().timestamp_key()) {
+            Some(Value::Timestamp(timestamp)) => Some(*timestamp),
+            _ => None,
+        },
+        Event::Metric(metric) => metric.timestamp,
+        Event::Trace(_) => None,
+    }
+}
+
+impl RuntimeTransform for Reorder {
+    fn hook_process<F>(&mut self, event: Event, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.hold(event, Instant::now(), emit_fn);
+    }
+
+    fn hook_shutdown<F>(&mut self, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.flush(emit_fn);
+    }
+
+    fn timer_handler<F>(&mut self, _timer: Timer, emit_fn: F)
+    where
+        F: FnMut(Event) -> (),
+    {
+        self.release(Instant::now(), emit_fn);
+    }
+
+    fn timers(&self) -> Vec<Timer> {
+        vec![Timer {
+            id: 0,
+            interval_seconds: 1,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn build(config: &str) -> Reorder {
+        let config: ReorderConfig = toml::from_str(config).unwrap();
+        Reorder::new(Duration::from_secs(config.window_secs), config.max_events)
+    }
+
+    fn log(message: &str, secs: i64) -> Event {
+        let mut event = Event::from(message);
+        event.as_mut_log().insert(
+            event::log_schema().timestamp_key().clone(),
+            Utc.timestamp(secs, 0),
+        );
+        event
+    }
+
+    fn messages(events: &[Event]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| event.as_log()[&event::log_schema().message_key()].to_string_lossy())
+            .collect()
+    }
+
+    #[test]
+    fn reorder_sorts_events_within_window() {
+        let mut reorder = build("window_secs = 5");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        for (message, secs) in &[("c", 30), ("a", 10), ("d", 40), ("b", 20)] {
+            reorder.hold(log(message, *secs), start, |event| output.push(event));
+        }
+        reorder.hold(log("e", 50), start + Duration::from_secs(3), |event| {
+            output.push(event)
+        });
+
+        reorder.release(start + Duration::from_secs(4), |event| output.push(event));
+        assert!(output.is_empty());
+
+        reorder.release(start + Duration::from_secs(5), |event| output.push(event));
+        assert_eq!(messages(&output), vec!["a", "b", "c", "d"]);
+
+        reorder.release(start + Duration::from_secs(8), |event| output.push(event));
+        assert_eq!(messages(&output), vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn reorder_releases_earlier_events_with_due_ones() {
+        let mut reorder = build("window_secs = 5");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        reorder.hold(log("b", 20), start, |event| output.push(event));
+        reorder.hold(log("a", 10), start + Duration::from_secs(2), |event| {
+            output.push(event)
+        });
+        reorder.hold(log("c", 30), start + Duration::from_secs(2), |event| {
+            output.push(event)
+        });
+
+        // Only "b" is due, but "a" has to go out before it.
+        reorder.release(start + Duration::from_secs(5), |event| output.push(event));
+        assert_eq!(messages(&output), vec!["a", "b"]);
+        assert_eq!(reorder.held.len(), 1);
+    }
+
+    #[test]
+    fn reorder_drops_events_behind_released_ones() {
+        let mut reorder = build("window_secs = 5");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        reorder.hold(log("b", 20), start, |event| output.push(event));
+        reorder.release(start + Duration::from_secs(5), |event| output.push(event));
+
+        let later = start + Duration::from_secs(6);
+        reorder.hold(log("a", 10), later, |event| output.push(event));
+        reorder.hold(log("also b", 20), later, |event| output.push(event));
+        reorder.hold(log("c", 30), later, |event| output.push(event));
+        reorder.flush(|event| output.push(event));
+
+        assert_eq!(messages(&output), vec!["b", "also b", "c"]);
+    }
+
+    #[test]
+    fn reorder_releases_oldest_event_over_max_events() {
+        let mut reorder = build("max_events = 2");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        for (message, secs) in &[("b", 20), ("a", 10), ("c", 30)] {
+            reorder.hold(log(message, *secs), start, |event| output.push(event));
+        }
+        assert_eq!(messages(&output), vec!["a"]);
+        assert_eq!(reorder.held.len(), 2);
+    }
+
+    #[test]
+    fn reorder_flushes_held_events_on_shutdown() {
+        let mut reorder = build("window_secs = 60");
+        let start = Instant::now();
+        let mut output = Vec::new();
+
+        reorder.hold(log("b", 20), start, |event| output.push(event));
+        reorder.hold(Event::from("untimed"), start, |event| output.push(event));
+        reorder.hold(log("a", 10), start, |event| output.push(event));
+        assert_eq!(messages(&output), vec!["untimed"]);
+
+        reorder.hook_shutdown(|event| output.push(event));
+        assert_eq!(messages(&output), vec!["untimed", "a", "b"]);
+        assert!(reorder.held.is_empty());
+    }
+}
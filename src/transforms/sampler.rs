@@ -1,6 +1,6 @@
 use super::Transform;
 use crate::{
-    event::{self, Event},
+    event::{self, sample_rate, Event},
     topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
 };
 use regex::RegexSet; // TODO: use regex::bytes
@@ -74,9 +74,7 @@ impl Transform for Sampler {
         }
 
         if seahash::hash(message.as_bytes()) % self.rate == 0 {
-            event
-                .as_mut_log()
-                .insert(Atom::from("sample_rate"), self.rate.to_string());
+            sample_rate::compose(event.as_mut_log(), self.rate);
 
             Some(event)
         } else {
@@ -88,7 +86,7 @@ impl Transform for Sampler {
 #[cfg(test)]
 mod tests {
     use super::Sampler;
-    use crate::event::{self, Event};
+    use crate::event::{self, sample_rate, Event};
     use crate::transforms::Transform;
     use approx::assert_relative_eq;
     use regex::RegexSet;
@@ -194,6 +192,42 @@ mod tests {
         assert!(passing.as_log().get(&Atom::from("sample_rate")).is_none());
     }
 
+    #[test]
+    fn sampler_composes_sampling_rates() {
+        let mut first = Sampler::new(2, None, RegexSet::new(&["na"]).unwrap());
+        let mut second = Sampler::new(5, None, RegexSet::new(&["na"]).unwrap());
+
+        let passing = random_events(10000)
+            .into_iter()
+            .filter(|s| {
+                !s.as_log()[&event::log_schema().message_key()]
+                    .to_string_lossy()
+                    .contains("na")
+            })
+            .filter_map(|event| first.transform(event))
+            .find_map(|event| second.transform(event))
+            .unwrap();
+        assert_eq!(passing.as_log()[&Atom::from("sample_rate")], "10".into());
+        assert_eq!(sample_rate::get(passing.as_log()), 10);
+
+        // Rates set upstream, say by the source, are composed with too.
+        let mut sampler = Sampler::new(3, None, RegexSet::new(&["na"]).unwrap());
+        let passing = random_events(10000)
+            .into_iter()
+            .filter(|s| {
+                !s.as_log()[&event::log_schema().message_key()]
+                    .to_string_lossy()
+                    .contains("na")
+            })
+            .map(|mut event| {
+                event.as_mut_log().insert("sample_rate", 4);
+                event
+            })
+            .find_map(|event| sampler.transform(event))
+            .unwrap();
+        assert_eq!(sample_rate::get(passing.as_log()), 12);
+    }
+
     fn random_events(n: usize) -> Vec<Event> {
         use rand::distributions::Alphanumeric;
         use rand::{thread_rng, Rng};
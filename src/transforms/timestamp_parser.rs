@@ -0,0 +1,419 @@
+use super::{BuildError, Transform};
+use crate::{
+    event::{self, Value},
+    topology::config::{DataType, TransformConfig, TransformContext, TransformDescription},
+    types::DefaultTimezone,
+    Event,
+};
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Deserialize, Serialize, Debug, Derivative)]
+#[serde(deny_unknown_fields, default)]
+#[derivative(Default)]
+pub struct TimestampParserConfig {
+    pub field: Option<Atom>,
+    pub target_field: Option<Atom>,
+    pub formats: Vec<String>,
+    #[derivative(Default(value = "true"))]
+    pub auto_detect: bool,
+    pub timezone: Option<String>,
+    pub set_timestamp: bool,
+    pub on_error: OnError,
+}
+
+#[derive(Deserialize, Serialize, Debug, Derivative, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum OnError {
+    /// Pass the event through untouched.
+    #[derivative(Default)]
+    Keep,
+    /// Pass the event through without the unparsable field.
+    RemoveField,
+    /// Drop the event.
+    Drop,
+}
+
+inventory::submit! {
+    TransformDescription::new::<TimestampParserConfig>("timestamp_parser")
+}
+
+#[typetag::serde(name = "timestamp_parser")]
+impl TransformConfig for TimestampParserConfig {
+    fn build(&self, _cx: TransformContext) -> crate::Result<Box<dyn Transform>> {
+        let field = self
+            .field
+            .clone()
+            .unwrap_or_else(|| event::log_schema().timestamp_key().clone());
+        let target_field = self.target_field.clone().unwrap_or_else(|| field.clone());
+        let timezone = match &self.timezone {
+            Some(timezone) => {
+                DefaultTimezone::parse(timezone).ok_or_else(|| BuildError::InvalidTimezone {
+                    timezone: timezone.clone(),
+                })?
+            }
+            None => DefaultTimezone::Local,
+        };
+
+        if self.formats.is_empty() && !self.auto_detect {
+            return Err("At least one format is required when `auto_detect` is disabled".into());
+        }
+
+        Ok(Box::new(TimestampParser {
+            field,
+            target_field,
+            formats: self.formats.clone(),
+            auto_detect: self.auto_detect,
+            timezone,
+            set_timestamp: self.set_timestamp,
+            on_error: self.on_error,
+        }))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn output_type(&self) -> DataType {
+        DataType::Log
+    }
+
+    fn transform_type(&self) -> &'static str {
+        "timestamp_parser"
+    }
+}
+
+/// Formats carrying their own offset, tried before the naive ones.
+const ZONED_FORMATS: &[&str] = &[
+    "%d/%b/%Y:%H:%M:%S %z",    // Apache common log
+    "%Y-%m-%d %H:%M:%S%.f %z", // SQL style with offset
+    "%a %b %e %H:%M:%S %z %Y", // `date` command output, numeric TZ
+];
+
+/// Formats without an offset, interpreted in the configured time zone.
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f", // ISO 8601 without TZ
+    "%Y-%m-%d %H:%M:%S%.f", // SQL style
+    "%d/%b/%Y:%H:%M:%S",    // Apache without TZ
+    "%a %b %e %H:%M:%S %Y", // ctime
+];
+
+/// Syslog (RFC 3164) timestamps lack a year, the current one is assumed.
+const SYSLOG_FORMAT: &str = "%b %e %H:%M:%S";
+
+/// Epoch values at or above this are treated as milliseconds. In seconds it
+/// would be past the year 5000.
+const EPOCH_MILLIS_THRESHOLD: f64 = 100_000_000_000.0;
+
+pub struct TimestampParser {
+    field: Atom,
+    target_field: Atom,
+    formats: Vec<String>,
+    auto_detect: bool,
+    timezone: DefaultTimezone,
+    set_timestamp: bool,
+    on_error: OnError,
+}
+
+impl TimestampParser {
+    fn parse(&self, s: &str) -> Option<DateTime<Utc>> {
+        let s = s.trim();
+        self.formats
+            .iter()
+            .find_map(|format| self.parse_format(s, format))
+            .or_else(|| {
+                if self.auto_detect {
+                    self.detect(s)
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn parse_format(&self, s: &str, format: &str) -> Option<DateTime<Utc>> {
+        if format_has_zone(format) {
+            DateTime::parse_from_str(s, format)
+                .ok()
+                .map(|ts| ts.with_timezone(&Utc))
+        } else {
+            NaiveDateTime::parse_from_str(s, format)
+                .ok()
+                .and_then(|naive| self.timezone.from_naive(&naive))
+        }
+    }
+
+    fn detect(&self, s: &str) -> Option<DateTime<Utc>> {
+        if let Some(ts) = s.parse::<f64>().ok().and_then(from_epoch) {
+            return Some(ts);
+        }
+        if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+            return Some(ts.with_timezone(&Utc));
+        }
+        if let Ok(ts) = DateTime::parse_from_rfc2822(s) {
+            return Some(ts.with_timezone(&Utc));
+        }
+        if let Some(ts) = ZONED_FORMATS
+            .iter()
+            .find_map(|format| DateTime::parse_from_str(s, format).ok())
+        {
+            return Some(ts.with_timezone(&Utc));
+        }
+        if let Some(ts) = NAIVE_FORMATS
+            .iter()
+            .find_map(|format| self.parse_format(s, format))
+        {
+            return Some(ts);
+        }
+        let with_year = format!("{} {}", self.timezone.current_year(), s);
+        self.parse_format(&with_year, &format!("%Y {}", SYSLOG_FORMAT))
+    }
+}
+
+fn from_epoch(value: f64) -> Option<DateTime<Utc>> {
+    if !value.is_finite() {
+        return None;
+    }
+    let millis = if value.abs() >= EPOCH_MILLIS_THRESHOLD {
+        value
+    } else {
+        value * 1000.0
+    };
+    let secs = (millis / 1000.0).floor();
+    let nanos = ((millis - secs * 1000.0) * 1_000_000.0).round() as u32;
+    match Utc.timestamp_opt(secs as i64, nanos.min(999_999_999)) {
+        LocalResult::Single(ts) => Some(ts),
+        _ => None,
+    }
+}
+
+fn format_has_zone(format: &str) -> bool {
+    ["%Z", "%z", "%:z", "%#z", "%+"]
+        .iter()
+        .any(|zone| format.contains(zone))
+}
+
+impl Transform for TimestampParser {
+    fn transform(&mut self, mut event: Event) -> Option<Event> {
+        let log = event.as_mut_log();
+
+        let parsed = match log.get(&self.field) {
+            None => {
+                debug!(
+                    message = "Field does not exist.",
+                    field = self.field.as_ref(),
+                    rate_limit_secs = 30,
+                );
+                return Some(event);
+            }
+            Some(Value::Timestamp(ts)) => Some(*ts),
+            Some(Value::Integer(i)) => from_epoch(*i as f64),
+            Some(Value::Float(f)) => from_epoch(*f),
+            Some(Value::Bytes(bytes)) => self.parse(&String::from_utf8_lossy(bytes)),
+            Some(_) => None,
+        };
+
+        match parsed {
+            Some(ts) => {
+                if self.target_field != self.field {
+                    log.remove(&self.field);
+                }
+                log.insert(self.target_field.clone(), ts);
+                if self.set_timestamp {
+                    log.insert(event::log_schema().timestamp_key().clone(), ts);
+                }
+                Some(event)
+            }
+            None => {
+                debug!(
+                    message = "Could not parse timestamp.",
+                    field = self.field.as_ref(),
+                    rate_limit_secs = 30,
+                );
+                match self.on_error {
+                    OnError::Keep => Some(event),
+                    OnError::RemoveField => {
+                        log.remove(&self.field);
+                        Some(event)
+                    }
+                    OnError::Drop => None,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnError, TimestampParserConfig};
+    use crate::{
+        event::{self, Event, Value},
+        topology::config::{TransformConfig, TransformContext},
+    };
+    use chrono::{DateTime, Datelike, TimeZone, Utc};
+
+    fn parse(value: impl Into<Value>, config: TimestampParserConfig) -> Option<Event> {
+        let mut event = Event::from("message");
+        event.as_mut_log().insert("ts", value);
+        let rt = crate::runtime::Runtime::single_threaded().unwrap();
+        let mut parser = TimestampParserConfig {
+            field: Some("ts".into()),
+            ..config
+        }
+        .build(TransformContext::new_test(rt.executor()))
+        .unwrap();
+        parser.transform(event)
+    }
+
+    fn parsed(value: impl Into<Value>, config: TimestampParserConfig) -> DateTime<Utc> {
+        match parse(value, config).unwrap().as_log()[&"ts".into()] {
+            Value::Timestamp(ts) => ts,
+            ref other => panic!("expected timestamp, got {:?}", other),
+        }
+    }
+
+    fn utc() -> TimestampParserConfig {
+        TimestampParserConfig {
+            timezone: Some("UTC".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn timestamp_parser_detects_common_formats() {
+        let expected = Utc.ymd(2020, 9, 14).and_hms(19, 9, 29);
+        for input in &[
+            "2020-09-14T19:09:29Z",
+            "2020-09-14T21:09:29+02:00",
+            "Mon, 14 Sep 2020 19:09:29 +0000",
+            "14/Sep/2020:12:09:29 -0700",
+            "2020-09-14 19:09:29",
+            "2020-09-14T19:09:29",
+            "1600110569",
+        ] {
+            assert_eq!(parsed(*input, utc()), expected, "parsing {}", input);
+        }
+    }
+
+    #[test]
+    fn timestamp_parser_detects_epochs() {
+        let expected = Utc.ymd(2020, 9, 14).and_hms_milli(19, 9, 29, 39);
+        assert_eq!(parsed("1600110569039", utc()), expected);
+        assert_eq!(parsed(1_600_110_569_039i64, utc()), expected);
+        assert_eq!(parsed(1_600_110_569.039f64, utc()), expected);
+        assert_eq!(
+            parsed(1_600_110_569i64, utc()),
+            Utc.ymd(2020, 9, 14).and_hms(19, 9, 29)
+        );
+    }
+
+    #[test]
+    fn timestamp_parser_syslog_assumes_current_year() {
+        let ts = parsed("Sep 14 19:09:29", utc());
+        assert_eq!(ts.year(), Utc::now().year());
+        assert_eq!((ts.month(), ts.day()), (9, 14));
+    }
+
+    #[test]
+    fn timestamp_parser_applies_default_timezone() {
+        let config = TimestampParserConfig {
+            timezone: Some("+02:00".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            parsed("2020-09-14 21:09:29", config),
+            Utc.ymd(2020, 9, 14).and_hms(19, 9, 29)
+        );
+
+        // An explicit offset wins over the default
+        let config = TimestampParserConfig {
+            timezone: Some("-0500".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            parsed("2020-09-14T19:09:29Z", config),
+            Utc.ymd(2020, 9, 14).and_hms(19, 9, 29)
+        );
+    }
+
+    #[test]
+    fn timestamp_parser_custom_formats() {
+        let config = TimestampParserConfig {
+            formats: vec!["%d.%m.%Y %H:%M".into(), "%Y%m%d%H%M%S %z".into()],
+            auto_detect: false,
+            timezone: Some("UTC".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            parsed("14.09.2020 19:09", config),
+            Utc.ymd(2020, 9, 14).and_hms(19, 9, 0)
+        );
+
+        let config = TimestampParserConfig {
+            formats: vec!["%Y%m%d%H%M%S %z".into()],
+            auto_detect: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            parsed("20200914210929 +0200", config),
+            Utc.ymd(2020, 9, 14).and_hms(19, 9, 29)
+        );
+
+        let config = TimestampParserConfig {
+            formats: vec!["%d.%m.%Y %H:%M".into()],
+            auto_detect: false,
+            ..Default::default()
+        };
+        let event = parse("2020-09-14T19:09:29Z", config).unwrap();
+        assert_eq!(event.as_log()[&"ts".into()], "2020-09-14T19:09:29Z".into());
+    }
+
+    #[test]
+    fn timestamp_parser_target_and_canonical_timestamp() {
+        let config = TimestampParserConfig {
+            target_field: Some("parsed".into()),
+            set_timestamp: true,
+            ..utc()
+        };
+        let event = parse("2020-09-14T19:09:29Z", config).unwrap();
+        let log = event.as_log();
+        let expected = Value::from(Utc.ymd(2020, 9, 14).and_hms(19, 9, 29));
+        assert!(log.get(&"ts".into()).is_none());
+        assert_eq!(log[&"parsed".into()], expected);
+        assert_eq!(log[&event::log_schema().timestamp_key()], expected);
+    }
+
+    #[test]
+    fn timestamp_parser_on_error() {
+        let keep = parse("not a timestamp", utc()).unwrap();
+        assert_eq!(keep.as_log()[&"ts".into()], "not a timestamp".into());
+
+        let config = TimestampParserConfig {
+            on_error: OnError::RemoveField,
+            ..utc()
+        };
+        let removed = parse("not a timestamp", config).unwrap();
+        assert!(removed.as_log().get(&"ts".into()).is_none());
+
+        let config = TimestampParserConfig {
+            on_error: OnError::Drop,
+            ..utc()
+        };
+        assert!(parse("not a timestamp", config).is_none());
+    }
+
+    #[test]
+    fn timestamp_parser_rejects_bad_timezone() {
+        let rt = crate::runtime::Runtime::single_threaded().unwrap();
+        for timezone in &["Europe/Berlin", "+2", "+25:00"] {
+            let config = TimestampParserConfig {
+                timezone: Some(timezone.to_string()),
+                ..Default::default()
+            };
+            assert!(config
+                .build(TransformContext::new_test(rt.executor()))
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Fingerprinting of log events, for transforms that key events by their
+//! contents, so they all do it the same way.
+//!
+//! The fingerprint is a hash of the whole event, of a set of its fields, or
+//! of a single field. Fields are always hashed in order of their names, so
+//! neither the order fields were added to an event in, nor the order they're
+//! configured in, changes it. Values are hashed with their type, so `"1"`
+//! and `1` differ, and a missing field differs from a null one.
+
+use crate::event::{LogEvent, Value};
+use openssl::sha::Sha256;
+use seahash::SeaHasher;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+use std::{collections::BTreeMap, hash::Hasher};
+use string_cache::DefaultAtom as Atom;
+
+#[derive(Debug, Snafu)]
+enum BuildError {
+    #[snafu(display("fingerprinting by fields requires at least one field"))]
+    NoFields,
+    #[snafu(display("fingerprinting by field requires exactly one field, got {}", count))]
+    NotSingleField { count: usize },
+    #[snafu(display("fingerprinting the whole event doesn't take fields"))]
+    UnexpectedFields,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Derivative)]
+#[serde(deny_unknown_fields)]
+#[derivative(Default)]
+pub struct FingerprintConfig {
+    #[serde(default)]
+    pub method: FingerprintMethod,
+    /// The fields hashed by the `fields` and `field` methods.
+    #[serde(default)]
+    pub fields: Vec<Atom>,
+    #[serde(default)]
+    pub algorithm: FingerprintAlgorithm,
+    /// Whether field names are hashed along with their values, so the same
+    /// value under another name gives another fingerprint.
+    #[serde(default = "crate::serde::default_true")]
+    #[derivative(Default(value = "true"))]
+    pub include_field_names: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum FingerprintMethod {
+    /// Every field of the event.
+    #[derivative(Default)]
+    Event,
+    /// The configured fields.
+    Fields,
+    /// The one configured field.
+    Field,
+}
+
+/// `seahash` is fast, but its 64 bits are only fit to tell apart a moderate
+/// number of events that aren't crafted to collide. `sha256` is slower, but
+/// keeps events apart even when they're chosen by someone to collide.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Derivative, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[derivative(Default)]
+pub enum FingerprintAlgorithm {
+    #[derivative(Default)]
+    Seahash,
+    Sha256,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Fingerprint {
+    Seahash(u64),
+    Sha256([u8; 32]),
+}
+
+impl FingerprintConfig {
+    pub fn build(&self) -> crate::Result<Fingerprinter> {
+        let mut fields = self.fields.clone();
+        fields.sort();
+        fields.dedup();
+
+        let fields = match (self.method, fields.len()) {
+            (FingerprintMethod::Event, 0) => None,
+            (FingerprintMethod::Event, _) => return Err(Box::new(BuildError::UnexpectedFields)),
+            (FingerprintMethod::Fields, 0) => return Err(Box::new(BuildError::NoFields)),
+            (FingerprintMethod::Field, count) if count != 1 => {
+                return Err(Box::new(BuildError::NotSingleField { count }))
+            }
+            _ => Some(fields),
+        };
+
+        Ok(Fingerprinter {
+            fields,
+            algorithm: self.algorithm,
+            include_field_names: self.include_field_names,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Fingerprinter {
+    /// Sorted by name, or `None` for the whole event.
+    fields: Option<Vec<Atom>>,
+    algorithm: FingerprintAlgorithm,
+    include_field_names: bool,
+}
+
+impl Fingerprinter {
+    pub fn fingerprint(&self, log: &LogEvent) -> Fingerprint {
+        let mut hasher = FingerprintHasher::new(self.algorithm);
+
+        match &self.fields {
+            Some(fields) => {
+                for field in fields {
+                    if self.include_field_names {
+                        hasher.write_str(field);
+                    }
+                    match log.get(field) {
+                        Some(value) => {
+                            hasher.write(&[1]);
+                            hasher.write_value(value);
+                        }
+                        None => hasher.write(&[0]),
+                    }
+                }
+            }
+            // `all_fields` yields fields ordered by their paths.
+            None => {
+                for (field, value) in log.all_fields() {
+                    if self.include_field_names {
+                        hasher.write_str(&field);
+                    }
+                    hasher.write_value(value);
+                }
+            }
+        }
+
+        hasher.finish()
+    }
+}
+
+enum FingerprintHasher {
+    Seahash(SeaHasher),
+    Sha256(Sha256),
+}
+
+impl FingerprintHasher {
+    fn new(algorithm: FingerprintAlgorithm) -> Self {
+        match algorithm {
+            FingerprintAlgorithm::Seahash => FingerprintHasher::Seahash(SeaHasher::new()),
+            FingerprintAlgorithm::Sha256 => FingerprintHasher::Sha256(Sha256::new()),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            FingerprintHasher::Seahash(hasher) => hasher.write(bytes),
+            FingerprintHasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Lengths are written ahead of variable length data, so that where one
+    /// ends and the next begins is part of the hash.
+    fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+
+    fn write_str(&mut self, string: &str) {
+        self.write_len(string.len());
+        self.write(string.as_bytes());
+    }
+
+    fn write_value(&mut self, value: &Value) {
+        match value {
+            Value::Bytes(bytes) => {
+                self.write(&[0]);
+                self.write_len(bytes.len());
+                self.write(bytes);
+            }
+            Value::Timestamp(timestamp) => {
+                self.write(&[1]);
+                self.write(&timestamp.timestamp().to_le_bytes());
+                self.write(&timestamp.timestamp_subsec_nanos().to_le_bytes());
+            }
+            Value::Integer(integer) => {
+                self.write(&[2]);
+                self.write(&integer.to_le_bytes());
+            }
+            Value::Float(float) => {
+                self.write(&[3]);
+                self.write(&float.to_bits().to_le_bytes());
+            }
+            Value::Boolean(boolean) => self.write(&[4, *boolean as u8]),
+            Value::Map(map) => {
+                self.write(&[5]);
+                self.write_map(map);
+            }
+            Value::Array(array) => {
+                self.write(&[6]);
+                self.write_len(array.len());
+                for value in array {
+                    self.write_value(value);
+                }
+            }
+            Value::Null => self.write(&[7]),
+        }
+    }
+
+    /// Maps are ordered by key, so their entries are hashed in the same
+    /// order however they were built.
+    fn write_map(&mut self, map: &BTreeMap<String, Value>) {
+        self.write_len(map.len());
+        for (key, value) in map {
+            self.write_str(key);
+            self.write_value(value);
+        }
+    }
+
+    fn finish(self) -> Fingerprint {
+        match self {
+            FingerprintHasher::Seahash(hasher) => Fingerprint::Seahash(hasher.finish()),
+            FingerprintHasher::Sha256(hasher) => Fingerprint::Sha256(hasher.finish()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprinter(config: &str) -> Fingerprinter {
+        toml::from_str::<FingerprintConfig>(config)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn log(fields: &[(&str, Value)]) -> LogEvent {
+        let mut log = LogEvent::new();
+        for (field, value) in fields {
+            log.insert(*field, value.clone());
+        }
+        log
+    }
+
+    #[test]
+    fn fingerprint_ignores_field_order() {
+        let first = log(&[
+            ("host", "web-1".into()),
+            ("request.path", "/".into()),
+            ("request.method", "GET".into()),
+            ("status", 200.into()),
+        ]);
+        let second = log(&[
+            ("status", 200.into()),
+            ("request.method", "GET".into()),
+            ("request.path", "/".into()),
+            ("host", "web-1".into()),
+        ]);
+
+        for algorithm in &["seahash", "sha256"] {
+            let selected = fingerprinter(&format!(
+                r#"
+                method = "fields"
+                fields = ["status", "request", "host"]
+                algorithm = "{}"
+                "#,
+                algorithm
+            ));
+            let permuted = fingerprinter(&format!(
+                r#"
+                method = "fields"
+                fields = ["host", "status", "request"]
+                algorithm = "{}"
+                "#,
+                algorithm
+            ));
+            assert_eq!(selected.fingerprint(&first), selected.fingerprint(&second));
+            assert_eq!(selected.fingerprint(&first), permuted.fingerprint(&second));
+
+            let event = fingerprinter(&format!(r#"algorithm = "{}""#, algorithm));
+            assert_eq!(event.fingerprint(&first), event.fingerprint(&second));
+        }
+    }
+
+    #[test]
+    fn fingerprint_by_selected_fields() {
+        let fields = fingerprinter(
+            r#"
+            method = "fields"
+            fields = ["host", "status"]
+            "#,
+        );
+        let first = log(&[("host", "web-1".into()), ("status", 200.into())]);
+        let other_field = log(&[
+            ("host", "web-1".into()),
+            ("status", 200.into()),
+            ("path", "/".into()),
+        ]);
+        let other_type = log(&[("host", "web-1".into()), ("status", "200".into())]);
+        let null = log(&[("host", "web-1".into()), ("status", Value::Null)]);
+        let missing = log(&[("host", "web-1".into())]);
+
+        assert_eq!(fields.fingerprint(&first), fields.fingerprint(&other_field));
+        assert_ne!(fields.fingerprint(&first), fields.fingerprint(&other_type));
+        assert_ne!(fields.fingerprint(&null), fields.fingerprint(&missing));
+
+        let event = fingerprinter("");
+        assert_ne!(event.fingerprint(&first), event.fingerprint(&other_field));
+    }
+
+    #[test]
+    fn fingerprint_field_names() {
+        let first = log(&[("a", "value".into())]);
+        let second = log(&[("b", "value".into())]);
+
+        let named = fingerprinter("");
+        assert_ne!(named.fingerprint(&first), named.fingerprint(&second));
+
+        let unnamed = fingerprinter("include_field_names = false");
+        assert_eq!(unnamed.fingerprint(&first), unnamed.fingerprint(&second));
+
+        // Where one value ends and the next begins matters.
+        let split = log(&[("a", "ab".into()), ("b", "c".into())]);
+        let moved = log(&[("a", "a".into()), ("b", "bc".into())]);
+        assert_ne!(unnamed.fingerprint(&split), unnamed.fingerprint(&moved));
+    }
+
+    #[test]
+    fn fingerprint_by_single_field() {
+        let field = fingerprinter(
+            r#"
+            method = "field"
+            fields = ["request_id"]
+            algorithm = "sha256"
+            "#,
+        );
+        let first = log(&[("request_id", "abc".into()), ("attempt", 1.into())]);
+        let retry = log(&[("request_id", "abc".into()), ("attempt", 2.into())]);
+
+        assert!(matches!(field.fingerprint(&first), Fingerprint::Sha256(_)));
+        assert_eq!(field.fingerprint(&first), field.fingerprint(&retry));
+    }
+
+    #[test]
+    fn fingerprint_config_validation() {
+        let build = |config: &str| toml::from_str::<FingerprintConfig>(config).unwrap().build();
+
+        assert!(build(r#"fields = ["host"]"#).is_err());
+        assert!(build(r#"method = "fields""#).is_err());
+        assert!(build(
+            r#"
+            method = "field"
+            fields = ["host", "status"]
+            "#
+        )
+        .is_err());
+        assert!(build(
+            r#"
+            method = "field"
+            fields = ["host", "host"]
+            "#
+        )
+        .is_ok());
+    }
+}
@@ -1,2 +1,4 @@
-#[cfg(any(feature = "transforms-lua"))]
+#[cfg(any(feature = "transforms-dedupe", feature = "transforms-merge"))]
+pub mod fingerprint;
+#[cfg(any(feature = "transforms-aggregate", feature = "transforms-lua"))]
 pub mod runtime_transform;
@@ -1,5 +1,8 @@
 use crate::event::Value;
-use chrono::{DateTime, Local, ParseError as ChronoParseError, TimeZone, Utc};
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, LocalResult, NaiveDateTime,
+    ParseError as ChronoParseError, TimeZone, Utc,
+};
 use lazy_static::lazy_static;
 use snafu::{ResultExt, Snafu};
 use std::collections::{HashMap, HashSet};
@@ -267,6 +270,56 @@ pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, Error> {
     Err(Error::AutoTimestampParseError { s: s.into() })
 }
 
+/// Time zone assumed for timestamps whose format carries no offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultTimezone {
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl DefaultTimezone {
+    /// Accepts `local`, `UTC`/`Z`, or a numeric offset like `+02:00`/`-0530`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "local" => return Some(DefaultTimezone::Local),
+            "UTC" | "utc" | "Z" => return Some(DefaultTimezone::Fixed(FixedOffset::east(0))),
+            _ => (),
+        }
+
+        let sign = match s.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return None,
+        };
+        let digits = s[1..].replace(':', "");
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let hours: i32 = digits[..2].parse().ok()?;
+        let minutes: i32 = digits[2..].parse().ok()?;
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(DefaultTimezone::Fixed)
+    }
+
+    pub fn from_naive(self, naive: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            DefaultTimezone::Local => single(Local.from_local_datetime(naive)),
+            DefaultTimezone::Fixed(offset) => single(offset.from_local_datetime(naive)),
+        }
+    }
+
+    pub fn current_year(self) -> i32 {
+        match self {
+            DefaultTimezone::Local => Local::now().year(),
+            DefaultTimezone::Fixed(offset) => Utc::now().with_timezone(&offset).year(),
+        }
+    }
+}
+
+fn single<TZ: TimeZone>(result: LocalResult<DateTime<TZ>>) -> Option<DateTime<Utc>> {
+    // Ambiguous local times (DST fall back) resolve to the earlier instant.
+    result.earliest().map(|ts| ts.with_timezone(&Utc))
+}
+
 #[cfg(test)]
 mod tests {
     use super::parse_bool;
@@ -124,3 +124,55 @@ pub fn cmd(opts: &Opts) -> exitcode::ExitCode {
         exitcode::OK
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_file;
+
+    #[test]
+    fn reports_failed_conditions() {
+        let path = temp_file().with_extension("toml");
+        std::fs::write(
+            &path,
+            r#"
+[transforms.bar]
+  inputs = ["foo"]
+  type = "add_fields"
+  [transforms.bar.fields]
+    new_field = "string value"
+
+[[tests]]
+  name = "failing test"
+
+  [tests.input]
+    insert_at = "bar"
+    value = "hello"
+
+  [[tests.outputs]]
+    extract_from = "bar"
+    [[tests.outputs.conditions]]
+      type = "check_fields"
+      "message.equals" = "goodbye"
+"#,
+        )
+        .unwrap();
+
+        // Only the first file sets the log schema, which is set once per
+        // process.
+        let mut tests = build_tests(1, &path).unwrap();
+        let (_, errors) = tests[0].run();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with(
+                "check transform 'bar' failed conditions:\n  \
+                 condition[0]: predicates failed: [ message.equals: \"goodbye\" ]\n\
+                 payloads (events encoded as JSON):\n"
+            ),
+            "unexpected error: {}",
+            errors[0]
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+mod support;
+
+use crate::support::{sink, source, transform};
+use futures01::{stream::iter_ok, sync::mpsc::SendError, Future, Sink, Stream};
+use vector::api;
+use vector::event::Event;
+use vector::test_util::{next_addr, runtime};
+use vector::topology::{self, config::Config};
+
+#[test]
+fn api_streams_tapped_events() {
+    let mut rt = runtime();
+
+    let (in1, source1) = source();
+    let (_out1, sink1) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_sink("out1", &["in1"], sink1);
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let options = api::Options {
+        enabled: true,
+        address: next_addr(),
+    };
+    let server = api::serve(&options, topology.taps(), topology.graph()).unwrap();
+    rt.spawn(server);
+
+    let client = hyper::Client::new();
+
+    let uri = format!("http://{}/tap?component=nope", options.address);
+    let response = rt.block_on(client.get(uri.parse().unwrap())).unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+
+    let uri = format!("http://{}/tap?component=in1&limit=1", options.address);
+    let response = rt.block_on(client.get(uri.parse().unwrap())).unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+
+    let events = vec![Event::from("first"), Event::from("second")];
+    let _ = rt
+        .block_on(in1.send_all(iter_ok::<_, SendError<Event>>(events)))
+        .unwrap();
+
+    let (chunk, _body) = rt
+        .block_on(response.into_body().into_future())
+        .map_err(|(error, _)| error)
+        .unwrap();
+    let line: serde_json::Value = serde_json::from_slice(&chunk.unwrap()).unwrap();
+    assert_eq!(line["log"]["message"], "first");
+
+    rt.block_on(topology.stop()).unwrap();
+    // The API server runs until it's dropped.
+    rt.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn api_describes_topology() {
+    vector::metrics::init().unwrap();
+    let mut rt = runtime();
+
+    let (in1, source1) = source();
+    let transform1 = transform(" transformed", 0.0);
+    let (out1, sink1) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_transform("t1", &["in1"], transform1);
+    config.add_sink("out1", &["t1"], sink1);
+
+    let (topology, _crash) = topology::start(config, &mut rt, true).unwrap();
+
+    let options = api::Options {
+        enabled: true,
+        address: next_addr(),
+    };
+    let server = api::serve(&options, topology.taps(), topology.graph()).unwrap();
+    rt.spawn(server);
+
+    let events = vec![Event::from("first"), Event::from("second")];
+    let _ = rt
+        .block_on(in1.send_all(iter_ok::<_, SendError<Event>>(events)))
+        .unwrap();
+    let received = rt.block_on(out1.take(2).collect()).unwrap();
+    assert_eq!(received.len(), 2);
+
+    let client = hyper::Client::new();
+    let uri = format!("http://{}/topology", options.address);
+    let response = rt.block_on(client.get(uri.parse().unwrap())).unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let body = rt.block_on(response.into_body().concat2()).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(
+        json["components"],
+        serde_json::json!([
+            {
+                "id": "in1",
+                "kind": "source",
+                "type": "mock",
+                "inputs": [],
+                "outputs": ["t1"],
+                "healthy": null,
+                "metrics": { "sent_events": 2, "errors": 0 },
+            },
+            {
+                "id": "t1",
+                "kind": "transform",
+                "type": "mock",
+                "inputs": ["in1"],
+                "outputs": ["out1"],
+                "healthy": null,
+                "metrics": { "received_events": 2, "sent_events": 2, "errors": 0 },
+            },
+            {
+                "id": "out1",
+                "kind": "sink",
+                "type": "mock",
+                "inputs": ["t1"],
+                "outputs": [],
+                "healthy": true,
+                "metrics": { "received_events": 2, "errors": 0 },
+            },
+        ])
+    );
+
+    rt.block_on(topology.stop()).unwrap();
+    rt.shutdown_now().wait().unwrap();
+}
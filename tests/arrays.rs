@@ -0,0 +1,95 @@
+#![cfg(all(
+    feature = "transforms-add_fields",
+    feature = "transforms-coercer",
+    feature = "transforms-json_parser",
+    feature = "transforms-remove_fields",
+))]
+
+use serde_json::json;
+use vector::{
+    event::{self, Event},
+    runtime::Runtime,
+    sinks::util::{encode_event, Encoding},
+    topology::config::{TransformConfig, TransformContext},
+    transforms::{
+        add_fields::AddFieldsConfig, coercer::CoercerConfig, json_parser::JsonParserConfig,
+        remove_fields::RemoveFieldsConfig, Transform,
+    },
+};
+
+fn build<T: TransformConfig>(rt: &Runtime, config: T) -> Box<dyn Transform> {
+    config
+        .build(TransformContext::new_test(rt.executor()))
+        .unwrap()
+}
+
+#[test]
+fn nested_arrays_round_trip() {
+    let rt = Runtime::single_threaded().unwrap();
+
+    let mut event = Event::new_empty_log();
+    event.as_mut_log().insert(
+        event::log_schema().message_key(),
+        json!({
+            "hosts": [
+                { "name": "a", "ports": ["80", "443"] },
+                { "name": "b", "ports": ["8080"] },
+            ],
+            "matrix": [[1, 2], [3]],
+            "tags": ["x", "y"],
+        })
+        .to_string(),
+    );
+
+    let mut transforms = vec![
+        build(&rt, JsonParserConfig::default()),
+        build(
+            &rt,
+            toml::from_str::<AddFieldsConfig>(
+                r#"
+                append = true
+
+                [fields]
+                tags = ["z"]
+                "hosts[1].ports[1]" = "9090"
+                "#,
+            )
+            .unwrap(),
+        ),
+        build(
+            &rt,
+            toml::from_str::<RemoveFieldsConfig>(
+                r#"fields = ["hosts[0].name", "matrix[1]", "tags[0]", "tags[9]"]"#,
+            )
+            .unwrap(),
+        ),
+        build(
+            &rt,
+            toml::from_str::<CoercerConfig>(
+                r#"
+                [types]
+                "hosts[0].ports" = "int"
+                "hosts[1].ports" = "int"
+                "#,
+            )
+            .unwrap(),
+        ),
+    ];
+
+    let event = transforms.iter_mut().fold(event, |event, transform| {
+        transform.transform(event).unwrap()
+    });
+    let encoded = encode_event(event, &Encoding::Json.into()).unwrap();
+
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&encoded).unwrap(),
+        json!({
+            "hosts": [
+                { "ports": [80, 443] },
+                { "name": "b", "ports": [8080, 9090] },
+            ],
+            "matrix": [[1, 2]],
+            "tags": [null, "y", "z"],
+        })
+    );
+}
@@ -51,6 +51,8 @@ fn test_buffering() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config
@@ -90,6 +92,8 @@ fn test_buffering() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config
@@ -156,6 +160,8 @@ fn test_max_size() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config
@@ -194,6 +200,8 @@ fn test_max_size() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config
@@ -240,6 +248,8 @@ fn test_max_size_resume() {
     config.sinks["out"].buffer = BufferConfig::Disk {
         max_size,
         when_full: Default::default(),
+        sync_writes: false,
+        encryption: None,
     };
     config.global.data_dir = Some(data_dir.clone());
 
@@ -295,6 +305,8 @@ fn test_reclaim_disk_space() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config
@@ -336,6 +348,8 @@ fn test_reclaim_disk_space() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config
@@ -376,6 +390,137 @@ fn test_reclaim_disk_space() {
     assert!(after_disk_size < before_disk_size / 2);
 }
 
+#[test]
+fn test_acknowledgements_enabled() {
+    let (_out_rx, sink_config) = support::sink_unconfirmed(10);
+    let (input_events, input_events2, output_events) =
+        run_sink_then_restart(sink_config, Some(true));
+
+    // None of the events were confirmed, so they're all sent again.
+    assert_eq!(input_events.len() * 2, output_events.len());
+    assert_eq!(input_events, &output_events[..input_events.len()]);
+    assert_eq!(input_events2, &output_events[input_events.len()..]);
+}
+
+#[test]
+fn test_acknowledgements_disabled() {
+    let (_out_rx, sink_config) = support::sink_unconfirmed(10);
+    let (_input_events, input_events2, output_events) =
+        run_sink_then_restart(sink_config, Some(false));
+
+    // The events were acked as the sink received them, so they're gone.
+    assert_eq!(input_events2, output_events);
+}
+
+#[cfg(feature = "sinks-http")]
+#[test]
+fn test_acknowledgements_confirming_sink() {
+    // A sink that can confirm delivery already waits for it when
+    // acknowledgements are left unset, enabling them only makes sure of it.
+    for acknowledgements in &[None, Some(true)] {
+        // Nothing listens at the address, so no request is ever confirmed.
+        let sink_config: sinks::http::HttpSinkConfig = toml::from_str(&format!(
+            r#"
+            uri = "http://{}"
+            encoding = "json"
+            "#,
+            next_addr()
+        ))
+        .unwrap();
+        let (input_events, input_events2, output_events) =
+            run_sink_then_restart(sink_config, *acknowledgements);
+
+        assert_eq!(input_events.len() * 2, output_events.len());
+        assert_eq!(input_events, &output_events[..input_events.len()]);
+        assert_eq!(input_events2, &output_events[input_events.len()..]);
+    }
+}
+
+/// Sends events to a disk-buffered sink, crashes, and sends more events
+/// after starting again with a sink that delivers them.
+fn run_sink_then_restart<S: config::SinkConfig + 'static>(
+    sink_config: S,
+    acknowledgements: Option<bool>,
+) -> (Vec<event::Event>, Vec<event::Event>, Vec<event::Event>) {
+    test_util::trace_init();
+
+    let data_dir = tempdir().unwrap();
+    let data_dir = data_dir.path().to_path_buf();
+    trace!(message = "Test data dir", ?data_dir);
+
+    let num_events: usize = 10;
+    let line_length = 100;
+    let max_size = 10_000;
+
+    let (in_tx, source_config, source_event_counter) = support::source_with_event_counter();
+    let config = {
+        let mut config = config::Config::empty();
+        config.add_source("in", source_config);
+        config.add_sink("out", &["in"], sink_config);
+        config.sinks["out"].buffer = BufferConfig::Disk {
+            max_size,
+            when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
+        };
+        config.sinks["out"].acknowledgements.enabled = acknowledgements;
+        config.global.data_dir = Some(data_dir.clone());
+        config
+    };
+
+    let mut rt = test_util::runtime();
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let (input_events, input_events_stream) =
+        test_util::random_events_with_stream(line_length, num_events);
+    let send = in_tx
+        .sink_map_err(|err| panic!(err))
+        .send_all(input_events_stream);
+    let _ = rt.block_on(send).unwrap();
+
+    test_util::wait_for_atomic_usize(source_event_counter, |x| x == num_events);
+
+    // Give the sink some time to receive the events and simulate a crash.
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    terminate_abruptly(rt, topology);
+
+    let (in_tx, source_config, source_event_counter) = support::source_with_event_counter();
+    let (out_rx, sink_config) = support::sink(10);
+    let config = {
+        let mut config = config::Config::empty();
+        config.add_source("in", source_config);
+        config.add_sink("out", &["in"], sink_config);
+        config.sinks["out"].buffer = BufferConfig::Disk {
+            max_size,
+            when_full: Default::default(),
+            sync_writes: false,
+            encryption: None,
+        };
+        config.global.data_dir = Some(data_dir.clone());
+        config
+    };
+
+    let mut rt = test_util::runtime();
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let (input_events2, input_events_stream) =
+        test_util::random_events_with_stream(line_length, num_events);
+    let send = in_tx
+        .sink_map_err(|err| panic!(err))
+        .send_all(input_events_stream);
+    let _ = rt.block_on(send).unwrap();
+
+    let output_events = test_util::receive_events(out_rx);
+
+    test_util::wait_for_atomic_usize(source_event_counter, |x| x == num_events);
+
+    terminate_gracefully(rt, topology);
+
+    (input_events, input_events2, output_events.wait())
+}
+
 fn compute_disk_size(dir: impl AsRef<std::path::Path>) -> u64 {
     walkdir::WalkDir::new(dir)
         .into_iter()
@@ -0,0 +1,87 @@
+mod support;
+
+use crate::support::{sink_failing_healthcheck, source};
+use futures01::{Sink, Stream};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use vector::{
+    event::Event,
+    test_util::{block_on, runtime, shutdown_on_idle},
+    topology::{self, config::Config},
+};
+
+/// Collects everything the subscriber writes, so the test can look at it.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn line_containing(&self, message: &str) -> String {
+        let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        output
+            .lines()
+            .find(|line| line.contains(message))
+            .unwrap_or_else(|| panic!("nothing logged {:?} in:\n{}", message, output))
+            .to_owned()
+    }
+}
+
+#[test]
+fn component_logs_carry_component_context() {
+    // Components run on the runtime's threads, so only a global subscriber
+    // sees what they log, which is why this test has a binary to itself.
+    let output = Output::default();
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::FmtSubscriber::builder()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::dispatcher::set_global_default(tracing::Dispatch::new(subscriber)).unwrap();
+
+    let (in1, source) = source();
+    let (out1, sink) = sink_failing_healthcheck(10);
+    let mut config = Config::empty();
+    config.add_source("in1", source);
+    config.add_sink("out1", &["in1"], sink);
+
+    let mut rt = runtime();
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    // Closing the input ends the source, which logs as it finishes.
+    let in1 = block_on(in1.send(Event::from("test"))).unwrap();
+    drop(in1);
+    let events = block_on(out1.take(1).collect()).unwrap();
+    assert_eq!(events.len(), 1);
+
+    block_on(topology.stop()).unwrap();
+    shutdown_on_idle(rt);
+
+    let source_line = output.line_containing("finished sending");
+    assert!(source_line.contains("component_id=in1"), "{}", source_line);
+    assert!(
+        source_line.contains("component_type=mock"),
+        "{}",
+        source_line
+    );
+    assert!(!source_line.contains("out1"), "{}", source_line);
+
+    let healthcheck_line = output.line_containing("Healthcheck: Failed");
+    assert!(
+        healthcheck_line.contains("component_id=out1"),
+        "{}",
+        healthcheck_line
+    );
+    assert!(!healthcheck_line.contains("in1"), "{}", healthcheck_line);
+}
@@ -465,6 +465,44 @@ fn cycle() {
     )
 }
 
+#[cfg(all(feature = "sources-socket", feature = "sinks-socket"))]
+#[test]
+fn dead_letter_cycle() {
+    let errors = load(
+        r#"
+        [sources.in]
+        type = "socket"
+        mode = "tcp"
+        address = "127.0.0.1:1235"
+
+        [sinks.out]
+        type = "socket"
+        mode = "tcp"
+        inputs = ["in"]
+        dead_letter = "dlq"
+        encoding = "text"
+        address = "127.0.0.1:9999"
+
+        [sinks.dlq]
+        type = "socket"
+        mode = "tcp"
+        inputs = []
+        dead_letter = "missing"
+        encoding = "text"
+        address = "127.0.0.1:9998"
+      "#,
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        errors,
+        vec![
+            "Dead-letter sink \"dlq\" for sink \"out\" can't have a dead-letter sink of its own.",
+            "Dead-letter sink \"missing\" for sink \"dlq\" doesn't exist.",
+        ]
+    )
+}
+
 #[cfg(all(feature = "sources-socket", feature = "sinks-socket"))]
 #[test]
 fn disabled_healthcheck() {
@@ -487,6 +525,60 @@ fn disabled_healthcheck() {
     .unwrap();
 }
 
+#[cfg(all(feature = "sources-socket", feature = "sinks-socket"))]
+#[test]
+fn acknowledgements_need_confirming_sink() {
+    let config = |enabled| {
+        format!(
+            r#"
+            [sources.in]
+            type = "socket"
+            mode = "tcp"
+            address = "127.0.0.1:1234"
+
+            [sinks.out]
+            type = "socket"
+            mode = "tcp"
+            inputs = ["in"]
+            address = "127.0.0.1:9999"
+            encoding = "text"
+            acknowledgements.enabled = {}
+            "#,
+            enabled
+        )
+    };
+
+    load(&config(false)).unwrap();
+
+    let errors = load(&config(true)).unwrap_err();
+    assert_eq!(
+        errors,
+        vec!["Sink \"out\" can't enable acknowledgements, as socket sinks can't confirm delivery."]
+    );
+}
+
+#[cfg(all(feature = "sources-stdin", feature = "sinks-http"))]
+#[test]
+fn acknowledgements_with_confirming_sink() {
+    for enabled in &[true, false] {
+        load(&format!(
+            r#"
+            [sources.in]
+            type = "stdin"
+
+            [sinks.out]
+            type = "http"
+            inputs = ["in"]
+            uri = "https://localhost"
+            encoding = "json"
+            acknowledgements.enabled = {}
+            "#,
+            enabled
+        ))
+        .unwrap();
+    }
+}
+
 #[cfg(all(feature = "sources-stdin", feature = "sinks-http"))]
 #[test]
 fn parses_sink_no_request() {
@@ -667,3 +759,25 @@ fn parses_sink_full_es_aws() {
     )
     .unwrap();
 }
+
+#[cfg(all(feature = "sources-stdin", feature = "sinks-console"))]
+#[test]
+fn empty_log_schema_key() {
+    let err = load(
+        r#"
+        [log_schema]
+        message_key = ""
+
+        [sources.in]
+        type = "stdin"
+
+        [sinks.out]
+        type = "console"
+        inputs = ["in"]
+        encoding = "json"
+        "#,
+    )
+    .unwrap_err();
+
+    assert_eq!(err, vec!["log_schema.message_key must not be empty"]);
+}
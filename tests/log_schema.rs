@@ -0,0 +1,75 @@
+#![cfg(all(feature = "sources-socket", feature = "sinks-socket"))]
+
+use futures01::Future;
+use serde_json::Value;
+use vector::event;
+use vector::test_util::{
+    block_on, next_addr, random_lines, receive, runtime, send_lines, shutdown_on_idle, wait_for_tcp,
+};
+use vector::topology::{self, Config};
+
+// The log schema is process wide, so this lives in its own test binary.
+#[test]
+fn pipe_with_custom_log_schema() {
+    let num_lines: usize = 100;
+
+    let in_addr = next_addr();
+    let out_addr = next_addr();
+
+    let config = Config::load(
+        format!(
+            r#"
+            [log_schema]
+            message_key = "msg"
+            timestamp_key = "ts"
+            host_key = "hostname"
+
+            [sources.in]
+            type = "socket"
+            mode = "tcp"
+            address = "{}"
+
+            [sinks.out]
+            type = "socket"
+            inputs = ["in"]
+            mode = "tcp"
+            address = "{}"
+            encoding = "json"
+            "#,
+            in_addr, out_addr
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    event::LOG_SCHEMA
+        .set(config.global.log_schema.clone())
+        .expect("Couldn't set schema");
+
+    let mut rt = runtime();
+
+    let output_lines = receive(&out_addr);
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+    // Wait for server to accept traffic
+    wait_for_tcp(in_addr);
+
+    let input_lines = random_lines(100).take(num_lines).collect::<Vec<_>>();
+    let send = send_lines(in_addr, input_lines.clone().into_iter());
+    rt.block_on(send).unwrap();
+
+    // Shut down server
+    block_on(topology.stop()).unwrap();
+    shutdown_on_idle(rt);
+
+    let output_lines = output_lines.wait();
+    assert_eq!(num_lines, output_lines.len());
+    for (input, output) in input_lines.iter().zip(output_lines) {
+        let output: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["msg"], Value::from(input.as_str()));
+        assert!(output.get("ts").is_some());
+        assert!(output.get("hostname").is_some());
+        assert!(output.get("message").is_none());
+        assert!(output.get("timestamp").is_none());
+        assert!(output.get("host").is_none());
+    }
+}
@@ -0,0 +1,76 @@
+mod support;
+
+use crate::support::{sink, source};
+use futures01::{Sink, Stream};
+use metrics_runtime::Measurement;
+use vector::{
+    event::{metadata, Event},
+    test_util::{block_on, runtime, shutdown_on_idle},
+    topology::{self, config::Config},
+};
+
+/// The value of a counter of the `in1` source.
+fn counter(name: &str) -> u64 {
+    let controller = vector::metrics::CONTROLLER.get().unwrap();
+    controller
+        .snapshot()
+        .into_measurements()
+        .into_iter()
+        .find(|(key, _)| {
+            key.name() == name
+                && key
+                    .labels()
+                    .any(|label| label.key() == "component_id" && label.value() == "in1")
+        })
+        .map(|(_, measurement)| match measurement {
+            Measurement::Counter(count) => count,
+            _ => panic!("{} is not a counter", name),
+        })
+        .unwrap_or(0)
+}
+
+#[test]
+fn source_counts_received_events_and_bytes() {
+    // The metrics system is global, which is why this test has a binary to
+    // itself.
+    vector::metrics::init().unwrap();
+
+    let (in1, source) = source();
+    let (out1, sink) = sink(10);
+    let mut config = Config::empty();
+    config.add_source("in1", source);
+    config.add_sink("out1", &["in1"], sink);
+
+    let mut rt = runtime();
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    // Decoded events count the size their source recorded, others the size
+    // of their message.
+    let mut decoded = Event::from("decoded");
+    decoded
+        .as_mut_log()
+        .metadata_mut()
+        .insert(metadata::RECEIVED_BYTES, 100);
+    let events = vec![
+        Event::from("a"),
+        Event::from("bb"),
+        Event::from("ccc"),
+        decoded,
+    ];
+
+    let (in1, _) = block_on(in1.send_all(futures01::stream::iter_ok(events))).unwrap();
+    let received = block_on(out1.take(4).collect()).unwrap();
+    assert_eq!(received.len(), 4);
+    // The size is only for counting, and doesn't go on with the event.
+    assert!(!received[3]
+        .as_log()
+        .metadata()
+        .contains(metadata::RECEIVED_BYTES));
+
+    assert_eq!(counter("component_received_events_total"), 4);
+    assert_eq!(counter("component_received_bytes_total"), 106);
+
+    drop(in1);
+    block_on(topology.stop()).unwrap();
+    shutdown_on_idle(rt);
+}
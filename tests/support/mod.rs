@@ -16,9 +16,13 @@ use std::sync::{
     Arc, Mutex,
 };
 use tracing::{error, info};
+use vector::buffers::Acker;
 use vector::event::{self, metric::MetricValue, Event, Value};
 use vector::shutdown::ShutdownSignal;
-use vector::sinks::{util::StreamSink, Healthcheck, RouterSink};
+use vector::sinks::{
+    util::{DeadLetter, StreamSink},
+    Healthcheck, RouterSink,
+};
 use vector::sources::Source;
 use vector::stream::StreamExt;
 use vector::topology::config::{
@@ -45,6 +49,18 @@ pub fn sink_dead() -> MockSinkConfig<DeadSink<Event>> {
     MockSinkConfig::new(DeadSink::new(), false)
 }
 
+pub fn sink_failing_permanently(reason: &str) -> FailingSinkConfig {
+    FailingSinkConfig {
+        reason: reason.into(),
+    }
+}
+
+pub fn sink_unconfirmed(channel_size: usize) -> (Receiver<Event>, UnconfirmedSinkConfig) {
+    let (tx, rx) = futures01::sync::mpsc::channel(channel_size);
+    let sink = UnconfirmedSinkConfig { sink: Some(tx) };
+    (rx, sink)
+}
+
 pub fn source() -> (Sender<Event>, MockSourceConfig) {
     let (tx, rx) = futures01::sync::mpsc::channel(0);
     let source = MockSourceConfig::new(rx);
@@ -186,6 +202,7 @@ impl Transform for MockTransform {
                     values.insert(self.suffix.clone());
                 }
             },
+            Event::Trace(_) => (),
         };
         Some(event)
     }
@@ -288,6 +305,95 @@ where
     }
 }
 
+/// A sink that can never deliver anything, so it hands every event it gets
+/// to its dead-letter sink.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FailingSinkConfig {
+    reason: String,
+}
+
+#[typetag::serde(name = "failing")]
+impl SinkConfig for FailingSinkConfig {
+    fn build(&self, cx: SinkContext) -> Result<(RouterSink, Healthcheck), vector::Error> {
+        let sink = FailingSink {
+            reason: self.reason.clone(),
+            dead_letter: cx.dead_letter(),
+            acker: cx.acker(),
+        };
+        Ok((Box::new(sink), Box::new(future::ok(()))))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "failing"
+    }
+}
+
+struct FailingSink {
+    reason: String,
+    dead_letter: DeadLetter,
+    acker: Acker,
+}
+
+impl Sink for FailingSink {
+    type SinkItem = Event;
+    type SinkError = ();
+
+    fn start_send(
+        &mut self,
+        item: Self::SinkItem,
+    ) -> futures01::StartSend<Self::SinkItem, Self::SinkError> {
+        self.dead_letter.send(item, &self.reason);
+        self.acker.ack(1);
+        Ok(futures01::AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> futures01::Poll<(), Self::SinkError> {
+        Ok(futures01::Async::Ready(()))
+    }
+}
+
+/// A sink that waits for confirmation of delivery before acking events,
+/// which never comes.
+#[derive(Debug, Serialize)]
+pub struct UnconfirmedSinkConfig {
+    #[serde(skip)]
+    sink: Option<Sender<Event>>,
+}
+
+#[typetag::serialize(name = "unconfirmed")]
+impl SinkConfig for UnconfirmedSinkConfig {
+    fn build(&self, _cx: SinkContext) -> Result<(RouterSink, Healthcheck), vector::Error> {
+        let sink = self.sink.clone().unwrap();
+        let sink = sink.sink_map_err(|error| {
+            error!(
+                message = "Ingesting an event failed at unconfirmed sink",
+                ?error
+            )
+        });
+        Ok((Box::new(sink), Box::new(future::ok(()))))
+    }
+
+    fn input_type(&self) -> DataType {
+        DataType::Any
+    }
+
+    fn sink_type(&self) -> &'static str {
+        "unconfirmed"
+    }
+
+    fn can_acknowledge(&self) -> bool {
+        true
+    }
+
+    fn typetag_deserialize(&self) {
+        unimplemented!("not intended for use in real configs")
+    }
+}
+
 /// Represents a sink that's never ready.
 /// Useful to simulate an upstream sink server that is down.
 #[derive(Debug, Clone)]
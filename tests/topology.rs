@@ -1,6 +1,9 @@
 mod support;
 
-use crate::support::{sink, sink_failing_healthcheck, source, transform, MockSourceConfig};
+use crate::support::{
+    sink, sink_failing_healthcheck, sink_failing_permanently, source, transform, MockSourceConfig,
+};
+use chrono::Utc;
 use futures01::{
     future, future::Future, sink::Sink, stream::iter_ok, stream::Stream, sync::mpsc::SendError,
     sync::oneshot,
@@ -11,10 +14,14 @@ use std::sync::{
 };
 use std::time::Duration;
 use std::{iter, thread};
-use vector::event::{self, Event};
+use vector::event::{self, Event, TraceEvent};
+use vector::sinks::util::dead_letter;
 use vector::test_util::{runtime, shutdown_on_idle, trace_init};
 use vector::topology;
 use vector::topology::config::Config;
+use vector::topology::max_age::{MaxAgeConfig, MissingTimestampPolicy};
+use vector::topology::tap::TapKind;
+use vector::topology::Topology;
 
 fn basic_config() -> Config {
     let mut config = Config::empty();
@@ -118,6 +125,48 @@ fn topology_source_and_sink() {
     assert_eq!(vec![event], res);
 }
 
+#[test]
+fn topology_built_in_code_delivers_events() {
+    let (in1, source1) = source();
+    let (out1, sink1) = sink(10);
+
+    let handle = Topology::builder()
+        .add_source("in1", source1)
+        .add_sink("out1", &["in1"], sink1)
+        .threads(1)
+        .build()
+        .unwrap()
+        .run()
+        .unwrap();
+
+    let event = Event::from("this");
+    in1.send(event.clone()).wait().unwrap();
+
+    handle.shutdown();
+
+    let res = out1.collect().wait().unwrap();
+    assert_eq!(vec![event], res);
+}
+
+#[test]
+fn topology_built_in_code_is_checked() {
+    let (_in1, source1) = source();
+    let (_out1, sink1) = sink(10);
+
+    let result = Topology::builder()
+        .add_source("in1", source1)
+        .add_sink("out1", &["in2"], sink1)
+        .build();
+
+    match result {
+        Err(errors) => assert_eq!(
+            errors,
+            vec![r#"Input "in2" for sink "out1" doesn't exist."#.to_owned()]
+        ),
+        Ok(_) => panic!("Topology with a missing input was built."),
+    }
+}
+
 #[test]
 fn topology_multiple_sources() {
     let mut rt = runtime();
@@ -523,3 +572,239 @@ fn topology_healthcheck_run_for_changes_on_reload() {
     config.add_sink("out2", &["in1"], sink_failing_healthcheck(10).1);
     assert!(topology.reload_config_and_respawn(config, &mut rt, true) == false);
 }
+
+#[test]
+fn topology_tags_events_with_source_id() {
+    let mut rt = runtime();
+    let (in1, source1) = source();
+    let transform1 = transform(" first", 0.0);
+    let (out1, sink1) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_transform("t1", &["in1"], transform1);
+    config.add_sink("out1", &["t1"], sink1);
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    in1.send(Event::from("this")).wait().unwrap();
+
+    rt.block_on(topology.stop()).unwrap();
+
+    let res = out1.collect().wait().unwrap();
+
+    shutdown_on_idle(rt);
+    assert_eq!(res[0].as_log().metadata().source_id(), Some("in1"));
+    assert!(!res[0].as_log().contains(&"source_id".into()));
+}
+
+#[test]
+fn topology_drops_expired_events() {
+    let mut rt = runtime();
+    let (in1, source1) = source();
+    let (out1, sink1) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_sink("out1", &["in1"], sink1);
+    config.sinks["out1"].max_age = Some(MaxAgeConfig {
+        secs: 3600,
+        missing_timestamp: MissingTimestampPolicy::IngestTime,
+    });
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let mut expired = Event::from("expired");
+    expired.as_mut_log().insert(
+        event::log_schema().timestamp_key().clone(),
+        Utc::now() - chrono::Duration::hours(2),
+    );
+    let mut no_timestamp = Event::from("no timestamp");
+    no_timestamp
+        .as_mut_log()
+        .remove(event::log_schema().timestamp_key());
+    let events = vec![expired, no_timestamp, Event::from("fresh")];
+    let _ = in1.send_all(iter_ok(events)).wait().unwrap();
+
+    rt.block_on(topology.stop()).unwrap();
+
+    let res = out1.collect().wait().unwrap();
+
+    shutdown_on_idle(rt);
+    // Events without a timestamp are aged from when they left the source.
+    assert_eq!(
+        res.into_iter().map(into_message).collect::<Vec<_>>(),
+        vec!["no timestamp", "fresh"]
+    );
+}
+
+#[test]
+fn topology_routes_failed_events_to_dead_letter_sink() {
+    let mut rt = runtime();
+    let (in1, source1) = source();
+    let (dlq, sink_dlq) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_sink(
+        "out1",
+        &["in1"],
+        sink_failing_permanently("Rejected upstream."),
+    );
+    config.add_sink("dlq", &[], sink_dlq);
+    config.sinks["out1"].dead_letter = Some("dlq".into());
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    in1.send(Event::from("this")).wait().unwrap();
+
+    rt.block_on(topology.stop()).unwrap();
+
+    let res = dlq.collect().wait().unwrap();
+
+    shutdown_on_idle(rt);
+    assert_eq!(res.len(), 1);
+    assert_eq!(into_message(res[0].clone()), "this");
+    let log = res[0].as_log();
+    assert_eq!(log[&dead_letter::SINK_FIELD.into()], "out1".into());
+    assert_eq!(
+        log[&dead_letter::REASON_FIELD.into()],
+        "Rejected upstream.".into()
+    );
+}
+
+#[test]
+fn topology_sends_to_sinks_whose_condition_matches() {
+    let mut rt = runtime();
+    let (in1, source1) = source();
+    let (out1, sink1) = sink(10);
+    let (out2, sink2) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_sink("out1", &["in1"], sink1);
+    config.add_sink("out2", &["in1"], sink2);
+    config.sinks["out1"].condition =
+        Some(toml::from_str(r#""message.contains" = "error""#).unwrap());
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let events = vec![
+        Event::from("error: disk full"),
+        Event::from("all good"),
+        Event::from("error: out of memory"),
+    ];
+    in1.send_all(iter_ok::<_, SendError<Event>>(events.clone()))
+        .wait()
+        .unwrap();
+
+    rt.block_on(topology.stop()).unwrap();
+
+    let res1 = out1.collect().wait().unwrap();
+    let res2 = out2.collect().wait().unwrap();
+
+    shutdown_on_idle(rt);
+    assert_eq!(
+        res1.into_iter().map(into_message).collect::<Vec<_>>(),
+        vec!["error: disk full", "error: out of memory"]
+    );
+    assert_eq!(res2, events);
+}
+
+#[test]
+fn topology_rejects_invalid_sink_condition() {
+    let mut config = basic_config();
+    config.sinks["out1"].condition =
+        Some(toml::from_str(r#""message.matches" = "error""#).unwrap());
+
+    let rt = runtime();
+    let errors = topology::builder::build_pieces(&config, rt.executor())
+        .err()
+        .unwrap();
+    assert!(errors[0].starts_with("Sink \"out1\": Invalid condition:"));
+}
+
+#[test]
+fn topology_taps_component_input_and_output() {
+    let mut rt = runtime();
+
+    let (in1, source1) = source();
+    let (out1, sink1) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_transform("t1", &["in1"], transform(" transformed", 0.0));
+    config.add_sink("out1", &["t1"], sink1);
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let taps = topology.taps();
+    let input = taps.tap("t1", TapKind::Input, 10).unwrap();
+    let output = taps.tap("t1", TapKind::Output, 10).unwrap();
+    assert!(taps.tap("out1", TapKind::Output, 10).is_err());
+    assert!(taps.tap("nope", TapKind::Input, 10).is_err());
+
+    let events = vec![Event::from("this"), Event::from("that")];
+    in1.send_all(iter_ok::<_, SendError<Event>>(events.clone()))
+        .wait()
+        .unwrap();
+
+    let input = input.take(2).collect().wait().unwrap();
+    let output = output.take(2).collect().wait().unwrap();
+
+    rt.block_on(topology.stop()).unwrap();
+    let res = out1.collect().wait().unwrap();
+    shutdown_on_idle(rt);
+
+    assert_eq!(
+        input.into_iter().map(|t| t.event).collect::<Vec<_>>(),
+        events
+    );
+    assert_eq!(
+        output
+            .into_iter()
+            .map(|t| into_message(t.event))
+            .collect::<Vec<_>>(),
+        vec!["this transformed", "that transformed"]
+    );
+    assert_eq!(res.len(), 2);
+}
+
+#[test]
+fn topology_passes_trace_events() {
+    let mut rt = runtime();
+
+    let (in1, source1) = source();
+    let transform1 = transform(" transformed", 0.0);
+    let (out1, sink1) = sink(10);
+
+    let mut config = Config::empty();
+    config.add_source("in1", source1);
+    config.add_transform("t1", &["in1"], transform1);
+    config.add_sink("out1", &["t1"], sink1);
+
+    let (topology, _crash) = topology::start(config, &mut rt, false).unwrap();
+
+    let root = TraceEvent::new("1234", "1");
+    let mut child = TraceEvent::new("1234", "2");
+    child.set_parent_id("1");
+    child.set_sampling_priority(2);
+    child.as_mut_log().insert("name", "query");
+    let events = vec![Event::from(root), Event::from(child)];
+
+    in1.send_all(iter_ok::<_, SendError<Event>>(events.clone()))
+        .wait()
+        .unwrap();
+
+    rt.block_on(topology.stop()).unwrap();
+    let res = out1.collect().wait().unwrap();
+    shutdown_on_idle(rt);
+
+    assert_eq!(res, events);
+    let child = res[1].as_trace();
+    assert_eq!(child.trace_id(), Some("1234".into()));
+    assert_eq!(child.span_id(), Some("2".into()));
+    assert_eq!(child.parent_id(), Some("1".into()));
+    assert_eq!(child.sampling_priority(), Some(2));
+    assert_eq!(res[0].as_trace().parent_id(), None);
+}